@@ -59,6 +59,26 @@ pub enum QueryError {
     },
 }
 
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("Transaction is invalid: {0}")]
+    InvalidTransaction(near_primitives::errors::InvalidTxError),
+    #[error("Node doesn't track shard {shard_id} required to execute the transaction")]
+    UnavailableShard { shard_id: ShardId },
+    #[error("Internal error occurred: {error_message}")]
+    InternalError { error_message: String },
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum TransactionTraceError {
+    #[error("Transaction {0} or its outcome is not known")]
+    UnknownTransaction(near_primitives::hash::CryptoHash),
+    #[error("Transaction results in more than {limit} receipts")]
+    TooManyReceipts { limit: usize },
+    #[error(transparent)]
+    Chain(#[from] Error),
+}
+
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
     /// The block is already known
@@ -305,6 +325,12 @@ impl From<EpochError> for Error {
     }
 }
 
+impl From<Error> for SimulateTransactionError {
+    fn from(error: Error) -> Self {
+        SimulateTransactionError::InternalError { error_message: error.to_string() }
+    }
+}
+
 impl From<ShardLayoutError> for Error {
     fn from(error: ShardLayoutError) -> Self {
         match error {
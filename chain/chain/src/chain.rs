@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::{HashMap, HashSet, VecDeque};
 
 use std::sync::Arc;
 use std::time::{Duration as TimeDuration, Instant};
@@ -14,7 +14,9 @@ use rand::seq::SliceRandom;
 use rand::SeedableRng;
 use tracing::{debug, error, info, warn, Span};
 
-use near_chain_primitives::error::{BlockKnownError, Error, LogTransientStorageError};
+use near_chain_primitives::error::{
+    BlockKnownError, Error, LogTransientStorageError, TransactionTraceError,
+};
 use near_primitives::block::{genesis_chunks, Tip};
 use near_primitives::challenge::{
     BlockDoubleSign, Challenge, ChallengeBody, ChallengesResult, ChunkProofs, ChunkState,
@@ -25,7 +27,7 @@ use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::merkle::{
     combine_hash, merklize, verify_path, Direction, MerklePath, MerklePathItem, PartialMerkleTree,
 };
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{ActionReceipt, Receipt, ReceiptEnum};
 use near_primitives::sharding::{
     ChunkHash, ChunkHashHeight, EncodedShardChunk, ReceiptList, ReceiptProof, ShardChunk,
     ShardChunkHeader, ShardInfo, ShardProof, StateSyncInfo,
@@ -39,16 +41,18 @@ use near_primitives::transaction::{ExecutionOutcomeWithIdAndProof, SignedTransac
 use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{
     AccountId, Balance, BlockExtra, BlockHeight, BlockHeightDelta, EpochId, Gas, MerkleHash,
-    NumBlocks, NumShards, ShardId, StateChangesForSplitStates, StateRoot,
+    NumBlocks, NumShards, RawStateChangesWithTrieKey, ShardId, StateChangesForSplitStates,
+    StateRoot,
 };
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::MaybeValidated;
 use near_primitives::views::{
     BlockStatusView, ExecutionOutcomeWithIdView, ExecutionStatusView, FinalExecutionOutcomeView,
     FinalExecutionOutcomeWithReceiptView, FinalExecutionStatus, LightClientBlockView,
-    SignedTransactionView,
+    SignedTransactionView, TraceDataDependencyView, TraceNodeView, TransactionTraceView,
 };
-use near_store::{DBCol, ShardTries, StoreUpdate};
+use near_store::flat_state::{self, FlatStateDelta, FlatStorageError};
+use near_store::{DBCol, ShardTries, StorageError, StoreUpdate};
 
 use crate::block_processing_utils::{
     BlockPreprocessInfo, BlockProcessingArtifact, BlocksInProcessing, DoneApplyChunkCallback,
@@ -189,7 +193,7 @@ impl OrphanBlockPool {
         self.orphans.len()
     }
 
-    fn len_evicted(&self) -> usize {
+    pub fn len_evicted(&self) -> usize {
         self.evicted
     }
 
@@ -621,6 +625,21 @@ impl Chain {
                     );
                 }
 
+                if cfg!(feature = "protocol_feature_flat_state") {
+                    let tries = runtime_adapter.get_tries();
+                    for (chunk_header, state_root) in
+                        genesis.chunks().iter().zip(state_roots.iter())
+                    {
+                        let shard_uid = runtime_adapter
+                            .shard_id_to_uid(chunk_header.shard_id(), &EpochId::default())?;
+                        tries.create_flat_storage_state_for_shard(
+                            shard_uid,
+                            state_root,
+                            genesis.hash(),
+                        )?;
+                    }
+                }
+
                 let block_head = Tip::from_header(genesis.header());
                 let header_head = block_head.clone();
                 store_update.save_head(&block_head)?;
@@ -1796,6 +1815,10 @@ impl Chain {
         let mut chain_store_update = self.mut_store().store_update();
         let mut store_update = StoreUpdate::new_with_tries(tries);
         store_update.delete_all(DBCol::State);
+        // Flat storage refers to values from the removed state.
+        store_update.delete_all(DBCol::FlatState);
+        store_update.delete_all(DBCol::FlatStateDeltas);
+        store_update.delete_all(DBCol::FlatStateMisc);
         chain_store_update.merge(store_update);
 
         // The reason to reset tail here is not to allow Tail be greater than Head
@@ -2029,6 +2052,8 @@ impl Chain {
             chain_update.postprocess_block(me, &block, block_preprocess_info, apply_results)?;
         chain_update.commit()?;
 
+        self.update_flat_storage_for_block(&block)?;
+
         self.pending_state_patch = None;
 
         if let Some(tip) = &new_head {
@@ -2074,6 +2099,34 @@ impl Chain {
         Ok(AcceptedBlock { hash: *block.hash(), status: block_status, provenance })
     }
 
+    /// Moves flat heads of the shards to the last final block of `block`.
+    ///
+    /// Must be called after the block is committed, since the flat head is moved only once
+    /// the new head and its delta are persisted.
+    fn update_flat_storage_for_block(&self, block: &Block) -> Result<(), Error> {
+        let new_flat_head = *block.header().last_final_block();
+        if new_flat_head == CryptoHash::default() {
+            return Ok(());
+        }
+        let epoch_id = block.header().epoch_id();
+        let tries = self.runtime_adapter.get_tries();
+        for shard_id in 0..self.runtime_adapter.num_shards(epoch_id)? {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, epoch_id)?;
+            if let Some(flat_storage_state) = tries.get_flat_storage_state_for_shard(shard_uid) {
+                match flat_storage_state.update_flat_head(&new_flat_head) {
+                    Ok(()) => {}
+                    // Happens if we haven't applied chunks of the shard since the flat head, e.g.
+                    // when we stopped tracking it.
+                    Err(FlatStorageError::BlockNotSupported(_)) => {
+                        debug!(target: "chain", ?shard_uid, %new_flat_head, "Can't move flat head");
+                    }
+                    Err(err) => return Err(StorageError::from(err).into()),
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Preprocess a block before applying chunks, verify that we have the necessary information
     /// to process the block an the block is valid.
     //  Note that this function does NOT introduce any changes to chain state.
@@ -3170,33 +3223,7 @@ impl Chain {
         transaction_hash: &CryptoHash,
     ) -> Result<FinalExecutionOutcomeView, Error> {
         let mut outcomes = self.get_recursive_transaction_results(transaction_hash)?;
-        let mut looking_for_id = *transaction_hash;
-        let num_outcomes = outcomes.len();
-        let status = outcomes
-            .iter()
-            .find_map(|outcome_with_id| {
-                if outcome_with_id.id == looking_for_id {
-                    match &outcome_with_id.outcome.status {
-                        ExecutionStatusView::Unknown if num_outcomes == 1 => {
-                            Some(FinalExecutionStatus::NotStarted)
-                        }
-                        ExecutionStatusView::Unknown => Some(FinalExecutionStatus::Started),
-                        ExecutionStatusView::Failure(e) => {
-                            Some(FinalExecutionStatus::Failure(e.clone()))
-                        }
-                        ExecutionStatusView::SuccessValue(v) => {
-                            Some(FinalExecutionStatus::SuccessValue(v.clone()))
-                        }
-                        ExecutionStatusView::SuccessReceiptId(id) => {
-                            looking_for_id = *id;
-                            None
-                        }
-                    }
-                } else {
-                    None
-                }
-            })
-            .expect("results should resolve to a final outcome");
+        let status = FinalExecutionStatus::from_outcomes(transaction_hash, &outcomes);
         let receipts_outcome = outcomes.split_off(1);
         let transaction = self.store.get_transaction(transaction_hash)?.ok_or_else(|| {
             Error::DBNotFoundErr(format!("Transaction {} is not found", transaction_hash))
@@ -3234,6 +3261,139 @@ impl Chain {
         Ok(FinalExecutionOutcomeWithReceiptView { final_outcome, receipts })
     }
 
+    /// Reconstructs the execution trace of a transaction from the outcomes of the transaction and
+    /// of the receipts it resulted in.  Receipts whose outcomes aren't known yet are reported as
+    /// pending.  Fails if the transaction results in more than `max_receipts` receipts.
+    pub fn get_transaction_trace(
+        &self,
+        transaction_hash: &CryptoHash,
+        max_receipts: usize,
+    ) -> Result<TransactionTraceView, TransactionTraceError> {
+        let transaction = self
+            .store
+            .get_transaction(transaction_hash)?
+            .ok_or(TransactionTraceError::UnknownTransaction(*transaction_hash))?;
+        let mut nodes = vec![];
+        let mut action_receipts = vec![];
+        let mut outcomes = vec![];
+        let mut pending_receipt_ids = vec![];
+        let mut queue = VecDeque::from([(*transaction_hash, None)]);
+        while let Some((id, parent_id)) = queue.pop_front() {
+            let outcome = match self.get_execution_outcome(&id) {
+                Ok(outcome) => outcome,
+                Err(Error::DBNotFoundErr(_)) if parent_id.is_some() => {
+                    pending_receipt_ids.push(id);
+                    continue;
+                }
+                Err(Error::DBNotFoundErr(_)) => {
+                    return Err(TransactionTraceError::UnknownTransaction(*transaction_hash))
+                }
+                Err(err) => return Err(err.into()),
+            };
+            let header = self.get_block_header(&outcome.block_hash)?;
+            let (action_receipt, predecessor_id, shard_id) = match parent_id {
+                None => {
+                    let shard_id = self.find_outcome_shard_id(&header, &id)?;
+                    (None, transaction.transaction.signer_id.clone(), shard_id)
+                }
+                Some(_) => {
+                    let shard_id = match self.store.get_shard_id_for_receipt_id(&id) {
+                        Ok(shard_id) => shard_id,
+                        Err(_) => self.find_outcome_shard_id(&header, &id)?,
+                    };
+                    // Local receipts, i.e. receipts of transactions signed by their receivers,
+                    // aren't stored; their predecessor is the signer.
+                    let receipt = self.store.get_receipt(&id)?;
+                    match receipt.map(|receipt| Receipt::clone(&receipt)) {
+                        Some(Receipt {
+                            predecessor_id,
+                            receipt: ReceiptEnum::Action(action_receipt),
+                            ..
+                        }) => (Some(action_receipt), predecessor_id, shard_id),
+                        Some(Receipt { predecessor_id, .. }) => (None, predecessor_id, shard_id),
+                        None => (None, transaction.transaction.signer_id.clone(), shard_id),
+                    }
+                }
+            };
+            let outcome: ExecutionOutcomeWithIdView = outcome.into();
+            queue.extend(
+                outcome.outcome.receipt_ids.iter().map(|receipt_id| (*receipt_id, Some(id))),
+            );
+            // Every id seen so far except the transaction's is a receipt.
+            if nodes.len() + pending_receipt_ids.len() + queue.len() > max_receipts {
+                return Err(TransactionTraceError::TooManyReceipts { limit: max_receipts });
+            }
+            nodes.push(TraceNodeView {
+                id,
+                parent_id,
+                predecessor_id,
+                block_hash: outcome.block_hash,
+                block_height: header.height(),
+                shard_id,
+                input_data: vec![],
+                outcome: outcome.outcome.clone(),
+            });
+            action_receipts.push(action_receipt);
+            outcomes.push(outcome);
+        }
+
+        // Data is sent by the receipt which has the data receiver among its output data receivers
+        // and which returned a value rather than passed the receivers on to another receipt.
+        let mut producers = HashMap::new();
+        for (node, action_receipt) in nodes.iter().zip(&action_receipts) {
+            let action_receipt = match action_receipt {
+                Some(action_receipt) => action_receipt,
+                None => continue,
+            };
+            if !matches!(node.outcome.status, ExecutionStatusView::SuccessReceiptId(_)) {
+                for data_receiver in &action_receipt.output_data_receivers {
+                    producers.insert(data_receiver.data_id, node.id);
+                }
+            }
+        }
+        for (node, action_receipt) in nodes.iter_mut().zip(&action_receipts) {
+            if let Some(ActionReceipt { input_data_ids, .. }) = action_receipt {
+                node.input_data = input_data_ids
+                    .iter()
+                    .map(|data_id| TraceDataDependencyView {
+                        data_id: *data_id,
+                        producer_id: producers.get(data_id).copied(),
+                    })
+                    .collect();
+            }
+        }
+
+        let status = if pending_receipt_ids.is_empty() {
+            FinalExecutionStatus::from_outcomes(transaction_hash, &outcomes)
+        } else {
+            FinalExecutionStatus::Started
+        };
+        Ok(TransactionTraceView {
+            transaction_hash: *transaction_hash,
+            status,
+            nodes,
+            pending_receipt_ids,
+        })
+    }
+
+    /// Finds the shard whose chunk in the block produced the outcome with given id.
+    fn find_outcome_shard_id(
+        &self,
+        header: &BlockHeader,
+        id: &CryptoHash,
+    ) -> Result<ShardId, Error> {
+        for shard_id in 0..self.runtime_adapter.num_shards(header.epoch_id())? {
+            if self
+                .store
+                .get_outcomes_by_block_hash_and_shard_id(header.hash(), shard_id)?
+                .contains(id)
+            {
+                return Ok(shard_id);
+            }
+        }
+        Err(Error::DBNotFoundErr(format!("OUTCOME IDS: {} in block {}", id, header.hash())))
+    }
+
     /// Find a validator to forward transactions to
     pub fn find_chunk_producer_for_forwarding(
         &self,
@@ -4448,7 +4608,12 @@ impl<'a> ChainUpdate<'a> {
     ) -> Result<(), Error> {
         let _span = tracing::debug_span!(target: "chain", "apply_chunk_postprocessing").entered();
         apply_results.into_iter().try_for_each(|result| -> Result<(), Error> {
-            self.process_apply_chunk_result(result?, *block.hash(), *prev_block.hash())
+            self.process_apply_chunk_result(
+                result?,
+                *block.hash(),
+                block.header().height(),
+                *prev_block.hash(),
+            )
         })
     }
 
@@ -4490,11 +4655,13 @@ impl<'a> ChainUpdate<'a> {
     }
 
     /// Postprocess split state results or state changes, do the necessary update on chain
-    /// for split state results: store the chunk extras and trie changes for the split states
+    /// for split state results: store the chunk extras, trie changes and flat storage deltas for
+    /// the split states
     /// for state changes, store the state changes for splitting states
     fn process_split_state(
         &mut self,
         block_hash: &CryptoHash,
+        height: BlockHeight,
         prev_block_hash: &CryptoHash,
         shard_uid: &ShardUId,
         apply_results_or_state_changes: ApplySplitStateResultOrStateChanges,
@@ -4562,6 +4729,13 @@ impl<'a> ChainUpdate<'a> {
                         &result.shard_uid,
                         new_chunk_extra,
                     );
+                    self.save_flat_state_changes(
+                        *block_hash,
+                        height,
+                        *prev_block_hash,
+                        result.shard_uid,
+                        &result.state_changes,
+                    );
                     self.chain_store_update.save_trie_changes(result.trie_changes);
                 }
                 assert_eq!(sum_gas_used, total_gas_used);
@@ -4578,11 +4752,31 @@ impl<'a> ChainUpdate<'a> {
         Ok(())
     }
 
+    /// Adds changes made by the chunk to flat storage of the shard, if it exists.
+    fn save_flat_state_changes(
+        &mut self,
+        block_hash: CryptoHash,
+        height: BlockHeight,
+        prev_hash: CryptoHash,
+        shard_uid: ShardUId,
+        state_changes: &[RawStateChangesWithTrieKey],
+    ) {
+        if let Some(flat_storage_state) =
+            self.runtime_adapter.get_tries().get_flat_storage_state_for_shard(shard_uid)
+        {
+            let delta = FlatStateDelta::from_state_changes(state_changes);
+            let block_info = flat_state::BlockInfo { hash: block_hash, height, prev_hash };
+            let store_update = flat_storage_state.add_block(block_info, delta);
+            self.chain_store_update.merge(store_update);
+        }
+    }
+
     /// Processed results of applying chunk
     fn process_apply_chunk_result(
         &mut self,
         result: ApplyChunkResult,
         block_hash: CryptoHash,
+        height: BlockHeight,
         prev_block_hash: CryptoHash,
     ) -> Result<(), Error> {
         match result {
@@ -4609,6 +4803,13 @@ impl<'a> ChainUpdate<'a> {
                         apply_result.total_balance_burnt,
                     ),
                 );
+                self.save_flat_state_changes(
+                    block_hash,
+                    height,
+                    prev_block_hash,
+                    shard_uid,
+                    apply_result.trie_changes.state_changes(),
+                );
                 self.chain_store_update.save_trie_changes(apply_result.trie_changes);
                 self.chain_store_update.save_outgoing_receipt(
                     &block_hash,
@@ -4625,6 +4826,7 @@ impl<'a> ChainUpdate<'a> {
                 if let Some(apply_results_or_state_changes) = apply_split_result_or_state_changes {
                     self.process_split_state(
                         &block_hash,
+                        height,
                         &prev_block_hash,
                         &shard_uid,
                         apply_results_or_state_changes,
@@ -4643,11 +4845,19 @@ impl<'a> ChainUpdate<'a> {
                 *new_extra.state_root_mut() = apply_result.new_root;
 
                 self.chain_store_update.save_chunk_extra(&block_hash, &shard_uid, new_extra);
+                self.save_flat_state_changes(
+                    block_hash,
+                    height,
+                    prev_block_hash,
+                    shard_uid,
+                    apply_result.trie_changes.state_changes(),
+                );
                 self.chain_store_update.save_trie_changes(apply_result.trie_changes);
 
                 if let Some(apply_results_or_state_changes) = apply_split_result_or_state_changes {
                     self.process_split_state(
                         &block_hash,
+                        height,
                         &prev_block_hash,
                         &shard_uid,
                         apply_results_or_state_changes,
@@ -4661,6 +4871,7 @@ impl<'a> ChainUpdate<'a> {
                 );
                 self.process_split_state(
                     &block_hash,
+                    height,
                     &prev_block_hash,
                     &shard_uid,
                     ApplySplitStateResultOrStateChanges::ApplySplitStateResults(results),
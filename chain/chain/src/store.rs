@@ -1172,6 +1172,9 @@ pub struct ChainStoreUpdate<'a> {
     add_state_dl_infos: Vec<StateSyncInfo>,
     remove_state_dl_infos: Vec<CryptoHash>,
     challenged_blocks: HashSet<CryptoHash>,
+    /// Data garbage collected from the canonical chain which is copied to the
+    /// cold database of an archival node.  Committed before the hot update.
+    cold_store_update: Option<StoreUpdate>,
 }
 
 impl<'a> ChainStoreUpdate<'a> {
@@ -1196,6 +1199,7 @@ impl<'a> ChainStoreUpdate<'a> {
             add_state_dl_infos: vec![],
             remove_state_dl_infos: vec![],
             challenged_blocks: HashSet::default(),
+            cold_store_update: None,
         }
     }
 }
@@ -2057,6 +2061,14 @@ impl<'a> ChainStoreUpdate<'a> {
     ) -> Result<(), Error> {
         let mut store_update = self.store().store_update();
 
+        // Finalized data is kept in the cold database, if there is one.
+        if let GCMode::Canonical(_) = gc_mode {
+            if self.cold_store_update.is_none() {
+                self.cold_store_update =
+                    self.store().cold_store().map(|store| store.store_update());
+            }
+        }
+
         // 1. Apply revert insertions or deletions from DBCol::TrieChanges for Trie
         {
             let shard_uids_to_gc: Vec<_> = self.get_shard_uids_to_gc(runtime_adapter, &block_hash);
@@ -2086,6 +2098,13 @@ impl<'a> ChainStoreUpdate<'a> {
                             &get_block_shard_uid(&block_hash, &shard_uid),
                         )?;
                         if let Some(trie_changes) = trie_changes {
+                            if let Some(cold_store_update) = self.cold_store_update.as_mut() {
+                                tries.copy_deletions_to_cold(
+                                    &trie_changes,
+                                    shard_uid,
+                                    cold_store_update,
+                                );
+                            }
                             tries.apply_deletions(&trie_changes, shard_uid, &mut store_update);
                             self.gc_col(
                                 DBCol::TrieChanges,
@@ -2254,6 +2273,7 @@ impl<'a> ChainStoreUpdate<'a> {
             Ok(receipt_ids) => {
                 for receipt_id in receipt_ids {
                     let key: Vec<u8> = receipt_id.into();
+                    self.copy_to_cold(DBCol::ReceiptIdToShardId, &key);
                     store_update.decrement_refcount(DBCol::ReceiptIdToShardId, &key);
                     self.chain_store.receipt_id_to_shard_id.pop(&key);
                     self.inc_gc(DBCol::ReceiptIdToShardId);
@@ -2273,6 +2293,7 @@ impl<'a> ChainStoreUpdate<'a> {
         }
 
         let key = get_block_shard_id(block_hash, shard_id);
+        self.copy_to_cold(DBCol::OutgoingReceipts, &key);
         store_update.delete(DBCol::OutgoingReceipts, &key);
         self.chain_store.outgoing_receipts.pop(&key);
         self.inc_gc(DBCol::OutgoingReceipts);
@@ -2307,8 +2328,35 @@ impl<'a> ChainStoreUpdate<'a> {
         Ok(())
     }
 
+    /// Copies the value to the cold database if the column is stored there and
+    /// the canonical chain is being garbage collected.
+    fn copy_to_cold(&mut self, col: DBCol, key: &[u8]) {
+        if !col.is_cold() {
+            return;
+        }
+        let cold_store_update = match self.cold_store_update.as_mut() {
+            Some(cold_store_update) => cold_store_update,
+            None => return,
+        };
+        let value = self
+            .chain_store
+            .store
+            .get(col, key)
+            .expect("Failed to read data to be copied to the cold database");
+        if let Some(value) = value {
+            if col.is_rc() {
+                cold_store_update.increment_refcount(col, key, &value);
+            } else if col.is_insert_only() {
+                cold_store_update.insert(col, key, &value);
+            } else {
+                cold_store_update.set(col, key, &value);
+            }
+        }
+    }
+
     fn gc_col(&mut self, col: DBCol, key: &[u8]) {
         assert!(col.is_gc());
+        self.copy_to_cold(col, key);
         let mut store_update = self.store().store_update();
         match col {
             DBCol::OutgoingReceipts => {
@@ -2436,7 +2484,11 @@ impl<'a> ChainStoreUpdate<'a> {
             | DBCol::_LastBlockWithNewChunk
             | DBCol::_TransactionRefCount
             | DBCol::StateChangesForSplitStates
-            | DBCol::CachedContractCode => {
+            | DBCol::CachedContractCode
+            | DBCol::FlatState
+            | DBCol::FlatStateDeltas
+            | DBCol::FlatStateMisc
+            | DBCol::PeerScores => {
                 unreachable!();
             }
         }
@@ -2893,6 +2945,10 @@ impl<'a> ChainStoreUpdate<'a> {
     }
 
     pub fn commit(mut self) -> Result<(), Error> {
+        // Data must reach the cold database before it's removed from the hot one.
+        if let Some(cold_store_update) = self.cold_store_update.take() {
+            cold_store_update.commit()?;
+        }
         let store_update = self.finalize()?;
         store_update.commit()?;
         let ChainStoreCacheUpdate {
@@ -3022,8 +3078,8 @@ mod tests {
     use near_primitives::types::{BlockHeight, EpochId, GCCount, NumBlocks};
     use near_primitives::utils::index_to_bytes;
     use near_primitives::validator_signer::InMemoryValidatorSigner;
-    use near_store::test_utils::create_test_store;
-    use near_store::DBCol;
+    use near_store::test_utils::{create_test_split_store, create_test_store};
+    use near_store::{DBCol, Store};
 
     use crate::store::{ChainStoreAccess, GCMode};
     use crate::store_validator::StoreValidator;
@@ -3035,7 +3091,10 @@ mod tests {
     }
 
     fn get_chain_with_epoch_length(epoch_length: NumBlocks) -> Chain {
-        let store = create_test_store();
+        get_chain_with_store(create_test_store(), epoch_length)
+    }
+
+    fn get_chain_with_store(store: Store, epoch_length: NumBlocks) -> Chain {
         let chain_genesis = ChainGenesis::test();
         let validators = vec![vec!["test1"]];
         let runtime_adapter = Arc::new(KeyValueRuntime::new_with_validators(
@@ -3400,6 +3459,41 @@ mod tests {
         *prev_block = block.clone();
     }
 
+    #[test]
+    fn test_clear_old_data_with_cold_store() {
+        let mut chain = get_chain_with_store(create_test_split_store(), 1);
+        let runtime_adapter = chain.runtime_adapter.clone();
+        let genesis = chain.get_block_by_height(0).unwrap();
+        let signer = Arc::new(InMemoryValidatorSigner::from_seed(
+            "test1".parse().unwrap(),
+            KeyType::ED25519,
+            "test1",
+        ));
+        let mut prev_block = genesis;
+        let mut blocks = vec![prev_block.clone()];
+        for i in 1..15 {
+            add_block(
+                &mut chain,
+                runtime_adapter.clone(),
+                &mut prev_block,
+                &mut blocks,
+                signer.clone(),
+                i,
+            );
+        }
+
+        let trie = chain.runtime_adapter.get_tries();
+        chain.clear_data(trie, &GCConfig { gc_blocks_limit: 100, ..GCConfig::default() }).unwrap();
+
+        // Garbage collected blocks are moved to the cold database and are still readable.
+        let cold_store = chain.store().store().cold_store().unwrap();
+        for i in 0..15 {
+            let hash = blocks[i].hash();
+            assert!(chain.get_block(hash).is_ok());
+            assert_eq!(cold_store.exists(DBCol::Block, hash.as_ref()).unwrap(), i < 8);
+        }
+    }
+
     #[test]
     fn test_clear_old_data_fixed_height() {
         let mut chain = get_chain();
@@ -1,5 +1,5 @@
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::sync::{Arc, RwLock};
 
 use borsh::{BorshDeserialize, BorshSerialize};
@@ -583,8 +583,17 @@ impl RuntimeAdapter for KeyValueRuntime {
         let validators = validators.into_iter().map(|stake| (stake, false)).collect::<Vec<_>>();
         Ok(validators)
     }
-    fn get_epoch_chunk_producers(&self, _epoch_id: &EpochId) -> Result<Vec<ValidatorStake>, Error> {
-        todo!()
+    fn get_epoch_chunk_producers(&self, epoch_id: &EpochId) -> Result<Vec<ValidatorStake>, Error> {
+        let valset = self.get_valset_for_epoch(epoch_id)?;
+        let mut chunk_producers: Vec<ValidatorStake> = vec![];
+        for shard_id in 0..self.num_shards {
+            for validator in self.get_chunk_producers(valset, shard_id) {
+                if !chunk_producers.iter().any(|x| x.account_id() == validator.account_id()) {
+                    chunk_producers.push(validator);
+                }
+            }
+        }
+        Ok(chunk_producers)
     }
 
     fn get_block_producer(
@@ -1035,6 +1044,76 @@ impl RuntimeAdapter for KeyValueRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_id: &EpochId,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        gas_limit: Gas,
+        transaction: &SignedTransaction,
+        _verify_signature: bool,
+    ) -> Result<Vec<ExecutionOutcomeWithId>, near_chain_primitives::error::SimulateTransactionError>
+    {
+        use near_chain_primitives::error::SimulateTransactionError;
+
+        // Only transfers are executed by this runtime.
+        let actions = &transaction.transaction.actions;
+        if actions.is_empty() || !matches!(actions[0], Action::Transfer(_)) {
+            return Err(SimulateTransactionError::InternalError {
+                error_message: "KeyValueRuntime only simulates transfers".to_string(),
+            });
+        }
+        // The new states are kept in memory like the ones of applied chunks, the chain keeps
+        // pointing at the original state roots.
+        let mut state_roots = state_roots.clone();
+        let mut transactions = vec![transaction.clone()];
+        let mut incoming_receipts = BTreeMap::from([(
+            self.account_id_to_shard_id(&transaction.transaction.signer_id, epoch_id)?,
+            vec![],
+        )]);
+        let mut outcomes = vec![];
+        while !incoming_receipts.is_empty() {
+            for (shard_id, receipts) in std::mem::take(&mut incoming_receipts) {
+                let state_root = state_roots
+                    .get_mut(&shard_id)
+                    .ok_or(SimulateTransactionError::UnavailableShard { shard_id })?;
+                let result = self.apply_transactions_with_optional_storage_proof(
+                    shard_id,
+                    state_root,
+                    block_height,
+                    block_timestamp,
+                    prev_block_hash,
+                    block_hash,
+                    &receipts,
+                    &std::mem::take(&mut transactions),
+                    ValidatorStakeIter::empty(),
+                    gas_price,
+                    gas_limit,
+                    &ChallengesResult::default(),
+                    random_seed,
+                    false,
+                    true,
+                    false,
+                    None,
+                )?;
+                *state_root = result.new_root;
+                outcomes.extend(result.outcomes);
+                for receipt in result.outgoing_receipts {
+                    incoming_receipts
+                        .entry(self.account_id_to_shard_id(&receipt.receiver_id, epoch_id)?)
+                        .or_default()
+                        .push(receipt);
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+
     fn obtain_state_part(
         &self,
         _shard_id: ShardId,
@@ -1565,6 +1644,16 @@ mod test {
 
     use near_primitives::shard_layout::{account_id_to_shard_id, ShardLayout};
 
+    use super::KeyValueRuntime;
+    use crate::RuntimeAdapter;
+    use assert_matches::assert_matches;
+    use near_chain_primitives::error::SimulateTransactionError;
+    use near_crypto::{InMemorySigner, KeyType};
+    use near_primitives::transaction::{Action, CreateAccountAction, SignedTransaction};
+    use near_primitives::types::{EpochId, StateRoot};
+    use near_store::test_utils::create_test_store;
+    use std::collections::HashMap;
+
     fn naive_build_receipt_hashes(
         receipts: &[Receipt],
         shard_layout: &ShardLayout,
@@ -1621,4 +1710,73 @@ mod test {
             test_build_receipt_hashes_with_num_shard(num_shards);
         }
     }
+
+    #[test]
+    fn test_simulate_transaction() {
+        let num_shards = 4;
+        let validators: Vec<AccountId> =
+            (0..8).map(|i| format!("test{}", i).parse().unwrap()).collect();
+        let runtime = KeyValueRuntime::new_with_validators(
+            create_test_store(),
+            vec![validators.clone()],
+            1,
+            num_shards,
+            10,
+        );
+        let sender = validators[0].clone();
+        let receiver = validators
+            .iter()
+            .find(|account_id| {
+                super::account_id_to_shard_id(account_id, num_shards)
+                    != super::account_id_to_shard_id(&sender, num_shards)
+            })
+            .unwrap()
+            .clone();
+        let signer = InMemorySigner::from_seed(sender.clone(), KeyType::ED25519, sender.as_ref());
+        let state_roots: HashMap<_, _> =
+            (0..num_shards).map(|shard_id| (shard_id, StateRoot::default())).collect();
+        let simulate = |transaction: &SignedTransaction| {
+            runtime.simulate_transaction(
+                &state_roots,
+                1,
+                0,
+                &CryptoHash::default(),
+                &CryptoHash::default(),
+                &EpochId::default(),
+                100,
+                CryptoHash::default(),
+                1_000_000,
+                transaction,
+                true,
+            )
+        };
+
+        // The transfer is executed on the sender's shard and its receipt on the receiver's one.
+        let transaction = SignedTransaction::send_money(
+            1,
+            sender.clone(),
+            receiver.clone(),
+            &signer,
+            10,
+            CryptoHash::default(),
+        );
+        let outcomes = simulate(&transaction).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].id, transaction.get_hash());
+        assert_eq!(outcomes[0].outcome.receipt_ids, vec![outcomes[1].id]);
+        assert_eq!(outcomes[1].outcome.executor_id, receiver);
+
+        let transaction = SignedTransaction::from_actions(
+            2,
+            sender,
+            receiver,
+            &signer,
+            vec![Action::CreateAccount(CreateAccountAction {})],
+            CryptoHash::default(),
+        );
+        assert_matches!(
+            simulate(&transaction),
+            Err(SimulateTransactionError::InternalError { .. })
+        );
+    }
 }
@@ -29,7 +29,8 @@ use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
     AccountId, ApprovalStake, Balance, BlockHeight, BlockHeightDelta, EpochHeight, EpochId, Gas,
-    MerkleHash, NumBlocks, ShardId, StateChangesForSplitStates, StateRoot, StateRootNode,
+    MerkleHash, NumBlocks, RawStateChangesWithTrieKey, ShardId, StateChangesForSplitStates,
+    StateRoot, StateRootNode,
 };
 use near_primitives::version::{
     ProtocolVersion, MIN_GAS_PRICE_NEP_92, MIN_GAS_PRICE_NEP_92_FIX, MIN_PROTOCOL_VERSION_NEP_92,
@@ -84,6 +85,9 @@ pub struct ApplySplitStateResult {
     pub shard_uid: ShardUId,
     pub trie_changes: WrappedTrieChanges,
     pub new_root: StateRoot,
+    /// Changes to the state of the split shard, used to update its flat storage.  They aren't
+    /// part of `trie_changes` so that they aren't saved as state changes of the block.
+    pub state_changes: Vec<RawStateChangesWithTrieKey>,
 }
 
 // This struct captures two cases
@@ -690,6 +694,27 @@ pub trait RuntimeAdapter: Send + Sync {
         request: &QueryRequest,
     ) -> Result<QueryResponse, near_chain_primitives::error::QueryError>;
 
+    /// Executes the transaction and all receipts produced by it on top of the given state roots
+    /// of the shards, in the context of the given block, without persisting any changes.
+    /// Receipts are applied on the shards of their receivers until there are none left, with at
+    /// most `gas_limit` gas burnt by every apply.  The receipts delayed in the shards before are
+    /// not executed.
+    /// Returns outcomes of the transaction and the receipts in the order of execution.
+    fn simulate_transaction(
+        &self,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_id: &EpochId,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        gas_limit: Gas,
+        transaction: &SignedTransaction,
+        verify_signature: bool,
+    ) -> Result<Vec<ExecutionOutcomeWithId>, near_chain_primitives::error::SimulateTransactionError>;
+
     fn get_validator_info(
         &self,
         epoch_id: ValidatorInfoIdentifier,
@@ -93,7 +93,9 @@ use near_chain::validate::validate_chunk_proofs;
 use near_chain::{
     byzantine_assert, Chain, ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter,
 };
+use near_chain_configs::TransactionPoolConfig;
 use near_network::types::{NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest};
+use near_pool::types::InsertTransactionResult;
 use near_pool::{PoolIteratorWrapper, TransactionPool};
 use near_primitives::block::Tip;
 use near_primitives::hash::{hash, CryptoHash};
@@ -483,6 +485,8 @@ pub struct ShardsManager {
     /// Useful to make tests deterministic and reproducible,
     /// while keeping the security of randomization of transactions in pool
     rng_seed: RngSeed,
+    /// Limits of the transaction pool of every shard.
+    transaction_pool_config: TransactionPoolConfig,
 }
 
 impl ShardsManager {
@@ -491,6 +495,7 @@ impl ShardsManager {
         runtime_adapter: Arc<dyn RuntimeAdapter>,
         network_adapter: Arc<dyn PeerManagerAdapter>,
         rng_seed: RngSeed,
+        transaction_pool_config: TransactionPoolConfig,
     ) -> Self {
         TransactionPool::init_metrics();
         Self {
@@ -508,6 +513,7 @@ impl ShardsManager {
             chunk_forwards_cache: lru::LruCache::new(CHUNK_FORWARD_CACHE_SIZE),
             seals_mgr: SealsManager::new(me, runtime_adapter),
             rng_seed,
+            transaction_pool_config,
         }
     }
 
@@ -953,8 +959,13 @@ impl ShardsManager {
         self.encoded_chunks.get_chunk_headers_for_block(prev_block_hash)
     }
 
-    /// Returns true if transaction is not in the pool before call
-    pub fn insert_transaction(&mut self, shard_id: ShardId, tx: SignedTransaction) -> bool {
+    /// Inserts the transaction into the pool of the given shard, returning whether it was
+    /// inserted or why it wasn't.
+    pub fn insert_transaction(
+        &mut self,
+        shard_id: ShardId,
+        tx: SignedTransaction,
+    ) -> InsertTransactionResult {
         self.pool_for_shard(shard_id).insert_transaction(tx)
     }
 
@@ -980,8 +991,12 @@ impl ShardsManager {
     }
 
     fn pool_for_shard(&mut self, shard_id: ShardId) -> &mut TransactionPool {
+        let transaction_pool_config = &self.transaction_pool_config;
         self.tx_pools.entry(shard_id).or_insert_with(|| {
-            TransactionPool::new(ShardsManager::random_seed(&self.rng_seed, shard_id))
+            TransactionPool::new(
+                ShardsManager::random_seed(&self.rng_seed, shard_id),
+                transaction_pool_config.clone(),
+            )
         })
     }
 
@@ -2277,6 +2292,7 @@ mod test {
             runtime_adapter,
             network_adapter.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         let added = Clock::instant();
         shards_manager.requested_partial_encoded_chunks.insert(
@@ -2334,6 +2350,7 @@ mod test {
             runtime_adapter.clone(),
             network_adapter,
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         let signer =
             InMemoryValidatorSigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
@@ -2501,6 +2518,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         // process chunk part 0
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&[0]);
@@ -2572,6 +2590,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
 
         // part id > num parts
@@ -2598,6 +2617,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let result = shards_manager
@@ -2664,6 +2684,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         shards_manager.request_chunks(
             vec![fixture.mock_chunk_header.clone()],
@@ -2719,6 +2740,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         let partial_encoded_chunk = fixture.make_partial_encoded_chunk(&fixture.mock_part_ords);
         let _ = shards_manager
@@ -2801,6 +2823,7 @@ mod test {
             fixture.mock_runtime.clone(),
             fixture.mock_network.clone(),
             TEST_SEED,
+            TransactionPoolConfig::default(),
         );
         let (most_parts, other_parts) = {
             let mut most_parts = fixture.mock_chunk_parts.clone();
@@ -2,6 +2,7 @@
 //! without backwards compatibility of JSON encoding.
 
 use std::collections::HashMap;
+use std::path::PathBuf;
 
 use crate::types::{StatusError, SyncStatus};
 use actix::Message;
@@ -15,6 +16,8 @@ use near_primitives::{
 };
 use serde::{Deserialize, Serialize};
 
+pub mod v1;
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(Serialize, Deserialize, Debug)]
 pub struct TrackedShardsView {
@@ -29,7 +32,7 @@ pub struct EpochInfoView {
     pub height: BlockHeight,
     pub first_block: Option<(CryptoHash, DateTime<chrono::Utc>)>,
     pub validators: Vec<ValidatorInfo>,
-    pub chunk_only_producers: Vec<String>,
+    pub chunk_only_producers: Vec<AccountId>,
     pub protocol_version: u32,
     pub shards_size_and_parts: Vec<(u64, u64, bool)>,
 }
@@ -140,6 +143,16 @@ pub struct ValidatorStatus {
     pub production: HashMap<BlockHeight, ProductionAtHeight>,
 }
 
+#[derive(Serialize, Debug)]
+pub struct CheckpointInfo {
+    pub path: PathBuf,
+    pub head_height: BlockHeight,
+    pub head_hash: CryptoHash,
+    // Last final block included in the checkpoint.
+    pub final_head_height: BlockHeight,
+    pub final_head_hash: CryptoHash,
+}
+
 // Different debug requests that can be sent by HTML pages, via GET.
 pub enum DebugStatus {
     // Request for the current sync status
@@ -158,6 +171,14 @@ impl Message for DebugStatus {
     type Result = Result<DebugStatusResponse, StatusError>;
 }
 
+// Creates a checkpoint of the database in the configured directory. Handled by the view client,
+// so that copying the database doesn't hold up the client.
+pub struct CreateCheckpoint;
+
+impl Message for CreateCheckpoint {
+    type Result = Result<CheckpointInfo, StatusError>;
+}
+
 #[derive(Serialize, Debug)]
 pub enum DebugStatusResponse {
     SyncStatus(SyncStatus),
@@ -168,4 +189,6 @@ pub enum DebugStatusResponse {
     BlockStatus(Vec<DebugBlockStatus>),
     // Detailed information about the validator (approvals, block & chunk production etc.)
     ValidatorStatus(ValidatorStatus),
+    // Location and chain heads of the newly created database checkpoint.
+    Checkpoint(CheckpointInfo),
 }
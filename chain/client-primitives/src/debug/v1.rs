@@ -0,0 +1,272 @@
+//! Structured debug API served under `/debug/api/v1/`.
+//!
+//! Unlike the rest of the [`super`] module, which backs the HTML debug pages,
+//! the JSON encoding of the structs in this module is stable so that it can be
+//! scraped by monitoring.  Fields may be added to it but are never removed,
+//! renamed or change meaning within a version; such changes require a new
+//! version of the API.
+
+use crate::types::{ShardSyncStatus, StatusError};
+use actix::Message;
+use chrono::DateTime;
+use near_primitives::hash::CryptoHash;
+use near_primitives::sharding::ChunkHash;
+use near_primitives::types::{AccountId, BlockHeight, EpochHeight, Gas, NumBlocks, ShardId};
+use near_primitives::version::ProtocolVersion;
+use serde::{Deserialize, Serialize};
+
+/// Version of the API defined in this module.
+pub const DEBUG_API_VERSION: u32 = 1;
+
+/// Requests of the structured debug API, one per endpoint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugApiRequest {
+    /// `/debug/api/v1/blocks`
+    Blocks,
+    /// `/debug/api/v1/epoch_info`
+    EpochInfo,
+    /// `/debug/api/v1/pools`
+    Pools,
+    /// `/debug/api/v1/sync`
+    Sync,
+    /// `/debug/api/v1/validator`
+    Validator,
+}
+
+impl DebugApiRequest {
+    /// Returns request for given endpoint name, i.e. the last segment of its
+    /// path.
+    pub fn from_endpoint(endpoint: &str) -> Option<Self> {
+        Some(match endpoint {
+            "blocks" => Self::Blocks,
+            "epoch_info" => Self::EpochInfo,
+            "pools" => Self::Pools,
+            "sync" => Self::Sync,
+            "validator" => Self::Validator,
+            _ => return None,
+        })
+    }
+}
+
+impl Message for DebugApiRequest {
+    type Result = Result<DebugApiResponse, StatusError>;
+}
+
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+pub enum DebugApiResponse {
+    Blocks(BlocksView),
+    EpochInfo(EpochInfoView),
+    Pools(PoolsView),
+    Sync(SyncView),
+    Validator(ValidatorView),
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlocksView {
+    /// Most recent blocks of the canonical chain, highest first.
+    pub blocks: Vec<BlockView>,
+    /// Heights in the range covered by `blocks` at which no block was
+    /// produced, highest first.
+    pub skipped_heights: Vec<SkippedHeightView>,
+    /// Blocks which were received but haven’t been processed yet, lowest
+    /// height first.
+    pub blocks_in_progress: Vec<BlockInProgressView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlockView {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    pub prev_hash: CryptoHash,
+    pub producer: Option<AccountId>,
+    /// Block timestamp in nanoseconds since the Unix epoch.
+    pub timestamp_nanosec: u64,
+    /// Time it took this node to process the block, if known.
+    pub processing_time_ms: Option<u64>,
+    /// Gas price relative to the gas price of the genesis block.
+    pub gas_price_ratio: f64,
+    pub chunks: Vec<ChunkView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkView {
+    pub shard_id: ShardId,
+    pub chunk_hash: ChunkHash,
+    pub producer: Option<AccountId>,
+    /// Whether a new chunk of the shard is included in the block.  If not,
+    /// the block carries over the chunk header of a previous block.
+    pub included: bool,
+    pub height_created: BlockHeight,
+    pub gas_used: Gas,
+    /// Time it took this node to process the chunk, if known.
+    pub processing_time_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SkippedHeightView {
+    pub height: BlockHeight,
+    /// Block producer which was expected to produce a block at the height.
+    pub expected_producer: Option<AccountId>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct BlockInProgressView {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    /// Time since the block was received.
+    pub received_ms_ago: u64,
+    /// Time the block spent in the orphan pool, up to now if it’s still there.
+    /// None if the block was never orphaned.
+    pub orphaned_ms: Option<u64>,
+    /// Time the block spent waiting for missing chunks, up to now if it’s
+    /// still waiting.  None if the block never missed any chunks.
+    pub missing_chunks_ms: Option<u64>,
+    /// Chunks of the block which had to be requested from peers.
+    pub requested_chunks: Vec<ChunkInProgressView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkInProgressView {
+    pub chunk_hash: ChunkHash,
+    /// Time since the chunk was requested.
+    pub requested_ms_ago: u64,
+    /// Time between requesting and receiving the chunk.  None if the chunk
+    /// hasn’t been received yet.
+    pub received_after_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EpochInfoView {
+    /// The next epoch followed by the most recent epochs, latest first.
+    pub epochs: Vec<EpochView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EpochView {
+    pub epoch_id: CryptoHash,
+    /// Height of the first block of the epoch.  For the next epoch it’s the
+    /// height the epoch is expected to start at.
+    pub start_height: BlockHeight,
+    /// First block of the epoch.  None for the next epoch.
+    pub first_block_hash: Option<CryptoHash>,
+    pub first_block_timestamp: Option<DateTime<chrono::Utc>>,
+    pub protocol_version: ProtocolVersion,
+    pub block_producers: Vec<EpochBlockProducerView>,
+    /// Chunk producers which aren’t block producers.
+    pub chunk_only_producers: Vec<AccountId>,
+    /// State of the shards at the start of the epoch.  Empty for the next
+    /// epoch.
+    pub shards: Vec<EpochShardView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EpochBlockProducerView {
+    pub account_id: AccountId,
+    pub is_slashed: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct EpochShardView {
+    pub shard_id: ShardId,
+    /// Size of the shard’s state in bytes, 0 if it isn’t known.
+    pub state_size: u64,
+    /// Number of parts the state is split into by state sync.
+    pub num_state_parts: u64,
+    /// Whether this node has the state sync header of the shard.
+    pub state_header_exists: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PoolsView {
+    /// Blocks whose previous block is unknown, lowest height first.
+    pub orphans: Vec<PooledBlockView>,
+    /// Number of blocks evicted from the orphan pool since the node started.
+    pub orphans_evicted: u64,
+    /// Blocks waiting for their chunks, lowest height first.
+    pub blocks_with_missing_chunks: Vec<PooledBlockView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct PooledBlockView {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    /// Time since the block was added to the pool, if known.
+    pub in_pool_ms: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncView {
+    /// Sync stage the node is in, e.g. `NoSync`, `HeaderSync` or `StateSync`.
+    pub status: String,
+    pub head_height: BlockHeight,
+    pub header_head_height: BlockHeight,
+    /// Progress of header or body sync, if the node is in one of them.
+    pub progress: Option<SyncProgressView>,
+    /// Hash of the block state is synced to, if the node is in state sync.
+    pub state_sync_hash: Option<CryptoHash>,
+    pub shards: Vec<ShardSyncView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SyncProgressView {
+    pub start_height: BlockHeight,
+    pub current_height: BlockHeight,
+    pub highest_height: BlockHeight,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ShardSyncView {
+    pub shard_id: ShardId,
+    pub tracked_this_epoch: bool,
+    pub tracked_next_epoch: bool,
+    /// Stage of state sync of the shard.  None if the shard isn’t being state
+    /// synced.
+    pub state_sync_status: Option<ShardSyncStatus>,
+    /// Number of finished and total downloads (of the state header or of the
+    /// state parts, depending on the stage) of the shard’s state sync.
+    pub state_sync_downloads_done: u64,
+    pub state_sync_downloads_total: u64,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ValidatorView {
+    /// Account of this node if it’s configured as a validator.
+    pub account_id: Option<AccountId>,
+    pub head_height: BlockHeight,
+    pub epoch_id: CryptoHash,
+    pub epoch_height: EpochHeight,
+    /// Block and chunk production of current epoch validators so far.
+    pub validators: Vec<ValidatorProductionStatsView>,
+    /// Blocks and chunks this node was to produce at recent and upcoming
+    /// heights, highest first.  Empty if the node isn’t a validator.
+    pub production: Vec<ProductionView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ValidatorProductionStatsView {
+    pub account_id: AccountId,
+    pub is_slashed: bool,
+    pub num_produced_blocks: NumBlocks,
+    pub num_expected_blocks: NumBlocks,
+    pub num_produced_chunks: NumBlocks,
+    pub num_expected_chunks: NumBlocks,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ProductionView {
+    pub height: BlockHeight,
+    /// Whether this node was the block producer at the height.
+    pub block_producer: bool,
+    /// Time when this node produced the block, if it did.
+    pub block_produced_at: Option<DateTime<chrono::Utc>>,
+    /// Chunks this node was the producer of at the height.
+    pub chunks: Vec<ChunkProductionView>,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct ChunkProductionView {
+    pub shard_id: ShardId,
+    /// How long producing the chunk took, if this node produced it.
+    pub production_duration_ms: Option<u64>,
+}
@@ -12,6 +12,7 @@ use near_primitives::errors::InvalidTxError;
 use near_primitives::hash::CryptoHash;
 use near_primitives::merkle::{MerklePath, PartialMerkleTree};
 use near_primitives::sharding::ChunkHash;
+use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{
     AccountId, BlockHeight, BlockReference, EpochId, EpochReference, MaybeBlockId, ShardId,
     TransactionOrReceiptId,
@@ -20,12 +21,12 @@ use near_primitives::utils::generate_random_string;
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
 use near_primitives::views::{
     BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
-    FinalExecutionOutcomeViewEnum, GasPriceView, LightClientBlockLiteView, LightClientBlockView,
-    QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView, StateChangesRequestView,
-    StateChangesView,
+    FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, GasPriceView,
+    LightClientBlockLiteView, LightClientBlockView, QueryRequest, QueryResponse, ReceiptView,
+    StateChangesKindsView, StateChangesRequestView, StateChangesView, TransactionTraceView,
 };
 pub use near_primitives::views::{StatusResponse, StatusSyncInfo};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 /// Combines errors coming from chain, tx pool and block producer.
 #[derive(Debug, thiserror::Error)]
@@ -68,7 +69,7 @@ impl Clone for DownloadStatus {
 }
 
 /// Various status of syncing a specific shard.
-#[derive(Clone, Debug, Serialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub enum ShardSyncStatus {
     StateDownloadHeader,
     StateDownloadParts,
@@ -772,6 +773,123 @@ impl Message for GetReceipt {
     type Result = Result<Option<ReceiptView>, GetReceiptError>;
 }
 
+/// Reconstructs the execution trace of a transaction executed by this node.
+pub struct GetTransactionTrace {
+    pub transaction_hash: CryptoHash,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum GetTransactionTraceError {
+    #[error("IO Error: {0}")]
+    IOError(String),
+    #[error(
+        "Transaction {0} has never been observed on this node or its outcome is not known yet"
+    )]
+    UnknownTransaction(CryptoHash),
+    #[error("Transaction results in more than {limit} receipts")]
+    TooManyReceipts { limit: usize },
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {0}")]
+    Unreachable(String),
+}
+
+impl From<near_chain_primitives::error::TransactionTraceError> for GetTransactionTraceError {
+    fn from(error: near_chain_primitives::error::TransactionTraceError) -> Self {
+        use near_chain_primitives::error::TransactionTraceError;
+        match error {
+            TransactionTraceError::UnknownTransaction(transaction_hash) => {
+                Self::UnknownTransaction(transaction_hash)
+            }
+            TransactionTraceError::TooManyReceipts { limit } => Self::TooManyReceipts { limit },
+            TransactionTraceError::Chain(near_chain_primitives::Error::IOErr(error)) => {
+                Self::IOError(error.to_string())
+            }
+            TransactionTraceError::Chain(error) => Self::Unreachable(error.to_string()),
+        }
+    }
+}
+
+impl Message for GetTransactionTrace {
+    type Result = Result<TransactionTraceView, GetTransactionTraceError>;
+}
+
+/// Executes the transaction and all its receipts on top of the state at the given block without
+/// persisting any changes.
+pub struct SimulateTransaction {
+    pub transaction: SignedTransaction,
+    pub block_reference: BlockReference,
+    /// Whether to verify the signature of the transaction.
+    pub verify_signature: bool,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum SimulateTransactionError {
+    #[error("IO Error: {error_message}")]
+    IOError { error_message: String },
+    #[error("Block either has never been observed on the node or has been garbage collected: {error_message}")]
+    UnknownBlock { error_message: String },
+    #[error("There are no fully synchronized blocks yet")]
+    NotSyncedYet,
+    #[error("Transaction is invalid: {0}")]
+    InvalidTransaction(InvalidTxError),
+    #[error("Node doesn't track shard {shard_id} required to execute the transaction")]
+    UnavailableShard { shard_id: ShardId },
+    #[error("Too many transactions are being simulated")]
+    TooManyRequests,
+    #[error("Internal error: {error_message}")]
+    InternalError { error_message: String },
+    // NOTE: Currently, the underlying errors are too broad, and while we tried to handle
+    // expected cases, we cannot statically guarantee that no other errors will be returned
+    // in the future.
+    // TODO #3851: Remove this variant once we can exhaustively match all the underlying errors
+    #[error("It is a bug if you receive this error type, please, report this incident: https://github.com/near/nearcore/issues/new/choose. Details: {error_message}")]
+    Unreachable { error_message: String },
+}
+
+impl From<near_chain_primitives::Error> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::Error) -> Self {
+        match error {
+            near_chain_primitives::Error::IOErr(error) => {
+                Self::IOError { error_message: error.to_string() }
+            }
+            near_chain_primitives::Error::DBNotFoundErr(error_message) => {
+                Self::UnknownBlock { error_message }
+            }
+            _ => Self::Unreachable { error_message: error.to_string() },
+        }
+    }
+}
+
+impl From<near_chain_primitives::error::SimulateTransactionError> for SimulateTransactionError {
+    fn from(error: near_chain_primitives::error::SimulateTransactionError) -> Self {
+        match error {
+            near_chain_primitives::error::SimulateTransactionError::InvalidTransaction(error) => {
+                Self::InvalidTransaction(error)
+            }
+            near_chain_primitives::error::SimulateTransactionError::UnavailableShard {
+                shard_id,
+            } => Self::UnavailableShard { shard_id },
+            near_chain_primitives::error::SimulateTransactionError::InternalError {
+                error_message,
+            } => Self::InternalError { error_message },
+        }
+    }
+}
+
+/// The simulation runs outside of the view client, the receiver yields its outcome once it's
+/// done.
+impl Message for SimulateTransaction {
+    type Result = Result<
+        futures::channel::oneshot::Receiver<
+            Result<FinalExecutionOutcomeView, SimulateTransactionError>,
+        >,
+        SimulateTransactionError,
+    >;
+}
+
 pub struct GetProtocolConfig(pub BlockReference);
 
 impl Message for GetProtocolConfig {
@@ -25,6 +25,7 @@ use near_chunks::{ProcessPartialEncodedChunkResult, ShardsManager};
 use near_network::types::{
     FullPeerInfo, NetworkClientResponses, NetworkRequests, PeerManagerAdapter,
 };
+use near_pool::types::InsertTransactionResult;
 use near_primitives::block::{Approval, ApprovalInner, ApprovalMessage, Block, BlockHeader, Tip};
 use near_primitives::challenge::{Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
@@ -42,6 +43,7 @@ use near_primitives::utils::MaybeValidated;
 use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::views::{BlockByChunksView, ChunkInfoView};
 
+use crate::external_state_sync::{DirectoryStateSource, ExternalStateSource};
 use crate::sync::{BlockSync, EpochSync, HeaderSync, StateSync, StateSyncResult};
 use crate::{metrics, SyncStatus};
 use itertools::Itertools;
@@ -107,6 +109,9 @@ pub struct Client {
     pub block_sync: BlockSync,
     /// Keeps track of syncing state.
     pub state_sync: StateSync,
+    /// Source of state headers and parts tried before the peers, shared by
+    /// the state sync and catchup state syncs.
+    external_state_source: Option<Arc<dyn ExternalStateSource>>,
     /// List of currently accumulated challenges.
     pub challenges: HashMap<CryptoHash, Challenge>,
     /// A ReedSolomon instance to reconstruct shard.
@@ -164,17 +169,21 @@ impl Client {
         } else {
             DoomslugThresholdMode::NoApprovals
         };
+        // Archival nodes with a cold database garbage collect the hot one, for
+        // which trie changes are needed.
+        let has_cold_store = runtime_adapter.get_store().cold_store().is_some();
         let chain = Chain::new(
             runtime_adapter.clone(),
             &chain_genesis,
             doomslug_threshold_mode,
-            !config.archive,
+            !config.archive || has_cold_store,
         )?;
         let shards_mgr = ShardsManager::new(
             validator_signer.as_ref().map(|x| x.validator_id().clone()),
             runtime_adapter.clone(),
             network_adapter.clone(),
             rng_seed,
+            config.transaction_pool.clone(),
         );
         let sync_status = SyncStatus::AwaitingPeers;
         let genesis_block = chain.genesis_block();
@@ -202,7 +211,15 @@ impl Client {
         );
         let block_sync =
             BlockSync::new(network_adapter.clone(), config.block_fetch_horizon, config.archive);
-        let state_sync = StateSync::new(network_adapter.clone(), config.state_sync_timeout);
+        let external_state_source = config
+            .state_sync_external_dir
+            .clone()
+            .map(|dir| Arc::new(DirectoryStateSource::new(dir)) as Arc<dyn ExternalStateSource>);
+        let state_sync = StateSync::new(
+            network_adapter.clone(),
+            config.state_sync_timeout,
+            external_state_source.clone(),
+        );
         let num_block_producer_seats = config.num_block_producer_seats as usize;
         let data_parts = runtime_adapter.num_data_parts();
         let parity_parts = runtime_adapter.num_total_parts() - data_parts;
@@ -237,6 +254,7 @@ impl Client {
             header_sync,
             block_sync,
             state_sync,
+            external_state_source,
             challenges: Default::default(),
             rs: ReedSolomonWrapper::new(data_parts, parity_parts),
             rebroadcasted_blocks: lru::LruCache::new(NUM_REBROADCAST_BLOCKS),
@@ -1215,7 +1233,9 @@ impl Client {
                 .entered();
                 let _gc_timer = metrics::GC_TIME.start_timer();
 
-                let result = if self.config.archive {
+                // Archival nodes with a cold database move old data there.
+                let has_cold_store = self.chain.store().store().cold_store().is_some();
+                let result = if self.config.archive && !has_cold_store {
                     self.chain.clear_archive_data(self.config.gc.gc_blocks_limit)
                 } else {
                     let tries = self.runtime_adapter.get_tries();
@@ -1753,8 +1773,24 @@ impl Client {
                 // TODO #6713: Transactions don't need to be recorded if the node is not a validator
                 // for the shard.
                 // If I'm not an active validator I should forward tx to next validators.
-                self.shards_mgr.insert_transaction(shard_id, tx.clone());
-                trace!(target: "client", shard_id, "Recorded a transaction.");
+                let rejected = match self.shards_mgr.insert_transaction(shard_id, tx.clone()) {
+                    InsertTransactionResult::Success => {
+                        trace!(target: "client", shard_id, "Recorded a transaction.");
+                        None
+                    }
+                    InsertTransactionResult::Duplicate => {
+                        trace!(target: "client", shard_id, "Transaction is already in the pool.");
+                        None
+                    }
+                    InsertTransactionResult::TooManyFromSigner => {
+                        debug!(target: "client", shard_id, "Signer has too many transactions in the pool.");
+                        Some(NetworkClientResponses::TooManyTxsFromSigner)
+                    }
+                    InsertTransactionResult::NoSpaceLeft => {
+                        debug!(target: "client", shard_id, "Transaction pool is full.");
+                        Some(NetworkClientResponses::TxPoolFull)
+                    }
+                };
 
                 // Active validator:
                 //   possibly forward to next epoch validators
@@ -1764,6 +1800,11 @@ impl Client {
                 if active_validator {
                     trace!(target: "client", account = ?me, shard_id, is_forwarded, "Recording a transaction.");
                     metrics::TRANSACTION_RECEIVED_VALIDATOR.inc();
+                    // The transaction won't be included by this validator, the sender
+                    // should retry later rather than wait for it.
+                    if let Some(response) = rejected {
+                        return Ok(response);
+                    }
 
                     if !is_forwarded {
                         self.possibly_forward_tx_to_next_epoch(tx)?;
@@ -1867,11 +1908,12 @@ impl Client {
                 }
             };
             let state_sync_timeout = self.config.state_sync_timeout;
+            let external_state_source = self.external_state_source.clone();
             let epoch_id = self.chain.get_block(&sync_hash)?.header().epoch_id().clone();
             let (state_sync, new_shard_sync, blocks_catch_up_state) =
                 self.catchup_state_syncs.entry(sync_hash).or_insert_with(|| {
                     (
-                        StateSync::new(network_adapter1, state_sync_timeout),
+                        StateSync::new(network_adapter1, state_sync_timeout, external_state_source),
                         new_shard_sync,
                         BlocksCatchUpState::new(sync_hash, epoch_id),
                     )
@@ -31,7 +31,7 @@ use near_client_primitives::types::{
 #[cfg(feature = "test_features")]
 use near_chain::ChainStoreAccess;
 use near_network::types::{
-    NetworkClientMessages, NetworkClientResponses, NetworkInfo, NetworkRequests,
+    EpochValidators, NetworkClientMessages, NetworkClientResponses, NetworkInfo, NetworkRequests,
     PeerManagerAdapter, PeerManagerMessageRequest,
 };
 use near_network_primitives::types::ReasonForBan;
@@ -44,7 +44,7 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::state_part::PartId;
 use near_primitives::syncing::StatePartKey;
 use near_primitives::time::{Clock, Utc};
-use near_primitives::types::BlockHeight;
+use near_primitives::types::{BlockHeight, EpochId};
 use near_primitives::unwrap_or_return;
 use near_primitives::utils::{from_timestamp, MaybeValidated};
 use near_primitives::validator_signer::ValidatorSigner;
@@ -83,6 +83,8 @@ pub struct ClientActor {
     node_id: PeerId,
     /// Last time we announced our accounts as validators.
     last_validator_announce_time: Option<Instant>,
+    /// Current and next epochs, the validators of which were last sent to the network.
+    last_validators_epochs: Option<(EpochId, EpochId)>,
     /// Info helper.
     info_helper: InfoHelper,
 
@@ -187,6 +189,7 @@ impl ClientActor {
                 peer_counter: 0,
             },
             last_validator_announce_time: None,
+            last_validators_epochs: None,
             info_helper,
             block_production_next_attempt: now,
             log_summary_timer_next_attempt: now,
@@ -864,6 +867,48 @@ impl ClientActor {
         }
     }
 
+    /// Send the validators of the current and the next epoch to the network, whenever the epochs
+    /// change, so that the validators maintain direct connections with each other.
+    fn check_send_validators(&mut self, prev_block_hash: CryptoHash) {
+        if self.client.validator_signer.is_none() {
+            return;
+        }
+        let runtime_adapter = &self.client.runtime_adapter;
+        let epoch_id =
+            unwrap_or_return!(runtime_adapter.get_epoch_id_from_prev_block(&prev_block_hash));
+        let next_epoch_id =
+            unwrap_or_return!(runtime_adapter.get_next_epoch_id_from_prev_block(&prev_block_hash));
+        let epochs = (epoch_id, next_epoch_id);
+        if self.last_validators_epochs.as_ref() == Some(&epochs) {
+            return;
+        }
+
+        let mut validators = vec![];
+        for epoch_id in [&epochs.0, &epochs.1] {
+            let block_producers = unwrap_or_return!(
+                runtime_adapter.get_epoch_block_producers_ordered(epoch_id, &prev_block_hash)
+            );
+            let chunk_producers =
+                unwrap_or_return!(runtime_adapter.get_epoch_chunk_producers(epoch_id));
+            let keys = block_producers
+                .into_iter()
+                .filter(|(_, is_slashed)| !is_slashed)
+                .map(|(validator_stake, _)| validator_stake)
+                .chain(chunk_producers)
+                .map(|validator_stake| {
+                    let (account_id, public_key, _stake) = validator_stake.destructure();
+                    (account_id, public_key)
+                })
+                .collect();
+            validators.push(EpochValidators { epoch_id: epoch_id.clone(), keys });
+        }
+        debug!(target: "client", epochs = ?epochs, "Sending validators to the network");
+        self.last_validators_epochs = Some(epochs);
+        self.network_adapter.do_send(PeerManagerMessageRequest::NetworkRequests(
+            NetworkRequests::SetValidators(validators),
+        ));
+    }
+
     /// Process the sandbox fast forward request. If the change in block height is past an epoch,
     /// we fast forward to just right before the epoch, produce some blocks to get past and into
     /// a new epoch, then we continue on with the residual amount to fast forward.
@@ -1245,6 +1290,7 @@ impl ClientActor {
                 epoch_height,
             );
             self.check_send_announce_account(*last_final_hash);
+            self.check_send_validators(*last_final_hash);
         }
     }
 
@@ -1602,6 +1648,7 @@ impl ClientActor {
                 // Announce this client's account id if their epoch is coming up.
                 let head = unwrap_or_run_later!(self.client.chain.head());
                 self.check_send_announce_account(head.prev_block_hash);
+                self.check_send_validators(head.prev_block_hash);
             }
             wait_period = self.client.config.sync_check_period;
         } else {
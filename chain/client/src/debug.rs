@@ -4,8 +4,11 @@
 use crate::ClientActor;
 use actix::{Context, Handler};
 use borsh::BorshSerialize;
+use near_chain::chain::ChainAccess;
 use near_chain::crypto_hash_timer::CryptoHashTimer;
+use near_chain::types::ValidatorInfoIdentifier;
 use near_chain::{near_chain_primitives, ChainStoreAccess};
+use near_client_primitives::debug::v1::{self, DebugApiRequest, DebugApiResponse};
 use near_client_primitives::debug::{
     BlockProduction, ChunkProduction, DebugStatus, DebugStatusResponse, ProductionAtHeight,
     ValidatorStatus,
@@ -13,11 +16,13 @@ use near_client_primitives::debug::{
 use near_client_primitives::types::Error;
 use near_client_primitives::{
     debug::{EpochInfoView, TrackedShardsView},
-    types::StatusError,
+    types::{StatusError, SyncStatus},
 };
 use near_performance_metrics_macros::perf;
 use near_primitives::syncing::get_num_state_parts;
-use near_primitives::types::{AccountId, BlockHeight};
+use near_primitives::time::Clock;
+use near_primitives::types::{AccountId, BlockHeight, ShardId};
+use near_primitives::views::BlockStatusView;
 use near_primitives::{
     hash::CryptoHash,
     syncing::{ShardStateSyncResponseHeader, StateHeaderKey},
@@ -70,7 +75,7 @@ impl ClientActor {
         &self,
         epoch_id: &EpochId,
         last_known_block_hash: &CryptoHash,
-    ) -> Result<(Vec<ValidatorInfo>, Vec<String>), Error> {
+    ) -> Result<(Vec<ValidatorInfo>, Vec<AccountId>), Error> {
         let mut block_producers_set = HashSet::new();
         let block_producers: Vec<ValidatorInfo> = self
             .client
@@ -91,7 +96,7 @@ impl ClientActor {
                 if block_producers_set.contains(&producer.account_id().to_string()) {
                     None
                 } else {
-                    Some(producer.account_id().to_string())
+                    Some(producer.account_id().clone())
                 }
             })
             .collect::<Vec<_>>();
@@ -438,3 +443,321 @@ impl ClientActor {
         })
     }
 }
+
+impl Handler<DebugApiRequest> for ClientActor {
+    type Result = Result<DebugApiResponse, StatusError>;
+
+    #[perf]
+    fn handle(&mut self, msg: DebugApiRequest, _ctx: &mut Context<Self>) -> Self::Result {
+        Ok(match msg {
+            DebugApiRequest::Blocks => DebugApiResponse::Blocks(self.get_blocks_view()?),
+            DebugApiRequest::EpochInfo => {
+                DebugApiResponse::EpochInfo(self.get_epoch_info_v1_view()?)
+            }
+            DebugApiRequest::Pools => DebugApiResponse::Pools(self.get_pools_view()),
+            DebugApiRequest::Sync => DebugApiResponse::Sync(self.get_sync_view()?),
+            DebugApiRequest::Validator => DebugApiResponse::Validator(self.get_validator_view()?),
+        })
+    }
+}
+
+/// Converts duration to milliseconds as reported by the debug API.
+fn as_millis(duration: std::time::Duration) -> u64 {
+    duration.as_millis() as u64
+}
+
+// Builders of the responses of the structured (`/debug/api/v1/`) debug API.
+impl ClientActor {
+    fn get_blocks_view(&self) -> Result<v1::BlocksView, near_chain_primitives::Error> {
+        let head = self.client.chain.head()?;
+        let initial_gas_price = self.client.chain.genesis_block().header().gas_price();
+
+        let mut blocks = Vec::new();
+        let mut skipped_heights = Vec::new();
+        let mut block_hash = head.last_block_hash;
+        let mut next_height = None;
+        for _ in 0..DEBUG_BLOCKS_TO_FETCH {
+            let block = match self.client.chain.get_block(&block_hash) {
+                Ok(block) => block,
+                Err(_) => break,
+            };
+            let header = block.header();
+            if let Some(next_height) = next_height {
+                for height in (header.height() + 1..next_height).rev() {
+                    skipped_heights.push(v1::SkippedHeightView {
+                        height,
+                        expected_producer: self
+                            .client
+                            .runtime_adapter
+                            .get_block_producer(header.epoch_id(), height)
+                            .ok(),
+                    });
+                }
+            }
+            let chunks = block
+                .chunks()
+                .iter()
+                .map(|chunk| v1::ChunkView {
+                    shard_id: chunk.shard_id(),
+                    chunk_hash: chunk.chunk_hash(),
+                    producer: self
+                        .client
+                        .runtime_adapter
+                        .get_chunk_producer(
+                            header.epoch_id(),
+                            chunk.height_created(),
+                            chunk.shard_id(),
+                        )
+                        .ok(),
+                    included: chunk.height_included() == header.height(),
+                    height_created: chunk.height_created(),
+                    gas_used: chunk.gas_used(),
+                    processing_time_ms: CryptoHashTimer::get_timer_value(chunk.chunk_hash().0)
+                        .map(as_millis),
+                })
+                .collect();
+            blocks.push(v1::BlockView {
+                hash: block_hash,
+                height: header.height(),
+                prev_hash: *header.prev_hash(),
+                producer: self
+                    .client
+                    .runtime_adapter
+                    .get_block_producer(header.epoch_id(), header.height())
+                    .ok(),
+                timestamp_nanosec: header.raw_timestamp(),
+                processing_time_ms: CryptoHashTimer::get_timer_value(block_hash).map(as_millis),
+                gas_price_ratio: header.gas_price() as f64 / initial_gas_price as f64,
+                chunks,
+            });
+            next_height = Some(header.height());
+            block_hash = *header.prev_hash();
+        }
+
+        let now = Clock::instant();
+        let tracker = &self.client.chain.blocks_delay_tracker;
+        let mut blocks_in_progress: Vec<_> = tracker
+            .blocks_in_progress
+            .iter()
+            .map(|(hash, block)| v1::BlockInProgressView {
+                hash: *hash,
+                height: block.height,
+                received_ms_ago: as_millis(now.saturating_duration_since(block.received_timestamp)),
+                orphaned_ms: block.orphaned_timestamp.map(|start| {
+                    as_millis(
+                        block
+                            .removed_from_orphan_timestamp
+                            .unwrap_or(now)
+                            .saturating_duration_since(start),
+                    )
+                }),
+                missing_chunks_ms: block.missing_chunks_timestamp.map(|start| {
+                    as_millis(
+                        block
+                            .removed_from_missing_chunks_timestamp
+                            .unwrap_or(now)
+                            .saturating_duration_since(start),
+                    )
+                }),
+                requested_chunks: block
+                    .chunks
+                    .iter()
+                    .filter_map(|chunk_hash| {
+                        let chunk = tracker.chunks_in_progress.get(chunk_hash)?;
+                        Some(v1::ChunkInProgressView {
+                            chunk_hash: chunk_hash.clone(),
+                            requested_ms_ago: as_millis(
+                                now.saturating_duration_since(chunk.chunk_requested),
+                            ),
+                            received_after_ms: chunk.chunk_received.map(|received| {
+                                as_millis(received.saturating_duration_since(chunk.chunk_requested))
+                            }),
+                        })
+                    })
+                    .collect(),
+            })
+            .collect();
+        blocks_in_progress.sort_by_key(|block| block.height);
+
+        Ok(v1::BlocksView { blocks, skipped_heights, blocks_in_progress })
+    }
+
+    fn get_epoch_info_v1_view(
+        &mut self,
+    ) -> Result<v1::EpochInfoView, near_chain_primitives::Error> {
+        let epochs = self
+            .get_recent_epoch_info()?
+            .into_iter()
+            .map(|epoch| v1::EpochView {
+                epoch_id: epoch.epoch_id,
+                start_height: epoch.height,
+                first_block_hash: epoch.first_block.map(|(hash, _)| hash),
+                first_block_timestamp: epoch.first_block.map(|(_, timestamp)| timestamp),
+                protocol_version: epoch.protocol_version,
+                block_producers: epoch
+                    .validators
+                    .into_iter()
+                    .map(|validator| v1::EpochBlockProducerView {
+                        account_id: validator.account_id,
+                        is_slashed: validator.is_slashed,
+                    })
+                    .collect(),
+                chunk_only_producers: epoch.chunk_only_producers,
+                shards: epoch
+                    .shards_size_and_parts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(shard_id, (state_size, num_state_parts, state_header_exists))| {
+                        v1::EpochShardView {
+                            shard_id: shard_id as ShardId,
+                            state_size,
+                            num_state_parts,
+                            state_header_exists,
+                        }
+                    })
+                    .collect(),
+            })
+            .collect();
+        Ok(v1::EpochInfoView { epochs })
+    }
+
+    fn get_pools_view(&self) -> v1::PoolsView {
+        let now = Clock::instant();
+        let tracker = &self.client.chain.blocks_delay_tracker;
+        let to_views = |blocks: Vec<BlockStatusView>, orphans: bool| {
+            let mut views: Vec<_> = blocks
+                .into_iter()
+                .map(|block| {
+                    let added = tracker.blocks_in_progress.get(&block.hash).and_then(|progress| {
+                        if orphans {
+                            progress.orphaned_timestamp
+                        } else {
+                            progress.missing_chunks_timestamp
+                        }
+                    });
+                    v1::PooledBlockView {
+                        hash: block.hash,
+                        height: block.height,
+                        in_pool_ms: added
+                            .map(|added| as_millis(now.saturating_duration_since(added))),
+                    }
+                })
+                .collect();
+            views.sort_by_key(|view| view.height);
+            views
+        };
+        let orphans = self.client.chain.orphans();
+        v1::PoolsView {
+            orphans: to_views(orphans.list_orphans_by_height(), true),
+            orphans_evicted: orphans.len_evicted() as u64,
+            blocks_with_missing_chunks: to_views(
+                self.client.chain.blocks_with_missing_chunks.list_blocks_by_height(),
+                false,
+            ),
+        }
+    }
+
+    fn get_sync_view(&self) -> Result<v1::SyncView, near_chain_primitives::Error> {
+        let tracked_shards = self.get_tracked_shards_view()?;
+        let (progress, state_sync) = match &self.client.sync_status {
+            SyncStatus::HeaderSync { start_height, current_height, highest_height }
+            | SyncStatus::BodySync { start_height, current_height, highest_height } => (
+                Some(v1::SyncProgressView {
+                    start_height: *start_height,
+                    current_height: *current_height,
+                    highest_height: *highest_height,
+                }),
+                None,
+            ),
+            SyncStatus::StateSync(sync_hash, shards) => (None, Some((sync_hash, shards))),
+            _ => (None, None),
+        };
+        let shards = tracked_shards
+            .shards_tracked_this_epoch
+            .iter()
+            .zip(tracked_shards.shards_tracked_next_epoch.iter())
+            .enumerate()
+            .map(|(shard_id, (&tracked_this_epoch, &tracked_next_epoch))| {
+                let download =
+                    state_sync.and_then(|(_, shards)| shards.get(&(shard_id as ShardId)));
+                v1::ShardSyncView {
+                    shard_id: shard_id as ShardId,
+                    tracked_this_epoch,
+                    tracked_next_epoch,
+                    state_sync_status: download.map(|download| download.status.clone()),
+                    state_sync_downloads_done: download.map_or(0, |download| {
+                        download.downloads.iter().filter(|status| status.done).count() as u64
+                    }),
+                    state_sync_downloads_total: download
+                        .map_or(0, |download| download.downloads.len() as u64),
+                }
+            })
+            .collect();
+        Ok(v1::SyncView {
+            status: self.client.sync_status.as_variant_name().to_string(),
+            head_height: self.client.chain.head()?.height,
+            header_head_height: self.client.chain.header_head()?.height,
+            progress,
+            state_sync_hash: state_sync.map(|(sync_hash, _)| *sync_hash),
+            shards,
+        })
+    }
+
+    fn get_validator_view(&mut self) -> Result<v1::ValidatorView, near_chain_primitives::Error> {
+        let head = self.client.chain.head()?;
+        let epoch_info = self
+            .client
+            .runtime_adapter
+            .get_validator_info(ValidatorInfoIdentifier::BlockHash(head.last_block_hash))?;
+        let validators = epoch_info
+            .current_validators
+            .into_iter()
+            .map(|validator| v1::ValidatorProductionStatsView {
+                account_id: validator.account_id,
+                is_slashed: validator.is_slashed,
+                num_produced_blocks: validator.num_produced_blocks,
+                num_expected_blocks: validator.num_expected_blocks,
+                num_produced_chunks: validator.num_produced_chunks,
+                num_expected_chunks: validator.num_expected_chunks,
+            })
+            .collect();
+
+        let status = self.get_validator_status()?;
+        let mut production: Vec<_> = status
+            .production
+            .into_iter()
+            .filter(|(_, production)| {
+                production.block_production.is_some() || !production.chunk_production.is_empty()
+            })
+            .map(|(height, production)| {
+                let mut chunks: Vec<_> = production
+                    .chunk_production
+                    .into_iter()
+                    .map(|(shard_id, chunk)| v1::ChunkProductionView {
+                        shard_id,
+                        production_duration_ms: chunk.chunk_production_duration_millis,
+                    })
+                    .collect();
+                chunks.sort_by_key(|chunk| chunk.shard_id);
+                v1::ProductionView {
+                    height,
+                    block_producer: production.block_production.is_some(),
+                    block_produced_at: production
+                        .block_production
+                        .and_then(|block| block.block_production_time),
+                    chunks,
+                }
+            })
+            .collect();
+        production.sort_by(|a, b| b.height.cmp(&a.height));
+
+        Ok(v1::ValidatorView {
+            account_id: status.validator_name,
+            head_height: head.height,
+            epoch_id: head.epoch_id.0,
+            epoch_height: epoch_info.epoch_height,
+            validators,
+            production,
+        })
+    }
+}
@@ -0,0 +1,312 @@
+//! State sync from sources other than the peers.
+//!
+//! Normally state headers and parts are requested from the peers which track
+//! the shard.  If only a few of them do, state sync may take very long or not
+//! finish at all.  To help with that, a node can dump the state headers and
+//! parts of the shards it tracks at each epoch's sync hash into a directory
+//! (see [`spawn_state_dumper`]), and other nodes can read them from that
+//! directory (or any other [`ExternalStateSource`]) instead of asking peers.
+//!
+//! Headers and parts from external sources are validated exactly like the ones
+//! received from the peers.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use tracing::{debug, error, info};
+
+use near_chain::{
+    Chain, ChainGenesis, ChainStoreAccess, DoomslugThresholdMode, Error, RuntimeAdapter,
+};
+use near_primitives::hash::CryptoHash;
+use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader};
+use near_primitives::types::ShardId;
+
+use crate::sync::StateSync;
+
+/// How often the dumper checks whether a new epoch has started.
+const STATE_DUMP_CHECK_PERIOD: Duration = Duration::from_secs(10);
+
+/// Default number of state parts of a shard read from an external source per
+/// state sync step.  Keeps a single step from blocking the client for too long.
+const DEFAULT_MAX_PART_READS_PER_STEP: usize = 16;
+
+/// Source of state headers and parts used by state sync before falling back
+/// to the peers.
+///
+/// Data returned by the source is validated before it's used, so the source
+/// doesn't need to be trusted.
+pub trait ExternalStateSource: Send + Sync {
+    /// Returns the state header of the shard at `sync_hash`, or `None` if the
+    /// source doesn't have it.
+    fn get_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+    ) -> io::Result<Option<ShardStateSyncResponseHeader>>;
+
+    /// Returns the state part of the shard at `sync_hash`, or `None` if the
+    /// source doesn't have it.
+    fn get_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+        part_id: u64,
+    ) -> io::Result<Option<Vec<u8>>>;
+
+    /// Maximum number of state parts of a shard read from the source per state
+    /// sync step.  Parts over the limit stay pending until the next step.
+    fn max_part_reads_per_step(&self) -> usize {
+        DEFAULT_MAX_PART_READS_PER_STEP
+    }
+}
+
+/// Directory holding state headers and parts, laid out as
+/// `<root>/<sync_hash>/shard_<shard_id>/{header,part_<part_id>}`.
+///
+/// The header of a shard is written after all of its parts, so a reader never
+/// sees a header without the parts.
+pub struct DirectoryStateSource {
+    root: PathBuf,
+}
+
+impl DirectoryStateSource {
+    pub fn new(root: PathBuf) -> Self {
+        Self { root }
+    }
+
+    fn shard_dir(&self, shard_id: ShardId, sync_hash: &CryptoHash) -> PathBuf {
+        self.root.join(sync_hash.to_string()).join(format!("shard_{}", shard_id))
+    }
+
+    fn header_path(&self, shard_id: ShardId, sync_hash: &CryptoHash) -> PathBuf {
+        self.shard_dir(shard_id, sync_hash).join("header")
+    }
+
+    fn part_path(&self, shard_id: ShardId, sync_hash: &CryptoHash, part_id: u64) -> PathBuf {
+        self.shard_dir(shard_id, sync_hash).join(format!("part_{}", part_id))
+    }
+
+    /// Returns whether the state of the shard at `sync_hash` was fully dumped.
+    pub fn has_shard(&self, shard_id: ShardId, sync_hash: &CryptoHash) -> bool {
+        self.header_path(shard_id, sync_hash).is_file()
+    }
+
+    pub fn save_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+        part_id: u64,
+        part: &[u8],
+    ) -> io::Result<()> {
+        write_atomically(&self.part_path(shard_id, sync_hash, part_id), part)
+    }
+
+    pub fn save_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+        header: &ShardStateSyncResponseHeader,
+    ) -> io::Result<()> {
+        write_atomically(&self.header_path(shard_id, sync_hash), &header.try_to_vec()?)
+    }
+}
+
+impl ExternalStateSource for DirectoryStateSource {
+    fn get_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+    ) -> io::Result<Option<ShardStateSyncResponseHeader>> {
+        read_if_exists(&self.header_path(shard_id, sync_hash))?
+            .map(|data| ShardStateSyncResponseHeader::try_from_slice(&data))
+            .transpose()
+    }
+
+    fn get_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+        part_id: u64,
+    ) -> io::Result<Option<Vec<u8>>> {
+        read_if_exists(&self.part_path(shard_id, sync_hash, part_id))
+    }
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
+/// Writes the file so that readers never observe it partially written.
+fn write_atomically(path: &Path, data: &[u8]) -> io::Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp_path = path.with_extension("tmp");
+    fs::write(&tmp_path, data)?;
+    fs::rename(&tmp_path, path)
+}
+
+/// Dumps state headers and parts of the shards whose state the node has at
+/// `sync_hash`, skipping the shards which were already dumped.
+///
+/// Returns early, leaving the shard being dumped incomplete, once `stop` is
+/// set.
+pub fn dump_state(
+    chain: &Chain,
+    sync_hash: CryptoHash,
+    dir: &DirectoryStateSource,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    let sync_header = chain.get_block_header(&sync_hash)?;
+    let prev_hash = *sync_header.prev_hash();
+    let epoch_id = chain.runtime_adapter.get_epoch_id_from_prev_block(&prev_hash)?;
+    for shard_id in 0..chain.runtime_adapter.num_shards(&epoch_id)? {
+        if dir.has_shard(shard_id, &sync_hash) {
+            continue;
+        }
+        let shard_uid = chain.runtime_adapter.shard_id_to_uid(shard_id, &epoch_id)?;
+        if chain.get_chunk_extra(&prev_hash, &shard_uid).is_err() {
+            // The node doesn't track the shard.
+            continue;
+        }
+        let header = chain.get_state_response_header(shard_id, sync_hash)?;
+        let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+        info!(target: "sync", shard_id, num_parts, %sync_hash, "Dumping state parts");
+        for part_id in 0..num_parts {
+            if stop.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+            let part = chain.get_state_response_part(shard_id, part_id, sync_hash)?;
+            dir.save_part(shard_id, &sync_hash, part_id, &part)?;
+        }
+        dir.save_header(shard_id, &sync_hash, &header)?;
+        info!(target: "sync", shard_id, %sync_hash, "Dumped state");
+    }
+    Ok(())
+}
+
+/// Handle of the thread started by [`spawn_state_dumper`].
+///
+/// Dropping the handle leaves the thread running; use [`Self::stop`] to stop
+/// it.
+pub struct StateDumperHandle {
+    stop: Arc<AtomicBool>,
+    thread: std::thread::JoinHandle<()>,
+}
+
+impl StateDumperHandle {
+    /// Stops the thread and waits for it to finish writing the part it's
+    /// dumping.
+    pub fn stop(self) {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.thread().unpark();
+        if self.thread.join().is_err() {
+            error!(target: "sync", "State dumper thread panicked");
+        }
+    }
+}
+
+/// Starts a thread which dumps the state of the tracked shards into `dir` at
+/// the sync hash of every new epoch.
+///
+/// The thread uses its own read-only view of the chain, so that dumping
+/// doesn't block block processing.
+pub fn spawn_state_dumper(
+    dir: PathBuf,
+    runtime_adapter: Arc<dyn RuntimeAdapter>,
+    chain_genesis: ChainGenesis,
+) -> Result<StateDumperHandle, Error> {
+    let chain = Chain::new_for_view_client(
+        runtime_adapter,
+        &chain_genesis,
+        DoomslugThresholdMode::TwoThirds,
+        false,
+    )?;
+    let dir = DirectoryStateSource::new(dir);
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread = std::thread::Builder::new().name("state_dumper".to_string()).spawn({
+        let stop = stop.clone();
+        move || {
+            let mut chain = chain;
+            let mut last_sync_hash = None;
+            while !stop.load(Ordering::Relaxed) {
+                match dump_state_at_head(&mut chain, &dir, &mut last_sync_hash, &stop) {
+                    Ok(()) => {}
+                    Err(err) => error!(target: "sync", ?err, "Failed to dump state"),
+                }
+                // Woken up early by `StateDumperHandle::stop`.
+                std::thread::park_timeout(STATE_DUMP_CHECK_PERIOD);
+            }
+            debug!(target: "sync", "State dumper stopped");
+        }
+    })?;
+    Ok(StateDumperHandle { stop, thread })
+}
+
+fn dump_state_at_head(
+    chain: &mut Chain,
+    dir: &DirectoryStateSource,
+    last_sync_hash: &mut Option<CryptoHash>,
+    stop: &AtomicBool,
+) -> Result<(), Error> {
+    let head = chain.store().head()?;
+    let sync_hash = StateSync::get_epoch_start_sync_hash(chain, &head.last_block_hash)?;
+    if *last_sync_hash == Some(sync_hash) {
+        return Ok(());
+    }
+    if chain.get_block_header(&sync_hash)?.prev_hash() == &CryptoHash::default() {
+        // There is nothing to sync to in the first epoch.
+        return Ok(());
+    }
+    debug!(target: "sync", %sync_hash, "New epoch, dumping state");
+    dump_state(chain, sync_hash, dir, stop)?;
+    *last_sync_hash = Some(sync_hash);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        spawn_state_dumper, DirectoryStateSource, ExternalStateSource, STATE_DUMP_CHECK_PERIOD,
+    };
+    use near_chain::test_utils::setup;
+    use near_chain::ChainGenesis;
+    use near_primitives::hash::hash;
+    use std::time::Instant;
+
+    #[test]
+    fn test_directory_state_source() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = DirectoryStateSource::new(dir.path().to_path_buf());
+        let sync_hash = hash(b"sync");
+
+        assert!(!source.has_shard(0, &sync_hash));
+        assert_eq!(source.get_part(0, &sync_hash, 0).unwrap(), None);
+        source.save_part(0, &sync_hash, 0, b"part").unwrap();
+        assert_eq!(source.get_part(0, &sync_hash, 0).unwrap(), Some(b"part".to_vec()));
+        assert_eq!(source.get_part(1, &sync_hash, 0).unwrap(), None);
+        assert!(source.get_header(0, &sync_hash).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_stop_state_dumper() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, runtime_adapter, _) = setup();
+        let dumper =
+            spawn_state_dumper(dir.path().to_path_buf(), runtime_adapter, ChainGenesis::test())
+                .unwrap();
+        let start = Instant::now();
+        dumper.stop();
+        assert!(start.elapsed() < STATE_DUMP_CHECK_PERIOD);
+    }
+}
@@ -3,11 +3,13 @@ pub use near_client_primitives::types::{
     GetChunk, GetExecutionOutcome, GetExecutionOutcomeResponse, GetExecutionOutcomesForBlock,
     GetGasPrice, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt,
     GetStateChanges, GetStateChangesInBlock, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfo, GetValidatorOrdered, Query,
-    QueryError, Status, StatusResponse, SyncStatus, TxStatus, TxStatusError,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetTransactionTrace, GetValidatorInfo,
+    GetValidatorOrdered, Query, QueryError, SimulateTransaction, SimulateTransactionError, Status,
+    StatusResponse, SyncStatus, TxStatus, TxStatusError,
 };
 
-pub use near_client_primitives::debug::DebugStatus;
+pub use near_client_primitives::debug::v1::DebugApiRequest;
+pub use near_client_primitives::debug::{CreateCheckpoint, DebugStatus};
 
 pub use crate::client::Client;
 pub use crate::client_actor::{start_client, ClientActor};
@@ -17,6 +19,7 @@ pub mod adversarial;
 mod client;
 mod client_actor;
 pub mod debug;
+pub mod external_state_sync;
 mod info;
 mod metrics;
 mod rocksdb_metrics;
@@ -18,6 +18,7 @@ use near_network::types::{FullPeerInfo, NetworkRequests, NetworkResponses, PeerM
 use near_primitives::block::Tip;
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::PeerId;
+use near_primitives::state_part::PartId;
 use near_primitives::syncing::get_num_state_parts;
 use near_primitives::time::{Clock, Utc};
 use near_primitives::types::validator_stake::ValidatorStake;
@@ -34,6 +35,8 @@ use near_network::types::PeerManagerMessageRequest;
 use near_network_primitives::types::AccountOrPeerIdOrHash;
 use near_primitives::shard_layout::ShardUId;
 
+use crate::external_state_sync::ExternalStateSource;
+
 /// Maximum number of block headers send over the network.
 pub const MAX_BLOCK_HEADERS: u64 = 512;
 
@@ -640,10 +643,17 @@ pub struct StateSync {
 
     /// Maps shard_id to result of splitting state for resharding
     split_state_roots: HashMap<ShardId, Result<HashMap<ShardUId, StateRoot>, Error>>,
+
+    /// Source of state headers and parts tried before requesting them from the peers.
+    external_source: Option<Arc<dyn ExternalStateSource>>,
 }
 
 impl StateSync {
-    pub fn new(network_adapter: Arc<dyn PeerManagerAdapter>, timeout: TimeDuration) -> Self {
+    pub fn new(
+        network_adapter: Arc<dyn PeerManagerAdapter>,
+        timeout: TimeDuration,
+        external_source: Option<Arc<dyn ExternalStateSource>>,
+    ) -> Self {
         StateSync {
             network_adapter,
             state_sync_time: Default::default(),
@@ -653,6 +663,7 @@ impl StateSync {
             timeout: Duration::from_std(timeout).unwrap(),
             state_parts_apply_results: HashMap::new(),
             split_state_roots: HashMap::new(),
+            external_source,
         }
     }
 
@@ -1086,6 +1097,23 @@ impl StateSync {
         shard_sync_download: ShardSyncDownload,
         highest_height_peers: &Vec<FullPeerInfo>,
     ) -> Result<ShardSyncDownload, near_chain::Error> {
+        let mut shard_sync_download = shard_sync_download;
+        // With an external source configured, only what the source failed to
+        // provide is requested from the peers.  The rest stays pending for the
+        // source to read on the next steps.
+        let failed_external_reads = self.external_source.as_ref().map(|source| {
+            Self::request_shard_from_external_source(
+                source.as_ref(),
+                shard_id,
+                chain,
+                sync_hash,
+                &mut shard_sync_download,
+            )
+        });
+        let request_from_peers = |download_id: usize| {
+            failed_external_reads.as_ref().map_or(true, |failed| failed.contains(&download_id))
+        };
+
         let possible_targets = self.possible_targets(
             me,
             shard_id,
@@ -1103,7 +1131,9 @@ impl StateSync {
         let mut new_shard_sync_download = shard_sync_download.clone();
 
         match shard_sync_download.status {
-            ShardSyncStatus::StateDownloadHeader => {
+            ShardSyncStatus::StateDownloadHeader
+                if !shard_sync_download.downloads[0].done && request_from_peers(0) =>
+            {
                 let target = possible_targets.choose(&mut thread_rng()).cloned().unwrap();
                 assert!(new_shard_sync_download.downloads[0].run_me.load(Ordering::SeqCst));
                 new_shard_sync_download.downloads[0].run_me.store(false, Ordering::SeqCst);
@@ -1139,7 +1169,9 @@ impl StateSync {
                     .downloads
                     .iter_mut()
                     .enumerate()
-                    .filter(|(_, download)| download.run_me.load(Ordering::SeqCst))
+                    .filter(|(part_id, download)| {
+                        download.run_me.load(Ordering::SeqCst) && request_from_peers(*part_id)
+                    })
                     .zip(possible_targets_sampler)
                 {
                     self.sent_request_part(target.clone(), part_id as u64, shard_id, sync_hash);
@@ -1177,6 +1209,94 @@ impl StateSync {
         Ok(new_shard_sync_download)
     }
 
+    /// Reads the header or the parts of the shard which need to be requested
+    /// from the external source, at most [`ExternalStateSource::max_part_reads_per_step`]
+    /// parts at a time.  Returns the indices of the downloads the source
+    /// doesn't have, failed to read or returned invalid, which are to be
+    /// requested from the peers instead.
+    fn request_shard_from_external_source(
+        source: &dyn ExternalStateSource,
+        shard_id: ShardId,
+        chain: &mut Chain,
+        sync_hash: CryptoHash,
+        shard_sync_download: &mut ShardSyncDownload,
+    ) -> HashSet<usize> {
+        let mut failed = HashSet::new();
+        match shard_sync_download.status {
+            ShardSyncStatus::StateDownloadHeader => {
+                let download = &mut shard_sync_download.downloads[0];
+                if download.done || !download.run_me.load(Ordering::SeqCst) {
+                    return failed;
+                }
+                let header = match source.get_header(shard_id, &sync_hash) {
+                    Ok(Some(header)) => header,
+                    Ok(None) => {
+                        failed.insert(0);
+                        return failed;
+                    }
+                    Err(err) => {
+                        warn!(target: "sync", shard_id, %sync_hash, ?err, "Failed to read state header from external source");
+                        failed.insert(0);
+                        return failed;
+                    }
+                };
+                match chain.set_state_header(shard_id, sync_hash, header) {
+                    Ok(()) => {
+                        debug!(target: "sync", shard_id, %sync_hash, "Got state header from external source");
+                        download.done = true;
+                        download.run_me.store(false, Ordering::SeqCst);
+                    }
+                    Err(err) => {
+                        error!(target: "sync", shard_id, %sync_hash, ?err, "Invalid state header from external source");
+                        failed.insert(0);
+                    }
+                }
+            }
+            ShardSyncStatus::StateDownloadParts => {
+                let num_parts = shard_sync_download.downloads.len() as u64;
+                for (part_id, download) in shard_sync_download
+                    .downloads
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(_, download)| {
+                        !download.done && download.run_me.load(Ordering::SeqCst)
+                    })
+                    .take(source.max_part_reads_per_step())
+                {
+                    let data = match source.get_part(shard_id, &sync_hash, part_id as u64) {
+                        Ok(Some(data)) => data,
+                        Ok(None) => {
+                            failed.insert(part_id);
+                            continue;
+                        }
+                        Err(err) => {
+                            warn!(target: "sync", shard_id, part_id, %sync_hash, ?err, "Failed to read state part from external source");
+                            failed.insert(part_id);
+                            continue;
+                        }
+                    };
+                    match chain.set_state_part(
+                        shard_id,
+                        sync_hash,
+                        PartId::new(part_id as u64, num_parts),
+                        &data,
+                    ) {
+                        Ok(()) => {
+                            download.done = true;
+                            download.run_me.store(false, Ordering::SeqCst);
+                        }
+                        Err(err) => {
+                            error!(target: "sync", shard_id, part_id, %sync_hash, ?err, "Invalid state part from external source");
+                            failed.insert(part_id);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+        failed
+    }
+
     pub fn run(
         &mut self,
         me: &Option<AccountId>,
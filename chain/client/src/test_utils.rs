@@ -1034,7 +1034,8 @@ pub fn setup_mock_all_validators(
                         | NetworkRequests::TxStatus(_, _, _)
                         | NetworkRequests::Query { .. }
                         | NetworkRequests::Challenge(_)
-                        | NetworkRequests::ReceiptOutComeRequest(_, _) => {}
+                        | NetworkRequests::ReceiptOutComeRequest(_, _)
+                        | NetworkRequests::SetValidators(_) => {}
                     };
                 }
                 Box::new(Some(resp))
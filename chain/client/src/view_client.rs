@@ -6,10 +6,12 @@ use near_primitives::time::Clock;
 use std::cmp::Ordering;
 use std::collections::{HashMap, VecDeque};
 use std::hash::Hash;
+use std::sync::atomic::{self, AtomicUsize};
 use std::sync::{Arc, Mutex, RwLock};
 use std::time::{Duration, Instant};
 
 use actix::{Actor, Addr, Handler, SyncArbiter, SyncContext};
+use futures::channel::oneshot;
 use tracing::{debug, error, info, trace, warn};
 
 use near_chain::types::ValidatorInfoIdentifier;
@@ -18,14 +20,16 @@ use near_chain::{
     RuntimeAdapter,
 };
 use near_chain_configs::{ClientConfig, ProtocolConfigView};
+use near_client_primitives::debug::{CheckpointInfo, CreateCheckpoint};
 use near_client_primitives::types::{
     Error, GetBlock, GetBlockError, GetBlockHash, GetBlockProof, GetBlockProofError,
     GetBlockProofResponse, GetBlockWithMerkleTree, GetChunkError, GetExecutionOutcome,
     GetExecutionOutcomeError, GetExecutionOutcomesForBlock, GetGasPrice, GetGasPriceError,
     GetNextLightClientBlockError, GetProtocolConfig, GetProtocolConfigError, GetReceipt,
     GetReceiptError, GetStateChangesError, GetStateChangesWithCauseInBlock,
-    GetStateChangesWithCauseInBlockForTrackedShards, GetValidatorInfoError, Query, QueryError,
-    TxStatus, TxStatusError,
+    GetStateChangesWithCauseInBlockForTrackedShards, GetTransactionTrace, GetTransactionTraceError,
+    GetValidatorInfoError, Query, QueryError, SimulateTransaction, SimulateTransactionError,
+    StatusError, TxStatus, TxStatusError,
 };
 use near_network::types::{NetworkRequests, PeerManagerAdapter, PeerManagerMessageRequest};
 #[cfg(feature = "test_features")]
@@ -44,8 +48,9 @@ use near_primitives::syncing::{
     ShardStateSyncResponse, ShardStateSyncResponseHeader, ShardStateSyncResponseV1,
     ShardStateSyncResponseV2,
 };
+use near_primitives::transaction::ExecutionOutcomeWithIdAndProof;
 use near_primitives::types::{
-    AccountId, BlockHeight, BlockId, BlockReference, EpochId, EpochReference, Finality,
+    AccountId, BlockHeight, BlockId, BlockReference, EpochId, EpochReference, Finality, Gas,
     MaybeBlockId, ShardId, TransactionOrReceiptId,
 };
 use near_primitives::views::validator_stake_view::ValidatorStakeView;
@@ -53,7 +58,7 @@ use near_primitives::views::{
     BlockView, ChunkView, EpochValidatorInfo, ExecutionOutcomeWithIdView,
     FinalExecutionOutcomeView, FinalExecutionOutcomeViewEnum, FinalExecutionStatus, GasPriceView,
     LightClientBlockView, QueryRequest, QueryResponse, ReceiptView, StateChangesKindsView,
-    StateChangesView,
+    StateChangesView, TransactionTraceView,
 };
 
 use crate::{
@@ -65,6 +70,8 @@ use crate::{
 const QUERY_REQUEST_LIMIT: usize = 500;
 /// Waiting time between requests, in ms
 const REQUEST_WAIT_TIME: u64 = 1000;
+/// Max number of receipts in a transaction trace.
+const MAX_TRANSACTION_TRACE_RECEIPTS: usize = 1000;
 
 const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
@@ -94,6 +101,8 @@ pub struct ViewClientActor {
     pub config: ClientConfig,
     request_manager: Arc<RwLock<ViewClientRequestManager>>,
     state_request_cache: Arc<Mutex<VecDeque<Instant>>>,
+    /// Number of transaction simulations running, shared by all view client threads.
+    running_simulations: Arc<AtomicUsize>,
 }
 
 impl ViewClientRequestManager {
@@ -111,6 +120,8 @@ impl ViewClientRequestManager {
 impl ViewClientActor {
     /// Maximum number of state requests allowed per `view_client_throttle_period`.
     const MAX_NUM_STATE_REQUESTS: usize = 30;
+    /// Maximum number of transaction simulations running at the same time.
+    const MAX_NUM_SIMULATIONS: usize = 4;
 
     pub fn new(
         validator_account_id: Option<AccountId>,
@@ -119,6 +130,7 @@ impl ViewClientActor {
         network_adapter: Arc<dyn PeerManagerAdapter>,
         config: ClientConfig,
         request_manager: Arc<RwLock<ViewClientRequestManager>>,
+        running_simulations: Arc<AtomicUsize>,
         adv: crate::adversarial::Controls,
     ) -> Result<Self, Error> {
         // TODO: should we create shared ChainStore that is passed to both Client and ViewClient?
@@ -137,6 +149,7 @@ impl ViewClientActor {
             config,
             request_manager,
             state_request_cache: Arc::new(Mutex::new(VecDeque::default())),
+            running_simulations,
         })
     }
 
@@ -963,6 +976,133 @@ impl Handler<GetReceipt> for ViewClientActor {
     }
 }
 
+impl Handler<GetTransactionTrace> for ViewClientActor {
+    type Result = Result<TransactionTraceView, GetTransactionTraceError>;
+
+    #[perf]
+    fn handle(&mut self, msg: GetTransactionTrace, _: &mut Self::Context) -> Self::Result {
+        self.chain
+            .get_transaction_trace(&msg.transaction_hash, MAX_TRANSACTION_TRACE_RECEIPTS)
+            .map_err(|err| err.into())
+    }
+}
+
+impl Handler<SimulateTransaction> for ViewClientActor {
+    type Result = Result<
+        oneshot::Receiver<Result<FinalExecutionOutcomeView, SimulateTransactionError>>,
+        SimulateTransactionError,
+    >;
+
+    /// Collects the state the transaction is simulated on and runs the simulation on a separate
+    /// thread, so that a long simulation doesn't hold up the view client.
+    #[perf]
+    fn handle(&mut self, msg: SimulateTransaction, _: &mut Self::Context) -> Self::Result {
+        let header = match msg.block_reference {
+            BlockReference::Finality(finality) => {
+                let block_hash = self.get_block_hash_by_finality(&finality)?;
+                self.chain.get_block_header(&block_hash)
+            }
+            BlockReference::BlockId(BlockId::Height(height)) => {
+                self.chain.get_header_by_height(height)
+            }
+            BlockReference::BlockId(BlockId::Hash(hash)) => self.chain.get_block_header(&hash),
+            BlockReference::SyncCheckpoint(sync_checkpoint) => {
+                if let Some(block_hash) =
+                    self.get_block_hash_by_sync_checkpoint(&sync_checkpoint)?
+                {
+                    self.chain.get_block_header(&block_hash)
+                } else {
+                    return Err(SimulateTransactionError::NotSyncedYet);
+                }
+            }
+        }?;
+        // The transaction is executed as if it was included into the next block, on top of the
+        // state after the given block.
+        let epoch_id = self.runtime_adapter.get_epoch_id_from_prev_block(header.hash())?;
+        if self.runtime_adapter.get_shard_layout(&epoch_id)?
+            != self.runtime_adapter.get_shard_layout(header.epoch_id())?
+        {
+            return Err(SimulateTransactionError::InternalError {
+                error_message: "Can't simulate transactions on top of the last block before \
+                                resharding"
+                    .to_string(),
+            });
+        }
+        // Only the shards tracked by the node can be used by the simulation.
+        let mut state_roots = HashMap::new();
+        let mut gas_limit = Gas::MAX;
+        for shard_id in 0..self.runtime_adapter.num_shards(header.epoch_id())? {
+            let shard_uid = self.runtime_adapter.shard_id_to_uid(shard_id, header.epoch_id())?;
+            match self.chain.get_chunk_extra(header.hash(), &shard_uid) {
+                Ok(chunk_extra) => {
+                    state_roots.insert(shard_id, *chunk_extra.state_root());
+                    gas_limit = gas_limit.min(chunk_extra.gas_limit());
+                }
+                Err(near_chain::Error::DBNotFoundErr(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+
+        let running_simulations = self.running_simulations.clone();
+        if running_simulations.fetch_add(1, atomic::Ordering::SeqCst) >= Self::MAX_NUM_SIMULATIONS {
+            running_simulations.fetch_sub(1, atomic::Ordering::SeqCst);
+            return Err(SimulateTransactionError::TooManyRequests);
+        }
+        let (sender, receiver) = oneshot::channel();
+        let runtime_adapter = self.runtime_adapter.clone();
+        let simulate = move || {
+            let outcomes = runtime_adapter.simulate_transaction(
+                &state_roots,
+                header.height() + 1,
+                header.raw_timestamp(),
+                header.hash(),
+                // There is no next block yet.
+                &CryptoHash::default(),
+                &epoch_id,
+                header.gas_price(),
+                *header.random_value(),
+                gas_limit,
+                &msg.transaction,
+                msg.verify_signature,
+            )?;
+            let mut outcomes: Vec<ExecutionOutcomeWithIdView> = outcomes
+                .into_iter()
+                .map(|outcome_with_id| {
+                    ExecutionOutcomeWithIdAndProof {
+                        proof: vec![],
+                        block_hash: *header.hash(),
+                        outcome_with_id,
+                    }
+                    .into()
+                })
+                .collect();
+            let status =
+                FinalExecutionStatus::from_outcomes(&msg.transaction.get_hash(), &outcomes);
+            let receipts_outcome = outcomes.split_off(1);
+            let transaction_outcome =
+                outcomes.pop().expect("transaction outcome is always present");
+            Ok(FinalExecutionOutcomeView {
+                status,
+                transaction: msg.transaction.into(),
+                transaction_outcome,
+                receipts_outcome,
+            })
+        };
+        let spawned = std::thread::Builder::new().name("simulate_tx".to_string()).spawn({
+            let running_simulations = running_simulations.clone();
+            move || {
+                let _ = sender.send(simulate());
+                running_simulations.fetch_sub(1, atomic::Ordering::SeqCst);
+            }
+        });
+        if let Err(err) = spawned {
+            running_simulations.fetch_sub(1, atomic::Ordering::SeqCst);
+            return Err(SimulateTransactionError::InternalError { error_message: err.to_string() });
+        }
+        Ok(receiver)
+    }
+}
+
 impl Handler<GetBlockProof> for ViewClientActor {
     type Result = Result<GetBlockProofResponse, GetBlockProofError>;
 
@@ -1357,6 +1497,31 @@ impl Handler<GetGasPrice> for ViewClientActor {
     }
 }
 
+impl Handler<CreateCheckpoint> for ViewClientActor {
+    type Result = Result<CheckpointInfo, StatusError>;
+
+    /// Creates a checkpoint of the database in a subdirectory of the configured checkpoint
+    /// directory named after the height of the last final block in the checkpoint.
+    #[perf]
+    fn handle(&mut self, _msg: CreateCheckpoint, _ctx: &mut Self::Context) -> Self::Result {
+        let checkpoint_dir = self.config.checkpoint_dir.as_ref().ok_or_else(|| {
+            StatusError::InternalError { error_message: "checkpoint_dir is not configured".into() }
+        })?;
+        let (path, manifest) = near_store::checkpoint::create_checkpoint_at_final_head(
+            self.chain.store().store(),
+            checkpoint_dir,
+        )
+        .map_err(|err| StatusError::InternalError { error_message: err.to_string() })?;
+        Ok(CheckpointInfo {
+            path,
+            head_height: manifest.head_height,
+            head_hash: manifest.head_hash,
+            final_head_height: manifest.final_head_height,
+            final_head_hash: manifest.final_head_hash,
+        })
+    }
+}
+
 /// Starts the View Client in a new arbiter (thread).
 pub fn start_view_client(
     validator_account_id: Option<AccountId>,
@@ -1367,6 +1532,7 @@ pub fn start_view_client(
     adv: crate::adversarial::Controls,
 ) -> Addr<ViewClientActor> {
     let request_manager = Arc::new(RwLock::new(ViewClientRequestManager::new()));
+    let running_simulations = Arc::new(AtomicUsize::new(0));
     SyncArbiter::start(config.view_client_threads, move || {
         // ViewClientActor::start_in_arbiter(&Arbiter::current(), move |_ctx| {
         let validator_account_id1 = validator_account_id.clone();
@@ -1381,6 +1547,7 @@ pub fn start_view_client(
             network_adapter1,
             config1,
             request_manager1,
+            running_simulations.clone(),
             adv.clone(),
         )
         .unwrap()
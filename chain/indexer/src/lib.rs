@@ -16,6 +16,9 @@ pub use near_indexer_primitives::{
 
 mod streamer;
 
+pub use streamer::cursor::Acknowledger;
+pub use streamer::filter::{AccountPattern, ActionKind, IndexerFilter};
+
 pub const INDEXER: &str = "indexer";
 
 /// Config wrapper to simplify signature and usage of `nearcore::init_configs`
@@ -53,7 +56,8 @@ pub struct InitConfigArgs {
 pub enum SyncModeEnum {
     /// Real-time syncing, always taking the latest finalized block to stream
     LatestSynced,
-    /// Starts syncing from the block NEAR Indexer was interrupted last time
+    /// Starts syncing right after the last acknowledged block, i.e. from the
+    /// block NEAR Indexer was interrupted last time
     FromInterruption,
     /// Specific block height to start syncing from
     BlockHeight(u64),
@@ -77,6 +81,18 @@ pub struct IndexerConfig {
     pub sync_mode: SyncModeEnum,
     /// Whether await for node to be synced or not
     pub await_for_node_synced: AwaitForNodeSyncedEnum,
+    /// Finality of the blocks to stream.  Blocks which aren’t final yet are
+    /// streamed sooner but after a chain reorganisation the blocks replacing
+    /// them are streamed as well, so listeners need to be prepared to see
+    /// a block at a height they’ve already seen.
+    pub finality: near_primitives::types::Finality,
+    /// Number of messages buffered for the listener.  When the buffer is full
+    /// the streamer stops fetching blocks until the listener catches up.  Must
+    /// be greater than zero.
+    pub buffer_size: usize,
+    /// Filter of the data included in streamed messages.  The default filter
+    /// includes everything.
+    pub filter: IndexerFilter,
 }
 
 /// This is the core component, which handles `nearcore` and internal `streamer`.
@@ -95,6 +111,10 @@ impl Indexer {
             "Load config from {}...",
             indexer_config.home_dir.display()
         );
+        anyhow::ensure!(
+            indexer_config.buffer_size > 0,
+            "Indexer buffer_size must be greater than zero"
+        );
 
         let near_config =
             nearcore::config::load_config(&indexer_config.home_dir, GenesisValidationMode::Full)
@@ -114,18 +134,38 @@ impl Indexer {
     }
 
     /// Boots up `near_indexer::streamer`, so it monitors the new blocks with chunks, transactions, receipts, and execution outcomes inside. The returned stream handler should be drained and handled on the user side.
+    ///
+    /// The stream ends if the data of a block can't be fetched after repeated attempts; see the logs for the reason.
+    ///
+    /// Messages are considered acknowledged as soon as they are handed over to the returned stream, so messages which haven't been handled by the time the indexer stops are not streamed again. Use [`Indexer::streamer_with_ack`] if they need to be.
     pub fn streamer(&self) -> mpsc::Receiver<StreamerMessage> {
-        let (sender, receiver) = mpsc::channel(100);
+        let (sender, receiver) = mpsc::channel(self.indexer_config.buffer_size);
         actix::spawn(streamer::start(
             self.view_client.clone(),
             self.client.clone(),
             self.indexer_config.clone(),
             self.near_config.config.store.clone(),
             sender,
+            None,
         ));
         receiver
     }
 
+    /// Boots up `near_indexer::streamer` like [`Indexer::streamer`] but messages are considered acknowledged only once the listener acknowledges them with the returned [`Acknowledger`]. With [`SyncModeEnum::FromInterruption`] streaming resumes after the last acknowledged message, so every message is delivered at least once even if the indexer stops while messages are still being handled.
+    pub fn streamer_with_ack(&self) -> (mpsc::Receiver<StreamerMessage>, Acknowledger) {
+        let (sender, receiver) = mpsc::channel(self.indexer_config.buffer_size);
+        let (acknowledger, acks) = Acknowledger::new();
+        actix::spawn(streamer::start(
+            self.view_client.clone(),
+            self.client.clone(),
+            self.indexer_config.clone(),
+            self.near_config.config.store.clone(),
+            sender,
+            Some(acks),
+        ));
+        (receiver, acknowledger)
+    }
+
     /// Expose neard config
     pub fn near_config(&self) -> &nearcore::NearConfig {
         &self.near_config
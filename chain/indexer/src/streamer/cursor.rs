@@ -0,0 +1,164 @@
+//! Position of the streamer persisted in the indexer database so that
+//! streaming can resume where it stopped after a restart.
+
+use std::sync::Arc;
+
+use rocksdb::DB;
+use tokio::sync::mpsc;
+use tracing::error;
+
+use near_indexer_primitives::StreamerMessage;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::views;
+
+use crate::INDEXER;
+
+/// Key under which the cursor is stored.
+const CURSOR_KEY: &[u8] = b"cursor";
+
+/// Key under which older versions of the indexer stored height of the last
+/// streamed block.  It’s only read when there’s no cursor yet.
+const LAST_SYNCED_BLOCK_HEIGHT_KEY: &[u8] = b"last_synced_block_height";
+
+/// Block of the last acknowledged message.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct Cursor {
+    pub block_height: BlockHeight,
+    pub block_hash: CryptoHash,
+}
+
+impl Cursor {
+    pub fn from_block(block: &views::BlockView) -> Self {
+        Self { block_height: block.header.height, block_hash: block.header.hash }
+    }
+}
+
+/// Position stored in the indexer database.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum StoredPosition {
+    Cursor(Cursor),
+    /// Height stored by an older version of the indexer.  The block at that
+    /// height may or may not have been delivered.
+    LastSyncedBlockHeight(BlockHeight),
+}
+
+pub(crate) fn read(db: &DB) -> anyhow::Result<Option<StoredPosition>> {
+    if let Some(value) = db.get(CURSOR_KEY)? {
+        return Ok(Some(StoredPosition::Cursor(serde_json::from_slice(&value)?)));
+    }
+    match db.get(LAST_SYNCED_BLOCK_HEIGHT_KEY)? {
+        Some(value) => {
+            let height = String::from_utf8(value)?.parse()?;
+            Ok(Some(StoredPosition::LastSyncedBlockHeight(height)))
+        }
+        None => Ok(None),
+    }
+}
+
+/// Stores the cursor.  The write is synced so a cursor which has been written
+/// survives a crash of the process or of the machine.
+pub(crate) fn write(db: &DB, cursor: &Cursor) -> anyhow::Result<()> {
+    let mut opts = rocksdb::WriteOptions::default();
+    opts.set_sync(true);
+    db.put_opt(CURSOR_KEY, serde_json::to_vec(cursor)?, &opts)?;
+    Ok(())
+}
+
+/// Handle for acknowledging messages received from
+/// [`crate::Indexer::streamer_with_ack`].
+///
+/// Streaming resumes after the last acknowledged message when the indexer is
+/// restarted with [`crate::SyncModeEnum::FromInterruption`].  Acknowledging
+/// a message implicitly acknowledges all messages streamed before it.
+#[derive(Clone, Debug)]
+pub struct Acknowledger(mpsc::UnboundedSender<Cursor>);
+
+impl Acknowledger {
+    pub(crate) fn new() -> (Self, mpsc::UnboundedReceiver<Cursor>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        (Self(sender), receiver)
+    }
+
+    /// Acknowledges that the message has been fully processed and doesn’t
+    /// need to be streamed again.
+    pub fn ack(&self, message: &StreamerMessage) {
+        // The receiver is gone only once the streamer has stopped in which
+        // case there’s nothing left to acknowledge.
+        let _ = self.0.send(Cursor::from_block(&message.block));
+    }
+}
+
+/// Persists cursors of acknowledged messages as they arrive.
+///
+/// Acknowledgements may arrive out of order, e.g. from different clones of
+/// the [`Acknowledger`], so a cursor lower than the one already persisted is
+/// ignored rather than moving the cursor backwards.
+pub(crate) async fn persist_acks(db: Arc<DB>, mut acks: mpsc::UnboundedReceiver<Cursor>) {
+    let mut persisted: Option<Cursor> = None;
+    while let Some(mut cursor) = acks.recv().await {
+        // Only the highest acknowledgement matters so skip any queued ones.
+        while let Ok(next) = acks.try_recv() {
+            if next.block_height >= cursor.block_height {
+                cursor = next;
+            }
+        }
+        if persisted.map_or(false, |persisted| cursor.block_height < persisted.block_height) {
+            continue;
+        }
+        match write(&db, &cursor) {
+            Ok(()) => persisted = Some(cursor),
+            Err(err) => error!(target: INDEXER, ?cursor, "Failed to persist cursor: {:#}", err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_write() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let db = DB::open_default(tmp_dir.path()).unwrap();
+        assert_eq!(read(&db).unwrap(), None);
+
+        db.put(LAST_SYNCED_BLOCK_HEIGHT_KEY, "42").unwrap();
+        assert_eq!(read(&db).unwrap(), Some(StoredPosition::LastSyncedBlockHeight(42)));
+
+        // The cursor takes precedence over the height of older versions.
+        let cursor = Cursor { block_height: 43, block_hash: CryptoHash::hash_bytes(b"43") };
+        write(&db, &cursor).unwrap();
+        assert_eq!(read(&db).unwrap(), Some(StoredPosition::Cursor(cursor)));
+
+        // The cursor survives reopening the database.
+        drop(db);
+        let db = DB::open_default(tmp_dir.path()).unwrap();
+        assert_eq!(read(&db).unwrap(), Some(StoredPosition::Cursor(cursor)));
+    }
+
+    #[test]
+    fn test_persist_acks() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(DB::open_default(tmp_dir.path()).unwrap());
+        let cursor = |height: BlockHeight| Cursor {
+            block_height: height,
+            block_hash: CryptoHash::hash_bytes(&height.to_le_bytes()),
+        };
+        let (acknowledger, acks) = Acknowledger::new();
+        actix::System::new().block_on(async {
+            let persisted = actix::spawn(persist_acks(db.clone(), acks));
+            acknowledger.0.send(cursor(5)).unwrap();
+            tokio::task::yield_now().await;
+            assert_eq!(read(&db).unwrap(), Some(StoredPosition::Cursor(cursor(5))));
+
+            // A late acknowledgement doesn’t move the cursor backwards.
+            acknowledger.0.send(cursor(3)).unwrap();
+            acknowledger.0.send(cursor(6)).unwrap();
+            acknowledger.0.send(cursor(4)).unwrap();
+            drop(acknowledger);
+            persisted.await.unwrap();
+        });
+        assert_eq!(read(&db).unwrap(), Some(StoredPosition::Cursor(cursor(6))));
+    }
+}
@@ -1,6 +1,6 @@
 //! Streamer watches the network and collects all the blocks and related chunks
 //! into one struct and pushes in in to the given queue
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use actix::Addr;
 use futures::stream::StreamExt;
@@ -11,6 +11,7 @@ use near_primitives::hash::CryptoHash;
 use near_primitives::{types, views};
 
 use super::errors::FailedToFetchData;
+use super::filter::IndexerFilter;
 use super::INDEXER;
 
 pub(crate) async fn fetch_status(
@@ -22,15 +23,14 @@ pub(crate) async fn fetch_status(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
-/// Fetches the status to retrieve `latest_block_height` to determine if we need to fetch
-/// entire block or we already fetched this block.
+/// Fetches the latest block of given finality to determine up to which height
+/// blocks need to be fetched.
 pub(crate) async fn fetch_latest_block(
     client: &Addr<near_client::ViewClientActor>,
+    finality: &types::Finality,
 ) -> Result<views::BlockView, FailedToFetchData> {
     client
-        .send(near_client::GetBlock(near_primitives::types::BlockReference::Finality(
-            near_primitives::types::Finality::Final,
-        )))
+        .send(near_client::GetBlock(types::BlockReference::Finality(finality.clone())))
         .await?
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
@@ -57,22 +57,40 @@ pub(crate) async fn fetch_block_by_hash(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
+/// Fetches the state changes of the shards matching the filter, without the
+/// ones which can't match it.
 pub(crate) async fn fetch_state_changes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
     epoch_id: near_primitives::types::EpochId,
+    filter: &IndexerFilter,
 ) -> Result<HashMap<near_primitives::types::ShardId, views::StateChangesView>, FailedToFetchData> {
-    client
+    let state_changes = client
         .send(near_client::GetStateChangesWithCauseInBlockForTrackedShards { block_hash, epoch_id })
         .await?
-        .map_err(|err| FailedToFetchData::String(err.to_string()))
+        .map_err(|err| FailedToFetchData::String(err.to_string()))?;
+    Ok(state_changes
+        .into_iter()
+        .filter(|(shard_id, _)| filter.matches_shard(*shard_id))
+        .map(|(shard_id, mut changes)| {
+            changes.retain(|change| filter.may_match_state_change(change));
+            (shard_id, changes)
+        })
+        .collect())
 }
 
 /// Fetch all ExecutionOutcomeWithId for current block
 /// Returns a HashMap where the key is shard id IndexerExecutionOutcomeWithOptionalReceipt
+///
+/// Outcomes of the transactions in `chunks` are all kept, since they are
+/// matched with the transactions by position.  Outcomes of receipts which
+/// don't match the filter are skipped, before the receipt is fetched if
+/// possible.
 pub(crate) async fn fetch_outcomes(
     client: &Addr<near_client::ViewClientActor>,
     block_hash: CryptoHash,
+    chunks: &[views::ChunkView],
+    filter: &IndexerFilter,
 ) -> Result<
     HashMap<near_primitives::types::ShardId, Vec<IndexerExecutionOutcomeWithOptionalReceipt>>,
     FailedToFetchData,
@@ -81,14 +99,29 @@ pub(crate) async fn fetch_outcomes(
         .send(near_client::GetExecutionOutcomesForBlock { block_hash })
         .await?
         .map_err(FailedToFetchData::String)?;
+    let transactions: HashSet<CryptoHash> =
+        chunks.iter().flat_map(|chunk| chunk.transactions.iter().map(|tx| tx.hash)).collect();
 
     let mut shard_execution_outcomes_with_receipts: HashMap<
         near_primitives::types::ShardId,
         Vec<IndexerExecutionOutcomeWithOptionalReceipt>,
     > = HashMap::new();
     for (shard_id, shard_outcomes) in outcomes {
+        if !filter.matches_shard(shard_id) {
+            continue;
+        }
         let mut outcomes_with_receipts: Vec<IndexerExecutionOutcomeWithOptionalReceipt> = vec![];
         for outcome in shard_outcomes {
+            if transactions.contains(&outcome.id) {
+                outcomes_with_receipts.push(IndexerExecutionOutcomeWithOptionalReceipt {
+                    execution_outcome: outcome,
+                    receipt: None,
+                });
+                continue;
+            }
+            if !filter.matches_receiver(&outcome.outcome.executor_id) {
+                continue;
+            }
             let receipt = match fetch_receipt_by_id(&client, outcome.id).await {
                 Ok(res) => res,
                 Err(e) => {
@@ -101,6 +134,10 @@ pub(crate) async fn fetch_outcomes(
                     None
                 }
             };
+            // Local receipts aren't stored, they are matched in `retain_matching`.
+            if receipt.as_ref().map_or(false, |receipt| !filter.matches_receipt(receipt)) {
+                continue;
+            }
             outcomes_with_receipts.push(IndexerExecutionOutcomeWithOptionalReceipt {
                 execution_outcome: outcome,
                 receipt,
@@ -134,16 +171,19 @@ async fn fetch_single_chunk(
         .map_err(|err| FailedToFetchData::String(err.to_string()))
 }
 
-/// Fetches all chunks belonging to given block.
+/// Fetches all chunks belonging to given block of the shards matching the filter.
 /// Includes transactions and receipts in custom struct (to provide more info).
 pub(crate) async fn fetch_block_chunks(
     client: &Addr<near_client::ViewClientActor>,
     block: &views::BlockView,
+    filter: &IndexerFilter,
 ) -> Result<Vec<views::ChunkView>, FailedToFetchData> {
     let mut futures: futures::stream::FuturesUnordered<_> = block
         .chunks
         .iter()
-        .filter(|chunk| chunk.height_included == block.header.height)
+        .filter(|chunk| {
+            chunk.height_included == block.header.height && filter.matches_shard(chunk.shard_id)
+        })
         .map(|chunk| fetch_single_chunk(&client, chunk.chunk_hash))
         .collect();
     let mut chunks = Vec::<views::ChunkView>::with_capacity(futures.len());
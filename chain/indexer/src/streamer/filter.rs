@@ -0,0 +1,455 @@
+//! Filter of the data included in streamed messages.
+
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use near_indexer_primitives::IndexerShard;
+use near_primitives::account::id::ParseAccountError;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::{AccountId, ShardId};
+use near_primitives::views;
+
+/// Pattern matching account ids.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum AccountPattern {
+    /// Matches any account.  Parsed from `*`.
+    Any,
+    /// Matches sub-accounts of given account at any depth but not the account
+    /// itself.  Parsed from `*.<account>`, e.g. `*.near`.
+    SubAccountsOf(AccountId),
+    /// Matches exactly given account.
+    Exact(AccountId),
+}
+
+impl AccountPattern {
+    pub fn matches(&self, account_id: &AccountId) -> bool {
+        match self {
+            Self::Any => true,
+            Self::SubAccountsOf(parent) => account_id
+                .as_str()
+                .strip_suffix(parent.as_str())
+                .map_or(false, |prefix| prefix.len() > 1 && prefix.ends_with('.')),
+            Self::Exact(exact) => exact == account_id,
+        }
+    }
+}
+
+impl FromStr for AccountPattern {
+    type Err = ParseAccountError;
+
+    fn from_str(pattern: &str) -> Result<Self, Self::Err> {
+        if pattern == "*" {
+            Ok(Self::Any)
+        } else if let Some(parent) = pattern.strip_prefix("*.") {
+            Ok(Self::SubAccountsOf(parent.parse()?))
+        } else {
+            Ok(Self::Exact(pattern.parse()?))
+        }
+    }
+}
+
+/// Kind of an action, i.e. [`views::ActionView`] without the data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ActionKind {
+    CreateAccount,
+    DeployContract,
+    FunctionCall,
+    Transfer,
+    Stake,
+    AddKey,
+    DeleteKey,
+    DeleteAccount,
+    Delegate,
+}
+
+impl From<&views::ActionView> for ActionKind {
+    fn from(action: &views::ActionView) -> Self {
+        match action {
+            views::ActionView::CreateAccount => Self::CreateAccount,
+            views::ActionView::DeployContract { .. } => Self::DeployContract,
+            views::ActionView::FunctionCall { .. } => Self::FunctionCall,
+            views::ActionView::Transfer { .. } => Self::Transfer,
+            views::ActionView::Stake { .. } => Self::Stake,
+            views::ActionView::AddKey { .. } => Self::AddKey,
+            views::ActionView::DeleteKey { .. } => Self::DeleteKey,
+            views::ActionView::DeleteAccount { .. } => Self::DeleteAccount,
+            views::ActionView::Delegate { .. } => Self::Delegate,
+        }
+    }
+}
+
+/// Filter of the data included in `StreamerMessage`s.
+///
+/// Every block is streamed regardless of the filter; the filter only limits
+/// which shards, transactions, receipts, execution outcomes and state changes
+/// are included in the message.  Criteria which are `None` match everything
+/// and the default filter lets all the data through.
+#[derive(Debug, Clone, Default)]
+pub struct IndexerFilter {
+    /// Shards to include.  Data of other shards isn’t even fetched from the
+    /// node.
+    pub shard_ids: Option<HashSet<ShardId>>,
+    /// Patterns one of which the receiver of a transaction or receipt must
+    /// match.
+    pub receiver_ids: Option<Vec<AccountPattern>>,
+    /// Patterns one of which the signer of a transaction or the predecessor of
+    /// a receipt must match.
+    pub predecessor_ids: Option<Vec<AccountPattern>>,
+    /// Kinds of actions one of which a transaction or receipt must contain.
+    /// Data receipts contain no actions so they never match.
+    pub action_kinds: Option<HashSet<ActionKind>>,
+}
+
+impl IndexerFilter {
+    pub(crate) fn matches_shard(&self, shard_id: ShardId) -> bool {
+        self.shard_ids.as_ref().map_or(true, |shard_ids| shard_ids.contains(&shard_id))
+    }
+
+    /// Whether the filter limits transactions and receipts within a shard.
+    fn filters_items(&self) -> bool {
+        self.receiver_ids.is_some() || self.predecessor_ids.is_some() || self.action_kinds.is_some()
+    }
+
+    /// Whether a transaction or receipt with given receiver may match the
+    /// filter.  The receiver of a receipt is the executor of its outcome, so
+    /// this can be checked before the receipt is fetched.
+    pub(crate) fn matches_receiver(&self, receiver_id: &AccountId) -> bool {
+        matches_any(&self.receiver_ids, receiver_id)
+    }
+
+    fn matches_item(
+        &self,
+        predecessor_id: &AccountId,
+        receiver_id: &AccountId,
+        actions: &[views::ActionView],
+    ) -> bool {
+        self.matches_receiver(receiver_id)
+            && matches_any(&self.predecessor_ids, predecessor_id)
+            && self.action_kinds.as_ref().map_or(true, |kinds| {
+                actions.iter().any(|action| kinds.contains(&ActionKind::from(action)))
+            })
+    }
+
+    fn matches_transaction(&self, transaction: &views::SignedTransactionView) -> bool {
+        self.matches_item(&transaction.signer_id, &transaction.receiver_id, &transaction.actions)
+    }
+
+    pub(crate) fn matches_receipt(&self, receipt: &views::ReceiptView) -> bool {
+        let actions = match &receipt.receipt {
+            views::ReceiptEnumView::Action { actions, .. } => actions.as_slice(),
+            views::ReceiptEnumView::Data { .. } => &[],
+        };
+        self.matches_item(&receipt.predecessor_id, &receipt.receiver_id, actions)
+    }
+
+    /// Whether a state change with a cause other than a transaction or
+    /// receipt, e.g. validator rewards, matches the filter: its account must
+    /// match `receiver_ids`.
+    fn matches_unattributed_state_change(&self, change: &views::StateChangeWithCauseView) -> bool {
+        self.receiver_ids.as_ref().map_or(false, |patterns| {
+            let account_id = state_change_account_id(&change.value);
+            patterns.iter().any(|pattern| pattern.matches(account_id))
+        })
+    }
+
+    /// Whether a state change may be kept by [`Self::retain_matching`].  For
+    /// the ones caused by transactions and receipts it’s decided there.
+    pub(crate) fn may_match_state_change(&self, change: &views::StateChangeWithCauseView) -> bool {
+        !self.filters_items()
+            || state_change_cause_id(&change.cause).is_some()
+            || self.matches_unattributed_state_change(change)
+    }
+
+    /// Removes transactions, receipts, execution outcomes and state changes
+    /// which don’t match the filter from the shard.
+    ///
+    /// A state change is kept if it was caused by a transaction or receipt
+    /// which is kept.  State changes with other causes, e.g. validator
+    /// rewards, are kept only if their account matches `receiver_ids`.
+    ///
+    /// The fetchers already skip the receipts, outcomes and state changes
+    /// which can’t match, but the rest can only be decided on the whole shard
+    /// because outcomes of local receipts are matched with the transactions
+    /// which created them, regardless of whether the transactions are kept.
+    pub(crate) fn retain_matching(&self, shard: &mut IndexerShard) {
+        if !self.filters_items() {
+            return;
+        }
+        let mut kept = HashSet::<CryptoHash>::new();
+        if let Some(chunk) = &mut shard.chunk {
+            chunk.transactions.retain(|tx| self.matches_transaction(&tx.transaction));
+            chunk.receipts.retain(|receipt| self.matches_receipt(receipt));
+            kept.extend(chunk.transactions.iter().map(|tx| tx.transaction.hash));
+            kept.extend(chunk.receipts.iter().map(|receipt| receipt.receipt_id));
+        }
+        shard.receipt_execution_outcomes.retain(|outcome| self.matches_receipt(&outcome.receipt));
+        kept.extend(
+            shard.receipt_execution_outcomes.iter().map(|outcome| outcome.receipt.receipt_id),
+        );
+        shard.state_changes.retain(|change| match state_change_cause_id(&change.cause) {
+            Some(id) => kept.contains(id),
+            None => self.matches_unattributed_state_change(change),
+        });
+    }
+}
+
+fn matches_any(patterns: &Option<Vec<AccountPattern>>, account_id: &AccountId) -> bool {
+    patterns
+        .as_ref()
+        .map_or(true, |patterns| patterns.iter().any(|pattern| pattern.matches(account_id)))
+}
+
+/// Hash of the transaction or id of the receipt which caused a state change.
+fn state_change_cause_id(cause: &views::StateChangeCauseView) -> Option<&CryptoHash> {
+    match cause {
+        views::StateChangeCauseView::TransactionProcessing { tx_hash } => Some(tx_hash),
+        views::StateChangeCauseView::ActionReceiptProcessingStarted { receipt_hash }
+        | views::StateChangeCauseView::ActionReceiptGasReward { receipt_hash }
+        | views::StateChangeCauseView::ReceiptProcessing { receipt_hash }
+        | views::StateChangeCauseView::PostponedReceipt { receipt_hash } => Some(receipt_hash),
+        _ => None,
+    }
+}
+
+fn state_change_account_id(value: &views::StateChangeValueView) -> &AccountId {
+    match value {
+        views::StateChangeValueView::AccountUpdate { account_id, .. }
+        | views::StateChangeValueView::AccountDeletion { account_id, .. }
+        | views::StateChangeValueView::AccessKeyUpdate { account_id, .. }
+        | views::StateChangeValueView::AccessKeyDeletion { account_id, .. }
+        | views::StateChangeValueView::DataUpdate { account_id, .. }
+        | views::StateChangeValueView::DataDeletion { account_id, .. }
+        | views::StateChangeValueView::ContractCodeUpdate { account_id, .. }
+        | views::StateChangeValueView::ContractCodeDeletion { account_id, .. } => account_id,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{EmptySigner, KeyType, PublicKey};
+    use near_indexer_primitives::{
+        IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt,
+        IndexerExecutionOutcomeWithReceipt, IndexerTransactionWithOutcome,
+    };
+    use near_primitives::sharding::{ShardChunkHeader, ShardChunkHeaderV1};
+    use near_primitives::transaction::{
+        ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
+    };
+    use near_primitives::validator_signer::EmptyValidatorSigner;
+
+    fn account(account_id: &str) -> AccountId {
+        account_id.parse().unwrap()
+    }
+
+    fn outcome(id: CryptoHash) -> views::ExecutionOutcomeWithIdView {
+        ExecutionOutcomeWithIdAndProof {
+            outcome_with_id: ExecutionOutcomeWithId { id, ..Default::default() },
+            ..Default::default()
+        }
+        .into()
+    }
+
+    fn transaction(
+        nonce: u64,
+        signer_id: &str,
+        receiver_id: &str,
+    ) -> IndexerTransactionWithOutcome {
+        let transaction: views::SignedTransactionView = SignedTransaction::send_money(
+            nonce,
+            account(signer_id),
+            account(receiver_id),
+            &EmptySigner {},
+            1,
+            CryptoHash::default(),
+        )
+        .into();
+        let outcome = IndexerExecutionOutcomeWithOptionalReceipt {
+            execution_outcome: outcome(transaction.hash),
+            receipt: None,
+        };
+        IndexerTransactionWithOutcome { transaction, outcome }
+    }
+
+    fn receipt(n: u8, predecessor_id: &str, receiver_id: &str) -> views::ReceiptView {
+        views::ReceiptView {
+            predecessor_id: account(predecessor_id),
+            receiver_id: account(receiver_id),
+            receipt_id: CryptoHash::hash_bytes(&[n]),
+            receipt: views::ReceiptEnumView::Action {
+                signer_id: account(predecessor_id),
+                signer_public_key: PublicKey::empty(KeyType::ED25519),
+                gas_price: 0,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions: vec![views::ActionView::Transfer { deposit: 1 }],
+            },
+        }
+    }
+
+    fn state_change(
+        cause: views::StateChangeCauseView,
+        account_id: &str,
+    ) -> views::StateChangeWithCauseView {
+        views::StateChangeWithCauseView {
+            cause,
+            value: views::StateChangeValueView::AccountDeletion { account_id: account(account_id) },
+        }
+    }
+
+    fn chunk_header() -> views::ChunkHeaderView {
+        ShardChunkHeader::V1(ShardChunkHeaderV1::new(
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            CryptoHash::default(),
+            0,
+            1,
+            0,
+            0,
+            0,
+            0,
+            CryptoHash::default(),
+            CryptoHash::default(),
+            vec![],
+            &EmptyValidatorSigner::default(),
+        ))
+        .into()
+    }
+
+    #[test]
+    fn test_retain_matching() {
+        let transactions = vec![
+            transaction(1, "alice.near", "app.near"),
+            transaction(2, "alice.near", "bob.near"),
+        ];
+        let receipts =
+            vec![receipt(1, "alice.near", "app.near"), receipt(2, "bob.near", "alice.near")];
+        let make_shard = || IndexerShard {
+            shard_id: 0,
+            chunk: Some(IndexerChunkView {
+                author: account("test"),
+                header: chunk_header(),
+                transactions: transactions.clone(),
+                receipts: receipts.clone(),
+            }),
+            receipt_execution_outcomes: receipts
+                .iter()
+                .map(|receipt| IndexerExecutionOutcomeWithReceipt {
+                    execution_outcome: outcome(receipt.receipt_id),
+                    receipt: receipt.clone(),
+                })
+                .collect(),
+            state_changes: vec![
+                state_change(
+                    views::StateChangeCauseView::TransactionProcessing {
+                        tx_hash: transactions[0].transaction.hash,
+                    },
+                    "alice.near",
+                ),
+                state_change(
+                    views::StateChangeCauseView::TransactionProcessing {
+                        tx_hash: transactions[1].transaction.hash,
+                    },
+                    "alice.near",
+                ),
+                state_change(
+                    views::StateChangeCauseView::ReceiptProcessing {
+                        receipt_hash: receipts[1].receipt_id,
+                    },
+                    "alice.near",
+                ),
+                state_change(views::StateChangeCauseView::ValidatorAccountsUpdate, "app.near"),
+                state_change(views::StateChangeCauseView::ValidatorAccountsUpdate, "bob.near"),
+            ],
+        };
+        let retained = |filter: &IndexerFilter| {
+            let mut shard = make_shard();
+            filter.retain_matching(&mut shard);
+            let chunk = shard.chunk.unwrap();
+            (
+                chunk.transactions.iter().map(|tx| tx.transaction.hash).collect::<Vec<_>>(),
+                chunk.receipts.iter().map(|receipt| receipt.receipt_id).collect::<Vec<_>>(),
+                shard
+                    .receipt_execution_outcomes
+                    .iter()
+                    .map(|outcome| outcome.execution_outcome.id)
+                    .collect::<Vec<_>>(),
+                shard.state_changes.len(),
+            )
+        };
+        let tx_hash = |i: usize| transactions[i].transaction.hash;
+        let receipt_id = |i: usize| receipts[i].receipt_id;
+
+        // The default filter keeps everything.
+        assert_eq!(
+            retained(&IndexerFilter::default()),
+            (
+                vec![tx_hash(0), tx_hash(1)],
+                vec![receipt_id(0), receipt_id(1)],
+                vec![receipt_id(0), receipt_id(1)],
+                5
+            )
+        );
+        // State changes follow their transactions and receipts, the others
+        // are matched by account.
+        let filter = IndexerFilter {
+            receiver_ids: Some(vec![AccountPattern::Exact(account("app.near"))]),
+            ..Default::default()
+        };
+        assert_eq!(
+            retained(&filter),
+            (vec![tx_hash(0)], vec![receipt_id(0)], vec![receipt_id(0)], 2)
+        );
+        let filter = IndexerFilter {
+            predecessor_ids: Some(vec![AccountPattern::Exact(account("bob.near"))]),
+            ..Default::default()
+        };
+        assert_eq!(retained(&filter), (vec![], vec![receipt_id(1)], vec![receipt_id(1)], 1));
+        let filter = IndexerFilter {
+            action_kinds: Some([ActionKind::FunctionCall].into_iter().collect()),
+            ..Default::default()
+        };
+        assert_eq!(retained(&filter), (vec![], vec![], vec![], 0));
+        let filter = IndexerFilter {
+            action_kinds: Some([ActionKind::Transfer].into_iter().collect()),
+            ..Default::default()
+        };
+        assert_eq!(
+            retained(&filter),
+            (
+                vec![tx_hash(0), tx_hash(1)],
+                vec![receipt_id(0), receipt_id(1)],
+                vec![receipt_id(0), receipt_id(1)],
+                3
+            )
+        );
+
+        // Only the state changes which can't be matched are skipped early.
+        let filter = IndexerFilter {
+            receiver_ids: Some(vec![AccountPattern::Exact(account("app.near"))]),
+            ..Default::default()
+        };
+        let shard = make_shard();
+        let changes =
+            shard.state_changes.iter().filter(|change| filter.may_match_state_change(change));
+        assert_eq!(changes.count(), 4);
+    }
+
+    #[test]
+    fn test_account_pattern() {
+        let account = |account_id: &str| account_id.parse::<AccountId>().unwrap();
+        let pattern = |pattern: &str| pattern.parse::<AccountPattern>().unwrap();
+
+        assert!(pattern("*").matches(&account("near")));
+        assert!(pattern("alice.near").matches(&account("alice.near")));
+        assert!(!pattern("alice.near").matches(&account("bob.near")));
+        assert!(pattern("*.near").matches(&account("alice.near")));
+        assert!(pattern("*.near").matches(&account("app.alice.near")));
+        assert!(!pattern("*.near").matches(&account("near")));
+        assert!(!pattern("*.near").matches(&account("aurora")));
+        assert!(!pattern("*.alice.near").matches(&account("malice.near")));
+        assert!("*.".parse::<AccountPattern>().is_err());
+        assert!("Alice".parse::<AccountPattern>().is_err());
+    }
+}
@@ -1,11 +1,13 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use actix::Addr;
 use async_recursion::async_recursion;
+use futures::future::LocalBoxFuture;
 use rocksdb::DB;
 use tokio::sync::mpsc;
 use tokio::time;
-use tracing::{debug, info};
+use tracing::{debug, error, info, warn};
 
 use near_indexer_primitives::{
     IndexerChunkView, IndexerExecutionOutcomeWithOptionalReceipt,
@@ -13,25 +15,37 @@ use near_indexer_primitives::{
     StreamerMessage,
 };
 use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
 use near_primitives::views;
 
-use crate::{AwaitForNodeSyncedEnum, IndexerConfig};
+use crate::{AwaitForNodeSyncedEnum, IndexerConfig, SyncModeEnum};
 
+use self::cursor::{Cursor, StoredPosition};
 use self::errors::FailedToFetchData;
 use self::fetchers::{
     fetch_block_by_hash, fetch_block_by_height, fetch_block_chunks, fetch_latest_block,
     fetch_outcomes, fetch_state_changes, fetch_status,
 };
+use self::filter::IndexerFilter;
 use self::utils::convert_transactions_sir_into_local_receipts;
 use crate::streamer::fetchers::fetch_protocol_config;
 use crate::INDEXER;
 
+pub(crate) mod cursor;
 mod errors;
 mod fetchers;
+pub(crate) mod filter;
 mod utils;
 
 const INTERVAL: Duration = Duration::from_millis(500);
 
+/// Number of attempts to build the message for a block before the streamer
+/// gives up.
+const MAX_BUILD_ATTEMPTS: u32 = 10;
+
+/// Longest delay between attempts to build the message for a block.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(60);
+
 /// Blocks #47317863 and #47317864 with restored receipts.
 const PROBLEMATIC_BLOKS: [CryptoHash; 2] = [
     CryptoHash(
@@ -68,18 +82,20 @@ fn test_problematic_blocks_hash() {
 async fn build_streamer_message(
     client: &Addr<near_client::ViewClientActor>,
     block: views::BlockView,
+    filter: &IndexerFilter,
 ) -> Result<StreamerMessage, FailedToFetchData> {
-    let chunks = fetch_block_chunks(&client, &block).await?;
+    let chunks = fetch_block_chunks(&client, &block, filter).await?;
 
     let protocol_config_view = fetch_protocol_config(&client, block.header.hash).await?;
     let num_shards = protocol_config_view.num_block_producer_seats_per_shard.len()
         as near_primitives::types::NumShards;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(client, block.header.hash, &chunks, filter).await?;
     let mut state_changes = fetch_state_changes(
         &client,
         block.header.hash,
         near_primitives::types::EpochId(block.header.epoch_id.clone()),
+        filter,
     )
     .await?;
     let mut indexer_shards = (0..num_shards)
@@ -167,6 +183,7 @@ async fn build_streamer_message(
                         &protocol_config_view,
                         prev_block,
                         execution_outcome.id,
+                        filter,
                     )
                     .await?
                     {
@@ -227,6 +244,11 @@ async fn build_streamer_message(
         )
     }
 
+    indexer_shards.retain(|shard| filter.matches_shard(shard.shard_id));
+    for shard in &mut indexer_shards {
+        filter.retain_matching(shard);
+    }
+
     Ok(StreamerMessage { block, shards: indexer_shards })
 }
 
@@ -237,10 +259,11 @@ async fn find_local_receipt_by_id_in_block(
     protocol_config_view: &near_chain_configs::ProtocolConfigView,
     block: views::BlockView,
     receipt_id: near_primitives::hash::CryptoHash,
+    filter: &IndexerFilter,
 ) -> Result<Option<views::ReceiptView>, FailedToFetchData> {
-    let chunks = fetch_block_chunks(&client, &block).await?;
+    let chunks = fetch_block_chunks(&client, &block, filter).await?;
 
-    let mut shards_outcomes = fetch_outcomes(&client, block.header.hash).await?;
+    let mut shards_outcomes = fetch_outcomes(client, block.header.hash, &chunks, filter).await?;
 
     for chunk in chunks {
         let views::ChunkView { header, transactions, .. } = chunk;
@@ -275,9 +298,105 @@ async fn find_local_receipt_by_id_in_block(
     Ok(None)
 }
 
+/// Blocks streamed and the data needed to build their messages: the node in
+/// production and a fake chain in tests.
+trait BlockSource {
+    /// Whether the node is still syncing, per `await_for_node_synced`.
+    fn is_syncing(&self) -> LocalBoxFuture<'_, Result<bool, FailedToFetchData>>;
+    /// The latest block of the configured finality.
+    fn latest_block(&self) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>>;
+    /// Block at given height on the canonical chain.
+    fn block_by_height(
+        &self,
+        height: BlockHeight,
+    ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>>;
+    fn block_by_hash(
+        &self,
+        hash: CryptoHash,
+    ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>>;
+    fn build_message(
+        &self,
+        block: views::BlockView,
+    ) -> LocalBoxFuture<'_, Result<StreamerMessage, FailedToFetchData>>;
+}
+
+struct Node {
+    view_client: Addr<near_client::ViewClientActor>,
+    client: Addr<near_client::ClientActor>,
+    indexer_config: IndexerConfig,
+}
+
+impl BlockSource for Node {
+    fn is_syncing(&self) -> LocalBoxFuture<'_, Result<bool, FailedToFetchData>> {
+        Box::pin(async move {
+            match self.indexer_config.await_for_node_synced {
+                AwaitForNodeSyncedEnum::WaitForFullSync => {
+                    Ok(fetch_status(&self.client).await?.sync_info.syncing)
+                }
+                AwaitForNodeSyncedEnum::StreamWhileSyncing => Ok(false),
+            }
+        })
+    }
+
+    fn latest_block(&self) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+        Box::pin(fetch_latest_block(&self.view_client, &self.indexer_config.finality))
+    }
+
+    fn block_by_height(
+        &self,
+        height: BlockHeight,
+    ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+        Box::pin(fetch_block_by_height(&self.view_client, height))
+    }
+
+    fn block_by_hash(
+        &self,
+        hash: CryptoHash,
+    ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+        Box::pin(fetch_block_by_hash(&self.view_client, hash))
+    }
+
+    fn build_message(
+        &self,
+        block: views::BlockView,
+    ) -> LocalBoxFuture<'_, Result<StreamerMessage, FailedToFetchData>> {
+        Box::pin(build_streamer_message(&self.view_client, block, &self.indexer_config.filter))
+    }
+}
+
+/// Returns the most recent block on the canonical chain among the block with
+/// given hash and its ancestors.
+async fn find_canonical_ancestor(
+    source: &impl BlockSource,
+    mut block_hash: CryptoHash,
+) -> Result<Cursor, FailedToFetchData> {
+    loop {
+        let block = source.block_by_hash(block_hash).await?;
+        if let Ok(canonical) = source.block_by_height(block.header.height).await {
+            if canonical.header.hash == block_hash {
+                return Ok(Cursor::from_block(&block));
+            }
+        }
+        block_hash = block.header.prev_hash;
+    }
+}
+
 /// Function that starts Streamer's busy loop. Every half a seconds it fetches the status
 /// compares to already fetched block height and in case it differs fetches new block of given height.
 ///
+/// Before a block is fetched a slot for its message is reserved in `blocks_sink` so
+/// the streamer doesn’t run ahead of the listener by more than the capacity of the
+/// channel.  Each block is checked to be a child of the block streamed before it;
+/// if it isn’t, the chain got reorganised and streaming resumes right after the
+/// most recent block still on the canonical chain.
+///
+/// Building a message is retried with increasing delays.  If it fails
+/// [`MAX_BUILD_ATTEMPTS`] times in a row the streamer stops, which closes
+/// `blocks_sink`.
+///
+/// If `acks` is given, the cursor is persisted as messages are acknowledged through
+/// it; otherwise it’s persisted as soon as a message is handed over to `blocks_sink`.
+///
 /// We have to pass `client: Addr<near_client::ClientActor>` and `view_client: Addr<near_client::ViewClientActor>`.
 pub(crate) async fn start(
     view_client: Addr<near_client::ViewClientActor>,
@@ -285,6 +404,7 @@ pub(crate) async fn start(
     indexer_config: IndexerConfig,
     store_config: near_store::StoreConfig,
     blocks_sink: mpsc::Sender<StreamerMessage>,
+    acks: Option<mpsc::UnboundedReceiver<Cursor>>,
 ) {
     info!(target: INDEXER, "Starting Streamer...");
     let indexer_db_path = near_store::Store::opener(&indexer_config.home_dir, &store_config)
@@ -292,79 +412,346 @@ pub(crate) async fn start(
         .join("indexer");
 
     // TODO: implement proper error handling
-    let db = DB::open_default(indexer_db_path).unwrap();
-    let mut last_synced_block_height: Option<near_primitives::types::BlockHeight> = None;
+    let db = Arc::new(DB::open_default(indexer_db_path).unwrap());
+    let auto_ack = match acks {
+        Some(acks) => {
+            actix::spawn(cursor::persist_acks(db.clone(), acks));
+            false
+        }
+        None => true,
+    };
+    let sync_mode = indexer_config.sync_mode.clone();
+    let node = Node { view_client, client, indexer_config };
+    stream(node, sync_mode, db, blocks_sink, auto_ack).await
+}
+
+async fn stream(
+    source: impl BlockSource,
+    sync_mode: SyncModeEnum,
+    db: Arc<DB>,
+    blocks_sink: mpsc::Sender<StreamerMessage>,
+    auto_ack: bool,
+) {
+    // Block of the last message sent to the listener or, until the first message
+    // is sent, of the message acknowledged before the restart.
+    let mut last_sent: Option<Cursor> = None;
+    let mut next_block_height: Option<BlockHeight> = None;
+    // Failed attempts to build the message for `next_block_height`.
+    let mut failed_attempts = 0;
 
     'main: loop {
         time::sleep(INTERVAL).await;
-        match indexer_config.await_for_node_synced {
-            AwaitForNodeSyncedEnum::WaitForFullSync => {
-                let status = fetch_status(&client).await;
-                if let Ok(status) = status {
-                    if status.sync_info.syncing {
-                        continue;
-                    }
-                }
-            }
-            AwaitForNodeSyncedEnum::StreamWhileSyncing => {}
-        };
+        if let Ok(true) = source.is_syncing().await {
+            continue;
+        }
 
-        let block = if let Ok(block) = fetch_latest_block(&view_client).await {
+        let block = if let Ok(block) = source.latest_block().await {
             block
         } else {
             continue;
         };
 
         let latest_block_height = block.header.height;
-        let start_syncing_block_height = if let Some(last_synced_block_height) =
-            last_synced_block_height
-        {
-            last_synced_block_height + 1
-        } else {
-            match indexer_config.sync_mode {
-                crate::SyncModeEnum::FromInterruption => {
-                    match db.get(b"last_synced_block_height").unwrap() {
-                        Some(value) => String::from_utf8(value).unwrap().parse::<u64>().unwrap(),
-                        None => latest_block_height,
+        if next_block_height.is_none() {
+            next_block_height = Some(match sync_mode {
+                SyncModeEnum::FromInterruption => match cursor::read(&db).unwrap() {
+                    Some(StoredPosition::Cursor(cursor)) => {
+                        last_sent = Some(cursor);
+                        cursor.block_height + 1
                     }
-                }
-                crate::SyncModeEnum::LatestSynced => latest_block_height,
-                crate::SyncModeEnum::BlockHeight(height) => height,
-            }
-        };
+                    Some(StoredPosition::LastSyncedBlockHeight(height)) => height,
+                    None => latest_block_height,
+                },
+                SyncModeEnum::LatestSynced => latest_block_height,
+                SyncModeEnum::BlockHeight(height) => height,
+            });
+        }
 
         debug!(
             target: INDEXER,
-            "Streaming is about to start from block #{} and the latest block is #{}",
-            start_syncing_block_height,
+            "Streaming is about to start from block #{:?} and the latest block is #{}",
+            next_block_height,
             latest_block_height
         );
-        for block_height in start_syncing_block_height..=latest_block_height {
-            if let Ok(block) = fetch_block_by_height(&view_client, block_height).await {
-                let response = build_streamer_message(&view_client, block).await;
-
-                match response {
-                    Ok(streamer_message) => {
-                        debug!(target: INDEXER, "{:#?}", &streamer_message);
-                        if blocks_sink.send(streamer_message).await.is_err() {
-                            info!(
+        while let Some(block_height) =
+            next_block_height.filter(|height| *height <= latest_block_height)
+        {
+            let permit = match blocks_sink.reserve().await {
+                Ok(permit) => permit,
+                Err(_) => {
+                    info!(
+                        target: INDEXER,
+                        "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
+                    );
+                    break 'main;
+                }
+            };
+            let block = match source.block_by_height(block_height).await {
+                Ok(block) => block,
+                Err(_) => {
+                    // There is no block at this height.
+                    next_block_height = Some(block_height + 1);
+                    continue;
+                }
+            };
+
+            if let Some(last) = last_sent.filter(|last| last.block_hash != block.header.prev_hash) {
+                // Either the last streamed block is no longer on the canonical chain
+                // or fetching a block in between failed.  Either way, resume after
+                // the most recent canonical block.
+                match find_canonical_ancestor(&source, last.block_hash).await {
+                    Ok(ancestor) => {
+                        if ancestor != last {
+                            warn!(
                                 target: INDEXER,
-                                "Unable to send StreamerMessage to listener, listener doesn't listen. terminating..."
+                                "Chain reorganised; rewinding from block #{} to #{}",
+                                last.block_height,
+                                ancestor.block_height
                             );
-                            break 'main;
                         }
+                        last_sent = Some(ancestor);
+                        next_block_height = Some(ancestor.block_height + 1);
                     }
                     Err(err) => {
-                        debug!(
+                        warn!(
                             target: INDEXER,
-                            "Missing data, skipping block #{}...", block_height
+                            "Unable to find canonical ancestor of block {}; restreaming from #{}: {:?}",
+                            last.block_hash,
+                            last.block_height,
+                            err
                         );
-                        debug!(target: INDEXER, "{:#?}", err);
+                        last_sent = None;
+                        next_block_height = Some(last.block_height);
                     }
                 }
+                failed_attempts = 0;
+                continue;
+            }
+
+            let streamer_message = match source.build_message(block).await {
+                Ok(streamer_message) => streamer_message,
+                Err(err) => {
+                    failed_attempts += 1;
+                    if failed_attempts >= MAX_BUILD_ATTEMPTS {
+                        error!(
+                            target: INDEXER,
+                            "Failed to build StreamerMessage for block #{} {} times, terminating: {:?}",
+                            block_height,
+                            failed_attempts,
+                            err
+                        );
+                        break 'main;
+                    }
+                    debug!(target: INDEXER, "Missing data, retrying block #{}...", block_height);
+                    debug!(target: INDEXER, "{:#?}", err);
+                    time::sleep(retry_delay(failed_attempts)).await;
+                    continue 'main;
+                }
+            };
+            debug!(target: INDEXER, "{:#?}", &streamer_message);
+            let cursor = Cursor::from_block(&streamer_message.block);
+            permit.send(streamer_message);
+            if auto_ack {
+                if let Err(err) = cursor::write(&db, &cursor) {
+                    error!(target: INDEXER, ?cursor, "Failed to persist cursor: {:#}", err);
+                }
+            }
+            last_sent = Some(cursor);
+            next_block_height = Some(block_height + 1);
+            failed_attempts = 0;
+        }
+    }
+}
+
+/// Delay before the next attempt to build a message, on top of [`INTERVAL`],
+/// after given number of failed attempts.
+fn retry_delay(failed_attempts: u32) -> Duration {
+    INTERVAL.saturating_mul(1 << failed_attempts.min(16)).min(MAX_RETRY_DELAY)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use near_crypto::{KeyType, Signature};
+
+    use super::*;
+
+    /// Chain whose canonical blocks can be replaced to simulate a reorg.
+    #[derive(Default)]
+    struct FakeChain {
+        headers: RefCell<HashMap<CryptoHash, views::BlockHeaderView>>,
+        canonical: RefCell<HashMap<BlockHeight, CryptoHash>>,
+        /// Number of blocks fetched by height.
+        fetched: Cell<usize>,
+    }
+
+    impl FakeChain {
+        /// Adds blocks on top of the canonical block at height `from - 1`,
+        /// replacing the canonical blocks at their heights.
+        fn extend(&self, fork: &str, from: BlockHeight, to: BlockHeight) -> Vec<CryptoHash> {
+            let mut hashes = vec![];
+            for height in from..=to {
+                let prev_hash = self.canonical.borrow().get(&(height - 1)).copied();
+                let header = header(fork, height, prev_hash.unwrap_or_default());
+                hashes.push(header.hash);
+                self.canonical.borrow_mut().insert(height, header.hash);
+                self.headers.borrow_mut().insert(header.hash, header);
             }
-            db.put(b"last_synced_block_height", &block_height.to_string()).unwrap();
-            last_synced_block_height = Some(block_height);
+            self.canonical.borrow_mut().retain(|height, _| *height <= to);
+            hashes
+        }
+    }
+
+    fn not_found() -> FailedToFetchData {
+        FailedToFetchData::String("block not found".to_string())
+    }
+
+    impl BlockSource for Rc<FakeChain> {
+        fn is_syncing(&self) -> LocalBoxFuture<'_, Result<bool, FailedToFetchData>> {
+            Box::pin(async { Ok(false) })
+        }
+
+        fn latest_block(&self) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+            let height = self.canonical.borrow().keys().max().copied();
+            Box::pin(async move {
+                let hash = self.canonical.borrow().get(&height.ok_or_else(not_found)?).copied();
+                self.block_by_hash(hash.ok_or_else(not_found)?).await
+            })
+        }
+
+        fn block_by_height(
+            &self,
+            height: BlockHeight,
+        ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+            self.fetched.set(self.fetched.get() + 1);
+            let hash = self.canonical.borrow().get(&height).copied();
+            Box::pin(async move { self.block_by_hash(hash.ok_or_else(not_found)?).await })
+        }
+
+        fn block_by_hash(
+            &self,
+            hash: CryptoHash,
+        ) -> LocalBoxFuture<'_, Result<views::BlockView, FailedToFetchData>> {
+            let header = self.headers.borrow().get(&hash).cloned();
+            let block = header.map(|header| views::BlockView {
+                author: "test.near".parse().unwrap(),
+                header,
+                chunks: vec![],
+            });
+            Box::pin(async move { block.ok_or_else(not_found) })
+        }
+
+        fn build_message(
+            &self,
+            block: views::BlockView,
+        ) -> LocalBoxFuture<'_, Result<StreamerMessage, FailedToFetchData>> {
+            Box::pin(async move { Ok(StreamerMessage { block, shards: vec![] }) })
+        }
+    }
+
+    fn header(fork: &str, height: BlockHeight, prev_hash: CryptoHash) -> views::BlockHeaderView {
+        views::BlockHeaderView {
+            height,
+            prev_height: Some(height - 1),
+            epoch_id: CryptoHash::default(),
+            next_epoch_id: CryptoHash::default(),
+            hash: CryptoHash::hash_bytes(format!("{}{}", fork, height).as_bytes()),
+            prev_hash,
+            prev_state_root: CryptoHash::default(),
+            chunk_receipts_root: CryptoHash::default(),
+            chunk_headers_root: CryptoHash::default(),
+            chunk_tx_root: CryptoHash::default(),
+            outcome_root: CryptoHash::default(),
+            chunks_included: 0,
+            challenges_root: CryptoHash::default(),
+            timestamp: 0,
+            timestamp_nanosec: 0,
+            random_value: CryptoHash::default(),
+            validator_proposals: vec![],
+            chunk_mask: vec![],
+            gas_price: 0,
+            block_ordinal: None,
+            rent_paid: 0,
+            validator_reward: 0,
+            total_supply: 0,
+            challenges_result: vec![],
+            last_final_block: CryptoHash::default(),
+            last_ds_final_block: CryptoHash::default(),
+            next_bp_hash: CryptoHash::default(),
+            block_merkle_root: CryptoHash::default(),
+            epoch_sync_data_hash: None,
+            approvals: vec![],
+            signature: Signature::empty(KeyType::ED25519),
+            latest_protocol_version: 0,
         }
     }
+
+    async fn recv(receiver: &mut mpsc::Receiver<StreamerMessage>, count: usize) -> Vec<CryptoHash> {
+        let mut hashes = vec![];
+        for _ in 0..count {
+            hashes.push(receiver.recv().await.unwrap().block.header.hash);
+        }
+        hashes
+    }
+
+    #[test]
+    fn test_stream_reorg() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(DB::open_default(tmp_dir.path()).unwrap());
+        let chain = Rc::new(FakeChain::default());
+        actix::System::new().block_on(async {
+            let fork_a = chain.extend("a", 1, 3);
+            let (sender, mut receiver) = mpsc::channel(10);
+            actix::spawn(stream(
+                chain.clone(),
+                SyncModeEnum::BlockHeight(1),
+                db.clone(),
+                sender,
+                true,
+            ));
+            assert_eq!(recv(&mut receiver, 3).await, fork_a);
+
+            // Block #3 is replaced; the streamer notices it when block #4
+            // doesn’t build on top of the block it has streamed last.
+            let fork_b = chain.extend("b", 3, 4);
+            assert_eq!(recv(&mut receiver, 2).await, fork_b);
+            let cursor = Cursor { block_height: 4, block_hash: fork_b[1] };
+            assert_eq!(cursor::read(&db).unwrap(), Some(StoredPosition::Cursor(cursor)));
+        });
+    }
+
+    #[test]
+    fn test_stream_backpressure() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let db = Arc::new(DB::open_default(tmp_dir.path()).unwrap());
+        let chain = Rc::new(FakeChain::default());
+        actix::System::new().block_on(async {
+            let hashes = chain.extend("a", 1, 5);
+            let (sender, mut receiver) = mpsc::channel(2);
+            actix::spawn(stream(
+                chain.clone(),
+                SyncModeEnum::BlockHeight(1),
+                db.clone(),
+                sender,
+                true,
+            ));
+
+            // Blocks aren’t fetched while the listener’s buffer is full.
+            time::sleep(INTERVAL * 4).await;
+            assert_eq!(chain.fetched.get(), 2);
+            let cursor = Cursor { block_height: 2, block_hash: hashes[1] };
+            assert_eq!(cursor::read(&db).unwrap(), Some(StoredPosition::Cursor(cursor)));
+
+            assert_eq!(recv(&mut receiver, 5).await, hashes);
+        });
+    }
+
+    #[test]
+    fn test_retry_delay() {
+        assert_eq!(retry_delay(1), INTERVAL * 2);
+        assert_eq!(retry_delay(MAX_BUILD_ATTEMPTS), MAX_RETRY_DELAY);
+    }
 }
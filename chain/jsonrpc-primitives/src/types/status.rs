@@ -11,6 +11,14 @@ pub struct RpcDebugStatusResponse {
     pub status_response: near_client_primitives::debug::DebugStatusResponse,
 }
 
+/// Response of the structured debug API served under `/debug/api/v1/`.
+#[derive(Debug, Serialize)]
+pub struct RpcDebugApiResponse {
+    /// Version of the debug API the data conforms to.
+    pub version: u32,
+    pub data: near_client_primitives::debug::v1::DebugApiResponse,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RpcHealthResponse;
 
@@ -0,0 +1,100 @@
+use serde::{Deserialize, Serialize};
+
+/// What a WebSocket client wants to be notified about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "subscription_type", rename_all = "snake_case")]
+pub enum RpcSubscribeRequest {
+    /// Every new final block.
+    FinalBlocks,
+    /// Every new chunk of the shard included in a final block.
+    Chunks { shard_id: near_primitives::types::ShardId },
+    /// State changes in every new final block matching the request, e.g.
+    /// changes of contract data under a key prefix.
+    StateChanges {
+        #[serde(flatten)]
+        state_changes_request: near_primitives::views::StateChangesRequestView,
+    },
+    /// Final execution outcome of the transaction, sent once.
+    TransactionOutcome {
+        tx_hash: near_primitives::hash::CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscribeResponse {
+    pub subscription_id: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcUnsubscribeRequest {
+    pub subscription_id: u64,
+}
+
+/// Params of the `subscription` notification sent to the WebSocket client.
+///
+/// Exactly one of `result` and `error` is set.  A subscription which sent an
+/// error is finished.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RpcSubscriptionNotification {
+    pub subscription_id: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<crate::errors::RpcError>,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSubscriptionError {
+    #[error("Subscription {subscription_id} doesn't exist")]
+    UnknownSubscription { subscription_id: u64 },
+    #[error("Too many subscriptions on the connection, the limit is {limit}")]
+    TooManySubscriptions { limit: usize },
+    #[error("Subscriptions are only supported over WebSocket")]
+    NotWebSocket,
+}
+
+impl From<RpcSubscriptionError> for crate::errors::RpcError {
+    fn from(error: RpcSubscriptionError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSubscriptionError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RpcSubscribeRequest;
+
+    #[test]
+    fn test_parse_state_changes_subscription() {
+        let request: RpcSubscribeRequest = serde_json::from_value(serde_json::json!({
+            "subscription_type": "state_changes",
+            "changes_type": "data_changes",
+            "account_ids": ["test.near"],
+            "key_prefix_base64": "U1RBVEU=",
+        }))
+        .unwrap();
+        match request {
+            RpcSubscribeRequest::StateChanges {
+                state_changes_request:
+                    near_primitives::views::StateChangesRequestView::DataChanges {
+                        account_ids,
+                        key_prefix,
+                    },
+            } => {
+                assert_eq!(account_ids, vec!["test.near".parse().unwrap()]);
+                assert_eq!(key_prefix, b"STATE".to_vec().into());
+            }
+            request => panic!("unexpected request: {:?}", request),
+        }
+    }
+}
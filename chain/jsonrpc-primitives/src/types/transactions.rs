@@ -30,6 +30,12 @@ pub enum RpcTransactionError {
     },
     #[error("Node doesn't track this shard. Cannot determine whether the transaction is valid")]
     DoesNotTrackShard,
+    #[error("The transaction pool of the node is full. Try again later")]
+    TransactionPoolFull,
+    #[error(
+        "The signer has too many transactions in the transaction pool of the node. Try again later"
+    )]
+    TooManyTransactionsFromSigner,
     #[error("Transaction with hash {transaction_hash} was routed")]
     RequestRouted { transaction_hash: near_primitives::hash::CryptoHash },
     #[error("Transaction {requested_transaction_hash} doesn't exist")]
@@ -81,3 +87,108 @@ impl From<RpcTransactionError> for crate::errors::RpcError {
         Self::new_internal_or_handler_error(Some(error_data), error_data_value)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct RpcSimulateTransactionRequest {
+    pub signed_transaction: near_primitives::transaction::SignedTransaction,
+    pub block_reference: near_primitives::types::BlockReference,
+    pub skip_signature_verification: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcSimulateTransactionResponse {
+    #[serde(flatten)]
+    pub final_execution_outcome: near_primitives::views::FinalExecutionOutcomeView,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcSimulateTransactionError {
+    #[error("Block either has never been observed on the node or has been garbage collected: {error_message}")]
+    UnknownBlock {
+        #[serde(skip_serializing)]
+        error_message: String,
+    },
+    #[error("There are no fully synchronized blocks on the node yet")]
+    NoSyncedBlocks,
+    #[error("An error happened during transaction execution: {context:?}")]
+    InvalidTransaction {
+        #[serde(skip_serializing)]
+        context: near_primitives::errors::InvalidTxError,
+    },
+    #[error("Node doesn't track shard {shard_id} required to execute the transaction")]
+    UnavailableShard { shard_id: near_primitives::types::ShardId },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcSimulateTransactionError> for crate::errors::RpcError {
+    fn from(error: RpcSimulateTransactionError) -> Self {
+        let error_data = match &error {
+            RpcSimulateTransactionError::UnknownBlock { error_message } => {
+                Value::String(format!("DB Not Found Error: {}", error_message))
+            }
+            RpcSimulateTransactionError::InvalidTransaction { context } => {
+                if let Ok(value) =
+                    serde_json::to_value(crate::errors::ServerError::TxExecutionError(
+                        near_primitives::errors::TxExecutionError::InvalidTxError(context.clone()),
+                    ))
+                {
+                    value
+                } else {
+                    Value::String(error.to_string())
+                }
+            }
+            _ => Value::String(error.to_string()),
+        };
+
+        let error_data_value = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcSimulateTransactionError: {:?}", err),
+                )
+            }
+        };
+
+        Self::new_internal_or_handler_error(Some(error_data), error_data_value)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RpcTransactionTraceRequest {
+    pub tx_hash: near_primitives::hash::CryptoHash,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct RpcTransactionTraceResponse {
+    #[serde(flatten)]
+    pub transaction_trace: near_primitives::views::TransactionTraceView,
+}
+
+#[derive(thiserror::Error, Debug, Serialize, Deserialize)]
+#[serde(tag = "name", content = "info", rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum RpcTransactionTraceError {
+    #[error("Transaction {requested_transaction_hash} has never been observed on this node or its outcome is not known yet")]
+    UnknownTransaction { requested_transaction_hash: near_primitives::hash::CryptoHash },
+    #[error("Transaction results in more than {limit} receipts")]
+    TooManyReceipts { limit: usize },
+    #[error("The node reached its limits. Try again later. More details: {error_message}")]
+    InternalError { error_message: String },
+}
+
+impl From<RpcTransactionTraceError> for crate::errors::RpcError {
+    fn from(error: RpcTransactionTraceError) -> Self {
+        let error_data = match serde_json::to_value(error) {
+            Ok(value) => value,
+            Err(err) => {
+                return Self::new_internal_error(
+                    None,
+                    format!("Failed to serialize RpcTransactionTraceError: {:?}", err),
+                )
+            }
+        };
+        Self::new_internal_or_handler_error(Some(error_data.clone()), error_data)
+    }
+}
@@ -251,6 +251,14 @@ impl JsonRpcClient {
         call_method(&self.client, &self.server_addr, "EXPERIMENTAL_receipt", request)
     }
 
+    #[allow(non_snake_case)]
+    pub fn EXPERIMENTAL_tx_trace(
+        &self,
+        request: near_jsonrpc_primitives::types::transactions::RpcTransactionTraceRequest,
+    ) -> RpcRequest<near_jsonrpc_primitives::types::transactions::RpcTransactionTraceResponse> {
+        call_method(&self.client, &self.server_addr, "EXPERIMENTAL_tx_trace", request)
+    }
+
     #[allow(non_snake_case)]
     pub fn EXPERIMENTAL_protocol_config(
         &self,
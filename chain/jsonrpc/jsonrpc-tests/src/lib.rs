@@ -40,12 +40,9 @@ pub fn start_all_with_validity_period_and_no_epoch_sync(
     );
 
     let addr = format!("127.0.0.1:{}", open_port());
-    start_http(
-        RpcConfig::new(&addr),
-        TEST_GENESIS_CONFIG.clone(),
-        client_addr,
-        view_client_addr.clone(),
-    );
+    let mut rpc_config = RpcConfig::new(&addr);
+    rpc_config.enable_debug_rpc = true;
+    start_http(rpc_config, TEST_GENESIS_CONFIG.clone(), client_addr, view_client_addr.clone());
     (view_client_addr, addr)
 }
 
@@ -26,3 +26,50 @@ fn test_status() {
         }));
     });
 }
+
+/// Retrieve structured debug information via HTTP GET.
+#[test]
+fn test_debug_api_v1() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let client = awc::Client::new();
+            let get = |endpoint: &str| {
+                client.get(format!("http://{}/debug/api/v1/{}", addr, endpoint)).send()
+            };
+
+            let blocks: serde_json::Value = get("blocks").await.unwrap().json().await.unwrap();
+            assert_eq!(blocks["version"], 1);
+            assert_eq!(blocks["data"]["blocks"][0]["height"], 0);
+            assert_eq!(blocks["data"]["skipped_heights"], serde_json::json!([]));
+
+            let epoch_info: serde_json::Value =
+                get("epoch_info").await.unwrap().json().await.unwrap();
+            let epochs = epoch_info["data"]["epochs"].as_array().unwrap();
+            let genesis_epoch = epochs.last().unwrap();
+            assert_eq!(genesis_epoch["start_height"], 0);
+            assert_eq!(genesis_epoch["block_producers"][0]["account_id"], "test1");
+            assert_eq!(genesis_epoch["shards"][0]["shard_id"], 0);
+
+            let pools: serde_json::Value = get("pools").await.unwrap().json().await.unwrap();
+            assert_eq!(pools["data"]["orphans"], serde_json::json!([]));
+            assert_eq!(pools["data"]["orphans_evicted"], 0);
+
+            let sync: serde_json::Value = get("sync").await.unwrap().json().await.unwrap();
+            assert_eq!(sync["data"]["head_height"], 0);
+            assert_eq!(sync["data"]["shards"][0]["shard_id"], 0);
+
+            let validator: serde_json::Value =
+                get("validator").await.unwrap().json().await.unwrap();
+            assert_eq!(validator["data"]["account_id"], "test1");
+
+            let unknown = get("unknown").await.unwrap();
+            assert_eq!(unknown.status(), awc::http::StatusCode::METHOD_NOT_ALLOWED);
+
+            System::current().stop();
+        });
+    });
+}
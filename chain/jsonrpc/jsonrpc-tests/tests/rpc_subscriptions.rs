@@ -0,0 +1,96 @@
+use actix::System;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+
+use near_actix_test_utils::run_actix;
+use near_logger_utils::init_test_logger;
+
+use near_jsonrpc_tests as test_utils;
+
+type WsConnection = actix_codec::Framed<awc::BoxedSocket, awc::ws::Codec>;
+
+async fn connect(addr: &str) -> WsConnection {
+    let (_response, connection) =
+        awc::Client::new().ws(format!("ws://{}/ws", addr)).connect().await.unwrap();
+    connection
+}
+
+async fn call(connection: &mut WsConnection, method: &str, params: Value) -> Value {
+    let request = json!({"jsonrpc": "2.0", "id": "dontcare", "method": method, "params": params});
+    connection.send(awc::ws::Message::Text(request.to_string().into())).await.unwrap();
+    // Skip notifications of the active subscriptions.
+    loop {
+        let message = receive(connection).await;
+        if message.get("id").is_some() {
+            return message;
+        }
+    }
+}
+
+async fn receive(connection: &mut WsConnection) -> Value {
+    loop {
+        match connection.next().await.unwrap().unwrap() {
+            awc::ws::Frame::Text(data) => return serde_json::from_slice(&data).unwrap(),
+            awc::ws::Frame::Ping(_) | awc::ws::Frame::Pong(_) => continue,
+            frame => panic!("unexpected frame: {:?}", frame),
+        }
+    }
+}
+
+/// Subscribe to final blocks and receive a notification about one.
+#[test]
+fn test_subscribe_final_blocks() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::Validator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            let response = call(
+                &mut connection,
+                "EXPERIMENTAL_subscribe",
+                json!({"subscription_type": "final_blocks"}),
+            )
+            .await;
+            let subscription_id = response["result"]["subscription_id"].clone();
+            assert!(subscription_id.is_u64(), "{}", response);
+
+            let notification = receive(&mut connection).await;
+            assert_eq!(notification["method"], "subscription");
+            assert_eq!(notification["params"]["subscription_id"], subscription_id);
+            assert!(notification["params"]["result"]["header"]["height"].is_u64());
+
+            let response = call(
+                &mut connection,
+                "EXPERIMENTAL_unsubscribe",
+                json!({ "subscription_id": subscription_id }),
+            )
+            .await;
+            assert_eq!(response["result"], Value::Null, "{}", response);
+            System::current().stop();
+        });
+    });
+}
+
+/// Regular methods work over the WebSocket, unknown subscriptions are reported.
+#[test]
+fn test_websocket_requests() {
+    init_test_logger();
+
+    run_actix(async {
+        let (_view_client_addr, addr) = test_utils::start_all(test_utils::NodeType::NonValidator);
+
+        actix::spawn(async move {
+            let mut connection = connect(&addr).await;
+            let response = call(&mut connection, "status", json!([])).await;
+            assert_eq!(response["result"]["chain_id"], "unittest");
+
+            let response =
+                call(&mut connection, "EXPERIMENTAL_unsubscribe", json!({"subscription_id": 42}))
+                    .await;
+            assert_eq!(response["error"]["cause"]["name"], "UNKNOWN_SUBSCRIPTION", "{}", response);
+            System::current().stop();
+        });
+    });
+}
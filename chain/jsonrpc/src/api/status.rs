@@ -26,6 +26,14 @@ impl RpcFrom<near_client_primitives::debug::DebugStatusResponse>
     }
 }
 
+impl RpcFrom<near_client_primitives::debug::v1::DebugApiResponse>
+    for near_jsonrpc_primitives::types::status::RpcDebugApiResponse
+{
+    fn rpc_from(data: near_client_primitives::debug::v1::DebugApiResponse) -> Self {
+        Self { version: near_client_primitives::debug::v1::DEBUG_API_VERSION, data }
+    }
+}
+
 impl RpcFrom<StatusResponse> for RpcHealthResponse {
     fn rpc_from(_status_response: StatusResponse) -> Self {
         Self {}
@@ -1,16 +1,21 @@
 use serde_json::Value;
 
-use near_client_primitives::types::TxStatusError;
+use near_client_primitives::types::{
+    GetTransactionTraceError, SimulateTransactionError, TxStatusError,
+};
 use near_jsonrpc_primitives::errors::RpcParseError;
 use near_jsonrpc_primitives::types::transactions::{
-    RpcBroadcastTransactionRequest, RpcTransactionError, RpcTransactionResponse,
-    RpcTransactionStatusCommonRequest, TransactionInfo,
+    RpcBroadcastTransactionRequest, RpcSimulateTransactionError, RpcSimulateTransactionRequest,
+    RpcTransactionError, RpcTransactionResponse, RpcTransactionStatusCommonRequest,
+    RpcTransactionTraceError, RpcTransactionTraceRequest, TransactionInfo,
 };
 use near_primitives::hash::CryptoHash;
-use near_primitives::types::AccountId;
+use near_primitives::types::{AccountId, BlockReference};
 use near_primitives::views::FinalExecutionOutcomeViewEnum;
 
-use super::{parse_params, parse_signed_transaction, RpcFrom, RpcRequest};
+use super::{
+    decode_signed_transaction, parse_params, parse_signed_transaction, RpcFrom, RpcRequest,
+};
 
 impl RpcRequest for RpcBroadcastTransactionRequest {
     fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
@@ -59,3 +64,93 @@ impl RpcFrom<FinalExecutionOutcomeViewEnum> for RpcTransactionResponse {
         Self { final_execution_outcome }
     }
 }
+
+impl RpcRequest for RpcSimulateTransactionRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        #[derive(serde::Deserialize)]
+        struct Params {
+            signed_tx_base64: String,
+            #[serde(flatten)]
+            block_reference: BlockReference,
+            #[serde(default)]
+            skip_signature_verification: bool,
+        }
+
+        let params = parse_params::<Params>(value)?;
+        Ok(Self {
+            signed_transaction: decode_signed_transaction(&params.signed_tx_base64)?,
+            block_reference: params.block_reference,
+            skip_signature_verification: params.skip_signature_verification,
+        })
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcSimulateTransactionError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<SimulateTransactionError> for RpcSimulateTransactionError {
+    fn rpc_from(error: SimulateTransactionError) -> Self {
+        match error {
+            SimulateTransactionError::UnknownBlock { error_message } => {
+                Self::UnknownBlock { error_message }
+            }
+            SimulateTransactionError::NotSyncedYet => Self::NoSyncedBlocks,
+            SimulateTransactionError::InvalidTransaction(context) => {
+                Self::InvalidTransaction { context }
+            }
+            SimulateTransactionError::UnavailableShard { shard_id } => {
+                Self::UnavailableShard { shard_id }
+            }
+            SimulateTransactionError::IOError { error_message }
+            | SimulateTransactionError::InternalError { error_message } => {
+                Self::InternalError { error_message }
+            }
+            SimulateTransactionError::TooManyRequests => {
+                Self::InternalError { error_message: error.to_string() }
+            }
+            SimulateTransactionError::Unreachable { ref error_message } => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcSimulateTransactionError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
+
+impl RpcRequest for RpcTransactionTraceRequest {
+    fn parse(value: Option<Value>) -> Result<Self, RpcParseError> {
+        parse_params::<Self>(value)
+    }
+}
+
+impl RpcFrom<actix::MailboxError> for RpcTransactionTraceError {
+    fn rpc_from(error: actix::MailboxError) -> Self {
+        Self::InternalError { error_message: error.to_string() }
+    }
+}
+
+impl RpcFrom<GetTransactionTraceError> for RpcTransactionTraceError {
+    fn rpc_from(error: GetTransactionTraceError) -> Self {
+        match error {
+            GetTransactionTraceError::IOError(error_message) => {
+                Self::InternalError { error_message }
+            }
+            GetTransactionTraceError::UnknownTransaction(requested_transaction_hash) => {
+                Self::UnknownTransaction { requested_transaction_hash }
+            }
+            GetTransactionTraceError::TooManyReceipts { limit } => Self::TooManyReceipts { limit },
+            GetTransactionTraceError::Unreachable(ref error_message) => {
+                tracing::warn!(target: "jsonrpc", "Unreachable error occurred: {}", error_message);
+                crate::metrics::RPC_UNREACHABLE_ERROR_COUNT
+                    .with_label_values(&["RpcTransactionTraceError"])
+                    .inc();
+                Self::InternalError { error_message: error.to_string() }
+            }
+        }
+    }
+}
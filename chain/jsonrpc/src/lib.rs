@@ -16,10 +16,10 @@ use tracing::info;
 
 use near_chain_configs::GenesisConfig;
 use near_client::{
-    ClientActor, DebugStatus, GetBlock, GetBlockProof, GetChunk, GetExecutionOutcome, GetGasPrice,
-    GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig, GetReceipt, GetStateChanges,
-    GetStateChangesInBlock, GetValidatorInfo, GetValidatorOrdered, Query, Status, TxStatus,
-    ViewClientActor,
+    ClientActor, CreateCheckpoint, DebugApiRequest, DebugStatus, GetBlock, GetBlockProof, GetChunk,
+    GetExecutionOutcome, GetGasPrice, GetNetworkInfo, GetNextLightClientBlock, GetProtocolConfig,
+    GetReceipt, GetStateChanges, GetStateChangesInBlock, GetTransactionTrace, GetValidatorInfo,
+    GetValidatorOrdered, Query, SimulateTransaction, Status, TxStatus, ViewClientActor,
 };
 pub use near_jsonrpc_client as client;
 use near_jsonrpc_primitives::errors::RpcError;
@@ -35,6 +35,7 @@ use near_primitives::views::FinalExecutionOutcomeViewEnum;
 
 mod api;
 mod metrics;
+mod subscriptions;
 
 use api::RpcRequest;
 pub use api::{RpcFrom, RpcInto};
@@ -136,6 +137,8 @@ impl near_jsonrpc_primitives::types::transactions::RpcTransactionError {
         match responses {
             NetworkClientResponses::InvalidTx(context) => Self::InvalidTransaction { context },
             NetworkClientResponses::NoResponse => Self::TimeoutError,
+            NetworkClientResponses::TxPoolFull => Self::TransactionPoolFull,
+            NetworkClientResponses::TooManyTxsFromSigner => Self::TooManyTransactionsFromSigner,
             NetworkClientResponses::DoesNotTrackShard | NetworkClientResponses::RequestRouted => {
                 Self::DoesNotTrackShard
             }
@@ -332,6 +335,16 @@ impl JsonRpcHandler {
             "EXPERIMENTAL_receipt" => {
                 process_method_call(request, |params| self.receipt(params)).await
             }
+            "EXPERIMENTAL_simulate_tx" => {
+                process_method_call(request, |params| self.simulate_tx(params)).await
+            }
+            "EXPERIMENTAL_tx_trace" => {
+                process_method_call(request, |params| self.tx_trace(params)).await
+            }
+            "EXPERIMENTAL_subscribe" | "EXPERIMENTAL_unsubscribe" => Err(
+                near_jsonrpc_primitives::types::subscriptions::RpcSubscriptionError::NotWebSocket
+                    .into(),
+            ),
             "EXPERIMENTAL_tx_status" => {
                 process_method_call(request, |params| self.tx_status_common(params, true)).await
             }
@@ -745,6 +758,11 @@ impl JsonRpcHandler {
                 "/debug/api/validator_status" => {
                     self.client_send(DebugStatus::ValidatorStatus).await?
                 }
+                "/debug/api/checkpoint" => {
+                    near_client_primitives::debug::DebugStatusResponse::Checkpoint(
+                        self.view_client_send(CreateCheckpoint).await?,
+                    )
+                }
                 _ => return Ok(None),
             };
             return Ok(Some(debug_status.rpc_into()));
@@ -753,6 +771,22 @@ impl JsonRpcHandler {
         }
     }
 
+    pub async fn debug_v1(
+        &self,
+        endpoint: &str,
+    ) -> Result<
+        Option<near_jsonrpc_primitives::types::status::RpcDebugApiResponse>,
+        near_jsonrpc_primitives::types::status::RpcStatusError,
+    > {
+        if !self.enable_debug_rpc {
+            return Ok(None);
+        }
+        match DebugApiRequest::from_endpoint(endpoint) {
+            Some(request) => Ok(Some(self.client_send(request).await?.rpc_into())),
+            None => Ok(None),
+        }
+    }
+
     pub async fn protocol_config(
         &self,
         request_data: near_jsonrpc_primitives::types::config::RpcProtocolConfigRequest,
@@ -835,6 +869,48 @@ impl JsonRpcHandler {
         }
     }
 
+    async fn simulate_tx(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionResponse,
+        near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionError,
+    > {
+        let simulation = self
+            .view_client_send(SimulateTransaction {
+                transaction: request_data.signed_transaction,
+                block_reference: request_data.block_reference,
+                verify_signature: !request_data.skip_signature_verification,
+            })
+            .await?;
+        let final_execution_outcome = simulation
+            .await
+            .map_err(|_| {
+                near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionError::InternalError {
+                    error_message: "Simulation was interrupted".to_string(),
+                }
+            })?
+            .map_err(RpcFrom::rpc_from)?;
+        Ok(near_jsonrpc_primitives::types::transactions::RpcSimulateTransactionResponse {
+            final_execution_outcome,
+        })
+    }
+
+    async fn tx_trace(
+        &self,
+        request_data: near_jsonrpc_primitives::types::transactions::RpcTransactionTraceRequest,
+    ) -> Result<
+        near_jsonrpc_primitives::types::transactions::RpcTransactionTraceResponse,
+        near_jsonrpc_primitives::types::transactions::RpcTransactionTraceError,
+    > {
+        let transaction_trace = self
+            .view_client_send(GetTransactionTrace { transaction_hash: request_data.tx_hash })
+            .await?;
+        Ok(near_jsonrpc_primitives::types::transactions::RpcTransactionTraceResponse {
+            transaction_trace,
+        })
+    }
+
     async fn changes_in_block(
         &self,
         request: near_jsonrpc_primitives::types::changes::RpcStateChangesInBlockRequest,
@@ -1230,6 +1306,17 @@ async fn debug_handler(
     }
 }
 
+async fn debug_v1_handler(
+    path: web::Path<String>,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    match handler.debug_v1(&path).await {
+        Ok(Some(value)) => Ok(HttpResponse::Ok().json(&value)),
+        Ok(None) => Ok(HttpResponse::MethodNotAllowed().finish()),
+        Err(_) => Ok(HttpResponse::ServiceUnavailable().finish()),
+    }
+}
+
 fn health_handler(
     handler: web::Data<JsonRpcHandler>,
 ) -> impl Future<Output = Result<HttpResponse, HttpError>> {
@@ -1351,6 +1438,7 @@ pub fn start_http(
             .app_data(web::JsonConfig::default().limit(limits_config.json_payload_max_size))
             .wrap(middleware::Logger::default())
             .service(web::resource("/").route(web::post().to(rpc_handler)))
+            .service(web::resource("/ws").route(web::get().to(subscriptions::ws_handler)))
             .service(
                 web::resource("/status")
                     .route(web::get().to(status_handler))
@@ -1364,6 +1452,9 @@ pub fn start_http(
             .service(web::resource("/network_info").route(web::get().to(network_info_handler)))
             .service(web::resource("/metrics").route(web::get().to(prometheus_handler)))
             .service(web::resource("/debug/api/{api}").route(web::get().to(debug_handler)))
+            .service(
+                web::resource("/debug/api/v1/{endpoint}").route(web::get().to(debug_v1_handler)),
+            )
             .service(debug_html)
             .service(display_debug_html)
     })
@@ -1,4 +1,4 @@
-use near_metrics::{exponential_buckets, HistogramVec, IntCounter, IntCounterVec};
+use near_metrics::{exponential_buckets, HistogramVec, IntCounter, IntCounterVec, IntGauge};
 use once_cell::sync::Lazy;
 
 pub static RPC_PROCESSING_TIME: Lazy<HistogramVec> = Lazy::new(|| {
@@ -55,3 +55,10 @@ pub static RPC_UNREACHABLE_ERROR_COUNT: Lazy<IntCounterVec> = Lazy::new(|| {
     )
     .unwrap()
 });
+pub static WS_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    near_metrics::try_create_int_gauge(
+        "near_rpc_ws_connections",
+        "Number of open WebSocket connections to the RPC server",
+    )
+    .unwrap()
+});
@@ -0,0 +1,391 @@
+//! WebSocket subscriptions.
+//!
+//! Clients connect to the `/ws` endpoint and send JSON RPC requests over the
+//! WebSocket.  All the regular methods are available, and additionally the
+//! `EXPERIMENTAL_subscribe` method (see [`RpcSubscribeRequest`] for its
+//! params) and the `EXPERIMENTAL_unsubscribe` method.  Each subscription is
+//! assigned an id which is returned from `EXPERIMENTAL_subscribe` and included
+//! in the `subscription` notifications sent to the client.
+//!
+//! Subscriptions are driven by polling the view client at the configured
+//! polling interval, so notifications arrive with a delay of up to that
+//! interval.  A client which doesn't read the messages as fast as they are
+//! produced is disconnected, see [`MAX_QUEUED_MESSAGES_PER_CONNECTION`].
+
+use std::collections::HashMap;
+
+use actix_codec::{Decoder, Encoder};
+use actix_http::ws::{CloseCode, CloseReason, Codec, Frame, Message as WsMessage};
+use actix_web::body::BodyStream;
+use actix_web::web::BytesMut;
+use actix_web::{web, Error as HttpError, HttpRequest, HttpResponse};
+use futures::channel::mpsc;
+use futures::{FutureExt, StreamExt};
+use serde_json::Value;
+use tokio::task::JoinHandle;
+use tokio::time::sleep;
+use tracing::debug;
+
+use near_client::{GetBlock, GetChunk, GetStateChanges, TxStatus};
+use near_jsonrpc_primitives::errors::RpcError;
+use near_jsonrpc_primitives::message::{Message, Request};
+use near_jsonrpc_primitives::types::blocks::RpcBlockError;
+use near_jsonrpc_primitives::types::changes::{
+    RpcStateChangesError, RpcStateChangesInBlockResponse,
+};
+use near_jsonrpc_primitives::types::chunks::RpcChunkError;
+use near_jsonrpc_primitives::types::subscriptions::{
+    RpcSubscribeRequest, RpcSubscribeResponse, RpcSubscriptionError, RpcSubscriptionNotification,
+    RpcUnsubscribeRequest,
+};
+use near_jsonrpc_primitives::types::transactions::RpcTransactionError;
+use near_primitives::types::{BlockId, BlockReference, Finality};
+use near_primitives::views::BlockView;
+
+use crate::api::RpcRequest;
+use crate::{metrics, serialize_response, JsonRpcHandler};
+
+/// Maximum number of active subscriptions on a single connection.
+const MAX_SUBSCRIPTIONS_PER_CONNECTION: usize = 32;
+
+/// Maximum number of messages waiting to be sent on a single connection.
+/// Once it's reached, the connection is closed together with its
+/// subscriptions, since the client couldn't tell which notifications it
+/// missed.
+const MAX_QUEUED_MESSAGES_PER_CONNECTION: usize = 256;
+
+pub(crate) async fn ws_handler(
+    req: HttpRequest,
+    payload: web::Payload,
+    handler: web::Data<JsonRpcHandler>,
+) -> Result<HttpResponse, HttpError> {
+    let mut response = actix_http::ws::handshake(req.head())?;
+    let (sender, receiver) = mpsc::channel(MAX_QUEUED_MESSAGES_PER_CONNECTION);
+    let mut codec = Codec::new();
+    let body = receiver.map(move |message| {
+        let mut buffer = BytesMut::new();
+        codec.encode(message, &mut buffer).map(|()| buffer.freeze())
+    });
+    let response = response.message_body(BodyStream::new(body))?;
+    actix_web::rt::spawn(Connection::new(handler, sender).run(payload));
+    Ok(HttpResponse::from(response).map_into_boxed_body())
+}
+
+/// State of a single WebSocket connection.
+struct Connection {
+    handler: web::Data<JsonRpcHandler>,
+    sender: mpsc::Sender<WsMessage>,
+    subscriptions: HashMap<u64, JoinHandle<()>>,
+    next_subscription_id: u64,
+}
+
+impl Connection {
+    fn new(handler: web::Data<JsonRpcHandler>, sender: mpsc::Sender<WsMessage>) -> Self {
+        Self { handler, sender, subscriptions: HashMap::new(), next_subscription_id: 0 }
+    }
+
+    async fn run(mut self, mut payload: web::Payload) {
+        metrics::WS_CONNECTIONS.inc();
+        let mut codec = Codec::new();
+        let mut buffer = BytesMut::new();
+        'read: while let Some(chunk) = payload.next().await {
+            match chunk {
+                Ok(chunk) => buffer.extend_from_slice(&chunk),
+                Err(err) => {
+                    debug!(target: "jsonrpc", ?err, "WebSocket connection failed");
+                    break;
+                }
+            }
+            loop {
+                match codec.decode(&mut buffer) {
+                    Ok(Some(frame)) => {
+                        if !self.handle_frame(frame).await {
+                            break 'read;
+                        }
+                    }
+                    Ok(None) => break,
+                    Err(err) => {
+                        debug!(target: "jsonrpc", ?err, "Invalid WebSocket frame");
+                        self.close(CloseCode::Protocol);
+                        break 'read;
+                    }
+                }
+            }
+        }
+        for (_, subscription) in self.subscriptions.drain() {
+            subscription.abort();
+        }
+        metrics::WS_CONNECTIONS.dec();
+    }
+
+    /// Handles a frame received from the client.  Returns whether the
+    /// connection should be kept open.
+    async fn handle_frame(&mut self, frame: Frame) -> bool {
+        match frame {
+            Frame::Text(data) | Frame::Binary(data) => {
+                let response = match serde_json::from_slice::<Message>(&data) {
+                    Ok(message) => self.process(message).await,
+                    Err(err) => Message::error(RpcError::parse_error(err.to_string())),
+                };
+                self.send(&response)
+            }
+            Frame::Ping(data) => send(&mut self.sender, WsMessage::Pong(data)),
+            Frame::Pong(_) => true,
+            Frame::Close(reason) => {
+                send(&mut self.sender, WsMessage::Close(reason));
+                false
+            }
+            Frame::Continuation(_) => {
+                self.close(CloseCode::Unsupported);
+                false
+            }
+        }
+    }
+
+    async fn process(&mut self, message: Message) -> Message {
+        let request = match message {
+            Message::Request(request) => request,
+            _ => {
+                return Message::error(RpcError::parse_error(
+                    "JSON RPC Request format was expected".to_owned(),
+                ))
+            }
+        };
+        let id = request.id.clone();
+        let result = match request.method.as_ref() {
+            "EXPERIMENTAL_subscribe" => self.subscribe(request),
+            "EXPERIMENTAL_unsubscribe" => self.unsubscribe(request),
+            _ => self.handler.process_request(request).await,
+        };
+        Message::response(id, result)
+    }
+
+    fn subscribe(&mut self, request: Request) -> Result<Value, RpcError> {
+        let subscribe_request = RpcSubscribeRequest::parse(request.params)?;
+        // Transaction outcome subscriptions finish on their own.
+        self.subscriptions.retain(|_, subscription| subscription.now_or_never().is_none());
+        if self.subscriptions.len() >= MAX_SUBSCRIPTIONS_PER_CONNECTION {
+            return Err(RpcSubscriptionError::TooManySubscriptions {
+                limit: MAX_SUBSCRIPTIONS_PER_CONNECTION,
+            }
+            .into());
+        }
+        let subscription_id = self.next_subscription_id;
+        self.next_subscription_id += 1;
+        let subscription = Subscription {
+            handler: self.handler.clone(),
+            sender: self.sender.clone(),
+            subscription_id,
+        };
+        self.subscriptions
+            .insert(subscription_id, actix_web::rt::spawn(subscription.run(subscribe_request)));
+        serialize_response(RpcSubscribeResponse { subscription_id })
+    }
+
+    fn unsubscribe(&mut self, request: Request) -> Result<Value, RpcError> {
+        let RpcUnsubscribeRequest { subscription_id } =
+            RpcUnsubscribeRequest::parse(request.params)?;
+        match self.subscriptions.remove(&subscription_id) {
+            Some(subscription) => {
+                subscription.abort();
+                Ok(Value::Null)
+            }
+            None => Err(RpcSubscriptionError::UnknownSubscription { subscription_id }.into()),
+        }
+    }
+
+    fn send(&mut self, message: &Message) -> bool {
+        match serde_json::to_string(message) {
+            Ok(text) => send(&mut self.sender, WsMessage::Text(text.into())),
+            Err(err) => {
+                debug!(target: "jsonrpc", ?err, "Failed to serialize WebSocket message");
+                true
+            }
+        }
+    }
+
+    fn close(&mut self, code: CloseCode) {
+        send(&mut self.sender, WsMessage::Close(Some(CloseReason::from(code))));
+    }
+}
+
+/// Queues a message to be sent to the client.  Returns whether the client is
+/// still connected; the connection is closed if too many messages are queued.
+fn send(sender: &mut mpsc::Sender<WsMessage>, message: WsMessage) -> bool {
+    match sender.try_send(message) {
+        Ok(()) => true,
+        Err(err) => {
+            if err.is_full() {
+                debug!(target: "jsonrpc", "WebSocket client doesn't keep up, closing the connection");
+                sender.close_channel();
+            }
+            false
+        }
+    }
+}
+
+/// A single subscription, running as a task until the client unsubscribes
+/// or disconnects.
+struct Subscription {
+    handler: web::Data<JsonRpcHandler>,
+    sender: mpsc::Sender<WsMessage>,
+    subscription_id: u64,
+}
+
+impl Subscription {
+    async fn run(mut self, request: RpcSubscribeRequest) {
+        match request {
+            RpcSubscribeRequest::TransactionOutcome { tx_hash, sender_account_id } => {
+                self.wait_for_transaction_outcome(tx_hash, sender_account_id).await
+            }
+            request => self.follow_final_blocks(request).await,
+        }
+    }
+
+    async fn wait_for_transaction_outcome(
+        &mut self,
+        tx_hash: near_primitives::hash::CryptoHash,
+        sender_account_id: near_primitives::types::AccountId,
+    ) {
+        loop {
+            let tx_status = self
+                .handler
+                .view_client_send::<_, _, RpcTransactionError, _>(TxStatus {
+                    tx_hash,
+                    signer_account_id: sender_account_id.clone(),
+                    fetch_receipt: false,
+                })
+                .await;
+            match tx_status {
+                Ok(Some(outcome)) => {
+                    self.notify(serialize_response(outcome));
+                    return;
+                }
+                // The transaction may not have reached the node yet.
+                Ok(None) | Err(RpcTransactionError::UnknownTransaction { .. }) => {}
+                Err(err) => {
+                    self.notify(Err(err.into()));
+                    return;
+                }
+            }
+            sleep(self.handler.polling_config.polling_interval).await;
+        }
+    }
+
+    async fn follow_final_blocks(&mut self, request: RpcSubscribeRequest) {
+        let mut last_height = None;
+        loop {
+            let final_block = self
+                .handler
+                .view_client_send::<_, _, RpcBlockError, _>(GetBlock(BlockReference::Finality(
+                    Finality::Final,
+                )))
+                .await;
+            match final_block {
+                Ok(final_block) => {
+                    let final_height = final_block.header.height;
+                    let first_height = last_height.map_or(final_height, |height| height + 1);
+                    for height in first_height..final_height {
+                        // Heights without a block are skipped.
+                        if let Ok(block) = self
+                            .handler
+                            .view_client_send::<_, _, RpcBlockError, _>(GetBlock(
+                                BlockReference::BlockId(BlockId::Height(height)),
+                            ))
+                            .await
+                        {
+                            if !self.notify_block(&request, &block).await {
+                                return;
+                            }
+                        }
+                    }
+                    if last_height != Some(final_height)
+                        && !self.notify_block(&request, &final_block).await
+                    {
+                        return;
+                    }
+                    last_height = Some(final_height);
+                }
+                Err(err) => debug!(target: "jsonrpc", ?err, "Failed to get final block"),
+            }
+            sleep(self.handler.polling_config.polling_interval).await;
+        }
+    }
+
+    /// Sends notifications the subscription needs for the new final block.
+    /// Returns whether the client is still connected.
+    async fn notify_block(&mut self, request: &RpcSubscribeRequest, block: &BlockView) -> bool {
+        match request {
+            RpcSubscribeRequest::FinalBlocks => self.notify(serialize_response(block)),
+            RpcSubscribeRequest::Chunks { shard_id } => {
+                for chunk in block.chunks.iter().filter(|chunk| {
+                    chunk.shard_id == *shard_id && chunk.height_included == block.header.height
+                }) {
+                    match self
+                        .handler
+                        .view_client_send::<_, _, RpcChunkError, _>(GetChunk::ChunkHash(
+                            chunk.chunk_hash.into(),
+                        ))
+                        .await
+                    {
+                        Ok(chunk_view) => {
+                            if !self.notify(serialize_response(chunk_view)) {
+                                return false;
+                            }
+                        }
+                        Err(err) => debug!(target: "jsonrpc", ?err, "Failed to get chunk"),
+                    }
+                }
+                true
+            }
+            RpcSubscribeRequest::StateChanges { state_changes_request } => {
+                let block_hash = block.header.hash;
+                match self
+                    .handler
+                    .view_client_send::<_, _, RpcStateChangesError, _>(GetStateChanges {
+                        block_hash,
+                        state_changes_request: state_changes_request.clone(),
+                    })
+                    .await
+                {
+                    Ok(changes) if changes.is_empty() => true,
+                    Ok(changes) => {
+                        self.notify(serialize_response(RpcStateChangesInBlockResponse {
+                            block_hash,
+                            changes,
+                        }))
+                    }
+                    Err(err) => {
+                        debug!(target: "jsonrpc", ?err, "Failed to get state changes");
+                        true
+                    }
+                }
+            }
+            RpcSubscribeRequest::TransactionOutcome { .. } => {
+                unreachable!("transaction outcomes don't follow blocks")
+            }
+        }
+    }
+
+    /// Sends a notification to the client.  Returns whether the client is
+    /// still connected.
+    fn notify(&mut self, result: Result<Value, RpcError>) -> bool {
+        let (result, error) = match result {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        let notification =
+            RpcSubscriptionNotification { subscription_id: self.subscription_id, result, error };
+        let message = match serde_json::to_value(notification) {
+            Ok(params) => Message::notification("subscription".to_string(), Some(params)),
+            Err(err) => {
+                debug!(target: "jsonrpc", ?err, "Failed to serialize notification");
+                return true;
+            }
+        };
+        match serde_json::to_string(&message) {
+            Ok(text) => send(&mut self.sender, WsMessage::Text(text.into())),
+            Err(_) => true,
+        }
+    }
+}
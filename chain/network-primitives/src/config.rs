@@ -1,12 +1,15 @@
-use crate::network_protocol::PeerInfo;
+use crate::config_json::{CaptureConfig, MessageQuotasConfig, PEER_MESSAGE_TYPES};
+use crate::network_protocol::{PeerInfo, RoutedMessageBody};
 use crate::types::{Blacklist, ROUTED_MESSAGE_TTL};
 use near_crypto::{KeyType, SecretKey};
 use near_primitives::network::PeerId;
 use near_primitives::types::AccountId;
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
 use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::Duration;
+use strum::VariantNames;
 
 /// How much height horizon to give to consider peer up to date.
 pub const HIGHEST_PEER_HORIZON: u64 = 5;
@@ -14,6 +17,9 @@ pub const HIGHEST_PEER_HORIZON: u64 = 5;
 /// Maximum amount of routes to store for each account id.
 pub const MAX_ROUTES_TO_STORE: usize = 5;
 
+/// Maximum number of addresses a validator may advertise for TIER1 connections.
+pub const MAX_PEERS_PER_VALIDATOR: usize = 10;
+
 /// ValidatorEndpoints are the endpoints that peers should connect to, to send messages to this
 /// validator. Validator will sign the endpoints and broadcast them to the network.
 /// For a static setup (a static IP, or a list of relay nodes with static IPs) use PublicAddrs.
@@ -22,8 +28,8 @@ pub const MAX_ROUTES_TO_STORE: usize = 5;
 pub enum ValidatorEndpoints {
     /// Single public address of this validator, or a list of public addresses of trusted nodes
     /// willing to route messages to this validator. Validator will connect to the listed relay
-    /// nodes on startup.
-    PublicAddrs(Vec<SocketAddr>),
+    /// nodes on startup. The peer id of the validator's own address is the id of this node.
+    PublicAddrs(Vec<PeerInfo>),
     /// Addresses of the format "<domain/ip>:<port>" of STUN servers.
     /// The IP of the validator will be determined dynamically by querying all the STUN servers on
     /// the list.
@@ -50,6 +56,8 @@ pub struct NetworkConfig {
     pub validator: Option<ValidatorConfig>,
 
     pub boot_nodes: Vec<PeerInfo>,
+    /// File with more nodes to connect to, see `config_json::Config::seed_file`.
+    pub seed_file: Option<PathBuf>,
     pub whitelist_nodes: Vec<PeerInfo>,
     pub handshake_timeout: Duration,
     pub reconnect_delay: Duration,
@@ -101,6 +109,12 @@ pub struct NetworkConfig {
     pub outbound_disabled: bool,
     /// Not clear old data, set `true` for archive nodes.
     pub archive: bool,
+    /// Recording of the messages exchanged with the peers, if enabled.
+    pub capture: Option<CaptureConfig>,
+    /// Refuse the peers which use the borsh encoding.
+    pub proto_only: bool,
+    /// Quotas on the messages received from every peer.
+    pub message_quotas: MessageQuotasConfig,
 }
 
 impl NetworkConfig {
@@ -110,6 +124,7 @@ impl NetworkConfig {
         validator_signer: Option<Arc<dyn ValidatorSigner>>,
         archive: bool,
     ) -> Self {
+        let node_id = PeerId::new(node_key.public_key());
         Self {
             node_key,
             validator: validator_signer.as_ref().map(|signer| ValidatorConfig {
@@ -117,8 +132,18 @@ impl NetworkConfig {
                 endpoints: if cfg.public_addrs.len() > 0 {
                     ValidatorEndpoints::PublicAddrs(
                         cfg.public_addrs
-                            .into_iter()
-                            .map(|addr| addr.parse().expect("Failed to parse SocketAddr"))
+                            .iter()
+                            .map(|addr| {
+                                // Relay nodes are given as "peer_id@IP:port".
+                                if addr.contains('@') {
+                                    addr.as_str().try_into().expect("Failed to parse PeerInfo")
+                                } else {
+                                    PeerInfo::new(
+                                        node_id.clone(),
+                                        addr.parse().expect("Failed to parse SocketAddr"),
+                                    )
+                                }
+                            })
                             .collect(),
                     )
                 } else {
@@ -137,6 +162,7 @@ impl NetworkConfig {
                     .map(|chunk| chunk.try_into().expect("Failed to parse PeerInfo"))
                     .collect()
             },
+            seed_file: cfg.seed_file,
             whitelist_nodes: (|| -> Vec<_> {
                 let w = &cfg.whitelist_nodes;
                 if w.is_empty() {
@@ -178,6 +204,9 @@ impl NetworkConfig {
                 .collect(),
             outbound_disabled: false,
             archive,
+            capture: cfg.capture,
+            proto_only: cfg.proto_only,
+            message_quotas: cfg.message_quotas,
         }
     }
 
@@ -196,13 +225,17 @@ impl NetworkConfig {
                 KeyType::ED25519,
                 seed,
             )),
-            endpoints: ValidatorEndpoints::PublicAddrs(vec![node_addr]),
+            endpoints: ValidatorEndpoints::PublicAddrs(vec![PeerInfo::new(
+                PeerId::new(node_key.public_key()),
+                node_addr,
+            )]),
         };
         NetworkConfig {
             node_addr: Some(node_addr),
             node_key,
             validator: Some(validator),
             boot_nodes: vec![],
+            seed_file: None,
             whitelist_nodes: vec![],
             handshake_timeout: Duration::from_secs(60),
             reconnect_delay: Duration::from_secs(60),
@@ -226,6 +259,9 @@ impl NetworkConfig {
             blacklist: Blacklist::default(),
             outbound_disabled: false,
             archive: false,
+            capture: None,
+            proto_only: false,
+            message_quotas: MessageQuotasConfig::default(),
         }
     }
 
@@ -263,6 +299,43 @@ impl NetworkConfig {
                 self.peer_recent_time_window.as_secs(), UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE.as_secs()
             );
         }
+
+        if let Some(ValidatorConfig { endpoints: ValidatorEndpoints::PublicAddrs(addrs), .. }) =
+            &self.validator
+        {
+            if addrs.len() > MAX_PEERS_PER_VALIDATOR {
+                anyhow::bail!(
+                    "Too many public_addrs({}), at most {} are allowed.",
+                    addrs.len(),
+                    MAX_PEERS_PER_VALIDATOR
+                );
+            }
+        }
+
+        let quotas = &self.message_quotas;
+        let limits = quotas
+            .per_peer_bytes
+            .iter()
+            .map(|limit| ("per_peer_bytes", limit))
+            .chain(quotas.consensus_bytes.iter().map(|limit| ("consensus_bytes", limit)))
+            .chain(quotas.per_message_type.iter().map(|(name, limit)| (name.as_str(), limit)));
+        for (name, limit) in limits {
+            if limit.per_second == 0 || limit.burst == 0 {
+                anyhow::bail!(
+                    "Invalid message quota for {}: per_second({}) and burst({}) must be positive.",
+                    name,
+                    limit.per_second,
+                    limit.burst
+                );
+            }
+        }
+        for name in quotas.per_message_type.keys() {
+            if !PEER_MESSAGE_TYPES.contains(&name.as_str())
+                && !RoutedMessageBody::VARIANTS.contains(&name.as_str())
+            {
+                anyhow::bail!("Invalid message quota: unknown message type {}.", name);
+            }
+        }
         Ok(())
     }
 }
@@ -274,7 +347,10 @@ pub const UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE: Duration = Duration::from_
 
 #[cfg(test)]
 mod test {
-    use crate::types::{NetworkConfig, UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE};
+    use crate::types::{
+        NetworkConfig, RateLimit, ValidatorConfig, ValidatorEndpoints, MAX_PEERS_PER_VALIDATOR,
+        UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
+    };
 
     #[test]
     fn test_network_config() {
@@ -300,5 +376,36 @@ mod test {
         nc.peer_recent_time_window = UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE;
         let res = nc.verify();
         assert!(res.is_err(), "{:?}", res);
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        nc.message_quotas
+            .per_message_type
+            .insert("StateRequestPart".to_string(), RateLimit { per_second: 0, burst: 10 });
+        let res = nc.verify();
+        assert!(res.is_err(), "{:?}", res);
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        nc.message_quotas
+            .per_message_type
+            .insert("StateRequestParts".to_string(), RateLimit { per_second: 10, burst: 10 });
+        let res = nc.verify();
+        assert!(res.is_err(), "{:?}", res);
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        for name in ["StateRequestPart", "Block"] {
+            nc.message_quotas
+                .per_message_type
+                .insert(name.to_string(), RateLimit { per_second: 10, burst: 10 });
+        }
+        assert!(nc.verify().is_ok());
+
+        let mut nc = NetworkConfig::from_seed("123", 213);
+        if let Some(ValidatorConfig { endpoints: ValidatorEndpoints::PublicAddrs(addrs), .. }) =
+            &mut nc.validator
+        {
+            *addrs = vec![addrs[0].clone(); MAX_PEERS_PER_VALIDATOR + 1];
+        }
+        let res = nc.verify();
+        assert!(res.is_err(), "{:?}", res);
     }
 }
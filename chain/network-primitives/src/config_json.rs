@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::time::Duration;
 
 /// Time to persist Accounts Id in the router without removing them in seconds.
@@ -42,6 +44,113 @@ fn default_ttl_account_id_router() -> Duration {
 fn default_peer_stats_period() -> Duration {
     Duration::from_secs(5)
 }
+/// Size of a capture file after which a new one is started.
+fn default_capture_max_file_size() -> u64 {
+    100 * 1024 * 1024
+}
+/// Number of capture files to keep.
+fn default_capture_max_files() -> usize {
+    10
+}
+
+/// Recording of all the messages exchanged with the peers, to be replayed
+/// later with `neard network-replay` when debugging the peer protocol.
+/// Every message is written to disk, so it should be enabled only for the
+/// time needed to reproduce an issue.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CaptureConfig {
+    /// Directory the capture files are written to.
+    pub dir: PathBuf,
+    /// Size in bytes after which a new capture file is started.
+    #[serde(default = "default_capture_max_file_size")]
+    pub max_file_size: u64,
+    /// Number of capture files to keep, the oldest ones are removed.
+    #[serde(default = "default_capture_max_files")]
+    pub max_files: usize,
+}
+
+/// Number of messages over quota kept per peer until the quotas allow them.
+fn default_max_delayed_messages() -> usize {
+    1000
+}
+
+/// Total size of the messages over quota kept per peer.
+fn default_max_delayed_bytes() -> u64 {
+    64 * 1024 * 1024
+}
+
+/// Names of the `PeerMessage` variants which can be given a quota in
+/// `MessageQuotasConfig::per_message_type`.  The routed messages are limited
+/// by the names of the `RoutedMessageBody` variants instead.
+pub const PEER_MESSAGE_TYPES: &[&str] = &[
+    "Handshake",
+    "HandshakeFailure",
+    "LastEdge",
+    "SyncRoutingTable",
+    "RequestUpdateNonce",
+    "ResponseUpdateNonce",
+    "PeersRequest",
+    "PeersResponse",
+    "BlockHeadersRequest",
+    "BlockHeaders",
+    "BlockRequest",
+    "Block",
+    "Transaction",
+    "Disconnect",
+    "Challenge",
+    "EpochSyncRequest",
+    "EpochSyncResponse",
+    "EpochSyncFinalizationRequest",
+    "EpochSyncFinalizationResponse",
+];
+
+/// Token bucket limit: `per_second` tokens are added every second, up to `burst`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RateLimit {
+    pub per_second: u64,
+    pub burst: u64,
+}
+
+/// Quotas on the messages received from every peer, so that a peer flooding
+/// us with requests can't starve the processing of blocks.  Messages over
+/// quota are delayed until the quota allows them, and dropped if too many of
+/// them are waiting.  Consensus-critical messages (blocks, approvals,
+/// chunk parts and challenges) are limited by `consensus_bytes` rather than
+/// `per_peer_bytes`, so that other messages can't use up their quota, and
+/// the delayed ones are processed first.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct MessageQuotasConfig {
+    /// Limit on the bytes received from a peer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub per_peer_bytes: Option<RateLimit>,
+    /// Limit on the bytes of the consensus-critical messages received from a peer.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub consensus_bytes: Option<RateLimit>,
+    /// Limits on the number of messages of a type received from a peer,
+    /// keyed by the name of the `PeerMessage` variant or, for the routed
+    /// messages, of the `RoutedMessageBody` variant, e.g. `"StateRequestPart"`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub per_message_type: BTreeMap<String, RateLimit>,
+    /// Number of messages over quota kept per peer, the ones over it are dropped.
+    #[serde(default = "default_max_delayed_messages")]
+    pub max_delayed_messages: usize,
+    /// Total size of the messages over quota kept per peer, the ones over it
+    /// are dropped.
+    #[serde(default = "default_max_delayed_bytes")]
+    pub max_delayed_bytes: u64,
+}
+
+impl Default for MessageQuotasConfig {
+    fn default() -> Self {
+        Self {
+            per_peer_bytes: None,
+            consensus_bytes: None,
+            per_message_type: BTreeMap::new(),
+            max_delayed_messages: default_max_delayed_messages(),
+            max_delayed_bytes: default_max_delayed_bytes(),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Config {
@@ -63,6 +172,12 @@ pub struct Config {
     ///   ed25519:86EtEy7epneKyrcJwSWP7zsisTkfDRH5CFVszt4qiQYw@nearnode.com:24567
     #[serde(default)]
     pub whitelist_nodes: String,
+    /// File with nodes to connect to, in addition to `boot_nodes`, one per
+    /// line in the same format.  Host names are resolved and the file is
+    /// reloaded whenever it's modified, so the list can be updated without
+    /// restarting the node.  Lines starting with `#` are ignored.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed_file: Option<PathBuf>,
     /// Maximum number of active peers. Hard limit.
     #[serde(default = "default_max_num_peers")]
     pub max_num_peers: u32,
@@ -104,16 +219,28 @@ pub struct Config {
     /// Period to check on peer status
     #[serde(default = "default_peer_stats_period")]
     pub peer_stats_period: Duration,
+    /// Recording of the messages exchanged with the peers, disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub capture: Option<CaptureConfig>,
+    /// Talk to the peers only in the protobuf encoding, refusing the ones
+    /// which use the legacy borsh encoding.
+    #[serde(default)]
+    pub proto_only: bool,
+    /// Quotas on the messages received from every peer, none by default.
+    #[serde(default)]
+    pub message_quotas: MessageQuotasConfig,
 
     /// List of the public addresses (IP:port) of this node. Useful only if this node is a validator.
     /// This list will be signed and broadcasted to the whole network, so that everyone
-    /// knows how to reach the validator.
+    /// knows how to reach the validator, and other validators keep direct (TIER1) connections
+    /// to these addresses.
     ///
     /// Recommended setup (requires static IP):
     /// In the simplest case this list should contains just 1 public address of this validator.
     /// In case the validator doesn't have a public IP (i.e. it is hidden in a private network),
     /// this list should contain public addresses of the trusted nodes which will be routing messages to the
-    /// validator - validator will connect to these nodes immediately after startup.
+    /// validator, in the "peer_id@IP:port" format - validator will connect to these nodes immediately
+    /// after startup.
     ///
     /// Less recommended setup (requires exactly one public dynamic/ephemeral or static IP):
     /// If the list is empty, the validator node will query trusted_stun_servers to determine its own IP.
@@ -125,8 +252,7 @@ pub struct Config {
     /// If the list is empty and STUN servers' response is ambiguous, the peers which connect to
     /// this validator node will natually observe the address of the validator and broadcast it.
     /// This setup is not reliable in presence of byzantine peers.
-    /// TODO: unskip, once the functionality is implemented.
-    #[serde(skip)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub public_addrs: Vec<String>,
     /// List of endpoints of trusted STUN servers (https://datatracker.ietf.org/doc/html/rfc8489).
     /// Used only if this node is a validator and public_ips is empty (see description of
@@ -143,6 +269,7 @@ impl Default for Config {
             external_address: "".to_string(),
             boot_nodes: "".to_string(),
             whitelist_nodes: "".to_string(),
+            seed_file: None,
             max_num_peers: default_max_num_peers(),
             minimum_outbound_peers: default_minimum_outbound_connections(),
             ideal_connections_lo: default_ideal_connections_lo(),
@@ -157,6 +284,9 @@ impl Default for Config {
             blacklist: vec![],
             ttl_account_id_router: default_ttl_account_id_router(),
             peer_stats_period: default_peer_stats_period(),
+            capture: None,
+            proto_only: false,
+            message_quotas: MessageQuotasConfig::default(),
             public_addrs: vec![],
             trusted_stun_servers: vec![],
         }
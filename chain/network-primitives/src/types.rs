@@ -34,8 +34,12 @@ pub use crate::network_protocol::{
 };
 
 pub use crate::blacklist::{Blacklist, Entry as BlacklistEntry};
-pub use crate::config::{NetworkConfig, ValidatorConfig, ValidatorEndpoints};
-pub use crate::config_json::Config as ConfigJSON;
+pub use crate::config::{
+    NetworkConfig, ValidatorConfig, ValidatorEndpoints, MAX_PEERS_PER_VALIDATOR,
+};
+pub use crate::config_json::{
+    CaptureConfig, Config as ConfigJSON, MessageQuotasConfig, RateLimit, PEER_MESSAGE_TYPES,
+};
 pub use crate::network_protocol::edge::{Edge, EdgeState, PartialEdgeInfo};
 
 /// Number of hops a message is allowed to travel before being dropped.
@@ -160,6 +164,18 @@ impl KnownPeerStatus {
     }
 }
 
+/// What the node has learned about a peer from interacting with it.  Used to
+/// prefer reliable peers when choosing which peers to connect to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerScore {
+    /// Moving average of the round trip times of handshakes with the peer.
+    pub latency: Option<time::Duration>,
+    /// Number of requested blocks and chunk parts the peer has sent.
+    pub useful_messages: u64,
+    /// Number of times the peer misbehaved, e.g. got banned.
+    pub misbehaviours: u64,
+}
+
 /// Information node stores about known peers.
 #[derive(Debug, Clone)]
 pub struct KnownPeerState {
@@ -167,6 +183,7 @@ pub struct KnownPeerState {
     pub status: KnownPeerStatus,
     pub first_seen: time::Utc,
     pub last_seen: time::Utc,
+    pub score: PeerScore,
 }
 
 impl KnownPeerState {
@@ -176,6 +193,7 @@ impl KnownPeerState {
             status: KnownPeerStatus::Unknown,
             first_seen: now,
             last_seen: now,
+            score: PeerScore::default(),
         }
     }
 }
@@ -0,0 +1,287 @@
+//! Recording of the messages exchanged with the peers, for debugging.
+//!
+//! When `NetworkConfig::capture` is set, every `PeerActor` writes each message
+//! it sends or receives, exactly as it went over the wire, to the capture
+//! files in the configured directory.  A new file is started whenever the
+//! current one exceeds `max_file_size` and only the newest `max_files` files
+//! are kept.
+//!
+//! Messages are written by a dedicated thread.  Inbound messages are recorded
+//! before they are parsed, so that the invalid ones are captured too.
+//!
+//! Every record in a file is a borsh encoded `CapturedMessage` preceded by its
+//! length as a little endian `u32`, the same framing messages have on the
+//! wire.  Captures are read back with `read` and can be replayed against a
+//! node with `replay`.
+
+use crate::network_protocol::Encoding;
+use crate::types::PeerMessage;
+use anyhow::Context as _;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_network_primitives::time;
+use near_network_primitives::types::CaptureConfig;
+use near_primitives::network::PeerId;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use tracing::warn;
+
+mod replay;
+#[cfg(test)]
+mod tests;
+
+pub use replay::replay;
+
+const FILE_PREFIX: &str = "capture-";
+const FILE_SUFFIX: &str = ".bin";
+
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    /// Message received from the peer.
+    Inbound,
+    /// Message sent to the peer.
+    Outbound,
+}
+
+/// A message sent to or received from a peer.
+#[derive(BorshSerialize, BorshDeserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CapturedMessage {
+    /// Unix timestamp of the message in nanoseconds.
+    pub timestamp: i128,
+    /// The peer the message was exchanged with.  It's `None` for the messages
+    /// received on an inbound connection before the handshake completed.
+    pub peer_id: Option<PeerId>,
+    pub direction: Direction,
+    /// `None` if the encoding of the connection wasn't known yet when the
+    /// message was received.  Such messages are proto if they parse as proto
+    /// and borsh otherwise, like `PeerActor` parses them.
+    pub encoding: Option<Encoding>,
+    /// The message as it was sent over the wire.
+    pub bytes: Vec<u8>,
+}
+
+impl CapturedMessage {
+    pub fn timestamp(&self) -> anyhow::Result<time::Utc> {
+        Ok(time::Utc::from_unix_timestamp_nanos(self.timestamp)?)
+    }
+
+    /// Parses the message.  Inbound messages are recorded before they are
+    /// parsed, so this fails for the invalid messages peers sent.
+    pub fn message(&self) -> anyhow::Result<PeerMessage> {
+        let encoding = match self.encoding {
+            Some(encoding) => encoding,
+            None if PeerMessage::deserialize(Encoding::Proto, &self.bytes).is_ok() => {
+                Encoding::Proto
+            }
+            None => Encoding::Borsh,
+        };
+        Ok(PeerMessage::deserialize(encoding, &self.bytes)?)
+    }
+}
+
+// `Encoding` lives next to the `borsh` module of `network_protocol`, which
+// shadows the crate the derive macros refer to, hence the manual impls.
+impl BorshSerialize for Encoding {
+    fn serialize<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        let tag: u8 = match self {
+            Encoding::Borsh => 0,
+            Encoding::Proto => 1,
+        };
+        tag.serialize(writer)
+    }
+}
+
+impl BorshDeserialize for Encoding {
+    fn deserialize(buf: &mut &[u8]) -> io::Result<Self> {
+        match u8::deserialize(buf)? {
+            0 => Ok(Encoding::Borsh),
+            1 => Ok(Encoding::Proto),
+            tag => Err(io::Error::new(io::ErrorKind::InvalidData, format!("encoding {tag}"))),
+        }
+    }
+}
+
+fn file_index(path: &Path) -> Option<u64> {
+    let name = path.file_name()?.to_str()?;
+    name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_SUFFIX)?.parse().ok()
+}
+
+/// Capture files in the directory, oldest first.
+fn list_files(dir: &Path) -> anyhow::Result<Vec<(u64, PathBuf)>> {
+    let mut files = vec![];
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if let Some(index) = file_index(&path) {
+            files.push((index, path));
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Reads the messages of a capture file, or of all the capture files in a
+/// directory, in the order they were recorded.  A record cut short at the end
+/// of a file (e.g. because the node was killed) is skipped.
+pub fn read(path: &Path) -> anyhow::Result<Vec<CapturedMessage>> {
+    let paths = if path.is_dir() {
+        list_files(path)?.into_iter().map(|(_, path)| path).collect()
+    } else {
+        vec![path.to_path_buf()]
+    };
+    let mut messages = vec![];
+    for path in paths {
+        let mut data = vec![];
+        File::open(&path)
+            .and_then(|mut file| file.read_to_end(&mut data))
+            .with_context(|| format!("failed to read {}", path.display()))?;
+        let mut data = &data[..];
+        while data.len() >= 4 {
+            let len = u32::from_le_bytes(data[..4].try_into().unwrap()) as usize;
+            if data.len() < 4 + len {
+                break;
+            }
+            let message = CapturedMessage::try_from_slice(&data[4..4 + len])
+                .with_context(|| format!("invalid record in {}", path.display()))?;
+            messages.push(message);
+            data = &data[4 + len..];
+        }
+    }
+    Ok(messages)
+}
+
+/// Number of records waiting to be written, beyond which new messages are
+/// dropped rather than slowing down the node.
+const MAX_QUEUED_RECORDS: usize = 10_000;
+
+/// Records the captured messages.  Shared by all the `PeerActor`s of the
+/// node, which only encode the messages and hand them over to a dedicated
+/// thread writing them to the rotated capture files.
+pub(crate) struct Recorder {
+    records: Option<crossbeam_channel::Sender<Vec<u8>>>,
+    writer: Option<std::thread::JoinHandle<()>>,
+    /// Messages dropped because too many were waiting to be written.
+    dropped: Arc<AtomicU64>,
+}
+
+impl Recorder {
+    /// Files already in the directory are kept (and count towards
+    /// `max_files`), new ones are started after them.
+    pub fn new(cfg: CaptureConfig) -> anyhow::Result<Self> {
+        std::fs::create_dir_all(&cfg.dir)
+            .with_context(|| format!("failed to create {}", cfg.dir.display()))?;
+        let files = list_files(&cfg.dir)?;
+        let next_index = files.last().map_or(0, |(index, _)| index + 1);
+        let files = files.into_iter().map(|(_, path)| path).collect();
+        let dropped = Arc::new(AtomicU64::new(0));
+        let writer = Writer { cfg, next_index, file: None, files, dropped: dropped.clone() };
+        let (sender, receiver) = crossbeam_channel::bounded(MAX_QUEUED_RECORDS);
+        let writer = std::thread::Builder::new()
+            .name("network_capture".to_string())
+            .spawn(move || writer.run(receiver))
+            .context("failed to start the capture writer thread")?;
+        Ok(Self { records: Some(sender), writer: Some(writer), dropped })
+    }
+
+    /// Queues the message to be recorded.  Failures to write it are logged
+    /// rather than returned, so that a full disk doesn't affect the node.
+    pub fn record(
+        &self,
+        timestamp: time::Utc,
+        peer_id: Option<&PeerId>,
+        direction: Direction,
+        encoding: Option<Encoding>,
+        bytes: &[u8],
+    ) {
+        let message = CapturedMessage {
+            timestamp: timestamp.unix_timestamp_nanos(),
+            peer_id: peer_id.cloned(),
+            direction,
+            encoding,
+            bytes: bytes.to_vec(),
+        };
+        let record = message.try_to_vec().unwrap();
+        if self.records.as_ref().map_or(true, |records| records.try_send(record).is_err()) {
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Drop for Recorder {
+    /// Waits until the queued messages are written.
+    fn drop(&mut self) {
+        // The writer stops once the channel is disconnected and drained.
+        self.records = None;
+        if let Some(writer) = self.writer.take() {
+            if writer.join().is_err() {
+                warn!(target: "network", "Capture writer thread panicked");
+            }
+        }
+    }
+}
+
+struct Writer {
+    cfg: CaptureConfig,
+    /// Index of the next file to start.
+    next_index: u64,
+    /// The file being written and the number of bytes written to it.
+    file: Option<(BufWriter<File>, u64)>,
+    /// Capture files in the directory, oldest first.
+    files: VecDeque<PathBuf>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl Writer {
+    fn run(mut self, records: crossbeam_channel::Receiver<Vec<u8>>) {
+        while let Ok(record) = records.recv() {
+            let mut result = self.write(&record);
+            // Flush whenever there is nothing more to write, so that the
+            // capture is complete even if the node crashes, which is often the
+            // incident being debugged.
+            if result.is_ok() && records.is_empty() {
+                if let Some((file, _)) = &mut self.file {
+                    result = file.flush();
+                }
+            }
+            if let Err(err) = result {
+                warn!(target: "network", ?err, dir = ?self.cfg.dir, "Failed to capture a message");
+                // Start a new file on the next message.
+                self.file = None;
+            }
+            let dropped = self.dropped.swap(0, Ordering::Relaxed);
+            if dropped > 0 {
+                warn!(target: "network", dropped, "Capture can't keep up, dropped messages");
+            }
+        }
+    }
+
+    fn write(&mut self, record: &[u8]) -> io::Result<()> {
+        if self.file.as_ref().map_or(true, |(_, size)| *size >= self.cfg.max_file_size) {
+            self.start_file()?;
+        }
+        let (file, size) = self.file.as_mut().unwrap();
+        file.write_all(&(record.len() as u32).to_le_bytes())?;
+        file.write_all(record)?;
+        *size += 4 + record.len() as u64;
+        Ok(())
+    }
+
+    fn start_file(&mut self) -> io::Result<()> {
+        if let Some((mut file, _)) = self.file.take() {
+            file.flush()?;
+        }
+        let path = self.cfg.dir.join(format!("{FILE_PREFIX}{:06}{FILE_SUFFIX}", self.next_index));
+        self.next_index += 1;
+        self.file = Some((BufWriter::new(File::create(&path)?), 0));
+        self.files.push_back(path);
+        while self.files.len() > self.cfg.max_files.max(1) {
+            let path = self.files.pop_front().unwrap();
+            if let Err(err) = std::fs::remove_file(&path) {
+                warn!(target: "network", ?err, ?path, "Failed to remove an old capture file");
+            }
+        }
+        Ok(())
+    }
+}
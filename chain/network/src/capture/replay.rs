@@ -0,0 +1,128 @@
+use crate::capture::{CapturedMessage, Direction};
+use crate::network_protocol::Encoding;
+use crate::peer::codec::Codec;
+use crate::types::{Handshake, PeerMessage};
+use anyhow::{anyhow, bail, Context as _};
+use futures::{SinkExt as _, StreamExt as _};
+use near_crypto::{KeyType, SecretKey};
+use near_network_primitives::types::{PartialEdgeInfo, PeerInfo};
+use near_primitives::network::PeerId;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_util::codec::Framed;
+use tracing::debug;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether the message can be sent again on a new connection.  Handshakes and
+/// the edges of the original connection belong to that connection only.
+fn is_replayable(msg: &PeerMessage) -> bool {
+    !matches!(
+        msg,
+        PeerMessage::Handshake(_) | PeerMessage::HandshakeFailure(..) | PeerMessage::LastEdge(_)
+    )
+}
+
+/// Replays the messages a node received from its peers against the `target`
+/// node.  Connects to the target as a new peer, with a random key, and sends
+/// it the captured inbound messages in the order they were received.  Only
+/// the messages received from `from` are replayed if it's given.
+///
+/// The handshake is made with the protocol version and the chain info of the
+/// first replayed handshake, so the target has to be on the same chain as the
+/// captured node.  With `realtime` the original intervals between the messages
+/// are kept, otherwise they are sent as fast as possible.  Returns the number
+/// of replayed messages.
+pub async fn replay(
+    messages: &[CapturedMessage],
+    target: &PeerInfo,
+    from: Option<&PeerId>,
+    realtime: bool,
+) -> anyhow::Result<usize> {
+    let addr = target.addr.context("address of the target is required")?;
+    let mut selected = vec![];
+    for captured in messages.iter().filter(|m| m.direction == Direction::Inbound) {
+        let msg = match captured.message() {
+            Ok(msg) => msg,
+            Err(err) => {
+                debug!(target: "network", ?err, "Skipping invalid captured message");
+                continue;
+            }
+        };
+        let peer_id = match (&captured.peer_id, &msg) {
+            (Some(peer_id), _) => Some(peer_id),
+            (None, PeerMessage::Handshake(handshake)) => Some(&handshake.sender_peer_id),
+            (None, _) => None,
+        };
+        if from.map_or(true, |from| peer_id == Some(from)) {
+            selected.push((captured, msg));
+        }
+    }
+    let captured_handshake = selected
+        .iter()
+        .find_map(|(_, msg)| match msg {
+            PeerMessage::Handshake(handshake) => Some(handshake.clone()),
+            _ => None,
+        })
+        .context("no handshake to replay in the capture")?;
+
+    let key = SecretKey::from_random(KeyType::ED25519);
+    let my_peer_id = PeerId::new(key.public_key());
+    let stream =
+        TcpStream::connect(addr).await.with_context(|| format!("failed to connect to {addr}"))?;
+    // Nodes don't accept peers without an address, so we advertise the port
+    // of the connection, even though nothing listens on it.
+    let port = stream.local_addr()?.port();
+    let (mut sink, mut stream) = Framed::new(stream, Codec::default()).split();
+    let handshake = Handshake::new(
+        captured_handshake.protocol_version,
+        my_peer_id.clone(),
+        target.id.clone(),
+        Some(port),
+        captured_handshake.sender_chain_info,
+        PartialEdgeInfo::new(&my_peer_id, &target.id, 1, &key),
+    );
+    let protocol_version = handshake.protocol_version;
+    sink.send(PeerMessage::Handshake(handshake).serialize(Encoding::Proto, protocol_version))
+        .await?;
+    tokio::time::timeout(HANDSHAKE_TIMEOUT, async {
+        loop {
+            let bytes = stream
+                .next()
+                .await
+                .context("connection closed during the handshake")??
+                .map_err(|reason| anyhow!("invalid message from the target: {reason:?}"))?;
+            match PeerMessage::deserialize(Encoding::Proto, &bytes) {
+                Ok(PeerMessage::Handshake(_)) => return Ok(()),
+                Ok(PeerMessage::HandshakeFailure(_, reason)) => {
+                    bail!("handshake failed: {reason:?}")
+                }
+                _ => {}
+            }
+        }
+    })
+    .await
+    .context("handshake timed out")??;
+    debug!(target: "network", peer_id = %my_peer_id, "Replaying messages as");
+
+    // Drop whatever the target sends, so that it doesn't block on a full connection.
+    tokio::spawn(async move { while let Some(Ok(_)) = stream.next().await {} });
+
+    let start = tokio::time::Instant::now();
+    let first_timestamp = selected.first().map_or(0, |(captured, _)| captured.timestamp);
+    let mut count = 0;
+    for (captured, msg) in selected {
+        if !is_replayable(&msg) {
+            continue;
+        }
+        if realtime {
+            let offset = (captured.timestamp - first_timestamp).max(0) as u64;
+            tokio::time::sleep_until(start + Duration::from_nanos(offset)).await;
+        }
+        sink.send(msg.serialize(Encoding::Proto, protocol_version))
+            .await
+            .with_context(|| format!("failed to send message {count}"))?;
+        count += 1;
+    }
+    Ok(count)
+}
@@ -0,0 +1,138 @@
+use crate::capture::{read, replay, CapturedMessage, Direction, Recorder};
+use crate::network_protocol::testonly as data;
+use crate::network_protocol::Encoding;
+use crate::peer;
+use crate::peer_manager;
+use crate::peer_manager::testonly::Event;
+use crate::testonly::fake_client;
+use crate::testonly::make_rng;
+use crate::types::PeerMessage;
+use near_logger_utils::init_test_logger;
+use near_network_primitives::time;
+use near_network_primitives::types::{CaptureConfig, NetworkConfig, PeerInfo};
+use near_primitives::network::PeerId;
+use std::sync::Arc;
+use tokio::net::TcpStream;
+
+#[test]
+fn test_recorder_rotation() {
+    let dir = tempfile::tempdir().unwrap();
+    let cfg = CaptureConfig { dir: dir.path().to_path_buf(), max_file_size: 200, max_files: 2 };
+    let clock = time::FakeClock::default();
+    let peer_id = data::make_peer_id(&mut make_rng(921853233));
+
+    let recorder = Recorder::new(cfg.clone()).unwrap();
+    let mut want = vec![];
+    for i in 0..10u8 {
+        let bytes = vec![i; 50];
+        recorder.record(
+            clock.now_utc(),
+            Some(&peer_id),
+            Direction::Inbound,
+            Some(Encoding::Proto),
+            &bytes,
+        );
+        want.push(CapturedMessage {
+            timestamp: clock.now_utc().unix_timestamp_nanos(),
+            peer_id: Some(peer_id.clone()),
+            direction: Direction::Inbound,
+            encoding: Some(Encoding::Proto),
+            bytes,
+        });
+        clock.advance(time::Duration::SECOND);
+    }
+    // Dropping the recorder waits for the messages to be written.
+    drop(recorder);
+    // Every file fits 2 messages, only the last 2 files are kept.
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    assert_eq!(read(dir.path()).unwrap(), want[6..]);
+
+    // A new recorder continues after the existing files.
+    let recorder = Recorder::new(cfg).unwrap();
+    recorder.record(clock.now_utc(), None, Direction::Outbound, None, &[1, 2, 3]);
+    drop(recorder);
+    let got = read(dir.path()).unwrap();
+    assert_eq!(got.len(), 3);
+    assert_eq!(got[..2], want[8..]);
+    assert_eq!(got[2].bytes, vec![1, 2, 3]);
+}
+
+// Messages received by a node with capture enabled can be replayed against
+// another node.
+#[tokio::test]
+async fn capture_and_replay() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut cfg = NetworkConfig::from_seed("test1", crate::test_utils::open_port());
+    cfg.capture =
+        Some(CaptureConfig { dir: dir.path().to_path_buf(), max_file_size: 1 << 20, max_files: 2 });
+    let mut pm = peer_manager::testonly::start(chain.clone(), cfg).await;
+    let peer_cfg = peer::testonly::PeerConfig {
+        signer: data::make_signer(rng),
+        chain: chain.clone(),
+        peers: vec![],
+        start_handshake_with: Some(PeerId::new(pm.cfg.node_key.public_key())),
+        force_encoding: Some(Encoding::Proto),
+    };
+    let stream = TcpStream::connect(pm.cfg.node_addr.unwrap()).await.unwrap();
+    let mut peer =
+        peer::testonly::PeerHandle::start_endpoint(clock.clock(), peer_cfg, stream).await;
+    peer.complete_handshake().await;
+    let block = chain.blocks[5].clone();
+    peer.send(PeerMessage::Block(block.clone())).await;
+    pm.events
+        .recv_until(|ev| match ev {
+            Event::Client(fake_client::Event::Block(b)) if b == block => Some(()),
+            _ => None,
+        })
+        .await;
+
+    // The handshake and the block from the peer, and our handshake, were captured.
+    // Messages are written in the background, so wait for the block to be written.
+    let mut messages = loop {
+        let messages = read(dir.path()).unwrap();
+        if messages.iter().any(|m| m.message().ok() == Some(PeerMessage::Block(block.clone()))) {
+            break messages;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+    };
+    let inbound: Vec<_> = messages
+        .iter()
+        .filter(|m| m.direction == Direction::Inbound)
+        .map(|m| m.message().unwrap())
+        .collect();
+    assert!(matches!(&inbound[0], PeerMessage::Handshake(h) if h.sender_peer_id == peer.cfg.id()));
+    assert!(inbound.contains(&PeerMessage::Block(block.clone())));
+    assert!(messages.iter().any(|m| m.direction == Direction::Outbound
+        && m.peer_id == Some(peer.cfg.id())
+        && matches!(m.message().unwrap(), PeerMessage::Handshake(_))));
+
+    let mut pm2 = peer_manager::testonly::start(
+        chain.clone(),
+        NetworkConfig::from_seed("test2", crate::test_utils::open_port()),
+    )
+    .await;
+    let target =
+        PeerInfo::new(PeerId::new(pm2.cfg.node_key.public_key()), pm2.cfg.node_addr.unwrap());
+    // Invalid messages are captured too, but not replayed.
+    messages.push(CapturedMessage {
+        timestamp: clock.now_utc().unix_timestamp_nanos(),
+        peer_id: Some(peer.cfg.id()),
+        direction: Direction::Inbound,
+        encoding: None,
+        bytes: vec![0xff; 3],
+    });
+    let count = replay(&messages, &target, Some(&peer.cfg.id()), false).await.unwrap();
+    assert!(count > 0);
+    pm2.events
+        .recv_until(|ev| match ev {
+            Event::Client(fake_client::Event::Block(b)) if b == block => Some(()),
+            _ => None,
+        })
+        .await;
+}
@@ -1,8 +1,10 @@
 pub use crate::peer_manager::peer_manager_actor::{Event, PeerManagerActor};
+pub use crate::peer_manager::peer_source::{PeerSource, SeedFile};
 pub use crate::peer_manager::peer_store::iter_peers_from_store;
 #[cfg(feature = "test_features")]
 pub use crate::stats::metrics::RECEIVED_INFO_ABOUT_ITSELF;
 
+pub mod capture;
 mod network_protocol;
 mod peer;
 mod peer_manager;
@@ -26,29 +26,33 @@ use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, EpochId, ProtocolVersion};
+use near_primitives::validator_signer::ValidatorSigner;
 use near_primitives::version::PEER_MIN_ALLOWED_PROTOCOL_VERSION;
+use proto::peer_message::Message_type as ProtoMT;
 use protobuf::Message as _;
 use std::fmt;
 use thiserror::Error;
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct PeerAddr {
-    addr: std::net::SocketAddr,
-    peer_id: Option<PeerId>,
+    pub addr: std::net::SocketAddr,
+    pub peer_id: Option<PeerId>,
 }
 
 #[derive(PartialEq, Eq, Clone, Debug)]
 pub struct Validator {
-    peers: Vec<PeerAddr>,
-    account_id: AccountId,
-    epoch_id: EpochId,
-    timestamp: time::Utc,
+    /// Addresses of the validator node, or of the relay nodes routing
+    /// messages to it.
+    pub peers: Vec<PeerAddr>,
+    pub account_id: AccountId,
+    pub epoch_id: EpochId,
+    pub timestamp: time::Utc,
 }
 
 impl Validator {
-    pub fn sign(self, signer: &dyn near_crypto::Signer) -> SignedValidator {
+    pub fn sign(self, signer: &dyn ValidatorSigner) -> SignedValidator {
         let payload = proto::AccountKeyPayload::from(&self).write_to_bytes().unwrap();
-        let signature = signer.sign(&payload);
+        let signature = signer.sign_account_key_payload(&payload);
         SignedValidator { validator: self, payload: AccountKeySignedPayload { payload, signature } }
     }
 }
@@ -66,6 +70,17 @@ pub struct SignedValidator {
     payload: AccountKeySignedPayload,
 }
 
+impl SignedValidator {
+    pub fn validator(&self) -> &Validator {
+        &self.validator
+    }
+
+    /// Checks that the payload is signed with the given account key.
+    pub fn verify(&self, key: &near_crypto::PublicKey) -> bool {
+        self.payload.signature.verify(&self.payload.payload, key)
+    }
+}
+
 #[derive(PartialEq, Eq, Clone, Debug, Default)]
 pub struct RoutingTableUpdate {
     pub edges: Vec<Edge>,
@@ -82,8 +97,8 @@ impl RoutingTableUpdate {
         Self { edges: Vec::new(), accounts, validators: Vec::new() }
     }
 
-    pub(crate) fn new(edges: Vec<Edge>, accounts: Vec<AnnounceAccount>) -> Self {
-        Self { edges, accounts, validators: Vec::new() }
+    pub(crate) fn from_validators(validators: Vec<SignedValidator>) -> Self {
+        Self { edges: Vec::new(), accounts: Vec::new(), validators }
     }
 }
 /// Structure representing handshake between peers.
@@ -190,11 +205,31 @@ pub enum ParsePeerMessageError {
     ProtoConv(proto_conv::ParsePeerMessageError),
 }
 
+/// Protocol version from which the peers parse the native protobuf fields of
+/// the routed messages.  The routed messages sent to the older peers carry only
+/// the legacy borsh-encoded message.
+pub(crate) const PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION: ProtocolVersion = 57;
+
 impl PeerMessage {
-    pub(crate) fn serialize(&self, enc: Encoding) -> Vec<u8> {
+    /// Serializes the message for a peer using the given protocol version.
+    pub(crate) fn serialize(&self, enc: Encoding, protocol_version: ProtocolVersion) -> Vec<u8> {
         match enc {
             Encoding::Borsh => borsh::PeerMessage::from(self).try_to_vec().unwrap(),
-            Encoding::Proto => proto::PeerMessage::from(self).write_to_bytes().unwrap(),
+            Encoding::Proto => match self {
+                PeerMessage::Routed(r)
+                    if protocol_version < PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION =>
+                {
+                    proto::PeerMessage {
+                        message_type: Some(ProtoMT::Routed(
+                            proto_conv::routed_message_to_legacy_proto(r),
+                        )),
+                        ..Default::default()
+                    }
+                }
+                _ => proto::PeerMessage::from(self),
+            }
+            .write_to_bytes()
+            .unwrap(),
         }
     }
 
@@ -264,4 +299,22 @@ impl PeerMessage {
             _ => false,
         }
     }
+
+    /// Whether the message is needed for the consensus to make progress, so
+    /// it has to be processed before any other.
+    pub(crate) fn is_consensus_critical(&self) -> bool {
+        match self {
+            PeerMessage::Block(_) | PeerMessage::Challenge(_) => true,
+            PeerMessage::Routed(r) => matches!(
+                r.msg.body,
+                RoutedMessageBody::BlockApproval(_)
+                    | RoutedMessageBody::PartialEncodedChunk(_)
+                    | RoutedMessageBody::PartialEncodedChunkForward(_)
+                    | RoutedMessageBody::PartialEncodedChunkRequest(_)
+                    | RoutedMessageBody::PartialEncodedChunkResponse(_)
+                    | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            ),
+            _ => false,
+        }
+    }
 }
@@ -3,6 +3,7 @@ mod crypto;
 mod handshake;
 mod net;
 mod peer_message;
+mod routed;
 mod time;
 /// Contains protobuf <-> network_protocol conversions.
 mod util;
@@ -12,5 +13,7 @@ use crypto::*;
 use handshake::*;
 use net::*;
 pub(crate) use peer_message::*;
+pub(crate) use routed::routed_message_to_legacy_proto;
+use routed::*;
 use time::*;
 use util::*;
@@ -5,8 +5,6 @@ use crate::network_protocol::proto;
 use crate::network_protocol::proto::peer_message::Message_type as ProtoMT;
 use crate::network_protocol::{PeerMessage, RoutingTableUpdate};
 use borsh::{BorshDeserialize as _, BorshSerialize as _};
-use near_network_primitives::time::error::ComponentRange;
-use near_network_primitives::types::{RoutedMessage, RoutedMessageV2};
 use near_primitives::block::{Block, BlockHeader};
 use near_primitives::challenge::Challenge;
 use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
@@ -48,6 +46,9 @@ impl TryFrom<&proto::RoutingTableUpdate> for RoutingTableUpdate {
 
 //////////////////////////////////////////
 
+// TODO: blocks, headers, transactions, challenges and epoch sync responses are
+// still passed as borsh wrappers, see "Borsh wrappers" in docs/network.md.
+
 impl From<&BlockHeader> for proto::BlockHeader {
     fn from(x: &BlockHeader) -> Self {
         Self { borsh: x.try_to_vec().unwrap(), ..Default::default() }
@@ -136,11 +137,7 @@ impl From<&PeerMessage> for proto::PeerMessage {
                     borsh: t.try_to_vec().unwrap(),
                     ..Default::default()
                 }),
-                PeerMessage::Routed(r) => ProtoMT::Routed(proto::RoutedMessage {
-                    borsh: r.msg.try_to_vec().unwrap(),
-                    created_at: MF::from_option(r.created_at.as_ref().map(utc_to_proto)),
-                    ..Default::default()
-                }),
+                PeerMessage::Routed(r) => ProtoMT::Routed(r.as_ref().into()),
                 PeerMessage::Disconnect => ProtoMT::Disconnect(proto::Disconnect::new()),
                 PeerMessage::Challenge(r) => ProtoMT::Challenge(proto::Challenge {
                     borsh: r.try_to_vec().unwrap(),
@@ -177,7 +174,6 @@ impl From<&PeerMessage> for proto::PeerMessage {
 }
 
 pub type ParseTransactionError = borsh::maybestd::io::Error;
-pub type ParseRoutedError = borsh::maybestd::io::Error;
 pub type ParseChallengeError = borsh::maybestd::io::Error;
 pub type ParseEpochSyncResponseError = borsh::maybestd::io::Error;
 pub type ParseEpochSyncFinalizationResponseError = borsh::maybestd::io::Error;
@@ -222,8 +218,6 @@ pub enum ParsePeerMessageError {
     EpochSyncFinalizationRequest(ParseRequiredError<ParseCryptoHashError>),
     #[error("epoch_sync_finalization_response: {0}")]
     EpochSyncFinalizationResponse(ParseEpochSyncFinalizationResponseError),
-    #[error("routed_created_at: {0}")]
-    RoutedCreatedAtTimestamp(ComponentRange),
 }
 
 impl TryFrom<&proto::PeerMessage> for PeerMessage {
@@ -269,15 +263,9 @@ impl TryFrom<&proto::PeerMessage> for PeerMessage {
             ProtoMT::Transaction(t) => PeerMessage::Transaction(
                 SignedTransaction::try_from_slice(&t.borsh).map_err(Self::Error::Transaction)?,
             ),
-            ProtoMT::Routed(r) => PeerMessage::Routed(Box::new(RoutedMessageV2 {
-                msg: RoutedMessage::try_from_slice(&r.borsh).map_err(Self::Error::Routed)?,
-                created_at: r
-                    .created_at
-                    .as_ref()
-                    .map(utc_from_proto)
-                    .transpose()
-                    .map_err(Self::Error::RoutedCreatedAtTimestamp)?,
-            })),
+            ProtoMT::Routed(r) => {
+                PeerMessage::Routed(Box::new(r.try_into().map_err(Self::Error::Routed)?))
+            }
             ProtoMT::Disconnect(_) => PeerMessage::Disconnect,
             ProtoMT::Challenge(c) => PeerMessage::Challenge(
                 Challenge::try_from_slice(&c.borsh).map_err(Self::Error::Challenge)?,
@@ -0,0 +1,578 @@
+/// Conversion functions for RoutedMessage and the bodies it can carry.
+use super::*;
+
+use crate::network_protocol::proto;
+use crate::network_protocol::proto::peer_id_or_hash::Target_type as ProtoTT;
+use crate::network_protocol::proto::query_response::Response as ProtoQR;
+use crate::network_protocol::proto::routed_message_body::Body_type as ProtoBT;
+use borsh::{BorshDeserialize as _, BorshSerialize as _};
+use near_network_primitives::types::{
+    PartialEncodedChunkForwardMsg, PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg,
+    PeerIdOrHash, Ping, Pong, RoutedMessage, RoutedMessageBody, RoutedMessageV2, StateResponseInfo,
+    StateResponseInfoV1,
+};
+use near_primitives::account::id::ParseAccountError;
+use near_primitives::block_header::Approval;
+use near_primitives::sharding::{
+    ChunkHash, PartialEncodedChunk, PartialEncodedChunkPart, PartialEncodedChunkV1, ReceiptProof,
+};
+use near_primitives::transaction::SignedTransaction;
+use near_primitives::types::BlockReference;
+use near_primitives::views::{FinalExecutionOutcomeView, QueryRequest, QueryResponse};
+use protobuf::MessageField as MF;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParsePeerIdOrHashError {
+    #[error("empty target")]
+    Empty,
+    #[error("peer_id: {0}")]
+    PeerId(ParsePeerIdError),
+    #[error("hash: {0}")]
+    Hash(ParseCryptoHashError),
+}
+
+impl From<&PeerIdOrHash> for proto::PeerIdOrHash {
+    fn from(x: &PeerIdOrHash) -> Self {
+        Self {
+            target_type: Some(match x {
+                PeerIdOrHash::PeerId(peer_id) => ProtoTT::PeerId(peer_id.into()),
+                PeerIdOrHash::Hash(hash) => ProtoTT::Hash(hash.into()),
+            }),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::PeerIdOrHash> for PeerIdOrHash {
+    type Error = ParsePeerIdOrHashError;
+    fn try_from(x: &proto::PeerIdOrHash) -> Result<Self, Self::Error> {
+        Ok(match x.target_type.as_ref().ok_or(Self::Error::Empty)? {
+            ProtoTT::PeerId(peer_id) => {
+                Self::PeerId(peer_id.try_into().map_err(Self::Error::PeerId)?)
+            }
+            ProtoTT::Hash(hash) => Self::Hash(hash.try_into().map_err(Self::Error::Hash)?),
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+pub type ParsePartialEncodedChunkPartError = borsh::maybestd::io::Error;
+
+impl From<&PartialEncodedChunkPart> for proto::PartialEncodedChunkPart {
+    fn from(x: &PartialEncodedChunkPart) -> Self {
+        Self { borsh: x.try_to_vec().unwrap(), ..Self::default() }
+    }
+}
+
+impl TryFrom<&proto::PartialEncodedChunkPart> for PartialEncodedChunkPart {
+    type Error = ParsePartialEncodedChunkPartError;
+    fn try_from(x: &proto::PartialEncodedChunkPart) -> Result<Self, Self::Error> {
+        Self::try_from_slice(&x.borsh)
+    }
+}
+
+//////////////////////////////////////////
+
+pub type ParseReceiptProofError = borsh::maybestd::io::Error;
+
+impl From<&ReceiptProof> for proto::ReceiptProof {
+    fn from(x: &ReceiptProof) -> Self {
+        Self { borsh: x.try_to_vec().unwrap(), ..Self::default() }
+    }
+}
+
+impl TryFrom<&proto::ReceiptProof> for ReceiptProof {
+    type Error = ParseReceiptProofError;
+    fn try_from(x: &proto::ReceiptProof) -> Result<Self, Self::Error> {
+        Self::try_from_slice(&x.borsh)
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParsePartialEncodedChunkRequestError {
+    #[error("chunk_hash: {0}")]
+    ChunkHash(ParseRequiredError<ParseCryptoHashError>),
+}
+
+impl From<&PartialEncodedChunkRequestMsg> for proto::PartialEncodedChunkRequest {
+    fn from(x: &PartialEncodedChunkRequestMsg) -> Self {
+        // tracking_shards is a set, sort it to keep the encoding deterministic.
+        let mut tracking_shards: Vec<_> = x.tracking_shards.iter().copied().collect();
+        tracking_shards.sort();
+        Self {
+            chunk_hash: MF::some((&x.chunk_hash.0).into()),
+            part_ords: x.part_ords.clone(),
+            tracking_shards,
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::PartialEncodedChunkRequest> for PartialEncodedChunkRequestMsg {
+    type Error = ParsePartialEncodedChunkRequestError;
+    fn try_from(x: &proto::PartialEncodedChunkRequest) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunk_hash: ChunkHash(
+                try_from_required(&x.chunk_hash).map_err(Self::Error::ChunkHash)?,
+            ),
+            part_ords: x.part_ords.clone(),
+            tracking_shards: x.tracking_shards.iter().copied().collect(),
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParsePartialEncodedChunkResponseError {
+    #[error("chunk_hash: {0}")]
+    ChunkHash(ParseRequiredError<ParseCryptoHashError>),
+    #[error("parts: {0}")]
+    Parts(ParseVecError<ParsePartialEncodedChunkPartError>),
+    #[error("receipts: {0}")]
+    Receipts(ParseVecError<ParseReceiptProofError>),
+}
+
+impl From<&PartialEncodedChunkResponseMsg> for proto::PartialEncodedChunkResponse {
+    fn from(x: &PartialEncodedChunkResponseMsg) -> Self {
+        Self {
+            chunk_hash: MF::some((&x.chunk_hash.0).into()),
+            parts: x.parts.iter().map(Into::into).collect(),
+            receipts: x.receipts.iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::PartialEncodedChunkResponse> for PartialEncodedChunkResponseMsg {
+    type Error = ParsePartialEncodedChunkResponseError;
+    fn try_from(x: &proto::PartialEncodedChunkResponse) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunk_hash: ChunkHash(
+                try_from_required(&x.chunk_hash).map_err(Self::Error::ChunkHash)?,
+            ),
+            parts: try_from_slice(&x.parts).map_err(Self::Error::Parts)?,
+            receipts: try_from_slice(&x.receipts).map_err(Self::Error::Receipts)?,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParsePartialEncodedChunkForwardError {
+    #[error("chunk_hash: {0}")]
+    ChunkHash(ParseRequiredError<ParseCryptoHashError>),
+    #[error("inner_header_hash: {0}")]
+    InnerHeaderHash(ParseRequiredError<ParseCryptoHashError>),
+    #[error("merkle_root: {0}")]
+    MerkleRoot(ParseRequiredError<ParseCryptoHashError>),
+    #[error("signature: {0}")]
+    Signature(ParseRequiredError<ParseSignatureError>),
+    #[error("prev_block_hash: {0}")]
+    PrevBlockHash(ParseRequiredError<ParseCryptoHashError>),
+    #[error("parts: {0}")]
+    Parts(ParseVecError<ParsePartialEncodedChunkPartError>),
+}
+
+impl From<&PartialEncodedChunkForwardMsg> for proto::PartialEncodedChunkForward {
+    fn from(x: &PartialEncodedChunkForwardMsg) -> Self {
+        Self {
+            chunk_hash: MF::some((&x.chunk_hash.0).into()),
+            inner_header_hash: MF::some((&x.inner_header_hash).into()),
+            merkle_root: MF::some((&x.merkle_root).into()),
+            signature: MF::some((&x.signature).into()),
+            prev_block_hash: MF::some((&x.prev_block_hash).into()),
+            height_created: x.height_created,
+            shard_id: x.shard_id,
+            parts: x.parts.iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+}
+
+impl TryFrom<&proto::PartialEncodedChunkForward> for PartialEncodedChunkForwardMsg {
+    type Error = ParsePartialEncodedChunkForwardError;
+    fn try_from(x: &proto::PartialEncodedChunkForward) -> Result<Self, Self::Error> {
+        Ok(Self {
+            chunk_hash: ChunkHash(
+                try_from_required(&x.chunk_hash).map_err(Self::Error::ChunkHash)?,
+            ),
+            inner_header_hash: try_from_required(&x.inner_header_hash)
+                .map_err(Self::Error::InnerHeaderHash)?,
+            merkle_root: try_from_required(&x.merkle_root).map_err(Self::Error::MerkleRoot)?,
+            signature: try_from_required(&x.signature).map_err(Self::Error::Signature)?,
+            prev_block_hash: try_from_required(&x.prev_block_hash)
+                .map_err(Self::Error::PrevBlockHash)?,
+            height_created: x.height_created,
+            shard_id: x.shard_id,
+            parts: try_from_slice(&x.parts).map_err(Self::Error::Parts)?,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParsePingError {
+    #[error("source: {0}")]
+    Source(ParseRequiredError<ParsePeerIdError>),
+}
+
+impl From<&Ping> for proto::Ping {
+    fn from(x: &Ping) -> Self {
+        Self { nonce: x.nonce, source: MF::some((&x.source).into()), ..Self::default() }
+    }
+}
+
+impl TryFrom<&proto::Ping> for Ping {
+    type Error = ParsePingError;
+    fn try_from(x: &proto::Ping) -> Result<Self, Self::Error> {
+        Ok(Self {
+            nonce: x.nonce,
+            source: try_from_required(&x.source).map_err(Self::Error::Source)?,
+        })
+    }
+}
+
+impl From<&Pong> for proto::Pong {
+    fn from(x: &Pong) -> Self {
+        Self { nonce: x.nonce, source: MF::some((&x.source).into()), ..Self::default() }
+    }
+}
+
+impl TryFrom<&proto::Pong> for Pong {
+    type Error = ParsePingError;
+    fn try_from(x: &proto::Pong) -> Result<Self, Self::Error> {
+        Ok(Self {
+            nonce: x.nonce,
+            source: try_from_required(&x.source).map_err(Self::Error::Source)?,
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+impl From<&RoutedMessageBody> for proto::RoutedMessageBody {
+    fn from(x: &RoutedMessageBody) -> Self {
+        Self {
+            body_type: match x {
+                RoutedMessageBody::BlockApproval(a) => {
+                    Some(ProtoBT::BlockApproval(proto::Approval {
+                        borsh: a.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::ForwardTx(t) => {
+                    Some(ProtoBT::ForwardTx(proto::SignedTransaction {
+                        borsh: t.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::TxStatusRequest(account_id, tx_hash) => {
+                    Some(ProtoBT::TxStatusRequest(proto::TxStatusRequest {
+                        account_id: account_id.to_string(),
+                        tx_hash: MF::some(tx_hash.into()),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::TxStatusResponse(o) => {
+                    Some(ProtoBT::TxStatusResponse(proto::FinalExecutionOutcomeView {
+                        borsh: o.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::QueryRequest { query_id, block_reference, request } => {
+                    Some(ProtoBT::QueryRequest(proto::QueryRequest {
+                        query_id: query_id.clone(),
+                        block_reference: MF::some(proto::BlockReference {
+                            borsh: block_reference.try_to_vec().unwrap(),
+                            ..Default::default()
+                        }),
+                        request: MF::some(proto::Query {
+                            borsh: request.try_to_vec().unwrap(),
+                            ..Default::default()
+                        }),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::QueryResponse { query_id, response } => {
+                    Some(ProtoBT::QueryResponse(proto::QueryResponse {
+                        query_id: query_id.clone(),
+                        response: Some(match response {
+                            Ok(r) => ProtoQR::Result(proto::QueryResult {
+                                borsh: r.try_to_vec().unwrap(),
+                                ..Default::default()
+                            }),
+                            Err(err) => ProtoQR::Error(err.clone()),
+                        }),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::ReceiptOutcomeRequest(receipt_id) => {
+                    Some(ProtoBT::ReceiptOutcomeRequest(proto::ReceiptOutcomeRequest {
+                        receipt_id: MF::some(receipt_id.into()),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::Unused => None,
+                RoutedMessageBody::StateRequestHeader(shard_id, sync_hash) => {
+                    Some(ProtoBT::StateRequestHeader(proto::StateRequestHeader {
+                        shard_id: *shard_id,
+                        sync_hash: MF::some(sync_hash.into()),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::StateRequestPart(shard_id, sync_hash, part_id) => {
+                    Some(ProtoBT::StateRequestPart(proto::StateRequestPart {
+                        shard_id: *shard_id,
+                        sync_hash: MF::some(sync_hash.into()),
+                        part_id: *part_id,
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::StateResponse(r) => {
+                    Some(ProtoBT::StateResponse(proto::StateResponseInfoV1 {
+                        borsh: r.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::PartialEncodedChunkRequest(r) => {
+                    Some(ProtoBT::PartialEncodedChunkRequest(r.into()))
+                }
+                RoutedMessageBody::PartialEncodedChunkResponse(r) => {
+                    Some(ProtoBT::PartialEncodedChunkResponse(r.into()))
+                }
+                RoutedMessageBody::PartialEncodedChunk(c) => {
+                    Some(ProtoBT::PartialEncodedChunk(proto::PartialEncodedChunkV1 {
+                        borsh: c.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::Ping(p) => Some(ProtoBT::Ping(p.into())),
+                RoutedMessageBody::Pong(p) => Some(ProtoBT::Pong(p.into())),
+                RoutedMessageBody::VersionedPartialEncodedChunk(c) => {
+                    Some(ProtoBT::VersionedPartialEncodedChunk(proto::PartialEncodedChunk {
+                        borsh: c.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::VersionedStateResponse(r) => {
+                    Some(ProtoBT::VersionedStateResponse(proto::StateResponseInfo {
+                        borsh: r.try_to_vec().unwrap(),
+                        ..Default::default()
+                    }))
+                }
+                RoutedMessageBody::PartialEncodedChunkForward(f) => {
+                    Some(ProtoBT::PartialEncodedChunkForward(f.into()))
+                }
+            },
+            ..Default::default()
+        }
+    }
+}
+
+pub type ParseBorshPayloadError = borsh::maybestd::io::Error;
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseRoutedMessageBodyError {
+    #[error("empty body")]
+    Empty,
+    #[error("block_approval: {0}")]
+    BlockApproval(ParseBorshPayloadError),
+    #[error("forward_tx: {0}")]
+    ForwardTx(ParseBorshPayloadError),
+    #[error("tx_status_request.account_id: {0}")]
+    TxStatusRequestAccountId(ParseAccountError),
+    #[error("tx_status_request.tx_hash: {0}")]
+    TxStatusRequestTxHash(ParseRequiredError<ParseCryptoHashError>),
+    #[error("tx_status_response: {0}")]
+    TxStatusResponse(ParseBorshPayloadError),
+    #[error("query_request.block_reference: {0}")]
+    QueryRequestBlockReference(ParseRequiredError<ParseBorshPayloadError>),
+    #[error("query_request.request: {0}")]
+    QueryRequestRequest(ParseRequiredError<ParseBorshPayloadError>),
+    #[error("query_response.response: {0}")]
+    QueryResponseResponse(ParseRequiredError<ParseBorshPayloadError>),
+    #[error("receipt_outcome_request: {0}")]
+    ReceiptOutcomeRequest(ParseRequiredError<ParseCryptoHashError>),
+    #[error("state_request_header: {0}")]
+    StateRequestHeader(ParseRequiredError<ParseCryptoHashError>),
+    #[error("state_request_part: {0}")]
+    StateRequestPart(ParseRequiredError<ParseCryptoHashError>),
+    #[error("state_response: {0}")]
+    StateResponse(ParseBorshPayloadError),
+    #[error("partial_encoded_chunk_request: {0}")]
+    PartialEncodedChunkRequest(ParsePartialEncodedChunkRequestError),
+    #[error("partial_encoded_chunk_response: {0}")]
+    PartialEncodedChunkResponse(ParsePartialEncodedChunkResponseError),
+    #[error("partial_encoded_chunk: {0}")]
+    PartialEncodedChunk(ParseBorshPayloadError),
+    #[error("ping: {0}")]
+    Ping(ParsePingError),
+    #[error("pong: {0}")]
+    Pong(ParsePingError),
+    #[error("versioned_partial_encoded_chunk: {0}")]
+    VersionedPartialEncodedChunk(ParseBorshPayloadError),
+    #[error("versioned_state_response: {0}")]
+    VersionedStateResponse(ParseBorshPayloadError),
+    #[error("partial_encoded_chunk_forward: {0}")]
+    PartialEncodedChunkForward(ParsePartialEncodedChunkForwardError),
+}
+
+impl TryFrom<&proto::RoutedMessageBody> for RoutedMessageBody {
+    type Error = ParseRoutedMessageBodyError;
+    fn try_from(x: &proto::RoutedMessageBody) -> Result<Self, Self::Error> {
+        Ok(match x.body_type.as_ref().ok_or(Self::Error::Empty)? {
+            ProtoBT::BlockApproval(a) => Self::BlockApproval(
+                Approval::try_from_slice(&a.borsh).map_err(Self::Error::BlockApproval)?,
+            ),
+            ProtoBT::ForwardTx(t) => Self::ForwardTx(
+                SignedTransaction::try_from_slice(&t.borsh).map_err(Self::Error::ForwardTx)?,
+            ),
+            ProtoBT::TxStatusRequest(r) => Self::TxStatusRequest(
+                r.account_id.clone().try_into().map_err(Self::Error::TxStatusRequestAccountId)?,
+                try_from_required(&r.tx_hash).map_err(Self::Error::TxStatusRequestTxHash)?,
+            ),
+            ProtoBT::TxStatusResponse(o) => Self::TxStatusResponse(
+                FinalExecutionOutcomeView::try_from_slice(&o.borsh)
+                    .map_err(Self::Error::TxStatusResponse)?,
+            ),
+            ProtoBT::QueryRequest(r) => Self::QueryRequest {
+                query_id: r.query_id.clone(),
+                block_reference: map_from_required(&r.block_reference, |b| {
+                    BlockReference::try_from_slice(&b.borsh)
+                })
+                .map_err(Self::Error::QueryRequestBlockReference)?,
+                request: map_from_required(&r.request, |q| QueryRequest::try_from_slice(&q.borsh))
+                    .map_err(Self::Error::QueryRequestRequest)?,
+            },
+            ProtoBT::QueryResponse(r) => Self::QueryResponse {
+                query_id: r.query_id.clone(),
+                response: match r.response.as_ref() {
+                    Some(ProtoQR::Result(q)) => Ok(QueryResponse::try_from_slice(&q.borsh)
+                        .map_err(|err| {
+                            Self::Error::QueryResponseResponse(ParseRequiredError::Other(err))
+                        })?),
+                    Some(ProtoQR::Error(err)) => Err(err.clone()),
+                    None => {
+                        return Err(Self::Error::QueryResponseResponse(ParseRequiredError::Missing))
+                    }
+                },
+            },
+            ProtoBT::ReceiptOutcomeRequest(r) => Self::ReceiptOutcomeRequest(
+                try_from_required(&r.receipt_id).map_err(Self::Error::ReceiptOutcomeRequest)?,
+            ),
+            ProtoBT::StateRequestHeader(r) => Self::StateRequestHeader(
+                r.shard_id,
+                try_from_required(&r.sync_hash).map_err(Self::Error::StateRequestHeader)?,
+            ),
+            ProtoBT::StateRequestPart(r) => Self::StateRequestPart(
+                r.shard_id,
+                try_from_required(&r.sync_hash).map_err(Self::Error::StateRequestPart)?,
+                r.part_id,
+            ),
+            ProtoBT::StateResponse(r) => Self::StateResponse(
+                StateResponseInfoV1::try_from_slice(&r.borsh)
+                    .map_err(Self::Error::StateResponse)?,
+            ),
+            ProtoBT::PartialEncodedChunkRequest(r) => Self::PartialEncodedChunkRequest(
+                r.try_into().map_err(Self::Error::PartialEncodedChunkRequest)?,
+            ),
+            ProtoBT::PartialEncodedChunkResponse(r) => Self::PartialEncodedChunkResponse(
+                r.try_into().map_err(Self::Error::PartialEncodedChunkResponse)?,
+            ),
+            ProtoBT::PartialEncodedChunk(c) => Self::PartialEncodedChunk(
+                PartialEncodedChunkV1::try_from_slice(&c.borsh)
+                    .map_err(Self::Error::PartialEncodedChunk)?,
+            ),
+            ProtoBT::Ping(p) => Self::Ping(p.try_into().map_err(Self::Error::Ping)?),
+            ProtoBT::Pong(p) => Self::Pong(p.try_into().map_err(Self::Error::Pong)?),
+            ProtoBT::VersionedPartialEncodedChunk(c) => Self::VersionedPartialEncodedChunk(
+                PartialEncodedChunk::try_from_slice(&c.borsh)
+                    .map_err(Self::Error::VersionedPartialEncodedChunk)?,
+            ),
+            ProtoBT::VersionedStateResponse(r) => Self::VersionedStateResponse(
+                StateResponseInfo::try_from_slice(&r.borsh)
+                    .map_err(Self::Error::VersionedStateResponse)?,
+            ),
+            ProtoBT::PartialEncodedChunkForward(f) => Self::PartialEncodedChunkForward(
+                f.try_into().map_err(Self::Error::PartialEncodedChunkForward)?,
+            ),
+        })
+    }
+}
+
+//////////////////////////////////////////
+
+#[derive(thiserror::Error, Debug)]
+pub enum ParseRoutedError {
+    #[error("borsh: {0}")]
+    Borsh(borsh::maybestd::io::Error),
+    #[error("created_at: {0}")]
+    CreatedAt(ParseTimestampError),
+    #[error("target: {0}")]
+    Target(ParseRequiredError<ParsePeerIdOrHashError>),
+    #[error("author: {0}")]
+    Author(ParseRequiredError<ParsePeerIdError>),
+    #[error("signature: {0}")]
+    Signature(ParseRequiredError<ParseSignatureError>),
+    #[error("ttl: {0}")]
+    Ttl(std::num::TryFromIntError),
+    #[error("body: {0}")]
+    Body(ParseRoutedMessageBodyError),
+}
+
+impl From<&RoutedMessageV2> for proto::RoutedMessage {
+    fn from(x: &RoutedMessageV2) -> Self {
+        Self {
+            created_at: MF::from_option(x.created_at.as_ref().map(utc_to_proto)),
+            target: MF::some((&x.msg.target).into()),
+            author: MF::some((&x.msg.author).into()),
+            signature: MF::some((&x.msg.signature).into()),
+            ttl: x.msg.ttl.into(),
+            body: MF::some((&x.msg.body).into()),
+            ..Self::default()
+        }
+    }
+}
+
+/// Encodes the message for the peers which parse only the borsh-encoded
+/// message, see `PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION`.
+pub(crate) fn routed_message_to_legacy_proto(x: &RoutedMessageV2) -> proto::RoutedMessage {
+    proto::RoutedMessage {
+        borsh: x.msg.try_to_vec().unwrap(),
+        created_at: MF::from_option(x.created_at.as_ref().map(utc_to_proto)),
+        ..Default::default()
+    }
+}
+
+impl TryFrom<&proto::RoutedMessage> for RoutedMessageV2 {
+    type Error = ParseRoutedError;
+    fn try_from(x: &proto::RoutedMessage) -> Result<Self, Self::Error> {
+        // Messages without a body come from the nodes which send the whole
+        // message borsh-encoded.
+        let msg = match x.body.as_ref() {
+            None => RoutedMessage::try_from_slice(&x.borsh).map_err(Self::Error::Borsh)?,
+            Some(body) => RoutedMessage {
+                target: try_from_required(&x.target).map_err(Self::Error::Target)?,
+                author: try_from_required(&x.author).map_err(Self::Error::Author)?,
+                signature: try_from_required(&x.signature).map_err(Self::Error::Signature)?,
+                ttl: u8::try_from(x.ttl).map_err(Self::Error::Ttl)?,
+                body: body.try_into().map_err(Self::Error::Body)?,
+            },
+        };
+        Ok(Self {
+            msg,
+            created_at: x
+                .created_at
+                .as_ref()
+                .map(utc_from_proto)
+                .transpose()
+                .map_err(Self::Error::CreatedAt)?,
+        })
+    }
+}
@@ -4,22 +4,37 @@ use crate::types::{Handshake, RoutingTableUpdate};
 use near_crypto::{InMemorySigner, KeyType, SecretKey};
 use near_network_primitives::time;
 use near_network_primitives::types::{
-    AccountOrPeerIdOrHash, Edge, PartialEdgeInfo, PeerChainInfoV2, PeerInfo, RawRoutedMessage,
-    RoutedMessageBody,
+    AccountOrPeerIdOrHash, Edge, PartialEdgeInfo, PartialEncodedChunkForwardMsg,
+    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, PeerChainInfoV2, PeerInfo, Ping,
+    Pong, RawRoutedMessage, RoutedMessageBody, StateResponseInfo, StateResponseInfoV1,
+    StateResponseInfoV2,
 };
 use near_primitives::block::{genesis_chunks, Block, BlockHeader, GenesisId};
+use near_primitives::block_header::Approval;
 use near_primitives::challenge::{BlockDoubleSign, Challenge, ChallengeBody};
 use near_primitives::hash::CryptoHash;
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::num_rational::Ratio;
 use near_primitives::sharding::{
-    ChunkHash, EncodedShardChunk, EncodedShardChunkBody, PartialEncodedChunkPart,
-    ReedSolomonWrapper, ShardChunk,
+    ChunkHash, EncodedShardChunk, EncodedShardChunkBody, PartialEncodedChunk,
+    PartialEncodedChunkPart, PartialEncodedChunkV1, PartialEncodedChunkV2, ReedSolomonWrapper,
+    ShardChunk, ShardChunkHeaderV1,
+};
+use near_primitives::syncing::{
+    ShardStateSyncResponse, ShardStateSyncResponseV1, ShardStateSyncResponseV2,
+};
+use near_primitives::transaction::{
+    ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof, SignedTransaction,
+};
+use near_primitives::types::{
+    AccountId, BlockHeight, BlockReference, EpochId, Finality, StateRoot,
 };
-use near_primitives::transaction::SignedTransaction;
-use near_primitives::types::{AccountId, BlockHeight, EpochId, StateRoot};
 use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
 use near_primitives::version::PROTOCOL_VERSION;
+use near_primitives::views::{
+    ContractCodeView, FinalExecutionOutcomeView, FinalExecutionStatus, QueryRequest, QueryResponse,
+    QueryResponseKind,
+};
 use rand::distributions::Standard;
 use rand::Rng;
 use std::collections::HashMap;
@@ -294,6 +309,122 @@ pub fn make_routed_message<R: Rng>(rng: &mut R, body: RoutedMessageBody) -> Box<
         None,
     )
 }
+
+/// Makes a body of every `RoutedMessageBody` variant, except for `Unused`.
+pub fn make_routed_message_bodies<R: Rng>(rng: &mut R, chain: &Chain) -> Vec<RoutedMessageBody> {
+    let block = &chain.blocks[chain.blocks.len() - 1];
+    let prev = &chain.blocks[chain.blocks.len() - 2];
+    let chunk = chain.chunks[&block.chunks()[0].chunk_hash()].clone();
+    let header = chunk.cloned_header();
+    let parts = make_chunk_parts(chunk);
+    let signer = make_validator_signer(rng);
+    let tx = make_signed_transaction(rng);
+    let peer_id = make_peer_id(rng);
+    let header_v1 = ShardChunkHeaderV1::new(
+        *prev.hash(),            // prev_block_hash
+        StateRoot::default(),    // prev_state_root
+        CryptoHash::default(),   // outcome_root
+        CryptoHash::default(),   // encoded_merkle_root
+        0,                       // encoded_length
+        block.header().height(), // height
+        0,                       // shard_id
+        0,                       // gas_used
+        1000,                    // gas_limit
+        0,                       // balance_burnt
+        CryptoHash::default(),   // outgoing_receipts_root
+        CryptoHash::default(),   // tx_root
+        vec![],                  // validator_proposals
+        &signer,
+    );
+    let state_part = (3, rng.sample_iter(&Standard).take(100).collect::<Vec<u8>>());
+    vec![
+        RoutedMessageBody::BlockApproval(Approval::new(
+            *prev.hash(),
+            prev.header().height(),
+            block.header().height(),
+            &signer,
+        )),
+        RoutedMessageBody::ForwardTx(tx.clone()),
+        RoutedMessageBody::TxStatusRequest(tx.transaction.signer_id.clone(), tx.get_hash()),
+        RoutedMessageBody::TxStatusResponse(FinalExecutionOutcomeView {
+            status: FinalExecutionStatus::SuccessValue("".to_string()),
+            transaction: tx.clone().into(),
+            transaction_outcome: ExecutionOutcomeWithIdAndProof {
+                proof: vec![],
+                block_hash: *block.hash(),
+                outcome_with_id: ExecutionOutcomeWithId {
+                    id: tx.get_hash(),
+                    outcome: Default::default(),
+                },
+            }
+            .into(),
+            receipts_outcome: vec![],
+        }),
+        RoutedMessageBody::QueryRequest {
+            query_id: format!("query{}", rng.gen::<u32>()),
+            block_reference: BlockReference::Finality(Finality::Final),
+            request: QueryRequest::ViewAccount { account_id: make_account_id(rng) },
+        },
+        RoutedMessageBody::QueryResponse {
+            query_id: format!("query{}", rng.gen::<u32>()),
+            response: Ok(QueryResponse {
+                kind: QueryResponseKind::ViewCode(ContractCodeView {
+                    code: rng.sample_iter(&Standard).take(100).collect(),
+                    hash: CryptoHash::hash_bytes(&rng.gen::<[u8; 32]>()),
+                }),
+                block_height: block.header().height(),
+                block_hash: *block.hash(),
+            }),
+        },
+        RoutedMessageBody::ReceiptOutcomeRequest(CryptoHash::hash_bytes(&rng.gen::<[u8; 32]>())),
+        RoutedMessageBody::StateRequestHeader(0, *block.hash()),
+        RoutedMessageBody::StateRequestPart(0, *block.hash(), 3),
+        RoutedMessageBody::StateResponse(StateResponseInfoV1 {
+            shard_id: 0,
+            sync_hash: *block.hash(),
+            state_response: ShardStateSyncResponseV1 {
+                header: None,
+                part: Some(state_part.clone()),
+            },
+        }),
+        RoutedMessageBody::PartialEncodedChunkRequest(PartialEncodedChunkRequestMsg {
+            chunk_hash: header.chunk_hash(),
+            part_ords: vec![0, 2, 4],
+            tracking_shards: [0, 1, 3].into_iter().collect(),
+        }),
+        RoutedMessageBody::PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg {
+            chunk_hash: header.chunk_hash(),
+            parts: parts.clone(),
+            receipts: vec![],
+        }),
+        RoutedMessageBody::PartialEncodedChunk(PartialEncodedChunkV1 {
+            header: header_v1,
+            parts: parts[..2].to_vec(),
+            receipts: vec![],
+        }),
+        RoutedMessageBody::Ping(Ping { nonce: rng.gen(), source: peer_id.clone() }),
+        RoutedMessageBody::Pong(Pong { nonce: rng.gen(), source: peer_id }),
+        RoutedMessageBody::VersionedPartialEncodedChunk(PartialEncodedChunk::V2(
+            PartialEncodedChunkV2 {
+                header: header.clone(),
+                parts: parts[..2].to_vec(),
+                receipts: vec![],
+            },
+        )),
+        RoutedMessageBody::VersionedStateResponse(StateResponseInfo::V2(StateResponseInfoV2 {
+            shard_id: 0,
+            sync_hash: *block.hash(),
+            state_response: ShardStateSyncResponse::V2(ShardStateSyncResponseV2 {
+                header: None,
+                part: Some(state_part),
+            }),
+        })),
+        RoutedMessageBody::PartialEncodedChunkForward(
+            PartialEncodedChunkForwardMsg::from_header_and_parts(&header, parts),
+        ),
+    ]
+}
+
 pub fn make_ipv4(rng: &mut impl Rng) -> net::IpAddr {
     net::IpAddr::V4(net::Ipv4Addr::from(rng.gen::<[u8; 4]>()))
 }
@@ -335,6 +466,6 @@ pub fn make_validator(rng: &mut impl Rng, clock: &time::Clock, account_id: Accou
 }
 
 pub fn make_signed_validator(rng: &mut impl Rng, clock: &time::Clock) -> SignedValidator {
-    let signer = make_signer(rng);
-    make_validator(rng, clock, signer.account_id.clone()).sign(&signer)
+    let signer = make_validator_signer(rng);
+    make_validator(rng, clock, signer.validator_id().clone()).sign(&signer)
 }
@@ -1,14 +1,19 @@
 use crate::network_protocol::testonly as data;
-use crate::network_protocol::Encoding;
+use crate::network_protocol::{proto, Encoding, PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION};
 use crate::testonly::make_rng;
 use crate::types::{HandshakeFailureReason, PeerMessage};
-use anyhow::{bail, Context as _};
+use anyhow::{anyhow, bail, Context as _};
+use borsh::{BorshDeserialize as _, BorshSerialize as _};
 use near_network_primitives::time;
-use near_network_primitives::types::{
-    PartialEncodedChunkRequestMsg, PartialEncodedChunkResponseMsg, RoutedMessageBody,
-};
-use near_primitives::syncing::EpochSyncResponse;
-use near_primitives::types::EpochId;
+use near_network_primitives::types::RoutedMessage;
+use near_primitives::serialize::{from_base64, to_base64};
+use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse};
+use near_primitives::types::{EpochId, ProtocolVersion};
+use near_primitives::version::{PEER_MIN_ALLOWED_PROTOCOL_VERSION, PROTOCOL_VERSION};
+use protobuf::Message as _;
+use rand::Rng;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
 
 // TODO: RoutingTableUpdate.validators field is supported only in proto encoding.
 // Remove this test once borsh support is removed.
@@ -18,98 +23,116 @@ fn serialize_deserialize_protobuf_only() {
     let clock = time::FakeClock::default();
     let rt = data::make_routing_table(&mut rng, &clock.clock());
     let m = PeerMessage::SyncRoutingTable(rt);
-    let m2 = PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto)).unwrap();
+    let m2 =
+        PeerMessage::deserialize(Encoding::Proto, &m.serialize(Encoding::Proto, PROTOCOL_VERSION))
+            .unwrap();
     assert_eq!(m, m2);
 }
 
-#[test]
-fn serialize_deserialize() -> anyhow::Result<()> {
-    let mut rng = make_rng(89028037453);
-    let mut clock = time::FakeClock::default();
-
-    let chain = data::Chain::make(&mut clock, &mut rng, 12);
-    let a = data::make_signer(&mut rng);
-    let b = data::make_signer(&mut rng);
+/// Makes a message of every `PeerMessage` variant, and a `Routed` message
+/// of every `RoutedMessageBody` variant.
+fn make_messages<R: Rng>(rng: &mut R, clock: &mut time::FakeClock) -> Vec<PeerMessage> {
+    let chain = data::Chain::make(clock, rng, 6);
+    let a = data::make_signer(rng);
+    let b = data::make_signer(rng);
     let edge = data::make_edge(&a, &b);
     let epoch_id = EpochId(chain.blocks[1].hash().clone());
+    let header = chain.blocks[5].header();
 
-    let chunk_hash = chain.blocks[3].chunks()[0].chunk_hash();
-    let routed_message1 = data::make_routed_message(
-        &mut rng,
-        RoutedMessageBody::PartialEncodedChunkRequest(PartialEncodedChunkRequestMsg {
-            chunk_hash: chunk_hash.clone(),
-            part_ords: vec![],
-            tracking_shards: Default::default(),
-        }),
-    );
-    let routed_message2 = data::make_routed_message(
-        &mut rng,
-        RoutedMessageBody::PartialEncodedChunkResponse(PartialEncodedChunkResponseMsg {
-            chunk_hash: chunk_hash.clone(),
-            parts: data::make_chunk_parts(chain.chunks[&chunk_hash].clone()),
-            receipts: vec![],
-        }),
-    );
-    let mut routing_table = data::make_routing_table(&mut rng, &clock.clock());
-    // TODO: validators field is supported only in proto encoding.
-    // Remove this line once borsh support is removed.
-    routing_table.validators = vec![];
-
-    let msgs = [
-        PeerMessage::Handshake(data::make_handshake(&mut rng, &chain)),
+    let mut msgs = vec![
+        PeerMessage::Handshake(data::make_handshake(rng, &chain)),
         PeerMessage::HandshakeFailure(
-            data::make_peer_info(&mut rng),
+            data::make_peer_info(rng),
             HandshakeFailureReason::InvalidTarget,
         ),
         PeerMessage::LastEdge(edge.clone()),
-        PeerMessage::SyncRoutingTable(routing_table),
-        PeerMessage::RequestUpdateNonce(data::make_partial_edge(&mut rng)),
+        PeerMessage::SyncRoutingTable(data::make_routing_table(rng, &clock.clock())),
+        PeerMessage::RequestUpdateNonce(data::make_partial_edge(rng)),
         PeerMessage::ResponseUpdateNonce(edge),
         PeerMessage::PeersRequest,
-        PeerMessage::PeersResponse((0..5).map(|_| data::make_peer_info(&mut rng)).collect()),
+        PeerMessage::PeersResponse((0..5).map(|_| data::make_peer_info(rng)).collect()),
         PeerMessage::BlockHeadersRequest(chain.blocks.iter().map(|b| b.hash().clone()).collect()),
         PeerMessage::BlockHeaders(chain.get_block_headers()),
         PeerMessage::BlockRequest(chain.blocks[5].hash().clone()),
         PeerMessage::Block(chain.blocks[5].clone()),
-        PeerMessage::Transaction(data::make_signed_transaction(&mut rng)),
-        PeerMessage::Routed(routed_message1),
-        PeerMessage::Routed(routed_message2),
+        PeerMessage::Transaction(data::make_signed_transaction(rng)),
         PeerMessage::Disconnect,
-        PeerMessage::Challenge(data::make_challenge(&mut rng)),
+        PeerMessage::Challenge(data::make_challenge(rng)),
         PeerMessage::EpochSyncRequest(epoch_id.clone()),
         PeerMessage::EpochSyncResponse(Box::new(EpochSyncResponse::UpToDate)),
         PeerMessage::EpochSyncFinalizationRequest(epoch_id),
-        // TODO: EpochSyncFinalizationResponse
+        // The content is not consistent, but it doesn't matter for the encoding.
+        PeerMessage::EpochSyncFinalizationResponse(Box::new(EpochSyncFinalizationResponse {
+            cur_epoch_header: header.clone(),
+            prev_epoch_headers: chain.get_block_headers(),
+            header_sync_init_header: header.clone(),
+            header_sync_init_header_tree: Default::default(),
+            prev_epoch_first_block_info: Default::default(),
+            prev_epoch_prev_last_block_info: Default::default(),
+            prev_epoch_last_block_info: Default::default(),
+            prev_epoch_info: Default::default(),
+            cur_epoch_info: Default::default(),
+            next_epoch_info: Default::default(),
+        })),
     ];
+    for body in data::make_routed_message_bodies(rng, &chain) {
+        let mut msg = data::make_routed_message(rng, body);
+        msg.created_at = Some(clock.now_utc());
+        msgs.push(PeerMessage::Routed(msg));
+    }
+    msgs
+}
+
+#[test]
+fn serialize_deserialize() -> anyhow::Result<()> {
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+    let msgs = make_messages(&mut rng, &mut clock);
 
     // Check that serialize;deserialize = 1
-    for enc in [Encoding::Proto, Encoding::Borsh] {
+    for (enc, version) in [
+        (Encoding::Proto, PROTOCOL_VERSION),
+        (Encoding::Proto, PEER_MIN_ALLOWED_PROTOCOL_VERSION),
+        (Encoding::Borsh, PROTOCOL_VERSION),
+    ] {
         for m in &msgs {
+            let mut m = m.clone();
+            if enc == Encoding::Borsh {
+                // TODO: validators field is supported only in proto encoding.
+                // Remove this once borsh support is removed.
+                if let PeerMessage::SyncRoutingTable(rt) = &mut m {
+                    rt.validators = vec![];
+                }
+                // Borsh doesn't carry created_at.
+                if let PeerMessage::Routed(r) = &mut m {
+                    r.created_at = None;
+                }
+            }
             (|| {
-                let m2 = PeerMessage::deserialize(enc, &m.serialize(enc))
+                let m2 = PeerMessage::deserialize(enc, &m.serialize(enc, version))
                     .with_context(|| format!("{m}"))?;
-                if *m != m2 {
+                if m != m2 {
                     bail!("deserialize(serialize({m}) = {m2}");
                 }
                 anyhow::Ok(())
             })()
-            .with_context(|| format!("encoding={enc:?}"))?;
+            .with_context(|| format!("encoding={enc:?}, version={version}"))?;
         }
     }
 
     // Test the unambiguous parsing argument described in
     // https://docs.google.com/document/d/1gCWmt9O-h_-5JDXIqbKxAaSS3Q9pryB1f9DDY1mMav4/edit#heading=h.x1awbr2acslb
     for m in &msgs {
-        let x = m.serialize(Encoding::Proto);
+        let x = m.serialize(Encoding::Proto, PROTOCOL_VERSION);
         assert!(x[0] >= 32, "serialize({},PROTO)[0] = {:?}, want >= 32", m, x.get(0));
-        let y = m.serialize(Encoding::Borsh);
+        let y = m.serialize(Encoding::Borsh, PROTOCOL_VERSION);
         assert!(y[0] <= 21, "serialize({},BORSH)[0] = {:?}, want <= 21", m, y.get(0));
     }
 
     // Encodings should never be compatible.
     for (from, to) in [(Encoding::Proto, Encoding::Borsh), (Encoding::Borsh, Encoding::Proto)] {
         for m in &msgs {
-            let bytes = &m.serialize(from);
+            let bytes = &m.serialize(from, PROTOCOL_VERSION);
             match PeerMessage::deserialize(to, bytes) {
                 Err(_) => {}
                 Ok(m2) => {
@@ -121,3 +144,179 @@ fn serialize_deserialize() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+// Nodes which don't set the native fields of RoutedMessage send the
+// whole message borsh-encoded.
+#[test]
+fn deserialize_legacy_routed_message() {
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 6);
+    for body in data::make_routed_message_bodies(&mut rng, &chain) {
+        let mut msg = data::make_routed_message(&mut rng, body);
+        msg.created_at = Some(clock.now_utc());
+        // Replace the native fields with the borsh-encoded message.
+        let mut legacy = proto::PeerMessage::from(&PeerMessage::Routed(msg.clone()));
+        let routed = legacy.mut_routed();
+        *routed = proto::RoutedMessage {
+            borsh: msg.msg.try_to_vec().unwrap(),
+            created_at: routed.created_at.clone(),
+            ..Default::default()
+        };
+        let got =
+            PeerMessage::deserialize(Encoding::Proto, &legacy.write_to_bytes().unwrap()).unwrap();
+        assert_eq!(PeerMessage::Routed(msg), got);
+    }
+}
+
+/// Routed messages are sent in exactly one encoding: the legacy borsh-encoded
+/// message to the peers which don't know about the native fields, and only the
+/// native fields to the others.
+#[test]
+fn serialize_routed_message_by_protocol_version() {
+    let mut rng = make_rng(89028037453);
+    let mut clock = time::FakeClock::default();
+    let chain = data::Chain::make(&mut clock, &mut rng, 6);
+    for body in data::make_routed_message_bodies(&mut rng, &chain) {
+        let routed = data::make_routed_message(&mut rng, body);
+        let want = routed.msg.clone();
+        let msg = PeerMessage::Routed(routed);
+
+        let bytes = msg.serialize(Encoding::Proto, PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION - 1);
+        let got = proto::PeerMessage::parse_from_bytes(&bytes).unwrap();
+        assert!(got.routed().body.is_none());
+        assert_eq!(want, RoutedMessage::try_from_slice(&got.routed().borsh).unwrap());
+
+        let bytes = msg.serialize(Encoding::Proto, PROTO_ROUTED_MESSAGE_PROTOCOL_VERSION);
+        let got = proto::PeerMessage::parse_from_bytes(&bytes).unwrap();
+        assert!(got.routed().borsh.is_empty());
+        assert!(got.routed().body.is_some());
+    }
+}
+
+/// Directory with the corpora of encoded messages, one file per protocol version.
+const CORPUS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/res/protocol_corpus");
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct CorpusEntry {
+    /// `PeerMessage::msg_variant()` of the encoded message.
+    name: String,
+    encoding: String,
+    /// base64 of the encoded message.
+    data: String,
+}
+
+fn corpus_path(version: ProtocolVersion) -> PathBuf {
+    Path::new(CORPUS_DIR).join(format!("{version}.json"))
+}
+
+fn parse_encoding(name: &str) -> anyhow::Result<Encoding> {
+    match name {
+        "Borsh" => Ok(Encoding::Borsh),
+        "Proto" => Ok(Encoding::Proto),
+        _ => bail!("unknown encoding {name}"),
+    }
+}
+
+/// Messages encoded by the older versions have to stay parsable, as long as
+/// the versions are supported.  Every corpus is checked against the current
+/// code, and the corpus of the current version additionally has to be encoded
+/// the same way by the current code, which catches incompatible changes of the
+/// schemas.  A corpus is never changed once committed: an intended change of
+/// the encoding comes with a protocol version bump, after which the corpus of
+/// the new version is generated with
+/// `UPDATE_PROTOCOL_CORPUS=1 cargo test -p near-network corpus`.
+#[test]
+fn corpus() -> anyhow::Result<()> {
+    if std::env::var_os("UPDATE_PROTOCOL_CORPUS").is_some() {
+        let path = corpus_path(PROTOCOL_VERSION);
+        if path.exists() {
+            bail!(
+                "{} already exists, the corpora of the existing versions are frozen",
+                path.display()
+            );
+        }
+        let mut rng = make_rng(89028037453);
+        let mut clock = time::FakeClock::default();
+        let mut entries = vec![];
+        for enc in [Encoding::Proto, Encoding::Borsh] {
+            for m in make_messages(&mut rng, &mut clock) {
+                entries.push(CorpusEntry {
+                    name: m.msg_variant().to_string(),
+                    encoding: <&str>::from(enc).to_string(),
+                    data: to_base64(m.serialize(enc, PROTOCOL_VERSION)),
+                });
+            }
+        }
+        std::fs::create_dir_all(CORPUS_DIR)?;
+        std::fs::write(path, serde_json::to_string_pretty(&entries)? + "\n")?;
+    }
+
+    let mut versions = vec![];
+    for entry in std::fs::read_dir(CORPUS_DIR)? {
+        let path = entry?.path();
+        let version = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok());
+        match version {
+            Some(version) => versions.push(version),
+            None => bail!("unexpected file {}", path.display()),
+        }
+    }
+    // Nightly builds have a higher version than the newest corpus.
+    let latest = versions
+        .iter()
+        .copied()
+        .filter(|v| *v <= PROTOCOL_VERSION)
+        .max()
+        .context("no corpus for the current version")?;
+    for version in versions {
+        let path = corpus_path(version);
+        let entries: Vec<CorpusEntry> = serde_json::from_slice(&std::fs::read(&path)?)
+            .with_context(|| format!("{}", path.display()))?;
+        let mut names = HashSet::new();
+        for e in &entries {
+            (|| {
+                let enc = parse_encoding(&e.encoding)?;
+                let data = from_base64(&e.data).map_err(|err| anyhow!("{err}"))?;
+                let m = PeerMessage::deserialize(enc, &data)?;
+                if m.msg_variant() != e.name {
+                    bail!("got {}", m.msg_variant());
+                }
+                if version == latest && m.serialize(enc, version) != data {
+                    bail!(
+                        "the encoding has changed; keep it compatible with version {version}, \
+                        or bump the protocol version and generate the corpus of the new one"
+                    );
+                }
+                if enc == Encoding::Proto {
+                    names.insert(e.name.as_str());
+                }
+                anyhow::Ok(())
+            })()
+            .with_context(|| format!("{}: {} ({})", path.display(), e.name, e.encoding))?;
+        }
+        if version == latest {
+            let mut rng = make_rng(89028037453);
+            let mut clock = time::FakeClock::default();
+            for m in make_messages(&mut rng, &mut clock) {
+                if !names.contains(m.msg_variant()) {
+                    bail!(
+                        "{}: {} is missing; the corpus of a version is frozen, so a new \
+                        message needs a protocol version bump and the corpus of the new one",
+                        path.display(),
+                        m
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+// The quotas per message type are validated against this list.
+#[test]
+fn peer_message_types() {
+    use strum::VariantNames as _;
+    let want: Vec<_> = PeerMessage::VARIANTS.iter().filter(|v| **v != "Routed").collect();
+    let got: Vec<_> = near_network_primitives::types::PEER_MESSAGE_TYPES.iter().collect();
+    assert_eq!(want, got);
+}
@@ -1,5 +1,6 @@
 pub(crate) mod codec;
 pub(crate) mod peer_actor;
+mod quota;
 mod tracker;
 mod transfer_stats;
 
@@ -1,6 +1,9 @@
+use crate::capture::{Direction, Recorder};
 use crate::network_protocol::{Encoding, ParsePeerMessageError};
 use crate::peer::codec::Codec;
+use crate::peer::quota::MessageQuotas;
 use crate::peer::tracker::Tracker;
+use crate::peer_manager::peer_score::PeerScoreEvent;
 use crate::private_actix::PeersResponse;
 use crate::private_actix::{PeerToManagerMsg, PeerToManagerMsgResp};
 use crate::private_actix::{
@@ -19,9 +22,9 @@ use lru::LruCache;
 use near_crypto::Signature;
 use near_network_primitives::time;
 use near_network_primitives::types::{
-    Ban, NetworkViewClientMessages, NetworkViewClientResponses, PeerChainInfoV2, PeerIdOrHash,
-    PeerInfo, PeerManagerRequest, PeerManagerRequestWithContext, PeerType, ReasonForBan,
-    RoutedMessage, RoutedMessageBody, RoutedMessageFrom, StateResponseInfo,
+    Ban, MessageQuotasConfig, NetworkViewClientMessages, NetworkViewClientResponses,
+    PeerChainInfoV2, PeerIdOrHash, PeerInfo, PeerManagerRequest, PeerManagerRequestWithContext,
+    PeerType, ReasonForBan, RoutedMessage, RoutedMessageBody, RoutedMessageFrom, StateResponseInfo,
     UPDATE_INTERVAL_LAST_TIME_RECEIVED_MESSAGE,
 };
 use near_network_primitives::types::{Edge, PartialEdgeInfo};
@@ -79,6 +82,8 @@ pub(crate) struct PeerActor {
     framed: FramedWrite<Vec<u8>, WriteHalf, Codec, Codec>,
     /// Handshake timeout.
     handshake_timeout: time::Duration,
+    /// When we sent the handshake to an outbound peer, to measure its latency.
+    handshake_sent: Option<time::Instant>,
     /// Peer manager recipient to break the dependency loop.
     /// PeerManager is a recipient of 2 types of messages, therefore
     /// to inject a fake PeerManager in tests, we need a separate
@@ -113,6 +118,12 @@ pub(crate) struct PeerActor {
     /// Whether the PeerActor should skip protobuf support detection and use
     /// a given encoding right away.
     force_encoding: Option<Encoding>,
+    /// Recorder of the exchanged messages, if capturing is enabled.
+    capture: Option<Arc<Recorder>>,
+    /// Quotas on the messages received from the peer.
+    quotas: MessageQuotas,
+    /// Whether processing of the messages delayed by the quotas is scheduled.
+    delayed_messages_scheduled: bool,
 }
 
 impl Debug for PeerActor {
@@ -149,6 +160,8 @@ impl PeerActor {
         peer_counter: Arc<AtomicUsize>,
         throttle_controller: ThrottleController,
         force_encoding: Option<Encoding>,
+        capture: Option<Arc<Recorder>>,
+        message_quotas: MessageQuotasConfig,
     ) -> Self {
         let now = clock.now();
         PeerActor {
@@ -161,6 +174,7 @@ impl PeerActor {
             protocol_version: PROTOCOL_VERSION,
             framed,
             handshake_timeout,
+            handshake_sent: None,
             peer_manager_addr,
             peer_manager_wrapper_addr,
             client_addr,
@@ -176,6 +190,9 @@ impl PeerActor {
             throttle_controller,
             protocol_buffers_supported: false,
             force_encoding,
+            capture,
+            quotas: MessageQuotas::new(message_quotas, now),
+            delayed_messages_scheduled: false,
         }
     }
 
@@ -199,7 +216,11 @@ impl PeerActor {
     fn parse_message(&mut self, msg: &[u8]) -> Result<PeerMessage, ParsePeerMessageError> {
         let _span = tracing::trace_span!(target: "network", "parse_message").entered();
         if let Some(e) = self.encoding() {
-            return PeerMessage::deserialize(e, msg);
+            let msg = PeerMessage::deserialize(e, msg)?;
+            if e == Encoding::Proto {
+                self.protocol_buffers_supported = true;
+            }
+            return Ok(msg);
         }
         if let Ok(msg) = PeerMessage::deserialize(Encoding::Proto, msg) {
             self.protocol_buffers_supported = true;
@@ -208,6 +229,12 @@ impl PeerActor {
         return PeerMessage::deserialize(Encoding::Borsh, msg);
     }
 
+    fn capture(&self, direction: Direction, enc: Option<Encoding>, bytes: &[u8]) {
+        if let Some(capture) = &self.capture {
+            capture.record(self.clock.now_utc(), self.other_peer_id(), direction, enc, bytes);
+        }
+    }
+
     fn send_message_or_log(&mut self, msg: &PeerMessage) {
         if let Err(err) = self.send_message(msg) {
             warn!(target: "network", "send_message(): {}", err);
@@ -236,9 +263,10 @@ impl PeerActor {
             _ => (),
         };
 
-        let bytes = msg.serialize(enc);
+        let bytes = msg.serialize(enc, self.protocol_version);
         self.tracker.increment_sent(bytes.len() as u64);
         let bytes_len = bytes.len();
+        self.capture(Direction::Outbound, Some(enc), &bytes);
         if !self.framed.write(bytes) {
             #[cfg(feature = "performance_stats")]
             let tid = near_rust_allocator_proxy::get_tid();
@@ -300,6 +328,9 @@ impl PeerActor {
                         }
                     };
 
+                    if act.peer_type == PeerType::Outbound {
+                        act.handshake_sent = Some(act.clock.now());
+                    }
                     act.send_message_or_log(&handshake);
                     actix::fut::ready(())
                 }
@@ -464,7 +495,11 @@ impl PeerActor {
                 let block_hash = *block.hash();
                 self.tracker.push_received(block_hash);
                 self.chain_info.height = max(self.chain_info.height, block.header().height());
-                NetworkClientMessages::Block(block, peer_id, self.tracker.has_request(&block_hash))
+                let was_requested = self.tracker.has_request(&block_hash);
+                if was_requested {
+                    self.update_peer_score(peer_id.clone(), PeerScoreEvent::UsefulMessage);
+                }
+                NetworkClientMessages::Block(block, peer_id, was_requested)
             }
             PeerMessage::Transaction(transaction) => NetworkClientMessages::Transaction {
                 transaction,
@@ -584,6 +619,10 @@ impl PeerActor {
             .spawn(ctx);
     }
 
+    fn update_peer_score(&self, peer_id: PeerId, event: PeerScoreEvent) {
+        self.peer_manager_addr.do_send(PeerToManagerMsg::UpdatePeerScore(peer_id, event));
+    }
+
     /// Hook called on every valid message received from this peer from the network.
     fn on_receive_message(&mut self) {
         if let Some(peer_id) = self.other_peer_id().cloned() {
@@ -607,6 +646,25 @@ impl PeerActor {
         self.tracker.increment_received(msg_len as u64);
     }
 
+    /// Processes the messages delayed by the quotas once the quotas allow them.
+    fn schedule_delayed_messages(&mut self, ctx: &mut Context<PeerActor>) {
+        if self.delayed_messages_scheduled {
+            return;
+        }
+        let wait = match self.quotas.next_ready(self.clock.now()) {
+            Some(wait) => wait,
+            None => return,
+        };
+        self.delayed_messages_scheduled = true;
+        near_performance_metrics::actix::run_later(ctx, wait.try_into().unwrap(), |act, ctx| {
+            act.delayed_messages_scheduled = false;
+            for msg in act.quotas.pop_ready(act.clock.now()) {
+                act.handle_peer_message(ctx, msg);
+            }
+            act.schedule_delayed_messages(ctx);
+        });
+    }
+
     /// Check whenever we exceeded number of transactions we got since last block.
     /// If so, drop the transaction.
     fn should_we_drop_msg(&self, msg: &PeerMessage) -> bool {
@@ -702,14 +760,24 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
         // as long as it travels to PeerManager, etc.
 
         self.update_stats_on_receiving_message(msg.len());
+        // Captured before parsing, so that invalid messages are captured too.
+        self.capture(Direction::Inbound, self.encoding(), &msg);
         let peer_msg = match self.parse_message(&msg) {
             Ok(msg) => msg,
             Err(err) => {
                 debug!(target: "network", "Received invalid data {:?} from {}: {}", logging::pretty_vec(&msg), self.peer_info, err);
+                // If proto is required and the very first message isn't proto, the
+                // peer uses borsh, so there is no point in waiting for the handshake
+                // timeout. Peers detecting the encoding send every message in both
+                // encodings, proto first, so they get past this check.
+                if self.force_encoding == Some(Encoding::Proto) && !self.protocol_buffers_supported
+                {
+                    info!(target: "network", peer_info = %self.peer_info, "Peer doesn't use proto encoding, disconnecting");
+                    ctx.stop();
+                }
                 return;
             }
         };
-
         if self.should_we_drop_msg(&peer_msg) {
             return;
         }
@@ -748,6 +816,24 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                 .inc_by(msg.len() as u64);
         }
 
+        // The messages exchanged during the handshake are not subject to the quotas.
+        let peer_msg = if self.peer_status == PeerStatus::Ready {
+            match self.quotas.admit(self.clock.now(), peer_msg, msg.len()) {
+                Some(peer_msg) => peer_msg,
+                None => {
+                    self.schedule_delayed_messages(ctx);
+                    return;
+                }
+            }
+        } else {
+            peer_msg
+        };
+        self.handle_peer_message(ctx, peer_msg);
+    }
+}
+
+impl PeerActor {
+    fn handle_peer_message(&mut self, ctx: &mut Context<PeerActor>, peer_msg: PeerMessage) {
         match (self.peer_status, peer_msg) {
             (_, PeerMessage::HandshakeFailure(peer_info, reason)) => {
                 match reason {
@@ -880,6 +966,10 @@ impl StreamHandler<Result<Vec<u8>, ReasonForBan>> for PeerActor {
                     .then(move |res, act, ctx| {
                         match res.map(|f|f.into_inner().unwrap_consolidate_response()) {
                             Ok(RegisterPeerResponse::Accept(edge_info)) => {
+                                if let Some(sent) = act.handshake_sent.take() {
+                                    let latency = act.clock.now() - sent;
+                                    act.update_peer_score(peer_info.id.clone(), PeerScoreEvent::Latency(latency));
+                                }
                                 act.peer_info = Some(peer_info).into();
                                 act.peer_status = PeerStatus::Ready;
                                 // Respond to handshake if it's inbound and connection was consolidated.
@@ -0,0 +1,373 @@
+//! Quotas on the messages received from a peer, see `MessageQuotasConfig`.
+use crate::stats::metrics;
+use crate::types::PeerMessage;
+use near_network_primitives::time;
+use near_network_primitives::types::{MessageQuotasConfig, RateLimit};
+use std::collections::{HashMap, VecDeque};
+
+/// Token bucket, refilled continuously at `per_second` tokens per second.
+struct TokenBucket {
+    per_second: f64,
+    burst: f64,
+    tokens: f64,
+    updated: time::Instant,
+}
+
+impl TokenBucket {
+    fn new(limit: &RateLimit, now: time::Instant) -> Self {
+        Self {
+            per_second: limit.per_second as f64,
+            burst: limit.burst as f64,
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: time::Instant) {
+        let elapsed = (now - self.updated).as_seconds_f64().max(0.);
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.burst);
+        self.updated = now;
+    }
+
+    /// Time until `cost` tokens are available.  A cost above `burst` only
+    /// requires a full bucket, otherwise it could never be paid.
+    fn wait_time(&mut self, now: time::Instant, cost: u64) -> time::Duration {
+        self.refill(now);
+        let missing = (cost as f64).min(self.burst) - self.tokens;
+        if missing <= 0. {
+            return time::Duration::ZERO;
+        }
+        time::Duration::seconds_f64(missing / self.per_second)
+    }
+
+    /// Takes `cost` tokens, as many as there are if there are not enough.
+    fn take(&mut self, now: time::Instant, cost: u64) {
+        self.refill(now);
+        self.tokens = (self.tokens - cost as f64).max(0.);
+    }
+}
+
+struct DelayedMessage {
+    msg: PeerMessage,
+    size: usize,
+    since: time::Instant,
+}
+
+/// Quotas of a single peer and the messages over quota waiting to be processed.
+pub(crate) struct MessageQuotas {
+    cfg: MessageQuotasConfig,
+    per_peer_bytes: Option<TokenBucket>,
+    /// Bytes of the consensus-critical messages, which don't count towards
+    /// `per_peer_bytes`.
+    consensus_bytes: Option<TokenBucket>,
+    per_message_type: HashMap<&'static str, TokenBucket>,
+    /// Messages over quota, by type, oldest first.  Every type has its own
+    /// queue, so that a flood of one type doesn't delay the others.  A type
+    /// is either consensus-critical or not, so is every queue.
+    delayed: HashMap<&'static str, VecDeque<DelayedMessage>>,
+    delayed_count: usize,
+    delayed_bytes: u64,
+}
+
+impl MessageQuotas {
+    pub fn new(cfg: MessageQuotasConfig, now: time::Instant) -> Self {
+        let per_peer_bytes = cfg.per_peer_bytes.as_ref().map(|limit| TokenBucket::new(limit, now));
+        let consensus_bytes =
+            cfg.consensus_bytes.as_ref().map(|limit| TokenBucket::new(limit, now));
+        Self {
+            cfg,
+            per_peer_bytes,
+            consensus_bytes,
+            per_message_type: HashMap::new(),
+            delayed: HashMap::new(),
+            delayed_count: 0,
+            delayed_bytes: 0,
+        }
+    }
+
+    fn bytes_bucket(&mut self, critical: bool) -> Option<&mut TokenBucket> {
+        if critical {
+            self.consensus_bytes.as_mut()
+        } else {
+            self.per_peer_bytes.as_mut()
+        }
+    }
+
+    /// Time until the quotas allow a message of the given type and size.
+    fn wait_time(
+        &mut self,
+        now: time::Instant,
+        variant: &'static str,
+        critical: bool,
+        size: usize,
+    ) -> time::Duration {
+        let mut wait = match self.bytes_bucket(critical) {
+            Some(bucket) => bucket.wait_time(now, size as u64),
+            None => time::Duration::ZERO,
+        };
+        if let Some(limit) = self.cfg.per_message_type.get(variant) {
+            let bucket = self
+                .per_message_type
+                .entry(variant)
+                .or_insert_with(|| TokenBucket::new(limit, now));
+            wait = wait.max(bucket.wait_time(now, 1));
+        }
+        wait
+    }
+
+    fn take(&mut self, now: time::Instant, variant: &'static str, critical: bool, size: usize) {
+        if let Some(bucket) = self.bytes_bucket(critical) {
+            bucket.take(now, size as u64);
+        }
+        if let Some(bucket) = self.per_message_type.get_mut(variant) {
+            bucket.take(now, 1);
+        }
+    }
+
+    /// Returns the message if it can be processed right away.  Otherwise the
+    /// message is kept until the quotas allow it, or dropped if too many
+    /// messages are waiting already.
+    pub fn admit(
+        &mut self,
+        now: time::Instant,
+        msg: PeerMessage,
+        size: usize,
+    ) -> Option<PeerMessage> {
+        let variant = msg.msg_variant();
+        let critical = msg.is_consensus_critical();
+        // Messages of a type can't overtake the delayed ones of the same type.
+        let queued = self.delayed.get(variant).map_or(false, |queue| !queue.is_empty());
+        if !queued && self.wait_time(now, variant, critical, size) == time::Duration::ZERO {
+            self.take(now, variant, critical, size);
+            return Some(msg);
+        }
+        if self.delayed_count >= self.cfg.max_delayed_messages
+            || self.delayed_bytes + size as u64 > self.cfg.max_delayed_bytes
+        {
+            metrics::MessageDropped::QuotaExceeded.inc_peer_msg(&msg);
+            return None;
+        }
+        metrics::PEER_MESSAGE_DELAYED_BY_QUOTA.with_label_values(&[variant]).inc();
+        self.delayed.entry(variant).or_default().push_back(DelayedMessage {
+            msg,
+            size,
+            since: now,
+        });
+        self.delayed_count += 1;
+        self.delayed_bytes += size as u64;
+        None
+    }
+
+    /// Removes and returns the delayed messages which the quotas allow now:
+    /// the consensus-critical ones first, otherwise in the order they were
+    /// received.
+    pub fn pop_ready(&mut self, now: time::Instant) -> Vec<PeerMessage> {
+        let mut ready = vec![];
+        loop {
+            // The message first in line among the ones at the front of their
+            // queues which the quotas allow.
+            let mut next: Option<(&'static str, (bool, time::Instant))> = None;
+            let fronts: Vec<_> = self
+                .delayed
+                .iter()
+                .filter_map(|(variant, queue)| {
+                    queue
+                        .front()
+                        .map(|m| (*variant, m.msg.is_consensus_critical(), m.size, m.since))
+                })
+                .collect();
+            for (variant, critical, size, since) in fronts {
+                // Non-critical messages go after the critical ones.
+                let order = (!critical, since);
+                if next.map_or(false, |(_, next_order)| next_order <= order) {
+                    continue;
+                }
+                if self.wait_time(now, variant, critical, size) == time::Duration::ZERO {
+                    next = Some((variant, order));
+                }
+            }
+            let (variant, critical) = match next {
+                Some((variant, (not_critical, _))) => (variant, !not_critical),
+                None => return ready,
+            };
+            let queue = self.delayed.get_mut(variant).unwrap();
+            let m = queue.pop_front().unwrap();
+            if queue.is_empty() {
+                self.delayed.remove(variant);
+            }
+            self.delayed_count -= 1;
+            self.delayed_bytes -= m.size as u64;
+            self.take(now, variant, critical, m.size);
+            metrics::PEER_MESSAGE_QUOTA_DELAY.observe((now - m.since).as_seconds_f64());
+            ready.push(m.msg);
+        }
+    }
+
+    /// Time until one of the delayed messages can be processed, `None` if
+    /// there are no delayed messages.
+    pub fn next_ready(&mut self, now: time::Instant) -> Option<time::Duration> {
+        let fronts: Vec<_> = self
+            .delayed
+            .iter()
+            .filter_map(|(variant, queue)| {
+                queue.front().map(|m| (*variant, m.msg.is_consensus_critical(), m.size))
+            })
+            .collect();
+        let mut next: Option<time::Duration> = None;
+        for (variant, critical, size) in fronts {
+            let wait = self.wait_time(now, variant, critical, size);
+            next = Some(next.map_or(wait, |next| next.min(wait)));
+        }
+        next
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::testonly::make_rng;
+    use near_network_primitives::types::RoutedMessageBody;
+    use near_primitives::hash::CryptoHash;
+
+    fn limit(per_second: u64, burst: u64) -> RateLimit {
+        RateLimit { per_second, burst }
+    }
+
+    #[test]
+    fn message_type_quota() {
+        let clock = time::FakeClock::default();
+        let mut cfg = MessageQuotasConfig::default();
+        cfg.per_message_type.insert("StateRequestPart".to_string(), limit(2, 2));
+        cfg.max_delayed_messages = 2;
+        let mut quotas = MessageQuotas::new(cfg, clock.now());
+        let mut rng = make_rng(921853233);
+        let requests: Vec<_> = (0..5)
+            .map(|part_id| {
+                let body = RoutedMessageBody::StateRequestPart(0, CryptoHash::default(), part_id);
+                PeerMessage::Routed(data::make_routed_message(&mut rng, body))
+            })
+            .collect();
+
+        // The burst is admitted, then the messages are delayed, then dropped.
+        for msg in &requests[..2] {
+            assert_eq!(quotas.admit(clock.now(), msg.clone(), 10).as_ref(), Some(msg));
+        }
+        for msg in &requests[2..] {
+            assert_eq!(quotas.admit(clock.now(), msg.clone(), 10), None);
+        }
+        // Other messages are not affected.
+        assert_eq!(
+            quotas.admit(clock.now(), PeerMessage::PeersRequest, 10),
+            Some(PeerMessage::PeersRequest)
+        );
+        assert_eq!(quotas.next_ready(clock.now()), Some(time::Duration::milliseconds(500)));
+        assert_eq!(quotas.pop_ready(clock.now()), vec![]);
+
+        clock.advance(time::Duration::milliseconds(500));
+        assert_eq!(quotas.pop_ready(clock.now()), vec![requests[2].clone()]);
+        clock.advance(time::Duration::SECOND);
+        assert_eq!(quotas.pop_ready(clock.now()), vec![requests[3].clone()]);
+        assert_eq!(quotas.next_ready(clock.now()), None);
+    }
+
+    #[test]
+    fn per_peer_quota() {
+        let clock = time::FakeClock::default();
+        let cfg = MessageQuotasConfig {
+            per_peer_bytes: Some(limit(100, 100)),
+            ..MessageQuotasConfig::default()
+        };
+        let mut quotas = MessageQuotas::new(cfg, clock.now());
+
+        assert_eq!(
+            quotas.admit(clock.now(), PeerMessage::PeersRequest, 80),
+            Some(PeerMessage::PeersRequest)
+        );
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::Disconnect, 50), None);
+        // Consensus-critical messages don't use this quota.
+        let challenge = PeerMessage::Challenge(data::make_challenge(&mut make_rng(921853233)));
+        assert_eq!(quotas.admit(clock.now(), challenge.clone(), 50), Some(challenge));
+        assert_eq!(quotas.next_ready(clock.now()), Some(time::Duration::milliseconds(300)));
+
+        // A message bigger than the burst waits for a full bucket.
+        clock.advance(time::Duration::milliseconds(300));
+        assert_eq!(quotas.pop_ready(clock.now()), vec![PeerMessage::Disconnect]);
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::PeersRequest, 1000), None);
+        assert_eq!(quotas.next_ready(clock.now()), Some(time::Duration::SECOND));
+        clock.advance(time::Duration::SECOND);
+        assert_eq!(quotas.pop_ready(clock.now()), vec![PeerMessage::PeersRequest]);
+    }
+
+    #[test]
+    fn consensus_quota() {
+        let clock = time::FakeClock::default();
+        let mut cfg = MessageQuotasConfig {
+            per_peer_bytes: Some(limit(100, 100)),
+            consensus_bytes: Some(limit(100, 100)),
+            ..MessageQuotasConfig::default()
+        };
+        cfg.per_message_type.insert("Challenge".to_string(), limit(1, 1));
+        let mut quotas = MessageQuotas::new(cfg, clock.now());
+        let mut rng = make_rng(921853233);
+        let challenges: Vec<_> =
+            (0..2).map(|_| PeerMessage::Challenge(data::make_challenge(&mut rng))).collect();
+
+        // Consensus-critical messages have their own quotas, including the
+        // ones per message type.
+        assert_eq!(
+            quotas.admit(clock.now(), PeerMessage::PeersRequest, 100),
+            Some(PeerMessage::PeersRequest)
+        );
+        assert_eq!(
+            quotas.admit(clock.now(), challenges[0].clone(), 50),
+            Some(challenges[0].clone())
+        );
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::Disconnect, 50), None);
+        clock.advance(time::Duration::milliseconds(100));
+        assert_eq!(quotas.admit(clock.now(), challenges[1].clone(), 50), None);
+
+        // The delayed consensus-critical messages are processed first.
+        clock.advance(time::Duration::SECOND);
+        assert_eq!(
+            quotas.pop_ready(clock.now()),
+            vec![challenges[1].clone(), PeerMessage::Disconnect]
+        );
+    }
+
+    #[test]
+    fn max_delayed_bytes() {
+        let clock = time::FakeClock::default();
+        let cfg = MessageQuotasConfig {
+            per_peer_bytes: Some(limit(100, 100)),
+            max_delayed_bytes: 150,
+            ..MessageQuotasConfig::default()
+        };
+        let mut quotas = MessageQuotas::new(cfg, clock.now());
+
+        assert_eq!(
+            quotas.admit(clock.now(), PeerMessage::PeersRequest, 100),
+            Some(PeerMessage::PeersRequest)
+        );
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::Disconnect, 100), None);
+        // Dropped, as the delayed messages would take more than 150 bytes.
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::PeersRequest, 100), None);
+        clock.advance(time::Duration::milliseconds(10));
+        assert_eq!(
+            quotas.admit(clock.now(), PeerMessage::BlockRequest(CryptoHash::default()), 50),
+            None
+        );
+
+        clock.advance(time::Duration::milliseconds(990));
+        assert_eq!(quotas.pop_ready(clock.now()), vec![PeerMessage::Disconnect]);
+        // There is room again.
+        assert_eq!(quotas.admit(clock.now(), PeerMessage::PeersRequest, 100), None);
+        clock.advance(time::Duration::milliseconds(500));
+        assert_eq!(
+            quotas.pop_ready(clock.now()),
+            vec![PeerMessage::BlockRequest(CryptoHash::default())]
+        );
+        clock.advance(time::Duration::SECOND);
+        assert_eq!(quotas.pop_ready(clock.now()), vec![PeerMessage::PeersRequest]);
+    }
+}
@@ -11,8 +11,8 @@ use actix::{Actor, Context, Handler, StreamHandler as _};
 use near_crypto::InMemorySigner;
 use near_network_primitives::time;
 use near_network_primitives::types::{
-    AccountOrPeerIdOrHash, Edge, PartialEdgeInfo, PeerInfo, PeerType, RawRoutedMessage,
-    RoutedMessageBody, RoutedMessageV2,
+    AccountOrPeerIdOrHash, Edge, MessageQuotasConfig, PartialEdgeInfo, PeerInfo, PeerType,
+    RawRoutedMessage, RoutedMessageBody, RoutedMessageV2,
 };
 use near_performance_metrics::framed_write::FramedWrite;
 use near_primitives::network::PeerId;
@@ -146,6 +146,7 @@ impl Handler<PeerToManagerMsg> for FakePeerManagerActor {
                 self.event_sink.push(Event::PeersResponse(resp.peers));
                 PeerToManagerMsgResp::Empty
             }
+            PeerToManagerMsg::UpdatePeerScore(..) => PeerToManagerMsgResp::Empty,
             _ => panic!("unsupported message"),
         }
     }
@@ -233,6 +234,8 @@ impl PeerHandle {
                     Arc::new(AtomicUsize::new(0)),
                     rate_limiter,
                     cfg.force_encoding,
+                    None,
+                    MessageQuotasConfig::default(),
                 )
             })
         })
@@ -1,5 +1,8 @@
 pub(crate) mod peer_manager_actor;
+pub(crate) mod peer_score;
+pub(crate) mod peer_source;
 pub(crate) mod peer_store;
+pub(crate) mod tier1;
 
 #[cfg(test)]
 pub(crate) mod testonly;
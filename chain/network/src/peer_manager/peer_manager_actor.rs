@@ -1,7 +1,11 @@
-use crate::network_protocol::Encoding;
+use crate::capture::Recorder;
+use crate::network_protocol::{Encoding, PeerAddr, SignedValidator, Validator};
 use crate::peer::codec::Codec;
 use crate::peer::peer_actor::PeerActor;
+use crate::peer_manager::peer_score;
+use crate::peer_manager::peer_source::{PeerSource, SeedFile};
 use crate::peer_manager::peer_store::PeerStore;
+use crate::peer_manager::tier1::{self, Tier1};
 use crate::private_actix::{
     PeerRequestResult, PeersRequest, RegisterPeer, RegisterPeerResponse, SendMessage, StopMsg,
     Unregister, ValidateEdgeList,
@@ -23,13 +27,14 @@ use actix::{
     Recipient, Running, StreamHandler, WrapFuture,
 };
 use anyhow::bail;
+use lru::LruCache;
 use near_network_primitives::time;
 use near_network_primitives::types::{
     AccountOrPeerIdOrHash, Ban, Edge, InboundTcpConnect, KnownPeerStatus, KnownProducer,
     NetworkConfig, NetworkViewClientMessages, NetworkViewClientResponses, OutboundTcpConnect,
     PeerIdOrHash, PeerInfo, PeerManagerRequest, PeerManagerRequestWithContext, PeerType, Ping,
     Pong, RawRoutedMessage, ReasonForBan, RoutedMessageBody, RoutedMessageFrom, RoutedMessageV2,
-    StateResponseInfo,
+    StateResponseInfo, ValidatorEndpoints,
 };
 use near_network_primitives::types::{EdgeState, PartialEdgeInfo};
 use near_performance_metrics::framed_write::FramedWrite;
@@ -74,6 +79,9 @@ const MONITOR_PEERS_MAX_DURATION: time::Duration = time::Duration::milliseconds(
 const MONITOR_PEERS_INITIAL_DURATION: time::Duration = time::Duration::milliseconds(10);
 /// Limit number of pending Peer actors to avoid OOM.
 const LIMIT_PENDING_PEERS: usize = 60;
+/// Number of chunk requests sent by this node to remember, so that the peers
+/// serving them can be credited.
+const OUTSTANDING_CHUNK_REQUESTS_CACHE_SIZE: usize = 10_000;
 /// How ofter should we broadcast edges.
 const BROADCAST_VALIDATED_EDGES_INTERVAL: time::Duration = time::Duration::milliseconds(50);
 /// Maximum amount of time spend processing edges.
@@ -85,6 +93,10 @@ const UPDATE_ROUTING_TABLE_INTERVAL: time::Duration = time::Duration::millisecon
 /// How often to report bandwidth stats.
 const REPORT_BANDWIDTH_STATS_TRIGGER_INTERVAL: time::Duration =
     time::Duration::milliseconds(60_000);
+/// How often to poll the peer sources for new peers.
+const POLL_PEER_SOURCES_INTERVAL: time::Duration = time::Duration::milliseconds(60_000);
+/// How often to connect to the validators we aren't connected to, if we are a validator.
+const TIER1_CONNECT_INTERVAL: time::Duration = time::Duration::milliseconds(10_000);
 
 /// Max number of messages we received from peer, and they are in progress, before we start throttling.
 /// Disabled for now (TODO PUT UNDER FEATURE FLAG)
@@ -188,6 +200,17 @@ pub struct PeerManagerActor {
     /// Whitelisted nodes, which are allowed to connect even if the connection limit has been
     /// reached.
     whitelist_nodes: Vec<WhitelistNode>,
+    /// Sources of peers to bootstrap from, in addition to the boot nodes.
+    peer_sources: Vec<Arc<dyn PeerSource>>,
+    /// Validators of the current and the next epoch, and the addresses they
+    /// can be reached at.
+    tier1: Tier1,
+    /// Recorder of the messages exchanged with the peers, shared by all the
+    /// `PeerActor`s, if capturing is enabled.
+    capture: Option<Arc<Recorder>>,
+    /// Hashes of the chunk requests sent by this node which haven't been
+    /// answered yet, with the peers they were sent to.
+    outstanding_chunk_requests: LruCache<CryptoHash, PeerId>,
     /// test-only.
     event_sink: Sink<Event>,
 }
@@ -258,6 +281,12 @@ impl Actor for PeerManagerActor {
 
         // Periodically prints bandwidth stats for each peer.
         self.report_bandwidth_stats_trigger(ctx, REPORT_BANDWIDTH_STATS_TRIGGER_INTERVAL);
+
+        // Periodically adds peers from the peer sources to the peer store.
+        self.poll_peer_sources_trigger(ctx, POLL_PEER_SOURCES_INTERVAL);
+
+        // Periodically connects to the other validators, if we are a validator.
+        self.tier1_connect_trigger(ctx, TIER1_CONNECT_INTERVAL);
     }
 
     /// Try to gracefully disconnect from connected peers.
@@ -310,6 +339,15 @@ impl PeerManagerActor {
             v
         };
 
+        let mut peer_sources: Vec<Arc<dyn PeerSource>> = vec![];
+        if let Some(path) = &config.seed_file {
+            peer_sources.push(Arc::new(SeedFile::new(path.clone())));
+        }
+        let capture = match &config.capture {
+            Some(cfg) => Some(Arc::new(Recorder::new(cfg.clone())?)),
+            None => None,
+        };
+
         Ok(Self {
             clock,
             my_peer_id,
@@ -328,10 +366,20 @@ impl PeerManagerActor {
             txns_since_last_block,
             peer_counter: Arc::new(AtomicUsize::new(0)),
             whitelist_nodes,
+            peer_sources,
+            tier1: Tier1::default(),
+            capture,
+            outstanding_chunk_requests: LruCache::new(OUTSTANDING_CHUNK_REQUESTS_CACHE_SIZE),
             event_sink: Sink::void(),
         })
     }
 
+    /// Adds a source of peers to bootstrap from.
+    pub fn with_peer_source(mut self, peer_source: Arc<dyn PeerSource>) -> Self {
+        self.peer_sources.push(peer_source);
+        self
+    }
+
     /// test-only, sets the event handler.
     pub fn with_event_sink(mut self, event_sink: Sink<Event>) -> Self {
         self.event_sink = event_sink;
@@ -397,6 +445,134 @@ impl PeerManagerActor {
         )
     }
 
+    /// Adds the data of the validators to TIER1 and broadcasts the new data.
+    /// Bans the peer the data came from if it is invalid.
+    fn broadcast_validators(&mut self, peer_id: Option<&PeerId>, validators: Vec<SignedValidator>) {
+        let validators = match self.tier1.insert(validators) {
+            Ok(validators) => validators,
+            Err(err) => {
+                debug!(target: "network", ?peer_id, ?err, "Received invalid validator data");
+                if let Some(peer_id) = peer_id {
+                    let reason = match err {
+                        tier1::Error::InvalidSignature(_) => ReasonForBan::InvalidSignature,
+                        tier1::Error::TooManyPeers(_) => ReasonForBan::Abusive,
+                    };
+                    self.try_ban_peer(peer_id, reason);
+                }
+                return;
+            }
+        };
+        if validators.is_empty() {
+            return;
+        }
+        Self::broadcast_message(
+            &self.connected_peers,
+            SendMessage {
+                message: PeerMessage::SyncRoutingTable(RoutingTableUpdate::from_validators(
+                    validators,
+                )),
+                context: Span::current().context(),
+            },
+        )
+    }
+
+    /// Signs and broadcasts the addresses this node can be reached at, for
+    /// every epoch it is a validator in, unless it has done it already.
+    fn advertise_validator(&mut self) {
+        let validator = match &self.config.validator {
+            Some(validator) => validator,
+            None => return,
+        };
+        // TODO: determine the public address with STUN servers.
+        let addrs = match &validator.endpoints {
+            ValidatorEndpoints::PublicAddrs(addrs) => addrs,
+            ValidatorEndpoints::TrustedStunServers(_) => return,
+        };
+        let account_id = validator.account_id();
+        let mut validators = vec![];
+        for epoch_id in self.tier1.epochs_of(&account_id) {
+            if self.tier1.contains(&epoch_id, &account_id) {
+                continue;
+            }
+            let data = Validator {
+                peers: addrs
+                    .iter()
+                    .filter_map(|peer_info| {
+                        Some(PeerAddr {
+                            addr: peer_info.addr?,
+                            peer_id: Some(peer_info.id.clone()),
+                        })
+                    })
+                    .collect(),
+                account_id: account_id.clone(),
+                epoch_id,
+                timestamp: self.clock.now_utc(),
+            };
+            validators.push(data.sign(validator.signer.as_ref()));
+        }
+        self.broadcast_validators(None, validators);
+    }
+
+    /// Whether we are a validator, and the peer is the node of a validator: advertised by the
+    /// validator for TIER1 and the one it announced its account with.
+    fn is_tier1_peer(&self, peer_id: &PeerId) -> bool {
+        self.config.validator.as_ref().map_or(false, |v| self.tier1.is_validator(&v.account_id()))
+            && self.tier1.is_validator_peer(peer_id, |account_id| {
+                self.routing_table_view.peek_account_owner(account_id)
+            })
+    }
+
+    /// Peers to connect to, if we are a validator: the other validators we
+    /// aren't connected to, and our own relay nodes.
+    fn tier1_peers_to_connect(&self) -> Vec<PeerInfo> {
+        let validator = match &self.config.validator {
+            Some(validator) => validator,
+            None => return vec![],
+        };
+        let is_connected = |peer_id: &PeerId| {
+            self.connected_peers.contains_key(peer_id)
+                || self.outgoing_peers.contains(peer_id)
+                || self.peer_store.is_banned(peer_id)
+        };
+        let mut peers = vec![];
+        let account_id = validator.account_id();
+        if self.tier1.is_validator(&account_id) {
+            peers = self.tier1.peers_to_connect(&account_id, is_connected);
+        }
+        if let ValidatorEndpoints::PublicAddrs(addrs) = &validator.endpoints {
+            peers.extend(
+                addrs
+                    .iter()
+                    .filter(|peer_info| peer_info.id != self.my_peer_id)
+                    .filter(|peer_info| !is_connected(&peer_info.id))
+                    .cloned(),
+            );
+        }
+        peers
+    }
+
+    /// Periodically connects to the validators we aren't connected to, and to
+    /// our own relay nodes, if we are a validator.
+    fn tier1_connect_trigger(&mut self, ctx: &mut Context<Self>, every: time::Duration) {
+        if !self.config.outbound_disabled {
+            for peer_info in self.tier1_peers_to_connect() {
+                debug!(target: "network", ?peer_info, "Connecting to a TIER1 peer");
+                self.outgoing_peers.insert(peer_info.id.clone());
+                ctx.notify(PeerManagerMessageRequest::OutboundTcpConnect(OutboundTcpConnect {
+                    peer_info,
+                }));
+            }
+        }
+
+        near_performance_metrics::actix::run_later(
+            ctx,
+            every.try_into().unwrap(),
+            move |act, ctx| {
+                act.tier1_connect_trigger(ctx, every);
+            },
+        );
+    }
+
     /// `update_routing_table_trigger` schedule updating routing table to `RoutingTableActor`
     /// Usually we do edge pruning once per hour. However it may be disabled in following cases:
     /// - there are edges, that were supposed to be added, but are still in EdgeValidatorActor,
@@ -452,6 +628,39 @@ impl PeerManagerActor {
         );
     }
 
+    /// Periodically polls the peer sources and adds the peers they return to
+    /// the peer store.  Sources are polled in blocking threads, as they may
+    /// read files or resolve host names.
+    fn poll_peer_sources_trigger(&mut self, ctx: &mut Context<Self>, every: time::Duration) {
+        for peer_source in &self.peer_sources {
+            let peer_source = peer_source.clone();
+            tokio::task::spawn_blocking(move || (peer_source.name(), peer_source.poll()))
+                .into_actor(self)
+                .map(|res, act, _ctx| match res {
+                    Ok((_, Ok(None))) => {}
+                    Ok((name, Ok(Some(peers)))) => {
+                        info!(target: "network", source = %name, peers = peers.len(), "Loaded peers");
+                        if let Err(err) = act.peer_store.add_seed_peers(&act.clock, peers) {
+                            error!(target: "network", ?err, "Failed to add peers to the peer store");
+                        }
+                    }
+                    Ok((name, Err(err))) => {
+                        warn!(target: "network", source = %name, ?err, "Failed to load peers");
+                    }
+                    Err(err) => error!(target: "network", ?err, "Peer source panicked"),
+                })
+                .spawn(ctx);
+        }
+
+        near_performance_metrics::actix::run_later(
+            ctx,
+            every.try_into().unwrap(),
+            move |act, ctx| {
+                act.poll_peer_sources_trigger(ctx, every);
+            },
+        );
+    }
+
     /// Receives list of edges that were verified, in a trigger every 20ms, and adds them to
     /// the routing table.
     fn broadcast_validated_edges_trigger(
@@ -621,10 +830,11 @@ impl PeerManagerActor {
                 // sending messages.
                 let known_accounts = act.routing_table_view.get_announce_accounts();
                 addr.do_send(SendMessage {
-                    message: PeerMessage::SyncRoutingTable(RoutingTableUpdate::new(
-                        known_edges,
-                        known_accounts.cloned().collect(),
-                    )),
+                    message: PeerMessage::SyncRoutingTable(RoutingTableUpdate {
+                        edges: known_edges,
+                        accounts: known_accounts.cloned().collect(),
+                        validators: act.tier1.validators().cloned().collect(),
+                    }),
                     context: Span::current().context(),
                 });
 
@@ -781,6 +991,9 @@ impl PeerManagerActor {
         let peer_counter = self.peer_counter.clone();
         peer_counter.fetch_add(1, Ordering::SeqCst);
         let clock = self.clock.clone();
+        let capture = self.capture.clone();
+        let force_encoding = self.config.proto_only.then(|| Encoding::Proto);
+        let message_quotas = self.config.message_quotas.clone();
         PeerActor::start_in_arbiter(&arbiter.handle(), move |ctx| {
             let (read, write) = tokio::io::split(stream);
 
@@ -815,7 +1028,9 @@ impl PeerManagerActor {
                 txns_since_last_block,
                 peer_counter,
                 rate_limiter,
-                None,
+                force_encoding,
+                capture,
+                message_quotas,
             )
         });
     }
@@ -861,6 +1076,16 @@ impl PeerManagerActor {
         self.whitelist_nodes.iter().any(|wn| wn.addr.ip() == *ip)
     }
 
+    /// is_tier1_ip checks whether the IP address of an inbound connection may
+    /// belong to the node of a validator (see `is_tier1_peer`), if we are a validator.
+    /// Like `is_ip_whitelisted`, it should be evaluated BEFORE the Handshake.
+    fn is_tier1_ip(&self, ip: &IpAddr) -> bool {
+        self.config.validator.as_ref().map_or(false, |v| self.tier1.is_validator(&v.account_id()))
+            && self.tier1.is_validator_ip(ip, |account_id| {
+                self.routing_table_view.peek_account_owner(account_id)
+            })
+    }
+
     /// Returns single random peer with close to the highest height
     fn highest_height_peers(&self) -> Vec<FullPeerInfo> {
         // This finds max height among peers, and returns one peer close to such height.
@@ -1055,8 +1280,11 @@ impl PeerManagerActor {
         let mut safe_set = HashSet::new();
 
         // If there is not enough non-whitelisted peers, return without disconnecting anyone.
-        let whitelisted_peers =
-            filter_peers(&|p| self.is_peer_whitelisted(&p.full_peer_info.peer_info));
+        // Connections to the other validators are treated as whitelisted.
+        let whitelisted_peers = filter_peers(&|p| {
+            self.is_peer_whitelisted(&p.full_peer_info.peer_info)
+                || self.is_tier1_peer(&p.full_peer_info.peer_info.id)
+        });
         if self.connected_peers.len() - whitelisted_peers.len()
             <= self.config.ideal_connections_hi as usize
         {
@@ -1153,13 +1381,19 @@ impl PeerManagerActor {
         }
 
         if self.is_outbound_bootstrap_needed() {
-            if let Some(peer_info) = self.peer_store.unconnected_peer(|peer_state| {
-                // Ignore connecting to ourself
-                self.my_peer_id == peer_state.peer_info.id
+            let connected_subnets = peer_score::count_subnets(
+                self.connected_peers.values().filter_map(|p| p.full_peer_info.peer_info.addr),
+            );
+            if let Some(peer_info) = self.peer_store.unconnected_peer(
+                |peer_state| {
+                    // Ignore connecting to ourself
+                    self.my_peer_id == peer_state.peer_info.id
                     || self.config.node_addr == peer_state.peer_info.addr
                     // Or to peers we are currently trying to connect to
                     || self.outgoing_peers.contains(&peer_state.peer_info.id)
-            }) {
+                },
+                &connected_subnets,
+            ) {
                 // Start monitor_peers_attempts from start after we discover the first healthy peer
                 if !self.started_connect_attempts {
                     self.started_connect_attempts = true;
@@ -1182,6 +1416,10 @@ impl PeerManagerActor {
             error!(target: "network", ?err, "Failed to remove expired peers");
         };
 
+        if let Err(err) = self.peer_store.flush_scores() {
+            error!(target: "network", ?err, "Failed to store peer scores");
+        }
+
         let new_interval = min(max_interval, interval * EXPONENTIAL_BACKOFF_RATIO);
 
         near_performance_metrics::actix::run_later(
@@ -1290,19 +1528,7 @@ impl PeerManagerActor {
         }
 
         match self.routing_table_view.find_route(&self.clock, &msg.msg.target) {
-            Ok(peer_id) => {
-                // Remember if we expect a response for this message.
-                if msg.msg.author == self.my_peer_id && msg.expect_response() {
-                    trace!(target: "network", ?msg, "initiate route back");
-                    self.routing_table_view.add_route_back(
-                        &self.clock,
-                        msg.hash(),
-                        self.my_peer_id.clone(),
-                    );
-                }
-
-                Self::send_message(&self.connected_peers, peer_id, PeerMessage::Routed(msg))
-            }
+            Ok(peer_id) => self.send_signed_message_via(peer_id, msg),
             Err(find_route_error) => {
                 // TODO(MarX, #1369): Message is dropped here. Define policy for this case.
                 metrics::MessageDropped::NoRouteFound.inc(&msg.msg.body);
@@ -1320,6 +1546,41 @@ impl PeerManagerActor {
         }
     }
 
+    /// Send signed message to the connected peer, which is the next hop on its route.
+    /// Return whether the message is sent or not.
+    fn send_signed_message_via(&mut self, next_hop: PeerId, msg: Box<RoutedMessageV2>) -> bool {
+        // Remember if we expect a response for this message.
+        if msg.msg.author == self.my_peer_id && msg.expect_response() {
+            trace!(target: "network", ?msg, "initiate route back");
+            self.routing_table_view.add_route_back(
+                &self.clock,
+                msg.hash(),
+                self.my_peer_id.clone(),
+            );
+            if let (
+                RoutedMessageBody::PartialEncodedChunkRequest(_),
+                PeerIdOrHash::PeerId(target),
+            ) = (&msg.msg.body, &msg.msg.target)
+            {
+                self.outstanding_chunk_requests.put(msg.hash(), target.clone());
+            }
+        }
+
+        Self::send_message(&self.connected_peers, next_hop, PeerMessage::Routed(msg))
+    }
+
+    /// Connected peer to send a consensus message for the validator through,
+    /// bypassing the routing table: the validator itself or one of its relays.
+    fn tier1_next_hop(&self, account_id: &AccountId, target: &PeerId) -> Option<PeerId> {
+        if self.connected_peers.contains_key(target) {
+            return Some(target.clone());
+        }
+        self.tier1
+            .peer_ids(account_id)
+            .into_iter()
+            .find(|peer_id| self.connected_peers.contains_key(peer_id))
+    }
+
     /// Route message to target peer.
     /// Return whether the message is sent or not.
     fn send_message_to_peer(&mut self, msg: RawRoutedMessage) -> bool {
@@ -1346,6 +1607,16 @@ impl PeerManagerActor {
             }
         };
 
+        if tier1::is_tier1_message(&msg) {
+            if let Some(next_hop) = self.tier1_next_hop(account_id, &target) {
+                metrics::TIER1_MESSAGES.with_label_values(&[<&'static str>::from(&msg)]).inc();
+                let msg =
+                    RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(target), body: msg };
+                let msg = self.sign_routed_message(msg, self.my_peer_id.clone());
+                return self.send_signed_message_via(next_hop, msg);
+            }
+        }
+
         let msg = RawRoutedMessage { target: AccountOrPeerIdOrHash::PeerId(target), body: msg };
         self.send_message_to_peer(msg)
     }
@@ -1561,6 +1832,11 @@ impl PeerManagerActor {
                 self.broadcast_accounts(vec![announce_account]);
                 NetworkResponses::NoResponse
             }
+            NetworkRequests::SetValidators(epochs) => {
+                self.tier1.set_keys(epochs);
+                self.advertise_validator();
+                NetworkResponses::NoResponse
+            }
             NetworkRequests::PartialEncodedChunkRequest { target, request, create_time } => {
                 metrics::PARTIAL_ENCODED_CHUNK_REQUEST_DELAY
                     .observe((self.clock.now() - create_time.0).as_seconds_f64());
@@ -1712,7 +1988,7 @@ impl PeerManagerActor {
             || msg
                 .stream
                 .peer_addr()
-                .map(|addr| self.is_ip_whitelisted(&addr.ip()))
+                .map(|addr| self.is_ip_whitelisted(&addr.ip()) || self.is_tier1_ip(&addr.ip()))
                 .unwrap_or(false)
         {
             self.try_connect_peer(ctx.address(), msg.stream, PeerType::Inbound, None, None);
@@ -1808,6 +2084,7 @@ impl PeerManagerActor {
         if msg.peer_type == PeerType::Inbound
             && !self.is_inbound_allowed()
             && !self.is_peer_whitelisted(&msg.peer_info)
+            && !self.is_tier1_peer(&msg.peer_info.id)
         {
             // TODO(1896): Gracefully drop inbound connection for other peer.
             debug!(target: "network",
@@ -1967,6 +2244,10 @@ impl PeerManagerActor {
                 }
                 PeerToManagerMsgResp::Empty
             }
+            PeerToManagerMsg::UpdatePeerScore(peer_id, event) => {
+                self.peer_store.update_score(&peer_id, &event);
+                PeerToManagerMsgResp::Empty
+            }
             PeerToManagerMsg::ReceivedMessage(peer_id, last_time_received_message) => {
                 if let Some(connected_peer) = self.connected_peers.get_mut(&peer_id) {
                     connected_peer.last_time_received_message = last_time_received_message;
@@ -2044,6 +2325,7 @@ impl PeerManagerActor {
                 // Process edges and add new edges to the routing table. Also broadcast new edges.
                 let edges = routing_table_update.edges;
                 let accounts = routing_table_update.accounts;
+                self.broadcast_validators(Some(&peer_id), routing_table_update.validators);
 
                 // Filter known accounts before validating them.
                 let accounts: Vec<(AnnounceAccount, Option<EpochId>)> = accounts
@@ -2104,6 +2386,7 @@ impl PeerManagerActor {
 
         if Self::message_for_me(&mut self.routing_table_view, &self.my_peer_id, &msg.msg.target) {
             self.record_routed_msg_latency(&msg);
+            self.credit_chunk_response(&msg);
             // Handle Ping and Pong message if they are for us without sending to client.
             // i.e. Return false in case of Ping and Pong
             match &msg.msg.body {
@@ -2129,6 +2412,21 @@ impl PeerManagerActor {
         }
     }
 
+    /// Credits the peer which served the chunk parts (rather than the one
+    /// which routed them), if it answered a request sent by this node.
+    /// Unsolicited responses and repeated ones are not credited.
+    fn credit_chunk_response(&mut self, msg: &RoutedMessageV2) {
+        if let (RoutedMessageBody::PartialEncodedChunkResponse(_), PeerIdOrHash::Hash(hash)) =
+            (&msg.msg.body, &msg.msg.target)
+        {
+            if self.outstanding_chunk_requests.peek(hash) == Some(&msg.msg.author) {
+                self.outstanding_chunk_requests.pop(hash);
+                self.peer_store
+                    .update_score(&msg.msg.author, &peer_score::PeerScoreEvent::UsefulMessage);
+            }
+        }
+    }
+
     // The routed message received its destination. If the timestamp of creation of this message is
     // known, then update the corresponding latency metric histogram.
     fn record_routed_msg_latency(&self, msg: &RoutedMessageV2) {
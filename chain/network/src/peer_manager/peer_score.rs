@@ -0,0 +1,118 @@
+//! Scoring of known peers, used to choose which peers to connect to.
+//!
+//! A peer's score grows with the number of requested blocks and chunk parts
+//! it has sent us and drops with its latency and with every misbehaviour.
+//! Peers we know nothing about score 0.  When choosing a peer to connect to,
+//! the score is also lowered for every connection we already have to the
+//! peer's subnet, so that a few operators can't take all our connections.
+
+use near_network_primitives::time;
+use near_network_primitives::types::{KnownPeerState, PeerScore};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+
+/// Weight of the previous value in the moving average of latency.
+const LATENCY_SMOOTHING: f64 = 0.8;
+/// Score lost per second of latency.
+const LATENCY_WEIGHT: f64 = 2.0;
+/// Score lost per misbehaviour.
+const MISBEHAVIOUR_WEIGHT: f64 = 5.0;
+/// Score lost per connection we already have to the subnet of the peer.
+const SUBNET_CONNECTION_WEIGHT: f64 = 2.0;
+/// Number of the best candidates the peer to connect to is chosen from at
+/// random, so that peers we know nothing about keep being tried.
+const SELECTION_POOL_SIZE: usize = 3;
+
+/// Event affecting the score of a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PeerScoreEvent {
+    /// Round trip time of a handshake with the peer.
+    Latency(time::Duration),
+    /// The peer sent us a block or a chunk part we requested.
+    UsefulMessage,
+    /// The peer misbehaved.
+    Misbehaviour,
+}
+
+pub(crate) fn update_score(score: &mut PeerScore, event: &PeerScoreEvent) {
+    match event {
+        PeerScoreEvent::Latency(latency) => {
+            score.latency = Some(match score.latency {
+                Some(average) => average * LATENCY_SMOOTHING + *latency * (1.0 - LATENCY_SMOOTHING),
+                None => *latency,
+            })
+        }
+        PeerScoreEvent::UsefulMessage => score.useful_messages += 1,
+        PeerScoreEvent::Misbehaviour => score.misbehaviours += 1,
+    }
+}
+
+/// Score of the peer, the higher the better.
+pub(crate) fn score_value(score: &PeerScore) -> f64 {
+    let usefulness = (score.useful_messages as f64).ln_1p();
+    let latency = score.latency.map_or(0.0, |latency| latency.as_seconds_f64());
+    usefulness - LATENCY_WEIGHT * latency - MISBEHAVIOUR_WEIGHT * score.misbehaviours as f64
+}
+
+/// Subnet of an address: /24 for IPv4 and /48 for IPv6 addresses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Subnet {
+    V4([u8; 3]),
+    V6([u16; 3]),
+}
+
+impl Subnet {
+    pub fn of(addr: &SocketAddr) -> Self {
+        let ip = match addr.ip() {
+            // IPv4-mapped IPv6 addresses belong to the IPv4 subnet.
+            IpAddr::V6(ip) if ip.segments()[..6] == [0, 0, 0, 0, 0, 0xffff] => {
+                IpAddr::V4(ip.to_ipv4().unwrap())
+            }
+            ip => ip,
+        };
+        match ip {
+            IpAddr::V4(ip) => {
+                let [a, b, c, _] = ip.octets();
+                Self::V4([a, b, c])
+            }
+            IpAddr::V6(ip) => {
+                let [a, b, c, ..] = ip.segments();
+                Self::V6([a, b, c])
+            }
+        }
+    }
+}
+
+/// Counts the addresses in every subnet.
+pub(crate) fn count_subnets(addrs: impl Iterator<Item = SocketAddr>) -> HashMap<Subnet, usize> {
+    let mut counts = HashMap::new();
+    for addr in addrs {
+        *counts.entry(Subnet::of(&addr)).or_default() += 1;
+    }
+    counts
+}
+
+/// Chooses the peer to connect to among the candidates, given the number of
+/// connections we have to every subnet.  Candidates without an address are
+/// skipped.
+pub(crate) fn choose_peer<'a>(
+    candidates: impl Iterator<Item = &'a KnownPeerState>,
+    connected_subnets: &HashMap<Subnet, usize>,
+) -> Option<&'a KnownPeerState> {
+    let mut candidates: Vec<(f64, &KnownPeerState)> = candidates
+        .filter_map(|peer_state| {
+            let addr = peer_state.peer_info.addr?;
+            let connections = connected_subnets.get(&Subnet::of(&addr)).copied().unwrap_or(0);
+            let value =
+                score_value(&peer_state.score) - SUBNET_CONNECTION_WEIGHT * connections as f64;
+            Some((value, peer_state))
+        })
+        .collect();
+    // Shuffling first breaks ties between equally scored peers at random.
+    candidates.shuffle(&mut thread_rng());
+    candidates.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    candidates.truncate(SELECTION_POOL_SIZE);
+    candidates.choose(&mut thread_rng()).map(|(_, peer_state)| *peer_state)
+}
@@ -0,0 +1,114 @@
+//! Sources of peers to bootstrap from, in addition to the boot nodes and the
+//! peers learned from other peers.
+
+use anyhow::Context;
+use near_network_primitives::types::PeerInfo;
+use near_primitives::network::PeerId;
+use parking_lot::Mutex;
+use std::net::ToSocketAddrs;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+/// Source of peers to bootstrap from.  `PeerManagerActor` polls the sources
+/// periodically, in a thread where blocking is fine, and adds the peers they
+/// return to the peer store with the same trust as the boot nodes.
+pub trait PeerSource: Send + Sync + 'static {
+    /// Name of the source to use in logs.
+    fn name(&self) -> String;
+
+    /// Returns the peers of the source if they changed since the last call.
+    fn poll(&self) -> anyhow::Result<Option<Vec<PeerInfo>>>;
+}
+
+/// List of peers in a local file, reloaded whenever the file is modified.
+///
+/// Every line is a peer in the `peer_id@host:port` format of `boot_nodes`,
+/// where host is an IP address or a domain name resolved on every reload.
+/// Empty lines and lines starting with `#` are ignored.
+pub struct SeedFile {
+    path: PathBuf,
+    /// Modification time of the file when it was last loaded.
+    modified: Mutex<Option<SystemTime>>,
+}
+
+impl SeedFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path, modified: Mutex::new(None) }
+    }
+
+    fn load(&self) -> anyhow::Result<Vec<PeerInfo>> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        let mut peers = vec![];
+        for (i, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let peer = parse_seed(line).with_context(|| format!("line {}: {}", i + 1, line))?;
+            peers.push(peer);
+        }
+        Ok(peers)
+    }
+}
+
+impl PeerSource for SeedFile {
+    fn name(&self) -> String {
+        format!("seed file {}", self.path.display())
+    }
+
+    fn poll(&self) -> anyhow::Result<Option<Vec<PeerInfo>>> {
+        let modified = std::fs::metadata(&self.path)?.modified()?;
+        let mut last_modified = self.modified.lock();
+        if *last_modified == Some(modified) {
+            return Ok(None);
+        }
+        let peers = self.load()?;
+        *last_modified = Some(modified);
+        Ok(Some(peers))
+    }
+}
+
+fn parse_seed(s: &str) -> anyhow::Result<PeerInfo> {
+    let (peer_id, host_port) = s.split_once('@').context("expected peer_id@host:port")?;
+    let peer_id = PeerId::new(peer_id.parse().context("invalid peer id")?);
+    let addr = host_port
+        .to_socket_addrs()
+        .with_context(|| format!("failed to resolve {}", host_port))?
+        .next()
+        .with_context(|| format!("no addresses for {}", host_port))?;
+    Ok(PeerInfo { id: peer_id, addr: Some(addr), account_id: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_crypto::{KeyType, SecretKey};
+
+    #[test]
+    fn test_seed_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("seeds.txt");
+        let peer_id = PeerId::new(SecretKey::from_seed(KeyType::ED25519, "seed").public_key());
+        std::fs::write(&path, format!("# Seeds\n\n{}@127.0.0.1:24567\n", peer_id)).unwrap();
+
+        let seed_file = SeedFile::new(path.clone());
+        let peers = seed_file.poll().unwrap().unwrap();
+        assert_eq!(peers, vec![PeerInfo::new(peer_id.clone(), "127.0.0.1:24567".parse().unwrap())]);
+        // Unchanged.
+        assert_eq!(seed_file.poll().unwrap(), None);
+
+        // The modification time is set explicitly, since writes in quick succession may leave
+        // it unchanged on filesystems with coarse timestamps.
+        let modified = std::fs::metadata(&path).unwrap().modified().unwrap();
+        let write = |contents: String, seconds: u64| {
+            std::fs::write(&path, contents).unwrap();
+            let mtime = modified + std::time::Duration::from_secs(seconds);
+            filetime::set_file_mtime(&path, filetime::FileTime::from_system_time(mtime)).unwrap();
+        };
+        write(format!("{}@127.0.0.1:24568\nnot a peer\n", peer_id), 1);
+        assert!(seed_file.poll().is_err());
+        write(format!("{}@127.0.0.1:24568\n", peer_id), 2);
+        let peers = seed_file.poll().unwrap().unwrap();
+        assert_eq!(peers[0].addr, Some("127.0.0.1:24568".parse().unwrap()));
+    }
+}
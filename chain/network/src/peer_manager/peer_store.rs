@@ -1,3 +1,4 @@
+use crate::peer_manager::peer_score::{self, PeerScoreEvent, Subnet};
 use crate::store;
 use anyhow::bail;
 use near_network_primitives::time;
@@ -8,7 +9,7 @@ use near_primitives::network::PeerId;
 use rand::seq::IteratorRandom;
 use rand::thread_rng;
 use std::collections::hash_map::{Entry, Iter};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
 use std::ops::Not;
 use tracing::{debug, error, info};
@@ -52,6 +53,8 @@ pub struct PeerStore {
     // they will not be present in this list, otherwise they will be present.
     addr_peers: HashMap<SocketAddr, VerifiedPeer>,
     blacklist: Blacklist,
+    /// Peers whose scores changed since they were last written to the DB.
+    dirty_scores: HashSet<PeerId>,
 }
 
 impl PeerStore {
@@ -105,6 +108,7 @@ impl PeerStore {
                 first_seen: peer_state.first_seen,
                 last_seen: peer_state.last_seen,
                 status,
+                score: peer_state.score,
             };
 
             let is_blacklisted =
@@ -125,6 +129,7 @@ impl PeerStore {
                         // If it says in database, that peer should be banned, ban the peer.
                         current_peer_state.get_mut().status = peer_state.status;
                     }
+                    current_peer_state.get_mut().score = peer_state.score;
                 }
                 // Peer is not a boot node
                 Entry::Vacant(entry) => {
@@ -141,8 +146,13 @@ impl PeerStore {
             }
         }
 
-        let mut peer_store =
-            PeerStore { store, peer_states: peerid_2_state, addr_peers: addr_2_peer, blacklist };
+        let mut peer_store = PeerStore {
+            store,
+            peer_states: peerid_2_state,
+            addr_peers: addr_2_peer,
+            blacklist,
+            dirty_scores: HashSet::new(),
+        };
         peer_store.delete_peers(&peers_to_delete)?;
         Ok(peer_store)
     }
@@ -174,7 +184,9 @@ impl PeerStore {
         let entry = self.peer_states.get_mut(&peer_info.id).unwrap();
         entry.last_seen = clock.now_utc();
         entry.status = KnownPeerStatus::Connected;
-        Ok(self.store.set_peer_state(&peer_info.id, entry)?)
+        self.store.set_peer_state(&peer_info.id, entry)?;
+        self.dirty_scores.remove(&peer_info.id);
+        Ok(())
     }
 
     pub(crate) fn peer_disconnected(
@@ -186,6 +198,7 @@ impl PeerStore {
             peer_state.last_seen = clock.now_utc();
             peer_state.status = KnownPeerStatus::NotConnected;
             self.store.set_peer_state(peer_id, peer_state)?;
+            self.dirty_scores.remove(peer_id);
         } else {
             bail!("Peer {} is missing in the peer store", peer_id);
         }
@@ -202,7 +215,9 @@ impl PeerStore {
             let now = clock.now_utc();
             peer_state.last_seen = now;
             peer_state.status = KnownPeerStatus::Banned(ban_reason, now);
+            peer_score::update_score(&mut peer_state.score, &PeerScoreEvent::Misbehaviour);
             self.store.set_peer_state(peer_id, peer_state)?;
+            self.dirty_scores.remove(peer_id);
         } else {
             bail!("Peer {} is missing in the peer store", peer_id);
         }
@@ -231,6 +246,23 @@ impl PeerStore {
         Ok(())
     }
 
+    /// Updates the score of a known peer.  The score is written to the DB by
+    /// [`Self::flush_scores`] or with any other change of the peer's state.
+    pub(crate) fn update_score(&mut self, peer_id: &PeerId, event: &PeerScoreEvent) {
+        if let Some(peer_state) = self.peer_states.get_mut(peer_id) {
+            peer_score::update_score(&mut peer_state.score, event);
+            self.dirty_scores.insert(peer_id.clone());
+        }
+    }
+
+    /// Writes the scores changed since they were last written to the DB.
+    pub(crate) fn flush_scores(&mut self) -> anyhow::Result<()> {
+        for peer_id in std::mem::take(&mut self.dirty_scores) {
+            self.touch(&peer_id)?;
+        }
+        Ok(())
+    }
+
     /// Find a random subset of peers based on filter.
     fn find_peers<F>(&self, filter: F, count: usize) -> Vec<PeerInfo>
     where
@@ -245,21 +277,20 @@ impl PeerStore {
     }
 
     /// Return unconnected or peers with unknown status that we can try to connect to.
-    /// Peers with unknown addresses are filtered out.
+    /// Peers with unknown addresses are filtered out.  Peers with higher scores
+    /// and in subnets with fewer connections, as given by `connected_subnets`,
+    /// are preferred.
     pub(crate) fn unconnected_peer(
         &self,
         ignore_fn: impl Fn(&KnownPeerState) -> bool,
+        connected_subnets: &HashMap<Subnet, usize>,
     ) -> Option<PeerInfo> {
-        self.find_peers(
-            |p| {
-                (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
-                    && !ignore_fn(p)
-                    && p.peer_info.addr.is_some()
-            },
-            1,
-        )
-        .get(0)
-        .cloned()
+        let candidates = self.peer_states.values().filter(|p| {
+            (p.status == KnownPeerStatus::NotConnected || p.status == KnownPeerStatus::Unknown)
+                && !ignore_fn(p)
+                && p.peer_info.addr.is_some()
+        });
+        peer_score::choose_peer(candidates, connected_subnets).map(|p| p.peer_info.clone())
     }
 
     /// Return healthy known peers up to given amount.
@@ -294,6 +325,7 @@ impl PeerStore {
 
     /// Copies the in-mem state of the peer to DB.
     fn touch(&mut self, peer_id: &PeerId) -> anyhow::Result<()> {
+        self.dirty_scores.remove(peer_id);
         Ok(match self.peer_states.get(peer_id) {
             Some(peer_state) => self.store.set_peer_state(&peer_id, peer_state)?,
             None => (),
@@ -427,6 +459,24 @@ impl PeerStore {
         Ok(())
     }
 
+    /// Adds peers from a [`PeerSource`](super::peer_source::PeerSource).
+    ///
+    /// The sources are configured by the node operator, so the peers are
+    /// trusted as much as the boot nodes.
+    pub(crate) fn add_seed_peers(
+        &mut self,
+        clock: &time::Clock,
+        peers: Vec<PeerInfo>,
+    ) -> anyhow::Result<()> {
+        for peer_info in peers {
+            let is_blacklisted = peer_info.addr.map_or(false, |addr| self.blacklist.contains(addr));
+            if !is_blacklisted {
+                self.add_peer(clock, peer_info, TrustLevel::Signed)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Adds a peer we’ve connected to but haven’t verified ID yet.
     ///
     /// We've connected to the host (thus know that the address is correct) and
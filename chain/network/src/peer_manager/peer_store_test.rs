@@ -1,5 +1,5 @@
 use near_crypto::{KeyType, SecretKey};
-use near_network_primitives::types::{Blacklist, BlacklistEntry};
+use near_network_primitives::types::{Blacklist, BlacklistEntry, PeerScore};
 use near_store::test_utils::create_test_store;
 use near_store::{Store, StoreOpener};
 use std::collections::HashSet;
@@ -61,8 +61,8 @@ fn test_unconnected_peer() {
         let store = store::Store::from(opener.open());
         let peer_store =
             PeerStore::new(&clock.clock(), store, &boot_nodes, Default::default()).unwrap();
-        assert!(peer_store.unconnected_peer(|_| false).is_some());
-        assert!(peer_store.unconnected_peer(|_| true).is_none());
+        assert!(peer_store.unconnected_peer(|_| false, &HashMap::new()).is_some());
+        assert!(peer_store.unconnected_peer(|_| true, &HashMap::new()).is_none());
     }
 }
 
@@ -383,3 +383,71 @@ fn test_delete_peers() {
     }
     assert_peers_in_store(&opener, &[]);
 }
+
+#[test]
+fn test_peer_scores() {
+    let clock = time::FakeClock::default();
+    let (_tmp_dir, opener) = Store::test_opener();
+    let peer_infos: Vec<_> = (0..5)
+        .map(|i| {
+            let addr = SocketAddrV4::new(Ipv4Addr::new(10, 0, i, 1), 24567).into();
+            get_peer_info(get_peer_id(format!("node{}", i)), Some(addr))
+        })
+        .collect();
+    let latency = time::Duration::milliseconds(100);
+    {
+        let store = store::Store::from(opener.open());
+        let mut peer_store =
+            PeerStore::new(&clock.clock(), store, &peer_infos, Default::default()).unwrap();
+        peer_store.update_score(&peer_infos[1].id, &PeerScoreEvent::UsefulMessage);
+        peer_store.update_score(&peer_infos[1].id, &PeerScoreEvent::Latency(latency));
+        peer_store.peer_ban(&clock.clock(), &peer_infos[2].id, ReasonForBan::Abusive).unwrap();
+        peer_store.peer_unban(&peer_infos[2].id).unwrap();
+        peer_store.flush_scores().unwrap();
+    }
+
+    let store = store::Store::from(opener.open());
+    let peer_store =
+        PeerStore::new(&clock.clock(), store, &peer_infos, Default::default()).unwrap();
+    let score = |i: usize| peer_store.peer_states[&peer_infos[i].id].score.clone();
+    assert_eq!(
+        score(1),
+        PeerScore { latency: Some(latency), useful_messages: 1, misbehaviours: 0 }
+    );
+    assert_eq!(score(2).misbehaviours, 1);
+
+    // The misbehaving peer and the peer in the subnet we're already connected
+    // to are never among the best candidates.
+    let connected_subnets = peer_score::count_subnets(
+        [SocketAddrV4::new(Ipv4Addr::new(10, 0, 0, 2), 1).into()].into_iter(),
+    );
+    for _ in 0..20 {
+        let peer_info = peer_store.unconnected_peer(|_| false, &connected_subnets).unwrap();
+        assert!(peer_info.id != peer_infos[0].id && peer_info.id != peer_infos[2].id);
+    }
+}
+
+#[test]
+fn test_subnet() {
+    let v4: SocketAddr = "10.1.2.3:24567".parse().unwrap();
+    let mapped: SocketAddr = "[::ffff:10.1.2.200]:1".parse().unwrap();
+    let other: SocketAddr = "10.1.3.3:24567".parse().unwrap();
+    assert_eq!(Subnet::of(&v4), Subnet::of(&mapped));
+    assert_ne!(Subnet::of(&v4), Subnet::of(&other));
+    let v6: SocketAddr = "[2001:db8:1:2::1]:1".parse().unwrap();
+    let v6_same: SocketAddr = "[2001:db8:1:3::1]:1".parse().unwrap();
+    assert_eq!(Subnet::of(&v6), Subnet::of(&v6_same));
+}
+
+#[test]
+fn test_add_seed_peers() {
+    let clock = time::FakeClock::default();
+    let store = store::Store::from(create_test_store());
+    let blacklist: Blacklist =
+        ["127.0.0.1:1".parse::<BlacklistEntry>().unwrap()].into_iter().collect();
+    let mut peer_store = PeerStore::new(&clock.clock(), store, &[], blacklist).unwrap();
+    let seeds = vec![gen_peer_info(0), gen_peer_info(1)];
+    peer_store.add_seed_peers(&clock.clock(), seeds.clone()).unwrap();
+    assert!(check_exist(&peer_store, &seeds[0].id, Some((get_addr(0), TrustLevel::Signed))));
+    assert!(!peer_store.peer_states.contains_key(&seeds[1].id));
+}
@@ -4,16 +4,18 @@ use crate::peer;
 use crate::peer_manager;
 use crate::peer_manager::peer_manager_actor::Event as PME;
 use crate::peer_manager::testonly::Event;
-use crate::testonly::make_rng;
-use crate::types::{PeerMessage, RoutingTableUpdate};
+use crate::testonly::{fake_client, make_rng};
+use crate::types::{Handshake, PeerMessage, RoutingTableUpdate};
 use near_logger_utils::init_test_logger;
 use near_network_primitives::time;
-use near_network_primitives::types::NetworkConfig;
+use near_network_primitives::types::{NetworkConfig, RateLimit};
 use near_network_primitives::types::{Ping, RoutedMessageBody};
 use near_primitives::network::PeerId;
+use near_primitives::version::PROTOCOL_VERSION;
 use rand::Rng as _;
 use std::collections::HashSet;
 use std::sync::Arc;
+use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
 use tokio::net::TcpStream;
 
 // After the initial exchange, all subsequent SyncRoutingTable messages are
@@ -149,3 +151,110 @@ async fn ttl() {
         }
     }
 }
+
+// A node with proto_only set disconnects the peers using the borsh encoding
+// right away, and still accepts the ones using proto.
+#[tokio::test]
+async fn proto_only() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+    let mut cfg = NetworkConfig::from_seed("test1", crate::test_utils::open_port());
+    cfg.proto_only = true;
+    let pm = peer_manager::testonly::start(chain.clone(), cfg).await;
+    let pm_id = PeerId::new(pm.cfg.node_key.public_key());
+
+    let borsh_cfg = peer::testonly::PeerConfig {
+        signer: data::make_signer(rng),
+        chain: chain.clone(),
+        peers: vec![],
+        start_handshake_with: Some(pm_id.clone()),
+        force_encoding: Some(Encoding::Borsh),
+    };
+    let mut stream = TcpStream::connect(pm.cfg.node_addr.unwrap()).await.unwrap();
+    let handshake = PeerMessage::Handshake(Handshake::new(
+        PROTOCOL_VERSION,
+        borsh_cfg.id(),
+        pm_id.clone(),
+        Some(stream.local_addr().unwrap().port()),
+        chain.get_info(),
+        borsh_cfg.partial_edge_info(&pm_id, 1),
+    ))
+    .serialize(Encoding::Borsh, PROTOCOL_VERSION);
+    stream.write_u32_le(handshake.len() as u32).await.unwrap();
+    stream.write_all(&handshake).await.unwrap();
+    // The connection is closed without a response, long before the handshake timeout.
+    let mut buf = [0; 1];
+    let res = tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut buf))
+        .await
+        .expect("connection is still open");
+    assert!(matches!(res, Ok(0) | Err(_)), "got {res:?}");
+
+    let proto_cfg = peer::testonly::PeerConfig {
+        signer: data::make_signer(rng),
+        chain,
+        peers: vec![],
+        start_handshake_with: Some(pm_id),
+        force_encoding: Some(Encoding::Proto),
+    };
+    let stream = TcpStream::connect(pm.cfg.node_addr.unwrap()).await.unwrap();
+    let mut peer =
+        peer::testonly::PeerHandle::start_endpoint(clock.clock(), proto_cfg, stream).await;
+    peer.complete_handshake().await;
+}
+
+// Messages over quota are delayed, while the consensus-critical ones, which
+// have their own quota, are processed right away.
+#[tokio::test]
+async fn message_quotas() {
+    init_test_logger();
+    let mut rng = make_rng(921853233);
+    let rng = &mut rng;
+    let mut clock = time::FakeClock::default();
+    let chain = Arc::new(data::Chain::make(&mut clock, rng, 10));
+    let mut cfg = NetworkConfig::from_seed("test1", crate::test_utils::open_port());
+    cfg.message_quotas
+        .per_message_type
+        .insert("Transaction".to_string(), RateLimit { per_second: 2, burst: 1 });
+    let mut pm = peer_manager::testonly::start(chain.clone(), cfg).await;
+    let peer_cfg = peer::testonly::PeerConfig {
+        signer: data::make_signer(rng),
+        chain: chain.clone(),
+        peers: vec![],
+        start_handshake_with: Some(PeerId::new(pm.cfg.node_key.public_key())),
+        force_encoding: Some(Encoding::Proto),
+    };
+    let stream = TcpStream::connect(pm.cfg.node_addr.unwrap()).await.unwrap();
+    let mut peer =
+        peer::testonly::PeerHandle::start_endpoint(clock.clock(), peer_cfg, stream).await;
+    peer.complete_handshake().await;
+
+    let txs: Vec<_> = (0..3).map(|_| data::make_signed_transaction(rng)).collect();
+    for tx in &txs {
+        peer.send(PeerMessage::Transaction(tx.clone())).await;
+    }
+    let block = chain.blocks[5].clone();
+    peer.send(PeerMessage::Block(block.clone())).await;
+
+    let mut want = vec![
+        fake_client::Event::Transaction(txs[0].clone()),
+        fake_client::Event::Block(block),
+        fake_client::Event::Transaction(txs[1].clone()),
+        fake_client::Event::Transaction(txs[2].clone()),
+    ];
+    want.reverse();
+    while let Some(next) = want.pop() {
+        let got = pm
+            .events
+            .recv_until(|ev| match ev {
+                Event::Client(
+                    ev @ (fake_client::Event::Transaction(_) | fake_client::Event::Block(_)),
+                ) => Some(ev),
+                _ => None,
+            })
+            .await;
+        assert_eq!(next, got);
+    }
+}
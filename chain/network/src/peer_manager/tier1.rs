@@ -0,0 +1,317 @@
+//! TIER1 network: direct connections between the validators of the current and
+//! the next epoch, used to send consensus messages (block approvals and chunk
+//! parts) without going through multi-hop routes.
+//!
+//! Validators sign the addresses they can be reached at (their own public
+//! addresses or the addresses of their relay nodes) for every epoch they are a
+//! validator in, and broadcast them in `RoutingTableUpdate::validators`.
+//! Every validator connects to at least one of the advertised addresses of
+//! every other validator, and sends consensus messages for the validator over
+//! such a connection, falling back to routing if there is none.
+
+use crate::network_protocol::{PeerAddr, SignedValidator};
+use crate::types::EpochValidators;
+use near_crypto::PublicKey;
+use near_network_primitives::types::{PeerInfo, RoutedMessageBody, MAX_PEERS_PER_VALIDATOR};
+use near_primitives::network::PeerId;
+use near_primitives::types::{AccountId, EpochId};
+use rand::seq::SliceRandom;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+#[derive(thiserror::Error, Debug, PartialEq, Eq)]
+pub(crate) enum Error {
+    #[error("invalid signature of validator {0}")]
+    InvalidSignature(AccountId),
+    #[error("validator {0} advertised more than {MAX_PEERS_PER_VALIDATOR} peers")]
+    TooManyPeers(AccountId),
+}
+
+/// Whether the message should be sent over a TIER1 connection, if there is one.
+pub(crate) fn is_tier1_message(body: &RoutedMessageBody) -> bool {
+    matches!(
+        body,
+        RoutedMessageBody::BlockApproval(_)
+            | RoutedMessageBody::PartialEncodedChunk(_)
+            | RoutedMessageBody::VersionedPartialEncodedChunk(_)
+            | RoutedMessageBody::PartialEncodedChunkForward(_)
+            | RoutedMessageBody::PartialEncodedChunkRequest(_)
+    )
+}
+
+/// Validators of the current and the next epoch, and the signed addresses
+/// they can be reached at.
+#[derive(Default)]
+pub(crate) struct Tier1 {
+    keys: HashMap<(EpochId, AccountId), PublicKey>,
+    /// The newest signed data of every validator, per epoch.
+    validators: HashMap<(EpochId, AccountId), SignedValidator>,
+}
+
+impl Tier1 {
+    /// Sets the validators, forgetting the data of the validators of other epochs.
+    pub fn set_keys(&mut self, epochs: Vec<EpochValidators>) {
+        self.keys.clear();
+        for EpochValidators { epoch_id, keys } in epochs {
+            for (account_id, key) in keys {
+                self.keys.insert((epoch_id.clone(), account_id), key);
+            }
+        }
+        let keys = &self.keys;
+        self.validators.retain(|key, _| keys.contains_key(key));
+    }
+
+    /// Epochs in which the account is a validator.
+    pub fn epochs_of(&self, account_id: &AccountId) -> Vec<EpochId> {
+        self.keys
+            .keys()
+            .filter(|(_, validator)| validator == account_id)
+            .map(|(epoch_id, _)| epoch_id.clone())
+            .collect()
+    }
+
+    pub fn is_validator(&self, account_id: &AccountId) -> bool {
+        self.keys.keys().any(|(_, validator)| validator == account_id)
+    }
+
+    /// Whether there is data of the account for the epoch.
+    pub fn contains(&self, epoch_id: &EpochId, account_id: &AccountId) -> bool {
+        self.validators.contains_key(&(epoch_id.clone(), account_id.clone()))
+    }
+
+    /// Adds the data of the validators and returns the data that was new,
+    /// to broadcast it further. Data of accounts which are not validators of
+    /// the known epochs, and data older than the one we have, is skipped.
+    /// Nothing is added if any of the data isn't signed by its validator, or
+    /// lists more than `MAX_PEERS_PER_VALIDATOR` peers.
+    pub fn insert(
+        &mut self,
+        validators: Vec<SignedValidator>,
+    ) -> Result<Vec<SignedValidator>, Error> {
+        let mut new = vec![];
+        for signed in validators {
+            let validator = signed.validator();
+            let key = (validator.epoch_id.clone(), validator.account_id.clone());
+            let public_key = match self.keys.get(&key) {
+                Some(public_key) => public_key,
+                None => continue,
+            };
+            if let Some(known) = self.validators.get(&key) {
+                if known.validator().timestamp >= validator.timestamp {
+                    continue;
+                }
+            }
+            if !signed.verify(public_key) {
+                return Err(Error::InvalidSignature(validator.account_id.clone()));
+            }
+            if validator.peers.len() > MAX_PEERS_PER_VALIDATOR {
+                return Err(Error::TooManyPeers(validator.account_id.clone()));
+            }
+            new.push(signed);
+        }
+        // The same validator may come more than once, keep the newest data.
+        new.sort_by_key(|signed| signed.validator().timestamp);
+        for signed in &new {
+            let validator = signed.validator();
+            let key = (validator.epoch_id.clone(), validator.account_id.clone());
+            self.validators.insert(key, signed.clone());
+        }
+        Ok(new)
+    }
+
+    /// All the data of the validators, to sync it with a new peer.
+    pub fn validators(&self) -> impl Iterator<Item = &SignedValidator> {
+        self.validators.values()
+    }
+
+    /// Ids of the peers the validator can be reached at.
+    pub fn peer_ids(&self, account_id: &AccountId) -> Vec<PeerId> {
+        self.validators
+            .values()
+            .map(|signed| signed.validator())
+            .filter(|validator| &validator.account_id == account_id)
+            .flat_map(|validator| validator.peers.iter().filter_map(|peer| peer.peer_id.clone()))
+            .collect()
+    }
+
+    /// Whether the peer is the node of some validator: one of the peers the
+    /// validator can be reached at, which is also the peer the validator has
+    /// announced its account with (as returned by `account_owner`). Relays
+    /// of the validators don't count.
+    pub fn is_validator_peer(
+        &self,
+        peer_id: &PeerId,
+        account_owner: impl Fn(&AccountId) -> Option<PeerId>,
+    ) -> bool {
+        self.validator_peers(account_owner).any(|peer| peer.peer_id.as_ref() == Some(peer_id))
+    }
+
+    /// Whether the address is the one of the node of some validator, see
+    /// `is_validator_peer`.
+    pub fn is_validator_ip(
+        &self,
+        ip: &IpAddr,
+        account_owner: impl Fn(&AccountId) -> Option<PeerId>,
+    ) -> bool {
+        self.validator_peers(account_owner).any(|peer| peer.addr.ip() == *ip)
+    }
+
+    fn validator_peers(
+        &self,
+        account_owner: impl Fn(&AccountId) -> Option<PeerId>,
+    ) -> impl Iterator<Item = &PeerAddr> {
+        self.validators.values().map(|signed| signed.validator()).flat_map(move |validator| {
+            let owner = account_owner(&validator.account_id);
+            validator.peers.iter().filter(move |peer| owner.is_some() && peer.peer_id == owner)
+        })
+    }
+
+    /// Peers to connect to, so that we are connected to every validator other
+    /// than `me`: a random address of every validator we are not connected to
+    /// through any of its addresses.
+    pub fn peers_to_connect(
+        &self,
+        me: &AccountId,
+        is_connected: impl Fn(&PeerId) -> bool,
+    ) -> Vec<PeerInfo> {
+        let mut peers: HashMap<&AccountId, Vec<PeerInfo>> = HashMap::new();
+        for signed in self.validators.values() {
+            let validator = signed.validator();
+            if &validator.account_id == me {
+                continue;
+            }
+            let account_peers = peers.entry(&validator.account_id).or_default();
+            for peer in &validator.peers {
+                if let Some(peer_id) = &peer.peer_id {
+                    account_peers.push(PeerInfo::new(peer_id.clone(), peer.addr));
+                }
+            }
+        }
+        peers
+            .into_values()
+            .filter(|account_peers| !account_peers.iter().any(|peer| is_connected(&peer.id)))
+            .filter_map(|account_peers| account_peers.choose(&mut thread_rng()).cloned())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::network_protocol::testonly as data;
+    use crate::network_protocol::{PeerAddr, Validator};
+    use crate::testonly::make_rng;
+    use near_network_primitives::time;
+    use near_primitives::hash::hash;
+    use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
+
+    fn make_validator(
+        signer: &InMemoryValidatorSigner,
+        epoch_id: &EpochId,
+        peer: &PeerInfo,
+        timestamp: time::Utc,
+    ) -> SignedValidator {
+        Validator {
+            peers: vec![PeerAddr { addr: peer.addr.unwrap(), peer_id: Some(peer.id.clone()) }],
+            account_id: signer.validator_id().clone(),
+            epoch_id: epoch_id.clone(),
+            timestamp,
+        }
+        .sign(signer)
+    }
+
+    #[test]
+    fn test_tier1() {
+        let mut rng = make_rng(921853233);
+        let clock = time::FakeClock::default();
+        let signers: Vec<_> = (0..3).map(|_| data::make_validator_signer(&mut rng)).collect();
+        let peers: Vec<_> = (0..3).map(|_| data::make_peer_info(&mut rng)).collect();
+        let epoch_id = EpochId(hash(b"epoch"));
+        let other_epoch_id = EpochId(hash(b"other epoch"));
+
+        let mut tier1 = Tier1::default();
+        tier1.set_keys(vec![EpochValidators {
+            epoch_id: epoch_id.clone(),
+            keys: signers[..2]
+                .iter()
+                .map(|signer| (signer.validator_id().clone(), signer.public_key()))
+                .collect(),
+        }]);
+        assert!(tier1.is_validator(signers[0].validator_id()));
+        assert!(!tier1.is_validator(signers[2].validator_id()));
+
+        let now = clock.now_utc();
+        let v0 = make_validator(&signers[0], &epoch_id, &peers[0], now);
+        let v1 = make_validator(&signers[1], &epoch_id, &peers[1], now);
+        // Not a validator, or not a validator of the epoch.
+        let v2 = make_validator(&signers[2], &epoch_id, &peers[2], now);
+        let v1_other_epoch = make_validator(&signers[1], &other_epoch_id, &peers[1], now);
+        assert_eq!(
+            tier1.insert(vec![v0.clone(), v1.clone(), v2, v1_other_epoch]),
+            Ok(vec![v0.clone(), v1])
+        );
+        // Known data is not new.
+        assert_eq!(tier1.insert(vec![v0]), Ok(vec![]));
+        assert_eq!(tier1.peer_ids(signers[1].validator_id()), vec![peers[1].id.clone()]);
+        // Only the peer a validator announced its account with is its node.
+        let owner = |account_id: &AccountId| {
+            (account_id == signers[0].validator_id()).then(|| peers[0].id.clone())
+        };
+        assert!(tier1.is_validator_peer(&peers[0].id, owner));
+        assert!(tier1.is_validator_ip(&peers[0].addr.unwrap().ip(), owner));
+        assert!(!tier1.is_validator_peer(&peers[1].id, owner));
+        assert!(!tier1.is_validator_peer(&peers[2].id, owner));
+
+        // Data signed by another account is rejected, together with the rest of the data.
+        let v0_newer =
+            make_validator(&signers[0], &epoch_id, &peers[2], now + time::Duration::SECOND);
+        let forged = Validator {
+            account_id: signers[1].validator_id().clone(),
+            ..make_validator(&signers[2], &epoch_id, &peers[2], now + time::Duration::SECOND)
+                .validator()
+                .clone()
+        }
+        .sign(&signers[2]);
+        assert_eq!(
+            tier1.insert(vec![v0_newer.clone(), forged]),
+            Err(Error::InvalidSignature(signers[1].validator_id().clone()))
+        );
+        assert_eq!(tier1.peer_ids(signers[0].validator_id()), vec![peers[0].id.clone()]);
+        // Newer data replaces the older one.
+        assert_eq!(tier1.insert(vec![v0_newer.clone()]), Ok(vec![v0_newer]));
+        assert_eq!(tier1.peer_ids(signers[0].validator_id()), vec![peers[2].id.clone()]);
+
+        // Data listing too many peers is rejected.
+        let too_many = Validator {
+            peers: vec![
+                PeerAddr {
+                    addr: peers[2].addr.unwrap(),
+                    peer_id: Some(peers[2].id.clone())
+                };
+                MAX_PEERS_PER_VALIDATOR + 1
+            ],
+            ..make_validator(&signers[1], &epoch_id, &peers[1], now + time::Duration::SECOND)
+                .validator()
+                .clone()
+        }
+        .sign(&signers[1]);
+        assert_eq!(
+            tier1.insert(vec![too_many]),
+            Err(Error::TooManyPeers(signers[1].validator_id().clone()))
+        );
+        assert_eq!(tier1.peer_ids(signers[1].validator_id()), vec![peers[1].id.clone()]);
+
+        // We connect to the other validators only.
+        let me = signers[0].validator_id();
+        assert_eq!(
+            tier1.peers_to_connect(me, |_| false),
+            vec![PeerInfo::new(peers[1].id.clone(), peers[1].addr.unwrap())]
+        );
+        assert_eq!(tier1.peers_to_connect(me, |peer_id| peer_id == &peers[1].id), vec![]);
+
+        // Data of the validators of the old epochs is forgotten.
+        tier1.set_keys(vec![]);
+        assert_eq!(tier1.validators().count(), 0);
+    }
+}
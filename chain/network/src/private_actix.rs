@@ -2,6 +2,7 @@
 /// They are not meant to be used outside.
 use crate::network_protocol::{PeerMessage, RoutingTableUpdate};
 use crate::peer::peer_actor::PeerActor;
+use crate::peer_manager::peer_score::PeerScoreEvent;
 use conqueue::QueueSender;
 use near_network_primitives::time;
 use near_network_primitives::types::{
@@ -46,6 +47,7 @@ pub(crate) enum PeerToManagerMsg {
     RouteBack(Box<RoutedMessageBody>, CryptoHash),
     UpdatePeerInfo(PeerInfo),
     ReceivedMessage(PeerId, time::Instant),
+    UpdatePeerScore(PeerId, PeerScoreEvent),
 }
 
 /// List of all replies to messages to `PeerManager`. See `PeerManagerMessageRequest` for more details.
@@ -114,6 +114,20 @@ impl RoutingTableView {
             .ok_or(FindRouteError::AccountNotFound)
     }
 
+    /// Find peer that owns this AccountId, without caching the announcement.
+    pub(crate) fn peek_account_owner(&self, account_id: &AccountId) -> Option<PeerId> {
+        if let Some(announce_account) = self.account_peers.peek(account_id) {
+            return Some(announce_account.peer_id.clone());
+        }
+        match self.store.get_account_announcement(account_id) {
+            Err(e) => {
+                warn!(target: "network", "Error loading announce account from store: {:?}", e);
+                None
+            }
+            Ok(announce_account) => announce_account.map(|a| a.peer_id),
+        }
+    }
+
     /// Add (account id, peer id) to routing table.
     /// Note: There is at most one peer id per account id.
     pub(crate) fn add_account(&mut self, announce_account: AnnounceAccount) {
@@ -1,4 +1,5 @@
 use crate::network_protocol::Encoding;
+use crate::types::PeerMessage;
 use near_metrics::{
     exponential_buckets, try_create_histogram, try_create_histogram_vec, try_create_int_counter,
     try_create_int_counter_vec, try_create_int_gauge, Histogram, HistogramVec, IntCounter,
@@ -140,6 +141,15 @@ pub(crate) static BROADCAST_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
     .unwrap()
 });
 
+pub(crate) static TIER1_MESSAGES: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_network_tier1_messages_total",
+        "Number of routed messages sent over direct connections between validators, by message types",
+        &["type"],
+    )
+    .unwrap()
+});
+
 pub(crate) static NETWORK_ROUTED_MSG_LATENCY: Lazy<HistogramVec> = Lazy::new(|| {
     try_create_histogram_vec(
         "near_network_routed_msg_latency",
@@ -150,12 +160,29 @@ pub(crate) static NETWORK_ROUTED_MSG_LATENCY: Lazy<HistogramVec> = Lazy::new(||
     .unwrap()
 });
 
+pub(crate) static PEER_MESSAGE_DELAYED_BY_QUOTA: Lazy<IntCounterVec> = Lazy::new(|| {
+    try_create_int_counter_vec(
+        "near_peer_message_delayed_by_quota_total",
+        "Number of messages received from peers which were delayed because of the message quotas",
+        &["type"],
+    )
+    .unwrap()
+});
+pub(crate) static PEER_MESSAGE_QUOTA_DELAY: Lazy<Histogram> = Lazy::new(|| {
+    try_create_histogram(
+        "near_peer_message_quota_delay_seconds",
+        "Time the messages delayed because of the message quotas waited",
+    )
+    .unwrap()
+});
+
 #[derive(Clone, Copy, strum::AsRefStr)]
 pub(crate) enum MessageDropped {
     NoRouteFound,
     UnknownAccount,
     InputTooLong,
     MaxCapacityExceeded,
+    QuotaExceeded,
 }
 
 impl MessageDropped {
@@ -163,6 +190,10 @@ impl MessageDropped {
         self.inc_msg_type(msg.into())
     }
 
+    pub fn inc_peer_msg(self, msg: &PeerMessage) {
+        self.inc_msg_type(msg.msg_variant())
+    }
+
     pub fn inc_unknown_msg(self) {
         self.inc_msg_type("unknown")
     }
@@ -4,7 +4,7 @@
 use near_network_primitives::types::{Edge, KnownPeerState};
 use near_primitives::network::{AnnounceAccount, PeerId};
 use near_primitives::types::AccountId;
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use tracing::debug;
 
 mod schema;
@@ -118,7 +118,8 @@ impl Store {
 
 // PeerStore storage.
 impl Store {
-    /// Inserts (peer_id,peer_state) to Peers column.
+    /// Inserts (peer_id,peer_state) to Peers column and (peer_id,peer_state.score)
+    /// to PeerScores column.
     pub fn set_peer_state(
         &mut self,
         peer_id: &PeerId,
@@ -126,19 +127,33 @@ impl Store {
     ) -> Result<(), Error> {
         let mut update = self.0.new_update();
         update.set::<schema::Peers>(peer_id, peer_state);
+        update.set::<schema::PeerScores>(peer_id, &peer_state.score);
         self.0.commit(update).map_err(Error)
     }
 
-    /// Deletes rows with keys in <peers> from Peers column.
+    /// Deletes rows with keys in <peers> from Peers and PeerScores columns.
     pub fn delete_peer_states(&mut self, peers: &[PeerId]) -> Result<(), Error> {
         let mut update = self.0.new_update();
-        peers.iter().for_each(|p| update.delete::<schema::Peers>(p));
+        for p in peers {
+            update.delete::<schema::Peers>(p);
+            update.delete::<schema::PeerScores>(p);
+        }
         self.0.commit(update).map_err(Error)
     }
 
-    /// Reads the whole Peers column.
+    /// Reads the whole Peers column, together with the scores of the peers.
+    /// Peers without a score (e.g. written by an older binary) get the default one.
     pub fn list_peer_states(&self) -> Result<Vec<(PeerId, KnownPeerState)>, Error> {
-        self.0.iter::<schema::Peers>().collect::<Result<_, _>>().map_err(Error)
+        let mut scores: HashMap<_, _> =
+            self.0.iter::<schema::PeerScores>().collect::<Result<_, _>>().map_err(Error)?;
+        self.0
+            .iter::<schema::Peers>()
+            .map(|item| {
+                let (peer_id, mut peer_state) = item.map_err(Error)?;
+                peer_state.score = scores.remove(&peer_id).unwrap_or_default();
+                Ok((peer_id, peer_state))
+            })
+            .collect()
     }
 }
 
@@ -95,6 +95,34 @@ impl BorshRepr for KnownPeerStateRepr {
                 .map_err(invalid_data)?,
             last_seen: time::Utc::from_unix_timestamp_nanos(s.last_seen as i128)
                 .map_err(invalid_data)?,
+            score: Default::default(),
+        })
+    }
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PeerScoreRepr {
+    /// In nanos.
+    latency: Option<u64>,
+    useful_messages: u64,
+    misbehaviours: u64,
+}
+
+impl BorshRepr for PeerScoreRepr {
+    type T = primitives::PeerScore;
+    fn to_repr(s: &primitives::PeerScore) -> Self {
+        Self {
+            latency: s.latency.map(|d| d.whole_nanoseconds().clamp(0, u64::MAX as i128) as u64),
+            useful_messages: s.useful_messages,
+            misbehaviours: s.misbehaviours,
+        }
+    }
+
+    fn from_repr(s: Self) -> Result<primitives::PeerScore, Error> {
+        Ok(primitives::PeerScore {
+            latency: s.latency.map(|nanos| time::Duration::nanoseconds(nanos as i64)),
+            useful_messages: s.useful_messages,
+            misbehaviours: s.misbehaviours,
         })
     }
 }
@@ -143,6 +171,15 @@ impl Column for Peers {
     type Value = KnownPeerStateRepr;
 }
 
+/// Scores are kept apart from the Peers rows, so that the Peers column
+/// stays readable by the binaries which don't score peers.
+pub struct PeerScores;
+impl Column for PeerScores {
+    const COL: DBCol = DBCol::PeerScores;
+    type Key = Borsh<PeerId>;
+    type Value = PeerScoreRepr;
+}
+
 pub struct PeerComponent;
 impl Column for PeerComponent {
     const COL: DBCol = DBCol::PeerComponent;
@@ -11,3 +11,28 @@ fn borsh_wrapper_is_transparent() {
     let e = data::make_edge(&s1, &s2);
     assert_eq!(Borsh(e.clone()).try_to_vec().unwrap(), e.try_to_vec().unwrap());
 }
+
+#[test]
+fn peer_score_is_not_stored_in_peers_rows() {
+    let mut rng = make_rng(89028037453);
+    let now = time::Utc::from_unix_timestamp(1_600_000_000).unwrap();
+    let mut state = primitives::KnownPeerState::new(data::make_peer_info(&mut rng), now);
+    state.score = primitives::PeerScore {
+        latency: Some(time::Duration::milliseconds(120)),
+        useful_messages: 7,
+        misbehaviours: 1,
+    };
+    let mut store = Store::new(near_store::test_utils::create_test_store().into_inner());
+    let mut update = store.new_update();
+    update.set::<Peers>(&state.peer_info.id, &state);
+    update.set::<PeerScores>(&state.peer_info.id, &state.score);
+    store.commit(update).unwrap();
+
+    // Peers rows keep the format of the binaries which don't score peers.
+    let raw = store.0.get_raw_bytes(DBCol::Peers, &to_vec::<Borsh<PeerId>>(&state.peer_info.id));
+    assert_eq!(raw.unwrap().unwrap(), to_vec::<KnownPeerStateRepr>(&state));
+    let got = store.get::<Peers>(&state.peer_info.id).unwrap().unwrap();
+    assert_eq!((got.peer_info, got.last_seen), (state.peer_info.clone(), now));
+    assert_eq!(got.score, primitives::PeerScore::default());
+    assert_eq!(store.get::<PeerScores>(&state.peer_info.id).unwrap(), Some(state.score));
+}
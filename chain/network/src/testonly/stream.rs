@@ -7,6 +7,7 @@ use tokio::net;
 use tokio::sync::Mutex as AsyncMutex;
 
 use crate::network_protocol::{Encoding, PeerMessage};
+use near_primitives::version::PROTOCOL_VERSION;
 
 pub struct Stream {
     pub local_addr: std::net::SocketAddr,
@@ -69,10 +70,10 @@ impl Stream {
 
     pub async fn write(&self, msg: &PeerMessage) {
         if let Some(enc) = self.encoding() {
-            self.write_encoded(&msg.serialize(enc)).await;
+            self.write_encoded(&msg.serialize(enc, PROTOCOL_VERSION)).await;
         } else {
-            self.write_encoded(&msg.serialize(Encoding::Proto)).await;
-            self.write_encoded(&msg.serialize(Encoding::Borsh)).await;
+            self.write_encoded(&msg.serialize(Encoding::Proto, PROTOCOL_VERSION)).await;
+            self.write_encoded(&msg.serialize(Encoding::Borsh, PROTOCOL_VERSION)).await;
         }
     }
 
@@ -4,6 +4,7 @@ pub use crate::network_protocol::{
 };
 use crate::routing::routing_table_view::RoutingTableInfo;
 use futures::future::BoxFuture;
+use near_crypto::PublicKey;
 use near_network_primitives::time;
 use near_network_primitives::types::{
     AccountIdOrPeerTrackingShard, AccountOrPeerIdOrHash, KnownProducer, OutboundTcpConnect,
@@ -21,6 +22,7 @@ use near_primitives::syncing::{EpochSyncFinalizationResponse, EpochSyncResponse}
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::{AccountId, BlockReference, EpochId, ShardId};
 use near_primitives::views::{KnownProducerView, NetworkInfoView, PeerInfoView, QueryRequest};
+use std::collections::HashMap;
 use std::fmt::Debug;
 
 /// Peer stats query.
@@ -168,6 +170,9 @@ pub enum NetworkRequests {
     },
     /// Announce account
     AnnounceAccount(AnnounceAccount),
+    /// Validators of the current and the next epoch. Validators keep direct (TIER1) connections
+    /// with each other, to send consensus messages over them.
+    SetValidators(Vec<EpochValidators>),
 
     /// Request chunk parts and/or receipts
     PartialEncodedChunkRequest {
@@ -208,6 +213,13 @@ pub enum NetworkRequests {
     Challenge(Challenge),
 }
 
+/// Public keys of the validators of an epoch.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EpochValidators {
+    pub epoch_id: EpochId,
+    pub keys: HashMap<AccountId, PublicKey>,
+}
+
 /// Combines peer address info, chain and edge information.
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct FullPeerInfo {
@@ -336,6 +348,11 @@ pub enum NetworkClientResponses {
     ValidTx,
     /// Invalid transaction inserted into mempool as response to Transaction.
     InvalidTx(InvalidTxError),
+    /// Valid transaction not inserted into mempool, because the mempool is full.
+    TxPoolFull,
+    /// Valid transaction not inserted into mempool, because its signer has
+    /// too many transactions in the mempool already.
+    TooManyTxsFromSigner,
     /// The request is routed to other shards
     RequestRouted,
     /// The node being queried does not track the shard needed and therefore cannot provide userful
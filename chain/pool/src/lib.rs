@@ -1,44 +1,89 @@
-use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
-use crate::types::{PoolIterator, PoolKey, TransactionGroup};
+use crate::types::{InsertTransactionResult, PoolIterator, PoolKey, TransactionGroup};
 use borsh::BorshSerialize;
+use near_chain_configs::TransactionPoolConfig;
 use near_crypto::PublicKey;
 use near_primitives::epoch_manager::RngSeed;
 use near_primitives::hash::{hash, CryptoHash};
 use near_primitives::transaction::SignedTransaction;
 use near_primitives::types::AccountId;
-use std::ops::Bound;
 
 mod metrics;
 pub mod types;
 
+/// What the pool remembers about every transaction it holds, so that the
+/// limits can be updated when only the hash of a removed transaction is known.
+struct TransactionInfo {
+    signer_id: AccountId,
+    size: u64,
+    /// Number of the transaction in the order of insertion into the pool.
+    inserted: u64,
+}
+
+/// Position of a transaction group in the eviction and priority indices.
+#[derive(Clone, Copy)]
+struct GroupIndex {
+    len: usize,
+    /// Number of the last transaction inserted into the group.
+    last_inserted: u64,
+    /// Number of the oldest transaction in the group.
+    first_inserted: u64,
+}
+
 /// Transaction pool: keeps track of transactions that were not yet accepted into the block chain.
 pub struct TransactionPool {
     /// Transactions are grouped by a pair of (account ID, signer public key).
     /// NOTE: It's more efficient on average to keep transactions unsorted and with potentially
     /// conflicting nonce than to create a BTreeMap for every transaction.
     transactions: BTreeMap<PoolKey, Vec<SignedTransaction>>,
-    /// Set of all hashes to quickly check if the given transaction is in the pool.
-    unique_transactions: HashSet<CryptoHash>,
+    /// All transactions in the pool by hash, to quickly check if the given transaction is in the
+    /// pool.
+    unique_transactions: HashMap<CryptoHash, TransactionInfo>,
     /// A uniquely generated key seed to randomize PoolKey order.
     key_seed: RngSeed,
-    /// The key after which the pool iterator starts. Doesn't have to be present in the pool.
-    last_used_key: PoolKey,
+    /// Limits on the pool size.
+    config: TransactionPoolConfig,
+    /// Total size in bytes of the transactions in the pool.
+    total_size: u64,
+    /// Number of transactions in the pool for every signer account.
+    signer_counts: HashMap<AccountId, u64>,
+    /// Groups ordered by eviction priority: the number of transactions in the group, then the
+    /// number of the last transaction inserted into the group.  The last entry is evicted first.
+    eviction_index: BTreeSet<(usize, u64, PoolKey)>,
+    /// Groups ordered by priority: the number of the oldest transaction in the group.  The pool
+    /// iterator returns groups in this order, so that the transactions which have been waiting
+    /// the longest are included into chunks first.
+    priority_index: BTreeSet<(u64, PoolKey)>,
+    /// The entries of `eviction_index` and `priority_index` by group.  Contains exactly the groups
+    /// of `transactions`.
+    group_index: HashMap<PoolKey, GroupIndex>,
+    /// Number of transactions inserted so far, used to tell which group received a transaction
+    /// last.
+    inserted_count: u64,
 }
 
 impl TransactionPool {
-    pub fn new(key_seed: RngSeed) -> Self {
+    pub fn new(key_seed: RngSeed, config: TransactionPoolConfig) -> Self {
         Self {
             key_seed,
             transactions: BTreeMap::new(),
-            unique_transactions: HashSet::new(),
-            last_used_key: CryptoHash::default(),
+            unique_transactions: HashMap::new(),
+            config,
+            total_size: 0,
+            signer_counts: HashMap::new(),
+            eviction_index: BTreeSet::new(),
+            priority_index: BTreeSet::new(),
+            group_index: HashMap::new(),
+            inserted_count: 0,
         }
     }
 
     pub fn init_metrics() {
         // A `get()` call initializes a metric even if its value is zero.
         metrics::TRANSACTION_POOL_TOTAL.get();
+        metrics::TRANSACTION_POOL_SIZE.get();
+        metrics::TRANSACTION_POOL_EVICTED.get();
     }
 
     fn key(&self, account_id: &AccountId, public_key: &PublicKey) -> PoolKey {
@@ -49,22 +94,138 @@ impl TransactionPool {
     }
 
     /// Insert a signed transaction into the pool that passed validation.
-    pub fn insert_transaction(&mut self, signed_transaction: SignedTransaction) -> bool {
-        if !self.unique_transactions.insert(signed_transaction.get_hash()) {
+    ///
+    /// If the pool is full, transactions of the pool keys which have more transactions than the
+    /// key of the new transaction are evicted, starting from the largest group and the highest
+    /// nonce, i.e. the transaction which would be included into a chunk last.  This way a signer
+    /// flooding the pool only pushes out its own transactions.  Among the groups of the same size
+    /// the one which received a transaction last is evicted from first, so that the transactions
+    /// which have been waiting the longest keep their place.
+    pub fn insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let result = self.try_insert_transaction(signed_transaction);
+        if result != InsertTransactionResult::Success {
+            metrics::TRANSACTION_POOL_REJECTED.with_label_values(&[result.as_str()]).inc();
+        }
+        result
+    }
+
+    fn try_insert_transaction(
+        &mut self,
+        signed_transaction: SignedTransaction,
+    ) -> InsertTransactionResult {
+        let hash = signed_transaction.get_hash();
+        let size = signed_transaction.get_size();
+        if self.unique_transactions.contains_key(&hash) {
             // The hash of this transaction was already seen, skip it.
-            return false;
+            return InsertTransactionResult::Duplicate;
         }
-        metrics::TRANSACTION_POOL_TOTAL.inc();
 
         let signer_id = &signed_transaction.transaction.signer_id;
-        let signer_public_key = &signed_transaction.transaction.public_key;
-        self.transactions
-            .entry(self.key(signer_id, signer_public_key))
-            .or_insert_with(Vec::new)
-            .push(signed_transaction);
+        if let Some(limit) = self.config.per_signer_limit {
+            if self.signer_counts.get(signer_id).copied().unwrap_or(0) >= limit {
+                return InsertTransactionResult::TooManyFromSigner;
+            }
+        }
+
+        let key = self.key(signer_id, &signed_transaction.transaction.public_key);
+        if let Some(limit) = self.config.size_limit {
+            if size > limit {
+                return InsertTransactionResult::NoSpaceLeft;
+            }
+            while self.total_size + size > limit {
+                if !self.evict_transaction(&key) {
+                    return InsertTransactionResult::NoSpaceLeft;
+                }
+            }
+        }
+
+        self.inserted_count += 1;
+        let inserted = self.inserted_count;
+        *self.signer_counts.entry(signer_id.clone()).or_insert(0) += 1;
+        self.unique_transactions
+            .insert(hash, TransactionInfo { signer_id: signer_id.clone(), size, inserted });
+        self.total_size += size;
+        metrics::TRANSACTION_POOL_TOTAL.inc();
+        metrics::TRANSACTION_POOL_SIZE.add(size as i64);
+
+        self.unindex_group(&key);
+        self.transactions.entry(key).or_insert_with(Vec::new).push(signed_transaction);
+        self.index_group(key, inserted);
+        InsertTransactionResult::Success
+    }
+
+    /// Adds the group of the given key to the eviction and priority indices.
+    fn index_group(&mut self, key: PoolKey, last_inserted: u64) {
+        if let Some(group) = self.transactions.get(&key) {
+            let first_inserted = group
+                .iter()
+                .filter_map(|tx| self.unique_transactions.get(&tx.get_hash()))
+                .map(|info| info.inserted)
+                .min()
+                .unwrap_or(last_inserted);
+            let index = GroupIndex { len: group.len(), last_inserted, first_inserted };
+            self.eviction_index.insert((index.len, last_inserted, key));
+            self.priority_index.insert((first_inserted, key));
+            self.group_index.insert(key, index);
+        }
+    }
+
+    /// Removes the group of the given key from the eviction and priority indices, before the group
+    /// is changed.  Returns the number of the last transaction inserted into the group.
+    fn unindex_group(&mut self, key: &PoolKey) -> Option<u64> {
+        let index = self.group_index.remove(key)?;
+        self.eviction_index.remove(&(index.len, index.last_inserted, *key));
+        self.priority_index.remove(&(index.first_inserted, *key));
+        Some(index.last_inserted)
+    }
+
+    /// Evicts the transaction with the highest nonce from the largest group, unless the group of
+    /// `new_key` (including the transaction being inserted) would be at least as large.
+    /// Returns false if nothing was evicted.
+    fn evict_transaction(&mut self, new_key: &PoolKey) -> bool {
+        let new_group_len = self.transactions.get(new_key).map_or(0, Vec::len) + 1;
+        let victim_key = match self.eviction_index.iter().rev().find(|(_, _, key)| key != new_key) {
+            Some((len, _, key)) if *len > new_group_len => *key,
+            _ => return false,
+        };
+
+        let last_inserted = self.unindex_group(&victim_key).expect("indexed");
+        let group = self.transactions.get_mut(&victim_key).expect("just found");
+        let (index, _) = group
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, tx)| tx.transaction.nonce)
+            .expect("groups in the pool are not empty");
+        let tx = group.swap_remove(index);
+        if group.is_empty() {
+            self.transactions.remove(&victim_key);
+        } else {
+            self.index_group(victim_key, last_inserted);
+        }
+        self.remove_unique_transaction(&tx.get_hash());
+        metrics::TRANSACTION_POOL_EVICTED.inc();
         true
     }
 
+    /// Forgets the transaction with the given hash, updating the limits and metrics.
+    /// The transaction itself must be already removed from its group.
+    fn remove_unique_transaction(&mut self, hash: &CryptoHash) {
+        if let Some(info) = self.unique_transactions.remove(hash) {
+            self.total_size -= info.size;
+            if let Some(count) = self.signer_counts.get_mut(&info.signer_id) {
+                *count -= 1;
+                if *count == 0 {
+                    self.signer_counts.remove(&info.signer_id);
+                }
+            }
+            metrics::TRANSACTION_POOL_TOTAL.dec();
+            metrics::TRANSACTION_POOL_SIZE.sub(info.size as i64);
+        }
+    }
+
     /// Returns a pool iterator wrapper that implements an iterator like trait to iterate over
     /// transaction groups in the proper order defined by the protocol.
     /// When the iterator is dropped, all remaining groups are inserted back into the pool.
@@ -77,7 +238,7 @@ impl TransactionPool {
     pub fn remove_transactions(&mut self, transactions: &[SignedTransaction]) {
         let mut grouped_transactions = HashMap::new();
         for tx in transactions {
-            if self.unique_transactions.contains(&tx.get_hash()) {
+            if self.unique_transactions.contains_key(&tx.get_hash()) {
                 let signer_id = &tx.transaction.signer_id;
                 let signer_public_key = &tx.transaction.public_key;
                 grouped_transactions
@@ -88,17 +249,18 @@ impl TransactionPool {
         }
         for (key, hashes) in grouped_transactions {
             let mut remove_entry = false;
+            let last_inserted = self.unindex_group(&key);
             if let Some(v) = self.transactions.get_mut(&key) {
                 v.retain(|tx| !hashes.contains(&tx.get_hash()));
                 remove_entry = v.is_empty();
             }
             if remove_entry {
                 self.transactions.remove(&key);
+            } else if let Some(last_inserted) = last_inserted {
+                self.index_group(key, last_inserted);
             }
             for hash in &hashes {
-                if self.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.remove_unique_transaction(hash);
             }
         }
     }
@@ -113,6 +275,11 @@ impl TransactionPool {
     pub fn len(&self) -> usize {
         self.unique_transactions.len()
     }
+
+    /// Total size in bytes of the transactions in the pool.
+    pub fn transaction_size(&self) -> u64 {
+        self.total_size
+    }
 }
 
 /// PoolIterator is a structure to pull transactions from the pool.
@@ -135,7 +302,8 @@ impl<'a> PoolIteratorWrapper<'a> {
 /// The iterator works with the following algorithm:
 /// On next(), the iterator tries to get a transaction group from the pool, sorts transactions in
 /// it, and add it to the back of the sorted groups queue.
-/// Remembers the last used key, so it can continue from the next key.
+/// Groups are taken from the pool in the order of priority, starting from the group whose oldest
+/// transaction has been in the pool the longest.
 ///
 /// If the pool is empty, the iterator gets the group from the front of the sorted groups queue.
 ///
@@ -151,37 +319,23 @@ impl<'a> PoolIteratorWrapper<'a> {
 /// And all non-empty group from the sorted groups queue are inserted back into the pool.
 impl<'a> PoolIterator for PoolIteratorWrapper<'a> {
     fn next(&mut self) -> Option<&mut TransactionGroup> {
-        if !self.pool.transactions.is_empty() {
-            let key = *self
-                .pool
-                .transactions
-                .range((Bound::Excluded(self.pool.last_used_key), Bound::Unbounded))
-                .next()
-                .map(|(k, _v)| k)
-                .unwrap_or_else(|| {
-                    self.pool
-                        .transactions
-                        .keys()
-                        .next()
-                        .expect("we've just checked that the map is not empty")
-                });
-            self.pool.last_used_key = key;
+        if let Some(&(_, key)) = self.pool.priority_index.iter().next() {
             let mut transactions =
                 self.pool.transactions.remove(&key).expect("just checked existence");
+            let last_inserted = self.pool.unindex_group(&key).expect("indexed");
             transactions.sort_by_key(|st| std::cmp::Reverse(st.transaction.nonce));
             self.sorted_groups.push_back(TransactionGroup {
                 key,
                 transactions,
                 removed_transaction_hashes: vec![],
+                last_inserted,
             });
             Some(self.sorted_groups.back_mut().expect("just pushed"))
         } else {
             while let Some(sorted_group) = self.sorted_groups.pop_front() {
                 if sorted_group.transactions.is_empty() {
                     for hash in sorted_group.removed_transaction_hashes {
-                        if self.pool.unique_transactions.remove(&hash) {
-                            metrics::TRANSACTION_POOL_TOTAL.dec();
-                        }
+                        self.pool.remove_unique_transaction(&hash);
                     }
                 } else {
                     self.sorted_groups.push_back(sorted_group);
@@ -200,12 +354,11 @@ impl<'a> Drop for PoolIteratorWrapper<'a> {
     fn drop(&mut self) {
         for group in self.sorted_groups.drain(..) {
             for hash in group.removed_transaction_hashes {
-                if self.pool.unique_transactions.remove(&hash) {
-                    metrics::TRANSACTION_POOL_TOTAL.dec();
-                }
+                self.pool.remove_unique_transaction(&hash);
             }
             if !group.transactions.is_empty() {
                 self.pool.transactions.insert(group.key, group.transactions);
+                self.pool.index_group(group.key, group.last_inserted);
             }
         }
     }
@@ -253,7 +406,7 @@ mod tests {
         mut transactions: Vec<SignedTransaction>,
         expected_weight: u32,
     ) -> (Vec<u64>, TransactionPool) {
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions {
@@ -364,7 +517,7 @@ mod tests {
             })
             .collect::<Vec<_>>();
 
-        let mut pool = TransactionPool::new(TEST_SEED);
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
         let mut rng = thread_rng();
         transactions.shuffle(&mut rng);
         for tx in transactions.clone() {
@@ -428,9 +581,10 @@ mod tests {
         assert_eq!(txs.len(), 10);
     }
 
-    /// Test pool iterator remembers the last key.
+    /// Test pool iterator returns the transactions left in the pool before the ones inserted
+    /// after them.
     #[test]
-    fn test_pool_iterator_remembers_the_last_key() {
+    fn test_pool_iterator_returns_remaining_transactions_first() {
         let transactions = (1..=10)
             .map(|i| {
                 let signer_id = AccountId::try_from(format!("user_{}", i)).unwrap();
@@ -465,4 +619,151 @@ mod tests {
         new_nonces.sort();
         assert_ne!(nonces, new_nonces);
     }
+
+    /// Test pool iterator returns the groups whose transactions have waited the longest first,
+    /// even after newer transactions are added to them.
+    #[test]
+    fn test_pool_iterator_priority() {
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
+        for i in 1..=5 {
+            let signer_id = format!("user_{}", i);
+            insert_all(&mut pool, generate_transactions(&signer_id, &signer_id, i, i));
+        }
+        insert_all(&mut pool, generate_transactions("user_5", "user_5", 6, 6));
+        insert_all(&mut pool, generate_transactions("user_1", "user_1", 7, 7));
+
+        let mut signers = vec![];
+        let mut pool_iter = pool.pool_iterator();
+        while let Some(iter) = pool_iter.next() {
+            if let Some(tx) = iter.next() {
+                signers.push(tx.transaction.signer_id.to_string());
+            }
+        }
+        assert_eq!(
+            signers,
+            vec!["user_1", "user_2", "user_3", "user_4", "user_5", "user_1", "user_5"]
+        );
+    }
+
+    fn insert_all(
+        pool: &mut TransactionPool,
+        transactions: Vec<SignedTransaction>,
+    ) -> Vec<InsertTransactionResult> {
+        transactions.into_iter().map(|tx| pool.insert_transaction(tx)).collect()
+    }
+
+    #[test]
+    fn test_insert_duplicate() {
+        let transactions = generate_transactions("alice.near", "alice.near", 1, 2);
+        let mut pool = TransactionPool::new(TEST_SEED, TransactionPoolConfig::default());
+        assert_eq!(
+            insert_all(&mut pool, transactions.clone()),
+            vec![InsertTransactionResult::Success; 2]
+        );
+        assert_eq!(
+            insert_all(&mut pool, transactions.clone()),
+            vec![InsertTransactionResult::Duplicate; 2]
+        );
+        assert_eq!(pool.len(), 2);
+        let size: u64 = transactions.iter().map(|tx| tx.get_size()).sum();
+        assert_eq!(pool.transaction_size(), size);
+    }
+
+    /// Signer limit applies to all access keys of an account and is freed up once the
+    /// transactions leave the pool.
+    #[test]
+    fn test_per_signer_limit() {
+        let config = TransactionPoolConfig { size_limit: None, per_signer_limit: Some(3) };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        let alice_transactions = generate_transactions("alice.near", "alice.near", 1, 2);
+        let results = insert_all(&mut pool, alice_transactions.clone());
+        assert_eq!(results, vec![InsertTransactionResult::Success; 2]);
+        let results = insert_all(&mut pool, generate_transactions("alice.near", "bob.near", 1, 2));
+        assert_eq!(
+            results,
+            vec![InsertTransactionResult::Success, InsertTransactionResult::TooManyFromSigner]
+        );
+        let results = insert_all(&mut pool, generate_transactions("bob.near", "bob.near", 1, 2));
+        assert_eq!(results, vec![InsertTransactionResult::Success; 2]);
+
+        pool.remove_transactions(&alice_transactions);
+        let results = insert_all(&mut pool, generate_transactions("alice.near", "bob.near", 2, 3));
+        assert_eq!(results, vec![InsertTransactionResult::Success; 2]);
+        assert_eq!(pool.len(), 5);
+    }
+
+    /// When the pool is full, transactions with the highest nonces of the largest group are
+    /// evicted, but only if that group is larger than the group of the new transaction.
+    #[test]
+    fn test_size_limit_evicts_largest_group() {
+        let alice_transactions = generate_transactions("alice.near", "alice.near", 1, 10);
+        let tx_size = alice_transactions[0].get_size();
+        let config =
+            TransactionPoolConfig { size_limit: Some(10 * tx_size), per_signer_limit: None };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        let results = insert_all(&mut pool, alice_transactions);
+        assert_eq!(results, vec![InsertTransactionResult::Success; 10]);
+        assert_eq!(pool.transaction_size(), 10 * tx_size);
+
+        // Alice can't push out her own transactions.
+        let results =
+            insert_all(&mut pool, generate_transactions("alice.near", "alice.near", 11, 11));
+        assert_eq!(results, vec![InsertTransactionResult::NoSpaceLeft]);
+
+        // Bob's transactions replace Alice's ones until both groups are of the same size.
+        let bob_transactions = generate_transactions("bob.near", "bob.near", 1, 6);
+        let bob_tx_size = bob_transactions[0].get_size();
+        let results = insert_all(&mut pool, bob_transactions);
+        let mut expected = vec![InsertTransactionResult::Success; 5];
+        expected.push(InsertTransactionResult::NoSpaceLeft);
+        assert_eq!(results, expected);
+        assert_eq!(pool.len(), 10);
+        assert_eq!(pool.transaction_size(), 5 * tx_size + 5 * bob_tx_size);
+
+        let mut nonces: Vec<_> = prepare_transactions(&mut pool, 10)
+            .iter()
+            .filter(|tx| tx.transaction.signer_id.as_ref() == "alice.near")
+            .map(|tx| tx.transaction.nonce)
+            .collect();
+        nonces.sort();
+        assert_eq!(nonces, (1..=5).collect::<Vec<u64>>());
+        assert_eq!(pool.len(), 0);
+        assert_eq!(pool.transaction_size(), 0);
+    }
+
+    /// Among the groups of the same size, the one which received a transaction last is evicted
+    /// from first.
+    #[test]
+    fn test_size_limit_evicts_newest_group() {
+        let alice_transactions = generate_transactions("alice.near", "alice.near", 1, 3);
+        let bobby_transactions = generate_transactions("bobby.near", "bobby.near", 1, 3);
+        let tx_size = alice_transactions[0].get_size();
+        assert_eq!(bobby_transactions[0].get_size(), tx_size);
+        let config =
+            TransactionPoolConfig { size_limit: Some(6 * tx_size), per_signer_limit: None };
+        let mut pool = TransactionPool::new(TEST_SEED, config);
+        // Both groups end up with 3 transactions, Bobby's one received a transaction last.
+        let a = &alice_transactions;
+        let b = &bobby_transactions;
+        let transactions = vec![&a[0], &a[1], &b[0], &b[1], &a[2], &b[2]];
+        let transactions = transactions.into_iter().cloned().collect();
+        let results = insert_all(&mut pool, transactions);
+        assert_eq!(results, vec![InsertTransactionResult::Success; 6]);
+
+        let results =
+            insert_all(&mut pool, generate_transactions("carol.near", "carol.near", 1, 1));
+        assert_eq!(results, vec![InsertTransactionResult::Success]);
+        let mut remaining: Vec<_> = prepare_transactions(&mut pool, 10)
+            .iter()
+            .map(|tx| (tx.transaction.signer_id.to_string(), tx.transaction.nonce))
+            .collect();
+        remaining.sort();
+        let expected: Vec<_> =
+            [("alice.near", 1), ("alice.near", 2), ("alice.near", 3), ("bobby.near", 1)]
+                .iter()
+                .chain(&[("bobby.near", 2), ("carol.near", 1)])
+                .map(|(signer, nonce)| (signer.to_string(), *nonce))
+                .collect();
+        assert_eq!(remaining, expected);
+    }
 }
@@ -3,7 +3,8 @@ use near_primitives::transaction::SignedTransaction;
 
 /// Trait acts like an iterator. It iterates over transactions groups by returning mutable
 /// references to them. Each transaction group implements a draining iterator to pull transactions.
-/// The order of the transaction groups is round robin scheduling.
+/// The transaction groups are returned in the order of priority: the group whose oldest transaction
+/// has been in the pool the longest comes first, then the groups are returned round robin.
 /// When this iterator is dropped the remaining transactions are returned back to the pool.
 pub trait PoolIterator {
     fn next(&mut self) -> Option<&mut TransactionGroup>;
@@ -21,6 +22,8 @@ pub struct TransactionGroup {
     pub(crate) transactions: Vec<SignedTransaction>,
     /// Hashes of the transactions that were pulled from the group using `.next()`.
     pub(crate) removed_transaction_hashes: Vec<CryptoHash>,
+    /// Number of the last transaction inserted into the group, for the eviction order.
+    pub(crate) last_inserted: u64,
 }
 
 impl TransactionGroup {
@@ -35,3 +38,30 @@ impl TransactionGroup {
         }
     }
 }
+
+/// Outcome of inserting a transaction into the pool.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InsertTransactionResult {
+    /// Transaction was inserted into the pool.
+    Success,
+    /// Transaction with the same hash is already in the pool.
+    Duplicate,
+    /// Signer of the transaction already has the maximum allowed number of
+    /// transactions in the pool.
+    TooManyFromSigner,
+    /// Pool is full and there are no transactions which could be evicted to
+    /// make room for this one.
+    NoSpaceLeft,
+}
+
+impl InsertTransactionResult {
+    /// Name of the outcome, used as a metric label.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InsertTransactionResult::Success => "success",
+            InsertTransactionResult::Duplicate => "duplicate",
+            InsertTransactionResult::TooManyFromSigner => "too_many_from_signer",
+            InsertTransactionResult::NoSpaceLeft => "no_space_left",
+        }
+    }
+}
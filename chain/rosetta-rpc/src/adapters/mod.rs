@@ -267,6 +267,11 @@ impl From<NearActions> for Vec<crate::models::Operation> {
                     );
                 }
 
+                near_primitives::transaction::Action::Delegate(_) => {
+                    // Delegated actions are executed in a receipt of their own,
+                    // so they aren't operations of this transaction.
+                }
+
                 near_primitives::transaction::Action::AddKey(action) => {
                     let initiate_add_key_operation_id =
                         crate::models::OperationIdentifier::new(&operations);
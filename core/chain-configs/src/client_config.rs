@@ -1,6 +1,7 @@
 //! Chain Client Configuration
 use std::cmp::max;
 use std::cmp::min;
+use std::path::PathBuf;
 use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
@@ -52,6 +53,34 @@ impl Default for GCConfig {
     }
 }
 
+/// Default upper bound of the total size of transactions in a shard's pool.
+pub const DEFAULT_TRANSACTION_POOL_SIZE_LIMIT: u64 = 100_000_000;
+
+fn default_transaction_pool_size_limit() -> Option<u64> {
+    Some(DEFAULT_TRANSACTION_POOL_SIZE_LIMIT)
+}
+
+/// Configuration of the transaction pools (one per tracked shard).
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct TransactionPoolConfig {
+    /// Upper bound of the total size in bytes of the transactions kept in the
+    /// pool of a single shard.  When the pool is full, transactions of the
+    /// signers with the most pending transactions are evicted to make room for
+    /// new ones.  None is no limit.
+    #[serde(default = "default_transaction_pool_size_limit")]
+    pub size_limit: Option<u64>,
+    /// Maximum number of transactions of a single signer account kept in the
+    /// pool of a single shard.  None is no limit.
+    #[serde(default)]
+    pub per_signer_limit: Option<u64>,
+}
+
+impl Default for TransactionPoolConfig {
+    fn default() -> Self {
+        Self { size_limit: default_transaction_pool_size_limit(), per_signer_limit: None }
+    }
+}
+
 fn default_gc_blocks_limit() -> NumBlocks {
     GCConfig::default().gc_blocks_limit
 }
@@ -106,6 +135,15 @@ pub struct ClientConfig {
     pub header_sync_expected_height_per_second: u64,
     /// How long to wait for a response during state sync
     pub state_sync_timeout: Duration,
+    /// Directory to dump state headers and parts of the tracked shards into at
+    /// the start of every epoch.  None disables dumping.
+    pub state_sync_dump_dir: Option<PathBuf>,
+    /// Directory with dumped state headers and parts to read from during state
+    /// sync before requesting them from the peers.
+    pub state_sync_external_dir: Option<PathBuf>,
+    /// Directory to create database checkpoints in when one is requested
+    /// through the debug RPC.  None disables such checkpoints.
+    pub checkpoint_dir: Option<PathBuf>,
     /// Minimum number of peers to start syncing.
     pub min_num_peers: usize,
     /// Period between logging summary information.
@@ -156,6 +194,8 @@ pub struct ClientConfig {
     pub max_gas_burnt_view: Option<Gas>,
     /// Re-export storage layer statistics as prometheus metrics.
     pub enable_statistics_export: bool,
+    /// Limits of the transaction pools.
+    pub transaction_pool: TransactionPoolConfig,
 }
 
 impl ClientConfig {
@@ -187,6 +227,9 @@ impl ClientConfig {
             header_sync_progress_timeout: Duration::from_secs(2),
             header_sync_stall_ban_timeout: Duration::from_secs(30),
             state_sync_timeout: Duration::from_secs(TEST_STATE_SYNC_TIMEOUT),
+            state_sync_dump_dir: None,
+            state_sync_external_dir: None,
+            checkpoint_dir: None,
             header_sync_expected_height_per_second: 1,
             min_num_peers: 1,
             log_summary_period: Duration::from_secs(10),
@@ -215,6 +258,7 @@ impl ClientConfig {
             trie_viewer_state_size_limit: None,
             max_gas_burnt_view: None,
             enable_statistics_export: true,
+            transaction_pool: TransactionPoolConfig::default(),
         }
     }
 }
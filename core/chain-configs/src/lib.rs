@@ -3,8 +3,8 @@ mod genesis_config;
 pub mod genesis_validate;
 
 pub use client_config::{
-    ClientConfig, GCConfig, LogSummaryStyle, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
-    MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
+    ClientConfig, GCConfig, LogSummaryStyle, TransactionPoolConfig, DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
+    DEFAULT_TRANSACTION_POOL_SIZE_LIMIT, MIN_GC_NUM_EPOCHS_TO_KEEP, TEST_STATE_SYNC_TIMEOUT,
 };
 pub use genesis_config::{
     get_initial_supply, Genesis, GenesisConfig, GenesisRecords, GenesisValidationMode,
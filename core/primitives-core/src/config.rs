@@ -312,6 +312,11 @@ pub struct ExtCostsConfig {
     /// Cost of calling ecrecover
     pub ecrecover_base: Gas,
 
+    /// Cost of calling ed25519_verify
+    pub ed25519_verify_base: Gas,
+    /// Cost per message byte of ed25519_verify
+    pub ed25519_verify_byte: Gas,
+
     /// Cost for calling logging.
     pub log_base: Gas,
     /// Cost for logging per byte
@@ -455,6 +460,8 @@ impl ExtCostsConfig {
             // Cost per byte is 3542227. There are 64 bytes in a block.
             ripemd160_block: SAFETY_MULTIPLIER * 226702528,
             ecrecover_base: SAFETY_MULTIPLIER * 1121789875000,
+            ed25519_verify_base: SAFETY_MULTIPLIER * 70000000000,
+            ed25519_verify_byte: SAFETY_MULTIPLIER * 3000000,
             log_base: SAFETY_MULTIPLIER * 1181104350,
             log_byte: SAFETY_MULTIPLIER * 4399597,
             storage_write_base: SAFETY_MULTIPLIER * 21398912000,
@@ -521,6 +528,8 @@ impl ExtCostsConfig {
             ripemd160_base: 0,
             ripemd160_block: 0,
             ecrecover_base: 0,
+            ed25519_verify_base: 0,
+            ed25519_verify_byte: 0,
             log_base: 0,
             log_byte: 0,
             storage_write_base: 0,
@@ -590,6 +599,8 @@ pub enum ExtCosts {
     ripemd160_base,
     ripemd160_block,
     ecrecover_base,
+    ed25519_verify_base,
+    ed25519_verify_byte,
     log_base,
     log_byte,
     storage_write_base,
@@ -671,6 +682,8 @@ impl ExtCosts {
             ripemd160_base => config.ripemd160_base,
             ripemd160_block => config.ripemd160_block,
             ecrecover_base => config.ecrecover_base,
+            ed25519_verify_base => config.ed25519_verify_base,
+            ed25519_verify_byte => config.ed25519_verify_byte,
             log_base => config.log_base,
             log_byte => config.log_byte,
             storage_write_base => config.storage_write_base,
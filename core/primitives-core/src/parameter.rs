@@ -79,6 +79,9 @@ pub enum Parameter {
     ActionDeleteKeySendSir,
     ActionDeleteKeySendNotSir,
     ActionDeleteKeyExecution,
+    ActionDelegateSendSir,
+    ActionDelegateSendNotSir,
+    ActionDelegateExecution,
 
     // Smart contract dynamic gas costs
     WasmRegularOpCost,
@@ -108,6 +111,8 @@ pub enum Parameter {
     WasmRipemd160Base,
     WasmRipemd160Block,
     WasmEcrecoverBase,
+    WasmEd25519VerifyBase,
+    WasmEd25519VerifyByte,
     WasmLogBase,
     WasmLogByte,
     WasmStorageWriteBase,
@@ -203,6 +208,7 @@ pub enum FeeParameter {
     ActionAddFunctionCallKey,
     ActionAddFunctionCallKeyPerByte,
     ActionDeleteKey,
+    ActionDelegate,
 }
 
 impl Parameter {
@@ -236,6 +242,8 @@ impl Parameter {
             Parameter::WasmRipemd160Base,
             Parameter::WasmRipemd160Block,
             Parameter::WasmEcrecoverBase,
+            Parameter::WasmEd25519VerifyBase,
+            Parameter::WasmEd25519VerifyByte,
             Parameter::WasmLogBase,
             Parameter::WasmLogByte,
             Parameter::WasmStorageWriteBase,
@@ -113,6 +113,16 @@ pub struct ActionCreationConfig {
 
     /// Base cost of deleting an account.
     pub delete_account_cost: Fee,
+
+    /// Base cost of a delegate action, excluding the cost of the delegated
+    /// actions.
+    // Genesis files written before the action was introduced don't have it.
+    #[serde(default = "default_delegate_cost")]
+    pub delegate_cost: Fee,
+}
+
+fn default_delegate_cost() -> Fee {
+    Fee { send_sir: 200_000_000_000, send_not_sir: 200_000_000_000, execution: 200_000_000_000 }
 }
 
 /// Describes the cost of creating an access key.
@@ -219,6 +229,11 @@ impl RuntimeFeesConfig {
                     send_not_sir: 147489000000,
                     execution: 147489000000,
                 },
+                delegate_cost: Fee {
+                    send_sir: 200000000000,
+                    send_not_sir: 200000000000,
+                    execution: 200000000000,
+                },
             },
             storage_usage_config: StorageUsageConfig {
                 // See Account in core/primitives/src/account.rs for the data structure.
@@ -253,7 +268,8 @@ impl RuntimeFeesConfig {
                     function_call_cost_per_byte: free.clone(),
                 },
                 delete_key_cost: free.clone(),
-                delete_account_cost: free,
+                delete_account_cost: free.clone(),
+                delegate_cost: free,
             },
             storage_usage_config: StorageUsageConfig {
                 num_bytes_account: 0,
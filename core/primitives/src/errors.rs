@@ -1,5 +1,5 @@
 use crate::serialize::u128_dec_format;
-use crate::types::{AccountId, Balance, EpochId, Gas, Nonce};
+use crate::types::{AccountId, Balance, EpochId, Gas, Nonce, ProtocolVersion};
 use borsh::{BorshDeserialize, BorshSerialize};
 use near_crypto::PublicKey;
 use serde::{Deserialize, Serialize};
@@ -200,6 +200,10 @@ pub enum ActionsValidationError {
     UnsuitableStakingKey { public_key: PublicKey },
     /// The attached amount of gas in a FunctionCall action has to be a positive number.
     FunctionCallZeroAttachedGas,
+    /// The action isn't supported by the current protocol version.
+    UnsupportedProtocolFeature { protocol_feature: String, version: ProtocolVersion },
+    /// The signature of a Delegate action doesn't match its public key.
+    InvalidDelegateActionSignature,
 }
 
 /// Describes the error for validating a receipt.
@@ -317,6 +321,14 @@ impl Display for ActionsValidationError {
                 f,
                 "The attached amount of gas in a FunctionCall action has to be a positive number",
             ),
+            ActionsValidationError::UnsupportedProtocolFeature { protocol_feature, version } => write!(
+                f,
+                "Protocol feature {} is not supported in protocol version {}",
+                protocol_feature, version
+            ),
+            ActionsValidationError::InvalidDelegateActionSignature => {
+                write!(f, "The signature of the Delegate action is invalid")
+            }
         }
     }
 }
@@ -441,6 +453,18 @@ pub enum ActionErrorKind {
     OnlyImplicitAccountCreationAllowed { account_id: AccountId },
     /// Delete account whose state is large is temporarily banned.
     DeleteAccountWithLargeState { account_id: AccountId },
+    /// Signature of the delegate action doesn't match its public key.
+    DelegateActionInvalidSignature,
+    /// Sender of the delegate action isn't the receiver of the transaction.
+    DelegateActionSenderDoesNotMatchTxReceiver { sender_id: AccountId, receiver_id: AccountId },
+    /// The block height is past the `max_block_height` of the delegate action.
+    DelegateActionExpired,
+    /// The access key which signed the delegate action can't be used for it.
+    DelegateActionAccessKeyError(InvalidAccessKeyError),
+    /// Nonce of the delegate action must be larger than the nonce of the access key.
+    DelegateActionInvalidNonce { delegate_nonce: Nonce, ak_nonce: Nonce },
+    /// Nonce of the delegate action is too large, see `AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER`.
+    DelegateActionNonceTooLarge { delegate_nonce: Nonce, upper_bound: Nonce },
 }
 
 impl From<ActionErrorKind> for ActionError {
@@ -751,6 +775,12 @@ impl Display for ActionErrorKind {
             ActionErrorKind::InsufficientStake { account_id, stake, minimum_stake } => write!(f, "Account {} tries to stake {} but minimum required stake is {}", account_id, stake, minimum_stake),
             ActionErrorKind::OnlyImplicitAccountCreationAllowed { account_id } => write!(f, "CreateAccount action is called on hex-characters account of length 64 {}", account_id),
             ActionErrorKind::DeleteAccountWithLargeState { account_id } => write!(f, "The state of account {} is too large and therefore cannot be deleted", account_id),
+            ActionErrorKind::DelegateActionInvalidSignature => write!(f, "Invalid signature of the delegate action"),
+            ActionErrorKind::DelegateActionSenderDoesNotMatchTxReceiver { sender_id, receiver_id } => write!(f, "Delegate action sender {} doesn't match the transaction receiver {}", sender_id, receiver_id),
+            ActionErrorKind::DelegateActionExpired => write!(f, "Delegate action has expired"),
+            ActionErrorKind::DelegateActionAccessKeyError(access_key_error) => Display::fmt(&access_key_error, f),
+            ActionErrorKind::DelegateActionInvalidNonce { delegate_nonce, ak_nonce } => write!(f, "Delegate action nonce {} must be larger than nonce of the used access key {}", delegate_nonce, ak_nonce),
+            ActionErrorKind::DelegateActionNonceTooLarge { delegate_nonce, upper_bound } => write!(f, "Delegate action nonce {} must be smaller than the access key nonce upper bound {}", delegate_nonce, upper_bound),
         }
     }
 }
@@ -37,6 +37,9 @@ pub struct ApplyState {
     pub cache: Option<Arc<dyn CompiledContractCache>>,
     /// Whether the chunk being applied is new.
     pub is_new_chunk: bool,
+    /// Whether signatures of the transactions are verified.  Only disabled when simulating
+    /// transactions.
+    pub verify_signatures: bool,
     /// Data for migrations that may need to be applied at the start of an epoch when protocol
     /// version changes
     pub migration_data: Arc<MigrationData>,
@@ -5,7 +5,7 @@ use std::hash::{Hash, Hasher};
 use borsh::{BorshDeserialize, BorshSerialize};
 use serde::{Deserialize, Serialize};
 
-use near_crypto::{PublicKey, Signature};
+use near_crypto::{PublicKey, Signature, Signer};
 
 use crate::account::AccessKey;
 use crate::errors::TxExecutionError;
@@ -13,7 +13,7 @@ use crate::hash::{hash, CryptoHash};
 use crate::logging;
 use crate::merkle::MerklePath;
 use crate::serialize::{base64_format, u128_dec_format_compatible};
-use crate::types::{AccountId, Balance, Gas, Nonce};
+use crate::types::{AccountId, Balance, BlockHeight, Gas, Nonce};
 use near_primitives_core::profile::ProfileData;
 
 pub type LogEntry = String;
@@ -70,6 +70,9 @@ pub enum Action {
     AddKey(AddKeyAction),
     DeleteKey(DeleteKeyAction),
     DeleteAccount(DeleteAccountAction),
+    /// Actions signed by another account and relayed by the signer of the
+    /// transaction, who pays for them.
+    Delegate(SignedDelegateAction),
 }
 
 impl Action {
@@ -220,6 +223,120 @@ impl From<DeleteAccountAction> for Action {
     }
 }
 
+/// Actions which `sender_id` wants executed on `receiver_id` without paying
+/// for them.  The account relaying the action pays for the gas and the
+/// attached deposits.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct DelegateAction {
+    /// Account which signed the action and on whose behalf the actions are
+    /// executed.
+    pub sender_id: AccountId,
+    /// Receiver of the delegated actions.
+    pub receiver_id: AccountId,
+    /// Delegated actions.
+    pub actions: Vec<NonDelegateAction>,
+    /// Nonce of the `public_key` access key, which must be larger than the
+    /// current one, the same way as for transactions.
+    pub nonce: Nonce,
+    /// The action can't be executed after this block height.
+    pub max_block_height: BlockHeight,
+    /// Access key of `sender_id` which signed the action.
+    pub public_key: PublicKey,
+}
+
+/// Prefix of the signed delegate action data.  It's larger than the maximum
+/// length of an account id, so the signed bytes can never be parsed as a
+/// transaction, which starts with the length of the signer id.
+const DELEGATE_ACTION_SIGNATURE_PREFIX: u32 = (1 << 30) + 366;
+
+impl DelegateAction {
+    pub fn get_actions(&self) -> Vec<Action> {
+        self.actions.iter().map(|action| action.0.clone()).collect()
+    }
+
+    /// Computes the hash signed by the sender.
+    pub fn get_hash(&self) -> CryptoHash {
+        let mut bytes = DELEGATE_ACTION_SIGNATURE_PREFIX.to_le_bytes().to_vec();
+        BorshSerialize::serialize(self, &mut bytes).expect("Failed to serialize");
+        hash(&bytes)
+    }
+}
+
+/// An action which isn't a delegate action, as delegate actions can't be
+/// nested.  The invariant is checked when the action is deserialized.
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+#[serde(into = "Action", try_from = "Action")]
+pub struct NonDelegateAction(Action);
+
+impl std::ops::Deref for NonDelegateAction {
+    type Target = Action;
+
+    fn deref(&self) -> &Action {
+        &self.0
+    }
+}
+
+impl From<NonDelegateAction> for Action {
+    fn from(action: NonDelegateAction) -> Self {
+        action.0
+    }
+}
+
+impl TryFrom<Action> for NonDelegateAction {
+    type Error = &'static str;
+
+    fn try_from(action: Action) -> Result<Self, Self::Error> {
+        match action {
+            Action::Delegate(_) => Err("Delegate action can't contain other delegate actions"),
+            action => Ok(Self(action)),
+        }
+    }
+}
+
+// Written by hand, because deriving them for the recursive type overflows
+// the trait resolution.
+impl BorshSerialize for NonDelegateAction {
+    fn serialize<W: std::io::Write>(&self, writer: &mut W) -> std::io::Result<()> {
+        BorshSerialize::serialize(&self.0, writer)
+    }
+}
+
+impl BorshDeserialize for NonDelegateAction {
+    fn deserialize(buf: &mut &[u8]) -> std::io::Result<Self> {
+        <Action as BorshDeserialize>::deserialize(buf)?
+            .try_into()
+            .map_err(|err: &str| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+    }
+}
+
+#[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, PartialEq, Eq, Clone, Debug)]
+pub struct SignedDelegateAction {
+    pub delegate_action: DelegateAction,
+    pub signature: Signature,
+}
+
+impl SignedDelegateAction {
+    pub fn new(delegate_action: DelegateAction, signer: &dyn Signer) -> Self {
+        let signature = signer.sign(delegate_action.get_hash().as_ref());
+        Self { delegate_action, signature }
+    }
+
+    /// Checks that the action was signed by `delegate_action.public_key`.
+    pub fn verify(&self) -> bool {
+        let hash = self.delegate_action.get_hash();
+        self.signature.verify(hash.as_ref(), &self.delegate_action.public_key)
+    }
+}
+
+impl From<SignedDelegateAction> for Action {
+    fn from(signed_delegate_action: SignedDelegateAction) -> Self {
+        Self::Delegate(signed_delegate_action)
+    }
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Eq, Debug, Clone)]
 #[borsh_init(init)]
@@ -493,6 +610,36 @@ mod tests {
         assert!(verify_transaction_signature(&decoded_tx, &valid_keys));
     }
 
+    #[test]
+    fn test_verify_delegate_action() {
+        let signer = InMemorySigner::from_random("alice".parse().unwrap(), KeyType::ED25519);
+        let delegate_action = DelegateAction {
+            sender_id: "alice".parse().unwrap(),
+            receiver_id: "bob".parse().unwrap(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 1 }).try_into().unwrap()],
+            nonce: 1,
+            max_block_height: 100,
+            public_key: signer.public_key(),
+        };
+        let signed = SignedDelegateAction::new(delegate_action.clone(), &signer);
+        assert!(signed.verify());
+
+        let mut tampered = signed.clone();
+        tampered.delegate_action.max_block_height = 1000;
+        assert!(!tampered.verify());
+
+        // Signature of the plain borsh bytes must not be accepted.
+        let signature = signer.sign(hash(&delegate_action.try_to_vec().unwrap()).as_ref());
+        assert!(
+            !SignedDelegateAction { delegate_action: delegate_action.clone(), signature }.verify()
+        );
+
+        // Nested delegate actions can't be deserialized.
+        let mut nested = delegate_action;
+        nested.actions = vec![NonDelegateAction(Action::Delegate(signed))];
+        assert!(DelegateAction::try_from_slice(&nested.try_to_vec().unwrap()).is_err());
+    }
+
     /// This test is change checker for a reason - we don't expect transaction format to change.
     /// If it does - you MUST update all of the dependencies: like nearlib and other clients.
     #[test]
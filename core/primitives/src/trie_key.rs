@@ -405,6 +405,11 @@ pub mod trie_key_parsers {
         res
     }
 
+    /// Prefix of the keys of all contract codes.
+    pub fn get_raw_prefix_for_contract_codes() -> Vec<u8> {
+        col::CONTRACT_CODE.to_vec()
+    }
+
     pub fn get_raw_prefix_for_contract_data(account_id: &AccountId, prefix: &[u8]) -> Vec<u8> {
         let mut res = Vec::with_capacity(
             col::CONTRACT_DATA.len()
@@ -49,6 +49,9 @@ pub trait ValidatorSigner: Sync + Send {
         epoch_id: &EpochId,
     ) -> Signature;
 
+    /// Signs a proto-serialized AccountKeyPayload of the network protocol.
+    fn sign_account_key_payload(&self, proto_bytes: &[u8]) -> Signature;
+
     fn compute_vrf_with_proof(
         &self,
         data: &[u8],
@@ -111,6 +114,10 @@ impl ValidatorSigner for EmptyValidatorSigner {
         Signature::default()
     }
 
+    fn sign_account_key_payload(&self, _proto_bytes: &[u8]) -> Signature {
+        Signature::default()
+    }
+
     fn compute_vrf_with_proof(
         &self,
         _data: &[u8],
@@ -201,6 +208,10 @@ impl ValidatorSigner for InMemoryValidatorSigner {
         self.signer.sign(hash.as_ref())
     }
 
+    fn sign_account_key_payload(&self, proto_bytes: &[u8]) -> Signature {
+        self.signer.sign(proto_bytes)
+    }
+
     fn compute_vrf_with_proof(
         &self,
         data: &[u8],
@@ -20,7 +20,7 @@ pub struct Version {
 pub type DbVersion = u32;
 
 /// Current version of the database.
-pub const DB_VERSION: DbVersion = 31;
+pub const DB_VERSION: DbVersion = 33;
 
 use crate::upgrade_schedule::{get_protocol_version_internal, ProtocolUpgradeVotingSchedule};
 /// Protocol version type.
@@ -167,6 +167,17 @@ pub enum ProtocolFeature {
     /// Validate account id for function call access keys.
     #[cfg(feature = "protocol_feature_account_id_in_function_call_permission")]
     AccountIdInFunctionCallPermission,
+    /// Read state values through flat storage on nodes which have it. Reads still charge gas for
+    /// the trie nodes on the path of the key.
+    #[cfg(feature = "protocol_feature_flat_state")]
+    FlatStorageReads,
+    /// Meta transactions: actions signed by one account and relayed (and paid
+    /// for) by another one.
+    #[cfg(feature = "protocol_feature_delegate_action")]
+    DelegateAction,
+    /// `ed25519_verify` host function to check ed25519 signatures from contracts.
+    #[cfg(feature = "protocol_feature_ed25519_verify")]
+    Ed25519Verify,
 }
 
 /// Both, outgoing and incoming tcp connections to peers, will be rejected if `peer's`
@@ -176,7 +187,7 @@ pub const PEER_MIN_ALLOWED_PROTOCOL_VERSION: ProtocolVersion = STABLE_PROTOCOL_V
 /// Current protocol version used on the mainnet.
 /// Some features (e. g. FixStorageUsage) require that there is at least one epoch with exactly
 /// the corresponding version
-const STABLE_PROTOCOL_VERSION: ProtocolVersion = 56;
+const STABLE_PROTOCOL_VERSION: ProtocolVersion = 57;
 
 /// Largest protocol version supported by the current binary.
 pub const PROTOCOL_VERSION: ProtocolVersion = if cfg!(feature = "nightly_protocol") {
@@ -252,6 +263,12 @@ impl ProtocolFeature {
             ProtocolFeature::ChunkOnlyProducers => 100,
             #[cfg(feature = "protocol_feature_fix_staking_threshold")]
             ProtocolFeature::FixStakingThreshold => 126,
+            #[cfg(feature = "protocol_feature_flat_state")]
+            ProtocolFeature::FlatStorageReads => 127,
+            #[cfg(feature = "protocol_feature_delegate_action")]
+            ProtocolFeature::DelegateAction => 128,
+            #[cfg(feature = "protocol_feature_ed25519_verify")]
+            ProtocolFeature::Ed25519Verify => 129,
             #[cfg(feature = "protocol_feature_account_id_in_function_call_permission")]
             ProtocolFeature::AccountIdInFunctionCallPermission => 130,
         }
@@ -35,9 +35,10 @@ use crate::sharding::{
     ShardChunkHeaderV3,
 };
 use crate::transaction::{
-    Action, AddKeyAction, CreateAccountAction, DeleteAccountAction, DeleteKeyAction,
-    DeployContractAction, ExecutionMetadata, ExecutionOutcome, ExecutionOutcomeWithIdAndProof,
-    ExecutionStatus, FunctionCallAction, SignedTransaction, StakeAction, TransferAction,
+    Action, AddKeyAction, CreateAccountAction, DelegateAction, DeleteAccountAction,
+    DeleteKeyAction, DeployContractAction, ExecutionMetadata, ExecutionOutcome,
+    ExecutionOutcomeWithIdAndProof, ExecutionStatus, FunctionCallAction, SignedDelegateAction,
+    SignedTransaction, StakeAction, TransferAction,
 };
 use crate::types::{
     AccountId, AccountWithPublicKey, Balance, BlockHeight, CompiledContractCache, EpochHeight,
@@ -870,6 +871,10 @@ pub enum ActionView {
     DeleteAccount {
         beneficiary_id: AccountId,
     },
+    Delegate {
+        delegate_action: DelegateAction,
+        signature: Signature,
+    },
 }
 
 impl From<Action> for ActionView {
@@ -897,6 +902,10 @@ impl From<Action> for ActionView {
             Action::DeleteAccount(action) => {
                 ActionView::DeleteAccount { beneficiary_id: action.beneficiary_id }
             }
+            Action::Delegate(action) => ActionView::Delegate {
+                delegate_action: action.delegate_action,
+                signature: action.signature,
+            },
         }
     }
 }
@@ -931,6 +940,9 @@ impl TryFrom<ActionView> for Action {
             ActionView::DeleteAccount { beneficiary_id } => {
                 Action::DeleteAccount(DeleteAccountAction { beneficiary_id })
             }
+            ActionView::Delegate { delegate_action, signature } => {
+                Action::Delegate(SignedDelegateAction { delegate_action, signature })
+            }
         })
     }
 }
@@ -1000,6 +1012,43 @@ impl Default for FinalExecutionStatus {
     }
 }
 
+impl FinalExecutionStatus {
+    /// Computes the status of a transaction from the outcomes of the transaction and all the
+    /// receipts produced by it, following the receipts the execution resulted in.
+    pub fn from_outcomes(
+        transaction_hash: &CryptoHash,
+        outcomes: &[ExecutionOutcomeWithIdView],
+    ) -> Self {
+        let mut looking_for_id = *transaction_hash;
+        let num_outcomes = outcomes.len();
+        outcomes
+            .iter()
+            .find_map(|outcome_with_id| {
+                if outcome_with_id.id == looking_for_id {
+                    match &outcome_with_id.outcome.status {
+                        ExecutionStatusView::Unknown if num_outcomes == 1 => {
+                            Some(FinalExecutionStatus::NotStarted)
+                        }
+                        ExecutionStatusView::Unknown => Some(FinalExecutionStatus::Started),
+                        ExecutionStatusView::Failure(e) => {
+                            Some(FinalExecutionStatus::Failure(e.clone()))
+                        }
+                        ExecutionStatusView::SuccessValue(v) => {
+                            Some(FinalExecutionStatus::SuccessValue(v.clone()))
+                        }
+                        ExecutionStatusView::SuccessReceiptId(id) => {
+                            looking_for_id = *id;
+                            None
+                        }
+                    }
+                } else {
+                    None
+                }
+            })
+            .expect("results should resolve to a final outcome")
+    }
+}
+
 #[cfg_attr(feature = "deepsize_feature", derive(deepsize::DeepSizeOf))]
 #[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Debug, PartialEq, Eq, Clone)]
 pub enum ServerError {
@@ -1229,6 +1278,46 @@ impl From<FinalExecutionOutcomeWithReceiptView> for FinalExecutionOutcomeView {
     }
 }
 
+/// Execution trace of a transaction: the transaction and all the action receipts it resulted in,
+/// with the blocks and shards they were executed in and the dependencies between them.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TransactionTraceView {
+    pub transaction_hash: CryptoHash,
+    /// Execution status of the transaction, `Started` while some receipts are pending.
+    pub status: FinalExecutionStatus,
+    /// The transaction followed by the executed receipts in the order they were created in.
+    pub nodes: Vec<TraceNodeView>,
+    /// Receipts created by the nodes which haven't been executed yet.
+    pub pending_receipt_ids: Vec<CryptoHash>,
+}
+
+/// Transaction or receipt in a [`TransactionTraceView`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceNodeView {
+    /// Hash of the transaction or id of the receipt.
+    pub id: CryptoHash,
+    /// The transaction or receipt whose execution created the receipt, `None` for the
+    /// transaction.
+    pub parent_id: Option<CryptoHash>,
+    /// Signer of the transaction or predecessor of the receipt.
+    pub predecessor_id: AccountId,
+    pub block_hash: CryptoHash,
+    pub block_height: BlockHeight,
+    pub shard_id: ShardId,
+    /// Data the receipt waited for, i.e. the results of the promises it's a callback of.
+    pub input_data: Vec<TraceDataDependencyView>,
+    /// Gas burnt, gas profile, logs and created receipts.
+    pub outcome: ExecutionOutcomeView,
+}
+
+/// Data a receipt in a [`TransactionTraceView`] depends on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct TraceDataDependencyView {
+    pub data_id: CryptoHash,
+    /// The receipt whose result is the data, if it's part of the trace.
+    pub producer_id: Option<CryptoHash>,
+}
+
 pub mod validator_stake_view {
     use crate::types::validator_stake::ValidatorStake;
     use borsh::{BorshDeserialize, BorshSerialize};
@@ -1521,7 +1610,7 @@ pub struct GasPriceView {
 ///
 /// [serializable view]: ./index.html
 /// [`StateChangesRequest`]: ../types/struct.StateChangesRequest.html
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "changes_type", rename_all = "snake_case")]
 pub enum StateChangesRequestView {
     AccountChanges {
@@ -0,0 +1,241 @@
+//! Checkpoints of the node database.
+//!
+//! A checkpoint is a directory holding a consistent copy of the database in
+//! its `data` subdirectory together with a manifest describing the chain head
+//! the copy was taken at.  Since RocksDB checkpoints hard link immutable SST
+//! files, creating one on the same filesystem as the database is cheap and can
+//! be done while the node is running.
+
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use near_primitives::block::Tip;
+use near_primitives::hash::CryptoHash;
+use near_primitives::types::BlockHeight;
+use near_primitives::version::DbVersion;
+
+use crate::db::{Mode, RocksDB};
+use crate::{DBCol, Store, StoreConfig, FINAL_HEAD_KEY, HEAD_KEY};
+
+/// Name of the manifest file within a checkpoint directory.
+pub const CHECKPOINT_MANIFEST_FILENAME: &str = "checkpoint.json";
+
+/// Name of the subdirectory of a checkpoint directory holding the database.
+pub const CHECKPOINT_DATA_DIRNAME: &str = "data";
+
+/// Description of a database checkpoint stored alongside of it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CheckpointManifest {
+    /// Version of the database in the checkpoint.
+    pub db_version: DbVersion,
+    /// Height of the head block at the time of the checkpoint.
+    pub head_height: BlockHeight,
+    pub head_hash: CryptoHash,
+    /// Height of the last final block at the time of the checkpoint.
+    pub final_head_height: BlockHeight,
+    pub final_head_hash: CryptoHash,
+    /// Whether data behind the garbage collection horizon has been removed
+    /// from the checkpoint, as it would have been on a non-archival node.
+    pub pruned: bool,
+}
+
+impl CheckpointManifest {
+    /// Reads manifest of the checkpoint in given directory.
+    pub fn read(checkpoint_dir: &Path) -> io::Result<Self> {
+        let path = checkpoint_dir.join(CHECKPOINT_MANIFEST_FILENAME);
+        let file = std::fs::File::open(&path)?;
+        serde_json::from_reader(io::BufReader::new(file)).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: failed to parse checkpoint manifest: {err}", path.display()),
+            )
+        })
+    }
+
+    /// Writes the manifest into given checkpoint directory overwriting any
+    /// existing manifest.
+    pub fn write(&self, checkpoint_dir: &Path) -> io::Result<()> {
+        let path = checkpoint_dir.join(CHECKPOINT_MANIFEST_FILENAME);
+        let file = std::fs::File::create(&path)?;
+        serde_json::to_writer_pretty(io::BufWriter::new(file), self).map_err(io::Error::from)
+    }
+
+    /// Reads the manifest data directly from a database.
+    fn from_store(store: &Store) -> io::Result<Self> {
+        let get_tip = |key: &[u8]| -> io::Result<Tip> {
+            store.get_ser(DBCol::BlockMisc, key)?.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("missing {} in database", String::from_utf8_lossy(key)),
+                )
+            })
+        };
+        let head = get_tip(HEAD_KEY)?;
+        let final_head = get_tip(FINAL_HEAD_KEY)?;
+        let db_version = store
+            .get(DBCol::DbVersion, crate::db::VERSION_KEY)?
+            .and_then(|value| serde_json::from_slice(&value).ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidData, "failed to read database version")
+            })?;
+        Ok(Self {
+            db_version,
+            head_height: head.height,
+            head_hash: head.last_block_hash,
+            final_head_height: final_head.height,
+            final_head_hash: final_head.last_block_hash,
+            pruned: false,
+        })
+    }
+}
+
+/// Returns path of the database within given checkpoint directory.
+pub fn checkpoint_data_path(checkpoint_dir: &Path) -> PathBuf {
+    checkpoint_dir.join(CHECKPOINT_DATA_DIRNAME)
+}
+
+/// Creates a checkpoint of the store in given directory.
+///
+/// The directory must not exist.  The manifest is populated from the copied
+/// database rather than from `store` so it describes exactly the state of the
+/// checkpoint even if the node keeps writing to `store` in the meantime.
+pub fn create_checkpoint(store: &Store, checkpoint_dir: &Path) -> io::Result<CheckpointManifest> {
+    if checkpoint_dir.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{}: checkpoint directory already exists", checkpoint_dir.display()),
+        ));
+    }
+    std::fs::create_dir_all(checkpoint_dir)?;
+    let data_path = checkpoint_data_path(checkpoint_dir);
+    tracing::info!(target: "store", path = %data_path.display(), "Creating database checkpoint");
+    store.storage.create_checkpoint(&data_path)?;
+
+    let manifest = {
+        let db = RocksDB::open(&data_path, &StoreConfig::default(), Mode::ReadOnly)?;
+        CheckpointManifest::from_store(&Store::new(Arc::new(db)))?
+    };
+    manifest.write(checkpoint_dir)?;
+    tracing::info!(
+        target: "store",
+        path = %checkpoint_dir.display(),
+        head_height = manifest.head_height,
+        final_head_height = manifest.final_head_height,
+        "Created database checkpoint"
+    );
+    Ok(manifest)
+}
+
+/// Creates a checkpoint of the store in a subdirectory of `parent_dir` named
+/// after the height of the last final block in the checkpoint.
+///
+/// Unlike the head, the last final block can’t be reverted by a reorg, so
+/// the name identifies the checkpoint.  The checkpoint is created in
+/// a temporary directory first since the last final block is known only once
+/// the database has been copied.  Fails if a checkpoint of the same final
+/// block already exists.
+pub fn create_checkpoint_at_final_head(
+    store: &Store,
+    parent_dir: &Path,
+) -> io::Result<(PathBuf, CheckpointManifest)> {
+    std::fs::create_dir_all(parent_dir)?;
+    let tmp_dir = tempfile::Builder::new().prefix(".checkpoint-").tempdir_in(parent_dir)?;
+    let tmp_checkpoint_dir = tmp_dir.path().join("checkpoint");
+    let manifest = create_checkpoint(store, &tmp_checkpoint_dir)?;
+    let checkpoint_dir = parent_dir.join(manifest.final_head_height.to_string());
+    if checkpoint_dir.exists() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{}: checkpoint directory already exists", checkpoint_dir.display()),
+        ));
+    }
+    std::fs::rename(&tmp_checkpoint_dir, &checkpoint_dir)?;
+    Ok((checkpoint_dir, manifest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::types::EpochId;
+
+    fn tip(height: BlockHeight) -> Tip {
+        Tip {
+            height,
+            last_block_hash: CryptoHash::hash_borsh(&height),
+            prev_block_hash: CryptoHash::hash_borsh(&(height - 1)),
+            epoch_id: EpochId::default(),
+            next_epoch_id: EpochId::default(),
+        }
+    }
+
+    #[test]
+    fn test_create_checkpoint() {
+        let (tmp_dir, opener) = Store::test_opener();
+        let store = opener.open();
+        crate::migrations::set_store_version(&store, near_primitives::version::DB_VERSION);
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip(12)).unwrap();
+        store_update.set_ser(DBCol::BlockMisc, FINAL_HEAD_KEY, &tip(10)).unwrap();
+        store_update.commit().unwrap();
+
+        let checkpoint_dir = tmp_dir.path().join("checkpoint");
+        let manifest = create_checkpoint(&store, &checkpoint_dir).unwrap();
+        assert_eq!(
+            manifest,
+            CheckpointManifest {
+                db_version: near_primitives::version::DB_VERSION,
+                head_height: 12,
+                head_hash: tip(12).last_block_hash,
+                final_head_height: 10,
+                final_head_hash: tip(10).last_block_hash,
+                pruned: false,
+            }
+        );
+        assert_eq!(CheckpointManifest::read(&checkpoint_dir).unwrap(), manifest);
+
+        // Writes to the store after the checkpoint don’t affect it.
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip(13)).unwrap();
+        store_update.commit().unwrap();
+        let db = RocksDB::open(
+            &checkpoint_data_path(&checkpoint_dir),
+            &StoreConfig::test_config(),
+            Mode::ReadOnly,
+        )
+        .unwrap();
+        let head: Tip =
+            Store::new(Arc::new(db)).get_ser(DBCol::BlockMisc, HEAD_KEY).unwrap().unwrap();
+        assert_eq!(head.height, 12);
+
+        let err = create_checkpoint(&store, &checkpoint_dir).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+    }
+
+    #[test]
+    fn test_create_checkpoint_at_final_head() {
+        let (tmp_dir, opener) = Store::test_opener();
+        let store = opener.open();
+        crate::migrations::set_store_version(&store, near_primitives::version::DB_VERSION);
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip(12)).unwrap();
+        store_update.set_ser(DBCol::BlockMisc, FINAL_HEAD_KEY, &tip(10)).unwrap();
+        store_update.commit().unwrap();
+
+        let parent_dir = tmp_dir.path().join("checkpoints");
+        let (checkpoint_dir, manifest) =
+            create_checkpoint_at_final_head(&store, &parent_dir).unwrap();
+        assert_eq!(checkpoint_dir, parent_dir.join("10"));
+        assert_eq!(manifest.final_head_height, 10);
+        assert_eq!(CheckpointManifest::read(&checkpoint_dir).unwrap(), manifest);
+
+        // Another checkpoint of the same final block fails and leaves nothing behind.
+        let mut store_update = store.store_update();
+        store_update.set_ser(DBCol::BlockMisc, HEAD_KEY, &tip(13)).unwrap();
+        store_update.commit().unwrap();
+        let err = create_checkpoint_at_final_head(&store, &parent_dir).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_dir(&parent_dir).unwrap().count(), 1);
+        assert_eq!(CheckpointManifest::read(&checkpoint_dir).unwrap().head_height, 12);
+    }
+}
@@ -233,6 +233,22 @@ pub enum DBCol {
     /// - *Rows*: BlockShardId (BlockHash || ShardId) - 40 bytes
     /// - *Column type*: StateChangesForSplitStates
     StateChangesForSplitStates = 49,
+    /// Flat state: references to values of the state trie at the flat head.
+    /// - *Rows*: ShardUId || serialized TrieKey
+    /// - *Column type*: ValueRef (value length and hash)
+    FlatState = 50,
+    /// Changes made to the flat state by blocks on top of the flat head.
+    /// - *Rows*: ShardUId || BlockHash
+    /// - *Column type*: block info and FlatStateDelta
+    FlatStateDeltas = 51,
+    /// Flat head per shard, i.e. the block for which `FlatState` holds the state.
+    /// - *Rows*: ShardUId
+    /// - *Column type*: BlockHash (CryptoHash)
+    FlatStateMisc = 52,
+    /// Scores of the peers known to the node, see [network_primitives::types::PeerScore].
+    /// - *Rows*: peer_id (PublicKey)
+    /// - *Content type*: latency and the numbers of useful messages and misbehaviours
+    PeerScores = 53,
 }
 
 impl DBCol {
@@ -323,11 +339,16 @@ impl DBCol {
             | DBCol::PeerComponent  // Peer related info doesn't GC
             | DBCol::LastComponentNonce
             | DBCol::ComponentEdges
+            | DBCol::PeerScores
             | DBCol::BlockOrdinal
             | DBCol::EpochInfo           // https://github.com/nearprotocol/nearcore/pull/2952
             | DBCol::EpochValidatorInfo  // https://github.com/nearprotocol/nearcore/pull/2952
             | DBCol::EpochStart          // https://github.com/nearprotocol/nearcore/pull/2952
-            | DBCol::CachedContractCode => false,
+            | DBCol::CachedContractCode
+            // Flat storage is cleaned up when its head moves
+            | DBCol::FlatState
+            | DBCol::FlatStateDeltas
+            | DBCol::FlatStateMisc => false,
             _ => true,
         }
     }
@@ -343,6 +364,34 @@ impl DBCol {
         }
     }
 
+    /// Whether data in this column is copied to the cold database before it’s
+    /// garbage collected.
+    ///
+    /// Only archival nodes with a cold database do that.  Columns which hold
+    /// data needed only for processing recent blocks (e.g. state parts or
+    /// trie changes) are not copied.
+    pub const fn is_cold(&self) -> bool {
+        match self {
+            DBCol::State
+            | DBCol::Block
+            | DBCol::BlockExtra
+            | DBCol::BlockInfo
+            | DBCol::NextBlockHashes
+            | DBCol::Chunks
+            | DBCol::ChunkExtra
+            | DBCol::ChunkHashesByHeight
+            | DBCol::Transactions
+            | DBCol::Receipts
+            | DBCol::IncomingReceipts
+            | DBCol::OutgoingReceipts
+            | DBCol::ReceiptIdToShardId
+            | DBCol::TransactionResult
+            | DBCol::OutcomeIds
+            | DBCol::StateChanges => true,
+            _ => false,
+        }
+    }
+
     /// Returns variant’s name as a static string.
     ///
     /// This is equivalent to [`Into::into`] but often makes the call site
@@ -405,6 +454,10 @@ impl fmt::Display for DBCol {
             Self::EpochValidatorInfo => "epoch validator info",
             Self::HeaderHashesByHeight => "header hashes indexed by their height",
             Self::StateChangesForSplitStates => "state changes indexed by block hash and shard id",
+            Self::FlatState => "flat state",
+            Self::FlatStateDeltas => "flat state deltas",
+            Self::FlatStateMisc => "flat state head",
+            Self::PeerScores => "peer scores",
         };
         write!(f, "{}", desc)
     }
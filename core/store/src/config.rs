@@ -4,6 +4,7 @@ use near_primitives::version::DbVersion;
 use crate::db::Mode;
 
 const STORE_PATH: &str = "data";
+const COLD_STORE_PATH: &str = "cold-data";
 
 #[derive(Clone, Debug, serde::Serialize, serde::Deserialize)]
 #[serde(default)]
@@ -42,11 +43,22 @@ pub struct StoreConfig {
     /// the performance of the storage
     pub block_size: bytesize::ByteSize,
 
-    /// Trie cache capacities
-    /// Default value: ShardUId {version: 1, shard_id: 3} -> 2_000_000. TODO: clarify
-    /// We're still experimenting with this parameter and it seems decreasing its value can improve
-    /// the performance of the storage
+    /// Size limits of the trie caches of the shards.
+    /// Default value: 50MB per shard and 10MB for nodes within 3 levels of the root.
+    /// Can be changed without restarting the node by editing the config file and sending SIGHUP
+    /// to neard.
+    pub trie_cache: crate::TrieCacheConfig,
+
+    /// Deprecated, use `trie_cache` instead.
+    /// Limits on the number of entries in the trie caches of particular shards.  They are turned
+    /// into size limits in `trie_cache` when the config is loaded, see
+    /// [`Self::apply_trie_cache_capacities`].
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub trie_cache_capacities: Vec<(ShardUId, usize)>,
+
+    /// Prefetching of the state needed to apply chunks into the trie caches.
+    /// Disabled by default.
+    pub trie_prefetch: crate::TriePrefetchConfig,
 }
 
 impl StoreConfig {
@@ -60,6 +72,14 @@ impl StoreConfig {
         Self { max_open_files: 512, ..Self::default() }
     }
 
+    /// Moves the limits set with the deprecated `trie_cache_capacities` into
+    /// `trie_cache`.  Returns whether there were any.
+    pub fn apply_trie_cache_capacities(&mut self) -> bool {
+        let capacities = std::mem::take(&mut self.trie_cache_capacities);
+        self.trie_cache.add_capacities(&capacities);
+        !capacities.is_empty()
+    }
+
     /// Returns cache size for given column.
     pub const fn col_cache_size(&self, col: crate::DBCol) -> bytesize::ByteSize {
         match col {
@@ -97,7 +117,10 @@ impl Default for StoreConfig {
             // we use it since then.
             block_size: bytesize::ByteSize::kib(16),
 
-            trie_cache_capacities: Default::default(),
+            trie_cache: Default::default(),
+            trie_cache_capacities: vec![],
+
+            trie_prefetch: Default::default(),
         }
     }
 }
@@ -123,6 +146,12 @@ pub struct StoreOpener<'a> {
 
     /// Which mode to open storeg in.
     mode: Mode,
+
+    /// Path to and configuration of the cold database, if any.
+    cold: Option<(std::path::PathBuf, &'a StoreConfig)>,
+
+    /// Home directory used to resolve relative database paths.
+    home_dir: std::path::PathBuf,
 }
 
 impl<'a> StoreOpener<'a> {
@@ -130,7 +159,22 @@ impl<'a> StoreOpener<'a> {
     pub(crate) fn new(home_dir: &std::path::Path, config: &'a StoreConfig) -> Self {
         let path =
             home_dir.join(config.path.as_deref().unwrap_or(std::path::Path::new(STORE_PATH)));
-        Self { path, config, mode: Mode::ReadWrite }
+        Self { path, config, mode: Mode::ReadWrite, cold: None, home_dir: home_dir.to_path_buf() }
+    }
+
+    /// Configures the cold database of an archival node.
+    ///
+    /// The path in the configuration is resolved like the path of the main
+    /// (hot) database and defaults to `cold-data`.  When set, the opened store
+    /// reads from both databases; see [`crate::Store::cold_store`].
+    pub fn cold(mut self, config: Option<&'a StoreConfig>) -> Self {
+        self.cold = config.map(|config| {
+            let path = self
+                .home_dir
+                .join(config.path.as_deref().unwrap_or(std::path::Path::new(COLD_STORE_PATH)));
+            (path, config)
+        });
+        self
     }
 
     /// Configure which mode the database should be opened in.
@@ -179,6 +223,14 @@ impl<'a> StoreOpener<'a> {
         }
         let db = crate::RocksDB::open(&self.path, &self.config, self.mode)
             .expect("Failed to open the database");
-        crate::Store::new(std::sync::Arc::new(db))
+        match &self.cold {
+            None => crate::Store::new(std::sync::Arc::new(db)),
+            Some((cold_path, cold_config)) => {
+                tracing::info!(target: "near", path=%cold_path.display(), "Opening cold RocksDB database");
+                let cold_db = crate::RocksDB::open(cold_path, cold_config, self.mode)
+                    .expect("Failed to open the cold database");
+                crate::Store::new_with_cold(std::sync::Arc::new(db), std::sync::Arc::new(cold_db))
+            }
+        }
     }
 }
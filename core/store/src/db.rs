@@ -17,6 +17,7 @@ use strum::IntoEnumIterator;
 use tracing::{error, info, warn};
 
 pub mod refcount;
+pub mod splitdb;
 
 pub const VERSION_KEY: &[u8; 7] = b"VERSION";
 
@@ -301,6 +302,12 @@ pub trait Database: Sync + Send {
 
     /// Returns statistics about the database if available.
     fn get_store_statistics(&self) -> Option<StoreStatistics>;
+
+    /// Creates a consistent point-in-time copy of the database at given path.
+    ///
+    /// The path must not exist.  Returns an error if the database doesn’t
+    /// support checkpoints (e.g. in-memory databases).
+    fn create_checkpoint(&self, path: &Path) -> io::Result<()>;
 }
 
 impl RocksDB {
@@ -443,6 +450,10 @@ impl Database for RocksDB {
         }
         None
     }
+
+    fn create_checkpoint(&self, path: &Path) -> io::Result<()> {
+        self.checkpoint()?.create_checkpoint(path).map_err(into_other)
+    }
 }
 
 /// Returns lowest value following largest value with given prefix.
@@ -543,6 +554,10 @@ impl Database for TestDB {
     fn get_store_statistics(&self) -> Option<StoreStatistics> {
         None
     }
+
+    fn create_checkpoint(&self, _path: &Path) -> io::Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "in-memory database has no checkpoints"))
+    }
 }
 
 fn assert_no_overwrite(col: DBCol, key: &[u8], value: &[u8], old_value: &[u8]) {
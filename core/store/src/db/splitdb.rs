@@ -0,0 +1,218 @@
+//! Database which combines a hot and a cold database.
+//!
+//! Archival nodes keep all the data since genesis.  To avoid putting years of
+//! history on the same disk and behind the same block cache as the data
+//! needed to follow the chain, finalized data which falls behind the garbage
+//! collection horizon is copied to a separate ‘cold’ database before it’s
+//! removed from the ‘hot’ one (see `ChainStoreUpdate::clear_block_data`).
+//!
+//! [`SplitDB`] presents the two databases as a single one.  Reads first look
+//! at the hot database and, for the columns copied to the cold database (see
+//! [`DBCol::is_cold`]), fall through to the cold one if the key is missing.
+//! Writes go to the hot database only; the cold database is written directly
+//! through [`crate::Store::cold_store`].
+
+use std::cmp::Ordering;
+use std::io;
+use std::iter::Peekable;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::db::{refcount, DBIterator, DBTransaction, Database, StoreStatistics};
+use crate::DBCol;
+
+pub struct SplitDB {
+    hot: Arc<dyn Database>,
+    cold: Arc<dyn Database>,
+}
+
+impl SplitDB {
+    pub fn new(hot: Arc<dyn Database>, cold: Arc<dyn Database>) -> Self {
+        Self { hot, cold }
+    }
+}
+
+impl Database for SplitDB {
+    fn get_raw_bytes(&self, col: DBCol, key: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let hot_value = self.hot.get_raw_bytes(col, key)?;
+        if !col.is_cold() {
+            return Ok(hot_value);
+        }
+        // Reference-counted values which were garbage collected may still be
+        // present in the hot database with a non-positive reference count
+        // until the next compaction.  Treat them as missing.
+        let is_present = match &hot_value {
+            None => false,
+            Some(value) => !col.is_rc() || refcount::decode_value_with_rc(value).0.is_some(),
+        };
+        if is_present {
+            return Ok(hot_value);
+        }
+        match self.cold.get_raw_bytes(col, key)? {
+            Some(cold_value) => Ok(Some(cold_value)),
+            None => Ok(hot_value),
+        }
+    }
+
+    fn iter<'a>(&'a self, col: DBCol) -> DBIterator<'a> {
+        if !col.is_cold() {
+            return self.hot.iter(col);
+        }
+        merge_iters(self.hot.iter(col), self.cold.iter(col))
+    }
+
+    fn iter_prefix<'a>(&'a self, col: DBCol, key_prefix: &'a [u8]) -> DBIterator<'a> {
+        if !col.is_cold() {
+            return self.hot.iter_prefix(col, key_prefix);
+        }
+        merge_iters(self.hot.iter_prefix(col, key_prefix), self.cold.iter_prefix(col, key_prefix))
+    }
+
+    fn iter_raw_bytes<'a>(&'a self, col: DBCol) -> DBIterator<'a> {
+        if !col.is_cold() {
+            return self.hot.iter_raw_bytes(col);
+        }
+        merge_iters(self.hot.iter_raw_bytes(col), self.cold.iter_raw_bytes(col))
+    }
+
+    fn write(&self, batch: DBTransaction) -> io::Result<()> {
+        self.hot.write(batch)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.hot.flush()?;
+        self.cold.flush()
+    }
+
+    fn get_store_statistics(&self) -> Option<StoreStatistics> {
+        self.hot.get_store_statistics()
+    }
+
+    /// Checkpoints are not supported: a checkpoint of the hot database alone
+    /// would miss the data already moved to the cold database.
+    fn create_checkpoint(&self, _path: &Path) -> io::Result<()> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "checkpoints of databases with cold storage are not supported",
+        ))
+    }
+}
+
+/// Merges two iterators sorted by key into a single sorted iterator.
+///
+/// If both iterators return the same key, the item from `hot` is used.
+fn merge_iters<'a>(hot: DBIterator<'a>, cold: DBIterator<'a>) -> DBIterator<'a> {
+    Box::new(MergeIter { hot: hot.peekable(), cold: cold.peekable() })
+}
+
+struct MergeIter<'a> {
+    hot: Peekable<DBIterator<'a>>,
+    cold: Peekable<DBIterator<'a>>,
+}
+
+impl<'a> Iterator for MergeIter<'a> {
+    type Item = io::Result<(Box<[u8]>, Box<[u8]>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let ordering = match (self.hot.peek(), self.cold.peek()) {
+            (None, None) => return None,
+            (Some(_), None) | (Some(Err(_)), _) => Ordering::Less,
+            (None, Some(_)) | (_, Some(Err(_))) => Ordering::Greater,
+            (Some(Ok((hot_key, _))), Some(Ok((cold_key, _)))) => hot_key.cmp(cold_key),
+        };
+        match ordering {
+            Ordering::Less => self.hot.next(),
+            Ordering::Greater => self.cold.next(),
+            Ordering::Equal => {
+                self.cold.next();
+                self.hot.next()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::SplitDB;
+    use crate::db::{DBTransaction, Database, TestDB};
+    use crate::DBCol;
+
+    fn write(db: &dyn Database, col: DBCol, items: &[(&str, &str)]) {
+        let mut transaction = DBTransaction::new();
+        for (key, value) in items {
+            transaction.set(col, key.as_bytes().to_vec(), value.as_bytes().to_vec());
+        }
+        db.write(transaction).unwrap();
+    }
+
+    fn collect(iter: crate::db::DBIterator) -> Vec<(Vec<u8>, Vec<u8>)> {
+        iter.map(|item| item.map(|(key, value)| (key.to_vec(), value.to_vec())))
+            .collect::<std::io::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_split_db_reads() {
+        let hot = Arc::new(TestDB::new());
+        let cold = Arc::new(TestDB::new());
+        write(&*hot, DBCol::Block, &[("a", "hot"), ("c", "hot")]);
+        write(&*cold, DBCol::Block, &[("a", "cold"), ("b", "cold"), ("d", "cold")]);
+        let split = SplitDB::new(hot.clone(), cold.clone());
+
+        assert_eq!(split.get_raw_bytes(DBCol::Block, b"a").unwrap(), Some(b"hot".to_vec()));
+        assert_eq!(split.get_raw_bytes(DBCol::Block, b"b").unwrap(), Some(b"cold".to_vec()));
+        assert_eq!(split.get_raw_bytes(DBCol::Block, b"e").unwrap(), None);
+        assert_eq!(
+            collect(split.iter(DBCol::Block)),
+            vec![
+                (b"a".to_vec(), b"hot".to_vec()),
+                (b"b".to_vec(), b"cold".to_vec()),
+                (b"c".to_vec(), b"hot".to_vec()),
+                (b"d".to_vec(), b"cold".to_vec()),
+            ]
+        );
+
+        // Writes only go to the hot database.
+        write(&split, DBCol::Block, &[("e", "new")]);
+        assert_eq!(hot.get_raw_bytes(DBCol::Block, b"e").unwrap(), Some(b"new".to_vec()));
+        assert_eq!(cold.get_raw_bytes(DBCol::Block, b"e").unwrap(), None);
+    }
+
+    #[test]
+    fn test_split_db_refcount() {
+        let hot = Arc::new(TestDB::new());
+        let cold = Arc::new(TestDB::new());
+        let split = SplitDB::new(hot.clone(), cold.clone());
+        let value = crate::db::refcount::add_positive_refcount(b"value", 1.try_into().unwrap());
+        let mut transaction = DBTransaction::new();
+        transaction.update_refcount(DBCol::State, b"key".to_vec(), value.clone());
+        cold.write(transaction).unwrap();
+        // Deleted value may linger in the hot database with zero refcount.
+        let mut transaction = DBTransaction::new();
+        transaction.set(DBCol::State, b"key".to_vec(), 0i64.to_le_bytes().to_vec());
+        hot.write(transaction).unwrap();
+
+        assert_eq!(split.get_raw_bytes(DBCol::State, b"key").unwrap(), Some(value));
+    }
+
+    #[test]
+    fn test_split_db_hot_only_columns() {
+        let hot = Arc::new(TestDB::new());
+        let cold = Arc::new(TestDB::new());
+        write(&*hot, DBCol::BlockMisc, &[("a", "hot")]);
+        write(&*cold, DBCol::BlockMisc, &[("a", "cold"), ("b", "cold")]);
+        let split = SplitDB::new(hot, cold);
+
+        // Columns which aren't copied to the cold database are read from the
+        // hot one only.
+        assert_eq!(split.get_raw_bytes(DBCol::BlockMisc, b"a").unwrap(), Some(b"hot".to_vec()));
+        assert_eq!(split.get_raw_bytes(DBCol::BlockMisc, b"b").unwrap(), None);
+        assert_eq!(collect(split.iter(DBCol::BlockMisc)), vec![(b"a".to_vec(), b"hot".to_vec())]);
+        assert_eq!(
+            collect(split.iter_prefix(DBCol::BlockMisc, b"b")),
+            Vec::<(Vec<u8>, Vec<u8>)>::new()
+        );
+    }
+}
@@ -0,0 +1,654 @@
+//! Flat storage: a key-value view of the state which doesn't require trie traversal.
+//!
+//! Reading a value through [`Trie`] means walking from the state root down to the leaf and
+//! fetching every node on the way from [`DBCol::State`]. Flat storage instead keeps, for every
+//! shard, a direct mapping from serialized `TrieKey` to the reference ([`ValueRef`]) of its value,
+//! so that a lookup costs a single read followed by fetching the value itself by hash.
+//!
+//! The mapping stored in [`DBCol::FlatState`] corresponds to the state at the *flat head*, which
+//! follows the last final block. Changes made by blocks on top of the flat head are kept as
+//! [`FlatStateDelta`]s, both in memory and in [`DBCol::FlatStateDeltas`], since these blocks may
+//! still be reverted. When finality moves forward, deltas up to the new final block are applied to
+//! the flat state and the flat head is moved.
+//!
+//! Flat storage is only used for reads when `ProtocolFeature::FlatStorageReads` is enabled. For now
+//! it's only created from the genesis state and it's dropped when the node state syncs, in which
+//! case reads go through the trie. Gas for a read is charged per trie node on the path of the key,
+//! so reads served by flat storage still walk the trie to charge the same gas on every node.
+//!
+//! [`DBCol::State`]: crate::DBCol::State
+//! [`DBCol::FlatState`]: crate::DBCol::FlatState
+//! [`DBCol::FlatStateDeltas`]: crate::DBCol::FlatStateDeltas
+
+use std::collections::HashMap;
+use std::io;
+use std::sync::{Arc, Mutex, RwLock};
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::errors::StorageError;
+use near_primitives::hash::{hash, CryptoHash};
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::types::{BlockHeight, RawStateChangesWithTrieKey, StateRoot};
+use tracing::{debug, info};
+
+use crate::trie::POISONED_LOCK_ERR;
+use crate::{Store, StoreUpdate, Trie};
+
+/// Reference to a value stored in the trie: its length and the hash under which the value
+/// itself can be found in [`DBCol::State`](crate::DBCol::State).
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValueRef {
+    pub length: u32,
+    pub hash: CryptoHash,
+}
+
+impl ValueRef {
+    pub fn new(value: &[u8]) -> Self {
+        Self { length: value.len() as u32, hash: hash(value) }
+    }
+}
+
+/// Changes made to the state by a single block.
+///
+/// Maps serialized trie keys to the new value reference, or to `None` if the key was removed.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct FlatStateDelta(HashMap<Vec<u8>, Option<ValueRef>>);
+
+impl FlatStateDelta {
+    /// Builds the delta from the state changes recorded while applying a chunk.
+    pub fn from_state_changes(changes: &[RawStateChangesWithTrieKey]) -> Self {
+        let delta = changes
+            .iter()
+            .map(|RawStateChangesWithTrieKey { trie_key, changes }| {
+                let data = &changes.last().expect("state_changes must not be empty").data;
+                (trie_key.to_vec(), data.as_deref().map(ValueRef::new))
+            })
+            .collect();
+        Self(delta)
+    }
+
+    /// Returns `Some(value_ref)` if the key was changed by this delta, where `value_ref` is `None`
+    /// for removed keys, and `None` if the key wasn't touched.
+    pub fn get(&self, key: &[u8]) -> Option<Option<ValueRef>> {
+        self.0.get(key).copied()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Merges `other` on top of this delta, so that changes from `other` take precedence.
+    pub fn merge(&mut self, other: &Self) {
+        self.0.extend(other.0.iter().map(|(key, value)| (key.clone(), *value)))
+    }
+
+    /// Writes the changes from the delta into [`DBCol::FlatState`](crate::DBCol::FlatState).
+    pub fn apply_to_flat_state(&self, store_update: &mut StoreUpdate, shard_uid: ShardUId) {
+        for (key, value) in self.0.iter() {
+            store_helper::set_ref(store_update, shard_uid, key, *value);
+        }
+    }
+}
+
+/// Block which has a delta stored in flat storage.
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlockInfo {
+    pub hash: CryptoHash,
+    pub height: BlockHeight,
+    pub prev_hash: CryptoHash,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum FlatStorageError {
+    /// Flat storage can't serve the block, because it's not a descendant of the flat head or it
+    /// wasn't added to flat storage. Contains the flat head and the requested block hash.
+    BlockNotSupported((CryptoHash, CryptoHash)),
+    /// Failed to read or write flat storage data.
+    StorageInternalError,
+}
+
+impl From<io::Error> for FlatStorageError {
+    fn from(_: io::Error) -> Self {
+        FlatStorageError::StorageInternalError
+    }
+}
+
+impl From<FlatStorageError> for StorageError {
+    fn from(err: FlatStorageError) -> Self {
+        match err {
+            FlatStorageError::BlockNotSupported((flat_head, block_hash)) => {
+                StorageError::StorageInconsistentState(format!(
+                    "Flat storage with head {} can't serve block {}",
+                    flat_head, block_hash
+                ))
+            }
+            FlatStorageError::StorageInternalError => StorageError::StorageInternalError,
+        }
+    }
+}
+
+/// Functions to read and write flat storage data in the database.
+pub mod store_helper {
+    use super::{BlockInfo, FlatStateDelta, ValueRef};
+    use crate::{DBCol, Store, StoreUpdate};
+    use borsh::{BorshDeserialize, BorshSerialize};
+    use near_primitives::errors::StorageError;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::ShardUId;
+    use std::io;
+
+    /// Delta together with the block which produced it, as stored in [`DBCol::FlatStateDeltas`].
+    #[derive(BorshSerialize, BorshDeserialize)]
+    pub(crate) struct BlockDelta {
+        pub block: BlockInfo,
+        pub delta: FlatStateDelta,
+    }
+
+    fn flat_state_key(shard_uid: ShardUId, key: &[u8]) -> Vec<u8> {
+        let mut result = Vec::with_capacity(8 + key.len());
+        result.extend_from_slice(&shard_uid.to_bytes());
+        result.extend_from_slice(key);
+        result
+    }
+
+    fn delta_key(shard_uid: ShardUId, block_hash: &CryptoHash) -> Vec<u8> {
+        flat_state_key(shard_uid, block_hash.as_ref())
+    }
+
+    pub fn get_ref(
+        store: &Store,
+        shard_uid: ShardUId,
+        key: &[u8],
+    ) -> Result<Option<ValueRef>, StorageError> {
+        store
+            .get_ser(DBCol::FlatState, &flat_state_key(shard_uid, key))
+            .map_err(|_| StorageError::StorageInternalError)
+    }
+
+    pub fn set_ref(
+        store_update: &mut StoreUpdate,
+        shard_uid: ShardUId,
+        key: &[u8],
+        value: Option<ValueRef>,
+    ) {
+        let key = flat_state_key(shard_uid, key);
+        match value {
+            Some(value) => {
+                store_update.set_ser(DBCol::FlatState, &key, &value).expect("Borsh cannot fail")
+            }
+            None => store_update.delete(DBCol::FlatState, &key),
+        }
+    }
+
+    pub fn get_flat_head(store: &Store, shard_uid: ShardUId) -> io::Result<Option<CryptoHash>> {
+        store.get_ser(DBCol::FlatStateMisc, &shard_uid.to_bytes())
+    }
+
+    pub fn set_flat_head(store_update: &mut StoreUpdate, shard_uid: ShardUId, hash: &CryptoHash) {
+        store_update
+            .set_ser(DBCol::FlatStateMisc, &shard_uid.to_bytes(), hash)
+            .expect("Borsh cannot fail")
+    }
+
+    pub(crate) fn set_delta(
+        store_update: &mut StoreUpdate,
+        shard_uid: ShardUId,
+        block_delta: &BlockDelta,
+    ) {
+        store_update
+            .set_ser(
+                DBCol::FlatStateDeltas,
+                &delta_key(shard_uid, &block_delta.block.hash),
+                block_delta,
+            )
+            .expect("Borsh cannot fail")
+    }
+
+    pub(crate) fn remove_delta(
+        store_update: &mut StoreUpdate,
+        shard_uid: ShardUId,
+        block_hash: &CryptoHash,
+    ) {
+        store_update.delete(DBCol::FlatStateDeltas, &delta_key(shard_uid, block_hash))
+    }
+
+    pub(crate) fn get_all_deltas(
+        store: &Store,
+        shard_uid: ShardUId,
+    ) -> io::Result<Vec<BlockDelta>> {
+        let prefix = shard_uid.to_bytes();
+        store
+            .iter_prefix_ser::<BlockDelta>(DBCol::FlatStateDeltas, &prefix)
+            .map(|item| item.map(|(_key, block_delta)| block_delta))
+            .collect()
+    }
+}
+
+/// Flat storage of a single shard: the flat head and deltas of blocks on top of it.
+///
+/// The structure is shared between everyone who needs to access flat storage of the shard;
+/// clones refer to the same underlying data.
+#[derive(Clone)]
+pub struct FlatStorageState(Arc<RwLock<FlatStorageStateInner>>);
+
+struct FlatStorageStateInner {
+    store: Store,
+    shard_uid: ShardUId,
+    /// Block for which [`DBCol::FlatState`](crate::DBCol::FlatState) holds the state.
+    flat_head: CryptoHash,
+    /// Blocks on top of the flat head for which we have deltas.
+    blocks: HashMap<CryptoHash, BlockInfo>,
+    deltas: HashMap<CryptoHash, Arc<FlatStateDelta>>,
+}
+
+impl FlatStorageStateInner {
+    /// Returns deltas of blocks between the flat head (exclusive) and `target_block_hash`
+    /// (inclusive), starting from the latest one.
+    fn get_deltas_between_blocks(
+        &self,
+        target_block_hash: &CryptoHash,
+    ) -> Result<Vec<Arc<FlatStateDelta>>, FlatStorageError> {
+        let mut block_hash = *target_block_hash;
+        let mut deltas = vec![];
+        while block_hash != self.flat_head {
+            let block = self
+                .blocks
+                .get(&block_hash)
+                .ok_or(FlatStorageError::BlockNotSupported((self.flat_head, *target_block_hash)))?;
+            let delta = self
+                .deltas
+                .get(&block_hash)
+                .ok_or(FlatStorageError::BlockNotSupported((self.flat_head, *target_block_hash)))?;
+            deltas.push(Arc::clone(delta));
+            block_hash = block.prev_hash;
+        }
+        Ok(deltas)
+    }
+
+    /// Returns whether `block_hash` is a strict descendant of `ancestor`, which is at
+    /// `ancestor_height`, among the blocks added to flat storage.
+    fn is_descendant(
+        &self,
+        block_hash: &CryptoHash,
+        ancestor: &CryptoHash,
+        ancestor_height: BlockHeight,
+    ) -> bool {
+        let mut block = match self.blocks.get(block_hash) {
+            Some(block) => block,
+            None => return false,
+        };
+        while block.height > ancestor_height {
+            if block.prev_hash == *ancestor {
+                return true;
+            }
+            block = match self.blocks.get(&block.prev_hash) {
+                Some(block) => block,
+                None => return false,
+            };
+        }
+        false
+    }
+}
+
+impl FlatStorageState {
+    /// Loads flat storage for the shard from the database.
+    ///
+    /// Returns `None` if flat storage was never created for the shard.
+    pub fn load(store: Store, shard_uid: ShardUId) -> io::Result<Option<Self>> {
+        let flat_head = match store_helper::get_flat_head(&store, shard_uid)? {
+            Some(flat_head) => flat_head,
+            None => return Ok(None),
+        };
+        let mut blocks = HashMap::new();
+        let mut deltas = HashMap::new();
+        for store_helper::BlockDelta { block, delta } in
+            store_helper::get_all_deltas(&store, shard_uid)?
+        {
+            blocks.insert(block.hash, block);
+            deltas.insert(block.hash, Arc::new(delta));
+        }
+        debug!(
+            target: "store",
+            ?shard_uid,
+            %flat_head,
+            num_deltas = deltas.len(),
+            "Loaded flat storage"
+        );
+        Ok(Some(Self(Arc::new(RwLock::new(FlatStorageStateInner {
+            store,
+            shard_uid,
+            flat_head,
+            blocks,
+            deltas,
+        })))))
+    }
+
+    /// Creates flat storage for the shard from the trie at the given state root, which must be
+    /// the state after the block `block_hash`. The block becomes the flat head.
+    pub fn create(
+        store: Store,
+        shard_uid: ShardUId,
+        trie: &Trie,
+        state_root: &StateRoot,
+        block_hash: &CryptoHash,
+    ) -> Result<Self, StorageError> {
+        let mut store_update = store.store_update();
+        let mut num_items = 0;
+        for item in trie.iter(state_root)? {
+            let (key, value) = item?;
+            store_helper::set_ref(&mut store_update, shard_uid, &key, Some(ValueRef::new(&value)));
+            num_items += 1;
+        }
+        store_helper::set_flat_head(&mut store_update, shard_uid, block_hash);
+        store_update.commit().map_err(|_| StorageError::StorageInternalError)?;
+        info!(target: "store", ?shard_uid, %block_hash, num_items, "Created flat storage");
+        Ok(Self(Arc::new(RwLock::new(FlatStorageStateInner {
+            store,
+            shard_uid,
+            flat_head: *block_hash,
+            blocks: HashMap::new(),
+            deltas: HashMap::new(),
+        }))))
+    }
+
+    pub fn get_flat_head(&self) -> CryptoHash {
+        self.0.read().expect(POISONED_LOCK_ERR).flat_head
+    }
+
+    /// Returns deltas of blocks between the flat head (exclusive) and `target_block_hash`
+    /// (inclusive), starting from the latest one.
+    pub fn get_deltas_between_blocks(
+        &self,
+        target_block_hash: &CryptoHash,
+    ) -> Result<Vec<Arc<FlatStateDelta>>, FlatStorageError> {
+        self.0.read().expect(POISONED_LOCK_ERR).get_deltas_between_blocks(target_block_hash)
+    }
+
+    /// Adds the delta produced by `block` and returns the store update persisting it.
+    ///
+    /// The delta becomes visible to readers immediately, which is fine because no one reads the
+    /// state of a block before it's processed.
+    pub fn add_block(&self, block: BlockInfo, delta: FlatStateDelta) -> StoreUpdate {
+        let mut guard = self.0.write().expect(POISONED_LOCK_ERR);
+        let mut store_update = guard.store.store_update();
+        let block_delta = store_helper::BlockDelta { block, delta };
+        store_helper::set_delta(&mut store_update, guard.shard_uid, &block_delta);
+        guard.blocks.insert(block.hash, block);
+        guard.deltas.insert(block.hash, Arc::new(block_delta.delta));
+        store_update
+    }
+
+    /// Moves the flat head to `new_head`, which must be a descendant of the current flat head.
+    ///
+    /// Applies deltas of all blocks up to `new_head` to the flat state and removes deltas of
+    /// blocks which aren't descendants of the new head, including the ones on other forks,
+    /// since they can no longer be requested. Changes are committed to the database before being
+    /// reflected in memory, so concurrent readers always observe consistent data.
+    pub fn update_flat_head(&self, new_head: &CryptoHash) -> Result<(), FlatStorageError> {
+        let mut guard = self.0.write().expect(POISONED_LOCK_ERR);
+        if *new_head == guard.flat_head {
+            return Ok(());
+        }
+        let shard_uid = guard.shard_uid;
+        let new_head_height = guard
+            .blocks
+            .get(new_head)
+            .ok_or(FlatStorageError::BlockNotSupported((guard.flat_head, *new_head)))?
+            .height;
+
+        let mut merged_delta = FlatStateDelta::default();
+        for delta in guard.get_deltas_between_blocks(new_head)?.iter().rev() {
+            merged_delta.merge(delta);
+        }
+        let removed_blocks: Vec<CryptoHash> = guard
+            .blocks
+            .keys()
+            .filter(|block_hash| !guard.is_descendant(block_hash, new_head, new_head_height))
+            .copied()
+            .collect();
+
+        let mut store_update = guard.store.store_update();
+        merged_delta.apply_to_flat_state(&mut store_update, shard_uid);
+        for block_hash in removed_blocks.iter() {
+            store_helper::remove_delta(&mut store_update, shard_uid, block_hash);
+        }
+        store_helper::set_flat_head(&mut store_update, shard_uid, new_head);
+        store_update.commit()?;
+
+        for block_hash in removed_blocks.iter() {
+            guard.blocks.remove(block_hash);
+            guard.deltas.remove(block_hash);
+        }
+        guard.flat_head = *new_head;
+        debug!(target: "store", ?shard_uid, %new_head, new_head_height, "Moved flat head");
+        Ok(())
+    }
+}
+
+/// View of flat storage at a specific block, used by [`Trie`] to read values without traversing
+/// the trie.
+///
+/// The view must only be used for reads at the state root corresponding to the block.
+#[derive(Clone)]
+pub struct FlatState {
+    store: Store,
+    block_hash: CryptoHash,
+    flat_storage_state: FlatStorageState,
+}
+
+impl FlatState {
+    pub fn new(store: Store, block_hash: CryptoHash, flat_storage_state: FlatStorageState) -> Self {
+        Self { store, block_hash, flat_storage_state }
+    }
+
+    /// Returns the reference to the value for the given serialized trie key.
+    pub fn get_ref(&self, key: &[u8]) -> Result<Option<ValueRef>, FlatStorageError> {
+        // Deltas must be collected before reading the flat state, as the flat head may move
+        // in between and deltas of blocks up to the new head are already applied to it.
+        let deltas = self.flat_storage_state.get_deltas_between_blocks(&self.block_hash)?;
+        for delta in deltas.iter() {
+            if let Some(value_ref) = delta.get(key) {
+                return Ok(value_ref);
+            }
+        }
+        let shard_uid = self.flat_storage_state.0.read().expect(POISONED_LOCK_ERR).shard_uid;
+        store_helper::get_ref(&self.store, shard_uid, key)
+            .map_err(|_| FlatStorageError::StorageInternalError)
+    }
+}
+
+/// Keeps flat storage of all shards and creates [`FlatState`] views for them.
+pub struct FlatStateFactory {
+    store: Store,
+    /// Flat storage per shard; `None` if it doesn't exist for the shard.
+    flat_storage_states: Mutex<HashMap<ShardUId, Option<FlatStorageState>>>,
+}
+
+impl FlatStateFactory {
+    pub fn new(store: Store) -> Self {
+        Self { store, flat_storage_states: Default::default() }
+    }
+
+    /// Returns flat storage of the shard, loading it from the database on first access.
+    pub fn get_flat_storage_state_for_shard(
+        &self,
+        shard_uid: ShardUId,
+    ) -> Option<FlatStorageState> {
+        let mut flat_storage_states = self.flat_storage_states.lock().expect(POISONED_LOCK_ERR);
+        flat_storage_states
+            .entry(shard_uid)
+            .or_insert_with(|| {
+                FlatStorageState::load(self.store.clone(), shard_uid).unwrap_or_else(|err| {
+                    tracing::error!(
+                        target: "store",
+                        ?shard_uid,
+                        ?err,
+                        "Failed to load flat storage"
+                    );
+                    None
+                })
+            })
+            .clone()
+    }
+
+    /// Forgets flat storage of all shards, e.g. after its data was removed from the database.
+    pub fn remove_all(&self) {
+        self.flat_storage_states.lock().expect(POISONED_LOCK_ERR).clear();
+    }
+
+    /// Registers newly created flat storage of the shard.
+    pub fn add_flat_storage_state_for_shard(
+        &self,
+        shard_uid: ShardUId,
+        flat_storage_state: FlatStorageState,
+    ) {
+        let mut flat_storage_states = self.flat_storage_states.lock().expect(POISONED_LOCK_ERR);
+        flat_storage_states.insert(shard_uid, Some(flat_storage_state));
+    }
+
+    /// Creates a view of the shard's flat storage at the given block, if flat storage exists.
+    pub fn new_flat_state_for_shard(
+        &self,
+        shard_uid: ShardUId,
+        block_hash: CryptoHash,
+    ) -> Option<FlatState> {
+        self.get_flat_storage_state_for_shard(shard_uid).map(|flat_storage_state| {
+            FlatState::new(self.store.clone(), block_hash, flat_storage_state)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BlockInfo, FlatStateDelta, FlatStorageError, FlatStorageState, ValueRef};
+    use crate::test_utils::create_tries;
+    use crate::{DBCol, Trie};
+    use near_primitives::hash::{hash, CryptoHash};
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::types::{RawStateChange, RawStateChangesWithTrieKey, StateChangeCause};
+
+    fn data_key(key: &[u8]) -> TrieKey {
+        TrieKey::ContractData { account_id: "alice.near".parse().unwrap(), key: key.to_vec() }
+    }
+
+    fn delta(changes: &[(&[u8], Option<&[u8]>)]) -> FlatStateDelta {
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|(key, value)| RawStateChangesWithTrieKey {
+                trie_key: data_key(key),
+                changes: vec![RawStateChange {
+                    cause: StateChangeCause::InitialState,
+                    data: value.map(<[u8]>::to_vec),
+                }],
+            })
+            .collect();
+        FlatStateDelta::from_state_changes(&changes)
+    }
+
+    fn block(height: u64, prev_hash: CryptoHash) -> BlockInfo {
+        BlockInfo { hash: hash(&height.to_le_bytes()), height, prev_hash }
+    }
+
+    #[test]
+    fn flat_storage_follows_blocks() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = create_tries();
+        let store = tries.get_store();
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let changes = vec![
+            (data_key(b"a").to_vec(), Some(b"1".to_vec())),
+            (data_key(b"b").to_vec(), Some(b"2".to_vec())),
+        ];
+        let trie_changes = trie.update(&Trie::empty_root(), changes.into_iter()).unwrap();
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid);
+        store_update.commit().unwrap();
+
+        let genesis = CryptoHash::default();
+        let flat_storage =
+            FlatStorageState::create(store.clone(), shard_uid, &trie, &root, &genesis).unwrap();
+        let get = |block_hash: CryptoHash, key: &[u8]| {
+            super::FlatState::new(store.clone(), block_hash, flat_storage.clone())
+                .get_ref(&data_key(key).to_vec())
+        };
+        assert_eq!(get(genesis, b"a"), Ok(Some(ValueRef::new(b"1"))));
+        assert_eq!(get(genesis, b"c"), Ok(None));
+
+        // Two blocks on top of genesis and a fork of two blocks starting at height 2.
+        let block1 = block(1, genesis);
+        let block2 = block(2, block1.hash);
+        let fork2 = BlockInfo { hash: hash(b"fork"), ..block2 };
+        let fork3 = BlockInfo { hash: hash(b"fork3"), ..block(3, fork2.hash) };
+        for (block, delta) in [
+            (block1, delta(&[(b"a", Some(b"3")), (b"c", Some(b"4"))])),
+            (block2, delta(&[(b"a", None)])),
+            (fork2, delta(&[(b"b", Some(b"5"))])),
+            (fork3, delta(&[(b"b", Some(b"6"))])),
+        ] {
+            flat_storage.add_block(block, delta).commit().unwrap();
+        }
+        assert_eq!(get(block1.hash, b"a"), Ok(Some(ValueRef::new(b"3"))));
+        assert_eq!(get(block2.hash, b"a"), Ok(None));
+        assert_eq!(get(block2.hash, b"c"), Ok(Some(ValueRef::new(b"4"))));
+        assert_eq!(get(fork2.hash, b"a"), Ok(Some(ValueRef::new(b"3"))));
+        assert_eq!(get(fork2.hash, b"b"), Ok(Some(ValueRef::new(b"5"))));
+        assert_eq!(get(fork3.hash, b"b"), Ok(Some(ValueRef::new(b"6"))));
+
+        // Moving the head applies the delta and drops blocks which can't be requested anymore.
+        flat_storage.update_flat_head(&block1.hash).unwrap();
+        assert_eq!(flat_storage.get_flat_head(), block1.hash);
+        assert_eq!(get(block1.hash, b"a"), Ok(Some(ValueRef::new(b"3"))));
+        assert_eq!(get(block2.hash, b"a"), Ok(None));
+        assert_eq!(
+            get(genesis, b"a"),
+            Err(FlatStorageError::BlockNotSupported((block1.hash, genesis)))
+        );
+        flat_storage.update_flat_head(&block2.hash).unwrap();
+        assert_eq!(
+            get(fork2.hash, b"b"),
+            Err(FlatStorageError::BlockNotSupported((block2.hash, fork2.hash)))
+        );
+        assert_eq!(
+            get(fork3.hash, b"b"),
+            Err(FlatStorageError::BlockNotSupported((block2.hash, fork3.hash)))
+        );
+        assert_eq!(store.iter(DBCol::FlatStateDeltas).count(), 0);
+
+        // Reloading from the database gives the same view.
+        let flat_storage = FlatStorageState::load(store.clone(), shard_uid).unwrap().unwrap();
+        assert_eq!(flat_storage.get_flat_head(), block2.hash);
+        let flat_state = super::FlatState::new(store, block2.hash, flat_storage);
+        assert_eq!(flat_state.get_ref(&data_key(b"a").to_vec()), Ok(None));
+        assert_eq!(flat_state.get_ref(&data_key(b"b").to_vec()), Ok(Some(ValueRef::new(b"2"))));
+    }
+
+    /// Reads served by flat storage touch, and hence charge gas for, the same trie nodes as reads
+    /// without it.
+    #[test]
+    fn flat_storage_reads_count_trie_nodes() {
+        let shard_uid = ShardUId::single_shard();
+        let tries = create_tries();
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let changes = [b"a".as_slice(), b"ab", b"b", b"bcd"]
+            .into_iter()
+            .map(|key| (data_key(key).to_vec(), Some(key.to_vec())));
+        let trie_changes = trie.update(&Trie::empty_root(), changes).unwrap();
+        let (store_update, root) = tries.apply_all(&trie_changes, shard_uid);
+        store_update.commit().unwrap();
+
+        let genesis = CryptoHash::default();
+        tries.create_flat_storage_state_for_shard(shard_uid, &root, &genesis).unwrap();
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let flat_trie = tries.get_trie_with_block_hash_for_shard(shard_uid, genesis);
+        assert!(flat_trie.flat_state.is_some());
+        for key in [b"a".as_slice(), b"ab", b"abc", b"bcd", b"c"] {
+            let key = data_key(key).to_vec();
+            assert_eq!(flat_trie.get(&root, &key), trie.get(&root, &key));
+            assert_eq!(flat_trie.get_trie_nodes_count(), trie.get_trie_nodes_count());
+        }
+    }
+}
@@ -32,13 +32,15 @@ pub use crate::trie::iterator::TrieIterator;
 pub use crate::trie::update::{TrieUpdate, TrieUpdateIterator, TrieUpdateValuePtr};
 pub use crate::trie::{
     estimator, split_state, ApplyStatePartResult, KeyForStateChanges, PartialStorage, ShardTries,
-    Trie, TrieCache, TrieCacheFactory, TrieCachingStorage, TrieChanges, TrieStorage,
-    WrappedTrieChanges,
+    Trie, TrieCache, TrieCacheConfig, TrieCacheFactory, TrieCachingStorage, TrieChanges,
+    TriePrefetchConfig, TriePrefetcher, TrieStorage, WrappedTrieChanges,
 };
 
+pub mod checkpoint;
 mod columns;
 mod config;
 pub mod db;
+pub mod flat_state;
 mod metrics;
 pub mod migrations;
 pub mod test_utils;
@@ -49,6 +51,10 @@ pub use crate::config::{StoreConfig, StoreOpener};
 #[derive(Clone)]
 pub struct Store {
     storage: Arc<dyn Database>,
+    /// Cold database of an archival node, if configured.  Reads through
+    /// `storage` already fall through to it; this is used to write data to it
+    /// directly.
+    cold_storage: Option<Arc<dyn Database>>,
 }
 
 impl Store {
@@ -73,7 +79,22 @@ impl Store {
     }
 
     pub(crate) fn new(storage: Arc<dyn Database>) -> Store {
-        Store { storage }
+        Store { storage, cold_storage: None }
+    }
+
+    /// Creates a store which reads from the hot database falling back to the
+    /// cold one, and writes to the hot database.
+    pub(crate) fn new_with_cold(hot: Arc<dyn Database>, cold: Arc<dyn Database>) -> Store {
+        let storage = Arc::new(db::splitdb::SplitDB::new(hot, Arc::clone(&cold)));
+        Store { storage, cold_storage: Some(cold) }
+    }
+
+    /// Returns store accessing only the cold database, if there is one.
+    ///
+    /// Data which is garbage collected from the hot database is expected to be
+    /// copied to the cold store first.
+    pub fn cold_store(&self) -> Option<Store> {
+        self.cold_storage.as_ref().map(|cold| Store::new(Arc::clone(cold)))
     }
 
     pub fn into_inner(self) -> Arc<dyn Database> {
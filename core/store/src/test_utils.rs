@@ -19,6 +19,11 @@ pub fn create_test_store() -> Store {
     Store::new(db)
 }
 
+/// Creates an in-memory store split into a hot and a cold database.
+pub fn create_test_split_store() -> Store {
+    Store::new_with_cold(Arc::new(TestDB::new()), Arc::new(TestDB::new()))
+}
+
 /// Creates a Trie using an in-memory database.
 pub fn create_tries() -> ShardTries {
     create_tries_complex(0, 1)
@@ -13,20 +13,25 @@ use near_primitives::hash::{hash, CryptoHash};
 pub use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::{StateRoot, StateRootNode};
 
+use crate::flat_state::{FlatState, FlatStorageError};
 use crate::trie::insert_delete::NodesStorage;
 use crate::trie::iterator::TrieIterator;
 use crate::trie::nibble_slice::NibbleSlice;
+pub use crate::trie::prefetching::{TriePrefetchConfig, TriePrefetcher};
 pub use crate::trie::shard_tries::{
     KeyForStateChanges, ShardTries, TrieCacheFactory, WrappedTrieChanges,
 };
-pub use crate::trie::trie_storage::{TrieCache, TrieCachingStorage, TrieStorage};
-use crate::trie::trie_storage::{TrieMemoryPartialStorage, TrieRecordingStorage};
+pub use crate::trie::trie_storage::{TrieCache, TrieCacheConfig, TrieCachingStorage, TrieStorage};
+use crate::trie::trie_storage::{
+    TrieMemoryPartialStorage, TrieOverlayStorage, TrieRecordingStorage,
+};
 use crate::StorageError;
 pub use near_primitives::types::TrieNodesCount;
 
 mod insert_delete;
 pub mod iterator;
 mod nibble_slice;
+mod prefetching;
 mod shard_tries;
 pub mod split_state;
 mod state_parts;
@@ -36,7 +41,7 @@ pub mod update;
 #[cfg(test)]
 mod trie_tests;
 
-const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
+pub(crate) const POISONED_LOCK_ERR: &str = "The lock was poisoned.";
 
 /// For fraud proofs
 #[derive(Debug, Clone)]
@@ -408,6 +413,9 @@ impl RawTrieNodeWithSize {
 
 pub struct Trie {
     pub(crate) storage: Box<dyn TrieStorage>,
+    /// When present, values are looked up in flat storage. The trie is still traversed so that
+    /// reads charge gas for the same trie nodes.
+    pub(crate) flat_state: Option<FlatState>,
 }
 
 /// Stores reference count change for some key-value pair in DB.
@@ -470,8 +478,8 @@ pub struct ApplyStatePartResult {
 }
 
 impl Trie {
-    pub fn new(store: Box<dyn TrieStorage>) -> Self {
-        Trie { storage: store }
+    pub fn new(store: Box<dyn TrieStorage>, flat_state: Option<FlatState>) -> Self {
+        Trie { storage: store, flat_state }
     }
 
     pub fn recording_reads(&self) -> Self {
@@ -482,7 +490,7 @@ impl Trie {
             shard_uid: storage.shard_uid,
             recorded: RefCell::new(Default::default()),
         };
-        Trie { storage: Box::new(storage) }
+        Trie { storage: Box::new(storage), flat_state: None }
     }
 
     pub fn empty_root() -> StateRoot {
@@ -505,6 +513,24 @@ impl Trie {
                 recorded_storage,
                 visited_nodes: Default::default(),
             }),
+            flat_state: None,
+        }
+    }
+
+    /// Returns a trie which additionally serves the nodes inserted by `changes`, so that the state
+    /// at `changes.new_root` can be read before the changes are committed.  Flat storage isn't
+    /// used by the returned trie since it only reflects the committed state.
+    pub fn with_uncommitted_changes(self, changes: &TrieChanges) -> Self {
+        let nodes = changes
+            .insertions
+            .iter()
+            .map(|change| {
+                (change.trie_node_or_value_hash, change.trie_node_or_value.as_slice().into())
+            })
+            .collect();
+        Trie {
+            storage: Box::new(TrieOverlayStorage { base: self.storage, nodes }),
+            flat_state: None,
         }
     }
 
@@ -613,7 +639,7 @@ impl Trie {
         if *root == Trie::empty_root() {
             return Ok(StateRootNode::empty());
         }
-        let data = self.storage.retrieve_raw_bytes(root)?;
+        let data = self.storage.retrieve_raw_node_at_depth(root, 0)?;
         match RawTrieNodeWithSize::decode(&data) {
             Ok(value) => {
                 let memory_usage = TrieNodeWithSize::from_raw(value).memory_usage;
@@ -632,12 +658,13 @@ impl Trie {
         mut key: NibbleSlice<'_>,
     ) -> Result<Option<(u32, CryptoHash)>, StorageError> {
         let mut hash = *root;
+        let mut depth = 0;
 
         loop {
             if hash == Trie::empty_root() {
                 return Ok(None);
             }
-            let bytes = self.storage.retrieve_raw_bytes(&hash)?;
+            let bytes = self.storage.retrieve_raw_node_at_depth(&hash, depth)?;
             let node = RawTrieNodeWithSize::decode(&bytes).map_err(|_| {
                 StorageError::StorageInconsistentState("RawTrieNode decode failed".to_string())
             })?;
@@ -678,6 +705,7 @@ impl Trie {
                     }
                 }
             };
+            depth += 1;
         }
     }
 
@@ -686,8 +714,24 @@ impl Trie {
         root: &CryptoHash,
         key: &[u8],
     ) -> Result<Option<(u32, CryptoHash)>, StorageError> {
-        let key = NibbleSlice::new(key);
-        self.lookup(root, key)
+        let key_nibbles = NibbleSlice::new(key);
+        if let Some(flat_state) = &self.flat_state {
+            match flat_state.get_ref(key) {
+                Ok(value_ref) => {
+                    // Nodes which state synced have no flat storage, so walk the trie anyway
+                    // to count the same touched nodes for gas as they do.
+                    let trie_value_ref = self.lookup(root, key_nibbles)?;
+                    let value_ref = value_ref.map(|value_ref| (value_ref.length, value_ref.hash));
+                    debug_assert_eq!(value_ref, trie_value_ref, "flat storage diverged from trie");
+                    return Ok(value_ref);
+                }
+                // The block may have been left behind by the flat head if it belongs to a fork
+                // which can no longer become final; fall back to the trie in that case.
+                Err(FlatStorageError::BlockNotSupported(_)) => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        self.lookup(root, key_nibbles)
     }
 
     pub fn get(&self, root: &CryptoHash, key: &[u8]) -> Result<Option<Vec<u8>>, StorageError> {
@@ -1178,6 +1222,35 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_trie_with_uncommitted_changes() {
+        let store = create_test_store();
+        let tries = ShardTries::test(store, 1);
+        let empty_root = Trie::empty_root();
+        let changes = vec![
+            (b"doge".to_vec(), Some(b"coin".to_vec())),
+            (b"docu".to_vec(), Some(b"value".to_vec())),
+        ];
+        let root = test_populate_trie(&tries, &empty_root, ShardUId::single_shard(), changes);
+
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+        let changes1 = trie
+            .update(&root, vec![(b"dodo".to_vec(), Some(b"asdf".to_vec()))].into_iter())
+            .unwrap();
+        let trie = trie.with_uncommitted_changes(&changes1);
+        let changes2 =
+            trie.update(&changes1.new_root, vec![(b"doge".to_vec(), None)].into_iter()).unwrap();
+        let trie = trie.with_uncommitted_changes(&changes2);
+
+        assert_eq!(trie.get(&changes2.new_root, b"dodo"), Ok(Some(b"asdf".to_vec())));
+        assert_eq!(trie.get(&changes2.new_root, b"docu"), Ok(Some(b"value".to_vec())));
+        assert_eq!(trie.get(&changes2.new_root, b"doge"), Ok(None));
+        assert_eq!(trie.get(&root, b"doge"), Ok(Some(b"coin".to_vec())));
+        // Nothing was written to the store.
+        let trie = tries.get_trie_for_shard(ShardUId::single_shard());
+        assert!(trie.get(&changes2.new_root, b"dodo").is_err());
+    }
+
     #[test]
     fn test_dump_load_trie() {
         let store = create_test_store();
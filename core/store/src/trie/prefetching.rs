@@ -0,0 +1,196 @@
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use crossbeam_channel::{Receiver, Sender};
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::trie_key::TrieKey;
+use near_primitives::types::StateRoot;
+
+use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
+use crate::{Store, Trie};
+
+/// Configuration of prefetching of the state needed to apply chunks.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TriePrefetchConfig {
+    /// Whether to prefetch accounts, access keys, contract code and hot
+    /// contract data used by transactions and receipts of a chunk before
+    /// applying them.
+    pub enable: bool,
+    /// Number of threads prefetching the state of a shard.
+    pub num_threads: usize,
+    /// Keys of contract data prefetched for every receiver of a function
+    /// call.  `STATE` is where contracts built with near-sdk keep their state.
+    pub hot_data_keys: Vec<String>,
+}
+
+impl Default for TriePrefetchConfig {
+    fn default() -> Self {
+        Self { enable: false, num_threads: 4, hot_data_keys: vec!["STATE".to_string()] }
+    }
+}
+
+/// Threads looking up keys queued by prefetchers.  The pool is started once
+/// per `ShardTries` and shared by all its prefetchers, so applying a chunk
+/// doesn’t spawn any threads.  The threads exit once the pool is dropped.
+pub(crate) struct TriePrefetchPool {
+    sender: Sender<(Arc<PrefetchTarget>, Vec<u8>)>,
+    config: Arc<TriePrefetchConfig>,
+}
+
+impl TriePrefetchPool {
+    pub(crate) fn new(config: Arc<TriePrefetchConfig>) -> Self {
+        let (sender, receiver) = crossbeam_channel::unbounded();
+        for _ in 0..config.num_threads {
+            let receiver = receiver.clone();
+            let spawned = std::thread::Builder::new()
+                .name("trie_prefetch".to_string())
+                .spawn(move || prefetch(receiver));
+            if let Err(err) = spawned {
+                tracing::warn!(target: "store", ?err, "Failed to spawn trie prefetching thread");
+                break;
+            }
+        }
+        Self { sender, config }
+    }
+}
+
+/// Trie a prefetcher looks keys up in.
+struct PrefetchTarget {
+    store: Store,
+    shard_cache: TrieCache,
+    shard_uid: ShardUId,
+    state_root: StateRoot,
+    /// Set once the prefetcher is dropped; keys still queued are skipped.
+    stopped: AtomicBool,
+}
+
+/// Looks up keys of a shard’s trie in background threads so that the nodes
+/// on their paths are in the shard cache by the time they’re read.
+///
+/// Prefetching only fills the shard cache.  It doesn’t touch the chunk cache
+/// nor the counters of the trie the chunk is applied with, so it doesn’t
+/// change gas costs nor any other result of applying a chunk.
+///
+/// Dropping the prefetcher discards keys not yet looked up.
+pub struct TriePrefetcher {
+    pool: Arc<TriePrefetchPool>,
+    target: Arc<PrefetchTarget>,
+    /// Keys queued so far, to look up each key only once.
+    queued: RefCell<HashSet<Vec<u8>>>,
+}
+
+impl TriePrefetcher {
+    /// Creates a prefetcher for the trie at given state root if the trie is
+    /// backed by the shard cache and prefetching is enabled.
+    pub fn new_if_enabled(trie: &Trie, state_root: StateRoot) -> Option<Self> {
+        let storage = trie.storage.as_caching_storage()?;
+        let pool = storage.prefetch_pool.clone()?;
+        let target = Arc::new(PrefetchTarget {
+            store: storage.store.clone(),
+            shard_cache: storage.shard_cache.clone(),
+            shard_uid: storage.shard_uid,
+            state_root,
+            stopped: AtomicBool::new(false),
+        });
+        Some(Self { pool, target, queued: Default::default() })
+    }
+
+    /// Contract data keys to prefetch for every receiver of a function call.
+    pub fn hot_data_keys(&self) -> impl Iterator<Item = &[u8]> {
+        self.pool.config.hot_data_keys.iter().map(|key| key.as_bytes())
+    }
+
+    /// Queues the key for prefetching unless it’s been queued already.
+    pub fn prefetch(&self, key: &TrieKey) {
+        let key = key.to_vec();
+        if self.queued.borrow_mut().insert(key.clone()) {
+            // Sending fails only if all the threads failed to spawn.
+            let _ = self.pool.sender.send((self.target.clone(), key));
+        }
+    }
+}
+
+impl Drop for TriePrefetcher {
+    fn drop(&mut self) {
+        self.target.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+fn prefetch(keys: Receiver<(Arc<PrefetchTarget>, Vec<u8>)>) {
+    while let Ok((target, key)) = keys.recv() {
+        if target.stopped.load(Ordering::Relaxed) {
+            continue;
+        }
+        let storage = TrieCachingStorage::new(
+            target.store.clone(),
+            target.shard_cache.clone(),
+            target.shard_uid,
+        );
+        // Failures will be hit and reported when the runtime reads the key.
+        let _ = Trie::new(Box::new(storage), None).get(&target.state_root, &key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::ShardUId;
+
+    use super::*;
+    use crate::test_utils::{create_test_store, test_populate_trie};
+    use crate::{ShardTries, TrieCacheFactory};
+
+    #[test]
+    fn test_prefetch() {
+        let store = create_test_store();
+        let shard_uid = ShardUId::single_shard();
+        let key = TrieKey::Account { account_id: "alice.near".parse().unwrap() };
+        let value = vec![42; 100];
+        let state_root = test_populate_trie(
+            &ShardTries::test(store.clone(), 1),
+            &CryptoHash::default(),
+            shard_uid,
+            vec![(key.to_vec(), Some(value.clone()))],
+        );
+
+        // Prefetching is disabled by default.
+        let tries = ShardTries::test(store.clone(), 1);
+        let trie = tries.get_trie_for_shard(shard_uid);
+        assert!(TriePrefetcher::new_if_enabled(&trie, state_root).is_none());
+
+        let config = TriePrefetchConfig { enable: true, ..Default::default() };
+        let tries = ShardTries::new(
+            store,
+            TrieCacheFactory::new(Default::default(), 0, 1).with_prefetch_config(&config),
+        );
+        // View tries don’t prefetch.
+        let trie = tries.get_view_trie_for_shard(shard_uid);
+        assert!(TriePrefetcher::new_if_enabled(&trie, state_root).is_none());
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let prefetcher = TriePrefetcher::new_if_enabled(&trie, state_root).unwrap();
+        prefetcher.prefetch(&key);
+        let cache = trie.storage.as_caching_storage().unwrap().shard_cache.clone();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        // The leaf node and the value.
+        while cache.len() < 2 {
+            assert!(Instant::now() < deadline, "key hasn’t been prefetched");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // Reads of prefetched nodes are counted the same way as any other.
+        assert_eq!(trie.get(&state_root, &key.to_vec()), Ok(Some(value)));
+        assert_eq!(trie.get_trie_nodes_count().db_reads, 2);
+        drop(prefetcher);
+
+        // Reads from flat storage walk the trie as well, so they're prefetched too.
+        let block_hash = CryptoHash::hash_bytes(b"block");
+        tries.create_flat_storage_state_for_shard(shard_uid, &state_root, &block_hash).unwrap();
+        let trie = tries.get_trie_with_block_hash_for_shard(shard_uid, block_hash);
+        assert!(TriePrefetcher::new_if_enabled(&trie, state_root).is_some());
+    }
+}
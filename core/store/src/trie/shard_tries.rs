@@ -12,43 +12,55 @@ use near_primitives::types::{
     NumShards, RawStateChange, RawStateChangesWithTrieKey, StateChangeCause, StateRoot,
 };
 
-use crate::trie::trie_storage::{TrieCache, TrieCachingStorage};
+use crate::flat_state::{FlatStateFactory, FlatStorageState};
+use crate::trie::prefetching::{TriePrefetchConfig, TriePrefetchPool};
+use crate::trie::trie_storage::{TrieCache, TrieCacheConfig, TrieCachingStorage};
 use crate::trie::{TrieRefcountChange, POISONED_LOCK_ERR};
 use crate::{DBCol, DBOp, DBTransaction};
-use crate::{Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
+use crate::{StorageError, Store, StoreUpdate, Trie, TrieChanges, TrieUpdate};
 
 /// Responsible for creation of trie caches, stores necessary configuration for it.
 #[derive(Default)]
 pub struct TrieCacheFactory {
-    capacities: HashMap<ShardUId, usize>,
+    /// Limits of the caches, which may be changed at runtime.
+    config: RwLock<TrieCacheConfig>,
     shard_version: ShardVersion,
     num_shards: NumShards,
+    /// Configuration of prefetching into the caches, if it's enabled.
+    prefetch_config: Option<Arc<TriePrefetchConfig>>,
 }
 
 impl TrieCacheFactory {
     pub fn new(
-        capacities: HashMap<ShardUId, usize>,
+        config: TrieCacheConfig,
         shard_version: ShardVersion,
         num_shards: NumShards,
     ) -> Self {
-        Self { capacities, shard_version, num_shards }
+        Self { config: RwLock::new(config), shard_version, num_shards, prefetch_config: None }
+    }
+
+    /// Enables prefetching into the caches used by tries for applying chunks
+    /// if the configuration enables it.
+    pub fn with_prefetch_config(mut self, config: &TriePrefetchConfig) -> Self {
+        self.prefetch_config = config.enable.then(|| Arc::new(config.clone()));
+        self
     }
 
     /// Create new cache for the given shard uid.
-    pub fn create_cache(&self, shard_uid: &ShardUId) -> TrieCache {
-        match self.capacities.get(shard_uid) {
-            Some(capacity) => TrieCache::with_capacity(*capacity),
-            None => TrieCache::new(),
-        }
+    pub fn create_cache(&self, shard_uid: &ShardUId, is_view: bool) -> TrieCache {
+        TrieCache::new(&self.config.read().expect(POISONED_LOCK_ERR), *shard_uid, is_view)
     }
 
     /// Create caches on the initialization of storage structures.
-    pub fn create_initial_caches(&self) -> HashMap<ShardUId, TrieCache> {
+    pub fn create_initial_caches(&self, is_view: bool) -> HashMap<ShardUId, TrieCache> {
         assert_ne!(self.num_shards, 0);
         let shards: Vec<_> = (0..self.num_shards)
             .map(|shard_id| ShardUId { version: self.shard_version, shard_id: shard_id as u32 })
             .collect();
-        shards.iter().map(|&shard_uid| (shard_uid, self.create_cache(&shard_uid))).collect()
+        shards
+            .iter()
+            .map(|&shard_uid| (shard_uid, self.create_cache(&shard_uid, is_view)))
+            .collect()
     }
 }
 
@@ -59,6 +71,9 @@ struct ShardTriesInner {
     caches: RwLock<HashMap<ShardUId, TrieCache>>,
     /// Cache for readers.
     view_caches: RwLock<HashMap<ShardUId, TrieCache>>,
+    flat_state_factory: FlatStateFactory,
+    /// Threads prefetching into `caches`, started if prefetching is enabled.
+    prefetch_pool: Option<Arc<TriePrefetchPool>>,
 }
 
 #[derive(Clone)]
@@ -66,13 +81,20 @@ pub struct ShardTries(Arc<ShardTriesInner>);
 
 impl ShardTries {
     pub fn new(store: Store, trie_cache_factory: TrieCacheFactory) -> Self {
-        let caches = trie_cache_factory.create_initial_caches();
-        let view_caches = trie_cache_factory.create_initial_caches();
+        let caches = trie_cache_factory.create_initial_caches(false);
+        let view_caches = trie_cache_factory.create_initial_caches(true);
+        let flat_state_factory = FlatStateFactory::new(store.clone());
+        let prefetch_pool = trie_cache_factory
+            .prefetch_config
+            .clone()
+            .map(|config| Arc::new(TriePrefetchPool::new(config)));
         ShardTries(Arc::new(ShardTriesInner {
             store,
             trie_cache_factory,
             caches: RwLock::new(caches),
             view_caches: RwLock::new(view_caches),
+            flat_state_factory,
+            prefetch_pool,
         }))
     }
 
@@ -80,6 +102,17 @@ impl ShardTries {
         Self::new(store, TrieCacheFactory::new(Default::default(), 0, num_shards))
     }
 
+    /// Applies new limits to the existing trie caches and to the ones created
+    /// later.
+    pub fn update_cache_config(&self, config: &TrieCacheConfig) {
+        *self.0.trie_cache_factory.config.write().expect(POISONED_LOCK_ERR) = config.clone();
+        for caches in [&self.0.caches, &self.0.view_caches] {
+            for cache in caches.read().expect(POISONED_LOCK_ERR).values() {
+                cache.update_config(config);
+            }
+        }
+    }
+
     pub fn is_same(&self, other: &Self) -> bool {
         Arc::ptr_eq(&self.0, &other.0)
     }
@@ -92,25 +125,71 @@ impl ShardTries {
         TrieUpdate::new(Rc::new(self.get_view_trie_for_shard(shard_uid)), state_root)
     }
 
-    fn get_trie_for_shard_internal(&self, shard_uid: ShardUId, is_view: bool) -> Trie {
+    fn get_trie_for_shard_internal(
+        &self,
+        shard_uid: ShardUId,
+        is_view: bool,
+        block_hash: Option<CryptoHash>,
+    ) -> Trie {
         let caches_to_use = if is_view { &self.0.view_caches } else { &self.0.caches };
         let cache = {
             let mut caches = caches_to_use.write().expect(POISONED_LOCK_ERR);
             caches
                 .entry(shard_uid)
-                .or_insert_with(|| self.0.trie_cache_factory.create_cache(&shard_uid))
+                .or_insert_with(|| self.0.trie_cache_factory.create_cache(&shard_uid, is_view))
                 .clone()
         };
-        let store = Box::new(TrieCachingStorage::new(self.0.store.clone(), cache, shard_uid));
-        Trie::new(store)
+        let mut store = Box::new(TrieCachingStorage::new(self.0.store.clone(), cache, shard_uid));
+        if !is_view {
+            store.prefetch_pool = self.0.prefetch_pool.clone();
+        }
+        let flat_state = block_hash.and_then(|block_hash| {
+            self.0.flat_state_factory.new_flat_state_for_shard(shard_uid, block_hash)
+        });
+        Trie::new(store, flat_state)
     }
 
     pub fn get_trie_for_shard(&self, shard_uid: ShardUId) -> Trie {
-        self.get_trie_for_shard_internal(shard_uid, false)
+        self.get_trie_for_shard_internal(shard_uid, false, None)
+    }
+
+    /// Returns a trie which serves reads from flat storage at the given block, if flat storage
+    /// exists for the shard. The trie must only be read at the state root of that block.
+    pub fn get_trie_with_block_hash_for_shard(
+        &self,
+        shard_uid: ShardUId,
+        block_hash: CryptoHash,
+    ) -> Trie {
+        self.get_trie_for_shard_internal(shard_uid, false, Some(block_hash))
     }
 
     pub fn get_view_trie_for_shard(&self, shard_uid: ShardUId) -> Trie {
-        self.get_trie_for_shard_internal(shard_uid, true)
+        self.get_trie_for_shard_internal(shard_uid, true, None)
+    }
+
+    /// Returns flat storage of the shard if it was created.
+    pub fn get_flat_storage_state_for_shard(
+        &self,
+        shard_uid: ShardUId,
+    ) -> Option<FlatStorageState> {
+        self.0.flat_state_factory.get_flat_storage_state_for_shard(shard_uid)
+    }
+
+    /// Creates flat storage of the shard from the trie at `state_root`, which must be the state
+    /// after block `block_hash`.
+    pub fn create_flat_storage_state_for_shard(
+        &self,
+        shard_uid: ShardUId,
+        state_root: &StateRoot,
+        block_hash: &CryptoHash,
+    ) -> Result<FlatStorageState, StorageError> {
+        let trie = self.get_view_trie_for_shard(shard_uid);
+        let flat_storage_state =
+            FlatStorageState::create(self.get_store(), shard_uid, &trie, state_root, block_hash)?;
+        self.0
+            .flat_state_factory
+            .add_flat_storage_state_for_shard(shard_uid, flat_storage_state.clone());
+        Ok(flat_storage_state)
     }
 
     pub fn get_store(&self) -> Store {
@@ -135,13 +214,17 @@ impl ShardTries {
                         cache.clear();
                     }
                 }
+                DBOp::DeleteAll { col } if *col == DBCol::FlatStateMisc => {
+                    // Flat storage is removed together with the state in reset_data_pre_state_sync
+                    self.0.flat_state_factory.remove_all();
+                }
                 _ => {}
             }
         }
         for (shard_uid, ops) in shards {
             let cache = caches
                 .entry(shard_uid)
-                .or_insert_with(|| self.0.trie_cache_factory.create_cache(&shard_uid))
+                .or_insert_with(|| self.0.trie_cache_factory.create_cache(&shard_uid, false))
                 .clone();
             cache.update_cache(ops);
         }
@@ -222,6 +305,26 @@ impl ShardTries {
         self.apply_deletions_inner(&trie_changes.deletions, shard_uid, store_update)
     }
 
+    /// Copies values removed by `trie_changes` to `store_update`, which is
+    /// expected to write to the cold database, so that the old state stays
+    /// readable after it's garbage collected from the hot database.
+    pub fn copy_deletions_to_cold(
+        &self,
+        trie_changes: &TrieChanges,
+        shard_uid: ShardUId,
+        cold_store_update: &mut StoreUpdate,
+    ) {
+        for TrieRefcountChange { trie_node_or_value_hash, trie_node_or_value, .. } in
+            trie_changes.deletions.iter()
+        {
+            let key = TrieCachingStorage::get_key_from_shard_uid_and_hash(
+                shard_uid,
+                trie_node_or_value_hash,
+            );
+            cold_store_update.increment_refcount(DBCol::State, key.as_ref(), trie_node_or_value);
+        }
+    }
+
     pub fn revert_insertions(
         &self,
         trie_changes: &TrieChanges,
@@ -12,7 +12,8 @@ use near_primitives::state_part::PartId;
 use near_primitives::trie_key::trie_key_parsers::parse_account_id_from_raw_key;
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::{
-    ConsolidatedStateChange, StateChangeCause, StateChangesForSplitStates, StateRoot,
+    ConsolidatedStateChange, RawStateChangesWithTrieKey, StateChangeCause,
+    StateChangesForSplitStates, StateRoot,
 };
 use std::collections::HashMap;
 
@@ -42,7 +43,7 @@ impl Trie {
 
 impl ShardTries {
     /// applies `changes` to split states
-    /// and returns the generated TrieChanges for all split states
+    /// and returns the generated TrieChanges and state changes for all split states
     /// Note that this function is different from the function `add_values_to_split_states`
     /// This function is used for applying updates to split states when processing blocks
     /// `add_values_to_split_states` are used to generate the initial states for shards split
@@ -52,7 +53,8 @@ impl ShardTries {
         state_roots: &HashMap<ShardUId, StateRoot>,
         changes: StateChangesForSplitStates,
         account_id_to_shard_id: &dyn Fn(&AccountId) -> ShardUId,
-    ) -> Result<HashMap<ShardUId, TrieChanges>, StorageError> {
+    ) -> Result<HashMap<ShardUId, (TrieChanges, Vec<RawStateChangesWithTrieKey>)>, StorageError>
+    {
         let mut trie_updates: HashMap<_, _> = self.get_trie_updates(state_roots);
         let mut insert_receipts = Vec::new();
         for ConsolidatedStateChange { trie_key, value } in changes.changes {
@@ -107,8 +109,7 @@ impl ShardTries {
 
         let mut trie_changes_map = HashMap::new();
         for (shard_uid, update) in trie_updates {
-            let (trie_changes, _) = update.finalize()?;
-            trie_changes_map.insert(shard_uid, trie_changes);
+            trie_changes_map.insert(shard_uid, update.finalize()?);
         }
         Ok(trie_changes_map)
     }
@@ -789,12 +790,22 @@ mod tests {
                 .unwrap();
             split_state_roots = trie_changes
                 .iter()
-                .map(|(shard_uid, trie_changes)| {
+                .map(|(shard_uid, (trie_changes, _))| {
                     let (state_update, state_root) = tries.apply_all(trie_changes, *shard_uid);
                     state_update.commit().unwrap();
                     (*shard_uid, state_root)
                 })
                 .collect();
+            // the returned state changes end with the values now stored in the split states
+            for (shard_uid, (_, state_changes)) in trie_changes.iter() {
+                let trie = tries.get_trie_for_shard(*shard_uid);
+                for change in state_changes {
+                    assert_eq!(
+                        trie.get(&split_state_roots[shard_uid], &change.trie_key.to_vec()).unwrap(),
+                        change.changes.last().unwrap().data
+                    );
+                }
+            }
 
             compare_state_and_split_states(
                 &tries,
@@ -5,33 +5,271 @@ use std::sync::{Arc, Mutex};
 use near_primitives::hash::CryptoHash;
 
 use crate::db::refcount::decode_value_with_rc;
+use crate::metrics;
+use crate::trie::prefetching::TriePrefetchPool;
 use crate::trie::POISONED_LOCK_ERR;
 use crate::{DBCol, StorageError, Store};
 use lru::LruCache;
+use near_metrics::{IntCounter, IntGauge};
 use near_primitives::shard_layout::ShardUId;
 use near_primitives::types::{TrieCacheMode, TrieNodesCount};
 use std::cell::{Cell, RefCell};
 use std::io::ErrorKind;
 
-/// Wrapper over LruCache which doesn't hold too large elements.
+/// Limits of the shard caches of trie nodes and values.
+#[derive(Clone, Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+#[serde(default)]
+pub struct TrieCacheConfig {
+    /// Limit on the total size of the nodes and values held by the cache of a
+    /// shard, apart from the pinned tier.
+    pub default_max_bytes: bytesize::ByteSize,
+    /// Overrides of `default_max_bytes` for particular shards.
+    pub per_shard_max_bytes: Vec<(ShardUId, bytesize::ByteSize)>,
+    /// Nodes with at most this many nodes above them on the path from the
+    /// root are kept in a separate, pinned tier of the cache, so that reads of
+    /// deeper nodes don’t evict them.
+    pub pinned_depth: usize,
+    /// Limit on the total size of the nodes in the pinned tier of the cache of
+    /// a shard.
+    pub pinned_max_bytes: bytesize::ByteSize,
+}
+
+impl TrieCacheConfig {
+    /// Returns the size limit of the cache of given shard, apart from the
+    /// pinned tier.
+    pub fn max_bytes(&self, shard_uid: ShardUId) -> u64 {
+        self.per_shard_max_bytes
+            .iter()
+            .find(|(shard, _)| *shard == shard_uid)
+            .map_or(self.default_max_bytes, |(_, max_bytes)| *max_bytes)
+            .as_u64()
+    }
+
+    /// Adds size limits for the shards given limits on the number of cached
+    /// entries, as set with the deprecated `store.trie_cache_capacities`
+    /// option.  Every entry is counted as the largest value which is cached,
+    /// so that the old default of 50000 entries matches the default size.
+    /// Shards which already have a size limit keep it.
+    pub fn add_capacities(&mut self, capacities: &[(ShardUId, usize)]) {
+        for (shard_uid, capacity) in capacities {
+            if self.per_shard_max_bytes.iter().any(|(shard, _)| shard == shard_uid) {
+                continue;
+            }
+            let max_bytes = capacity.saturating_mul(TRIE_LIMIT_CACHED_VALUE_SIZE);
+            self.per_shard_max_bytes.push((*shard_uid, bytesize::ByteSize::b(max_bytes as u64)));
+        }
+    }
+}
+
+impl Default for TrieCacheConfig {
+    fn default() -> Self {
+        Self {
+            default_max_bytes: TRIE_DEFAULT_SHARD_CACHE_MAX_BYTES,
+            per_shard_max_bytes: vec![],
+            pinned_depth: 3,
+            pinned_max_bytes: TRIE_DEFAULT_SHARD_CACHE_PINNED_MAX_BYTES,
+        }
+    }
+}
+
+/// LRU cache of nodes and values bounded by their total size.
+struct BoundedLruCache {
+    cache: LruCache<CryptoHash, Arc<[u8]>>,
+    total_size: u64,
+    max_bytes: u64,
+}
+
+impl BoundedLruCache {
+    fn new(max_bytes: u64) -> Self {
+        Self { cache: LruCache::unbounded(), total_size: 0, max_bytes }
+    }
+
+    fn entry_size(value: &[u8]) -> u64 {
+        value.len() as u64 + TRIE_CACHE_ENTRY_OVERHEAD
+    }
+
+    fn contains(&self, key: &CryptoHash) -> bool {
+        self.cache.contains(key)
+    }
+
+    fn get(&mut self, key: &CryptoHash) -> Option<Arc<[u8]>> {
+        self.cache.get(key).cloned()
+    }
+
+    /// Inserts the value unless it alone exceeds the limit.  Returns the
+    /// number of entries evicted to make room for it.
+    fn put(&mut self, key: CryptoHash, value: Arc<[u8]>) -> u64 {
+        self.pop(&key);
+        let size = Self::entry_size(&value);
+        if size > self.max_bytes {
+            return 0;
+        }
+        self.total_size += size;
+        self.cache.put(key, value);
+        self.shrink()
+    }
+
+    fn pop(&mut self, key: &CryptoHash) -> Option<Arc<[u8]>> {
+        let value = self.cache.pop(key)?;
+        self.total_size -= Self::entry_size(&value);
+        Some(value)
+    }
+
+    /// Changes the limit.  Returns the number of entries evicted to fit into
+    /// it.
+    fn set_max_bytes(&mut self, max_bytes: u64) -> u64 {
+        self.max_bytes = max_bytes;
+        self.shrink()
+    }
+
+    fn shrink(&mut self) -> u64 {
+        let mut evicted = 0;
+        while self.total_size > self.max_bytes {
+            match self.cache.pop_lru() {
+                Some((_, value)) => self.total_size -= Self::entry_size(&value),
+                None => break,
+            }
+            evicted += 1;
+        }
+        evicted
+    }
+
+    fn clear(&mut self) {
+        self.cache.clear();
+        self.total_size = 0;
+    }
+}
+
+/// Metrics of the cache of a shard.
+struct TrieCacheMetrics {
+    hits: IntCounter,
+    misses: IntCounter,
+    evictions: IntCounter,
+    pinned_evictions: IntCounter,
+    size: IntGauge,
+    pinned_size: IntGauge,
+}
+
+impl TrieCacheMetrics {
+    fn new(shard_uid: ShardUId, is_view: bool) -> Self {
+        let shard_id = shard_uid.shard_id.to_string();
+        let is_view = if is_view { "1" } else { "0" };
+        let labels = [shard_id.as_str(), is_view];
+        let tier_labels = |tier| [shard_id.as_str(), is_view, tier];
+        Self {
+            hits: metrics::SHARD_CACHE_HITS.with_label_values(&labels),
+            misses: metrics::SHARD_CACHE_MISSES.with_label_values(&labels),
+            evictions: metrics::SHARD_CACHE_EVICTIONS.with_label_values(&tier_labels("main")),
+            pinned_evictions: metrics::SHARD_CACHE_EVICTIONS
+                .with_label_values(&tier_labels("pinned")),
+            size: metrics::SHARD_CACHE_SIZE_BYTES.with_label_values(&tier_labels("main")),
+            pinned_size: metrics::SHARD_CACHE_SIZE_BYTES.with_label_values(&tier_labels("pinned")),
+        }
+    }
+}
+
+struct TrieCacheInner {
+    shard_uid: ShardUId,
+    pinned_depth: usize,
+    /// Nodes close to the root.
+    pinned: BoundedLruCache,
+    /// All the other nodes and values.
+    main: BoundedLruCache,
+    metrics: TrieCacheMetrics,
+}
+
+impl TrieCacheInner {
+    fn is_pinned_depth(&self, depth: Option<usize>) -> bool {
+        depth.map_or(false, |depth| depth <= self.pinned_depth)
+    }
+
+    fn get(&mut self, key: &CryptoHash, depth: Option<usize>) -> Option<Arc<[u8]>> {
+        let value = match self.pinned.get(key) {
+            Some(value) => Some(value),
+            None => {
+                let value = self.main.get(key);
+                // Nodes written by the chunk application end up in the main
+                // tier; move them to the pinned one once they’re read from
+                // close to the root.
+                if let (Some(value), true) = (&value, self.is_pinned_depth(depth)) {
+                    self.main.pop(key);
+                    self.put_pinned(*key, value.clone());
+                }
+                value
+            }
+        };
+        if value.is_some() {
+            self.metrics.hits.inc();
+        } else {
+            self.metrics.misses.inc();
+        }
+        value
+    }
+
+    fn put(&mut self, key: CryptoHash, value: Arc<[u8]>, depth: Option<usize>) {
+        if self.is_pinned_depth(depth) {
+            self.main.pop(&key);
+            self.put_pinned(key, value);
+        } else if !self.pinned.contains(&key) {
+            self.metrics.evictions.inc_by(self.main.put(key, value));
+        }
+    }
+
+    fn put_pinned(&mut self, key: CryptoHash, value: Arc<[u8]>) {
+        self.metrics.pinned_evictions.inc_by(self.pinned.put(key, value));
+    }
+
+    fn pop(&mut self, key: &CryptoHash) {
+        self.pinned.pop(key);
+        self.main.pop(key);
+    }
+
+    fn update_size_metrics(&self) {
+        self.metrics.size.set(self.main.total_size as i64);
+        self.metrics.pinned_size.set(self.pinned.total_size as i64);
+    }
+}
+
+/// Cache of trie nodes and values of a shard, bounded by their total size.
+///
+/// Nodes close to the root, which are read by nearly every lookup, are kept
+/// in a separate tier with its own limit.
 #[derive(Clone)]
-pub struct TrieCache(Arc<Mutex<LruCache<CryptoHash, Arc<[u8]>>>>);
+pub struct TrieCache(Arc<Mutex<TrieCacheInner>>);
 
 impl TrieCache {
-    pub fn new() -> Self {
-        Self::with_capacity(TRIE_DEFAULT_SHARD_CACHE_SIZE)
+    pub fn new(config: &TrieCacheConfig, shard_uid: ShardUId, is_view: bool) -> Self {
+        Self(Arc::new(Mutex::new(TrieCacheInner {
+            shard_uid,
+            pinned_depth: config.pinned_depth,
+            pinned: BoundedLruCache::new(config.pinned_max_bytes.as_u64()),
+            main: BoundedLruCache::new(config.max_bytes(shard_uid)),
+            metrics: TrieCacheMetrics::new(shard_uid, is_view),
+        })))
     }
 
-    pub fn with_capacity(cap: usize) -> Self {
-        Self(Arc::new(Mutex::new(LruCache::new(cap))))
+    pub fn get(&self, key: &CryptoHash) -> Option<Arc<[u8]>> {
+        self.get_at_depth(key, None)
     }
 
-    pub fn get(&self, key: &CryptoHash) -> Option<Arc<[u8]>> {
-        self.0.lock().expect(POISONED_LOCK_ERR).get(key).cloned()
+    /// Gets a node read at given depth, if known, moving it into the pinned
+    /// tier if it’s close enough to the root.
+    pub(crate) fn get_at_depth(&self, key: &CryptoHash, depth: Option<usize>) -> Option<Arc<[u8]>> {
+        self.0.lock().expect(POISONED_LOCK_ERR).get(key, depth)
+    }
+
+    /// Puts a node read at given depth, if known, or a value into the cache.
+    pub(crate) fn put(&self, key: CryptoHash, value: Arc<[u8]>, depth: Option<usize>) {
+        let mut guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        guard.put(key, value, depth);
+        guard.update_size_metrics();
     }
 
     pub fn clear(&self) {
-        self.0.lock().expect(POISONED_LOCK_ERR).clear()
+        let mut guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        guard.pinned.clear();
+        guard.main.clear();
+        guard.update_size_metrics();
     }
 
     pub fn update_cache(&self, ops: Vec<(CryptoHash, Option<&Vec<u8>>)>) {
@@ -40,7 +278,7 @@ impl TrieCache {
             if let Some(value_rc) = opt_value_rc {
                 if let (Some(value), _rc) = decode_value_with_rc(&value_rc) {
                     if value.len() < TRIE_LIMIT_CACHED_VALUE_SIZE {
-                        guard.put(hash, value.into());
+                        guard.put(hash, value.into(), None);
                     }
                 } else {
                     guard.pop(&hash);
@@ -49,12 +287,33 @@ impl TrieCache {
                 guard.pop(&hash);
             }
         }
+        guard.update_size_metrics();
+    }
+
+    /// Applies new limits, evicting the least recently used entries if the
+    /// cache no longer fits into them.
+    pub fn update_config(&self, config: &TrieCacheConfig) {
+        let mut guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        let inner = &mut *guard;
+        inner.pinned_depth = config.pinned_depth;
+        let evicted = inner.pinned.set_max_bytes(config.pinned_max_bytes.as_u64());
+        inner.metrics.pinned_evictions.inc_by(evicted);
+        let evicted = inner.main.set_max_bytes(config.max_bytes(inner.shard_uid));
+        inner.metrics.evictions.inc_by(evicted);
+        inner.update_size_metrics();
     }
 
     #[cfg(test)]
     pub(crate) fn len(&self) -> usize {
         let guard = self.0.lock().expect(POISONED_LOCK_ERR);
-        guard.len()
+        guard.pinned.cache.len() + guard.main.cache.len()
+    }
+
+    /// Returns the total size of the main and of the pinned tier.
+    #[cfg(test)]
+    pub(crate) fn total_sizes(&self) -> (u64, u64) {
+        let guard = self.0.lock().expect(POISONED_LOCK_ERR);
+        (guard.main.total_size, guard.pinned.total_size)
     }
 }
 
@@ -64,6 +323,16 @@ pub trait TrieStorage {
     /// StorageError if the storage fails internally or the hash is not present.
     fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError>;
 
+    /// Get bytes of a serialized TrieNode with `depth` nodes above it on the path from the root.
+    /// Storages with a cache may keep nodes close to the root longer.
+    fn retrieve_raw_node_at_depth(
+        &self,
+        hash: &CryptoHash,
+        _depth: usize,
+    ) -> Result<Arc<[u8]>, StorageError> {
+        self.retrieve_raw_bytes(hash)
+    }
+
     fn as_caching_storage(&self) -> Option<&TrieCachingStorage> {
         None
     }
@@ -143,16 +412,62 @@ impl TrieStorage for TrieMemoryPartialStorage {
     }
 }
 
-/// Default number of cache entries.
-/// It was chosen to fit into RAM well. RAM spend on trie cache should not exceed 50_000 * 4 (number of shards) *
-/// TRIE_LIMIT_CACHED_VALUE_SIZE * 2 (number of caches - for regular and view client) = 0.4 GB.
-/// In our tests on a single shard, it barely occupied 40 MB, which is dominated by state cache size
+/// Storage serving nodes inserted by trie changes which were not committed to the database,
+/// falling back to the underlying storage for all other nodes.
+/// Used to read the state after a chain of uncommitted state transitions, e.g. when simulating
+/// transactions.
+pub struct TrieOverlayStorage {
+    pub(crate) base: Box<dyn TrieStorage>,
+    pub(crate) nodes: HashMap<CryptoHash, Arc<[u8]>>,
+}
+
+impl TrieStorage for TrieOverlayStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        match self.nodes.get(hash) {
+            Some(val) => Ok(val.clone()),
+            None => self.base.retrieve_raw_bytes(hash),
+        }
+    }
+
+    fn retrieve_raw_node_at_depth(
+        &self,
+        hash: &CryptoHash,
+        depth: usize,
+    ) -> Result<Arc<[u8]>, StorageError> {
+        match self.nodes.get(hash) {
+            Some(val) => Ok(val.clone()),
+            None => self.base.retrieve_raw_node_at_depth(hash, depth),
+        }
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        self.base.get_trie_nodes_count()
+    }
+}
+
+/// Default limit on the total size of the cache of a shard, apart from the pinned tier.
+/// It was chosen to fit into RAM well. RAM spend on trie caches should not exceed 50 MB * 4 (number of shards) *
+/// 2 (number of caches - for regular and view client) = 0.4 GB.
+/// In our tests on a single shard, the cache barely occupied 40 MB, which is dominated by state cache size
 /// with 512 MB limit. The total RAM usage for a single shard was 1 GB.
 #[cfg(not(feature = "no_cache"))]
-const TRIE_DEFAULT_SHARD_CACHE_SIZE: usize = 50000;
+const TRIE_DEFAULT_SHARD_CACHE_MAX_BYTES: bytesize::ByteSize = bytesize::ByteSize::mb(50);
+
+#[cfg(feature = "no_cache")]
+const TRIE_DEFAULT_SHARD_CACHE_MAX_BYTES: bytesize::ByteSize = bytesize::ByteSize::b(0);
+
+/// Default limit on the total size of the pinned tier of the cache of a shard.
+/// The three upper levels of a trie take about 4_400 nodes * 512 bytes = 2.2 MB, and every block rewrites the
+/// nodes on the paths to the changed keys, so there is room for nodes of a few recent state roots.
+#[cfg(not(feature = "no_cache"))]
+const TRIE_DEFAULT_SHARD_CACHE_PINNED_MAX_BYTES: bytesize::ByteSize = bytesize::ByteSize::mb(10);
 
 #[cfg(feature = "no_cache")]
-const TRIE_DEFAULT_SHARD_CACHE_SIZE: usize = 1;
+const TRIE_DEFAULT_SHARD_CACHE_PINNED_MAX_BYTES: bytesize::ByteSize = bytesize::ByteSize::b(0);
+
+/// Memory taken by a cache entry besides the value: the key, the pointers of the LRU list and of the hash map and
+/// the reference counts of the value.
+pub(crate) const TRIE_CACHE_ENTRY_OVERHEAD: u64 = 100;
 
 /// Values above this size (in bytes) are never cached.
 /// Note that most of Trie inner nodes are smaller than this - e.g. branches use around 32 * 16 = 512 bytes.
@@ -173,6 +488,8 @@ pub struct TrieCachingStorage {
     /// Note that for both caches key is the hash of value, so for the fixed key the value is unique.
     pub(crate) chunk_cache: RefCell<HashMap<CryptoHash, Arc<[u8]>>>,
     pub(crate) cache_mode: Cell<TrieCacheMode>,
+    /// Threads prefetching into the shard cache, set if prefetching is enabled for the trie.
+    pub(crate) prefetch_pool: Option<Arc<TriePrefetchPool>>,
 
     /// Counts potentially expensive trie node reads which are served from disk in the worst case. Here we count reads
     /// from DB or shard cache.
@@ -189,6 +506,7 @@ impl TrieCachingStorage {
             shard_cache,
             cache_mode: Cell::new(TrieCacheMode::CachingShard),
             chunk_cache: RefCell::new(Default::default()),
+            prefetch_pool: None,
             db_read_nodes: Cell::new(0),
             mem_read_nodes: Cell::new(0),
         }
@@ -227,10 +545,9 @@ impl TrieCachingStorage {
     pub fn set_mode(&self, state: TrieCacheMode) {
         self.cache_mode.set(state);
     }
-}
 
-impl TrieStorage for TrieCachingStorage {
-    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+    /// Retrieves a node at given depth, if known, or a value.
+    fn retrieve(&self, hash: &CryptoHash, depth: Option<usize>) -> Result<Arc<[u8]>, StorageError> {
         // Try to get value from chunk cache containing nodes with cheaper access. We can do it for any `TrieCacheMode`,
         // because we charge for reading nodes only when `CachingChunk` mode is enabled anyway.
         if let Some(val) = self.chunk_cache.borrow_mut().get(hash) {
@@ -239,11 +556,12 @@ impl TrieStorage for TrieCachingStorage {
         }
 
         // Try to get value from shard cache containing most recently touched nodes.
-        let mut guard = self.shard_cache.0.lock().expect(POISONED_LOCK_ERR);
-        let val = match guard.get(hash) {
+        // The lock isn't held while reading from the database so that reads of other threads,
+        // e.g. prefetching ones, don't wait for it.
+        let val = match self.shard_cache.get_at_depth(hash, depth) {
             Some(val) => {
                 near_o11y::io_trace!(count: "shard_cache_hit");
-                val.clone()
+                val
             }
             None => {
                 near_o11y::io_trace!(count: "shard_cache_miss");
@@ -263,7 +581,7 @@ impl TrieStorage for TrieCachingStorage {
                 // is always a value hash, so for each key there could be only one value, and it is impossible to have
                 // **different** values for the given key in shard and chunk caches.
                 if val.len() < TRIE_LIMIT_CACHED_VALUE_SIZE {
-                    guard.put(*hash, val.clone());
+                    self.shard_cache.put(*hash, val.clone(), depth);
                 } else {
                     near_o11y::io_trace!(count: "shard_cache_too_large");
                 }
@@ -289,6 +607,20 @@ impl TrieStorage for TrieCachingStorage {
 
         Ok(val)
     }
+}
+
+impl TrieStorage for TrieCachingStorage {
+    fn retrieve_raw_bytes(&self, hash: &CryptoHash) -> Result<Arc<[u8]>, StorageError> {
+        self.retrieve(hash, None)
+    }
+
+    fn retrieve_raw_node_at_depth(
+        &self,
+        hash: &CryptoHash,
+        depth: usize,
+    ) -> Result<Arc<[u8]>, StorageError> {
+        self.retrieve(hash, Some(depth))
+    }
 
     fn as_caching_storage(&self) -> Option<&TrieCachingStorage> {
         Some(self)
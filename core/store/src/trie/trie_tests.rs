@@ -80,7 +80,7 @@ where
     print!("Test touches {} nodes, expected result {:?}...", size, expected);
     for i in 0..(size + 1) {
         let storage = IncompletePartialStorage::new(storage.clone(), i);
-        let trie = Trie { storage: Box::new(storage) };
+        let trie = Trie { storage: Box::new(storage), flat_state: None };
         let expected_result =
             if i < size { Err(&StorageError::TrieNodeMissing) } else { Ok(&expected) };
         assert_eq!(test(Rc::new(trie)).as_ref(), expected_result);
@@ -211,13 +211,27 @@ mod nodes_counter_tests {
 mod caching_storage_tests {
     use super::*;
     use crate::test_utils::{create_test_store, create_tries};
-    use crate::trie::trie_storage::{TrieCache, TrieCachingStorage, TRIE_LIMIT_CACHED_VALUE_SIZE};
+    use crate::trie::trie_storage::{
+        TrieCache, TrieCacheConfig, TrieCachingStorage, TRIE_CACHE_ENTRY_OVERHEAD,
+        TRIE_LIMIT_CACHED_VALUE_SIZE,
+    };
     use crate::trie::TrieRefcountChange;
-    use crate::{Store, TrieChanges};
+    use crate::{ShardTries, Store, TrieCacheFactory, TrieChanges};
     use assert_matches::assert_matches;
     use near_primitives::hash::hash;
     use near_primitives::types::TrieCacheMode;
 
+    /// Returns a configuration of caches fitting `num_values` values of given
+    /// length, without the pinned tier.
+    fn max_bytes_config(num_values: usize, value_len: usize) -> TrieCacheConfig {
+        let entry_size = value_len as u64 + TRIE_CACHE_ENTRY_OVERHEAD;
+        TrieCacheConfig {
+            default_max_bytes: bytesize::ByteSize::b(num_values as u64 * entry_size),
+            pinned_max_bytes: bytesize::ByteSize::b(0),
+            ..Default::default()
+        }
+    }
+
     fn create_store_with_values(values: &[Vec<u8>], shard_uid: ShardUId) -> Store {
         let tries = create_tries();
         let mut trie_changes = TrieChanges::empty(Trie::empty_root());
@@ -241,7 +255,7 @@ mod caching_storage_tests {
         let values = vec![value.clone()];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(&TrieCacheConfig::default(), shard_uid, false);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let key = hash(&value);
         assert_eq!(trie_cache.get(&key), None);
@@ -262,7 +276,8 @@ mod caching_storage_tests {
     fn test_retrieve_error() {
         let shard_uid = ShardUId::single_shard();
         let store = create_test_store();
-        let trie_caching_storage = TrieCachingStorage::new(store, TrieCache::new(), shard_uid);
+        let trie_cache = TrieCache::new(&TrieCacheConfig::default(), shard_uid, false);
+        let trie_caching_storage = TrieCachingStorage::new(store, trie_cache, shard_uid);
         let value = vec![1u8];
         let key = hash(&value);
 
@@ -277,7 +292,7 @@ mod caching_storage_tests {
         let values = vec![value.clone()];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(&TrieCacheConfig::default(), shard_uid, false);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let key = hash(&value);
 
@@ -299,7 +314,7 @@ mod caching_storage_tests {
         let values = vec![vec![1u8]];
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::new();
+        let trie_cache = TrieCache::new(&TrieCacheConfig::default(), shard_uid, false);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
         let value = &values[0];
         let key = hash(&value);
@@ -347,7 +362,7 @@ mod caching_storage_tests {
         let values: Vec<Vec<u8>> = (0..shard_cache_size as u8 + 1).map(|i| vec![i]).collect();
         let shard_uid = ShardUId::single_shard();
         let store = create_store_with_values(&values, shard_uid);
-        let trie_cache = TrieCache::with_capacity(shard_cache_size);
+        let trie_cache = TrieCache::new(&max_bytes_config(shard_cache_size, 1), shard_uid, false);
         let trie_caching_storage = TrieCachingStorage::new(store, trie_cache.clone(), shard_uid);
 
         let value = &values[0];
@@ -372,4 +387,103 @@ mod caching_storage_tests {
         assert_eq!(count_delta.db_reads, 0);
         assert_eq!(count_delta.mem_reads, 1);
     }
+
+    /// Check that the cache is bounded by the total size of the values rather than by their number.
+    #[test]
+    fn test_cache_size_limit() {
+        let shard_uid = ShardUId::single_shard();
+        let trie_cache = TrieCache::new(&max_bytes_config(3, 10), shard_uid, false);
+        let entry_size = 10 + TRIE_CACHE_ENTRY_OVERHEAD;
+        let small: Vec<Vec<u8>> = (0..3).map(|i| vec![i; 10]).collect();
+        for value in &small {
+            trie_cache.put(hash(value), value.as_slice().into(), None);
+        }
+        assert_eq!(trie_cache.len(), 3);
+        assert_eq!(trie_cache.total_sizes(), (3 * entry_size, 0));
+
+        // A value taking almost twice as much as the others evicts the two least recently used ones.
+        assert!(trie_cache.get(&hash(&small[0])).is_some());
+        let large = vec![3; entry_size as usize];
+        trie_cache.put(hash(&large), large.as_slice().into(), None);
+        assert_eq!(trie_cache.len(), 2);
+        assert_eq!(trie_cache.total_sizes(), (2 * entry_size + TRIE_CACHE_ENTRY_OVERHEAD, 0));
+        assert!(trie_cache.get(&hash(&small[0])).is_some());
+        assert!(trie_cache.get(&hash(&small[1])).is_none());
+        assert!(trie_cache.get(&hash(&large)).is_some());
+
+        // A value exceeding the limit alone isn't cached.
+        let huge = vec![4; 3 * entry_size as usize];
+        trie_cache.put(hash(&huge), huge.as_slice().into(), None);
+        assert!(trie_cache.get(&hash(&huge)).is_none());
+        assert_eq!(trie_cache.len(), 2);
+
+        // Shrinking the cache evicts the least recently used values.
+        trie_cache.update_config(&max_bytes_config(1, 10));
+        assert_eq!(trie_cache.len(), 0);
+        trie_cache.update_config(&max_bytes_config(2, 10));
+        trie_cache.put(hash(&small[0]), small[0].as_slice().into(), None);
+        assert_eq!(trie_cache.total_sizes(), (entry_size, 0));
+    }
+
+    /// Check that nodes close to the root aren't evicted by other nodes and values.
+    #[test]
+    fn test_pinned_tier() {
+        let shard_uid = ShardUId::single_shard();
+        let entry_size = 10 + TRIE_CACHE_ENTRY_OVERHEAD;
+        let config = TrieCacheConfig {
+            pinned_depth: 1,
+            pinned_max_bytes: bytesize::ByteSize::b(2 * entry_size),
+            ..max_bytes_config(2, 10)
+        };
+        let trie_cache = TrieCache::new(&config, shard_uid, false);
+        let values: Vec<Vec<u8>> = (0..6).map(|i| vec![i; 10]).collect();
+        trie_cache.put(hash(&values[0]), values[0].as_slice().into(), Some(0));
+        trie_cache.put(hash(&values[1]), values[1].as_slice().into(), Some(1));
+        for value in &values[2..] {
+            trie_cache.put(hash(value), value.as_slice().into(), Some(2));
+        }
+        assert_eq!(trie_cache.total_sizes(), (2 * entry_size, 2 * entry_size));
+        assert!(trie_cache.get(&hash(&values[0])).is_some());
+        assert!(trie_cache.get(&hash(&values[1])).is_some());
+        assert!(trie_cache.get(&hash(&values[2])).is_none());
+
+        // A node in the main tier moves to the pinned one when read close to the root.
+        assert!(trie_cache.get_at_depth(&hash(&values[5]), Some(0)).is_some());
+        assert_eq!(trie_cache.total_sizes(), (entry_size, 2 * entry_size));
+        assert!(trie_cache.get(&hash(&values[0])).is_none());
+        assert!(trie_cache.get(&hash(&values[1])).is_some());
+    }
+
+    /// Check that lookups put the nodes close to the root into the pinned tier and that limits of the caches of
+    /// shard tries can be changed.
+    #[test]
+    fn test_shard_tries_cache_config() {
+        let shard_uid = ShardUId::single_shard();
+        let config = TrieCacheConfig { pinned_depth: 0, ..Default::default() };
+        let tries = ShardTries::new(create_test_store(), TrieCacheFactory::new(config, 0, 1));
+        let changes = (0..16u8).map(|i| (vec![i * 16], Some(vec![i]))).collect();
+        let state_root =
+            crate::test_utils::test_populate_trie(&tries, &Trie::empty_root(), shard_uid, changes);
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let cache = trie.storage.as_caching_storage().unwrap().shard_cache.clone();
+        cache.clear();
+
+        assert_eq!(trie.get(&state_root, &[16]), Ok(Some(vec![1])));
+        let (main_size, pinned_size) = cache.total_sizes();
+        assert!(main_size > 0);
+        assert!(pinned_size > 0);
+
+        let config = TrieCacheConfig {
+            default_max_bytes: bytesize::ByteSize::b(0),
+            pinned_max_bytes: bytesize::ByteSize::b(0),
+            ..Default::default()
+        };
+        tries.update_cache_config(&config);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(trie.get(&state_root, &[32]), Ok(Some(vec![2])));
+        assert_eq!(cache.len(), 0);
+        let view_trie = tries.get_view_trie_for_shard(shard_uid);
+        assert_eq!(view_trie.get(&state_root, &[32]), Ok(Some(vec![2])));
+        assert_eq!(view_trie.storage.as_caching_storage().unwrap().shard_cache.len(), 0);
+    }
 }
@@ -0,0 +1,234 @@
+use borsh::BorshSerialize;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use near_chain::chain::ApplyStatePartsRequest;
+use near_chain::{ChainGenesis, Provenance};
+use near_chain_configs::Genesis;
+use near_client::external_state_sync::{dump_state, DirectoryStateSource, ExternalStateSource};
+use near_client::sync::{StateSync, StateSyncResult};
+use near_client::test_utils::TestEnv;
+use near_network::test_utils::MockPeerManagerAdapter;
+use near_network::types::{NetworkRequests, PeerManagerMessageRequest};
+use near_primitives::hash::CryptoHash;
+use near_primitives::state_part::PartId;
+use near_primitives::syncing::{get_num_state_parts, ShardStateSyncResponseHeader, StatePartKey};
+use near_primitives::types::ShardId;
+use near_store::DBCol;
+use nearcore::config::GenesisExt;
+
+use crate::tests::client::process_blocks::create_nightshade_runtimes;
+
+/// Produces blocks on the first client, processing them on both, until the
+/// second epoch starts and returns its sync hash.
+fn setup_env() -> (TestEnv, CryptoHash) {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let mut env = TestEnv::builder(ChainGenesis::new(&genesis))
+        .clients_count(2)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 2))
+        .build();
+    for height in 1..=epoch_length + 2 {
+        let block = env.clients[0].produce_block(height).unwrap().unwrap();
+        env.process_block(0, block.clone(), Provenance::PRODUCED);
+        env.process_block(1, block, Provenance::NONE);
+    }
+    let head = env.clients[0].chain.head().unwrap();
+    let sync_hash =
+        StateSync::get_epoch_start_sync_hash(&mut env.clients[0].chain, &head.last_block_hash)
+            .unwrap();
+    assert_eq!(env.clients[0].chain.get_block_header(&sync_hash).unwrap().height(), 6);
+    (env, sync_hash)
+}
+
+/// Checks that the dumped header and parts are the ones served to the peers
+/// and that a stopped dump leaves the shard incomplete.
+#[test]
+fn test_dump_state() {
+    let (env, sync_hash) = setup_env();
+    let chain = &env.clients[0].chain;
+
+    let stopped_dir = tempfile::tempdir().unwrap();
+    let stopped_source = DirectoryStateSource::new(stopped_dir.path().to_path_buf());
+    dump_state(chain, sync_hash, &stopped_source, &AtomicBool::new(true)).unwrap();
+    assert!(!stopped_source.has_shard(0, &sync_hash));
+    assert!(stopped_source.get_header(0, &sync_hash).unwrap().is_none());
+
+    let dir = tempfile::tempdir().unwrap();
+    let source = DirectoryStateSource::new(dir.path().to_path_buf());
+    dump_state(chain, sync_hash, &source, &AtomicBool::new(false)).unwrap();
+    assert!(source.has_shard(0, &sync_hash));
+    let header = source.get_header(0, &sync_hash).unwrap().unwrap();
+    assert_eq!(header, chain.get_state_response_header(0, sync_hash).unwrap());
+    let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+    for part_id in 0..num_parts {
+        assert_eq!(
+            source.get_part(0, &sync_hash, part_id).unwrap().unwrap(),
+            chain.get_state_response_part(0, part_id, sync_hash).unwrap()
+        );
+    }
+    assert!(source.get_part(0, &sync_hash, num_parts).unwrap().is_none());
+}
+
+/// Source which reads at most one part per step and fails to read the parts
+/// in `failing_parts`.
+struct LimitedSource {
+    inner: DirectoryStateSource,
+    failing_parts: Vec<u64>,
+    part_reads: AtomicUsize,
+}
+
+impl ExternalStateSource for LimitedSource {
+    fn get_header(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+    ) -> io::Result<Option<ShardStateSyncResponseHeader>> {
+        self.inner.get_header(shard_id, sync_hash)
+    }
+
+    fn get_part(
+        &self,
+        shard_id: ShardId,
+        sync_hash: &CryptoHash,
+        part_id: u64,
+    ) -> io::Result<Option<Vec<u8>>> {
+        self.part_reads.fetch_add(1, Ordering::Relaxed);
+        if self.failing_parts.contains(&part_id) {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "part read timed out"));
+        }
+        self.inner.get_part(shard_id, sync_hash, part_id)
+    }
+
+    fn max_part_reads_per_step(&self) -> usize {
+        1
+    }
+}
+
+/// Runs state sync of shard 0 on the second client without any peers, for at
+/// most `max_steps` steps.  Returns whether it completed and the requests sent
+/// to the network.
+fn run_state_sync(
+    env: &mut TestEnv,
+    sync_hash: CryptoHash,
+    source: Arc<dyn ExternalStateSource>,
+    max_steps: usize,
+) -> (bool, Vec<PeerManagerMessageRequest>) {
+    let network_adapter = Arc::new(MockPeerManagerAdapter::default());
+    let mut state_sync =
+        StateSync::new(network_adapter.clone(), Duration::from_secs(60), Some(source));
+    let client = &mut env.clients[1];
+    let runtime_adapter = client.runtime_adapter.clone();
+    let apply_result = RefCell::new(None);
+    let apply_state_parts = |msg: ApplyStatePartsRequest| {
+        let store = runtime_adapter.get_store();
+        let result = (0..msg.num_parts).try_for_each(|part_id| {
+            let key = StatePartKey(msg.sync_hash, msg.shard_id, part_id).try_to_vec().unwrap();
+            let part = store.get(DBCol::StateParts, &key).unwrap().unwrap();
+            runtime_adapter.apply_state_part(
+                msg.shard_id,
+                &msg.state_root,
+                PartId::new(part_id, msg.num_parts),
+                &part,
+                &msg.epoch_id,
+            )
+        });
+        *apply_result.borrow_mut() = Some(result);
+    };
+
+    let mut new_shard_sync = HashMap::new();
+    let mut completed = false;
+    // Requests to the peers are sent from futures spawned on the actix system.
+    let system = actix::System::new();
+    for _ in 0..max_steps {
+        let result = system.block_on(async {
+            state_sync
+                .run(
+                    &None,
+                    sync_hash,
+                    &mut new_shard_sync,
+                    &mut client.chain,
+                    &runtime_adapter,
+                    &vec![],
+                    vec![0],
+                    &apply_state_parts,
+                    &|_| panic!("unexpected state split"),
+                )
+                .unwrap()
+        });
+        if let Some(result) = apply_result.borrow_mut().take() {
+            state_sync.set_apply_result(0, result);
+        }
+        if let StateSyncResult::Completed = result {
+            completed = true;
+            break;
+        }
+    }
+    let requests = network_adapter.requests.write().unwrap().drain(..).collect();
+    (completed, requests)
+}
+
+/// Checks that state sync completes with the header and parts read from the
+/// external source when there are no peers to request them from.
+#[test]
+fn test_external_state_sync() {
+    let (mut env, sync_hash) = setup_env();
+    let dir = tempfile::tempdir().unwrap();
+    let source = DirectoryStateSource::new(dir.path().to_path_buf());
+    dump_state(&env.clients[0].chain, sync_hash, &source, &AtomicBool::new(false)).unwrap();
+
+    let (completed, requests) = run_state_sync(&mut env, sync_hash, Arc::new(source), 10);
+    assert!(completed, "state sync didn't complete");
+    assert!(requests.is_empty());
+}
+
+/// Checks that the parts over the per step limit of the external source are
+/// read from it on the next steps instead of being requested from the
+/// validators tracking the shard.
+#[test]
+fn test_external_state_sync_over_several_steps() {
+    let (mut env, sync_hash) = setup_env();
+    let dir = tempfile::tempdir().unwrap();
+    let inner = DirectoryStateSource::new(dir.path().to_path_buf());
+    dump_state(&env.clients[0].chain, sync_hash, &inner, &AtomicBool::new(false)).unwrap();
+    let source =
+        Arc::new(LimitedSource { inner, failing_parts: vec![], part_reads: AtomicUsize::new(0) });
+
+    let (completed, requests) = run_state_sync(&mut env, sync_hash, source.clone(), 20);
+    assert!(completed, "state sync didn't complete");
+    assert!(requests.is_empty(), "unexpected requests to peers: {:?}", requests);
+    let header = env.clients[0].chain.get_state_response_header(0, sync_hash).unwrap();
+    let num_parts = get_num_state_parts(header.state_root_node().memory_usage);
+    assert!(num_parts > 1);
+    assert_eq!(source.part_reads.load(Ordering::Relaxed), num_parts as usize);
+}
+
+/// Checks that only the parts which the external source failed to read are
+/// requested from the validators tracking the shard.
+#[test]
+fn test_external_state_sync_falls_back_to_peers() {
+    let (mut env, sync_hash) = setup_env();
+    let dir = tempfile::tempdir().unwrap();
+    let inner = DirectoryStateSource::new(dir.path().to_path_buf());
+    dump_state(&env.clients[0].chain, sync_hash, &inner, &AtomicBool::new(false)).unwrap();
+    let source =
+        Arc::new(LimitedSource { inner, failing_parts: vec![1], part_reads: AtomicUsize::new(0) });
+
+    let (completed, requests) = run_state_sync(&mut env, sync_hash, source, 20);
+    assert!(!completed);
+    assert!(!requests.is_empty());
+    for request in requests {
+        match request {
+            PeerManagerMessageRequest::NetworkRequests(NetworkRequests::StateRequestPart {
+                part_id,
+                ..
+            }) => assert_eq!(part_id, 1),
+            request => panic!("unexpected request: {:?}", request),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use assert_matches::assert_matches;
+use near_chain::{ChainGenesis, Provenance, RuntimeAdapter};
+use near_chain_configs::Genesis;
+use near_client::test_utils::TestEnv;
+use near_crypto::{InMemorySigner, KeyType};
+use near_logger_utils::init_test_logger;
+use near_primitives::runtime::config_store::RuntimeConfigStore;
+use near_primitives::shard_layout::ShardUId;
+use near_primitives::test_utils::encode;
+use near_primitives::transaction::{
+    Action, DeployContractAction, FunctionCallAction, SignedTransaction,
+};
+use near_primitives::views::FinalExecutionStatus;
+use near_store::test_utils::create_test_store;
+use near_store::{DBCol, StoreUpdate};
+use nearcore::config::GenesisExt;
+use nearcore::TrackedConfig;
+
+fn function_call(method_name: &str, args: &[u64]) -> Action {
+    Action::FunctionCall(FunctionCallAction {
+        method_name: method_name.to_string(),
+        args: encode(args),
+        gas: 100_000_000_000_000,
+        deposit: 0,
+    })
+}
+
+/// Nodes which state synced have no flat storage, so reads served by flat
+/// storage must burn the same gas as reads going through the trie.
+#[test]
+fn test_flat_storage_reads_burn_same_gas() {
+    init_test_logger();
+    let genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    let runtimes: Vec<Arc<dyn RuntimeAdapter>> = (0..2)
+        .map(|_| {
+            Arc::new(nearcore::NightshadeRuntime::test_with_runtime_config_store(
+                Path::new("."),
+                create_test_store(),
+                &genesis,
+                TrackedConfig::AllShards,
+                RuntimeConfigStore::test(),
+            )) as Arc<dyn RuntimeAdapter>
+        })
+        .collect();
+    let mut env = TestEnv::builder(ChainGenesis::new(&genesis))
+        .clients_count(2)
+        .runtime_adapters(runtimes)
+        .build();
+
+    // The second client drops flat storage the same way as before state sync.
+    let tries = env.clients[1].runtime_adapter.get_tries();
+    let mut store_update = StoreUpdate::new_with_tries(tries.clone());
+    store_update.delete_all(DBCol::FlatState);
+    store_update.delete_all(DBCol::FlatStateDeltas);
+    store_update.delete_all(DBCol::FlatStateMisc);
+    store_update.commit().unwrap();
+    let shard_uid = ShardUId::single_shard();
+    let flat_storage =
+        env.clients[0].runtime_adapter.get_tries().get_flat_storage_state_for_shard(shard_uid);
+    assert!(flat_storage.is_some());
+    assert!(tries.get_flat_storage_state_for_shard(shard_uid).is_none());
+
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let transactions = [
+        vec![Action::DeployContract(DeployContractAction {
+            code: near_test_contracts::rs_contract().to_vec(),
+        })],
+        vec![function_call("write_key_value", &[1, 10])],
+        vec![function_call("read_value", &[1])],
+    ];
+    let mut height = 1;
+    for (nonce, actions) in (1..).zip(transactions) {
+        let tip = env.clients[0].chain.head().unwrap();
+        let tx = SignedTransaction::from_actions(
+            nonce,
+            "test0".parse().unwrap(),
+            "test0".parse().unwrap(),
+            &signer,
+            actions,
+            tip.last_block_hash,
+        );
+        let tx_hash = tx.get_hash();
+        env.clients[0].process_tx(tx, false, false);
+        for _ in 0..3 {
+            let block = env.clients[0].produce_block(height).unwrap().unwrap();
+            env.process_block(0, block.clone(), Provenance::PRODUCED);
+            // The second client doesn't produce chunks, so it requests them from the first one.
+            let result = env.clients[1].process_block_test(block.into(), Provenance::NONE);
+            assert_matches!(result, Ok(_) | Err(near_chain::Error::ChunksMissing(_)));
+            env.process_partial_encoded_chunks_requests(1);
+            env.clients[1].finish_blocks_in_processing();
+            assert_eq!(env.clients[1].chain.head().unwrap().height, height);
+            height += 1;
+        }
+
+        let outcomes: Vec<_> = env
+            .clients
+            .iter()
+            .map(|client| client.chain.get_final_transaction_result(&tx_hash).unwrap())
+            .collect();
+        assert_matches!(outcomes[0].status, FinalExecutionStatus::SuccessValue(_));
+        let gas_burnt: Vec<Vec<_>> = outcomes
+            .iter()
+            .map(|outcome| {
+                outcome.receipts_outcome.iter().map(|receipt| receipt.outcome.gas_burnt).collect()
+            })
+            .collect();
+        assert_eq!(gas_burnt[0], gas_burnt[1]);
+    }
+}
@@ -1,6 +1,9 @@
 mod benchmarks;
 mod challenges;
 mod chunks_management;
+mod external_state_sync;
+#[cfg(feature = "protocol_feature_flat_state")]
+mod flat_storage;
 mod process_blocks;
 mod runtimes;
 #[cfg(feature = "sandbox")]
@@ -12,6 +12,7 @@ use near_primitives::num_rational::{Ratio, Rational32};
 
 use near_actix_test_utils::run_actix;
 use near_chain::chain::ApplyStatePartsRequest;
+use near_chain::near_chain_primitives::error::TransactionTraceError;
 use near_chain::types::LatestKnown;
 use near_chain::validate::validate_chunk_with_chunk_extra;
 use near_chain::{
@@ -2804,6 +2805,96 @@ fn test_execution_metadata() {
     assert_eq!(expected_receipt_cost, actual_receipt_cost)
 }
 
+/// Checks that the trace of a transaction scheduling a promise with a callback
+/// links the callback to the receipt producing its input data.
+#[test]
+fn test_transaction_trace() {
+    let epoch_length = 5;
+    let mut genesis = Genesis::test(vec!["test0".parse().unwrap(), "test1".parse().unwrap()], 1);
+    genesis.config.epoch_length = epoch_length;
+    let chain_genesis = ChainGenesis::new(&genesis);
+    let mut env = TestEnv::builder(chain_genesis)
+        .runtime_adapters(create_nightshade_runtimes(&genesis, 1))
+        .build();
+    let height = deploy_test_contract(
+        &mut env,
+        "test0".parse().unwrap(),
+        near_test_contracts::base_rs_contract(),
+        epoch_length,
+        1,
+    );
+
+    let gas: u64 = 20_000_000_000_000;
+    let data = serde_json::json!([
+        {"create": {
+        "account_id": "test0",
+        "method_name": "call_promise",
+        "arguments": [],
+        "amount": "0",
+        "gas": gas,
+        }, "id": 0 },
+        {"then": {
+        "promise_index": 0,
+        "account_id": "test0",
+        "method_name": "call_promise",
+        "arguments": [],
+        "amount": "0",
+        "gas": gas,
+        }, "id": 1}
+    ]);
+    let signer = InMemorySigner::from_seed("test0".parse().unwrap(), KeyType::ED25519, "test0");
+    let block = env.clients[0].chain.get_block_by_height(height - 1).unwrap();
+    let tx = SignedTransaction::from_actions(
+        height,
+        "test0".parse().unwrap(),
+        "test0".parse().unwrap(),
+        &signer,
+        vec![Action::FunctionCall(FunctionCallAction {
+            method_name: "call_promise".to_string(),
+            args: serde_json::to_vec(&data).unwrap(),
+            gas: 100_000_000_000_000,
+            deposit: 0,
+        })],
+        *block.hash(),
+    );
+    let tx_hash = tx.get_hash();
+    check_tx_processing(&mut env, tx, height, 5);
+
+    let chain = &env.clients[0].chain;
+    let trace = chain.get_transaction_trace(&tx_hash, 100).unwrap();
+    assert_eq!(trace.transaction_hash, tx_hash);
+    assert_matches!(trace.status, FinalExecutionStatus::SuccessValue(_));
+    assert!(trace.pending_receipt_ids.is_empty());
+    assert_eq!(trace.nodes[0].id, tx_hash);
+    assert_eq!(trace.nodes[0].parent_id, None);
+    let node = |id: &CryptoHash| trace.nodes.iter().find(|node| &node.id == id).unwrap();
+    for trace_node in &trace.nodes[1..] {
+        assert_eq!(trace_node.shard_id, 0);
+        let parent = node(trace_node.parent_id.as_ref().unwrap());
+        assert!(trace_node.block_height >= parent.block_height);
+    }
+
+    let callbacks: Vec<_> =
+        trace.nodes.iter().filter(|trace_node| !trace_node.input_data.is_empty()).collect();
+    assert_eq!(callbacks.len(), 1);
+    let callback = callbacks[0];
+    assert_eq!(callback.input_data.len(), 1);
+    let producer = node(callback.input_data[0].producer_id.as_ref().unwrap());
+    assert_eq!(producer.parent_id, callback.parent_id);
+    assert!(callback.block_height > producer.block_height);
+
+    let num_receipts = trace.nodes.len() - 1;
+    assert!(chain.get_transaction_trace(&tx_hash, num_receipts).is_ok());
+    assert_matches!(
+        chain.get_transaction_trace(&tx_hash, num_receipts - 1),
+        Err(TransactionTraceError::TooManyReceipts { limit }) if limit == num_receipts - 1
+    );
+    assert_matches!(
+        chain.get_transaction_trace(&CryptoHash::default(), 100),
+        Err(TransactionTraceError::UnknownTransaction(_))
+    );
+}
+
 #[test]
 fn test_epoch_protocol_version_change() {
     init_test_logger();
@@ -0,0 +1,298 @@
+//! Creating, pruning and restoring checkpoints of the node database.
+//!
+//! The checkpoint format is defined in [`near_store::checkpoint`].  This module
+//! adds the operations which need the full node configuration: garbage
+//! collecting a checkpoint down to a non-archival shape and restoring it into
+//! the home directory followed by validation of the restored database.
+
+use crate::{load_config, NearConfig, NightshadeRuntime};
+use anyhow::Context;
+use near_chain::store_validator::StoreValidator;
+use near_chain::{Chain, ChainGenesis, ChainStoreAccess, DoomslugThresholdMode, RuntimeAdapter};
+use near_chain_configs::{GCConfig, GenesisValidationMode};
+use near_store::checkpoint::{checkpoint_data_path, CheckpointManifest};
+use near_store::Store;
+use std::path::Path;
+use std::sync::Arc;
+use tracing::{error, info};
+
+/// Creates a checkpoint of the database in `home_dir` in `checkpoint_dir`.
+///
+/// The node must not be running.  Use the `/debug/api/checkpoint` endpoint to
+/// create a checkpoint of a running node.  If `prune` is set, the checkpoint is
+/// afterwards garbage collected with [`prune_checkpoint`].
+pub fn create_checkpoint(
+    home_dir: &Path,
+    genesis_validation: GenesisValidationMode,
+    checkpoint_dir: &Path,
+    prune: bool,
+) -> anyhow::Result<CheckpointManifest> {
+    let near_config = load_config(home_dir, genesis_validation)?;
+    ensure_no_cold_store(&near_config)?;
+    let opener = Store::opener(home_dir, &near_config.config.store);
+    let db_path = opener.get_path();
+    match opener.get_version_if_exists()? {
+        Some(near_primitives::version::DB_VERSION) => {}
+        Some(db_version) => anyhow::bail!(
+            "{}: expected DB version {} but got {}; run the node to migrate the database first",
+            db_path.display(),
+            near_primitives::version::DB_VERSION,
+            db_version
+        ),
+        None => anyhow::bail!("{}: storage doesn’t exist", db_path.display()),
+    }
+    let store = opener.open();
+    let manifest = near_store::checkpoint::create_checkpoint(&store, checkpoint_dir)
+        .with_context(|| format!("creating checkpoint in {}", checkpoint_dir.display()))?;
+    core::mem::drop(store);
+    if prune {
+        return prune_checkpoint_with_config(home_dir, &near_config, checkpoint_dir);
+    }
+    Ok(manifest)
+}
+
+/// Checkpoints hold a single database, so nodes with cold storage, whose data
+/// is split between two databases, can’t be checkpointed nor restored.
+fn ensure_no_cold_store(near_config: &NearConfig) -> anyhow::Result<()> {
+    anyhow::ensure!(
+        near_config.config.cold_store.is_none(),
+        "checkpoints of nodes with cold storage are not supported"
+    );
+    Ok(())
+}
+
+/// Garbage collects the checkpoint in `checkpoint_dir` the way a non-archival
+/// node configured as in `home_dir` would.
+///
+/// Data of blocks behind the garbage collection horizon is removed and so is
+/// data of forks.  Note that state is removed only if the checkpoint contains
+/// trie changes, which is not the case for checkpoints of archival nodes.
+pub fn prune_checkpoint(
+    home_dir: &Path,
+    genesis_validation: GenesisValidationMode,
+    checkpoint_dir: &Path,
+) -> anyhow::Result<CheckpointManifest> {
+    let near_config = load_config(home_dir, genesis_validation)?;
+    prune_checkpoint_with_config(home_dir, &near_config, checkpoint_dir)
+}
+
+fn prune_checkpoint_with_config(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    checkpoint_dir: &Path,
+) -> anyhow::Result<CheckpointManifest> {
+    let mut manifest = CheckpointManifest::read(checkpoint_dir)?;
+    let mut store_config = near_config.config.store.clone();
+    store_config.path = Some(checkpoint_data_path(checkpoint_dir));
+    let store = Store::opener(checkpoint_dir, &store_config).open();
+
+    let runtime = Arc::new(NightshadeRuntime::from_config(home_dir, store, near_config));
+    let mut chain = Chain::new(
+        runtime.clone(),
+        &ChainGenesis::new(&near_config.genesis),
+        DoomslugThresholdMode::TwoThirds,
+        true,
+    )?;
+
+    // The head of a checkpoint doesn’t move so the fork tail, which a running
+    // node advances at epoch boundaries, needs to be moved to the garbage
+    // collection horizon explicitly.
+    let head = chain.head()?;
+    let gc_stop_height = runtime.get_gc_stop_height(&head.last_block_hash);
+    if chain.store().fork_tail()? < gc_stop_height {
+        let mut chain_store_update = chain.mut_store().store_update();
+        chain_store_update.update_fork_tail(gc_stop_height);
+        chain_store_update.commit()?;
+    }
+
+    let gc_config = GCConfig { gc_blocks_limit: u64::MAX, ..near_config.client_config.gc.clone() };
+    info!(target: "checkpoint", %gc_stop_height, "Pruning checkpoint");
+    loop {
+        let tails = (chain.store().tail()?, chain.store().fork_tail()?);
+        chain.clear_data(runtime.get_tries(), &gc_config)?;
+        let new_tails = (chain.store().tail()?, chain.store().fork_tail()?);
+        if new_tails == tails {
+            break;
+        }
+        info!(target: "checkpoint", tail = new_tails.0, fork_tail = new_tails.1, "Pruning checkpoint");
+    }
+
+    manifest.pruned = true;
+    manifest.write(checkpoint_dir)?;
+    info!(target: "checkpoint", tail = chain.store().tail()?, "Pruned checkpoint");
+    Ok(manifest)
+}
+
+/// Restores the checkpoint in `checkpoint_dir` as the database of the node in
+/// `home_dir` and validates the result.
+///
+/// The node’s database must not exist.  The checkpoint is copied into
+/// a temporary directory next to the database and moved into place only once
+/// it passes validation, so an invalid checkpoint leaves no database behind.
+pub fn restore_checkpoint(
+    home_dir: &Path,
+    genesis_validation: GenesisValidationMode,
+    checkpoint_dir: &Path,
+) -> anyhow::Result<CheckpointManifest> {
+    let near_config = load_config(home_dir, genesis_validation)?;
+    ensure_no_cold_store(&near_config)?;
+    let manifest = CheckpointManifest::read(checkpoint_dir)?;
+    anyhow::ensure!(
+        manifest.db_version == near_primitives::version::DB_VERSION,
+        "{}: expected DB version {} but got {}",
+        checkpoint_dir.display(),
+        near_primitives::version::DB_VERSION,
+        manifest.db_version
+    );
+    anyhow::ensure!(
+        !(manifest.pruned && near_config.client_config.archive),
+        "{}: pruned checkpoint cannot be restored on an archival node",
+        checkpoint_dir.display()
+    );
+
+    let opener = Store::opener(home_dir, &near_config.config.store);
+    let db_path = opener.get_path();
+    anyhow::ensure!(
+        !opener.check_if_exists(),
+        "{}: database already exists; move it away before restoring a checkpoint",
+        db_path.display()
+    );
+    let parent_dir = db_path.parent().context("database path has no parent directory")?;
+    std::fs::create_dir_all(parent_dir)?;
+    // Removed when dropped, i.e. if the restored database turns out invalid.
+    let tmp_dir = tempfile::Builder::new().prefix(".restore-").tempdir_in(parent_dir)?;
+    let tmp_db_path = tmp_dir.path().join("data");
+    let src_path = checkpoint_data_path(checkpoint_dir);
+    info!(target: "checkpoint", src = %src_path.display(), dest = %db_path.display(), "Restoring checkpoint");
+    std::fs::create_dir(&tmp_db_path)?;
+    for entry in std::fs::read_dir(&src_path)? {
+        let entry = entry?;
+        std::fs::copy(entry.path(), tmp_db_path.join(entry.file_name()))
+            .with_context(|| format!("copying {}", entry.path().display()))?;
+    }
+
+    let checks = validate_restored_db(home_dir, &near_config, &manifest, &tmp_db_path)
+        .with_context(|| format!("{}: invalid checkpoint", checkpoint_dir.display()))?;
+    std::fs::rename(&tmp_db_path, db_path)
+        .with_context(|| format!("moving restored database to {}", db_path.display()))?;
+    info!(
+        target: "checkpoint",
+        head_height = manifest.head_height,
+        checks,
+        "Restored and validated checkpoint"
+    );
+    Ok(manifest)
+}
+
+/// Checks that the database restored in `db_path` matches the manifest and
+/// passes [`StoreValidator`].  Returns the number of checks done.
+fn validate_restored_db(
+    home_dir: &Path,
+    near_config: &NearConfig,
+    manifest: &CheckpointManifest,
+    db_path: &Path,
+) -> anyhow::Result<u64> {
+    let mut store_config = near_config.config.store.clone();
+    store_config.path = Some(db_path.to_path_buf());
+    let store = Store::opener(home_dir, &store_config).open();
+    for (key, hash) in [
+        (near_store::HEAD_KEY as &[u8], manifest.head_hash),
+        (near_store::FINAL_HEAD_KEY, manifest.final_head_hash),
+    ] {
+        let tip: Option<near_primitives::block::Tip> =
+            store.get_ser(near_store::DBCol::BlockMisc, key)?;
+        anyhow::ensure!(
+            tip.map(|tip| tip.last_block_hash) == Some(hash),
+            "restored {} doesn’t match the checkpoint manifest",
+            String::from_utf8_lossy(key)
+        );
+    }
+
+    let runtime = Arc::new(NightshadeRuntime::from_config(home_dir, store.clone(), near_config));
+    let mut store_validator = StoreValidator::new(
+        near_config.validator_signer.as_ref().map(|signer| signer.validator_id().clone()),
+        near_config.genesis.config.clone(),
+        runtime,
+        store,
+        near_config.client_config.archive,
+    );
+    store_validator.validate();
+    for err in store_validator.errors.iter() {
+        error!(target: "checkpoint", col = %err.col, key = %err.key, "{}", err.err);
+    }
+    anyhow::ensure!(
+        !store_validator.is_failed(),
+        "restored database failed validation with {} errors",
+        store_validator.num_failed()
+    );
+    Ok(store_validator.tests_done())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_primitives::hash::CryptoHash;
+
+    #[test]
+    fn test_restore_invalid_checkpoint() {
+        let home_dir = tempfile::tempdir().unwrap();
+        let home_dir = home_dir.path();
+        crate::config::init_configs(
+            home_dir,
+            Some("localnet"),
+            None,
+            Some("seed"),
+            1,
+            false,
+            None,
+            false,
+            None,
+            false,
+            None,
+            None,
+            None,
+        )
+        .unwrap();
+        let near_config = load_config(home_dir, GenesisValidationMode::UnsafeFast).unwrap();
+        let opener = Store::opener(home_dir, &near_config.config.store);
+        let db_path = opener.get_path().to_path_buf();
+        {
+            let store = opener.open();
+            near_store::migrations::set_store_version(&store, near_primitives::version::DB_VERSION);
+            let runtime = Arc::new(NightshadeRuntime::from_config(home_dir, store, &near_config));
+            let chain_genesis = ChainGenesis::new(&near_config.genesis);
+            Chain::new(runtime, &chain_genesis, DoomslugThresholdMode::TwoThirds, true).unwrap();
+        }
+        let checkpoint_dir = tempfile::tempdir().unwrap();
+        let checkpoint_dir = checkpoint_dir.path().join("checkpoint");
+        let manifest =
+            create_checkpoint(home_dir, GenesisValidationMode::UnsafeFast, &checkpoint_dir, false)
+                .unwrap();
+        std::fs::remove_dir_all(&db_path).unwrap();
+
+        // A checkpoint whose head doesn’t match the manifest leaves nothing behind.
+        CheckpointManifest { head_hash: CryptoHash::default(), ..manifest }
+            .write(&checkpoint_dir)
+            .unwrap();
+        let err = restore_checkpoint(home_dir, GenesisValidationMode::UnsafeFast, &checkpoint_dir)
+            .unwrap_err();
+        assert!(
+            format!("{:#}", err).contains("doesn’t match the checkpoint manifest"),
+            "{:#}",
+            err
+        );
+        assert!(!db_path.exists());
+        for entry in std::fs::read_dir(db_path.parent().unwrap()).unwrap() {
+            let name = entry.unwrap().file_name();
+            assert!(!name.to_string_lossy().starts_with(".restore-"), "{:?} left behind", name);
+        }
+
+        manifest.write(&checkpoint_dir).unwrap();
+        assert_eq!(
+            restore_checkpoint(home_dir, GenesisValidationMode::UnsafeFast, &checkpoint_dir)
+                .unwrap(),
+            manifest
+        );
+        assert!(opener.check_if_exists());
+    }
+}
@@ -16,7 +16,7 @@ use tracing::{info, warn};
 
 use near_chain_configs::{
     get_initial_supply, ClientConfig, GCConfig, Genesis, GenesisConfig, GenesisValidationMode,
-    LogSummaryStyle,
+    LogSummaryStyle, TransactionPoolConfig,
 };
 use near_crypto::{InMemorySigner, KeyFile, KeyType, PublicKey, Signer};
 #[cfg(feature = "json_rpc")]
@@ -317,6 +317,8 @@ pub struct Config {
     /// If set, overrides value in genesis configuration.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_gas_burnt_view: Option<Gas>,
+    /// Limits of the transaction pools.
+    pub transaction_pool: TransactionPoolConfig,
     /// Checkpoints let the user recover from interrupted DB migrations.
     #[serde(default = "default_use_checkpoints_for_db_migration")]
     pub use_db_migration_snapshot: bool,
@@ -328,6 +330,26 @@ pub struct Config {
     pub db_migration_snapshot_path: Option<PathBuf>,
     /// Different parameters to configure/optimize underlying storage.
     pub store: near_store::StoreConfig,
+    /// Configuration of the cold database of an archival node.  If set, data
+    /// behind the garbage collection horizon is moved from the main database
+    /// to the cold one, which may be placed on a slower and cheaper disk.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cold_store: Option<near_store::StoreConfig>,
+    /// If set, state headers and parts of the tracked shards are dumped into
+    /// this directory at the start of every epoch, so that other nodes can
+    /// state sync from it (see `state_sync_external_dir`).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_sync_dump_dir: Option<PathBuf>,
+    /// If set, state sync reads state headers and parts from this directory,
+    /// e.g. a mounted dump of another node, before requesting them from the
+    /// peers.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state_sync_external_dir: Option<PathBuf>,
+    /// If set, database checkpoints can be created in this directory through
+    /// the `/debug/api/checkpoint` endpoint.  Keep it on the same filesystem
+    /// as the database so that the checkpoint files are hard links.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checkpoint_dir: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -354,9 +376,14 @@ impl Default for Config {
             view_client_throttle_period: default_view_client_throttle_period(),
             trie_viewer_state_size_limit: default_trie_viewer_state_size_limit(),
             max_gas_burnt_view: None,
+            transaction_pool: TransactionPoolConfig::default(),
             db_migration_snapshot_path: None,
             use_db_migration_snapshot: true,
             store: near_store::StoreConfig::default(),
+            cold_store: None,
+            state_sync_dump_dir: None,
+            state_sync_external_dir: None,
+            checkpoint_dir: None,
         }
     }
 }
@@ -366,7 +393,7 @@ impl Config {
         let mut unrecognised_fields = Vec::new();
         let s = std::fs::read_to_string(path)
             .with_context(|| format!("Failed to read config from {}", path.display()))?;
-        let config =
+        let mut config: Self =
             serde_ignored::deserialize(&mut serde_json::Deserializer::from_str(&s), |path| {
                 unrecognised_fields.push(path.to_string());
             })
@@ -375,6 +402,13 @@ impl Config {
         if !unrecognised_fields.is_empty() {
             warn!("{}: encountered unrecognised fields: {:?}", path.display(), unrecognised_fields);
         }
+        if config.store.apply_trie_cache_capacities() {
+            warn!(
+                "{}: store.trie_cache_capacities is deprecated, converted it into store.trie_cache: {:?}",
+                path.display(),
+                config.store.trie_cache
+            );
+        }
 
         Ok(config)
     }
@@ -546,6 +580,9 @@ impl NearConfig {
                     .consensus
                     .header_sync_expected_height_per_second,
                 state_sync_timeout: config.consensus.state_sync_timeout,
+                state_sync_dump_dir: config.state_sync_dump_dir.clone(),
+                state_sync_external_dir: config.state_sync_external_dir.clone(),
+                checkpoint_dir: config.checkpoint_dir.clone(),
                 min_num_peers: config.consensus.min_num_peers,
                 log_summary_period: Duration::from_secs(10),
                 produce_empty_blocks: config.consensus.produce_empty_blocks,
@@ -571,6 +608,7 @@ impl NearConfig {
                 trie_viewer_state_size_limit: config.trie_viewer_state_size_limit,
                 max_gas_burnt_view: config.max_gas_burnt_view,
                 enable_statistics_export: config.store.enable_statistics_export,
+                transaction_pool: config.transaction_pool,
             },
             network_config: NetworkConfig::new(
                 config.network,
@@ -1330,3 +1368,28 @@ fn test_config_from_file() {
         );
     }
 }
+
+/// Tests that the deprecated trie cache capacities are turned into size limits.
+#[test]
+fn test_config_from_file_trie_cache_capacities() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("res/example-config-gc.json");
+    let mut data: serde_json::Value =
+        serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+    data["store"] = serde_json::json!({
+        "trie_cache": {"per_shard_max_bytes": [[{"version": 1, "shard_id": 0}, 1000]]},
+        "trie_cache_capacities": [
+            [{"version": 1, "shard_id": 0}, 10],
+            [{"version": 1, "shard_id": 3}, 2000000],
+        ],
+    });
+    let tmp = tempfile::NamedTempFile::new().unwrap();
+    tmp.as_file().write_all(data.to_string().as_bytes()).unwrap();
+
+    let config = Config::from_file(&tmp.into_temp_path()).unwrap();
+    assert!(config.store.trie_cache_capacities.is_empty());
+    let max_bytes =
+        |shard_id| config.store.trie_cache.max_bytes(near_store::ShardUId { version: 1, shard_id });
+    // Size limits set explicitly are kept.
+    assert_eq!(max_bytes(0), 1000);
+    assert_eq!(max_bytes(3), 2_000_000_000);
+}
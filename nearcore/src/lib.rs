@@ -6,7 +6,8 @@ use actix::{Actor, Addr, Arbiter};
 use actix_rt::ArbiterHandle;
 use actix_web;
 use anyhow::Context;
-use near_chain::ChainGenesis;
+use near_chain::{ChainGenesis, RuntimeAdapter};
+use near_client::external_state_sync::StateDumperHandle;
 use near_client::{start_client, start_view_client, ClientActor, ViewClientActor};
 use near_network::test_utils::NetworkRecipient;
 use near_network::PeerManagerActor;
@@ -17,7 +18,7 @@ use near_rosetta_rpc::start_rosetta_rpc;
 use near_rust_allocator_proxy::reset_memory_usage_max;
 use near_store::db::{Mode, RocksDB};
 use near_store::migrations::{migrate_28_to_29, migrate_29_to_30, set_store_version};
-use near_store::{DBCol, Store, StoreOpener};
+use near_store::{DBCol, ShardTries, Store, StoreOpener};
 use near_telemetry::TelemetryActor;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
@@ -25,6 +26,7 @@ use tokio::sync::oneshot;
 use tracing::{error, info, trace};
 
 pub mod append_only_map;
+pub mod checkpoint;
 pub mod config;
 mod download_file;
 mod metrics;
@@ -165,6 +167,21 @@ fn apply_store_migrations_if_exists(
         info!(target: "near", "Migrate DB from version 30 to 31");
         migrate_30_to_31(store_opener, &near_config);
     }
+    if db_version <= 31 {
+        // version 31 => 32: add DBCol::FlatState, DBCol::FlatStateDeltas and DBCol::FlatStateMisc
+        // Does not need to do anything since open db with option
+        // `create_missing_column_families`.  Flat storage of existing nodes
+        // is not populated, so reads keep going through the trie.
+        info!(target: "near", "Migrate DB from version 31 to 32");
+        set_store_version(&store_opener.open(), 32);
+    }
+    if db_version <= 32 {
+        // version 32 => 33: add DBCol::PeerScores
+        // Does not need to do anything since open db with option
+        // `create_missing_column_families`.  Peers start with default scores.
+        info!(target: "near", "Migrate DB from version 32 to 33");
+        set_store_version(&store_opener.open(), 33);
+    }
 
     if cfg!(feature = "nightly") || cfg!(feature = "nightly_protocol") {
         let store = store_opener.open();
@@ -201,9 +218,14 @@ fn apply_store_migrations_if_exists(
 }
 
 fn init_and_migrate_store(home_dir: &Path, near_config: &NearConfig) -> anyhow::Result<Store> {
+    let cold_store_config = near_config.config.cold_store.as_ref();
+    anyhow::ensure!(
+        cold_store_config.is_none() || near_config.client_config.archive,
+        "Cold storage can only be configured for archival nodes."
+    );
     let opener = Store::opener(home_dir, &near_config.config.store);
     let exists = apply_store_migrations_if_exists(&opener, near_config)?;
-    let store = opener.open();
+    let store = opener.cold(cold_store_config).open();
     if !exists {
         set_store_version(&store, near_primitives::version::DB_VERSION);
     }
@@ -232,6 +254,10 @@ pub struct NearNode {
     pub view_client: Addr<ViewClientActor>,
     pub arbiters: Vec<ArbiterHandle>,
     pub rpc_servers: Vec<(&'static str, actix_web::dev::ServerHandle)>,
+    /// Tries of the shards, shared by the client and the view client.
+    pub shard_tries: ShardTries,
+    /// Thread dumping state parts for external state sync, if enabled.
+    pub state_dumper: Option<StateDumperHandle>,
 }
 
 pub fn start_with_config(home_dir: &Path, config: NearConfig) -> anyhow::Result<NearNode> {
@@ -248,6 +274,7 @@ pub fn start_with_config_and_synchronization(
     let store = init_and_migrate_store(home_dir, &config)?;
 
     let runtime = Arc::new(NightshadeRuntime::from_config(home_dir, store.clone(), &config));
+    let shard_tries = runtime.get_tries();
 
     let telemetry = TelemetryActor::new(config.telemetry_config.clone()).start();
     let chain_genesis = ChainGenesis::new(&config.genesis);
@@ -264,6 +291,17 @@ pub fn start_with_config_and_synchronization(
         config.client_config.clone(),
         adv.clone(),
     );
+    let state_dumper = match &config.client_config.state_sync_dump_dir {
+        Some(dir) => Some(
+            near_client::external_state_sync::spawn_state_dumper(
+                dir.clone(),
+                runtime.clone(),
+                chain_genesis.clone(),
+            )
+            .context("failed to start state dumper")?,
+        ),
+        None => None,
+    };
     let (client_actor, client_arbiter_handle) = start_client(
         config.client_config,
         chain_genesis,
@@ -331,6 +369,8 @@ pub fn start_with_config_and_synchronization(
         view_client,
         rpc_servers,
         arbiters: vec![client_arbiter_handle, arbiter.handle()],
+        shard_tries,
+        state_dumper,
     })
 }
 
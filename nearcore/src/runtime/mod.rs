@@ -5,6 +5,7 @@ use crate::NearConfig;
 use borsh::ser::BorshSerialize;
 use borsh::BorshDeserialize;
 use errors::FromStateViewerErrors;
+use near_chain::near_chain_primitives::error::SimulateTransactionError;
 use near_chain::types::{
     ApplySplitStateResult, ApplyTransactionResult, BlockHeaderInfo, ValidatorInfoIdentifier,
 };
@@ -19,13 +20,14 @@ use near_pool::types::PoolIterator;
 use near_primitives::account::{AccessKey, Account};
 use near_primitives::block::{Approval, ApprovalInner};
 use near_primitives::challenge::ChallengesResult;
+use near_primitives::checked_feature;
 use near_primitives::contract::ContractCode;
 use near_primitives::epoch_manager::block_info::BlockInfo;
 use near_primitives::epoch_manager::epoch_info::EpochInfo;
 use near_primitives::epoch_manager::{EpochConfig, ShardConfig};
-use near_primitives::errors::{EpochError, InvalidTxError, RuntimeError};
+use near_primitives::errors::{EpochError, InvalidTxError, RuntimeError, StorageError};
 use near_primitives::hash::{hash, CryptoHash};
-use near_primitives::receipt::Receipt;
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt, ReceiptEnum};
 use near_primitives::runtime::config_store::RuntimeConfigStore;
 use near_primitives::runtime::migration_data::{MigrationData, MigrationFlags};
 use near_primitives::sandbox_state_patch::SandboxStatePatch;
@@ -36,7 +38,8 @@ use near_primitives::sharding::ChunkHash;
 use near_primitives::state_part::PartId;
 use near_primitives::state_record::{state_record_to_account_id, StateRecord};
 use near_primitives::syncing::{get_num_state_parts, STATE_PART_MEMORY_LIMIT};
-use near_primitives::transaction::SignedTransaction;
+use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
+use near_primitives::trie_key::TrieKey;
 use near_primitives::types::validator_stake::{ValidatorStake, ValidatorStakeIter};
 use near_primitives::types::{
     AccountId, ApprovalStake, Balance, BlockHeight, CompiledContractCache, EpochHeight, EpochId,
@@ -52,7 +55,8 @@ use near_store::split_state::get_delayed_receipts;
 use near_store::{
     get_genesis_hash, get_genesis_state_roots, set_genesis_hash, set_genesis_state_roots,
     ApplyStatePartResult, DBCol, PartialStorage, ShardTries, Store, StoreCompiledContractCache,
-    StoreUpdate, Trie, TrieCacheFactory, WrappedTrieChanges,
+    StoreUpdate, Trie, TrieCacheConfig, TrieCacheFactory, TrieChanges, TriePrefetchConfig,
+    WrappedTrieChanges,
 };
 use near_vm_runner::precompile_contract;
 use node_runtime::adapter::ViewRuntimeAdapter;
@@ -64,7 +68,7 @@ use node_runtime::{
     ValidatorAccountsUpdate,
 };
 use std::cmp::Ordering;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::path::Path;
 use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
@@ -155,7 +159,8 @@ impl NightshadeRuntime {
             config.client_config.max_gas_burnt_view,
             None,
             config.config.gc.gc_num_epochs_to_keep(),
-            config.config.store.trie_cache_capacities.clone(),
+            &config.config.store.trie_cache,
+            &config.config.store.trie_prefetch,
         )
     }
 
@@ -168,7 +173,8 @@ impl NightshadeRuntime {
         max_gas_burnt_view: Option<Gas>,
         runtime_config_store: Option<RuntimeConfigStore>,
         gc_num_epochs_to_keep: u64,
-        trie_cache_capacities: Vec<(ShardUId, usize)>,
+        trie_cache_config: &TrieCacheConfig,
+        trie_prefetch_config: &TriePrefetchConfig,
     ) -> Self {
         let runtime_config_store = match runtime_config_store {
             Some(store) => store,
@@ -188,10 +194,11 @@ impl NightshadeRuntime {
         let state_roots =
             Self::initialize_genesis_state_if_needed(store.clone(), home_dir, genesis);
         let trie_cache_factory = TrieCacheFactory::new(
-            trie_cache_capacities.into_iter().collect(),
+            trie_cache_config.clone(),
             genesis_config.shard_layout.version(),
             genesis.config.num_block_producer_seats_per_shard.len() as NumShards,
-        );
+        )
+        .with_prefetch_config(trie_prefetch_config);
         let tries = ShardTries::new(store.clone(), trie_cache_factory);
         let epoch_manager = Arc::new(RwLock::new(
             EpochManager::new_from_genesis_config(store.clone(), &genesis_config)
@@ -229,7 +236,8 @@ impl NightshadeRuntime {
             None,
             Some(runtime_config_store),
             DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
-            Default::default(),
+            &Default::default(),
+            &Default::default(),
         )
     }
 
@@ -544,6 +552,7 @@ impl NightshadeRuntime {
             config: self.runtime_config_store.get_config(current_protocol_version).clone(),
             cache: Some(Arc::new(StoreCompiledContractCache { store: self.store.clone() })),
             is_new_chunk,
+            verify_signatures: true,
             migration_data: Arc::clone(&self.migration_data),
             migration_flags: MigrationFlags {
                 is_first_block_of_version,
@@ -718,6 +727,11 @@ impl RuntimeAdapter for NightshadeRuntime {
 
     fn get_trie_for_shard(&self, shard_id: ShardId, prev_hash: &CryptoHash) -> Result<Trie, Error> {
         let shard_uid = self.get_shard_uid_from_prev_hash(shard_id, prev_hash)?;
+        let protocol_version =
+            self.get_epoch_protocol_version(&self.get_epoch_id_from_prev_block(prev_hash)?)?;
+        if checked_feature!("protocol_feature_flat_state", FlatStorageReads, protocol_version) {
+            return Ok(self.tries.get_trie_with_block_hash_for_shard(shard_uid, *prev_hash));
+        }
         Ok(self.tries.get_trie_for_shard(shard_uid))
     }
 
@@ -1634,6 +1648,164 @@ impl RuntimeAdapter for NightshadeRuntime {
         }
     }
 
+    fn simulate_transaction(
+        &self,
+        state_roots: &HashMap<ShardId, StateRoot>,
+        block_height: BlockHeight,
+        block_timestamp: u64,
+        prev_block_hash: &CryptoHash,
+        block_hash: &CryptoHash,
+        epoch_id: &EpochId,
+        gas_price: Balance,
+        random_seed: CryptoHash,
+        gas_limit: Gas,
+        transaction: &SignedTransaction,
+        verify_signature: bool,
+    ) -> Result<Vec<ExecutionOutcomeWithId>, SimulateTransactionError> {
+        let (shard_layout, epoch_height, current_protocol_version) = {
+            let epoch_manager = self.epoch_manager.read();
+            let epoch_info = epoch_manager.get_epoch_info(epoch_id).map_err(Error::from)?;
+            let shard_layout = epoch_manager.get_shard_layout(epoch_id).map_err(Error::from)?;
+            (shard_layout.clone(), epoch_info.epoch_height(), epoch_info.protocol_version())
+        };
+        let apply_state = ApplyState {
+            block_index: block_height,
+            prev_block_hash: *prev_block_hash,
+            block_hash: *block_hash,
+            epoch_id: epoch_id.clone(),
+            epoch_height,
+            gas_price,
+            block_timestamp,
+            // Receipts over the limit are delayed and executed by the subsequent applies, like
+            // they would be on chain.
+            gas_limit: Some(gas_limit),
+            random_seed,
+            current_protocol_version,
+            config: self.runtime_config_store.get_config(current_protocol_version).clone(),
+            cache: Some(Arc::new(StoreCompiledContractCache { store: self.store.clone() })),
+            is_new_chunk: true,
+            verify_signatures: verify_signature,
+            migration_data: Arc::clone(&self.migration_data),
+            migration_flags: MigrationFlags::default(),
+        };
+
+        // Changes made to every shard so far.  They are never committed, but served on top of the
+        // shard state for the subsequent applies.
+        let mut shard_changes: HashMap<ShardId, Vec<TrieChanges>> = HashMap::new();
+        // Shards may have delayed receipts of their own which get executed along the way.  Only
+        // the outcomes and receipts originating from the transaction are kept.
+        let mut known_ids = HashSet::from([transaction.get_hash()]);
+        let mut known_data_ids = HashSet::new();
+        let mut outcomes = vec![];
+
+        let mut transactions = vec![transaction.clone()];
+        let signer_shard_id =
+            account_id_to_shard_id(&transaction.transaction.signer_id, &shard_layout);
+        let mut incoming_receipts = BTreeMap::from([(signer_shard_id, vec![])]);
+        let delayed_receipt_indices_key = TrieKey::DelayedReceiptIndices.to_vec();
+        let shard_trie = |shard_uid, changes: &[TrieChanges]| {
+            let mut trie = self.tries.get_view_trie_for_shard(shard_uid);
+            for trie_changes in changes {
+                trie = trie.with_uncommitted_changes(trie_changes);
+            }
+            trie
+        };
+        let get_delayed_receipt_indices = |trie: &Trie, state_root: &StateRoot| {
+            let indices = match trie.get(state_root, &delayed_receipt_indices_key)? {
+                Some(bytes) => DelayedReceiptIndices::try_from_slice(&bytes).map_err(|err| {
+                    StorageError::StorageInconsistentState(format!(
+                        "Failed to deserialize delayed receipt indices: {}",
+                        err
+                    ))
+                })?,
+                None => DelayedReceiptIndices::default(),
+            };
+            Ok::<_, Error>(indices)
+        };
+        while !incoming_receipts.is_empty() {
+            let mut outgoing_receipts = vec![];
+            for (shard_id, receipts) in std::mem::take(&mut incoming_receipts) {
+                let state_root = *state_roots
+                    .get(&shard_id)
+                    .ok_or(SimulateTransactionError::UnavailableShard { shard_id })?;
+                let shard_uid = ShardUId::from_shard_id_and_layout(shard_id, &shard_layout);
+                let changes = shard_changes.entry(shard_id).or_default();
+                if changes.is_empty() {
+                    // The receipts delayed in the shard already are unrelated to the transaction,
+                    // they are skipped so that only the receipts of the transaction are executed.
+                    let trie = shard_trie(shard_uid, changes);
+                    let mut indices = get_delayed_receipt_indices(&trie, &state_root)?;
+                    if indices.first_index < indices.next_available_index {
+                        indices.first_index = indices.next_available_index;
+                        let value = indices.try_to_vec().expect("borsh cannot fail");
+                        let update = vec![(delayed_receipt_indices_key.clone(), Some(value))];
+                        changes.push(
+                            trie.update(&state_root, update.into_iter()).map_err(Error::from)?,
+                        );
+                    }
+                }
+                let trie = shard_trie(shard_uid, changes);
+                let state_root = changes.last().map_or(state_root, |changes| changes.new_root);
+                let apply_result = self
+                    .runtime
+                    .apply(
+                        trie,
+                        state_root,
+                        &None,
+                        &apply_state,
+                        &receipts,
+                        &std::mem::take(&mut transactions),
+                        &self.epoch_manager,
+                        None,
+                    )
+                    .map_err(|err| match err {
+                        RuntimeError::InvalidTxError(err) => {
+                            SimulateTransactionError::InvalidTransaction(err)
+                        }
+                        err => SimulateTransactionError::InternalError {
+                            error_message: format!("{:?}", err),
+                        },
+                    })?;
+                changes.push(apply_result.trie_changes);
+                // Receipts of the transaction delayed by the gas limit are executed by the next
+                // apply of the shard.
+                let indices = get_delayed_receipt_indices(
+                    &shard_trie(shard_uid, changes),
+                    &apply_result.state_root,
+                )?;
+                if indices.first_index < indices.next_available_index {
+                    incoming_receipts.entry(shard_id).or_default();
+                }
+                for outcome in apply_result.outcomes {
+                    if known_ids.contains(&outcome.id) {
+                        known_ids.extend(outcome.outcome.receipt_ids.iter().cloned());
+                        outcomes.push(outcome);
+                    }
+                }
+                outgoing_receipts.extend(apply_result.outgoing_receipts);
+            }
+            for receipt in outgoing_receipts {
+                let is_known = match &receipt.receipt {
+                    ReceiptEnum::Action(action_receipt) => {
+                        let is_known = known_ids.contains(&receipt.receipt_id);
+                        if is_known {
+                            known_data_ids.extend(action_receipt.input_data_ids.iter().cloned());
+                        }
+                        is_known
+                    }
+                    ReceiptEnum::Data(data_receipt) => {
+                        known_data_ids.contains(&data_receipt.data_id)
+                    }
+                };
+                if is_known {
+                    let shard_id = account_id_to_shard_id(&receipt.receiver_id, &shard_layout);
+                    incoming_receipts.entry(shard_id).or_default().push(receipt);
+                }
+            }
+        }
+        Ok(outcomes)
+    }
+
     fn get_validator_info(
         &self,
         epoch_id: ValidatorInfoIdentifier,
@@ -1699,7 +1871,7 @@ impl RuntimeAdapter for NightshadeRuntime {
 
         Ok(trie_changes
             .into_iter()
-            .map(|(shard_uid, trie_changes)| ApplySplitStateResult {
+            .map(|(shard_uid, (trie_changes, state_changes))| ApplySplitStateResult {
                 shard_uid,
                 new_root: trie_changes.new_root,
                 trie_changes: WrappedTrieChanges::new(
@@ -1709,6 +1881,7 @@ impl RuntimeAdapter for NightshadeRuntime {
                     vec![],
                     *block_hash,
                 ),
+                state_changes,
             })
             .collect())
     }
@@ -2014,7 +2187,7 @@ mod test {
     use near_logger_utils::init_test_logger;
     use near_primitives::block::Tip;
     use near_primitives::challenge::SlashedValidator;
-    use near_primitives::transaction::{Action, DeleteAccountAction, StakeAction};
+    use near_primitives::transaction::{Action, DeleteAccountAction, ExecutionStatus, StakeAction};
     use near_primitives::types::{BlockHeightDelta, Nonce, ValidatorId, ValidatorKickoutReason};
     use near_primitives::validator_signer::{InMemoryValidatorSigner, ValidatorSigner};
     use near_primitives::views::{
@@ -2181,7 +2354,9 @@ mod test {
                 None,
                 Some(RuntimeConfigStore::free()),
                 DEFAULT_GC_NUM_EPOCHS_TO_KEEP,
-                Default::default(),
+                &Default::default(),
+                // Prefetching must not change any results.
+                &TriePrefetchConfig { enable: true, ..Default::default() },
             );
             let (_store, state_roots) = runtime.genesis_state();
             let genesis_hash = hash(&vec![0]);
@@ -3431,6 +3606,90 @@ mod test {
         }
     }
 
+    /// Simulation executes the receipts of the transaction on the shards of their receivers
+    /// without changing the state, and verifies the signature only when asked to.  Every apply
+    /// burns at most the given gas limit.
+    #[test]
+    fn test_simulate_transaction() {
+        init_test_logger();
+        let num_nodes = 2;
+        let validators = (0..num_nodes)
+            .map(|i| AccountId::try_from(format!("test{}", i + 1)).unwrap())
+            .collect::<Vec<_>>();
+        let mut env = TestEnv::new(vec![validators.clone(), validators.clone()], 4, false);
+        env.step(vec![vec![], vec![]], vec![true, true], ChallengesResult::default());
+        let signer = InMemorySigner::from_seed(
+            validators[0].clone(),
+            KeyType::ED25519,
+            validators[0].as_ref(),
+        );
+        let transaction = SignedTransaction::send_money(
+            1,
+            validators[0].clone(),
+            validators[1].clone(),
+            &signer,
+            100,
+            CryptoHash::default(),
+        );
+        let state_roots: HashMap<_, _> = env
+            .state_roots
+            .iter()
+            .enumerate()
+            .map(|(shard_id, state_root)| (shard_id as ShardId, *state_root))
+            .collect();
+        let simulate_with_gas_limit =
+            |transaction: &SignedTransaction, verify_signature, gas_limit| {
+                env.runtime.simulate_transaction(
+                    &state_roots,
+                    env.head.height + 1,
+                    0,
+                    &env.head.last_block_hash,
+                    &CryptoHash::default(),
+                    &env.head.epoch_id,
+                    env.runtime.genesis_config.min_gas_price,
+                    CryptoHash::default(),
+                    gas_limit,
+                    transaction,
+                    verify_signature,
+                )
+            };
+        let simulate = |transaction: &SignedTransaction, verify_signature| {
+            simulate_with_gas_limit(
+                transaction,
+                verify_signature,
+                env.runtime.genesis_config.gas_limit,
+            )
+        };
+
+        let accounts: HashMap<_, _> = validators
+            .iter()
+            .map(|account_id| (account_id, env.view_account(account_id)))
+            .collect();
+        let outcomes = simulate(&transaction, true).unwrap();
+        assert_eq!(outcomes.len(), 2);
+        assert_eq!(outcomes[0].id, transaction.get_hash());
+        assert_eq!(outcomes[0].outcome.receipt_ids, vec![outcomes[1].id]);
+        assert_eq!(outcomes[1].outcome.executor_id, validators[1]);
+        assert_eq!(outcomes[1].outcome.status, ExecutionStatus::SuccessValue(vec![]));
+        for account_id in &validators {
+            assert_eq!(env.view_account(account_id), accounts[account_id]);
+        }
+
+        let unsigned_transaction = SignedTransaction::new(
+            Signature::empty(KeyType::ED25519),
+            transaction.transaction.clone(),
+        );
+        assert!(matches!(
+            simulate(&unsigned_transaction, true),
+            Err(SimulateTransactionError::InvalidTransaction(InvalidTxError::InvalidSignature))
+        ));
+        assert_eq!(simulate(&unsigned_transaction, false).unwrap().len(), 2);
+
+        // Receipts delayed by the gas limit are executed by the subsequent applies.
+        let delayed_outcomes = simulate_with_gas_limit(&transaction, true, 1).unwrap();
+        assert_eq!(delayed_outcomes, outcomes);
+    }
+
     #[test]
     fn test_proposal_deduped() {
         let num_nodes = 2;
@@ -1,13 +1,17 @@
 use crate::log_config_watcher::{LogConfigWatcher, UpdateBehavior};
 use actix::SystemRunner;
+use anyhow::Context as _;
 use clap::{Args, Parser};
 use near_chain_configs::GenesisValidationMode;
+use near_network_primitives::types::PeerInfo;
 use near_o11y::{
     default_subscriber, BuildEnvFilterError, DefaultSubscriberGuard, EnvFilterBuilder,
 };
+use near_primitives::network::PeerId;
 use near_primitives::types::{Gas, NumSeats, NumShards};
 use near_state_viewer::StateViewerSubCommand;
 use near_store::db::{Mode, RocksDB};
+use near_store::ShardTries;
 use std::cell::Cell;
 use std::net::SocketAddr;
 use std::path::{Path, PathBuf};
@@ -77,6 +81,14 @@ impl NeardCmd {
             NeardSubCommand::RecompressStorage(cmd) => {
                 cmd.run(&home_dir);
             }
+
+            NeardSubCommand::Checkpoint(cmd) => {
+                cmd.run(&home_dir, genesis_validation);
+            }
+
+            NeardSubCommand::NetworkReplay(cmd) => {
+                cmd.run(runtime);
+            }
         };
         Ok(())
     }
@@ -186,6 +198,22 @@ pub(super) enum NeardSubCommand {
     /// tool, it is planned to be removed by the end of 2022.
     #[clap(alias = "recompress_storage")]
     RecompressStorage(RecompressStorageSubCommand),
+    /// Creates, prunes and restores checkpoints of the database.
+    ///
+    /// A checkpoint is a consistent copy of the database together with a
+    /// manifest recording the head and the last final block it contains.  To
+    /// checkpoint a running node, configure `checkpoint_dir` and query the
+    /// `/debug/api/checkpoint` endpoint instead.
+    #[clap(subcommand)]
+    Checkpoint(CheckpointSubCommand),
+    /// Replays the messages a node received from its peers, recorded with the
+    /// `network.capture` config option, against a running node.
+    ///
+    /// Connects to the target node as a new peer and sends it the recorded
+    /// messages, to reproduce peer protocol issues offline.  The target has to
+    /// be on the same chain as the node the messages were captured on.
+    #[clap(alias = "network_replay")]
+    NetworkReplay(NetworkReplaySubCommand),
 }
 
 #[derive(Parser)]
@@ -412,17 +440,22 @@ impl RunCmd {
         let (tx, rx) = oneshot::channel::<()>();
         let sys = new_actix_system(runtime);
         sys.block_on(async move {
-            let nearcore::NearNode { rpc_servers, .. } =
+            let nearcore::NearNode { rpc_servers, shard_tries, state_dumper, .. } =
                 nearcore::start_with_config_and_synchronization(home_dir, near_config, Some(tx))
                     .expect("start_with_config");
 
-            let sig = wait_for_interrupt_signal(home_dir, rx).await;
+            let sig = wait_for_interrupt_signal(home_dir, rx, &shard_tries).await;
             warn!(target: "neard", "{}, stopping... this may take a few minutes.", sig);
             futures::future::join_all(rpc_servers.iter().map(|(name, server)| async move {
                 server.stop(true).await;
                 debug!(target: "neard", "{} server stopped", name);
             }))
             .await;
+            if let Some(state_dumper) = state_dumper {
+                // The dumper keeps the database open until it stops.
+                tokio::task::spawn_blocking(|| state_dumper.stop()).await.unwrap();
+                debug!(target: "neard", "State dumper stopped");
+            }
             actix::System::current().stop();
             opentelemetry::global::shutdown_tracer_provider(); // Finish sending spans.
         });
@@ -446,14 +479,22 @@ fn new_actix_system(runtime: Runtime) -> SystemRunner {
 }
 
 #[cfg(not(unix))]
-async fn wait_for_interrupt_signal(_home_dir: &Path, mut _rx_crash: Receiver<()>) -> &str {
+async fn wait_for_interrupt_signal(
+    _home_dir: &Path,
+    mut _rx_crash: Receiver<()>,
+    _shard_tries: &ShardTries,
+) -> &'static str {
     // TODO(#6372): Support graceful shutdown on windows.
     tokio::signal::ctrl_c().await.unwrap();
     "Ctrl+C"
 }
 
 #[cfg(unix)]
-async fn wait_for_interrupt_signal(home_dir: &Path, mut rx_crash: Receiver<()>) -> &str {
+async fn wait_for_interrupt_signal(
+    home_dir: &Path,
+    mut rx_crash: Receiver<()>,
+    shard_tries: &ShardTries,
+) -> &'static str {
     let watched_path = home_dir.join("log_config.json");
     let log_config_watcher = LogConfigWatcher { watched_path };
     // Apply the logging config file if it exists.
@@ -470,6 +511,7 @@ async fn wait_for_interrupt_signal(home_dir: &Path, mut rx_crash: Receiver<()>)
              _ = sigterm.recv() => "SIGTERM",
              _ = sighup.recv() => {
                 log_config_watcher.update(UpdateBehavior::UpdateOrReset);
+                update_trie_cache_config(home_dir, shard_tries);
                 continue;
              },
              _ = &mut rx_crash => "ClientActor died",
@@ -477,6 +519,21 @@ async fn wait_for_interrupt_signal(home_dir: &Path, mut rx_crash: Receiver<()>)
     }
 }
 
+/// Applies the trie cache limits from the config file to the running node.
+#[cfg(unix)]
+fn update_trie_cache_config(home_dir: &Path, shard_tries: &ShardTries) {
+    let config_path = home_dir.join(nearcore::config::CONFIG_FILENAME);
+    match nearcore::config::Config::from_file(&config_path) {
+        Ok(config) => {
+            info!(target: "neard", trie_cache=?config.store.trie_cache, "Changing the trie cache config.");
+            shard_tries.update_cache_config(&config.store.trie_cache);
+        }
+        Err(err) => {
+            error!(target: "neard", ?err, "Failed to update the trie cache config.");
+        }
+    }
+}
+
 #[derive(Parser)]
 pub(super) struct LocalnetCmd {
     /// Number of non-validators to initialize the localnet with.
@@ -556,6 +613,112 @@ impl RecompressStorageSubCommand {
     }
 }
 
+#[derive(Parser)]
+pub(super) enum CheckpointSubCommand {
+    /// Creates a checkpoint of the database.  The node must not be running.
+    Create {
+        /// Directory to create the checkpoint in.  It must not exist.
+        #[clap(long)]
+        output_dir: PathBuf,
+        /// Garbage collect the checkpoint as a non-archival node would.
+        #[clap(long)]
+        prune: bool,
+    },
+    /// Garbage collects an existing checkpoint as a non-archival node would.
+    Prune {
+        /// Directory of the checkpoint.
+        #[clap(long)]
+        checkpoint_dir: PathBuf,
+    },
+    /// Restores a checkpoint as the node’s database and validates it.  The
+    /// node’s database must not exist.
+    Restore {
+        /// Directory of the checkpoint.
+        #[clap(long)]
+        checkpoint_dir: PathBuf,
+    },
+}
+
+impl CheckpointSubCommand {
+    pub(super) fn run(self, home_dir: &Path, genesis_validation: GenesisValidationMode) {
+        let result = match self {
+            Self::Create { output_dir, prune } => nearcore::checkpoint::create_checkpoint(
+                home_dir,
+                genesis_validation,
+                &output_dir,
+                prune,
+            ),
+            Self::Prune { checkpoint_dir } => nearcore::checkpoint::prune_checkpoint(
+                home_dir,
+                genesis_validation,
+                &checkpoint_dir,
+            ),
+            Self::Restore { checkpoint_dir } => nearcore::checkpoint::restore_checkpoint(
+                home_dir,
+                genesis_validation,
+                &checkpoint_dir,
+            ),
+        };
+        match result {
+            Ok(manifest) => info!(
+                target: "neard",
+                head_height = manifest.head_height,
+                final_head_height = manifest.final_head_height,
+                pruned = manifest.pruned,
+                "Done"
+            ),
+            Err(err) => {
+                error!("{:#}", err);
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+#[derive(Args)]
+#[clap(arg_required_else_help = true)]
+pub(super) struct NetworkReplaySubCommand {
+    /// Capture file, or directory with capture files, to replay.
+    #[clap(long)]
+    capture: PathBuf,
+    /// Node to replay the messages against, as peer_id@host:port.
+    #[clap(long)]
+    target: String,
+    /// Replay only the messages received from this peer.
+    #[clap(long)]
+    from: Option<String>,
+    /// Keep the original intervals between the messages instead of sending
+    /// them as fast as possible.
+    #[clap(long)]
+    realtime: bool,
+}
+
+impl NetworkReplaySubCommand {
+    pub(super) fn run(self, runtime: Runtime) {
+        if let Err(err) = runtime.block_on(self.replay()) {
+            error!("{:#}", err);
+            std::process::exit(1);
+        }
+    }
+
+    async fn replay(self) -> anyhow::Result<()> {
+        let target: PeerInfo = self
+            .target
+            .parse()
+            .map_err(|err| anyhow::anyhow!("invalid target {}: {}", self.target, err))?;
+        let from = match &self.from {
+            Some(from) => Some(PeerId::new(from.parse().context("invalid peer id")?)),
+            None => None,
+        };
+        let messages = near_network::capture::read(&self.capture)?;
+        info!(target: "neard", messages = messages.len(), "Read the capture");
+        let count =
+            near_network::capture::replay(&messages, &target, from.as_ref(), self.realtime).await?;
+        info!(target: "neard", count, "Replayed the messages");
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -571,6 +734,44 @@ mod tests {
         }
     }
 
+    #[test]
+    fn checkpoint_subcommands() {
+        let cmd = NeardCmd::parse_from(&[
+            "test",
+            "checkpoint",
+            "create",
+            "--output-dir=/tmp/cp",
+            "--prune",
+        ]);
+        if let NeardSubCommand::Checkpoint(CheckpointSubCommand::Create { output_dir, prune }) =
+            cmd.subcmd
+        {
+            assert_eq!(output_dir, PathBuf::from("/tmp/cp"));
+            assert!(prune);
+        } else {
+            panic!("incorrect subcommand");
+        }
+        assert!(NeardCmd::try_parse_from(&["test", "checkpoint", "restore"]).is_err());
+    }
+
+    #[test]
+    fn network_replay_subcommand() {
+        let cmd = NeardCmd::parse_from(&[
+            "test",
+            "network-replay",
+            "--capture=/tmp/capture",
+            "--target=ed25519:C6HLP37VJN1Wj2irxxZPsVsSya92Rnx12tqK3us5erKV@127.0.0.1:24567",
+        ]);
+        if let NeardSubCommand::NetworkReplay(scmd) = cmd.subcmd {
+            assert_eq!(scmd.capture, PathBuf::from("/tmp/capture"));
+            assert_eq!(scmd.from, None);
+            assert!(!scmd.realtime);
+        } else {
+            panic!("incorrect subcommand");
+        }
+        assert!(NeardCmd::try_parse_from(&["test", "network-replay", "--capture=/tmp/c"]).is_err());
+    }
+
     #[test]
     fn equal_no_value_syntax() {
         assert!(NeardCmd::try_parse_from(&[
@@ -54,6 +54,15 @@ extern "C" {
         malleability_flag: u64,
         register_id: u64,
     ) -> u64;
+    #[cfg(feature = "nightly")]
+    fn ed25519_verify(
+        sig_len: u64,
+        sig_ptr: u64,
+        msg_len: u64,
+        msg_ptr: u64,
+        pub_key_len: u64,
+        pub_key_ptr: u64,
+    ) -> u64;
     // #####################
     // # Miscellaneous API #
     // #####################
@@ -467,6 +476,70 @@ pub unsafe fn ecrecover_10k() {
     }
 }
 
+// Function to measure `ed25519_verify_base`. Also measures `base` and `read_memory_*`. However
+// signature verification is more expensive than memory reads so we are okay overcharging it.
+// Verify a signature of a 32b message 500 times.
+#[cfg(feature = "nightly")]
+#[no_mangle]
+pub unsafe fn ed25519_verify_32b_500() {
+    let message: &[u8; 32] = b"Hello, world! ed25519_verify msg";
+    let signature: [u8; 64] = [
+        0x57, 0x06, 0x8d, 0xb0, 0x3f, 0xdd, 0x13, 0x01, 0x2a, 0xb7, 0x87, 0xd6, 0xe3, 0x83, 0x82,
+        0xfc, 0xe3, 0x29, 0x3e, 0x25, 0xb3, 0x8c, 0x6f, 0xb0, 0x21, 0x1e, 0x0f, 0x1c, 0xd3, 0x8c,
+        0x9d, 0x2e, 0xde, 0xdd, 0x17, 0x70, 0x2f, 0xb2, 0xf7, 0x46, 0x0a, 0xcf, 0x49, 0xee, 0x55,
+        0x36, 0x9a, 0x70, 0x15, 0x10, 0x24, 0x39, 0xcd, 0xc7, 0x72, 0xc1, 0x6b, 0xba, 0x11, 0xc1,
+        0x8a, 0x4e, 0x1f, 0x09,
+    ];
+    let public_key: [u8; 32] = [
+        0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0,
+        0x99, 0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55,
+        0x31, 0xb8,
+    ];
+
+    for _ in 0..500 {
+        ed25519_verify(
+            signature.len() as _,
+            signature.as_ptr() as _,
+            message.len() as _,
+            message.as_ptr() as _,
+            public_key.len() as _,
+            public_key.as_ptr() as _,
+        );
+    }
+}
+
+// Function to measure `ed25519_verify_byte`. Also measures `base`, `read_memory_*` and
+// `ed25519_verify_base`, which are small compared to hashing a 16kib message.
+// Verify a signature of a 16kib message 64 times.
+#[cfg(feature = "nightly")]
+#[no_mangle]
+pub unsafe fn ed25519_verify_16kib_64() {
+    let message = [b'x'; 16 * 1024];
+    let signature: [u8; 64] = [
+        0x51, 0x32, 0x39, 0xd8, 0x5d, 0xec, 0x31, 0x1f, 0x3a, 0x2c, 0x8b, 0xa7, 0x99, 0x02, 0xe7,
+        0xc3, 0x87, 0x5b, 0x9a, 0xd8, 0x1b, 0xd7, 0x59, 0x1d, 0xfe, 0x99, 0xd5, 0xb2, 0x98, 0x76,
+        0x18, 0x63, 0x8d, 0xef, 0x34, 0x69, 0xe9, 0x7b, 0xd4, 0xd0, 0xcb, 0xc6, 0x6e, 0xae, 0xfc,
+        0x69, 0xd8, 0x75, 0x68, 0x64, 0x43, 0x07, 0x85, 0x5a, 0xf2, 0x71, 0x14, 0x48, 0x77, 0x3c,
+        0x8e, 0x13, 0xc2, 0x0a,
+    ];
+    let public_key: [u8; 32] = [
+        0x03, 0xa1, 0x07, 0xbf, 0xf3, 0xce, 0x10, 0xbe, 0x1d, 0x70, 0xdd, 0x18, 0xe7, 0x4b, 0xc0,
+        0x99, 0x67, 0xe4, 0xd6, 0x30, 0x9b, 0xa5, 0x0d, 0x5f, 0x1d, 0xdc, 0x86, 0x64, 0x12, 0x55,
+        0x31, 0xb8,
+    ];
+
+    for _ in 0..64 {
+        ed25519_verify(
+            signature.len() as _,
+            signature.as_ptr() as _,
+            message.len() as _,
+            message.as_ptr() as _,
+            public_key.len() as _,
+            public_key.as_ptr() as _,
+        );
+    }
+}
+
 #[repr(C)]
 struct MultiexpElem([u8; 64], [u8; 32]);
 
@@ -1119,6 +1119,71 @@ impl<'a> VMLogic<'a> {
         Ok(false as u64)
     }
 
+    /// Verifies an ed25519 signature of the given message with the given public key.
+    ///
+    /// Returns a bool indicating whether the signature is valid as a `u64`.
+    ///
+    /// # Errors
+    ///
+    /// * If `signature_ptr`, `message_ptr`, or `public_key_ptr` point outside the memory or the
+    ///   registers use more memory than the limit, then returns `MemoryAccessViolation`.
+    /// * If the signature is not 64 bytes long or the public key is not 32 bytes long, then
+    ///   returns `Ed25519VerifyInvalidInput`.
+    ///
+    /// # Cost
+    ///
+    /// `base + ed25519_verify_base + ed25519_verify_byte * message_len`
+    pub fn ed25519_verify(
+        &mut self,
+        signature_len: u64,
+        signature_ptr: u64,
+        message_len: u64,
+        message_ptr: u64,
+        public_key_len: u64,
+        public_key_ptr: u64,
+    ) -> Result<u64> {
+        use near_crypto::{ED25519PublicKey, KeyType, PublicKey, Signature};
+
+        self.gas_counter.pay_base(base)?;
+        self.gas_counter.pay_base(ed25519_verify_base)?;
+
+        let signature = {
+            let vec = self.get_vec_from_memory_or_register(signature_ptr, signature_len)?;
+            if vec.len() != 64 {
+                return Err(VMLogicError::HostError(HostError::Ed25519VerifyInvalidInput {
+                    msg: format!(
+                        "The length of the signature: {}, does not match the expected 64 bytes",
+                        vec.len()
+                    ),
+                }));
+            }
+            match Signature::from_parts(KeyType::ED25519, &vec) {
+                Ok(signature) => signature,
+                Err(_) => return Ok(false as u64),
+            }
+        };
+
+        let message = self.get_vec_from_memory_or_register(message_ptr, message_len)?;
+        self.gas_counter.pay_per(ed25519_verify_byte, message.len() as u64)?;
+
+        let public_key = {
+            let vec = self.get_vec_from_memory_or_register(public_key_ptr, public_key_len)?;
+            match ED25519PublicKey::try_from(vec.as_slice()) {
+                Ok(public_key) => PublicKey::ED25519(public_key),
+                Err(_) => {
+                    return Err(VMLogicError::HostError(HostError::Ed25519VerifyInvalidInput {
+                        msg: format!(
+                            "The length of the public key: {}, does not match the expected 32 bytes",
+                            vec.len()
+                        ),
+                    }));
+                }
+            }
+        };
+
+        Ok(signature.verify(&message, &public_key) as u64)
+    }
+
     /// Called by gas metering injected into Wasm. Counts both towards `burnt_gas` and `used_gas`.
     ///
     /// # Errors
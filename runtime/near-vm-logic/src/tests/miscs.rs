@@ -618,6 +618,93 @@ fn test_ecrecover() {
     }
 }
 
+#[test]
+fn test_ed25519_verify() {
+    let signature: [u8; 64] = [
+        44, 98, 165, 47, 29, 94, 151, 69, 138, 14, 57, 207, 141, 182, 84, 253, 73, 51, 113, 72,
+        114, 122, 250, 116, 112, 180, 206, 105, 219, 182, 137, 190, 49, 147, 32, 149, 189, 53, 223,
+        111, 123, 6, 245, 145, 151, 204, 101, 163, 73, 76, 238, 75, 227, 61, 231, 240, 219, 17,
+        100, 83, 16, 200, 248, 0,
+    ];
+    let public_key: [u8; 32] = [
+        3, 161, 7, 191, 243, 206, 16, 190, 29, 112, 221, 24, 231, 75, 192, 153, 103, 228, 214, 48,
+        155, 165, 13, 95, 29, 220, 134, 100, 18, 85, 49, 184,
+    ];
+    let message = b"Hello, world!";
+
+    let mut logic_builder = VMLogicBuilder::default();
+    let mut logic = logic_builder.build(get_context(vec![], false));
+
+    let result = logic
+        .ed25519_verify(
+            signature.len() as _,
+            signature.as_ptr() as _,
+            message.len() as _,
+            message.as_ptr() as _,
+            public_key.len() as _,
+            public_key.as_ptr() as _,
+        )
+        .unwrap();
+    assert_eq!(result, 1);
+    assert_costs(map! {
+        ExtCosts::base: 1,
+        ExtCosts::read_memory_base: 3,
+        ExtCosts::read_memory_byte: 64 + 13 + 32,
+        ExtCosts::ed25519_verify_base: 1,
+        ExtCosts::ed25519_verify_byte: 13,
+    });
+    reset_costs_counter();
+
+    let wrong_message = b"Hello, world?";
+    let result = logic
+        .ed25519_verify(
+            signature.len() as _,
+            signature.as_ptr() as _,
+            wrong_message.len() as _,
+            wrong_message.as_ptr() as _,
+            public_key.len() as _,
+            public_key.as_ptr() as _,
+        )
+        .unwrap();
+    assert_eq!(result, 0);
+    reset_costs_counter();
+
+    let result = logic.ed25519_verify(
+        63,
+        signature.as_ptr() as _,
+        message.len() as _,
+        message.as_ptr() as _,
+        public_key.len() as _,
+        public_key.as_ptr() as _,
+    );
+    assert_eq!(
+        result,
+        Err(HostError::Ed25519VerifyInvalidInput {
+            msg: "The length of the signature: 63, does not match the expected 64 bytes"
+                .to_string(),
+        }
+        .into())
+    );
+    reset_costs_counter();
+
+    let result = logic.ed25519_verify(
+        signature.len() as _,
+        signature.as_ptr() as _,
+        message.len() as _,
+        message.as_ptr() as _,
+        31,
+        public_key.as_ptr() as _,
+    );
+    assert_eq!(
+        result,
+        Err(HostError::Ed25519VerifyInvalidInput {
+            msg: "The length of the public key: 31, does not match the expected 32 bytes"
+                .to_string(),
+        }
+        .into())
+    );
+}
+
 #[test]
 fn test_hash256_register() {
     let mut logic_builder = VMLogicBuilder::default();
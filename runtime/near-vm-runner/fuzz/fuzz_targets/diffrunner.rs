@@ -6,18 +6,13 @@ use near_primitives::version::PROTOCOL_VERSION;
 use near_vm_errors::{FunctionCallError, VMError};
 use near_vm_logic::mocks::mock_external::MockedExternal;
 use near_vm_logic::VMConfig;
+use near_vm_runner::differential::run_differential;
 use near_vm_runner::internal::VMKind;
 use near_vm_runner::VMResult;
 use near_vm_runner_fuzz::{create_context, find_entry_point, ArbitraryModule};
 
 libfuzzer_sys::fuzz_target!(|module: ArbitraryModule| {
     let code = ContractCode::new(module.0.module.to_bytes(), None);
-    let wasmer2 = run_fuzz(&code, VMKind::Wasmer2);
-    let wasmtime = run_fuzz(&code, VMKind::Wasmtime);
-    assert_eq!(wasmer2, wasmtime);
-});
-
-fn run_fuzz(code: &ContractCode, vm_kind: VMKind) -> VMResult {
     let mut fake_external = MockedExternal::new();
     let mut context = create_context(vec![]);
     context.prepaid_gas = 10u64.pow(14);
@@ -27,30 +22,32 @@ fn run_fuzz(code: &ContractCode, vm_kind: VMKind) -> VMResult {
 
     let promise_results = vec![];
 
-    let method_name = find_entry_point(code).unwrap_or_else(|| "main".to_string());
-    let res = vm_kind.runtime(config).unwrap().run(
-        code,
+    let method_name = find_entry_point(&code).unwrap_or_else(|| "main".to_string());
+    let (result, mismatch) = run_differential(
+        VMKind::Wasmer2,
+        VMKind::Wasmtime,
+        &code,
         &method_name,
         &mut fake_external,
         context,
+        &config,
         &fees,
         &promise_results,
         PROTOCOL_VERSION,
         None,
     );
-
-    // Remove the VMError message details as they can differ between runtimes
-    // TODO: maybe there's actually things we could check for equality here too?
-    match res {
-        VMResult::Ok(err) => VMResult::Ok(err),
-        VMResult::Aborted(mut outcome, _err) => {
-            outcome.logs = vec!["[censored]".to_owned()];
-            VMResult::Aborted(
-                outcome,
-                VMError::FunctionCallError(FunctionCallError::Nondeterministic(
-                    "[censored]".to_owned(),
-                )),
-            )
+    if let Some(mismatch) = mismatch {
+        // The details of nondeterministic errors differ between runtimes.
+        // TODO: maybe there's actually things we could check for equality here too?
+        if !is_nondeterministic(&result) {
+            panic!("{}", mismatch);
         }
     }
+});
+
+fn is_nondeterministic(result: &VMResult) -> bool {
+    matches!(
+        result.error(),
+        Some(VMError::FunctionCallError(FunctionCallError::Nondeterministic(_)))
+    )
 }
@@ -1,6 +1,7 @@
 use crate::errors::ContractPrecompilatonResult;
 use crate::vm_kind::VMKind;
 use borsh::{BorshDeserialize, BorshSerialize};
+use near_crypto::{PublicKey, Signature, Signer};
 use near_primitives::contract::ContractCode;
 use near_primitives::hash::CryptoHash;
 use near_primitives::types::CompiledContractCache;
@@ -8,6 +9,7 @@ use near_vm_errors::{CacheError, CompilationError};
 use near_vm_logic::{ProtocolVersion, VMConfig};
 use std::collections::HashMap;
 use std::fmt;
+use std::io;
 use std::sync::{Arc, Mutex};
 
 #[cfg(target_arch = "x86_64")]
@@ -57,8 +59,12 @@ pub fn get_contract_cache_key(
     config: &VMConfig,
 ) -> CryptoHash {
     let _span = tracing::debug_span!(target: "vm", "get_key").entered();
+    cache_key(code.hash(), vm_kind, config)
+}
+
+fn cache_key(code_hash: &CryptoHash, vm_kind: VMKind, config: &VMConfig) -> CryptoHash {
     let key = ContractCacheKey::Version4 {
-        code_hash: *code.hash(),
+        code_hash: *code_hash,
         vm_config_non_crypto_hash: config.non_crypto_hash(),
         vm_kind,
         vm_hash: vm_hash(vm_kind),
@@ -416,3 +422,136 @@ pub fn precompile_contract(
     let vm_kind = VMKind::for_protocol_version(current_protocol_version);
     precompile_contract_vm(vm_kind, wasm_code, config, cache)
 }
+
+/// Entry of the compiled contract cache (either the compiled code or the
+/// error of compiling it) taken out of the cache of one node so that it can
+/// be put into the cache of another one.
+///
+/// Compiled code is usable only by the same build of the same VM with the
+/// same config, so the artifact records all of those next to the code hash
+/// and is imported only if they match.  The VM runs compiled code without
+/// further validation, so the artifact is signed by the node which exported
+/// it, and the importing node accepts only artifacts signed by the keys it
+/// trusts.
+#[derive(Debug, Clone, PartialEq, Eq, BorshDeserialize, BorshSerialize)]
+pub struct ContractCacheArtifact {
+    code_hash: CryptoHash,
+    vm_kind: VMKind,
+    vm_config_non_crypto_hash: u64,
+    vm_hash: u64,
+    record: Vec<u8>,
+    signer: PublicKey,
+    /// Signature of `digest()`.
+    signature: Signature,
+}
+
+/// Result of importing a [`ContractCacheArtifact`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractCacheImport {
+    Imported,
+    /// The artifact was produced by another VM, another build of the VM or
+    /// with another VM config.
+    Incompatible,
+    /// The artifact isn’t signed by a trusted key or the signature doesn’t
+    /// match its contents.
+    Untrusted,
+}
+
+impl ContractCacheArtifact {
+    pub fn code_hash(&self) -> &CryptoHash {
+        &self.code_hash
+    }
+
+    pub fn signer(&self) -> &PublicKey {
+        &self.signer
+    }
+
+    /// Hash of everything the artifact says about the compiled code: the code
+    /// hash, the VM and its config, and the cache record itself.
+    fn digest(&self) -> CryptoHash {
+        let fields = (
+            self.code_hash,
+            self.vm_kind,
+            self.vm_config_non_crypto_hash,
+            self.vm_hash,
+            near_primitives::hash::hash(&self.record),
+        );
+        near_primitives::hash::hash(&fields.try_to_vec().unwrap())
+    }
+}
+
+/// Takes the cache entry of the contract with given code hash compiled by the
+/// default VM for the protocol version out of the cache and signs it with
+/// `signer`.  Returns `None` if the contract hasn’t been compiled yet.
+pub fn export_cached_contract(
+    code_hash: &CryptoHash,
+    config: &VMConfig,
+    current_protocol_version: ProtocolVersion,
+    cache: &dyn CompiledContractCache,
+    signer: &dyn Signer,
+) -> io::Result<Option<ContractCacheArtifact>> {
+    let vm_kind = VMKind::for_protocol_version(current_protocol_version);
+    export_cached_contract_vm(vm_kind, code_hash, config, cache, signer)
+}
+
+pub(crate) fn export_cached_contract_vm(
+    vm_kind: VMKind,
+    code_hash: &CryptoHash,
+    config: &VMConfig,
+    cache: &dyn CompiledContractCache,
+    signer: &dyn Signer,
+) -> io::Result<Option<ContractCacheArtifact>> {
+    let key = cache_key(code_hash, vm_kind, config);
+    Ok(cache.get(&key.0)?.map(|record| {
+        let mut artifact = ContractCacheArtifact {
+            code_hash: *code_hash,
+            vm_kind,
+            vm_config_non_crypto_hash: config.non_crypto_hash(),
+            vm_hash: vm_hash(vm_kind),
+            record,
+            signer: signer.public_key(),
+            signature: Signature::default(),
+        };
+        artifact.signature = signer.sign(artifact.digest().as_ref());
+        artifact
+    }))
+}
+
+/// Puts the artifact into the cache if it’s signed by one of the
+/// `trusted_keys` and has been produced by the default VM for the protocol
+/// version with the same config as the one given.  Entries already in the
+/// cache are overwritten.
+pub fn import_cached_contract(
+    artifact: &ContractCacheArtifact,
+    trusted_keys: &[PublicKey],
+    config: &VMConfig,
+    current_protocol_version: ProtocolVersion,
+    cache: &dyn CompiledContractCache,
+) -> io::Result<ContractCacheImport> {
+    let vm_kind = VMKind::for_protocol_version(current_protocol_version);
+    import_cached_contract_vm(vm_kind, artifact, trusted_keys, config, cache)
+}
+
+pub(crate) fn import_cached_contract_vm(
+    vm_kind: VMKind,
+    artifact: &ContractCacheArtifact,
+    trusted_keys: &[PublicKey],
+    config: &VMConfig,
+    cache: &dyn CompiledContractCache,
+) -> io::Result<ContractCacheImport> {
+    if !trusted_keys.contains(&artifact.signer)
+        || !artifact.signature.verify(artifact.digest().as_ref(), &artifact.signer)
+    {
+        return Ok(ContractCacheImport::Untrusted);
+    }
+    if artifact.vm_kind != vm_kind
+        || artifact.vm_config_non_crypto_hash != config.non_crypto_hash()
+        || artifact.vm_hash != vm_hash(vm_kind)
+    {
+        return Ok(ContractCacheImport::Incompatible);
+    }
+    CacheRecord::try_from_slice(&artifact.record)?;
+    let key = cache_key(&artifact.code_hash, vm_kind, config);
+    cache.put(&key.0, &artifact.record)?;
+    Ok(ContractCacheImport::Imported)
+}
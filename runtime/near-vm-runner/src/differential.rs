@@ -0,0 +1,529 @@
+//! Differential execution of function calls on two VMs, to catch differences
+//! in behaviour between VM implementations before switching to another one.
+//!
+//! The secondary VM runs after the primary one and doesn’t touch the
+//! [`External`]: host functions accessing it are answered from a log of the
+//! calls the primary VM made, so that both VMs see the same storage, data ids
+//! and trie node counts (and hence charge the same gas).  Once the secondary
+//! VM makes a call the primary one hasn’t made, the replay fails and the
+//! divergence is reported together with the differences in the results.
+//!
+//! With the `differential_vm` feature, [`crate::run`] runs every function
+//! call on the VM set with [`set_secondary_vm_kind`] as well and collects the
+//! mismatches, which can be taken with [`take_vm_mismatches`].
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+
+use near_primitives::config::VMConfig;
+use near_primitives::contract::ContractCode;
+use near_primitives::hash::CryptoHash;
+use near_primitives::runtime::fees::RuntimeFeesConfig;
+use near_primitives::types::{AccountId, Balance, CompiledContractCache, TrieNodesCount};
+use near_primitives::version::ProtocolVersion;
+use near_vm_errors::{AnyError, VMLogicError};
+use near_vm_logic::types::PromiseResult;
+use near_vm_logic::{External, VMContext, ValuePtr};
+
+use crate::runner::run_vm;
+use crate::vm_kind::VMKind;
+use crate::VMResult;
+
+type Result<T> = std::result::Result<T, VMLogicError>;
+
+/// Differences between the results of a function call executed on two VMs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VMMismatch {
+    pub code_hash: CryptoHash,
+    pub method_name: String,
+    pub primary_vm_kind: VMKind,
+    pub secondary_vm_kind: VMKind,
+    /// Human-readable descriptions of the differences, e.g. of the gas used
+    /// or of the first host function call the VMs disagree on.
+    pub differences: Vec<String>,
+}
+
+impl fmt::Display for VMMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:?} and {:?} disagree on method {} of contract {}",
+            self.primary_vm_kind, self.secondary_vm_kind, self.method_name, self.code_hash
+        )?;
+        for difference in &self.differences {
+            write!(f, "\n  {}", difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs the function call on the primary VM and then on the secondary one and
+/// compares the return values, logs, gas, balances, receipts and errors.
+/// Returns the result of the primary VM, which is the only one having
+/// effects on `ext`, and the differences, if any.
+///
+/// The VMs aren’t compared if the primary VM got an error from `ext`.
+pub fn run_differential(
+    primary_vm_kind: VMKind,
+    secondary_vm_kind: VMKind,
+    code: &ContractCode,
+    method_name: &str,
+    ext: &mut dyn External,
+    context: VMContext,
+    wasm_config: &VMConfig,
+    fees_config: &RuntimeFeesConfig,
+    promise_results: &[PromiseResult],
+    current_protocol_version: ProtocolVersion,
+    cache: Option<&dyn CompiledContractCache>,
+) -> (VMResult, Option<VMMismatch>) {
+    let mut recorder = RecordingExternal { inner: ext, log: RefCell::new(ExtLog::default()) };
+    let primary = run_vm(
+        primary_vm_kind,
+        code,
+        method_name,
+        &mut recorder,
+        context.clone(),
+        wasm_config,
+        fees_config,
+        promise_results,
+        current_protocol_version,
+        cache,
+    );
+    let log = recorder.log.into_inner();
+    if log.failed {
+        return (primary, None);
+    }
+
+    let mut replayer = ReplayingExternal {
+        calls: &log.calls,
+        next: Cell::new(0),
+        last_nodes_count: Cell::new((0, 0)),
+        divergence: RefCell::new(None),
+    };
+    let secondary = run_vm(
+        secondary_vm_kind,
+        code,
+        method_name,
+        &mut replayer,
+        context,
+        wasm_config,
+        fees_config,
+        promise_results,
+        current_protocol_version,
+        cache,
+    );
+    let mut differences = vec![];
+    if let Some(divergence) = replayer.divergence.into_inner() {
+        differences.push(divergence);
+    } else if replayer.next.get() != log.calls.len() {
+        differences.push(format!(
+            "secondary VM made {} of {} host calls of the primary VM",
+            replayer.next.get(),
+            log.calls.len()
+        ));
+    }
+    compare_results(&primary, &secondary, &mut differences);
+    let mismatch = (!differences.is_empty()).then(|| VMMismatch {
+        code_hash: *code.hash(),
+        method_name: method_name.to_string(),
+        primary_vm_kind,
+        secondary_vm_kind,
+        differences,
+    });
+    (primary, mismatch)
+}
+
+fn compare_results(primary: &VMResult, secondary: &VMResult, differences: &mut Vec<String>) {
+    fn compare<T: PartialEq + fmt::Debug>(
+        differences: &mut Vec<String>,
+        what: &str,
+        primary: &T,
+        secondary: &T,
+    ) {
+        if primary != secondary {
+            differences.push(format!("{}: {:?} != {:?}", what, primary, secondary));
+        }
+    }
+    let (outcome, other) = (primary.outcome(), secondary.outcome());
+    compare(differences, "return data", &outcome.return_data, &other.return_data);
+    compare(differences, "logs", &outcome.logs, &other.logs);
+    compare(differences, "burnt gas", &outcome.burnt_gas, &other.burnt_gas);
+    compare(differences, "used gas", &outcome.used_gas, &other.used_gas);
+    compare(differences, "balance", &outcome.balance, &other.balance);
+    compare(differences, "storage usage", &outcome.storage_usage, &other.storage_usage);
+    compare(differences, "receipts", &outcome.action_receipts, &other.action_receipts);
+    compare(differences, "error", &primary.error(), &secondary.error());
+}
+
+/// Call of a method of [`External`] which affects or depends on the state.
+#[derive(Debug, PartialEq)]
+enum ExtRequest {
+    StorageSet { key: Vec<u8>, value: Vec<u8> },
+    StorageGet { key: Vec<u8> },
+    StorageRemove { key: Vec<u8> },
+    StorageRemoveSubtree { prefix: Vec<u8> },
+    StorageHasKey { key: Vec<u8> },
+    GenerateDataId,
+    GetTrieNodesCount,
+    ValidatorStake { account_id: AccountId },
+    ValidatorTotalStake,
+}
+
+enum ExtResponse {
+    Unit,
+    /// Length of the value read and, if the VM dereferenced it, the value.
+    Value(Option<(u32, Option<Vec<u8>>)>),
+    Bool(bool),
+    DataId(CryptoHash),
+    TrieNodesCount(u64, u64),
+    Balance(Option<Balance>),
+}
+
+struct ExtCall {
+    request: ExtRequest,
+    response: ExtResponse,
+}
+
+#[derive(Default)]
+struct ExtLog {
+    calls: Vec<ExtCall>,
+    /// Whether any call returned an error.  Errors can’t be replayed, as they
+    /// can’t be cloned, but they are fatal for the runtime anyway.
+    failed: bool,
+}
+
+/// [`External`] which records the calls made by the primary VM.
+struct RecordingExternal<'a> {
+    inner: &'a mut dyn External,
+    log: RefCell<ExtLog>,
+}
+
+impl RecordingExternal<'_> {
+    fn record<T>(
+        &self,
+        request: ExtRequest,
+        result: Result<T>,
+        response: impl FnOnce(&T) -> ExtResponse,
+    ) -> Result<T> {
+        let mut log = self.log.borrow_mut();
+        match &result {
+            Ok(value) => log.calls.push(ExtCall { request, response: response(value) }),
+            Err(_) => log.failed = true,
+        }
+        result
+    }
+}
+
+impl External for RecordingExternal<'_> {
+    fn storage_set(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let result = self.inner.storage_set(key, value);
+        let request = ExtRequest::StorageSet { key: key.to_vec(), value: value.to_vec() };
+        self.record(request, result, |_| ExtResponse::Unit)
+    }
+
+    fn storage_get<'a>(&'a self, key: &[u8]) -> Result<Option<Box<dyn ValuePtr + 'a>>> {
+        let result = self.inner.storage_get(key);
+        let index = self.log.borrow().calls.len();
+        let request = ExtRequest::StorageGet { key: key.to_vec() };
+        let result = self.record(request, result, |ptr| {
+            ExtResponse::Value(ptr.as_ref().map(|ptr| (ptr.len(), None)))
+        })?;
+        Ok(result.map(|ptr| {
+            Box::new(RecordingValuePtr { inner: ptr, log: &self.log, index })
+                as Box<dyn ValuePtr + 'a>
+        }))
+    }
+
+    fn storage_remove(&mut self, key: &[u8]) -> Result<()> {
+        let result = self.inner.storage_remove(key);
+        self.record(ExtRequest::StorageRemove { key: key.to_vec() }, result, |_| ExtResponse::Unit)
+    }
+
+    fn storage_remove_subtree(&mut self, prefix: &[u8]) -> Result<()> {
+        let result = self.inner.storage_remove_subtree(prefix);
+        let request = ExtRequest::StorageRemoveSubtree { prefix: prefix.to_vec() };
+        self.record(request, result, |_| ExtResponse::Unit)
+    }
+
+    fn storage_has_key(&mut self, key: &[u8]) -> Result<bool> {
+        let result = self.inner.storage_has_key(key);
+        let request = ExtRequest::StorageHasKey { key: key.to_vec() };
+        self.record(request, result, |&has_key| ExtResponse::Bool(has_key))
+    }
+
+    fn generate_data_id(&mut self) -> CryptoHash {
+        let data_id = self.inner.generate_data_id();
+        self.log.borrow_mut().calls.push(ExtCall {
+            request: ExtRequest::GenerateDataId,
+            response: ExtResponse::DataId(data_id),
+        });
+        data_id
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        let count = self.inner.get_trie_nodes_count();
+        self.log.borrow_mut().calls.push(ExtCall {
+            request: ExtRequest::GetTrieNodesCount,
+            response: ExtResponse::TrieNodesCount(count.db_reads, count.mem_reads),
+        });
+        count
+    }
+
+    fn validator_stake(&self, account_id: &AccountId) -> Result<Option<Balance>> {
+        let result = self.inner.validator_stake(account_id);
+        let request = ExtRequest::ValidatorStake { account_id: account_id.clone() };
+        self.record(request, result, |&stake| ExtResponse::Balance(stake))
+    }
+
+    fn validator_total_stake(&self) -> Result<Balance> {
+        let result = self.inner.validator_total_stake();
+        self.record(ExtRequest::ValidatorTotalStake, result, |&stake| {
+            ExtResponse::Balance(Some(stake))
+        })
+    }
+}
+
+/// Value read by the primary VM, recorded when the VM dereferences it.
+struct RecordingValuePtr<'a> {
+    inner: Box<dyn ValuePtr + 'a>,
+    log: &'a RefCell<ExtLog>,
+    index: usize,
+}
+
+impl ValuePtr for RecordingValuePtr<'_> {
+    fn len(&self) -> u32 {
+        self.inner.len()
+    }
+
+    fn deref(&self) -> Result<Vec<u8>> {
+        let result = self.inner.deref();
+        let mut log = self.log.borrow_mut();
+        match &result {
+            Ok(value) => {
+                if let ExtResponse::Value(Some((_, recorded))) = &mut log.calls[self.index].response
+                {
+                    *recorded = Some(value.clone());
+                }
+            }
+            Err(_) => log.failed = true,
+        }
+        result
+    }
+}
+
+/// Error returned to the secondary VM once it diverges from the primary one.
+#[derive(Debug, PartialEq, Eq)]
+struct HostCallsDiverged;
+
+fn diverged() -> VMLogicError {
+    VMLogicError::ExternalError(AnyError::new(HostCallsDiverged))
+}
+
+/// [`External`] which answers the calls of the secondary VM from the log of
+/// the calls of the primary VM.
+struct ReplayingExternal<'a> {
+    calls: &'a [ExtCall],
+    next: Cell<usize>,
+    /// The last trie node count returned, which is returned again after the
+    /// divergence so that the VM doesn’t see the count decreasing.
+    last_nodes_count: Cell<(u64, u64)>,
+    divergence: RefCell<Option<String>>,
+}
+
+impl<'a> ReplayingExternal<'a> {
+    fn replay(&self, request: ExtRequest) -> Option<&'a ExtResponse> {
+        if self.divergence.borrow().is_some() {
+            return None;
+        }
+        let index = self.next.get();
+        match self.calls.get(index) {
+            Some(call) if call.request == request => {
+                self.next.set(index + 1);
+                Some(&call.response)
+            }
+            call => {
+                *self.divergence.borrow_mut() = Some(format!(
+                    "host call #{}: primary VM made {:?}, secondary VM made {:?}",
+                    index,
+                    call.map(|call| &call.request),
+                    request
+                ));
+                None
+            }
+        }
+    }
+
+    fn replay_unit(&self, request: ExtRequest) -> Result<()> {
+        match self.replay(request) {
+            Some(ExtResponse::Unit) => Ok(()),
+            _ => Err(diverged()),
+        }
+    }
+}
+
+impl External for ReplayingExternal<'_> {
+    fn storage_set(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.replay_unit(ExtRequest::StorageSet { key: key.to_vec(), value: value.to_vec() })
+    }
+
+    fn storage_get<'b>(&'b self, key: &[u8]) -> Result<Option<Box<dyn ValuePtr + 'b>>> {
+        match self.replay(ExtRequest::StorageGet { key: key.to_vec() }) {
+            Some(ExtResponse::Value(value)) => Ok(value.as_ref().map(|(len, value)| {
+                Box::new(ReplayedValuePtr {
+                    len: *len,
+                    value: value.as_ref(),
+                    divergence: &self.divergence,
+                }) as Box<dyn ValuePtr + 'b>
+            })),
+            _ => Err(diverged()),
+        }
+    }
+
+    fn storage_remove(&mut self, key: &[u8]) -> Result<()> {
+        self.replay_unit(ExtRequest::StorageRemove { key: key.to_vec() })
+    }
+
+    fn storage_remove_subtree(&mut self, prefix: &[u8]) -> Result<()> {
+        self.replay_unit(ExtRequest::StorageRemoveSubtree { prefix: prefix.to_vec() })
+    }
+
+    fn storage_has_key(&mut self, key: &[u8]) -> Result<bool> {
+        match self.replay(ExtRequest::StorageHasKey { key: key.to_vec() }) {
+            Some(ExtResponse::Bool(has_key)) => Ok(*has_key),
+            _ => Err(diverged()),
+        }
+    }
+
+    fn generate_data_id(&mut self) -> CryptoHash {
+        match self.replay(ExtRequest::GenerateDataId) {
+            Some(ExtResponse::DataId(data_id)) => *data_id,
+            _ => CryptoHash::default(),
+        }
+    }
+
+    fn get_trie_nodes_count(&self) -> TrieNodesCount {
+        if let Some(ExtResponse::TrieNodesCount(db_reads, mem_reads)) =
+            self.replay(ExtRequest::GetTrieNodesCount)
+        {
+            self.last_nodes_count.set((*db_reads, *mem_reads));
+        }
+        let (db_reads, mem_reads) = self.last_nodes_count.get();
+        TrieNodesCount { db_reads, mem_reads }
+    }
+
+    fn validator_stake(&self, account_id: &AccountId) -> Result<Option<Balance>> {
+        match self.replay(ExtRequest::ValidatorStake { account_id: account_id.clone() }) {
+            Some(ExtResponse::Balance(stake)) => Ok(*stake),
+            _ => Err(diverged()),
+        }
+    }
+
+    fn validator_total_stake(&self) -> Result<Balance> {
+        match self.replay(ExtRequest::ValidatorTotalStake) {
+            Some(ExtResponse::Balance(Some(stake))) => Ok(*stake),
+            _ => Err(diverged()),
+        }
+    }
+}
+
+struct ReplayedValuePtr<'a> {
+    len: u32,
+    value: Option<&'a Vec<u8>>,
+    divergence: &'a RefCell<Option<String>>,
+}
+
+impl ValuePtr for ReplayedValuePtr<'_> {
+    fn len(&self) -> u32 {
+        self.len
+    }
+
+    fn deref(&self) -> Result<Vec<u8>> {
+        match self.value {
+            Some(value) => Ok(value.clone()),
+            None => {
+                let mut divergence = self.divergence.borrow_mut();
+                if divergence.is_none() {
+                    *divergence =
+                        Some("secondary VM read a value the primary VM didn't read".to_string());
+                }
+                Err(diverged())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "differential_vm")]
+mod global {
+    use std::sync::Mutex;
+
+    use once_cell::sync::Lazy;
+
+    use super::VMMismatch;
+    use crate::vm_kind::VMKind;
+
+    static SECONDARY_VM_KIND: Lazy<Mutex<Option<VMKind>>> = Lazy::new(|| Mutex::new(None));
+    static MISMATCHES: Lazy<Mutex<Vec<VMMismatch>>> = Lazy::new(|| Mutex::new(vec![]));
+
+    /// Sets the VM which [`crate::run`] runs every function call on in
+    /// addition to the default VM for the protocol version.  `None` (the
+    /// default) disables differential execution.
+    pub fn set_secondary_vm_kind(vm_kind: Option<VMKind>) {
+        *SECONDARY_VM_KIND.lock().unwrap() = vm_kind;
+    }
+
+    pub(crate) fn secondary_vm_kind() -> Option<VMKind> {
+        *SECONDARY_VM_KIND.lock().unwrap()
+    }
+
+    pub(crate) fn report_mismatch(mismatch: VMMismatch) {
+        tracing::warn!(target: "vm", %mismatch, "VMs disagree on the result of a function call");
+        MISMATCHES.lock().unwrap().push(mismatch);
+    }
+
+    /// Returns the mismatches found by [`crate::run`] since the last call.
+    pub fn take_vm_mismatches() -> Vec<VMMismatch> {
+        std::mem::take(&mut *MISMATCHES.lock().unwrap())
+    }
+}
+
+#[cfg(feature = "differential_vm")]
+pub(crate) use global::{report_mismatch, secondary_vm_kind};
+#[cfg(feature = "differential_vm")]
+pub use global::{set_secondary_vm_kind, take_vm_mismatches};
+
+#[cfg(test)]
+mod tests {
+    use near_vm_logic::mocks::mock_external::MockedExternal;
+
+    use super::*;
+
+    #[test]
+    fn test_replay_divergence() {
+        let mut ext = MockedExternal::new();
+        let mut recorder = RecordingExternal { inner: &mut ext, log: RefCell::default() };
+        recorder.storage_set(b"key", b"value").unwrap();
+        assert_eq!(recorder.storage_get(b"key").unwrap().unwrap().deref().unwrap(), b"value");
+        recorder.get_trie_nodes_count();
+        recorder.storage_has_key(b"other").unwrap();
+        let log = recorder.log.into_inner();
+        assert!(!log.failed);
+        assert_eq!(log.calls.len(), 4);
+
+        let mut replayer = ReplayingExternal {
+            calls: &log.calls,
+            next: Cell::new(0),
+            last_nodes_count: Cell::new((0, 0)),
+            divergence: RefCell::new(None),
+        };
+        replayer.storage_set(b"key", b"value").unwrap();
+        let value = replayer.storage_get(b"key").unwrap().unwrap();
+        assert_eq!((value.len(), value.deref().unwrap()), (5, b"value".to_vec()));
+        drop(value);
+        replayer.get_trie_nodes_count();
+        assert_eq!(replayer.storage_has_key(b"key"), Err(diverged()));
+        assert!(replayer.divergence.borrow().as_ref().unwrap().starts_with("host call #3"));
+        // Calls after the divergence fail too.
+        assert_eq!(replayer.storage_remove(b"key"), Err(diverged()));
+        assert_eq!(replayer.next.get(), 3);
+    }
+}
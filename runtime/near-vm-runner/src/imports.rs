@@ -105,6 +105,7 @@ imports! {
     keccak512<[value_len: u64, value_ptr: u64, register_id: u64] -> []>,
     #[MathExtension] ripemd160<[value_len: u64, value_ptr: u64, register_id: u64] -> []>,
     #[MathExtension] ecrecover<[hash_len: u64, hash_ptr: u64, sign_len: u64, sig_ptr: u64, v: u64, malleability_flag: u64, register_id: u64] -> [u64]>,
+    #["protocol_feature_ed25519_verify", Ed25519Verify] ed25519_verify<[signature_len: u64, signature_ptr: u64, message_len: u64, message_ptr: u64, public_key_len: u64, public_key_ptr: u64] -> [u64]>,
     // #####################
     // # Miscellaneous API #
     // #####################
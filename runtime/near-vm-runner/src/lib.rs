@@ -1,6 +1,7 @@
 #![doc = include_str!("../README.md")]
 
 mod cache;
+pub mod differential;
 mod errors;
 mod imports;
 mod instrument;
@@ -22,8 +23,10 @@ pub use near_vm_errors::VMError;
 pub use near_vm_logic::with_ext_cost_counter;
 
 pub use cache::{
-    get_contract_cache_key, precompile_contract, precompile_contract_vm, MockCompiledContractCache,
+    export_cached_contract, get_contract_cache_key, import_cached_contract, precompile_contract,
+    precompile_contract_vm, ContractCacheArtifact, ContractCacheImport, MockCompiledContractCache,
 };
+pub use errors::ContractPrecompilatonResult;
 pub use runner::{run, VMResult, VM};
 
 /// This is public for internal experimentation use only, and should otherwise be considered an
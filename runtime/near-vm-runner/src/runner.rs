@@ -37,6 +37,55 @@ pub fn run(
     cache: Option<&dyn CompiledContractCache>,
 ) -> VMResult {
     let vm_kind = VMKind::for_protocol_version(current_protocol_version);
+    #[cfg(feature = "differential_vm")]
+    if let Some(secondary_vm_kind) = crate::differential::secondary_vm_kind() {
+        if secondary_vm_kind != vm_kind {
+            let (res, mismatch) = crate::differential::run_differential(
+                vm_kind,
+                secondary_vm_kind,
+                code,
+                method_name,
+                ext,
+                context,
+                wasm_config,
+                fees_config,
+                promise_results,
+                current_protocol_version,
+                cache,
+            );
+            if let Some(mismatch) = mismatch {
+                crate::differential::report_mismatch(mismatch);
+            }
+            return res;
+        }
+    }
+    run_vm(
+        vm_kind,
+        code,
+        method_name,
+        ext,
+        context,
+        wasm_config,
+        fees_config,
+        promise_results,
+        current_protocol_version,
+        cache,
+    )
+}
+
+/// Like [`run`] but with the given VM implementation.
+pub(crate) fn run_vm(
+    vm_kind: VMKind,
+    code: &ContractCode,
+    method_name: &str,
+    ext: &mut dyn External,
+    context: VMContext,
+    wasm_config: &VMConfig,
+    fees_config: &RuntimeFeesConfig,
+    promise_results: &[PromiseResult],
+    current_protocol_version: ProtocolVersion,
+    cache: Option<&dyn CompiledContractCache>,
+) -> VMResult {
     if let Some(runtime) = vm_kind.runtime(wasm_config.clone()) {
         let span = tracing::debug_span!(
             target: "vm",
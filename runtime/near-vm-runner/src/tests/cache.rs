@@ -2,11 +2,17 @@
 #![cfg(target_arch = "x86_64")]
 
 use super::{create_context, with_vm_variants, LATEST_PROTOCOL_VERSION};
+use crate::cache::{export_cached_contract_vm, import_cached_contract_vm};
 use crate::internal::VMKind;
 use crate::runner::VMResult;
 use crate::wasmer2_runner::Wasmer2VM;
-use crate::{prepare, MockCompiledContractCache};
+use crate::{
+    get_contract_cache_key, precompile_contract_vm, prepare, ContractCacheArtifact,
+    ContractCacheImport, MockCompiledContractCache,
+};
 use assert_matches::assert_matches;
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_crypto::{InMemorySigner, KeyType, Signer};
 use near_primitives::contract::ContractCode;
 use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::types::CompiledContractCache;
@@ -70,6 +76,80 @@ fn test_does_not_cache_io_error() {
     })
 }
 
+#[test]
+fn test_export_import_cached_contract() {
+    with_vm_variants(|vm_kind: VMKind| {
+        match vm_kind {
+            VMKind::Wasmer0 | VMKind::Wasmer2 => {}
+            VMKind::Wasmtime => return,
+        }
+        let code = ContractCode::new(near_test_contracts::trivial_contract().to_vec(), None);
+        let config = VMConfig::test();
+        let signer = InMemorySigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let trusted = [signer.public_key()];
+        let source = MockCompiledContractCache::default();
+        assert_eq!(
+            export_cached_contract_vm(vm_kind, code.hash(), &config, &source, &signer).unwrap(),
+            None
+        );
+        precompile_contract_vm(vm_kind, &code, &config, Some(&source)).unwrap().unwrap();
+        let artifact = export_cached_contract_vm(vm_kind, code.hash(), &config, &source, &signer)
+            .unwrap()
+            .unwrap();
+        assert_eq!(artifact.code_hash(), code.hash());
+        let import = |vm_kind, artifact: &ContractCacheArtifact, trusted: &[_], config, target| {
+            import_cached_contract_vm(vm_kind, artifact, trusted, config, target).unwrap()
+        };
+
+        // Artifacts of another VM or config are not imported.
+        let target = MockCompiledContractCache::default();
+        let other_vm_kind = match vm_kind {
+            VMKind::Wasmer0 => VMKind::Wasmer2,
+            _ => VMKind::Wasmer0,
+        };
+        assert_eq!(
+            import(other_vm_kind, &artifact, &trusted, &config, &target),
+            ContractCacheImport::Incompatible
+        );
+        let mut other_config = VMConfig::test();
+        other_config.regular_op_cost += 1;
+        assert_eq!(
+            import(vm_kind, &artifact, &trusted, &other_config, &target),
+            ContractCacheImport::Incompatible
+        );
+
+        // Nor are the ones signed by an untrusted key or tampered with.
+        let other_signer =
+            InMemorySigner::from_seed("other".parse().unwrap(), KeyType::ED25519, "other");
+        let untrusted =
+            export_cached_contract_vm(vm_kind, code.hash(), &config, &source, &other_signer)
+                .unwrap()
+                .unwrap();
+        assert_eq!(
+            import(vm_kind, &untrusted, &trusted, &config, &target),
+            ContractCacheImport::Untrusted
+        );
+        let mut tampered = artifact.try_to_vec().unwrap();
+        // The last byte of the cache record, which is followed by the signer
+        // (a key type and 32 bytes) and the signature (a key type and 64 bytes).
+        let record_end = tampered.len() - 1 - 32 - 1 - 64 - 1;
+        tampered[record_end] ^= 1;
+        let tampered = ContractCacheArtifact::try_from_slice(&tampered).unwrap();
+        assert_eq!(
+            import(vm_kind, &tampered, &trusted, &config, &target),
+            ContractCacheImport::Untrusted
+        );
+        assert_eq!(target.len(), 0);
+
+        assert_eq!(
+            import(vm_kind, &artifact, &trusted, &config, &target),
+            ContractCacheImport::Imported
+        );
+        let key = get_contract_cache_key(&code, vm_kind, &config);
+        assert_eq!(target.get(&key.0).unwrap(), source.get(&key.0).unwrap());
+    })
+}
+
 fn make_cached_contract_call_vm(
     cache: &dyn CompiledContractCache,
     code: &[u8],
@@ -76,6 +76,39 @@ pub fn test_read_write() {
     });
 }
 
+#[test]
+#[cfg(all(feature = "wasmer2_vm", feature = "wasmtime_vm", target_arch = "x86_64"))]
+pub fn test_run_differential() {
+    let code = test_contract();
+    let config = VMConfig::test();
+    let fees = RuntimeFeesConfig::test();
+    let mut fake_external = MockedExternal::new();
+    let mut run = |method_name, input| {
+        crate::differential::run_differential(
+            VMKind::Wasmer2,
+            VMKind::Wasmtime,
+            &code,
+            method_name,
+            &mut fake_external,
+            create_context(input),
+            &config,
+            &fees,
+            &[],
+            LATEST_PROTOCOL_VERSION,
+            None,
+        )
+    };
+
+    let (result, mismatch) = run("write_key_value", encode(&[10u64, 20u64]));
+    assert_run_result(result, 0);
+    assert_eq!(mismatch, None);
+    let (result, mismatch) = run("read_value", encode(&[10u64]));
+    assert_run_result(result, 20);
+    assert_eq!(mismatch, None);
+    // Only the primary VM writes to the storage.
+    assert_eq!(fake_external.fake_trie.len(), 1);
+}
+
 macro_rules! def_test_ext {
     ($name:ident, $method:expr, $expected:expr, $input:expr, $validator:expr) => {
         #[test]
@@ -1,9 +1,9 @@
-use borsh::BorshSerialize;
+use borsh::{BorshDeserialize, BorshSerialize};
 use near_primitives::checked_feature;
 use near_vm_logic::ProtocolVersion;
 use std::hash::Hash;
 
-#[derive(Clone, Copy, Debug, Hash, BorshSerialize, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, Hash, BorshDeserialize, BorshSerialize, PartialEq, Eq)]
 // Note, that VMKind is part of serialization protocol, so we cannot remove entries
 // from this list if particular VM reached publicly visible networks.
 //
@@ -18,6 +18,19 @@ pub enum VMKind {
     Wasmer2,
 }
 
+impl std::str::FromStr for VMKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wasmer0" => Ok(Self::Wasmer0),
+            "wasmtime" => Ok(Self::Wasmtime),
+            "wasmer2" => Ok(Self::Wasmer2),
+            _ => Err(format!("unknown VM kind {}, expected wasmer0, wasmtime or wasmer2", s)),
+        }
+    }
+}
+
 impl VMKind {
     pub fn for_protocol_version(protocol_version: ProtocolVersion) -> VMKind {
         // Only wasmtime supports non-x86_64 systems
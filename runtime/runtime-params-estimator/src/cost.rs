@@ -354,6 +354,12 @@ pub enum Cost {
     /// function `ecrecover` to verify an ECDSA signature and extract the
     /// signer.
     EcrecoverBase,
+    /// Estimates `ed25519_verify_base`, the cost charged once per call to the
+    /// host function `ed25519_verify` to verify an ed25519 signature.
+    Ed25519VerifyBase,
+    /// Estimates `ed25519_verify_byte`, the cost charged per message byte in
+    /// calls to the host function `ed25519_verify`.
+    Ed25519VerifyByte,
 
     // `storage_write` records a single key-value pair, initially in the
     // prospective changes in-memory hash map, and then once a full block has
@@ -78,6 +78,8 @@ fn runtime_fees_config(cost_table: &CostTable) -> anyhow::Result<RuntimeFeesConf
             },
             delete_key_cost: fee(Cost::ActionDeleteKey)?,
             delete_account_cost: fee(Cost::ActionDeleteAccount)?,
+            // TODO: estimate the cost of delegate actions.
+            delegate_cost: actual_fees_config.action_creation_config.delegate_cost.clone(),
         },
         ..actual_fees_config.clone()
     };
@@ -114,6 +116,8 @@ fn ext_costs_config(cost_table: &CostTable) -> anyhow::Result<ExtCostsConfig> {
         ripemd160_base: get(Cost::Ripemd160Base)?,
         ripemd160_block: get(Cost::Ripemd160Block)?,
         ecrecover_base: get(Cost::EcrecoverBase)?,
+        ed25519_verify_base: get(Cost::Ed25519VerifyBase)?,
+        ed25519_verify_byte: get(Cost::Ed25519VerifyByte)?,
         log_base: get(Cost::LogBase)?,
         log_byte: get(Cost::LogByte)?,
         storage_write_base: get(Cost::StorageWriteBase)?,
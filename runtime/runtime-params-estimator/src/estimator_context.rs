@@ -123,8 +123,11 @@ impl<'c> Testbed<'c> {
 
     pub(crate) fn trie_caching_storage(&mut self) -> TrieCachingStorage {
         let store = self.inner.store();
-        let caching_storage =
-            TrieCachingStorage::new(store, TrieCache::new(), ShardUId::single_shard());
+        let caching_storage = TrieCachingStorage::new(
+            store,
+            TrieCache::new(&Default::default(), ShardUId::single_shard(), false),
+            ShardUId::single_shard(),
+        );
         caching_storage
     }
 
@@ -156,6 +156,10 @@ static ALL_COSTS: &[(Cost, fn(&mut EstimatorContext) -> GasCost)] = &[
     (Cost::Ripemd160Base, ripemd160_base),
     (Cost::Ripemd160Block, ripemd160_block),
     (Cost::EcrecoverBase, ecrecover_base),
+    #[cfg(feature = "protocol_feature_ed25519_verify")]
+    (Cost::Ed25519VerifyBase, ed25519_verify_base),
+    #[cfg(feature = "protocol_feature_ed25519_verify")]
+    (Cost::Ed25519VerifyByte, ed25519_verify_byte),
     (Cost::AltBn128G1MultiexpBase, alt_bn128g1_multiexp_base),
     (Cost::AltBn128G1MultiexpElement, alt_bn128g1_multiexp_element),
     (Cost::AltBn128G1SumBase, alt_bn128g1_sum_base),
@@ -913,6 +917,15 @@ fn ecrecover_base(ctx: &mut EstimatorContext) -> GasCost {
     fn_cost(ctx, "ecrecover_10k", ExtCosts::ecrecover_base, 10_000)
 }
 
+#[cfg(feature = "protocol_feature_ed25519_verify")]
+fn ed25519_verify_base(ctx: &mut EstimatorContext) -> GasCost {
+    fn_cost(ctx, "ed25519_verify_32b_500", ExtCosts::ed25519_verify_base, 500)
+}
+#[cfg(feature = "protocol_feature_ed25519_verify")]
+fn ed25519_verify_byte(ctx: &mut EstimatorContext) -> GasCost {
+    fn_cost(ctx, "ed25519_verify_16kib_64", ExtCosts::ed25519_verify_byte, 64 * 16 * 1024)
+}
+
 fn alt_bn128g1_multiexp_base(ctx: &mut EstimatorContext) -> GasCost {
     fn_cost(ctx, "alt_bn128_g1_multiexp_1_10", ExtCosts::alt_bn128_g1_multiexp_base, 10)
 }
@@ -4,14 +4,16 @@ use near_crypto::PublicKey;
 use near_primitives::account::{AccessKey, AccessKeyPermission, Account};
 use near_primitives::checked_feature;
 use near_primitives::contract::ContractCode;
-use near_primitives::errors::{ActionError, ActionErrorKind, ContractCallError, RuntimeError};
+use near_primitives::errors::{
+    ActionError, ActionErrorKind, ContractCallError, InvalidAccessKeyError, RuntimeError,
+};
 use near_primitives::hash::CryptoHash;
 use near_primitives::receipt::{ActionReceipt, Receipt, ReceiptEnum};
 use near_primitives::runtime::config::AccountCreationConfig;
 use near_primitives::runtime::fees::RuntimeFeesConfig;
 use near_primitives::transaction::{
-    Action, AddKeyAction, DeleteAccountAction, DeleteKeyAction, DeployContractAction,
-    FunctionCallAction, StakeAction, TransferAction,
+    Action, AddKeyAction, DelegateAction, DeleteAccountAction, DeleteKeyAction,
+    DeployContractAction, FunctionCallAction, SignedDelegateAction, StakeAction, TransferAction,
 };
 use near_primitives::types::validator_stake::ValidatorStake;
 use near_primitives::types::{AccountId, BlockHeight, EpochInfoProvider, TrieCacheMode};
@@ -30,7 +32,10 @@ use near_vm_errors::{
 use near_vm_logic::types::PromiseResult;
 use near_vm_logic::VMContext;
 
-use crate::config::{safe_add_gas, RuntimeConfig};
+use crate::config::{
+    delegated_actions_exec_fees, delegated_actions_send_fees, safe_add_gas, total_prepaid_gas,
+    RuntimeConfig,
+};
 use crate::ext::{ExternalError, RuntimeExt};
 use crate::{ActionResult, ApplyState};
 use near_primitives::config::ViewConfig;
@@ -613,6 +618,148 @@ pub(crate) fn action_add_key(
     Ok(())
 }
 
+/// Checks the delegate action and creates the receipt with the delegated
+/// actions, sent on behalf of its sender (the receiver of the current receipt)
+/// and paid for by the signer of the current receipt.
+pub(crate) fn apply_delegate_action(
+    state_update: &mut TrieUpdate,
+    apply_state: &ApplyState,
+    action_receipt: &ActionReceipt,
+    sender_id: &AccountId,
+    signed_delegate_action: &SignedDelegateAction,
+    result: &mut ActionResult,
+) -> Result<(), RuntimeError> {
+    let delegate_action = &signed_delegate_action.delegate_action;
+
+    if !signed_delegate_action.verify() {
+        result.result = Err(ActionErrorKind::DelegateActionInvalidSignature.into());
+        return Ok(());
+    }
+    if apply_state.block_index > delegate_action.max_block_height {
+        result.result = Err(ActionErrorKind::DelegateActionExpired.into());
+        return Ok(());
+    }
+    if &delegate_action.sender_id != sender_id {
+        result.result = Err(ActionErrorKind::DelegateActionSenderDoesNotMatchTxReceiver {
+            sender_id: delegate_action.sender_id.clone(),
+            receiver_id: sender_id.clone(),
+        }
+        .into());
+        return Ok(());
+    }
+
+    let public_key = &delegate_action.public_key;
+    let mut access_key = match get_access_key(state_update, sender_id, public_key)? {
+        Some(access_key) => access_key,
+        None => {
+            result.result = Err(ActionErrorKind::DelegateActionAccessKeyError(
+                InvalidAccessKeyError::AccessKeyNotFound {
+                    account_id: sender_id.clone(),
+                    public_key: public_key.clone(),
+                },
+            )
+            .into());
+            return Ok(());
+        }
+    };
+    if let Err(err) = check_delegate_action_key(&access_key, apply_state, delegate_action) {
+        result.result = Err(err);
+        return Ok(());
+    }
+    access_key.nonce = delegate_action.nonce;
+    set_access_key(state_update, sender_id.clone(), public_key.clone(), &access_key);
+
+    let actions = delegate_action.get_actions();
+    let new_receipt = Receipt {
+        predecessor_id: sender_id.clone(),
+        receiver_id: delegate_action.receiver_id.clone(),
+        receipt_id: CryptoHash::default(),
+        receipt: ReceiptEnum::Action(ActionReceipt {
+            signer_id: action_receipt.signer_id.clone(),
+            signer_public_key: action_receipt.signer_public_key.clone(),
+            gas_price: action_receipt.gas_price,
+            output_data_receivers: vec![],
+            input_data_ids: vec![],
+            actions,
+        }),
+    };
+
+    // The fees of the new receipt were prepaid with the delegate action.  The
+    // send fees are burnt now and the rest is attached to the new receipt, so
+    // all of it counts as used.
+    let fees_config = &apply_state.config.transaction_costs;
+    let protocol_version = apply_state.current_protocol_version;
+    let send_fees = delegated_actions_send_fees(fees_config, delegate_action, protocol_version)?;
+    let exec_fees = delegated_actions_exec_fees(fees_config, delegate_action, protocol_version)?;
+    let prepaid_gas = total_prepaid_gas(&delegate_action.get_actions())?;
+    result.gas_burnt = safe_add_gas(result.gas_burnt, send_fees)?;
+    result.gas_used = safe_add_gas(result.gas_used, send_fees)?;
+    result.gas_used = safe_add_gas(result.gas_used, exec_fees)?;
+    result.gas_used = safe_add_gas(result.gas_used, prepaid_gas)?;
+    result.new_receipts.push(new_receipt);
+    Ok(())
+}
+
+/// Checks that the access key which signed the delegate action may be used for
+/// it, the same way as for transactions.
+fn check_delegate_action_key(
+    access_key: &AccessKey,
+    apply_state: &ApplyState,
+    delegate_action: &DelegateAction,
+) -> Result<(), ActionError> {
+    if delegate_action.nonce <= access_key.nonce {
+        return Err(ActionErrorKind::DelegateActionInvalidNonce {
+            delegate_nonce: delegate_action.nonce,
+            ak_nonce: access_key.nonce,
+        }
+        .into());
+    }
+    let upper_bound = apply_state.block_index * AccessKey::ACCESS_KEY_NONCE_RANGE_MULTIPLIER;
+    if delegate_action.nonce >= upper_bound {
+        return Err(ActionErrorKind::DelegateActionNonceTooLarge {
+            delegate_nonce: delegate_action.nonce,
+            upper_bound,
+        }
+        .into());
+    }
+
+    if let AccessKeyPermission::FunctionCall(ref function_call_permission) = access_key.permission {
+        let access_key_error = match delegate_action.actions.as_slice() {
+            [action] => match &**action {
+                Action::FunctionCall(function_call) => {
+                    if function_call.deposit > 0 {
+                        Some(InvalidAccessKeyError::DepositWithFunctionCall)
+                    } else if delegate_action.receiver_id.as_ref()
+                        != function_call_permission.receiver_id
+                    {
+                        Some(InvalidAccessKeyError::ReceiverMismatch {
+                            tx_receiver: delegate_action.receiver_id.clone(),
+                            ak_receiver: function_call_permission.receiver_id.clone(),
+                        })
+                    } else if !function_call_permission.method_names.is_empty()
+                        && function_call_permission
+                            .method_names
+                            .iter()
+                            .all(|method_name| &function_call.method_name != method_name)
+                    {
+                        Some(InvalidAccessKeyError::MethodNameMismatch {
+                            method_name: function_call.method_name.clone(),
+                        })
+                    } else {
+                        None
+                    }
+                }
+                _ => Some(InvalidAccessKeyError::RequiresFullAccess),
+            },
+            _ => Some(InvalidAccessKeyError::RequiresFullAccess),
+        };
+        if let Some(access_key_error) = access_key_error {
+            return Err(ActionErrorKind::DelegateActionAccessKeyError(access_key_error).into());
+        }
+    }
+    Ok(())
+}
+
 pub(crate) fn check_actor_permissions(
     action: &Action,
     account: &Option<Account>,
@@ -645,7 +792,10 @@ pub(crate) fn check_actor_permissions(
                 .into());
             }
         }
-        Action::CreateAccount(_) | Action::FunctionCall(_) | Action::Transfer(_) => (),
+        Action::CreateAccount(_)
+        | Action::FunctionCall(_)
+        | Action::Transfer(_)
+        | Action::Delegate(_) => (),
     };
     Ok(())
 }
@@ -712,7 +862,8 @@ pub(crate) fn check_account_existence(
         | Action::Stake(_)
         | Action::AddKey(_)
         | Action::DeleteKey(_)
-        | Action::DeleteAccount(_) => {
+        | Action::DeleteAccount(_)
+        | Action::Delegate(_) => {
             if account.is_none() {
                 return Err(ActionErrorKind::AccountDoesNotExist {
                     account_id: account_id.clone(),
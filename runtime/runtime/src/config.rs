@@ -11,7 +11,7 @@ pub use near_primitives::num_rational::Rational;
 pub use near_primitives::runtime::config::RuntimeConfig;
 use near_primitives::runtime::fees::{transfer_exec_fee, transfer_send_fee, RuntimeFeesConfig};
 use near_primitives::transaction::{
-    Action, AddKeyAction, DeployContractAction, FunctionCallAction, Transaction,
+    Action, AddKeyAction, DelegateAction, DeployContractAction, FunctionCallAction, Transaction,
 };
 use near_primitives::types::{AccountId, Balance, Gas};
 use near_primitives::version::{is_implicit_account_creation_enabled, ProtocolVersion};
@@ -120,6 +120,7 @@ pub fn total_send_fees(
             },
             DeleteKey(_) => cfg.delete_key_cost.send_fee(sender_is_receiver),
             DeleteAccount(_) => cfg.delete_account_cost.send_fee(sender_is_receiver),
+            Delegate(_) => cfg.delegate_cost.send_fee(sender_is_receiver),
         };
         result = safe_add_gas(result, delta)?;
     }
@@ -170,9 +171,47 @@ pub fn exec_fee(
         },
         DeleteKey(_) => cfg.delete_key_cost.exec_fee(),
         DeleteAccount(_) => cfg.delete_account_cost.exec_fee(),
+        Delegate(_) => cfg.delegate_cost.exec_fee(),
     }
 }
 
+/// Fees burnt when the receipt with the delegated actions is created, which
+/// is when the delegate action is executed.
+pub fn delegated_actions_send_fees(
+    config: &RuntimeFeesConfig,
+    delegate_action: &DelegateAction,
+    current_protocol_version: ProtocolVersion,
+) -> Result<Gas, IntegerOverflowError> {
+    let sender_is_receiver = delegate_action.sender_id == delegate_action.receiver_id;
+    safe_add_gas(
+        config.action_receipt_creation_config.send_fee(sender_is_receiver),
+        total_send_fees(
+            config,
+            sender_is_receiver,
+            &delegate_action.get_actions(),
+            &delegate_action.receiver_id,
+            current_protocol_version,
+        )?,
+    )
+}
+
+/// Fees burnt when the receipt with the delegated actions is executed.
+pub fn delegated_actions_exec_fees(
+    config: &RuntimeFeesConfig,
+    delegate_action: &DelegateAction,
+    current_protocol_version: ProtocolVersion,
+) -> Result<Gas, IntegerOverflowError> {
+    safe_add_gas(
+        config.action_receipt_creation_config.exec_fee(),
+        total_prepaid_exec_fees(
+            config,
+            &delegate_action.get_actions(),
+            &delegate_action.receiver_id,
+            current_protocol_version,
+        )?,
+    )
+}
+
 /// Returns transaction costs for a given transaction.
 pub fn tx_cost(
     config: &RuntimeFeesConfig,
@@ -239,23 +278,48 @@ pub fn total_prepaid_exec_fees(
 ) -> Result<Gas, IntegerOverflowError> {
     let mut result = 0;
     for action in actions {
-        let delta = exec_fee(config, action, receiver_id, current_protocol_version);
+        let mut delta = exec_fee(config, action, receiver_id, current_protocol_version);
+        if let Action::Delegate(signed_delegate_action) = action {
+            // The receipt with the delegated actions is sent and executed
+            // with the gas prepaid for the delegate action.
+            let delegate_action = &signed_delegate_action.delegate_action;
+            delta = safe_add_gas(
+                delta,
+                delegated_actions_send_fees(config, delegate_action, current_protocol_version)?,
+            )?;
+            delta = safe_add_gas(
+                delta,
+                delegated_actions_exec_fees(config, delegate_action, current_protocol_version)?,
+            )?;
+        }
         result = safe_add_gas(result, delta)?;
     }
     Ok(result)
 }
-/// Get the total sum of deposits for given actions.
+/// Get the total sum of deposits for given actions, including the delegated
+/// ones.
 pub fn total_deposit(actions: &[Action]) -> Result<Balance, IntegerOverflowError> {
     let mut total_balance: Balance = 0;
     for action in actions {
-        total_balance = safe_add_balance(total_balance, action.get_deposit_balance())?;
+        let action_balance = match action {
+            Action::Delegate(a) => total_deposit(&a.delegate_action.get_actions())?,
+            _ => action.get_deposit_balance(),
+        };
+        total_balance = safe_add_balance(total_balance, action_balance)?;
     }
     Ok(total_balance)
 }
 
-/// Get the total sum of prepaid gas for given actions.
+/// Get the total sum of prepaid gas for given actions, including the
+/// delegated ones.
 pub fn total_prepaid_gas(actions: &[Action]) -> Result<Gas, IntegerOverflowError> {
-    actions.iter().try_fold(0, |acc, action| safe_add_gas(acc, action.get_prepaid_gas()))
+    actions.iter().try_fold(0, |acc, action| {
+        let action_gas = match action {
+            Action::Delegate(a) => total_prepaid_gas(&a.delegate_action.get_actions())?,
+            _ => action.get_prepaid_gas(),
+        };
+        safe_add_gas(acc, action_gas)
+    })
 }
 
 #[cfg(test)]
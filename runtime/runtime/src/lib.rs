@@ -45,7 +45,7 @@ use near_primitives::{
 use near_store::{
     get, get_account, get_postponed_receipt, get_received_data, remove_postponed_receipt, set,
     set_account, set_postponed_receipt, set_received_data, PartialStorage, ShardTries,
-    StorageError, Trie, TrieChanges, TrieUpdate,
+    StorageError, Trie, TrieChanges, TriePrefetcher, TrieUpdate,
 };
 use near_store::{set_access_key, set_code};
 use near_vm_logic::types::PromiseResult;
@@ -70,6 +70,7 @@ pub mod config;
 pub mod ext;
 mod genesis;
 mod metrics;
+mod prefetch;
 pub mod state_viewer;
 mod verifier;
 
@@ -229,7 +230,7 @@ impl Runtime {
             state_update,
             apply_state.gas_price,
             signed_transaction,
-            true,
+            apply_state.verify_signatures,
             Some(apply_state.block_index),
             apply_state.current_protocol_version,
         ) {
@@ -442,6 +443,16 @@ impl Runtime {
                     apply_state.current_protocol_version,
                 )?;
             }
+            Action::Delegate(signed_delegate_action) => {
+                apply_delegate_action(
+                    state_update,
+                    apply_state,
+                    action_receipt,
+                    account_id,
+                    signed_delegate_action,
+                    &mut result,
+                )?;
+            }
         };
         Ok(result)
     }
@@ -1212,6 +1223,14 @@ impl Runtime {
             });
         }
 
+        // Warm up the shard cache with the state the transactions and the
+        // receipts are going to read.  Prefetching stops once the prefetcher
+        // is dropped at the end of this function.
+        let prefetcher = TriePrefetcher::new_if_enabled(&trie, root);
+        if let Some(prefetcher) = &prefetcher {
+            prefetch::prefetch(prefetcher, transactions, incoming_receipts);
+        }
+
         let mut outgoing_receipts = Vec::new();
         let mut validator_proposals = vec![];
         let mut local_receipts = vec![];
@@ -1467,13 +1486,14 @@ mod tests {
     use near_primitives::test_utils::{account_new, MockEpochInfoProvider};
     use near_primitives::transaction::DeployContractAction;
     use near_primitives::transaction::{
-        AddKeyAction, DeleteKeyAction, FunctionCallAction, TransferAction,
+        AddKeyAction, DelegateAction, DeleteKeyAction, FunctionCallAction, SignedDelegateAction,
+        TransferAction,
     };
     use near_primitives::types::MerkleHash;
     use near_primitives::version::PROTOCOL_VERSION;
-    use near_store::set_access_key;
     use near_store::test_utils::create_tries;
     use near_store::StoreCompiledContractCache;
+    use near_store::{get_access_key, set_access_key};
     use near_vm_runner::get_contract_cache_key;
     use near_vm_runner::internal::VMKind;
     use testlib::runtime_utils::{alice_account, bob_account};
@@ -1586,6 +1606,7 @@ mod tests {
             config: Arc::new(RuntimeConfig::test()),
             cache: Some(Arc::new(StoreCompiledContractCache { store: tries.get_store() })),
             is_new_chunk: true,
+            verify_signatures: true,
             migration_data: Arc::new(MigrationData::default()),
             migration_flags: MigrationFlags::default(),
         };
@@ -2314,6 +2335,71 @@ mod tests {
         assert_eq!(initial_account_state.storage_usage(), final_account_state.storage_usage());
     }
 
+    #[test]
+    fn test_delegate_action() {
+        let (runtime, tries, root, apply_state, signer, epoch_info_provider) =
+            setup_runtime(to_yocto(1_000_000), 0, 10u64.pow(15));
+
+        let delegate_action = DelegateAction {
+            sender_id: alice_account(),
+            receiver_id: bob_account(),
+            actions: vec![Action::Transfer(TransferAction { deposit: 1 }).try_into().unwrap()],
+            nonce: 1,
+            max_block_height: 10,
+            public_key: signer.public_key(),
+        };
+        let actions =
+            vec![Action::Delegate(SignedDelegateAction::new(delegate_action.clone(), &*signer))];
+        let receipts = create_receipts_with_actions(alice_account(), signer.clone(), actions);
+
+        let apply = |root| {
+            runtime
+                .apply(
+                    tries.get_trie_for_shard(ShardUId::single_shard()),
+                    root,
+                    &None,
+                    &apply_state,
+                    &receipts,
+                    &[],
+                    &epoch_info_provider,
+                    None,
+                )
+                .unwrap()
+        };
+        let apply_result = apply(root);
+        assert_eq!(apply_result.outcomes[0].outcome.status, ExecutionStatus::SuccessValue(vec![]));
+        let delegated_receipt = &apply_result.outgoing_receipts[0];
+        assert_eq!(delegated_receipt.predecessor_id, alice_account());
+        assert_eq!(delegated_receipt.receiver_id, bob_account());
+        match &delegated_receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => {
+                assert_eq!(action_receipt.signer_id, alice_account());
+                assert_eq!(action_receipt.actions, delegate_action.get_actions());
+            }
+            _ => panic!("expected an action receipt"),
+        }
+        let (store_update, root) =
+            tries.apply_all(&apply_result.trie_changes, ShardUId::single_shard());
+        store_update.commit().unwrap();
+        let state_update = tries.new_trie_update(ShardUId::single_shard(), root);
+        let access_key =
+            get_access_key(&state_update, &alice_account(), &signer.public_key()).unwrap().unwrap();
+        assert_eq!(access_key.nonce, 1);
+
+        // The same delegate action can't be executed twice.
+        let apply_result = apply(root);
+        assert_eq!(
+            apply_result.outcomes[0].outcome.status,
+            ExecutionStatus::Failure(TxExecutionError::ActionError(ActionError {
+                index: Some(0),
+                kind: ActionErrorKind::DelegateActionInvalidNonce {
+                    delegate_nonce: 1,
+                    ak_nonce: 1
+                },
+            }))
+        );
+    }
+
     #[test]
     fn test_delete_key_underflow() {
         let initial_locked = to_yocto(500_000);
@@ -0,0 +1,148 @@
+//! Selection of the state to prefetch before applying a chunk, see
+//! [`near_store::TriePrefetcher`].
+
+use near_primitives::receipt::{Receipt, ReceiptEnum};
+use near_primitives::transaction::{Action, SignedTransaction};
+use near_primitives::trie_key::TrieKey;
+use near_store::TriePrefetcher;
+
+/// Queues prefetching of the state which applying the transactions and the
+/// receipts is going to read first.
+pub(crate) fn prefetch(
+    prefetcher: &TriePrefetcher,
+    transactions: &[SignedTransaction],
+    receipts: &[Receipt],
+) {
+    let hot_data_keys: Vec<&[u8]> = prefetcher.hot_data_keys().collect();
+    for key in keys_to_prefetch(transactions, receipts, &hot_data_keys) {
+        prefetcher.prefetch(&key);
+    }
+}
+
+/// Returns the signers’ accounts and access keys of the transactions, the
+/// receivers’ accounts of the receipts and, for function calls, the receivers’
+/// contract code and hot contract data.
+fn keys_to_prefetch(
+    transactions: &[SignedTransaction],
+    receipts: &[Receipt],
+    hot_data_keys: &[&[u8]],
+) -> Vec<TrieKey> {
+    let mut keys = vec![];
+    for signed_transaction in transactions {
+        let transaction = &signed_transaction.transaction;
+        keys.push(TrieKey::Account { account_id: transaction.signer_id.clone() });
+        keys.push(TrieKey::AccessKey {
+            account_id: transaction.signer_id.clone(),
+            public_key: transaction.public_key.clone(),
+        });
+    }
+    for receipt in receipts {
+        let action_receipt = match &receipt.receipt {
+            ReceiptEnum::Action(action_receipt) => action_receipt,
+            // Data receipts only touch postponed receipts which aren't known
+            // upfront.
+            ReceiptEnum::Data(_) => continue,
+        };
+        let account_id = &receipt.receiver_id;
+        keys.push(TrieKey::Account { account_id: account_id.clone() });
+        let mut is_function_call = false;
+        for action in &action_receipt.actions {
+            match action {
+                Action::FunctionCall(_) => is_function_call = true,
+                Action::AddKey(add_key) => keys.push(TrieKey::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: add_key.public_key.clone(),
+                }),
+                Action::DeleteKey(delete_key) => keys.push(TrieKey::AccessKey {
+                    account_id: account_id.clone(),
+                    public_key: delete_key.public_key.clone(),
+                }),
+                _ => {}
+            }
+        }
+        if is_function_call {
+            keys.push(TrieKey::ContractCode { account_id: account_id.clone() });
+            keys.extend(hot_data_keys.iter().map(|key| TrieKey::ContractData {
+                account_id: account_id.clone(),
+                key: key.to_vec(),
+            }));
+        }
+    }
+    keys
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType, PublicKey};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::receipt::{ActionReceipt, DataReceipt};
+    use near_primitives::transaction::{DeleteKeyAction, FunctionCallAction, TransferAction};
+    use near_primitives::types::AccountId;
+
+    use super::*;
+
+    #[test]
+    fn test_keys_to_prefetch() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let signer = InMemorySigner::from_seed(alice.clone(), KeyType::ED25519, "alice.near");
+        let transaction = SignedTransaction::send_money(
+            1,
+            alice.clone(),
+            bob.clone(),
+            &signer,
+            1,
+            CryptoHash::default(),
+        );
+        let public_key = PublicKey::empty(KeyType::ED25519);
+        let receipt = |receiver_id: &AccountId, receipt| Receipt {
+            predecessor_id: alice.clone(),
+            receiver_id: receiver_id.clone(),
+            receipt_id: CryptoHash::default(),
+            receipt,
+        };
+        let action_receipt = |actions| {
+            ReceiptEnum::Action(ActionReceipt {
+                signer_id: alice.clone(),
+                signer_public_key: public_key.clone(),
+                gas_price: 0,
+                output_data_receivers: vec![],
+                input_data_ids: vec![],
+                actions,
+            })
+        };
+        let receipts = vec![
+            receipt(&alice, action_receipt(vec![Action::Transfer(TransferAction { deposit: 1 })])),
+            receipt(
+                &bob,
+                action_receipt(vec![
+                    Action::FunctionCall(FunctionCallAction {
+                        method_name: "f".to_string(),
+                        args: vec![],
+                        gas: 1,
+                        deposit: 0,
+                    }),
+                    Action::DeleteKey(DeleteKeyAction { public_key: public_key.clone() }),
+                ]),
+            ),
+            receipt(
+                &bob,
+                ReceiptEnum::Data(DataReceipt { data_id: CryptoHash::default(), data: None }),
+            ),
+        ];
+
+        let keys = keys_to_prefetch(&[transaction], &receipts, &[b"STATE"]);
+        assert_eq!(
+            keys,
+            vec![
+                TrieKey::Account { account_id: alice.clone() },
+                TrieKey::AccessKey { account_id: alice.clone(), public_key: signer.public_key },
+                TrieKey::Account { account_id: alice },
+                TrieKey::Account { account_id: bob.clone() },
+                TrieKey::AccessKey { account_id: bob.clone(), public_key },
+                TrieKey::ContractCode { account_id: bob.clone() },
+                TrieKey::ContractData { account_id: bob, key: b"STATE".to_vec() },
+            ]
+        );
+    }
+}
@@ -208,6 +208,7 @@ impl TrieViewer {
             config: config.clone(),
             cache: view_state.cache,
             is_new_chunk: false,
+            verify_signatures: true,
             migration_data: Arc::new(MigrationData::default()),
             migration_flags: MigrationFlags::default(),
         };
@@ -9,8 +9,8 @@ use near_primitives::{
     },
     receipt::{ActionReceipt, DataReceipt, Receipt, ReceiptEnum},
     transaction::{
-        Action, AddKeyAction, DeployContractAction, FunctionCallAction, SignedTransaction,
-        StakeAction,
+        Action, AddKeyAction, DeployContractAction, FunctionCallAction, SignedDelegateAction,
+        SignedTransaction, StakeAction,
     },
     types::{AccountId, Balance},
     version::ProtocolVersion,
@@ -55,6 +55,21 @@ pub fn validate_transaction(
         .into());
     }
 
+    if !checked_feature!(
+        "protocol_feature_delegate_action",
+        DelegateAction,
+        current_protocol_version
+    ) && transaction.actions.iter().any(|action| matches!(action, Action::Delegate(_)))
+    {
+        return Err(InvalidTxError::ActionsValidation(
+            ActionsValidationError::UnsupportedProtocolFeature {
+                protocol_feature: "DelegateAction".to_string(),
+                version: current_protocol_version,
+            },
+        )
+        .into());
+    }
+
     validate_actions(&config.wasm_config.limit_config, &transaction.actions)
         .map_err(InvalidTxError::ActionsValidation)?;
 
@@ -329,9 +344,22 @@ pub fn validate_action(
         Action::AddKey(a) => validate_add_key_action(limit_config, a),
         Action::DeleteKey(_) => Ok(()),
         Action::DeleteAccount(_) => Ok(()),
+        Action::Delegate(a) => validate_delegate_action(limit_config, a),
     }
 }
 
+/// Validates `SignedDelegateAction` by checking its signature and validating
+/// the delegated actions the same way as the actions of a receipt.
+fn validate_delegate_action(
+    limit_config: &VMLimitConfig,
+    signed_delegate_action: &SignedDelegateAction,
+) -> Result<(), ActionsValidationError> {
+    if !signed_delegate_action.verify() {
+        return Err(ActionsValidationError::InvalidDelegateActionSignature);
+    }
+    validate_actions(limit_config, &signed_delegate_action.delegate_action.get_actions())
+}
+
 /// Validates `DeployContractAction`. Checks that the given contract size doesn't exceed the limit.
 fn validate_deploy_contract_action(
     limit_config: &VMLimitConfig,
@@ -464,10 +492,11 @@ mod tests {
     use near_primitives::hash::{hash, CryptoHash};
     use near_primitives::test_utils::account_new;
     use near_primitives::transaction::{
-        CreateAccountAction, DeleteAccountAction, DeleteKeyAction, StakeAction, TransferAction,
+        CreateAccountAction, DelegateAction, DeleteAccountAction, DeleteKeyAction, StakeAction,
+        TransferAction,
     };
     use near_primitives::types::{AccountId, Balance, MerkleHash, StateChangeCause};
-    use near_primitives::version::PROTOCOL_VERSION;
+    use near_primitives::version::{ProtocolFeature, PROTOCOL_VERSION};
     use near_store::test_utils::create_tries;
     use testlib::runtime_utils::{alice_account, bob_account, eve_dot_alice_account};
 
@@ -1509,4 +1538,82 @@ mod tests {
         )
         .expect("valid action");
     }
+
+    fn signed_delegate_action(actions: Vec<Action>) -> SignedDelegateAction {
+        let signer = InMemorySigner::from_seed(bob_account(), KeyType::ED25519, "bob");
+        SignedDelegateAction::new(
+            DelegateAction {
+                sender_id: bob_account(),
+                receiver_id: alice_account(),
+                actions: actions.into_iter().map(|action| action.try_into().unwrap()).collect(),
+                nonce: 1,
+                max_block_height: 100,
+                public_key: signer.public_key(),
+            },
+            &signer,
+        )
+    }
+
+    #[test]
+    fn test_validate_action_valid_delegate() {
+        validate_action(
+            &VMLimitConfig::test(),
+            &Action::Delegate(signed_delegate_action(vec![Action::Transfer(TransferAction {
+                deposit: 1,
+            })])),
+        )
+        .expect("valid action");
+    }
+
+    #[test]
+    fn test_validate_action_delegate_validates_inner_actions() {
+        assert_eq!(
+            validate_action(
+                &VMLimitConfig::test(),
+                &Action::Delegate(signed_delegate_action(vec![
+                    Action::DeleteAccount(DeleteAccountAction { beneficiary_id: alice_account() }),
+                    Action::CreateAccount(CreateAccountAction {}),
+                ])),
+            )
+            .expect_err("expected an error"),
+            ActionsValidationError::DeleteActionMustBeFinal,
+        );
+    }
+
+    #[test]
+    fn test_validate_action_delegate_invalid_signature() {
+        let mut signed_delegate_action = signed_delegate_action(vec![]);
+        signed_delegate_action.delegate_action.nonce += 1;
+        assert_eq!(
+            validate_action(&VMLimitConfig::test(), &Action::Delegate(signed_delegate_action))
+                .expect_err("expected an error"),
+            ActionsValidationError::InvalidDelegateActionSignature,
+        );
+    }
+
+    #[test]
+    fn test_validate_transaction_delegate_unsupported() {
+        let config = RuntimeConfig::test();
+        let (signer, _, gas_price) =
+            setup_common(TESTING_INIT_BALANCE, 0, Some(AccessKey::full_access()));
+        let transaction = SignedTransaction::from_actions(
+            1,
+            alice_account(),
+            bob_account(),
+            &*signer,
+            vec![Action::Delegate(signed_delegate_action(vec![]))],
+            CryptoHash::default(),
+        );
+        let version = ProtocolFeature::FixContractLoadingCost.protocol_version();
+        assert_eq!(
+            validate_transaction(&config, gas_price, &transaction, true, version)
+                .expect_err("expected an error"),
+            RuntimeError::InvalidTxError(InvalidTxError::ActionsValidation(
+                ActionsValidationError::UnsupportedProtocolFeature {
+                    protocol_feature: "DelegateAction".to_string(),
+                    version,
+                },
+            )),
+        );
+    }
 }
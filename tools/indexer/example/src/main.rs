@@ -10,7 +10,10 @@ use near_indexer;
 
 mod configs;
 
-async fn listen_blocks(mut stream: mpsc::Receiver<near_indexer::StreamerMessage>) {
+async fn listen_blocks(
+    mut stream: mpsc::Receiver<near_indexer::StreamerMessage>,
+    acknowledger: near_indexer::Acknowledger,
+) {
     while let Some(streamer_message) = stream.recv().await {
         // TODO: handle data as you need
         // Example of `StreamerMessage` with all the data (the data is synthetic)
@@ -253,6 +256,9 @@ async fn listen_blocks(mut stream: mpsc::Receiver<near_indexer::StreamerMessage>
             streamer_message.shards.iter().map(|shard| if let Some(chunk) = &shard.chunk { chunk.receipts.len() } else { 0usize }).sum::<usize>(),
             streamer_message.shards.iter().map(|shard| shard.receipt_execution_outcomes.len()).sum::<usize>(),
         );
+        // Once the message is handled, acknowledge it so that it isn't streamed
+        // again after the indexer is restarted.
+        acknowledger.ack(&streamer_message);
     }
 }
 
@@ -278,12 +284,15 @@ fn main() -> Result<()> {
                 home_dir,
                 sync_mode: near_indexer::SyncModeEnum::FromInterruption,
                 await_for_node_synced: near_indexer::AwaitForNodeSyncedEnum::WaitForFullSync,
+                finality: near_indexer::near_primitives::types::Finality::Final,
+                buffer_size: 100,
+                filter: near_indexer::IndexerFilter::default(),
             };
             let system = actix::System::new();
             system.block_on(async move {
                 let indexer = near_indexer::Indexer::new(indexer_config).expect("Indexer::new()");
-                let stream = indexer.streamer();
-                actix::spawn(listen_blocks(stream));
+                let (stream, acknowledger) = indexer.streamer_with_ack();
+                actix::spawn(listen_blocks(stream, acknowledger));
             });
             system.run()?;
         }
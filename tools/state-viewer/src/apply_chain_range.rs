@@ -1,8 +1,11 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs::File;
 use std::io::Write;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Mutex;
 
+use anyhow::Context;
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use near_chain::chain::collect_receipts_from_response;
@@ -12,14 +15,15 @@ use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter}
 use near_chain_configs::Genesis;
 use near_primitives::borsh::maybestd::sync::Arc;
 use near_primitives::hash::CryptoHash;
-use near_primitives::receipt::DelayedReceiptIndices;
+use near_primitives::receipt::{DelayedReceiptIndices, Receipt};
+use near_primitives::shard_layout::ShardUId;
 use near_primitives::transaction::{
     Action, ExecutionOutcomeWithId, ExecutionOutcomeWithIdAndProof,
 };
 use near_primitives::trie_key::TrieKey;
 use near_primitives::types::chunk_extra::ChunkExtra;
-use near_primitives::types::{BlockHeight, ShardId};
-use near_store::{get, DBCol, Store};
+use near_primitives::types::{BlockHeight, RawStateChangesWithTrieKey, ShardId, StateRoot};
+use near_store::{get, DBCol, KeyForStateChanges, ShardTries, StorageError, Store};
 use nearcore::NightshadeRuntime;
 
 fn timestamp_ms() -> u64 {
@@ -39,12 +43,23 @@ struct ProgressReporter {
     non_empty_blocks: AtomicU64,
     // Total gas burned (in TGas)
     tgas_burned: AtomicU64,
+    // Number of chunks whose results differ from the stored ones. Only
+    // counted when comparing results.
+    divergent: AtomicU64,
 }
 
 impl ProgressReporter {
     pub fn inc_and_report_progress(&self, gas_burnt: u64) {
-        let ProgressReporter { cnt, ts, all, skipped, empty_blocks, non_empty_blocks, tgas_burned } =
-            self;
+        let ProgressReporter {
+            cnt,
+            ts,
+            all,
+            skipped,
+            empty_blocks,
+            non_empty_blocks,
+            tgas_burned,
+            divergent: _,
+        } = self;
         if gas_burnt == 0 {
             empty_blocks.fetch_add(1, Ordering::Relaxed);
         } else {
@@ -103,6 +118,219 @@ fn old_outcomes(
         .collect()
 }
 
+/// How a receipt or an outcome produced by the replay differs from the stored
+/// one with the same id.
+#[derive(Debug, PartialEq, Eq)]
+enum Difference {
+    /// Only the database has it.
+    Missing,
+    /// Only the replay produced it.
+    Unexpected,
+    /// Both have it but with different contents.
+    Different,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Difference::Missing => "is missing from the replay",
+            Difference::Unexpected => "is only in the replay",
+            Difference::Different => "differs",
+        })
+    }
+}
+
+/// Differences between the results of re-applying a chunk and the results
+/// stored in the database.
+struct Divergence {
+    /// Stored and replayed `ChunkExtra` if they differ.
+    chunk_extra: Option<(ChunkExtra, ChunkExtra)>,
+    /// Keys whose values in the stored post-state differ from the values
+    /// written by the replay, or an error if the stored state is unavailable.
+    trie_keys: Result<Vec<TrieKey>, StorageError>,
+    outgoing_receipts: Vec<(CryptoHash, Difference)>,
+    outcomes: Vec<(CryptoHash, Difference)>,
+}
+
+impl Divergence {
+    fn is_empty(&self) -> bool {
+        self.chunk_extra.is_none()
+            && self.trie_keys.as_ref().map_or(false, |keys| keys.is_empty())
+            && self.outgoing_receipts.is_empty()
+            && self.outcomes.is_empty()
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some((existing, new)) = &self.chunk_extra {
+            writeln!(f, "  chunk_extra: {:?}\n  existing_chunk_extra: {:?}", new, existing)?;
+        }
+        match &self.trie_keys {
+            Ok(trie_keys) => {
+                for trie_key in trie_keys {
+                    writeln!(f, "  trie key {:?} differs", trie_key)?;
+                }
+            }
+            Err(err) => writeln!(f, "  can't compare the state: {}", err)?,
+        }
+        for (receipt_id, difference) in &self.outgoing_receipts {
+            writeln!(f, "  outgoing receipt {} {}", receipt_id, difference)?;
+        }
+        for (id, difference) in &self.outcomes {
+            writeln!(f, "  outcome {} {}", id, difference)?;
+        }
+        Ok(())
+    }
+}
+
+/// Matches stored and replayed items by id and returns the ids of those which
+/// differ, in the order of the stored items followed by the replayed ones.
+fn diff_by_id<T: PartialEq>(
+    existing: &[(CryptoHash, T)],
+    new: &[(CryptoHash, T)],
+) -> Vec<(CryptoHash, Difference)> {
+    let new_by_id: HashMap<&CryptoHash, &T> = new.iter().map(|(id, item)| (id, item)).collect();
+    let existing_ids: HashSet<&CryptoHash> = existing.iter().map(|(id, _)| id).collect();
+    let mut differences = vec![];
+    for (id, item) in existing {
+        match new_by_id.get(id) {
+            None => differences.push((*id, Difference::Missing)),
+            Some(new_item) if *new_item != item => differences.push((*id, Difference::Different)),
+            Some(_) => {}
+        }
+    }
+    for (id, _) in new {
+        if !existing_ids.contains(id) {
+            differences.push((*id, Difference::Unexpected));
+        }
+    }
+    differences
+}
+
+/// Returns the keys whose values in the stored post-state differ from the
+/// values they have after the replay.
+///
+/// Only keys changed either by the replay or by the original application of
+/// the block need to be checked.  The latter are known from the stored
+/// `StateChanges` which cover account-specific keys of all tracked shards;
+/// keys of other shards are absent from both tries and so never differ.
+fn diff_state(
+    tries: &ShardTries,
+    shard_uid: ShardUId,
+    prev_state_root: &StateRoot,
+    existing_state_root: &StateRoot,
+    new_changes: &[RawStateChangesWithTrieKey],
+    existing_changes: Vec<RawStateChangesWithTrieKey>,
+) -> Result<Vec<TrieKey>, StorageError> {
+    let trie = tries.get_view_trie_for_shard(shard_uid);
+    let new_values: HashMap<Vec<u8>, Option<Vec<u8>>> = new_changes
+        .iter()
+        .filter_map(|change| {
+            let value = change.changes.last()?.data.clone();
+            Some((change.trie_key.to_vec(), value))
+        })
+        .collect();
+    // Sorted by the raw key so that the output is deterministic.
+    let trie_keys: BTreeMap<Vec<u8>, TrieKey> = new_changes
+        .iter()
+        .chain(existing_changes.iter())
+        .map(|change| (change.trie_key.to_vec(), change.trie_key.clone()))
+        .collect();
+    let mut differences = vec![];
+    for (key, trie_key) in trie_keys {
+        let new_value = match new_values.get(&key) {
+            Some(value) => value.clone(),
+            None => trie.get(prev_state_root, &key)?,
+        };
+        if trie.get(existing_state_root, &key)? != new_value {
+            differences.push(trie_key);
+        }
+    }
+    Ok(differences)
+}
+
+/// Compares the results of re-applying a chunk with the results stored in
+/// the database.  Fails if the stored results can't be read.
+fn diff_with_existing(
+    chain_store: &ChainStore,
+    tries: &ShardTries,
+    block_hash: &CryptoHash,
+    shard_uid: ShardUId,
+    prev_state_root: &StateRoot,
+    chunk_extra: &ChunkExtra,
+    apply_result: &ApplyTransactionResult,
+) -> anyhow::Result<Divergence> {
+    // Chunk extra is stored for blocks without a chunk too.
+    let existing_chunk_extra =
+        chain_store.get_chunk_extra(block_hash, &shard_uid).context("no stored chunk extra")?;
+    let trie_keys = if existing_chunk_extra.state_root() == chunk_extra.state_root() {
+        Ok(vec![])
+    } else {
+        let existing_changes = KeyForStateChanges::for_block(block_hash)
+            .find_iter(chain_store.store())
+            .collect::<Result<Vec<_>, _>>()
+            .context("can't read the stored state changes")?;
+        diff_state(
+            tries,
+            shard_uid,
+            prev_state_root,
+            existing_chunk_extra.state_root(),
+            apply_result.trie_changes.state_changes(),
+            existing_changes,
+        )
+    };
+
+    let shard_id = shard_uid.shard_id as ShardId;
+    let existing_receipts = chain_store
+        .get_outgoing_receipts(block_hash, shard_id)
+        .map(|receipts| receipts.to_vec())
+        .unwrap_or_default();
+    let receipts_by_id = |receipts: &[Receipt]| -> Vec<(CryptoHash, Receipt)> {
+        receipts.iter().map(|receipt| (receipt.receipt_id, receipt.clone())).collect()
+    };
+    let outgoing_receipts = diff_by_id(
+        &receipts_by_id(&existing_receipts),
+        &receipts_by_id(&apply_result.outgoing_receipts),
+    );
+
+    let outcomes = if existing_chunk_extra.outcome_root() == chunk_extra.outcome_root() {
+        vec![]
+    } else {
+        let mut existing_outcomes = vec![];
+        for id in chain_store
+            .get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)
+            .context("can't read the stored outcome ids")?
+        {
+            let outcomes =
+                chain_store.get_outcomes_by_id(&id).context("can't read the stored outcomes")?;
+            existing_outcomes.extend(
+                outcomes
+                    .into_iter()
+                    .filter(|outcome| outcome.block_hash == *block_hash)
+                    .map(|outcome| (outcome.outcome_with_id.id, outcome.outcome_with_id.outcome)),
+            );
+        }
+        let new_outcomes: Vec<_> = apply_result
+            .outcomes
+            .iter()
+            .map(|outcome| (outcome.id, outcome.outcome.clone()))
+            .collect();
+        diff_by_id(&existing_outcomes, &new_outcomes)
+    };
+
+    Ok(Divergence {
+        chunk_extra: if smart_equals(&existing_chunk_extra, chunk_extra) {
+            None
+        } else {
+            Some((existing_chunk_extra.as_ref().clone(), chunk_extra.clone()))
+        },
+        trie_keys,
+        outgoing_receipts,
+        outcomes,
+    })
+}
+
 fn maybe_add_to_csv(csv_file_mutex: &Mutex<Option<&mut File>>, s: &str) {
     let mut csv_file = csv_file_mutex.lock().unwrap();
     if let Some(csv_file) = csv_file.as_mut() {
@@ -120,6 +348,7 @@ fn apply_block_from_range(
     verbose_output: bool,
     csv_file_mutex: &Arc<Mutex<Option<&mut File>>>,
     only_contracts: bool,
+    compare_results: bool,
 ) {
     // normally save_trie_changes depends on whether the node is
     // archival, but here we don't care, and can just set it to false
@@ -141,6 +370,7 @@ fn apply_block_from_range(
     let mut num_tx = 0;
     let mut num_receipt = 0;
     let chunk_present: bool;
+    let prev_state_root: StateRoot;
 
     let block_author = runtime_adapter
         .get_block_producer(block.header().epoch_id(), block.header().height())
@@ -155,12 +385,14 @@ fn apply_block_from_range(
     } else if block.chunks()[shard_id as usize].height_included() == height {
         chunk_present = true;
         let res_existing_chunk_extra = chain_store.get_chunk_extra(&block_hash, &shard_uid);
+        // When comparing the results, a missing chunk extra is reported as a
+        // difference.
         assert!(
-            res_existing_chunk_extra.is_ok(),
+            compare_results || res_existing_chunk_extra.is_ok(),
             "Can't get existing chunk extra for block #{}",
             height
         );
-        existing_chunk_extra = Some(res_existing_chunk_extra.unwrap());
+        existing_chunk_extra = res_existing_chunk_extra.ok();
         let chunk_hash = block.chunks()[shard_id as usize].chunk_hash();
         let chunk = chain_store.get_chunk(&chunk_hash).unwrap_or_else(|error| {
             panic!(
@@ -212,6 +444,7 @@ fn apply_block_from_range(
 
         num_receipt = receipts.len();
         num_tx = chunk.transactions().len();
+        prev_state_root = *chunk_inner.prev_state_root();
         if only_contracts {
             let mut has_contracts = false;
             for tx in chunk.transactions() {
@@ -253,6 +486,7 @@ fn apply_block_from_range(
         let chunk_extra =
             chain_store.get_chunk_extra(block.header().prev_hash(), &shard_uid).unwrap();
         prev_chunk_extra = Some(chunk_extra.clone());
+        prev_state_root = *chunk_extra.state_root();
 
         runtime_adapter
             .apply_transactions(
@@ -276,30 +510,72 @@ fn apply_block_from_range(
             .unwrap()
     };
 
-    let (outcome_root, _) = ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
-    let chunk_extra = ChunkExtra::new(
-        &apply_result.new_root,
-        outcome_root,
-        apply_result.validator_proposals,
-        apply_result.total_gas_burnt,
-        genesis.config.gas_limit,
-        apply_result.total_balance_burnt,
-    );
+    let chunk_extra = match &prev_chunk_extra {
+        // Without a chunk, the chain keeps the previous chunk extra with only
+        // the state root updated.
+        Some(prev_chunk_extra) => {
+            let mut chunk_extra = ChunkExtra::clone(prev_chunk_extra);
+            *chunk_extra.state_root_mut() = apply_result.new_root;
+            chunk_extra
+        }
+        None => {
+            let (outcome_root, _) =
+                ApplyTransactionResult::compute_outcomes_proof(&apply_result.outcomes);
+            ChunkExtra::new(
+                &apply_result.new_root,
+                outcome_root,
+                apply_result.validator_proposals.clone(),
+                apply_result.total_gas_burnt,
+                genesis.config.gas_limit,
+                apply_result.total_balance_burnt,
+            )
+        }
+    };
 
     let state_update =
         runtime_adapter.get_tries().new_trie_update(shard_uid, *chunk_extra.state_root());
     let delayed_indices =
         get::<DelayedReceiptIndices>(&state_update, &TrieKey::DelayedReceiptIndices).unwrap();
 
+    if compare_results {
+        match diff_with_existing(
+            &chain_store,
+            &runtime_adapter.get_tries(),
+            &block_hash,
+            shard_uid,
+            &prev_state_root,
+            &chunk_extra,
+            &apply_result,
+        ) {
+            Ok(divergence) if divergence.is_empty() => {}
+            Ok(divergence) => {
+                progress_reporter.divergent.fetch_add(1, Ordering::Relaxed);
+                print!(
+                    "Divergence at block_height: {}, block_hash: {}, shard_id: {}\n{}",
+                    height, block_hash, shard_id, divergence
+                );
+            }
+            Err(err) => {
+                progress_reporter.divergent.fetch_add(1, Ordering::Relaxed);
+                println!(
+                    "Can't compare the results at block_height: {}, block_hash: {}, shard_id: {}: {:#}",
+                    height, block_hash, shard_id, err
+                );
+            }
+        }
+    }
+
     match existing_chunk_extra {
         Some(existing_chunk_extra) => {
             if verbose_output {
                 println!("block_height: {}, block_hash: {}\nchunk_extra: {:#?}\nexisting_chunk_extra: {:#?}\noutcomes: {:#?}", height, block_hash, chunk_extra, existing_chunk_extra, apply_result.outcomes);
             }
-            if !smart_equals(&existing_chunk_extra, &chunk_extra) {
+            if !compare_results && !smart_equals(&existing_chunk_extra, &chunk_extra) {
                 assert!(false, "Got a different ChunkExtra:\nblock_height: {}, block_hash: {}\nchunk_extra: {:#?}\nexisting_chunk_extra: {:#?}\nnew outcomes: {:#?}\n\nold outcomes: {:#?}\n", height, block_hash, chunk_extra, existing_chunk_extra, apply_result.outcomes, old_outcomes(store, &apply_result.outcomes));
             }
         }
+        // Already reported when comparing the results.
+        None if chunk_present => {}
         None => {
             assert!(prev_chunk_extra.is_some());
             assert!(apply_result.outcomes.is_empty());
@@ -328,6 +604,12 @@ fn apply_block_from_range(
     progress_reporter.inc_and_report_progress(apply_result.total_gas_burnt);
 }
 
+/// Re-applies chunks of the shard at heights in the given range.
+///
+/// With `compare_results`, every re-applied chunk is compared with the results
+/// stored in the database and the differences are printed rather than
+/// stopping at the first `ChunkExtra` mismatch.  Returns the number of chunks
+/// whose results differ.
 pub fn apply_chain_range(
     store: Store,
     genesis: &Genesis,
@@ -339,7 +621,8 @@ pub fn apply_chain_range(
     csv_file: Option<&mut File>,
     only_contracts: bool,
     sequential: bool,
-) {
+    compare_results: bool,
+) -> u64 {
     let parent_span = tracing::debug_span!(
         target: "state_viewer",
         "apply_chain_range",
@@ -347,7 +630,8 @@ pub fn apply_chain_range(
         ?end_height,
         %shard_id,
         only_contracts,
-        sequential)
+        sequential,
+        compare_results)
     .entered();
     let runtime_adapter: Arc<dyn RuntimeAdapter> = Arc::new(runtime);
     let chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height, false);
@@ -372,6 +656,7 @@ pub fn apply_chain_range(
         empty_blocks: AtomicU64::new(0),
         non_empty_blocks: AtomicU64::new(0),
         tgas_burned: AtomicU64::new(0),
+        divergent: AtomicU64::new(0),
     };
     let process_height = |height| {
         apply_block_from_range(
@@ -384,6 +669,7 @@ pub fn apply_chain_range(
             verbose_output,
             &csv_file_mutex,
             only_contracts,
+            compare_results,
         );
     };
 
@@ -409,10 +695,19 @@ pub fn apply_chain_range(
         });
     }
 
-    println!(
-        "No differences found after applying chunks in the range {}..={} for shard_id {}",
-        start_height, end_height, shard_id
-    );
+    let divergent = progress_reporter.divergent.load(Ordering::Relaxed);
+    if divergent == 0 {
+        println!(
+            "No differences found after applying chunks in the range {}..={} for shard_id {}",
+            start_height, end_height, shard_id
+        );
+    } else {
+        println!(
+            "Found differences in {} chunks after applying chunks in the range {}..={} for shard_id {}",
+            divergent, start_height, end_height, shard_id
+        );
+    }
+    divergent
 }
 
 /**
@@ -451,19 +746,22 @@ mod test {
     use std::path::Path;
     use std::sync::Arc;
 
-    use near_chain::{ChainGenesis, Provenance};
+    use near_chain::{ChainGenesis, ChainStore, ChainStoreAccess, Provenance};
     use near_chain_configs::Genesis;
     use near_client::test_utils::TestEnv;
     use near_crypto::{InMemorySigner, KeyType};
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::{get_block_shard_uid, ShardUId};
     use near_primitives::transaction::SignedTransaction;
+    use near_primitives::types::chunk_extra::ChunkExtra;
     use near_primitives::types::{BlockHeight, BlockHeightDelta, NumBlocks};
     use near_store::test_utils::create_test_store;
-    use near_store::Store;
+    use near_store::{DBCol, Store};
     use nearcore::config::GenesisExt;
     use nearcore::config::TESTING_INIT_STAKE;
     use nearcore::NightshadeRuntime;
 
-    use crate::apply_chain_range::apply_chain_range;
+    use crate::apply_chain_range::{apply_chain_range, diff_by_id, Difference};
 
     fn setup(epoch_length: NumBlocks) -> (Store, Genesis, TestEnv) {
         let mut genesis =
@@ -536,7 +834,7 @@ mod test {
         safe_produce_blocks(&mut env, 1, epoch_length * 2 + 1, None);
 
         let runtime = NightshadeRuntime::test(Path::new("."), store.clone(), &genesis);
-        apply_chain_range(store, &genesis, None, None, 0, runtime, true, None, false, false);
+        apply_chain_range(store, &genesis, None, None, 0, runtime, true, None, false, false, false);
     }
 
     #[test]
@@ -570,6 +868,7 @@ mod test {
             Some(file.as_file_mut()),
             false,
             false,
+            false,
         );
         let mut csv = String::new();
         file.as_file_mut().seek(SeekFrom::Start(0)).unwrap();
@@ -589,4 +888,149 @@ mod test {
         assert_eq!(has_tx, 1, "{:#?}", lines);
         assert_eq!(no_tx, 8, "{:#?}", lines);
     }
+
+    #[test]
+    fn test_apply_chain_range_compare_results() {
+        let epoch_length = 4;
+        let (store, genesis, mut env) = setup(epoch_length);
+        let genesis_hash = *env.clients[0].chain.genesis().hash();
+        let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        let tx = SignedTransaction::stake(
+            1,
+            "test1".parse().unwrap(),
+            &signer,
+            TESTING_INIT_STAKE,
+            signer.public_key.clone(),
+            genesis_hash,
+        );
+        env.clients[0].process_tx(tx, false, false);
+
+        safe_produce_blocks(&mut env, 1, epoch_length * 2 + 1, None);
+
+        let runtime = NightshadeRuntime::test(Path::new("."), store.clone(), &genesis);
+        let divergent = apply_chain_range(
+            store.clone(),
+            &genesis,
+            None,
+            None,
+            0,
+            runtime,
+            false,
+            None,
+            false,
+            false,
+            true,
+        );
+        assert_eq!(divergent, 0);
+
+        // Pretend the chunk with the transaction didn’t change the state.
+        let mut chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height, false);
+        let head = chain_store.head().unwrap();
+        let block_hash = (1..=head.height)
+            .filter_map(|height| chain_store.get_block_hash_by_height(height).ok())
+            .find(|block_hash| {
+                let block = chain_store.get_block(block_hash).unwrap();
+                let chunk_header = &block.chunks()[0];
+                chunk_header.height_included() == block.header().height()
+                    && !chain_store
+                        .get_chunk(&chunk_header.chunk_hash())
+                        .unwrap()
+                        .transactions()
+                        .is_empty()
+            })
+            .unwrap();
+        let block = chain_store.get_block(&block_hash).unwrap();
+        let shard_uid = ShardUId::single_shard();
+        let chunk_extra = chain_store.get_chunk_extra(&block_hash, &shard_uid).unwrap();
+        let prev_chunk_extra =
+            chain_store.get_chunk_extra(block.header().prev_hash(), &shard_uid).unwrap();
+        let mut store_update = chain_store.store_update();
+        store_update.save_chunk_extra(
+            &block_hash,
+            &shard_uid,
+            ChunkExtra::new(
+                prev_chunk_extra.state_root(),
+                *chunk_extra.outcome_root(),
+                chunk_extra.validator_proposals().collect(),
+                chunk_extra.gas_used(),
+                chunk_extra.gas_limit(),
+                chunk_extra.balance_burnt(),
+            ),
+        );
+        store_update.commit().unwrap();
+
+        let runtime = NightshadeRuntime::test(Path::new("."), store.clone(), &genesis);
+        let divergent = apply_chain_range(
+            store, &genesis, None, None, 0, runtime, false, None, false, false, true,
+        );
+        assert_eq!(divergent, 1);
+    }
+
+    #[test]
+    fn test_apply_chain_range_compare_results_no_chunks() {
+        let epoch_length = 4;
+        let (store, genesis, mut env) = setup(epoch_length);
+        let genesis_hash = *env.clients[0].chain.genesis().hash();
+        let signer = InMemorySigner::from_seed("test1".parse().unwrap(), KeyType::ED25519, "test1");
+        // The block without a chunk follows one whose chunk burnt gas, which
+        // the chain keeps in its chunk extra.
+        let tx = SignedTransaction::send_money(
+            1,
+            "test1".parse().unwrap(),
+            "test0".parse().unwrap(),
+            &signer,
+            100,
+            genesis_hash,
+        );
+        env.clients[0].process_tx(tx, false, false);
+
+        safe_produce_blocks(&mut env, 1, epoch_length * 2 + 1, Some(4));
+
+        let runtime = NightshadeRuntime::test(Path::new("."), store.clone(), &genesis);
+        let divergent = apply_chain_range(
+            store.clone(),
+            &genesis,
+            None,
+            None,
+            0,
+            runtime,
+            false,
+            None,
+            false,
+            false,
+            true,
+        );
+        assert_eq!(divergent, 0);
+
+        // A missing chunk extra is reported rather than a panic.
+        let chain_store = ChainStore::new(store.clone(), genesis.config.genesis_height, false);
+        let head = chain_store.head().unwrap();
+        let mut store_update = store.store_update();
+        store_update.delete(
+            DBCol::ChunkExtra,
+            &get_block_shard_uid(&head.last_block_hash, &ShardUId::single_shard()),
+        );
+        store_update.commit().unwrap();
+
+        let runtime = NightshadeRuntime::test(Path::new("."), store.clone(), &genesis);
+        let divergent = apply_chain_range(
+            store, &genesis, None, None, 0, runtime, false, None, false, false, true,
+        );
+        assert_eq!(divergent, 1);
+    }
+
+    #[test]
+    fn test_diff_by_id() {
+        let id = |n: u8| CryptoHash::hash_bytes(&[n]);
+        let existing = [(id(0), 0), (id(1), 1), (id(2), 2)];
+        let new = [(id(3), 3), (id(2), 2), (id(1), 10)];
+        assert_eq!(
+            diff_by_id(&existing, &new),
+            vec![
+                (id(0), Difference::Missing),
+                (id(1), Difference::Different),
+                (id(3), Difference::Unexpected)
+            ]
+        );
+    }
 }
@@ -1,12 +1,16 @@
 use crate::commands::*;
 use crate::epoch_info;
+use crate::export::{export, ExportFormat};
 use crate::rocksdb_stats::get_rocksdb_stats;
+use crate::storage_stats::StorageStatsConfig;
 use clap::{Args, Parser, Subcommand};
+use near_chain::{ChainStore, ChainStoreAccess};
 use near_chain_configs::GenesisValidationMode;
+use near_crypto::PublicKey;
 use near_primitives::account::id::AccountId;
 use near_primitives::hash::CryptoHash;
 use near_primitives::sharding::ChunkHash;
-use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::types::{BlockHeight, ShardId, StateRoot};
 use near_store::db::Mode;
 use near_store::Store;
 use nearcore::{load_config, NearConfig};
@@ -26,6 +30,9 @@ pub enum StateViewerSubCommand {
     /// Generate a file that contains all transactions from a block.
     #[clap(alias = "dump_tx")]
     DumpTx(DumpTxCmd),
+    /// Export blocks, chunk headers, transactions, receipts and execution
+    /// outcomes to Parquet or JSONL files partitioned by height.
+    Export(ExportCmd),
     /// Print chain from start_index to end_index.
     Chain(ChainCmd),
     /// Replay headers from chain.
@@ -47,6 +54,18 @@ pub enum StateViewerSubCommand {
     /// Dump contract data in storage of given account to binary file.
     #[clap(alias = "dump_account_storage")]
     DumpAccountStorage(DumpAccountStorageCmd),
+    /// Print per-account breakdown of the storage taken by the state of a
+    /// shard as JSON.
+    #[clap(alias = "storage_stats")]
+    StorageStats(StorageStatsCmd),
+    /// Compile contracts deployed in the state of the tracked shards into the
+    /// compiled contract cache.  Requires `--readwrite`.
+    #[clap(alias = "precompile_contracts")]
+    PrecompileContracts(PrecompileContractsCmd),
+    /// Export compiled contracts of the tracked shards into a bundle or
+    /// import them from a bundle exported by another node.
+    #[clap(alias = "contract_cache")]
+    ContractCache(ContractCacheCmd),
     /// Print `EpochInfo` of an epoch given by `--epoch_id` or by `--epoch_height`.
     #[clap(alias = "epoch_info")]
     EpochInfo(EpochInfoCmd),
@@ -83,6 +102,7 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::DumpState(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpStateRedis(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpTx(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::Export(cmd) => cmd.run(near_config, store),
             StateViewerSubCommand::Chain(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::Replay(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::ApplyRange(cmd) => cmd.run(home_dir, near_config, store),
@@ -91,6 +111,11 @@ impl StateViewerSubCommand {
             StateViewerSubCommand::CheckBlock => check_block_chunk_existence(store, near_config),
             StateViewerSubCommand::DumpCode(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::DumpAccountStorage(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::StorageStats(cmd) => cmd.run(home_dir, near_config, store),
+            StateViewerSubCommand::PrecompileContracts(cmd) => {
+                cmd.run(home_dir, near_config, store)
+            }
+            StateViewerSubCommand::ContractCache(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::EpochInfo(cmd) => cmd.run(home_dir, near_config, store),
             StateViewerSubCommand::RocksDBStats(cmd) => cmd.run(&store_opener.get_path()),
             StateViewerSubCommand::Receipts(cmd) => cmd.run(near_config, store),
@@ -185,6 +210,45 @@ impl DumpTxCmd {
     }
 }
 
+#[derive(Parser)]
+pub struct ExportCmd {
+    /// Height of the first block to export, inclusive.
+    #[clap(long)]
+    start_height: BlockHeight,
+    /// Height of the last block to export, inclusive.  Defaults to the head.
+    #[clap(long)]
+    end_height: Option<BlockHeight>,
+    /// Directory to write the files to.  Each table is written to
+    /// `<output-dir>/<table>/height_from=<height>/data.<format>`.
+    #[clap(long, parse(from_os_str))]
+    output_dir: PathBuf,
+    #[clap(long, arg_enum, default_value = "parquet")]
+    format: ExportFormat,
+    /// Number of heights in a partition.  Partitions start at multiples of it.
+    #[clap(long, default_value = "10000")]
+    partition_size: BlockHeight,
+}
+
+impl ExportCmd {
+    pub fn run(self, near_config: NearConfig, store: Store) {
+        let chain_store = ChainStore::new(
+            store,
+            near_config.genesis.config.genesis_height,
+            !near_config.client_config.archive,
+        );
+        let end_height = self.end_height.unwrap_or_else(|| chain_store.head().unwrap().height);
+        export(
+            &chain_store,
+            self.start_height,
+            end_height,
+            self.partition_size,
+            self.format,
+            &self.output_dir,
+        )
+        .expect("Failed to export...")
+    }
+}
+
 #[derive(Parser)]
 pub struct ChainCmd {
     #[clap(long)]
@@ -240,10 +304,26 @@ pub struct ApplyRangeCmd {
     only_contracts: bool,
     #[clap(long)]
     sequential: bool,
+    /// Compare state, outgoing receipts and outcomes of every re-applied chunk
+    /// with the ones stored in the database and report all differences
+    /// instead of stopping at the first one.  Exits with an error if any
+    /// differences are found.
+    #[clap(long)]
+    compare_results: bool,
+    /// Run every function call on this VM as well (`wasmer0`, `wasmer2` or
+    /// `wasmtime`) and report calls on which it disagrees with the VM of the
+    /// protocol version.  Exits with an error if any are found.
+    #[cfg(feature = "differential_vm")]
+    #[clap(long)]
+    differential_vm: Option<near_vm_runner::internal::VMKind>,
 }
 
 impl ApplyRangeCmd {
     pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        #[cfg(feature = "differential_vm")]
+        if let Some(vm_kind) = self.differential_vm {
+            near_vm_runner::differential::set_secondary_vm_kind(Some(vm_kind));
+        }
         apply_range(
             self.start_index,
             self.end_index,
@@ -255,6 +335,7 @@ impl ApplyRangeCmd {
             store,
             self.only_contracts,
             self.sequential,
+            self.compare_results,
         );
     }
 }
@@ -328,6 +409,120 @@ impl DumpAccountStorageCmd {
         );
     }
 }
+
+#[derive(Parser)]
+pub struct StorageStatsCmd {
+    #[clap(long, default_value = "0")]
+    shard_id: ShardId,
+    /// Height of the block whose pre-state is analysed.  Defaults to the head.
+    #[clap(long)]
+    height: Option<BlockHeight>,
+    /// State root to analyse instead of the one at `--height`.
+    #[clap(long)]
+    state_root: Option<StateRoot>,
+    /// Accounts to analyse.  If not set, all accounts of the shard are
+    /// analysed.
+    #[clap(long)]
+    account_ids: Option<Vec<AccountId>>,
+    /// Number of the largest contract data records to list for each account.
+    #[clap(long, default_value = "10")]
+    largest_keys: usize,
+    /// Length of the contract data key prefixes to group records by.  Zero
+    /// disables the prefix histogram.
+    #[clap(long, default_value = "1")]
+    prefix_len: usize,
+    /// Only output this many accounts taking the most storage.
+    #[clap(long)]
+    limit: Option<usize>,
+    /// File to write the JSON to instead of the standard output.
+    #[clap(long, parse(from_os_str))]
+    output: Option<PathBuf>,
+}
+
+impl StorageStatsCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        storage_stats(
+            self.shard_id,
+            self.height,
+            self.state_root,
+            self.account_ids,
+            StorageStatsConfig { largest_keys: self.largest_keys, prefix_len: self.prefix_len },
+            self.limit,
+            self.output.as_deref(),
+            home_dir,
+            near_config,
+            store,
+        );
+    }
+}
+
+#[derive(Parser)]
+pub struct PrecompileContractsCmd {
+    /// Shards whose contracts to compile.  Defaults to the shards tracked by
+    /// the node.
+    #[clap(long)]
+    shard_ids: Option<Vec<ShardId>>,
+    /// Number of threads compiling contracts.  Defaults to the number of
+    /// CPUs.
+    #[clap(long)]
+    threads: Option<usize>,
+}
+
+impl PrecompileContractsCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        let num_threads = self.threads.unwrap_or_else(rayon::current_num_threads);
+        precompile_contracts(self.shard_ids, num_threads, home_dir, near_config, store);
+    }
+}
+
+#[derive(Parser)]
+pub struct ContractCacheCmd {
+    #[clap(subcommand)]
+    subcmd: ContractCacheSubCommand,
+}
+
+#[derive(Subcommand)]
+enum ContractCacheSubCommand {
+    /// Write the contracts deployed in the state of the tracked shards which
+    /// have been compiled by the node into a bundle.
+    Export {
+        /// Shards whose contracts to export.  Defaults to the shards tracked
+        /// by the node.
+        #[clap(long)]
+        shard_ids: Option<Vec<ShardId>>,
+        #[clap(long, parse(from_os_str))]
+        output: PathBuf,
+        /// File with the key signing the exported contracts, in the format
+        /// of `node_key.json`.
+        #[clap(long, parse(from_os_str))]
+        key_file: PathBuf,
+    },
+    /// Put the contracts from a bundle into the compiled contract cache.
+    /// Contracts not deployed in the tracked shards, or compiled by another
+    /// VM or with another VM config are skipped, and the ones not signed by
+    /// one of `--trusted-keys` are refused.  Requires `--readwrite`.
+    Import {
+        #[clap(long, parse(from_os_str))]
+        input: PathBuf,
+        /// Public keys whose signatures are accepted.
+        #[clap(long, required = true)]
+        trusted_keys: Vec<PublicKey>,
+    },
+}
+
+impl ContractCacheCmd {
+    pub fn run(self, home_dir: &Path, near_config: NearConfig, store: Store) {
+        match self.subcmd {
+            ContractCacheSubCommand::Export { shard_ids, output, key_file } => {
+                export_contract_cache(shard_ids, &output, &key_file, home_dir, near_config, store)
+            }
+            ContractCacheSubCommand::Import { input, trusted_keys } => {
+                import_contract_cache(&input, &trusted_keys, home_dir, near_config, store)
+            }
+        }
+    }
+}
+
 #[derive(Args)]
 pub struct EpochInfoCmd {
     #[clap(subcommand)]
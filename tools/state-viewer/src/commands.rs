@@ -1,6 +1,8 @@
 use crate::apply_chain_range::apply_chain_range;
+use crate::contract_cache::{for_each_contract_code, BundleReader, BundleWriter, Precompiler};
 use crate::state_dump::state_dump;
 use crate::state_dump::state_dump_redis;
+use crate::storage_stats::{collect_storage_stats, ShardStorageStats, StorageStatsConfig};
 use crate::tx_dump::dump_tx_from_block;
 use crate::{apply_chunk, epoch_info};
 use ansi_term::Color::Red;
@@ -9,6 +11,7 @@ use near_chain::migrations::check_if_block_is_first_with_chunk_of_version;
 use near_chain::types::{ApplyTransactionResult, BlockHeaderInfo};
 use near_chain::Error;
 use near_chain::{ChainStore, ChainStoreAccess, ChainStoreUpdate, RuntimeAdapter};
+use near_crypto::{InMemorySigner, PublicKey, Signer};
 use near_epoch_manager::EpochManager;
 use near_network::iter_peers_from_store;
 use near_primitives::account::id::AccountId;
@@ -23,13 +26,14 @@ use near_primitives::types::chunk_extra::ChunkExtra;
 use near_primitives::types::{BlockHeight, ShardId, StateRoot};
 use near_primitives_core::types::Gas;
 use near_store::test_utils::create_test_store;
-use near_store::{Store, TrieIterator};
+use near_store::{Store, StoreCompiledContractCache, TrieIterator};
+use near_vm_runner::{export_cached_contract, import_cached_contract, ContractCacheImport};
 use nearcore::{NearConfig, NightshadeRuntime};
 use node_runtime::adapter::ViewRuntimeAdapter;
 use serde_json::json;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::{BufReader, BufWriter, Write};
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
@@ -153,11 +157,12 @@ pub(crate) fn apply_range(
     store: Store,
     only_contracts: bool,
     sequential: bool,
+    compare_results: bool,
 ) {
     let mut csv_file = csv_file.map(|filename| std::fs::File::create(filename).unwrap());
 
     let runtime = NightshadeRuntime::from_config(home_dir, store.clone(), &near_config);
-    apply_chain_range(
+    let divergent = apply_chain_range(
         store,
         &near_config.genesis,
         start_index,
@@ -168,7 +173,22 @@ pub(crate) fn apply_range(
         csv_file.as_mut(),
         only_contracts,
         sequential,
+        compare_results,
     );
+    #[cfg(feature = "differential_vm")]
+    let vm_mismatches = {
+        let mismatches = near_vm_runner::differential::take_vm_mismatches();
+        for mismatch in &mismatches {
+            println!("{}", mismatch);
+        }
+        println!("Found {} function calls on which the VMs disagree", mismatches.len());
+        mismatches.len()
+    };
+    #[cfg(not(feature = "differential_vm"))]
+    let vm_mismatches = 0;
+    if divergent > 0 || vm_mismatches > 0 {
+        std::process::exit(1);
+    }
 }
 
 pub(crate) fn dump_code(
@@ -246,6 +266,183 @@ pub(crate) fn dump_account_storage(
     std::process::exit(1);
 }
 
+pub(crate) fn storage_stats(
+    shard_id: ShardId,
+    height: Option<BlockHeight>,
+    state_root: Option<StateRoot>,
+    account_ids: Option<Vec<AccountId>>,
+    config: StorageStatsConfig,
+    limit: Option<usize>,
+    output: Option<&Path>,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let mode = height.map_or(LoadTrieMode::Latest, LoadTrieMode::Height);
+    let (runtime, state_roots, header) =
+        load_trie_stop_at_height(store, home_dir, &near_config, mode);
+    let state_root = state_root.unwrap_or(state_roots[shard_id as usize]);
+    let trie = runtime.get_trie_for_shard(shard_id, header.prev_hash()).unwrap();
+    let mut accounts =
+        collect_storage_stats(&trie, &state_root, account_ids.as_deref(), &config).unwrap();
+    if let Some(limit) = limit {
+        accounts.truncate(limit);
+    }
+    let stats = ShardStorageStats { shard_id, state_root, accounts };
+    match output {
+        Some(output) => {
+            serde_json::to_writer_pretty(File::create(output).unwrap(), &stats).unwrap();
+            println!("Storage stats of shard {} written to {}", shard_id, output.display());
+        }
+        None => println!("{}", serde_json::to_string_pretty(&stats).unwrap()),
+    }
+}
+
+/// State roots of the given shards, or of the shards tracked by the node if
+/// none are given.
+fn tracked_state_roots(
+    runtime: &NightshadeRuntime,
+    near_config: &NearConfig,
+    header: &BlockHeader,
+    state_roots: &[StateRoot],
+    shard_ids: Option<Vec<ShardId>>,
+) -> Vec<(ShardId, StateRoot)> {
+    let me = near_config.validator_signer.as_ref().map(|signer| signer.validator_id().clone());
+    let shard_ids = shard_ids.unwrap_or_else(|| {
+        (0..state_roots.len() as ShardId)
+            .filter(|&shard_id| {
+                runtime.cares_about_shard(me.as_ref(), header.prev_hash(), shard_id, true)
+            })
+            .collect()
+    });
+    shard_ids.into_iter().map(|shard_id| (shard_id, state_roots[shard_id as usize])).collect()
+}
+
+pub(crate) fn precompile_contracts(
+    shard_ids: Option<Vec<ShardId>>,
+    num_threads: usize,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let (runtime, state_roots, header) = load_trie(store.clone(), home_dir, &near_config);
+    let protocol_version = runtime.get_epoch_protocol_version(header.epoch_id()).unwrap();
+    let config = runtime.get_protocol_config(header.epoch_id()).unwrap().runtime_config.wasm_config;
+    let cache = Arc::new(StoreCompiledContractCache { store });
+    let mut precompiler = Precompiler::new(num_threads, config, protocol_version, cache);
+    let mut seen = HashSet::new();
+    for (shard_id, state_root) in
+        tracked_state_roots(&runtime, &near_config, &header, &state_roots, shard_ids)
+    {
+        let trie = runtime.get_trie_for_shard(shard_id, header.prev_hash()).unwrap();
+        for_each_contract_code(&trie, &state_root, &mut seen, |code| precompiler.add(code))
+            .unwrap();
+        println!("Queued contracts of shard {}", shard_id);
+    }
+    let stats = precompiler.finish();
+    println!(
+        "Compiled {} contracts, {} were already cached, {} failed to compile and {} couldn't be cached",
+        stats.compiled, stats.already_cached, stats.compilation_errors, stats.failed
+    );
+}
+
+pub(crate) fn export_contract_cache(
+    shard_ids: Option<Vec<ShardId>>,
+    output: &Path,
+    key_file: &Path,
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let (runtime, state_roots, header) = load_trie(store.clone(), home_dir, &near_config);
+    let protocol_version = runtime.get_epoch_protocol_version(header.epoch_id()).unwrap();
+    let config = runtime.get_protocol_config(header.epoch_id()).unwrap().runtime_config.wasm_config;
+    let cache = StoreCompiledContractCache { store };
+    let signer = InMemorySigner::from_file(key_file).unwrap();
+    let mut writer = BundleWriter::new(BufWriter::new(File::create(output).unwrap())).unwrap();
+    let (mut exported, mut missing) = (0, 0);
+    let mut seen = HashSet::new();
+    for (shard_id, state_root) in
+        tracked_state_roots(&runtime, &near_config, &header, &state_roots, shard_ids)
+    {
+        let trie = runtime.get_trie_for_shard(shard_id, header.prev_hash()).unwrap();
+        for_each_contract_code(&trie, &state_root, &mut seen, |code| match export_cached_contract(
+            code.hash(),
+            &config,
+            protocol_version,
+            &cache,
+            &signer,
+        )
+        .unwrap()
+        {
+            Some(artifact) => {
+                writer.write(&artifact).unwrap();
+                exported += 1;
+            }
+            None => missing += 1,
+        })
+        .unwrap();
+    }
+    writer.finish().unwrap();
+    println!(
+        "Exported {} compiled contracts to {} signed by {}, {} contracts haven't been compiled",
+        exported,
+        output.display(),
+        signer.public_key(),
+        missing
+    );
+}
+
+pub(crate) fn import_contract_cache(
+    input: &Path,
+    trusted_keys: &[PublicKey],
+    home_dir: &Path,
+    near_config: NearConfig,
+    store: Store,
+) {
+    let (runtime, state_roots, header) = load_trie(store.clone(), home_dir, &near_config);
+    let protocol_version = runtime.get_epoch_protocol_version(header.epoch_id()).unwrap();
+    let config = runtime.get_protocol_config(header.epoch_id()).unwrap().runtime_config.wasm_config;
+    // Only the contracts deployed in the tracked shards are imported.
+    let mut deployed = HashSet::new();
+    for (shard_id, state_root) in
+        tracked_state_roots(&runtime, &near_config, &header, &state_roots, None)
+    {
+        let trie = runtime.get_trie_for_shard(shard_id, header.prev_hash()).unwrap();
+        for_each_contract_code(&trie, &state_root, &mut deployed, |_| {}).unwrap();
+    }
+    let cache = StoreCompiledContractCache { store };
+    let mut reader = BundleReader::new(BufReader::new(File::open(input).unwrap())).unwrap();
+    let (mut imported, mut incompatible, mut untrusted, mut not_deployed) = (0, 0, 0, 0);
+    while let Some(artifact) = reader.read().unwrap() {
+        if !deployed.contains(artifact.code_hash()) {
+            not_deployed += 1;
+            continue;
+        }
+        match import_cached_contract(&artifact, trusted_keys, &config, protocol_version, &cache)
+            .unwrap()
+        {
+            ContractCacheImport::Imported => imported += 1,
+            ContractCacheImport::Incompatible => incompatible += 1,
+            ContractCacheImport::Untrusted => {
+                println!(
+                    "{}",
+                    Red.paint(format!(
+                        "Refused contract {} signed by untrusted key {} or with an invalid signature",
+                        artifact.code_hash(),
+                        artifact.signer()
+                    ))
+                );
+                untrusted += 1;
+            }
+        }
+    }
+    println!(
+        "Imported {} compiled contracts, skipped {} compiled by another VM or with another config, {} not deployed in the tracked shards and refused {} not signed by a trusted key",
+        imported, incompatible, not_deployed, untrusted
+    );
+}
+
 pub(crate) fn print_chain(
     start_height: BlockHeight,
     end_height: BlockHeight,
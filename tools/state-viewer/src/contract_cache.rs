@@ -0,0 +1,322 @@
+//! Precompilation of the contracts deployed in the state and export and
+//! import of compiled contracts, so that a node doesn’t stall compiling big
+//! contracts on their first calls after state sync.
+//!
+//! A bundle of compiled contracts starts with [`BUNDLE_MAGIC`] followed by
+//! borsh-serialized [`ContractCacheArtifact`]s, each prefixed by its length
+//! as a little-endian `u32`.  Every artifact is signed by the node which
+//! exported it and is imported only if the importing node trusts its key.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use near_primitives::config::VMConfig;
+use near_primitives::contract::ContractCode;
+use near_primitives::hash::CryptoHash;
+use near_primitives::trie_key::trie_key_parsers;
+use near_primitives::types::{CompiledContractCache, StateRoot};
+use near_primitives::version::ProtocolVersion;
+use near_store::{StorageError, Trie};
+use near_vm_runner::{precompile_contract, ContractCacheArtifact, ContractPrecompilatonResult};
+use std::collections::HashSet;
+use std::io::{self, Read, Write};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Arc;
+
+const BUNDLE_MAGIC: &[u8; 8] = b"NEARCCB\x02";
+
+/// Calls `f` with every contract code deployed in the state whose hash isn’t
+/// in `seen` yet, so that contracts deployed to many accounts (or in many
+/// shards) are handled once.
+pub(crate) fn for_each_contract_code(
+    trie: &Trie,
+    state_root: &StateRoot,
+    seen: &mut HashSet<CryptoHash>,
+    mut f: impl FnMut(ContractCode),
+) -> Result<(), StorageError> {
+    let prefix = trie_key_parsers::get_raw_prefix_for_contract_codes();
+    let mut iter = trie.iter(state_root)?;
+    iter.seek(&prefix)?;
+    for item in iter {
+        let (key, value) = item?;
+        if !key.starts_with(&prefix) {
+            break;
+        }
+        let code = ContractCode::new(value, None);
+        if seen.insert(*code.hash()) {
+            f(code);
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct PrecompileStats {
+    pub compiled: u64,
+    pub already_cached: u64,
+    /// Contracts which failed to compile.  The errors are cached as well.
+    pub compilation_errors: u64,
+    /// Contracts which couldn’t be cached because of a database error or
+    /// because the VM doesn’t support caching.
+    pub failed: u64,
+}
+
+enum PrecompileOutcome {
+    Compiled,
+    AlreadyCached,
+    CompilationError,
+    Failed,
+}
+
+/// Compiles contracts in a pool of threads and stores them in the compiled
+/// contract cache.
+pub(crate) struct Precompiler {
+    pool: rayon::ThreadPool,
+    num_threads: usize,
+    config: Arc<VMConfig>,
+    protocol_version: ProtocolVersion,
+    cache: Arc<dyn CompiledContractCache>,
+    sender: Sender<PrecompileOutcome>,
+    receiver: Receiver<PrecompileOutcome>,
+    in_flight: usize,
+    stats: PrecompileStats,
+}
+
+impl Precompiler {
+    pub fn new(
+        num_threads: usize,
+        config: VMConfig,
+        protocol_version: ProtocolVersion,
+        cache: Arc<dyn CompiledContractCache>,
+    ) -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(num_threads)
+            .thread_name(|_| "precompile".to_string())
+            .build()
+            .unwrap();
+        let (sender, receiver) = channel();
+        Self {
+            pool,
+            num_threads,
+            config: Arc::new(config),
+            protocol_version,
+            cache,
+            sender,
+            receiver,
+            in_flight: 0,
+            stats: PrecompileStats::default(),
+        }
+    }
+
+    /// Queues the contract for compilation.  Blocks while all the threads are
+    /// busy so that contracts aren’t held in memory waiting for a thread.
+    pub fn add(&mut self, code: ContractCode) {
+        if self.in_flight == self.num_threads {
+            self.wait_for_one();
+        }
+        let config = self.config.clone();
+        let protocol_version = self.protocol_version;
+        let cache = self.cache.clone();
+        let sender = self.sender.clone();
+        self.pool.spawn(move || {
+            let outcome = match precompile_contract(&code, &config, protocol_version, Some(&*cache))
+            {
+                Ok(Ok(ContractPrecompilatonResult::ContractCompiled)) => {
+                    PrecompileOutcome::Compiled
+                }
+                Ok(Ok(ContractPrecompilatonResult::ContractAlreadyInCache)) => {
+                    PrecompileOutcome::AlreadyCached
+                }
+                Ok(Ok(ContractPrecompilatonResult::CacheNotAvailable)) => PrecompileOutcome::Failed,
+                Ok(Err(err)) => {
+                    tracing::debug!(
+                        target: "state_viewer",
+                        code_hash = %code.hash(),
+                        ?err,
+                        "Contract failed to compile"
+                    );
+                    PrecompileOutcome::CompilationError
+                }
+                Err(err) => {
+                    tracing::warn!(
+                        target: "state_viewer",
+                        code_hash = %code.hash(),
+                        ?err,
+                        "Failed to cache compiled contract"
+                    );
+                    PrecompileOutcome::Failed
+                }
+            };
+            // The receiver is dropped only after all the outcomes are received.
+            let _ = sender.send(outcome);
+        });
+        self.in_flight += 1;
+    }
+
+    /// Waits for all the queued contracts to be compiled.
+    pub fn finish(mut self) -> PrecompileStats {
+        while self.in_flight > 0 {
+            self.wait_for_one();
+        }
+        self.stats
+    }
+
+    fn wait_for_one(&mut self) {
+        let outcome = self.receiver.recv().unwrap();
+        self.in_flight -= 1;
+        match outcome {
+            PrecompileOutcome::Compiled => self.stats.compiled += 1,
+            PrecompileOutcome::AlreadyCached => self.stats.already_cached += 1,
+            PrecompileOutcome::CompilationError => self.stats.compilation_errors += 1,
+            PrecompileOutcome::Failed => self.stats.failed += 1,
+        }
+    }
+}
+
+/// Writes compiled contracts into a bundle.
+pub(crate) struct BundleWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> BundleWriter<W> {
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(BUNDLE_MAGIC)?;
+        Ok(Self { writer })
+    }
+
+    pub fn write(&mut self, artifact: &ContractCacheArtifact) -> io::Result<()> {
+        let bytes = artifact.try_to_vec()?;
+        let len = u32::try_from(bytes.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "artifact too large"))?;
+        self.writer.write_all(&len.to_le_bytes())?;
+        self.writer.write_all(&bytes)
+    }
+
+    pub fn finish(mut self) -> io::Result<W> {
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// Reads compiled contracts from a bundle written by [`BundleWriter`].
+pub(crate) struct BundleReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> BundleReader<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0; BUNDLE_MAGIC.len()];
+        reader.read_exact(&mut magic)?;
+        if &magic != BUNDLE_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a compiled contract bundle",
+            ));
+        }
+        Ok(Self { reader })
+    }
+
+    /// Returns the next artifact or `None` at the end of the bundle.
+    pub fn read(&mut self) -> io::Result<Option<ContractCacheArtifact>> {
+        let mut len = [0; 4];
+        // End of the bundle is allowed only between artifacts.
+        if self.reader.read(&mut len[..1])? == 0 {
+            return Ok(None);
+        }
+        self.reader.read_exact(&mut len[1..])?;
+        let mut bytes = vec![0; u32::from_le_bytes(len) as usize];
+        self.reader.read_exact(&mut bytes)?;
+        Ok(Some(ContractCacheArtifact::try_from_slice(&bytes)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{InMemorySigner, KeyType, Signer};
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::trie_key::TrieKey;
+    use near_primitives::types::AccountId;
+    use near_primitives::version::PROTOCOL_VERSION;
+    use near_store::test_utils::{create_tries, test_populate_trie};
+    use near_vm_runner::{
+        export_cached_contract, import_cached_contract, ContractCacheImport,
+        MockCompiledContractCache,
+    };
+
+    use super::*;
+
+    #[test]
+    fn test_precompile_export_import() {
+        let code = near_test_contracts::trivial_contract().to_vec();
+        let other_code = near_test_contracts::rs_contract().to_vec();
+        let code_key = |account_id: &str| {
+            let account_id: AccountId = account_id.parse().unwrap();
+            TrieKey::ContractCode { account_id }.to_vec()
+        };
+        let changes = vec![
+            (code_key("alice.near"), Some(code.clone())),
+            (code_key("bob.near"), Some(code)),
+            (code_key("carol.near"), Some(other_code)),
+            (TrieKey::Account { account_id: "alice.near".parse().unwrap() }.to_vec(), Some(vec![])),
+        ];
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let state_root = test_populate_trie(&tries, &CryptoHash::default(), shard_uid, changes);
+        let trie = tries.get_trie_for_shard(shard_uid);
+
+        let mut codes = vec![];
+        let mut seen = HashSet::new();
+        for_each_contract_code(&trie, &state_root, &mut seen, |code| codes.push(code)).unwrap();
+        assert_eq!(codes.len(), 2);
+        // Contracts seen already are skipped.
+        for_each_contract_code(&trie, &state_root, &mut seen, |_| panic!()).unwrap();
+
+        let config = VMConfig::test();
+        let source = Arc::new(MockCompiledContractCache::default());
+        let mut precompiler = Precompiler::new(2, config.clone(), PROTOCOL_VERSION, source.clone());
+        for code in &codes {
+            precompiler.add(ContractCode::new(code.code().to_vec(), Some(*code.hash())));
+        }
+        let stats = precompiler.finish();
+        assert_eq!(stats.compiled + stats.failed, 2);
+        if stats.failed > 0 {
+            // The default VM of this build doesn’t support caching.
+            return;
+        }
+
+        let signer = InMemorySigner::from_seed("test".parse().unwrap(), KeyType::ED25519, "test");
+        let mut writer = BundleWriter::new(vec![]).unwrap();
+        for code in &codes {
+            let artifact =
+                export_cached_contract(code.hash(), &config, PROTOCOL_VERSION, &*source, &signer)
+                    .unwrap();
+            writer.write(&artifact.unwrap()).unwrap();
+        }
+        let bundle = writer.finish().unwrap();
+
+        let target = MockCompiledContractCache::default();
+        let mut reader = BundleReader::new(bundle.as_slice()).unwrap();
+        while let Some(artifact) = reader.read().unwrap() {
+            let trusted_keys = [signer.public_key()];
+            let import = import_cached_contract(
+                &artifact,
+                &trusted_keys,
+                &config,
+                PROTOCOL_VERSION,
+                &target,
+            );
+            assert_eq!(import.unwrap(), ContractCacheImport::Imported);
+        }
+        assert_eq!(target.len(), 2);
+        let mut precompiler = Precompiler::new(1, config, PROTOCOL_VERSION, Arc::new(target));
+        codes.into_iter().for_each(|code| precompiler.add(code));
+        assert_eq!(
+            precompiler.finish(),
+            PrecompileStats { already_cached: 2, ..PrecompileStats::default() }
+        );
+
+        assert!(BundleReader::new(&b"NEARCCB\x01"[..]).is_err());
+        let truncated = &bundle[..bundle.len() - 1];
+        let mut reader = BundleReader::new(truncated).unwrap();
+        assert!(reader.read().unwrap().is_some());
+        assert!(reader.read().is_err());
+    }
+}
@@ -0,0 +1,541 @@
+//! Export of blocks, chunk headers, transactions, receipts and execution
+//! outcomes into columnar files for analytics.
+//!
+//! Each table is partitioned by block height.  Partitions start at multiples
+//! of the partition size and each is written to
+//! `<output_dir>/<table>/height_from=<first height of the partition>/data.<format>`
+//! replacing the file if it exists.
+
+use crate::parquet;
+use near_chain::{ChainStore, ChainStoreAccess};
+use near_primitives::block::Block;
+use near_primitives::hash::CryptoHash;
+use near_primitives::receipt::Receipt;
+use near_primitives::transaction::{ExecutionOutcomeWithId, SignedTransaction};
+use near_primitives::types::{BlockHeight, ShardId};
+use near_primitives::views::{ActionView, ExecutionStatusView, ReceiptView};
+use std::fs;
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+#[derive(clap::ArgEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ExportFormat {
+    Parquet,
+    Jsonl,
+}
+
+impl ExportFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Parquet => "parquet",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ColumnType {
+    UInt64,
+    String,
+    /// JSON document.  Stored as a string in Parquet files and embedded as is
+    /// in JSONL files.
+    Json,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    UInt64(u64),
+    String(String),
+    Json(serde_json::Value),
+}
+
+impl Value {
+    fn column_type(&self) -> ColumnType {
+        match self {
+            Self::UInt64(_) => ColumnType::UInt64,
+            Self::String(_) => ColumnType::String,
+            Self::Json(_) => ColumnType::Json,
+        }
+    }
+
+    fn string(value: impl ToString) -> Self {
+        Self::String(value.to_string())
+    }
+
+    fn json(value: impl serde::Serialize) -> Self {
+        Self::Json(serde_json::to_value(value).unwrap())
+    }
+}
+
+/// Number of rows written to a Parquet file as a single row group.  Rows are
+/// buffered in memory until their row group is written.
+const ROW_GROUP_ROWS: usize = 10_000;
+
+enum TableOutput {
+    Parquet { writer: parquet::Writer<'static, BufWriter<fs::File>>, rows: Vec<Vec<Value>> },
+    Jsonl(BufWriter<fs::File>),
+}
+
+/// Table of a single partition written into its file as rows are pushed.
+///
+/// The file is written under a temporary name and renamed once complete so
+/// that readers never see a partially written file.
+struct Table {
+    name: &'static str,
+    columns: &'static [(&'static str, ColumnType)],
+    output: TableOutput,
+    num_rows: usize,
+    path: PathBuf,
+    tmp_path: PathBuf,
+}
+
+impl Table {
+    fn new(
+        name: &'static str,
+        columns: &'static [(&'static str, ColumnType)],
+        output_dir: &Path,
+        partition: BlockHeight,
+        format: ExportFormat,
+    ) -> anyhow::Result<Self> {
+        let dir = output_dir.join(name).join(format!("height_from={}", partition));
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("data.{}", format.extension()));
+        let tmp_path = dir.join(format!("data.{}.tmp", format.extension()));
+        let out = BufWriter::new(fs::File::create(&tmp_path)?);
+        let output = match format {
+            ExportFormat::Parquet => TableOutput::Parquet {
+                writer: parquet::Writer::new(out, columns)?,
+                rows: Vec::new(),
+            },
+            ExportFormat::Jsonl => TableOutput::Jsonl(out),
+        };
+        Ok(Self { name, columns, output, num_rows: 0, path, tmp_path })
+    }
+
+    fn push(&mut self, row: Vec<Value>) -> anyhow::Result<()> {
+        debug_assert!(
+            row.iter().map(Value::column_type).eq(self.columns.iter().map(|column| column.1)),
+            "row doesn’t match columns of table {}",
+            self.name
+        );
+        self.num_rows += 1;
+        match &mut self.output {
+            TableOutput::Parquet { writer, rows } => {
+                rows.push(row);
+                if rows.len() >= ROW_GROUP_ROWS {
+                    writer.write_row_group(rows)?;
+                    rows.clear();
+                }
+            }
+            TableOutput::Jsonl(out) => write_jsonl(out, self.columns, row)?,
+        }
+        Ok(())
+    }
+
+    /// Writes the remaining rows and moves the file into place.
+    fn finish(self) -> anyhow::Result<()> {
+        let out = match self.output {
+            TableOutput::Parquet { mut writer, rows } => {
+                writer.write_row_group(&rows)?;
+                writer.finish()?
+            }
+            TableOutput::Jsonl(out) => out,
+        };
+        out.into_inner()?.sync_all()?;
+        fs::rename(&self.tmp_path, &self.path)?;
+        Ok(())
+    }
+}
+
+fn write_jsonl(
+    out: &mut impl Write,
+    columns: &[(&str, ColumnType)],
+    row: Vec<Value>,
+) -> anyhow::Result<()> {
+    let object: serde_json::Map<String, serde_json::Value> = columns
+        .iter()
+        .zip(row)
+        .map(|(&(name, _), value)| {
+            let value = match value {
+                Value::UInt64(value) => value.into(),
+                Value::String(value) => value.into(),
+                Value::Json(value) => value,
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    serde_json::to_writer(&mut *out, &object)?;
+    out.write_all(b"\n")?;
+    Ok(())
+}
+
+const BLOCKS_COLUMNS: &[(&str, ColumnType)] = &[
+    ("height", ColumnType::UInt64),
+    ("hash", ColumnType::String),
+    ("prev_hash", ColumnType::String),
+    ("epoch_id", ColumnType::String),
+    ("timestamp_nanosec", ColumnType::UInt64),
+    ("gas_price", ColumnType::String),
+    ("total_supply", ColumnType::String),
+    ("num_chunks_included", ColumnType::UInt64),
+];
+
+const CHUNKS_COLUMNS: &[(&str, ColumnType)] = &[
+    ("block_height", ColumnType::UInt64),
+    ("block_hash", ColumnType::String),
+    ("shard_id", ColumnType::UInt64),
+    ("chunk_hash", ColumnType::String),
+    ("height_created", ColumnType::UInt64),
+    ("prev_block_hash", ColumnType::String),
+    ("prev_state_root", ColumnType::String),
+    ("outcome_root", ColumnType::String),
+    ("tx_root", ColumnType::String),
+    ("gas_used", ColumnType::UInt64),
+    ("gas_limit", ColumnType::UInt64),
+    ("balance_burnt", ColumnType::String),
+    ("encoded_length", ColumnType::UInt64),
+];
+
+const TRANSACTIONS_COLUMNS: &[(&str, ColumnType)] = &[
+    ("block_height", ColumnType::UInt64),
+    ("block_hash", ColumnType::String),
+    ("shard_id", ColumnType::UInt64),
+    ("chunk_hash", ColumnType::String),
+    ("hash", ColumnType::String),
+    ("signer_id", ColumnType::String),
+    ("public_key", ColumnType::String),
+    ("nonce", ColumnType::UInt64),
+    ("receiver_id", ColumnType::String),
+    ("actions", ColumnType::Json),
+];
+
+const RECEIPTS_COLUMNS: &[(&str, ColumnType)] = &[
+    ("block_height", ColumnType::UInt64),
+    ("block_hash", ColumnType::String),
+    ("shard_id", ColumnType::UInt64),
+    ("chunk_hash", ColumnType::String),
+    ("receipt_id", ColumnType::String),
+    ("predecessor_id", ColumnType::String),
+    ("receiver_id", ColumnType::String),
+    ("receipt", ColumnType::Json),
+];
+
+const OUTCOMES_COLUMNS: &[(&str, ColumnType)] = &[
+    ("block_height", ColumnType::UInt64),
+    ("block_hash", ColumnType::String),
+    ("shard_id", ColumnType::UInt64),
+    ("id", ColumnType::String),
+    ("executor_id", ColumnType::String),
+    ("gas_burnt", ColumnType::UInt64),
+    ("tokens_burnt", ColumnType::String),
+    ("receipt_ids", ColumnType::Json),
+    ("logs", ColumnType::Json),
+    ("status", ColumnType::Json),
+];
+
+/// Tables of a single partition.
+struct Tables {
+    blocks: Table,
+    chunks: Table,
+    transactions: Table,
+    receipts: Table,
+    outcomes: Table,
+    /// Number of chunks whose bodies aren’t in the database, e.g. because the
+    /// node doesn’t track their shards.
+    missing_chunks: usize,
+}
+
+impl Tables {
+    fn new(
+        output_dir: &Path,
+        partition: BlockHeight,
+        format: ExportFormat,
+    ) -> anyhow::Result<Self> {
+        let table = |name, columns| Table::new(name, columns, output_dir, partition, format);
+        Ok(Self {
+            blocks: table("blocks", BLOCKS_COLUMNS)?,
+            chunks: table("chunks", CHUNKS_COLUMNS)?,
+            transactions: table("transactions", TRANSACTIONS_COLUMNS)?,
+            receipts: table("receipts", RECEIPTS_COLUMNS)?,
+            outcomes: table("outcomes", OUTCOMES_COLUMNS)?,
+            missing_chunks: 0,
+        })
+    }
+
+    fn finish(self) -> anyhow::Result<()> {
+        for table in [self.blocks, self.chunks, self.transactions, self.receipts, self.outcomes] {
+            table.finish()?;
+        }
+        Ok(())
+    }
+
+    fn add_block(&mut self, chain_store: &ChainStore, block: &Block) -> anyhow::Result<()> {
+        let header = block.header();
+        let height = header.height();
+        let block_hash = header.hash();
+        self.blocks.push(vec![
+            Value::UInt64(height),
+            Value::string(block_hash),
+            Value::string(header.prev_hash()),
+            Value::string(header.epoch_id().0),
+            Value::UInt64(header.raw_timestamp()),
+            Value::string(header.gas_price()),
+            Value::string(header.total_supply()),
+            Value::UInt64(header.chunk_mask().iter().filter(|included| **included).count() as u64),
+        ])?;
+
+        for chunk_header in block.chunks().iter() {
+            if chunk_header.height_included() != height {
+                continue;
+            }
+            let shard_id = chunk_header.shard_id();
+            let chunk_hash = chunk_header.chunk_hash();
+            self.chunks.push(vec![
+                Value::UInt64(height),
+                Value::string(block_hash),
+                Value::UInt64(shard_id),
+                Value::string(chunk_hash.0),
+                Value::UInt64(chunk_header.height_created()),
+                Value::string(chunk_header.prev_block_hash()),
+                Value::string(chunk_header.prev_state_root()),
+                Value::string(chunk_header.outcome_root()),
+                Value::string(chunk_header.tx_root()),
+                Value::UInt64(chunk_header.gas_used()),
+                Value::UInt64(chunk_header.gas_limit()),
+                Value::string(chunk_header.balance_burnt()),
+                Value::UInt64(chunk_header.encoded_length()),
+            ])?;
+
+            let chunk = match chain_store.get_chunk(&chunk_hash) {
+                Ok(chunk) => chunk,
+                Err(_) => {
+                    self.missing_chunks += 1;
+                    continue;
+                }
+            };
+            for transaction in chunk.transactions() {
+                self.add_transaction(height, block_hash, shard_id, &chunk_hash.0, transaction)?;
+            }
+            for receipt in chunk.receipts() {
+                self.add_receipt(height, block_hash, shard_id, &chunk_hash.0, receipt)?;
+            }
+        }
+
+        for shard_id in 0..block.chunks().len() as ShardId {
+            for id in chain_store.get_outcomes_by_block_hash_and_shard_id(block_hash, shard_id)? {
+                for outcome in chain_store.get_outcomes_by_id(&id)? {
+                    if &outcome.block_hash == block_hash {
+                        self.add_outcome(height, block_hash, shard_id, outcome.outcome_with_id)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn add_transaction(
+        &mut self,
+        height: BlockHeight,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        chunk_hash: &CryptoHash,
+        signed_transaction: &SignedTransaction,
+    ) -> anyhow::Result<()> {
+        let transaction = &signed_transaction.transaction;
+        let actions: Vec<ActionView> =
+            transaction.actions.iter().cloned().map(ActionView::from).collect();
+        self.transactions.push(vec![
+            Value::UInt64(height),
+            Value::string(block_hash),
+            Value::UInt64(shard_id),
+            Value::string(chunk_hash),
+            Value::string(signed_transaction.get_hash()),
+            Value::string(&transaction.signer_id),
+            Value::string(&transaction.public_key),
+            Value::UInt64(transaction.nonce),
+            Value::string(&transaction.receiver_id),
+            Value::json(actions),
+        ])
+    }
+
+    fn add_receipt(
+        &mut self,
+        height: BlockHeight,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        chunk_hash: &CryptoHash,
+        receipt: &Receipt,
+    ) -> anyhow::Result<()> {
+        let receipt = ReceiptView::from(receipt.clone());
+        self.receipts.push(vec![
+            Value::UInt64(height),
+            Value::string(block_hash),
+            Value::UInt64(shard_id),
+            Value::string(chunk_hash),
+            Value::string(receipt.receipt_id),
+            Value::string(&receipt.predecessor_id),
+            Value::string(&receipt.receiver_id),
+            Value::json(receipt.receipt),
+        ])
+    }
+
+    fn add_outcome(
+        &mut self,
+        height: BlockHeight,
+        block_hash: &CryptoHash,
+        shard_id: ShardId,
+        outcome: ExecutionOutcomeWithId,
+    ) -> anyhow::Result<()> {
+        let ExecutionOutcomeWithId { id, outcome } = outcome;
+        self.outcomes.push(vec![
+            Value::UInt64(height),
+            Value::string(block_hash),
+            Value::UInt64(shard_id),
+            Value::string(id),
+            Value::string(&outcome.executor_id),
+            Value::UInt64(outcome.gas_burnt),
+            Value::string(outcome.tokens_burnt),
+            Value::json(outcome.receipt_ids),
+            Value::json(outcome.logs),
+            Value::json(ExecutionStatusView::from(outcome.status)),
+        ])
+    }
+}
+
+/// Exports blocks of the canonical chain at heights from `start_height` to
+/// `end_height` inclusive, along with their chunk headers, transactions,
+/// receipts and execution outcomes.
+pub(crate) fn export(
+    chain_store: &ChainStore,
+    start_height: BlockHeight,
+    end_height: BlockHeight,
+    partition_size: BlockHeight,
+    format: ExportFormat,
+    output_dir: &Path,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(partition_size > 0, "partition size must be positive");
+    anyhow::ensure!(start_height <= end_height, "start height is above end height");
+    let mut partition = start_height - start_height % partition_size;
+    while partition <= end_height {
+        let mut tables = Tables::new(output_dir, partition, format)?;
+        let partition_end = partition.saturating_add(partition_size - 1);
+        let first = partition.max(start_height);
+        let last = partition_end.min(end_height);
+        for height in first..=last {
+            // Heights without a block on the canonical chain are skipped.
+            if let Ok(block_hash) = chain_store.get_block_hash_by_height(height) {
+                let block = chain_store.get_block(&block_hash)?;
+                tables.add_block(chain_store, &block)?;
+            }
+        }
+        let summary = format!(
+            "Exported heights {}..={}: {} blocks, {} chunks, {} transactions, {} receipts, {} outcomes",
+            first,
+            last,
+            tables.blocks.num_rows,
+            tables.chunks.num_rows,
+            tables.transactions.num_rows,
+            tables.receipts.num_rows,
+            tables.outcomes.num_rows,
+        );
+        let missing_chunks = tables.missing_chunks;
+        tables.finish()?;
+        println!("{}", summary);
+        if missing_chunks > 0 {
+            println!(
+                "{} chunks are missing from the database; their transactions and receipts weren’t exported",
+                missing_chunks
+            );
+        }
+        partition = match partition_end.checked_add(1) {
+            Some(next) => next,
+            None => break,
+        };
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_chain::ChainGenesis;
+    use near_client::test_utils::TestEnv;
+
+    fn read_jsonl(path: &Path) -> Vec<serde_json::Value> {
+        fs::read_to_string(path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_export_jsonl() {
+        let mut env = TestEnv::builder(ChainGenesis::test()).build();
+        env.send_money(0);
+        for height in 1..=5 {
+            env.produce_block(0, height);
+        }
+
+        let output_dir = tempfile::tempdir().unwrap();
+        let chain_store = env.clients[0].chain.store();
+        export(chain_store, 1, 5, 4, ExportFormat::Jsonl, output_dir.path()).unwrap();
+
+        let blocks = read_jsonl(&output_dir.path().join("blocks/height_from=0/data.jsonl"));
+        let heights: Vec<_> =
+            blocks.iter().map(|block| block["height"].as_u64().unwrap()).collect();
+        assert_eq!(heights, [1, 2, 3]);
+        let blocks = read_jsonl(&output_dir.path().join("blocks/height_from=4/data.jsonl"));
+        let heights: Vec<_> =
+            blocks.iter().map(|block| block["height"].as_u64().unwrap()).collect();
+        assert_eq!(heights, [4, 5]);
+
+        let transactions: Vec<_> = ["height_from=0", "height_from=4"]
+            .iter()
+            .flat_map(|partition| {
+                read_jsonl(
+                    &output_dir.path().join("transactions").join(partition).join("data.jsonl"),
+                )
+            })
+            .collect();
+        assert_eq!(transactions.len(), 1);
+        assert_eq!(transactions[0]["actions"][0]["Transfer"]["deposit"], "100");
+        for table in ["chunks", "receipts", "outcomes"] {
+            assert!(output_dir.path().join(table).join("height_from=4/data.jsonl").exists());
+        }
+    }
+
+    #[test]
+    fn test_export_parquet() {
+        let mut env = TestEnv::builder(ChainGenesis::test()).build();
+        env.send_money(0);
+        for height in 1..=5 {
+            env.produce_block(0, height);
+        }
+
+        let jsonl_dir = tempfile::tempdir().unwrap();
+        let parquet_dir = tempfile::tempdir().unwrap();
+        let chain_store = env.clients[0].chain.store();
+        export(chain_store, 1, 5, 4, ExportFormat::Jsonl, jsonl_dir.path()).unwrap();
+        export(chain_store, 1, 5, 4, ExportFormat::Parquet, parquet_dir.path()).unwrap();
+
+        // Parquet files hold the same rows as JSONL files.
+        for table in ["blocks", "chunks", "transactions", "receipts", "outcomes"] {
+            for partition in ["height_from=0", "height_from=4"] {
+                let dir = Path::new(table).join(partition);
+                let file = fs::read(parquet_dir.path().join(&dir).join("data.parquet")).unwrap();
+                let (columns, rows) = parquet::tests::read(&file);
+                let columns: Vec<_> = columns
+                    .iter()
+                    .map(|(name, column_type)| (name.as_str(), *column_type))
+                    .collect();
+                let mut jsonl = Vec::new();
+                for row in rows {
+                    write_jsonl(&mut jsonl, &columns, row).unwrap();
+                }
+                let expected = fs::read(jsonl_dir.path().join(&dir).join("data.jsonl")).unwrap();
+                assert_eq!(jsonl, expected, "{}", dir.display());
+            }
+        }
+    }
+}
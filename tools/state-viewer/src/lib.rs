@@ -4,9 +4,13 @@ mod apply_chain_range;
 mod apply_chunk;
 pub mod cli;
 mod commands;
+mod contract_cache;
 mod epoch_info;
+mod export;
+mod parquet;
 mod rocksdb_stats;
 mod state_dump;
+mod storage_stats;
 mod tx_dump;
 
 pub use cli::StateViewerSubCommand;
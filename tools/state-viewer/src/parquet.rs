@@ -0,0 +1,206 @@
+//! Writer of Parquet files built on the `parquet` crate.
+//!
+//! Supports only what the export needs: flat schemas of required columns
+//! holding unsigned integers or strings.  Rows are written in row groups as
+//! they come and files are left uncompressed.
+
+use crate::export::{ColumnType, Value};
+use ::parquet::basic::{ConvertedType, Repetition, Type as PhysicalType};
+use ::parquet::data_type::{ByteArray, ByteArrayType, Int64Type};
+use ::parquet::file::properties::WriterProperties;
+use ::parquet::file::writer::SerializedFileWriter;
+use ::parquet::schema::types::Type;
+use std::io::Write;
+use std::sync::Arc;
+
+impl ColumnType {
+    fn physical_type(self) -> PhysicalType {
+        match self {
+            ColumnType::UInt64 => PhysicalType::INT64,
+            ColumnType::String | ColumnType::Json => PhysicalType::BYTE_ARRAY,
+        }
+    }
+
+    fn converted_type(self) -> ConvertedType {
+        match self {
+            ColumnType::UInt64 => ConvertedType::UINT_64,
+            ColumnType::String => ConvertedType::UTF8,
+            ColumnType::Json => ConvertedType::JSON,
+        }
+    }
+}
+
+/// Returns schema of a file with given columns.
+fn schema(columns: &[(&str, ColumnType)]) -> ::parquet::errors::Result<Type> {
+    let mut fields = columns
+        .iter()
+        .map(|&(name, column_type)| {
+            Type::primitive_type_builder(name, column_type.physical_type())
+                .with_repetition(Repetition::REQUIRED)
+                .with_converted_type(column_type.converted_type())
+                .build()
+                .map(Arc::new)
+        })
+        .collect::<::parquet::errors::Result<Vec<_>>>()?;
+    Type::group_type_builder("schema").with_fields(&mut fields).build()
+}
+
+pub(crate) struct Writer<'a, W: Write> {
+    inner: SerializedFileWriter<W>,
+    columns: &'a [(&'a str, ColumnType)],
+}
+
+impl<'a, W: Write> Writer<'a, W> {
+    pub(crate) fn new(out: W, columns: &'a [(&'a str, ColumnType)]) -> anyhow::Result<Self> {
+        let properties = WriterProperties::builder().build();
+        let inner =
+            SerializedFileWriter::new(out, Arc::new(schema(columns)?), Arc::new(properties))?;
+        Ok(Self { inner, columns })
+    }
+
+    /// Writes the rows as a single row group.
+    ///
+    /// Each row must hold values of the types of the corresponding columns.
+    pub(crate) fn write_row_group(&mut self, rows: &[Vec<Value>]) -> anyhow::Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut row_group = self.inner.next_row_group()?;
+        for (index, &(name, _)) in self.columns.iter().enumerate() {
+            let mut column = row_group
+                .next_column()?
+                .ok_or_else(|| anyhow::anyhow!("missing writer of column {}", name))?;
+            let values = rows.iter().map(|row| &row[index]);
+            match self.columns[index].1 {
+                ColumnType::UInt64 => {
+                    // Unsigned integers are stored as INT64 with the same bit
+                    // pattern.
+                    let values: Vec<i64> = values
+                        .map(|value| match value {
+                            Value::UInt64(value) => *value as i64,
+                            _ => unreachable!("value of column {} isn’t UInt64", name),
+                        })
+                        .collect();
+                    column.typed::<Int64Type>().write_batch(&values, None, None)?;
+                }
+                ColumnType::String | ColumnType::Json => {
+                    let values: Vec<ByteArray> = values
+                        .map(|value| match value {
+                            Value::String(value) => ByteArray::from(value.as_str()),
+                            Value::Json(value) => ByteArray::from(value.to_string().into_bytes()),
+                            Value::UInt64(_) => {
+                                unreachable!("value of column {} isn’t a string", name)
+                            }
+                        })
+                        .collect();
+                    column.typed::<ByteArrayType>().write_batch(&values, None, None)?;
+                }
+            }
+            column.close()?;
+        }
+        row_group.close()?;
+        Ok(())
+    }
+
+    /// Writes the file footer and returns the underlying writer.
+    pub(crate) fn finish(self) -> anyhow::Result<W> {
+        Ok(self.inner.into_inner()?)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use ::parquet::file::reader::{FileReader, SerializedFileReader};
+    use ::parquet::record::Field;
+    use std::io::{Seek, SeekFrom};
+
+    /// Reads columns and rows of the file with the `parquet` crate’s reader.
+    pub(crate) fn read(file: &[u8]) -> (Vec<(String, ColumnType)>, Vec<Vec<Value>>) {
+        let mut tmp = tempfile::tempfile().unwrap();
+        tmp.write_all(file).unwrap();
+        tmp.seek(SeekFrom::Start(0)).unwrap();
+        let reader = SerializedFileReader::new(tmp).unwrap();
+
+        let metadata = reader.metadata().file_metadata();
+        let columns: Vec<(String, ColumnType)> = metadata
+            .schema()
+            .get_fields()
+            .iter()
+            .map(|field| {
+                let column_type = match field.get_basic_info().converted_type() {
+                    ConvertedType::UINT_64 => ColumnType::UInt64,
+                    ConvertedType::UTF8 => ColumnType::String,
+                    ConvertedType::JSON => ColumnType::Json,
+                    other => panic!("unexpected type {} of column {}", other, field.name()),
+                };
+                assert_eq!(field.get_physical_type(), column_type.physical_type());
+                (field.name().to_string(), column_type)
+            })
+            .collect();
+
+        let rows: Vec<Vec<Value>> = reader
+            .get_row_iter(None)
+            .unwrap()
+            .map(|row| {
+                row.get_column_iter()
+                    .zip(&columns)
+                    .map(|((_, field), (_, column_type))| match (field, column_type) {
+                        (Field::ULong(value), ColumnType::UInt64) => Value::UInt64(*value),
+                        (Field::Str(value), ColumnType::String) => Value::String(value.clone()),
+                        (Field::Str(value), ColumnType::Json) => {
+                            Value::Json(serde_json::from_str(value).unwrap())
+                        }
+                        (field, column_type) => {
+                            panic!("unexpected value {:?} in {:?} column", field, column_type)
+                        }
+                    })
+                    .collect()
+            })
+            .collect();
+        assert_eq!(metadata.num_rows(), rows.len() as i64);
+        (columns, rows)
+    }
+
+    fn write(columns: &[(&str, ColumnType)], row_groups: &[Vec<Vec<Value>>]) -> Vec<u8> {
+        let mut writer = Writer::new(Vec::new(), columns).unwrap();
+        for rows in row_groups {
+            writer.write_row_group(rows).unwrap();
+        }
+        writer.finish().unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let columns = [
+            ("height", ColumnType::UInt64),
+            ("hash", ColumnType::String),
+            ("logs", ColumnType::Json),
+        ];
+        let row = |height: u64| {
+            vec![
+                Value::UInt64(height),
+                Value::String(format!("hash{height}")),
+                Value::Json(serde_json::json!({ "height": height, "logs": ["a", "ü"] })),
+            ]
+        };
+        let row_groups: Vec<Vec<Vec<Value>>> = vec![
+            (0..3000).map(row).collect(),
+            vec![],
+            (3000..3003).map(row).chain(std::iter::once(row(u64::MAX))).collect(),
+        ];
+        let (read_columns, rows) = read(&write(&columns, &row_groups));
+        let expected_columns: Vec<_> =
+            columns.iter().map(|&(name, column_type)| (name.to_string(), column_type)).collect();
+        assert_eq!(read_columns, expected_columns);
+        assert_eq!(rows, row_groups.concat());
+    }
+
+    #[test]
+    fn test_round_trip_empty() {
+        let columns = [("height", ColumnType::UInt64)];
+        let (read_columns, rows) = read(&write(&columns, &[]));
+        assert_eq!(read_columns, [("height".to_string(), ColumnType::UInt64)]);
+        assert!(rows.is_empty());
+    }
+}
@@ -0,0 +1,273 @@
+//! Per-account breakdown of the storage taken by a shard’s state.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap, HashMap, HashSet};
+
+use serde::Serialize;
+
+use near_primitives::serialize::to_base64;
+use near_primitives::state_record::StateRecord;
+use near_primitives::types::{AccountId, ShardId, StateRoot, StorageUsage};
+use near_store::{StorageError, Trie};
+
+/// What to include in the statistics of each account.
+pub(crate) struct StorageStatsConfig {
+    /// Number of the largest contract data records to list.
+    pub largest_keys: usize,
+    /// Length of the contract data key prefixes to group records by.
+    pub prefix_len: usize,
+}
+
+/// Contract data record.
+#[derive(Serialize, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct DataRecordStats {
+    /// Size of the key and the value in bytes.
+    pub bytes: u64,
+    /// The key, base64-encoded.
+    pub key: String,
+}
+
+/// Contract data records whose keys share a prefix.
+#[derive(Serialize, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PrefixStats {
+    pub keys: u64,
+    pub bytes: u64,
+}
+
+/// Storage statistics of a shard’s state.
+#[derive(Serialize, Debug)]
+pub(crate) struct ShardStorageStats {
+    pub shard_id: ShardId,
+    pub state_root: StateRoot,
+    /// Statistics of the accounts, largest first.
+    pub accounts: Vec<AccountStorageStats>,
+}
+
+/// Storage taken by an account.  Sizes are of the raw keys and values of the
+/// trie, except for contract data where only the keys and values as seen by
+/// the contract are counted.
+#[derive(Serialize, Debug)]
+pub(crate) struct AccountStorageStats {
+    pub account_id: AccountId,
+    /// Storage usage recorded in the account, i.e. the storage it pays for.
+    pub storage_usage: Option<StorageUsage>,
+    /// Size of all the account’s records.
+    pub total_bytes: u64,
+    pub contract_code_bytes: u64,
+    pub access_keys: u64,
+    pub access_key_bytes: u64,
+    pub data_keys: u64,
+    pub data_bytes: u64,
+    /// Size of received data and of postponed receipts.
+    pub other_bytes: u64,
+    /// The largest contract data records, largest first.
+    pub largest_keys: Vec<DataRecordStats>,
+    /// Number of contract data keys by size, rounded up to a power of two.
+    pub key_size_histogram: BTreeMap<usize, u64>,
+    /// Contract data by base64-encoded key prefix.
+    pub prefix_histogram: BTreeMap<String, PrefixStats>,
+    #[serde(skip)]
+    largest_keys_heap: BinaryHeap<Reverse<DataRecordStats>>,
+}
+
+impl AccountStorageStats {
+    fn new(account_id: AccountId) -> Self {
+        Self {
+            account_id,
+            storage_usage: None,
+            total_bytes: 0,
+            contract_code_bytes: 0,
+            access_keys: 0,
+            access_key_bytes: 0,
+            data_keys: 0,
+            data_bytes: 0,
+            other_bytes: 0,
+            largest_keys: vec![],
+            key_size_histogram: BTreeMap::new(),
+            prefix_histogram: BTreeMap::new(),
+            largest_keys_heap: BinaryHeap::new(),
+        }
+    }
+
+    fn add_data(&mut self, config: &StorageStatsConfig, key: &[u8], value: &[u8]) {
+        let bytes = (key.len() + value.len()) as u64;
+        self.data_keys += 1;
+        self.data_bytes += bytes;
+        *self.key_size_histogram.entry(key.len().next_power_of_two()).or_default() += 1;
+        if config.prefix_len > 0 {
+            let prefix = &key[..key.len().min(config.prefix_len)];
+            let stats = self.prefix_histogram.entry(to_base64(prefix)).or_default();
+            stats.keys += 1;
+            stats.bytes += bytes;
+        }
+        if config.largest_keys > 0 {
+            // The heap keeps the smallest of the largest records on top so
+            // that it can be replaced by a larger one.
+            let smallest = self.largest_keys_heap.peek().map(|Reverse(record)| record.bytes);
+            if self.largest_keys_heap.len() < config.largest_keys {
+                self.largest_keys_heap
+                    .push(Reverse(DataRecordStats { bytes, key: to_base64(key) }));
+            } else if smallest.map_or(false, |smallest| smallest < bytes) {
+                self.largest_keys_heap.pop();
+                self.largest_keys_heap
+                    .push(Reverse(DataRecordStats { bytes, key: to_base64(key) }));
+            }
+        }
+    }
+
+    fn finish(&mut self) {
+        let heap = std::mem::take(&mut self.largest_keys_heap);
+        self.largest_keys =
+            heap.into_sorted_vec().into_iter().map(|Reverse(record)| record).collect();
+    }
+}
+
+/// Iterates over the whole state of a shard and collects storage statistics
+/// of the accounts, or only of the given ones.  Returns the statistics sorted
+/// by the total size, largest first.
+pub(crate) fn collect_storage_stats(
+    trie: &Trie,
+    state_root: &StateRoot,
+    account_ids: Option<&[AccountId]>,
+    config: &StorageStatsConfig,
+) -> Result<Vec<AccountStorageStats>, StorageError> {
+    let account_ids: Option<HashSet<&AccountId>> =
+        account_ids.map(|account_ids| account_ids.iter().collect());
+    let mut stats = HashMap::<AccountId, AccountStorageStats>::new();
+    for item in trie.iter(state_root)? {
+        let (key, value) = item?;
+        let raw_bytes = (key.len() + value.len()) as u64;
+        let record = match StateRecord::from_raw_key_value(key, value) {
+            Some(record) => record,
+            // Records keeping track of postponed receipts are too small to
+            // matter.
+            None => continue,
+        };
+        let account_id = match &record {
+            StateRecord::Account { account_id, .. }
+            | StateRecord::Contract { account_id, .. }
+            | StateRecord::AccessKey { account_id, .. }
+            | StateRecord::Data { account_id, .. }
+            | StateRecord::ReceivedData { account_id, .. } => account_id,
+            StateRecord::PostponedReceipt(receipt) => &receipt.receiver_id,
+            // Delayed receipts belong to the shard rather than to an account.
+            StateRecord::DelayedReceipt(_) => continue,
+        };
+        if account_ids.as_ref().map_or(false, |account_ids| !account_ids.contains(account_id)) {
+            continue;
+        }
+        // Avoids cloning the account id for every record.
+        if !stats.contains_key(account_id) {
+            stats.insert(account_id.clone(), AccountStorageStats::new(account_id.clone()));
+        }
+        let account_stats = stats.get_mut(account_id).unwrap();
+        account_stats.total_bytes += raw_bytes;
+        match &record {
+            StateRecord::Account { account, .. } => {
+                account_stats.storage_usage = Some(account.storage_usage())
+            }
+            StateRecord::Contract { .. } => account_stats.contract_code_bytes += raw_bytes,
+            StateRecord::AccessKey { .. } => {
+                account_stats.access_keys += 1;
+                account_stats.access_key_bytes += raw_bytes;
+            }
+            StateRecord::Data { data_key, value, .. } => {
+                account_stats.add_data(config, data_key, value)
+            }
+            StateRecord::ReceivedData { .. } | StateRecord::PostponedReceipt(_) => {
+                account_stats.other_bytes += raw_bytes
+            }
+            StateRecord::DelayedReceipt(_) => unreachable!(),
+        }
+    }
+    let mut stats: Vec<_> = stats.into_values().collect();
+    stats.iter_mut().for_each(AccountStorageStats::finish);
+    stats.sort_by(|a, b| b.total_bytes.cmp(&a.total_bytes).then(a.account_id.cmp(&b.account_id)));
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use near_crypto::{KeyType, PublicKey};
+    use near_primitives::account::{AccessKey, Account};
+    use near_primitives::borsh::BorshSerialize;
+    use near_primitives::hash::CryptoHash;
+    use near_primitives::shard_layout::ShardUId;
+    use near_primitives::trie_key::TrieKey;
+    use near_store::test_utils::{create_tries, test_populate_trie};
+
+    use super::*;
+
+    #[test]
+    fn test_collect_storage_stats() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let data = |account_id: &AccountId, key: &[u8], value_len: usize| {
+            let trie_key =
+                TrieKey::ContractData { account_id: account_id.clone(), key: key.to_vec() };
+            (trie_key.to_vec(), Some(vec![0; value_len]))
+        };
+        let account = Account::new(0, 0, CryptoHash::default(), 100);
+        let code_key = TrieKey::ContractCode { account_id: alice.clone() }.to_vec();
+        let changes = vec![
+            (
+                TrieKey::Account { account_id: alice.clone() }.to_vec(),
+                Some(account.try_to_vec().unwrap()),
+            ),
+            (code_key.clone(), Some(vec![0; 50])),
+            (
+                TrieKey::AccessKey {
+                    account_id: alice.clone(),
+                    public_key: PublicKey::empty(KeyType::ED25519),
+                }
+                .to_vec(),
+                Some(AccessKey::full_access().try_to_vec().unwrap()),
+            ),
+            data(&alice, b"a1", 10),
+            data(&alice, b"a2", 1),
+            data(&alice, b"bbbb", 100),
+            data(&bob, b"x", 5),
+        ];
+        let tries = create_tries();
+        let shard_uid = ShardUId::single_shard();
+        let state_root = test_populate_trie(&tries, &CryptoHash::default(), shard_uid, changes);
+        let trie = tries.get_trie_for_shard(shard_uid);
+        let config = StorageStatsConfig { largest_keys: 2, prefix_len: 1 };
+
+        let stats = collect_storage_stats(&trie, &state_root, None, &config).unwrap();
+        assert_eq!(
+            stats.iter().map(|stats| &stats.account_id).collect::<Vec<_>>(),
+            vec![&alice, &bob]
+        );
+        let alice_stats = &stats[0];
+        assert_eq!(alice_stats.storage_usage, Some(100));
+        assert_eq!(alice_stats.contract_code_bytes, code_key.len() as u64 + 50);
+        assert_eq!(alice_stats.access_keys, 1);
+        assert_eq!(alice_stats.data_keys, 3);
+        assert_eq!(alice_stats.data_bytes, 12 + 3 + 104);
+        assert_eq!(
+            alice_stats.largest_keys,
+            vec![
+                DataRecordStats { bytes: 104, key: to_base64(b"bbbb") },
+                DataRecordStats { bytes: 12, key: to_base64(b"a1") },
+            ]
+        );
+        assert_eq!(alice_stats.key_size_histogram, BTreeMap::from([(2, 2), (4, 1)]));
+        assert_eq!(
+            alice_stats.prefix_histogram,
+            BTreeMap::from([
+                (to_base64(b"a"), PrefixStats { keys: 2, bytes: 15 }),
+                (to_base64(b"b"), PrefixStats { keys: 1, bytes: 104 }),
+            ])
+        );
+        assert_eq!(stats[1].storage_usage, None);
+        let bob_key = TrieKey::ContractData { account_id: bob.clone(), key: b"x".to_vec() };
+        assert_eq!(stats[1].total_bytes, bob_key.to_vec().len() as u64 + 5);
+
+        let stats =
+            collect_storage_stats(&trie, &state_root, Some(&[bob.clone()]), &config).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].account_id, bob);
+        assert_eq!(stats[0].data_bytes, 6);
+    }
+}